@@ -0,0 +1,254 @@
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::ir::{BinaryOp, Body, Expr, InvokeKind, Stmt, Terminator, Var};
+use crate::structure::Statement;
+
+/// Decompiles the synthetic method of a class with a name and descriptor,
+/// when an accessor can be made of it.
+pub type Decompile<'a> = dyn FnMut(&str, &str, &str) -> Option<(Body, Vec<Statement>)> + 'a;
+
+/// Turns calls of the accessors javac made before Java 11 back into the
+/// field accesses and calls they stand for. Nested classes could only use
+/// each other's private members through static methods of the class that
+/// has them, such as
+///   static int access$000(Outer outer) { return outer.count; }
+/// with the last digits telling a read from an assignment or an increment,
+/// and a private constructor through a synthetic one with an extra
+/// parameter of an empty class, which calls the real one. `decompile` is
+/// given the class, name and descriptor of a method and decompiles it if it
+/// is synthetic. An accessor whose body doesn't reduce to one expression of
+/// its parameters, each used once unless its argument can be evaluated
+/// again, is left called.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-6.html#jls-6.6.1
+pub fn inline_accessors(body: &mut Body, decompile: &mut Decompile) {
+    for id in 0..body.blocks.len() {
+        let mut stmts = std::mem::take(&mut body.blocks[id].stmts);
+        let mut terminator =
+            std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+        for stmt in &mut stmts {
+            if let Stmt::Expr(expr) = stmt {
+                for child in expr.children_mut() {
+                    child.walk_mut(&mut |expr| inline(expr, false, decompile));
+                }
+                inline(expr, true, decompile);
+                // an assignment made a statement again
+                if let Stmt::Expr(Expr::Assign { target, value }) = stmt {
+                    *stmt = Stmt::assign((**target).clone(), (**value).clone());
+                }
+                continue;
+            }
+            for expr in stmt.exprs_mut() {
+                expr.walk_mut(&mut |expr| inline(expr, false, decompile));
+            }
+        }
+        for expr in terminator.exprs_mut() {
+            expr.walk_mut(&mut |expr| inline(expr, false, decompile));
+        }
+        body.blocks[id].stmts = stmts;
+        body.blocks[id].terminator = terminator;
+    }
+}
+
+// Replaces a call of an accessor or an access constructor with what it does.
+// `statement` is whether the value of the call is unused.
+fn inline(expr: &mut Expr, statement: bool, decompile: &mut Decompile) {
+    match expr {
+        Expr::Invoke {
+            kind: InvokeKind::Static,
+            owner,
+            name,
+            descriptor,
+            args,
+            ..
+        } if name.starts_with("access$") => {
+            let Some((accessor, statements)) = decompile(owner, name, descriptor) else {
+                return;
+            };
+            let return_type = MethodDescriptor::parse(descriptor).and_then(|d| d.return_type);
+            let Some(mut access) = access(&statements, statement, return_type.as_ref()) else {
+                return;
+            };
+            if substitute(&mut access, &accessor.parameters, args) {
+                // a private method is called directly rather than with
+                // `invokespecial`
+                access.walk_mut(&mut |expr| {
+                    if let Expr::Invoke { kind, name, .. } = expr {
+                        if *kind == InvokeKind::Special && name != "<init>" {
+                            *kind = InvokeKind::Virtual;
+                        }
+                    }
+                });
+                *expr = access;
+            }
+        }
+        Expr::New {
+            class,
+            descriptor,
+            args,
+        } => {
+            if let Some((target, target_args)) = delegate(class, descriptor, args, decompile) {
+                *descriptor = target;
+                *args = target_args;
+            }
+        }
+        Expr::Invoke {
+            kind: InvokeKind::Special,
+            owner,
+            name,
+            descriptor,
+            args,
+            ..
+        } if name == "<init>" => {
+            if let Some((target, target_args)) = delegate(owner, descriptor, args, decompile) {
+                *descriptor = target;
+                *args = target_args;
+            }
+        }
+        _ => {}
+    }
+}
+
+// The expression an accessor's body reduces to, in terms of its
+// parameters, which is
+//   return outer.count;                  a read or a call
+//   outer.run();                         a call of a void method
+//   outer.count = value; return value;   an assignment
+//   int n = outer.count + 1; outer.count = n; return n;
+//                                        a compound assignment
+//   int n = outer.count; outer.count = n + 1; return n;
+//                                        a postfix increment
+// The last is only an assignment when its value isn't used, or otherwise
+// takes the increment back for an int or a long.
+fn access(
+    statements: &[Statement],
+    statement: bool,
+    return_type: Option<&FieldType>,
+) -> Option<Expr> {
+    let assign = |target: &Expr, value: &Expr| Expr::Assign {
+        target: Box::new(target.clone()),
+        value: Box::new(value.clone()),
+    };
+    match statements {
+        [Statement::Return(Some(value))] => Some(value.clone()),
+        [Statement::Stmt(Stmt::Expr(call))]
+        | [Statement::Stmt(Stmt::Expr(call)), Statement::Return(None)] => Some(call.clone()),
+        [Statement::Stmt(Stmt::Assign { target, value }), Statement::Return(Some(returned))]
+            if value == returned && matches!(value, Expr::Var(_)) =>
+        {
+            Some(assign(target, value))
+        }
+        [Statement::Declare {
+            var,
+            value: Some(value),
+        }, Statement::Stmt(Stmt::Assign {
+            target,
+            value: Expr::Var(stored),
+        }), Statement::Return(Some(Expr::Var(returned)))]
+            if stored == var && returned == var =>
+        {
+            Some(assign(target, value))
+        }
+        [Statement::Declare {
+            var,
+            value: Some(old),
+        }, Statement::Stmt(Stmt::Assign { target, value }), Statement::Return(Some(Expr::Var(returned)))]
+            if returned == var && old == target =>
+        {
+            let mut value = value.clone();
+            value.walk_mut(&mut |expr| {
+                if *expr == Expr::Var(*var) {
+                    *expr = target.clone();
+                }
+            });
+            if statement {
+                return Some(assign(target, &value));
+            }
+            let Expr::Binary { op, right, .. } = &value else {
+                return None;
+            };
+            let op = match op {
+                BinaryOp::Add => BinaryOp::Sub,
+                BinaryOp::Sub => BinaryOp::Add,
+                _ => return None,
+            };
+            let right = (**right).clone();
+            matches!(return_type, Some(FieldType::Int | FieldType::Long))
+                .then(|| Expr::binary(op, assign(target, &value), right))
+        }
+        _ => None,
+    }
+}
+
+// The descriptor and arguments of the constructor an access constructor
+// calls.
+fn delegate(
+    class: &str,
+    descriptor: &str,
+    args: &[Expr],
+    decompile: &mut Decompile,
+) -> Option<(String, Vec<Expr>)> {
+    let (constructor, statements) = decompile(class, "<init>", descriptor)?;
+    let this = constructor.this().map(Expr::Var);
+    let [Statement::Stmt(Stmt::Expr(Expr::Invoke {
+        owner,
+        name,
+        descriptor: target,
+        object,
+        args: target_args,
+        ..
+    }))] = statements.as_slice()
+    else {
+        return None;
+    };
+    if owner != class || name != "<init>" || object.as_deref() != this.as_ref() {
+        return None;
+    }
+    let mut call = Expr::New {
+        class: class.to_string(),
+        descriptor: target.clone(),
+        args: target_args.clone(),
+    };
+    // `this` isn't an argument
+    if !substitute(&mut call, &constructor.parameters[1..], args) {
+        return None;
+    }
+    let Expr::New { args, .. } = call else {
+        return None;
+    };
+    Some((target.clone(), args))
+}
+
+// Puts the arguments of a call in the place of the parameters they are
+// passed in, unless that would evaluate an argument more than once that
+// can't be.
+fn substitute(expr: &mut Expr, parameters: &[Var], args: &[Expr]) -> bool {
+    if parameters.len() != args.len() {
+        return false;
+    }
+    for (parameter, arg) in parameters.iter().zip(args) {
+        let mut uses = 0;
+        expr.walk(&mut |expr| uses += usize::from(*expr == Expr::Var(*parameter)));
+        if uses > 1 && !is_repeatable(arg) {
+            return false;
+        }
+    }
+    let mut unknown = false;
+    expr.walk_mut(&mut |expr| {
+        if let Expr::Var(var) = expr {
+            match parameters.iter().position(|parameter| parameter == var) {
+                Some(index) => *expr = args[index].clone(),
+                None => unknown = true,
+            }
+        }
+    });
+    !unknown
+}
+
+// Whether evaluating an expression again gives the same value, without
+// doing anything else.
+fn is_repeatable(expr: &Expr) -> bool {
+    match expr {
+        Expr::Var(_) | Expr::Const(_) => true,
+        Expr::Field { object, .. } => object.as_deref().is_none_or(is_repeatable),
+        _ => false,
+    }
+}
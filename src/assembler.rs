@@ -0,0 +1,443 @@
+use crate::builder::Code;
+use crate::decompile::DecompileResult;
+use crate::error::DecompileError;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::maxs;
+use crate::types::{ConstantPool, ExceptionTable};
+
+/// A position in the code, created with [`Assembler::new_label`] and placed
+/// with [`Assembler::place`]. Labels can be branched to before they are
+/// placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "L{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Label(Label),
+    Instruction(Instruction<Label>),
+}
+
+#[derive(Debug, Clone)]
+struct Handler {
+    start: Label,
+    end: Label,
+    handler: Label,
+    catch_type: u16,
+}
+
+/// Turns instructions and labels into a `Code` attribute.
+///
+/// Branch offsets are worked out once all labels are placed. A `goto` or
+/// `jsr` that can't reach its target with a 16 bit offset becomes `goto_w` or
+/// `jsr_w`, and a conditional branch that can't is rewritten as the inverted
+/// condition jumping over a `goto_w`. `ldc` becomes `ldc_w` for constant pool
+/// indexes above 255, and the local variable instructions use their short or
+/// `wide` forms as needed.
+///
+/// ```
+/// use decompile_rs::assembler::Assembler;
+/// use decompile_rs::instruction::{Instruction, Opcode, Operand};
+///
+/// let mut asm = Assembler::new();
+/// let top = asm.new_label();
+/// let done = asm.new_label();
+/// asm.place(top);
+/// asm.emit(Instruction::new(Opcode::Iload, Operand::Local(1)));
+/// asm.branch(Opcode::Ifeq, done);
+/// asm.emit(Instruction::new(Opcode::Iinc, Operand::Iinc { index: 1, value: -1 }));
+/// asm.branch(Opcode::Goto, top);
+/// asm.place(done);
+/// asm.op(Opcode::Return);
+/// let code = asm.assemble(1, 2)?;
+/// assert_eq!(code.code.len(), 11);
+/// # Ok::<(), decompile_rs::error::DecompileError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Assembler {
+    items: Vec<Item>,
+    labels: usize,
+    handlers: Vec<Handler>,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    /// Places the label before the next instruction.
+    pub fn place(&mut self, label: Label) {
+        self.items.push(Item::Label(label));
+    }
+
+    pub fn emit(&mut self, instruction: Instruction<Label>) {
+        self.items.push(Item::Instruction(instruction));
+    }
+
+    /// Emits an instruction without operands.
+    pub fn op(&mut self, opcode: Opcode) {
+        self.emit(Instruction::simple(opcode));
+    }
+
+    /// Emits a `goto`, `jsr` or conditional branch.
+    pub fn branch(&mut self, opcode: Opcode, target: Label) {
+        self.emit(Instruction::new(opcode, Operand::Branch(target)));
+    }
+
+    /// Adds an exception table entry covering the code from `start` up to,
+    /// but not including, `end`. A `catch_type` of 0 catches everything, as
+    /// used for `finally`.
+    pub fn try_catch(&mut self, start: Label, end: Label, handler: Label, catch_type: u16) {
+        self.handlers.push(Handler {
+            start,
+            end,
+            handler,
+            catch_type,
+        });
+    }
+
+    /// The instructions emitted so far, with labels left out.
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction<Label>> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Instruction(instruction) => Some(instruction),
+            Item::Label(_) => None,
+        })
+    }
+
+    /// Lays out and encodes the code.
+    pub fn assemble(&self, max_stack: u16, max_locals: u16) -> DecompileResult<Code> {
+        let (code, labels) = self.encode()?;
+
+        let mut exception_table = Vec::with_capacity(self.handlers.len());
+        for handler in &self.handlers {
+            exception_table.push(ExceptionTable {
+                start_pc: resolve(&labels, handler.start)? as u16,
+                end_pc: resolve(&labels, handler.end)? as u16,
+                handler_pc: resolve(&labels, handler.handler)? as u16,
+                catch_type: handler.catch_type,
+            });
+        }
+
+        Ok(Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes: Vec::new(),
+        })
+    }
+
+    /// Lays out and encodes the code, working out `max_stack` and
+    /// `max_locals` from it. `constant_pool` must hold the entries the
+    /// instructions refer to.
+    pub fn assemble_computing_maxs(
+        &self,
+        descriptor: &str,
+        is_static: bool,
+        constant_pool: &ConstantPool,
+    ) -> DecompileResult<Code> {
+        let mut code = self.assemble(0, 0)?;
+        let instructions = instruction::decode(&code.code)?;
+        let maxs = maxs::compute_maxs(
+            &instructions,
+            &code.exception_table,
+            descriptor,
+            is_static,
+            constant_pool,
+        )?;
+        code.max_stack = maxs.max_stack;
+        code.max_locals = maxs.max_locals;
+
+        Ok(code)
+    }
+
+    /// Returns the code and the pc of every label.
+    fn encode(&self) -> DecompileResult<(Vec<u8>, Vec<Option<u32>>)> {
+        // start with every branch in its short form and widen the ones that
+        // don't reach until nothing changes; widening only ever moves code
+        // further apart so this terminates
+        let mut wide = vec![false; self.items.len()];
+        let (labels, pcs) = loop {
+            let (labels, pcs) = self.layout(&wide);
+            let mut changed = false;
+            for (i, item) in self.items.iter().enumerate() {
+                if let Item::Instruction(Instruction {
+                    operand: Operand::Branch(target),
+                    ..
+                }) = item
+                {
+                    let offset = resolve(&labels, *target)? as i64 - pcs[i] as i64;
+                    if !wide[i] && i16::try_from(offset).is_err() {
+                        wide[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break (labels, pcs);
+            }
+        };
+
+        let mut code = Vec::new();
+        for (i, item) in self.items.iter().enumerate() {
+            let Item::Instruction(instruction) = item else {
+                continue;
+            };
+            let pc = pcs[i];
+            for target in instruction.targets() {
+                resolve(&labels, *target)?;
+            }
+            let resolved = instruction
+                .clone()
+                .map_targets(|label| labels[label.0].expect("resolved above"));
+
+            match (&resolved.operand, wide[i]) {
+                (Operand::Branch(target), true) => {
+                    let target = *target;
+                    match resolved.opcode {
+                        Opcode::Goto | Opcode::GotoW => {
+                            code.push(Opcode::GotoW as u8);
+                            code.extend(((target as i64 - pc as i64) as i32).to_be_bytes());
+                        }
+                        Opcode::Jsr | Opcode::JsrW => {
+                            code.push(Opcode::JsrW as u8);
+                            code.extend(((target as i64 - pc as i64) as i32).to_be_bytes());
+                        }
+                        opcode => {
+                            // if<!cond> +8; goto_w target
+                            let inverted = opcode
+                                .inverted()
+                                .expect("only conditional branches are left");
+                            code.push(inverted as u8);
+                            code.extend(8i16.to_be_bytes());
+                            code.push(Opcode::GotoW as u8);
+                            code.extend(((target as i64 - (pc + 3) as i64) as i32).to_be_bytes());
+                        }
+                    }
+                }
+                _ => resolved.encode(pc, &mut code)?,
+            }
+        }
+
+        if code.len() > u16::MAX as usize {
+            return Err(DecompileError::CodeTooLarge(code.len()));
+        }
+
+        Ok((code, labels))
+    }
+
+    /// Works out the pc of every item and label, given which branches use
+    /// their wide form.
+    fn layout(&self, wide: &[bool]) -> (Vec<Option<u32>>, Vec<u32>) {
+        let mut labels = vec![None; self.labels];
+        let mut pcs = Vec::with_capacity(self.items.len());
+        let mut pc = 0u32;
+        for (i, item) in self.items.iter().enumerate() {
+            pcs.push(pc);
+            match item {
+                Item::Label(label) => labels[label.0] = Some(pc),
+                Item::Instruction(instruction) => {
+                    let len = match (instruction.opcode, wide[i]) {
+                        (Opcode::Goto | Opcode::GotoW | Opcode::Jsr | Opcode::JsrW, true) => 5,
+                        (_, true) => 8,
+                        _ => instruction.len_at(pc),
+                    };
+                    pc += len as u32;
+                }
+            }
+        }
+
+        (labels, pcs)
+    }
+}
+
+fn resolve(labels: &[Option<u32>], label: Label) -> DecompileResult<u32> {
+    labels
+        .get(label.0)
+        .copied()
+        .flatten()
+        .ok_or(DecompileError::UnboundLabel(label.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::decode;
+
+    fn nops(asm: &mut Assembler, count: usize) {
+        for _ in 0..count {
+            asm.op(Opcode::Nop);
+        }
+    }
+
+    #[test]
+    fn far_gotos_are_widened() {
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        let start = asm.new_label();
+        asm.place(start);
+        asm.branch(Opcode::Goto, end);
+        nops(&mut asm, 40_000);
+        asm.branch(Opcode::Goto, start);
+        asm.place(end);
+        asm.op(Opcode::Return);
+        let code = asm.assemble(0, 0).unwrap().code;
+        assert_eq!(code[0], Opcode::GotoW as u8);
+        assert_eq!(i32::from_be_bytes(code[1..5].try_into().unwrap()), 40_010);
+        assert_eq!(code[40_005], Opcode::GotoW as u8);
+        let back = i32::from_be_bytes(code[40_006..40_010].try_into().unwrap());
+        assert_eq!(back, -40_005);
+        assert_eq!(code.len(), 40_011);
+    }
+
+    #[test]
+    fn near_gotos_stay_short() {
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        asm.branch(Opcode::Goto, end);
+        nops(&mut asm, 32_764);
+        asm.place(end);
+        asm.op(Opcode::Return);
+        let code = asm.assemble(0, 0).unwrap().code;
+        assert_eq!(code[0], Opcode::Goto as u8);
+        assert_eq!(i16::from_be_bytes([code[1], code[2]]), 32_767);
+    }
+
+    #[test]
+    fn far_conditional_branches_jump_over_goto_w() {
+        let mut asm = Assembler::new();
+        let far = asm.new_label();
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(0)));
+        asm.branch(Opcode::Ifeq, far);
+        nops(&mut asm, 40_000);
+        asm.place(far);
+        asm.op(Opcode::Return);
+        let code = asm.assemble(1, 1).unwrap().code;
+        // iload_0; ifne +8; goto_w far
+        assert_eq!(code[1], Opcode::Ifne as u8);
+        assert_eq!(i16::from_be_bytes([code[2], code[3]]), 8);
+        assert_eq!(code[4], Opcode::GotoW as u8);
+        assert_eq!(i32::from_be_bytes(code[5..9].try_into().unwrap()), 40_005);
+        let instructions = decode(&code).unwrap();
+        assert_eq!(instructions[2].0, 4);
+        assert_eq!(
+            instructions[2].1,
+            Instruction::new(Opcode::Goto, Operand::Branch(40_009))
+        );
+    }
+
+    #[test]
+    fn ldc_is_widened_for_large_indexes() {
+        let mut asm = Assembler::new();
+        asm.emit(Instruction::new(Opcode::Ldc, Operand::Constant(255)));
+        asm.emit(Instruction::new(Opcode::Ldc, Operand::Constant(256)));
+        let code = asm.assemble(2, 0).unwrap().code;
+        assert_eq!(code, [Opcode::Ldc as u8, 255, Opcode::LdcW as u8, 1, 0]);
+    }
+
+    #[test]
+    fn switches_are_padded() {
+        for offset in 0..4 {
+            let mut asm = Assembler::new();
+            let (one, other) = (asm.new_label(), asm.new_label());
+            nops(&mut asm, offset);
+            asm.emit(Instruction::new(
+                Opcode::Tableswitch,
+                Operand::TableSwitch {
+                    default: other,
+                    low: 1,
+                    high: 1,
+                    targets: vec![one],
+                },
+            ));
+            asm.emit(Instruction::new(
+                Opcode::Lookupswitch,
+                Operand::LookupSwitch {
+                    default: other,
+                    pairs: vec![(1, one)],
+                },
+            ));
+            asm.place(one);
+            asm.place(other);
+            asm.op(Opcode::Return);
+            let code = asm.assemble(1, 1).unwrap().code;
+            // the default offsets of both are at the next multiple of four
+            let instructions = decode(&code).unwrap();
+            let end = code.len() as u32 - 1;
+            for (pc, _) in &instructions[offset..offset + 2] {
+                let aligned = (*pc as usize + 4) & !3;
+                assert!(code[*pc as usize + 1..aligned].iter().all(|b| *b == 0));
+                let default = i32::from_be_bytes(code[aligned..aligned + 4].try_into().unwrap());
+                assert_eq!(default as u32, end - pc);
+            }
+            let end = code.len() as u32 - 1;
+            assert_eq!(
+                instructions[offset].1,
+                Instruction::new(
+                    Opcode::Tableswitch,
+                    Operand::TableSwitch {
+                        default: end,
+                        low: 1,
+                        high: 1,
+                        targets: vec![end],
+                    }
+                )
+            );
+            assert_eq!(
+                instructions[offset + 1].1,
+                Instruction::new(
+                    Opcode::Lookupswitch,
+                    Operand::LookupSwitch {
+                        default: end,
+                        pairs: vec![(1, end)],
+                    }
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn exception_table_uses_label_pcs() {
+        let mut asm = Assembler::new();
+        let (start, end, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.place(start);
+        asm.emit(Instruction::new(Opcode::Aload, Operand::Local(0)));
+        asm.op(Opcode::Athrow);
+        asm.place(end);
+        asm.place(handler);
+        asm.op(Opcode::Athrow);
+        asm.try_catch(start, end, handler, 7);
+        let code = asm.assemble(1, 1).unwrap();
+        let entry = &code.exception_table[0];
+        assert_eq!(
+            (
+                entry.start_pc,
+                entry.end_pc,
+                entry.handler_pc,
+                entry.catch_type
+            ),
+            (0, 2, 2, 7)
+        );
+    }
+
+    #[test]
+    fn unplaced_labels_are_errors() {
+        let mut asm = Assembler::new();
+        let nowhere = asm.new_label();
+        asm.branch(Opcode::Goto, nowhere);
+        assert!(matches!(
+            asm.assemble(0, 0),
+            Err(DecompileError::UnboundLabel(0))
+        ));
+    }
+}
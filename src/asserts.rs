@@ -0,0 +1,126 @@
+use crate::conditions;
+use crate::ir::{Body, Const, Expr, InvokeKind, LogicalOp, Stmt, UnaryOp};
+use crate::structure::{self, Statement};
+
+// The synthetic field javac gives a class that asserts, which is set once
+// the class is initialized.
+const ASSERTIONS_DISABLED: &str = "$assertionsDisabled";
+
+/// Turns the checks javac compiles `assert` statements into back into them.
+/// A class that asserts gets a static field telling whether assertions are
+/// disabled for it, which its `<clinit>` sets first,
+///   static final boolean $assertionsDisabled = !Outer.class.desiredAssertionStatus();
+/// and each `assert condition : message;` becomes a check of it:
+///   if (!$assertionsDisabled && !condition) throw new AssertionError(message);
+/// The assignment of the field is removed along with the checks.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.10
+pub fn rebuild_asserts(body: &Body, statements: &mut Vec<Statement>) {
+    statements.retain(|statement| !sets_assertions_disabled(statement));
+    structure::walk_statements_mut(statements, &mut |statement| {
+        if let Some(assert) = assert(body, statement) {
+            *statement = assert;
+        }
+    });
+}
+
+// The `assert` statement a check is, if it is one.
+fn assert(body: &Body, statement: &Statement) -> Option<Statement> {
+    let Statement::If {
+        condition,
+        then,
+        otherwise,
+    } = statement
+    else {
+        return None;
+    };
+    if !otherwise.is_empty() {
+        return None;
+    }
+    let mut conjuncts = Vec::new();
+    flatten_and(condition, &mut conjuncts);
+    let (first, rest) = conjuncts.split_first()?;
+    if !is_enabled(first) {
+        return None;
+    }
+    let mut failed = rest
+        .iter()
+        .map(|&conjunct| conjunct.clone())
+        .reduce(|left, right| Expr::logical(LogicalOp::And, left, right));
+    let mut then = then.as_slice();
+    // a check that wasn't merged into one condition
+    if failed.is_none() {
+        if let [Statement::If {
+            condition,
+            then: inner,
+            otherwise,
+        }] = then
+        {
+            if otherwise.is_empty() {
+                failed = Some(condition.clone());
+                then = inner;
+            }
+        }
+    }
+    let [Statement::Throw(Expr::New { class, args, .. })] = then else {
+        return None;
+    };
+    if class != "java/lang/AssertionError" || args.len() > 1 {
+        return None;
+    }
+    let condition = match failed {
+        Some(failed) => conditions::negate(body, failed),
+        None => Expr::Const(Const::Boolean(false)),
+    };
+    Some(Statement::Assert {
+        condition,
+        message: args.first().cloned(),
+    })
+}
+
+// The operands of a chain of `&&`, from left to right.
+fn flatten_and<'e>(expr: &'e Expr, conjuncts: &mut Vec<&'e Expr>) {
+    match expr {
+        Expr::Logical {
+            op: LogicalOp::And,
+            left,
+            right,
+        } => {
+            flatten_and(left, conjuncts);
+            flatten_and(right, conjuncts);
+        }
+        expr => conjuncts.push(expr),
+    }
+}
+
+// Whether an expression is `!$assertionsDisabled`.
+fn is_enabled(expr: &Expr) -> bool {
+    matches!(expr, Expr::Unary {
+        op: UnaryOp::Not,
+        operand,
+    } if is_assertions_disabled(operand))
+}
+
+fn is_assertions_disabled(expr: &Expr) -> bool {
+    matches!(expr, Expr::Field {
+        name,
+        object: None,
+        ..
+    } if name == ASSERTIONS_DISABLED)
+}
+
+// Whether a statement is the assignment of `$assertionsDisabled` in
+// `<clinit>`.
+fn sets_assertions_disabled(statement: &Statement) -> bool {
+    let Statement::Stmt(Stmt::Assign { target, value }) = statement else {
+        return false;
+    };
+    is_assertions_disabled(target)
+        && matches!(value, Expr::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } if matches!(&**operand, Expr::Invoke {
+            kind: InvokeKind::Virtual,
+            name,
+            ..
+        } if name == "desiredAssertionStatus"))
+}
@@ -0,0 +1,664 @@
+use crate::decompile::CAFE_BABE;
+use crate::types::{
+    Attribute, ClassFile, ConstantPool, ConstantPoolType, CpInfo, ExceptionTable, FieldInfo,
+    MethodInfo, ACC_PUBLIC, ACC_SUPER,
+};
+use crate::writer;
+use std::collections::HashMap;
+
+/// Builds a constant pool, handing out the index of each entry and reusing an
+/// existing entry when an identical one is requested again.
+///
+/// ```
+/// use decompile_rs::builder::ConstantPoolBuilder;
+///
+/// let mut pool = ConstantPoolBuilder::new();
+/// let println = pool.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V");
+/// assert_eq!(println, pool.method_ref("java/io/PrintStream", "println", "(Ljava/lang/String;)V"));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ConstantPoolBuilder {
+    pool: ConstantPool,
+    // keyed on the serialized entry, which sidesteps float equality and
+    // keeps e.g. 0.0 and -0.0 apart
+    indexes: HashMap<Vec<u8>, u16>,
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from the constant pool of an existing class so new entries are
+    /// appended and existing ones reused, leaving every existing index valid.
+    pub fn from_class_file(class_file: &ClassFile) -> Self {
+        let pool = class_file.constant_pool().clone();
+        let mut indexes = HashMap::new();
+        for (i, cp_info) in pool.iter().enumerate() {
+            if cp_info.info.is_some() {
+                indexes.entry(key(cp_info)).or_insert(i as u16 + 1);
+            }
+        }
+
+        Self { pool, indexes }
+    }
+
+    /// Returns the index of the entry, adding it to the pool if needed.
+    pub fn add(&mut self, info: ConstantPoolType) -> u16 {
+        let cp_info = CpInfo::new(info);
+        let key = key(&cp_info);
+        if let Some(index) = self.indexes.get(&key) {
+            return *index;
+        }
+
+        let slots = if cp_info.is_wide() { 2 } else { 1 };
+        // constant_pool_count is a u16 and counts one more than the entries
+        assert!(
+            self.pool.len() + slots < u16::MAX as usize,
+            "constant pool overflow"
+        );
+
+        let index = self.pool.add(cp_info);
+        self.indexes.insert(key, index);
+        index
+    }
+
+    pub fn utf8(&mut self, value: &str) -> u16 {
+        self.add(ConstantPoolType::utf8(value))
+    }
+
+    pub fn integer(&mut self, value: i32) -> u16 {
+        self.add(ConstantPoolType::ConstantInteger { value })
+    }
+
+    pub fn float(&mut self, value: f32) -> u16 {
+        self.add(ConstantPoolType::ConstantFloat { value })
+    }
+
+    pub fn long(&mut self, value: i64) -> u16 {
+        self.add(ConstantPoolType::ConstantLong { value })
+    }
+
+    pub fn double(&mut self, value: f64) -> u16 {
+        self.add(ConstantPoolType::ConstantDouble { value })
+    }
+
+    /// A `CONSTANT_Class`, given an internal name such as `java/lang/Object`
+    /// or an array descriptor such as `[I`.
+    pub fn class(&mut self, name: &str) -> u16 {
+        let name_idx = self.utf8(name);
+        self.add(ConstantPoolType::ConstantClass { name_idx })
+    }
+
+    pub fn string(&mut self, value: &str) -> u16 {
+        let string_idx = self.utf8(value);
+        self.add(ConstantPoolType::ConstantString { string_idx })
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> u16 {
+        let name_idx = self.utf8(name);
+        let desc_idx = self.utf8(descriptor);
+        self.add(ConstantPoolType::ConstantNameAndType { name_idx, desc_idx })
+    }
+
+    pub fn field_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(owner);
+        let name_and_type_idx = self.name_and_type(name, descriptor);
+        self.add(ConstantPoolType::ConstantFieldRef {
+            class_index,
+            name_and_type_idx,
+        })
+    }
+
+    pub fn method_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(owner);
+        let name_and_type_idx = self.name_and_type(name, descriptor);
+        self.add(ConstantPoolType::ConstantMethodRef {
+            class_index,
+            name_and_type_idx,
+        })
+    }
+
+    pub fn interface_method_ref(&mut self, owner: &str, name: &str, descriptor: &str) -> u16 {
+        let class_index = self.class(owner);
+        let name_and_type_idx = self.name_and_type(name, descriptor);
+        self.add(ConstantPoolType::ConstantInterfaceMethodRef {
+            class_index,
+            name_and_type_idx,
+        })
+    }
+
+    /// A `CONSTANT_MethodHandle`; `ref_idx` is the field or method ref the
+    /// handle points at.
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-5.html#jvms-5.4.3.5
+    pub fn method_handle(&mut self, ref_kind: u8, ref_idx: u16) -> u16 {
+        self.add(ConstantPoolType::ConstantMethodHandle { ref_kind, ref_idx })
+    }
+
+    pub fn method_type(&mut self, descriptor: &str) -> u16 {
+        let desc_idx = self.utf8(descriptor);
+        self.add(ConstantPoolType::ConstantMethodType { desc_idx })
+    }
+
+    /// A `CONSTANT_Dynamic`; `bootstrap_method_attr_index` indexes the
+    /// class's `BootstrapMethods` attribute.
+    pub fn dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> u16 {
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstantPoolType::ConstantDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        })
+    }
+
+    pub fn invoke_dynamic(
+        &mut self,
+        bootstrap_method_attr_index: u16,
+        name: &str,
+        descriptor: &str,
+    ) -> u16 {
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        self.add(ConstantPoolType::ConstantInvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        })
+    }
+
+    pub fn module(&mut self, name: &str) -> u16 {
+        let name_idx = self.utf8(name);
+        self.add(ConstantPoolType::ConstantModule { name_idx })
+    }
+
+    pub fn package(&mut self, name: &str) -> u16 {
+        let name_idx = self.utf8(name);
+        self.add(ConstantPoolType::ConstantPackage { name_idx })
+    }
+
+    /// The value written as `constant_pool_count` in the class file.
+    pub fn constant_pool_count(&self) -> u16 {
+        (self.pool.len() + 1) as u16
+    }
+
+    /// The entries added so far.
+    pub fn pool(&self) -> &ConstantPool {
+        &self.pool
+    }
+
+    pub fn build(self) -> ConstantPool {
+        self.pool
+    }
+}
+
+fn key(cp_info: &CpInfo) -> Vec<u8> {
+    let mut key = Vec::new();
+    writer::write_cp_info(&mut key, cp_info).expect("writing to a Vec can't fail");
+    key
+}
+
+/// Describes a field for [`ClassBuilder::field`].
+#[derive(Debug, Clone)]
+pub struct FieldBuilder {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    constant_value: Option<ConstantPoolType>,
+    signature: Option<String>,
+}
+
+impl FieldBuilder {
+    pub fn new(access_flags: u16, name: &str, descriptor: &str) -> Self {
+        Self {
+            access_flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            constant_value: None,
+            signature: None,
+        }
+    }
+
+    /// Adds a `ConstantValue` attribute holding an int, float, long or
+    /// double constant. A `ConstantUtf8` is taken to mean a string constant,
+    /// see [`FieldBuilder::string_value`].
+    pub fn constant_value(mut self, value: ConstantPoolType) -> Self {
+        self.constant_value = Some(value);
+        self
+    }
+
+    pub fn string_value(self, value: &str) -> Self {
+        self.constant_value(ConstantPoolType::utf8(value))
+    }
+
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    fn build(self, pool: &mut ConstantPoolBuilder) -> FieldInfo {
+        let name_index = pool.utf8(&self.name);
+        let descriptor_index = pool.utf8(&self.descriptor);
+
+        let mut attributes = Vec::new();
+        let mut value = None;
+        if let Some(constant) = self.constant_value {
+            let constant_value_index = match constant {
+                // a bare utf8 stands for the string it holds, see string_value()
+                ConstantPoolType::ConstantUtf8 { value: s, .. } => {
+                    value = Some(s.clone());
+                    pool.string(&s)
+                }
+                ConstantPoolType::ConstantInteger { value: v } => {
+                    value = Some(format!("{v}"));
+                    pool.integer(v)
+                }
+                ConstantPoolType::ConstantFloat { value: v } => {
+                    value = Some(format!("{v}"));
+                    pool.float(v)
+                }
+                ConstantPoolType::ConstantLong { value: v } => {
+                    value = Some(format!("{v}"));
+                    pool.long(v)
+                }
+                ConstantPoolType::ConstantDouble { value: v } => {
+                    value = Some(format!("{v}"));
+                    pool.double(v)
+                }
+                other => pool.add(other),
+            };
+            attributes.push(Attribute::ConstantValue {
+                attribute_name_index: pool.utf8("ConstantValue"),
+                attribute_length: 2,
+                constant_value_index,
+            });
+        }
+        if let Some(signature) = self.signature {
+            attributes.push(signature_attribute(pool, &signature));
+        }
+
+        FieldInfo {
+            access_flags: self.access_flags,
+            name_index,
+            descriptor_index,
+            attributes_count: attributes.len() as u16,
+            name: self.name,
+            descriptor: self.descriptor,
+            value,
+            attributes,
+        }
+    }
+}
+
+/// The contents of a `Code` attribute. Constant pool indexes in `code` and
+/// `exception_table` must come from the builder the method is added to.
+#[derive(Debug, Clone, Default)]
+pub struct Code {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionTable>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Code {
+    pub fn new(max_stack: u16, max_locals: u16, code: Vec<u8>) -> Self {
+        Self {
+            max_stack,
+            max_locals,
+            code,
+            ..Self::default()
+        }
+    }
+
+    /// The contents of a `Code` attribute, `None` for any other attribute.
+    pub fn from_attribute(attr: Attribute) -> Option<Self> {
+        match attr {
+            Attribute::Code {
+                max_stack,
+                max_locals,
+                code,
+                exception_table,
+                attributes,
+                ..
+            } => Some(Self {
+                max_stack,
+                max_locals,
+                code,
+                exception_table,
+                attributes,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Builds the `Code` attribute. `attribute_length` and the counts are
+    /// filled in from the contents.
+    pub fn into_attribute(self, pool: &mut ConstantPoolBuilder) -> Attribute {
+        let mut attr = Attribute::Code {
+            attribute_name_index: pool.utf8("Code"),
+            attribute_length: 0,
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code_length: self.code.len() as u32,
+            code: self.code,
+            exception_table_length: self.exception_table.len() as u16,
+            exception_table: self.exception_table,
+            attributes_count: self.attributes.len() as u16,
+            attributes: self.attributes,
+        };
+        set_attribute_length(&mut attr);
+        attr
+    }
+}
+
+/// Describes a method for [`ClassBuilder::method`].
+#[derive(Debug, Clone)]
+pub struct MethodBuilder {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    code: Option<Code>,
+    exceptions: Vec<String>,
+    signature: Option<String>,
+    attributes: Vec<Attribute>,
+}
+
+impl MethodBuilder {
+    pub fn new(access_flags: u16, name: &str, descriptor: &str) -> Self {
+        Self {
+            access_flags,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+            code: None,
+            exceptions: Vec::new(),
+            signature: None,
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn code(mut self, code: Code) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Adds a class to the `Exceptions` attribute (the `throws` clause).
+    pub fn throws(mut self, class: &str) -> Self {
+        self.exceptions.push(class.to_string());
+        self
+    }
+
+    pub fn signature(mut self, signature: &str) -> Self {
+        self.signature = Some(signature.to_string());
+        self
+    }
+
+    /// Adds an already built attribute as is.
+    pub fn attribute(mut self, attr: Attribute) -> Self {
+        self.attributes.push(attr);
+        self
+    }
+
+    fn build(self, pool: &mut ConstantPoolBuilder) -> MethodInfo {
+        let name_index = pool.utf8(&self.name);
+        let descriptor_index = pool.utf8(&self.descriptor);
+
+        let mut attributes = Vec::new();
+        if let Some(code) = self.code {
+            attributes.push(code.into_attribute(pool));
+        }
+        if !self.exceptions.is_empty() {
+            let exception_index_table: Vec<u16> =
+                self.exceptions.iter().map(|e| pool.class(e)).collect();
+            let mut attr = Attribute::Exceptions {
+                attribute_name_index: pool.utf8("Exceptions"),
+                attribute_length: 0,
+                number_of_exceptions: exception_index_table.len() as u16,
+                exception_index_table,
+            };
+            set_attribute_length(&mut attr);
+            attributes.push(attr);
+        }
+        if let Some(signature) = self.signature {
+            attributes.push(signature_attribute(pool, &signature));
+        }
+        attributes.extend(self.attributes);
+
+        MethodInfo {
+            access_flags: self.access_flags,
+            name_index,
+            descriptor_index,
+            attributes_count: attributes.len() as u16,
+            attributes,
+        }
+    }
+}
+
+/// Builds a [`ClassFile`] from names and descriptors, taking care of the
+/// constant pool.
+///
+/// ```
+/// use decompile_rs::builder::{ClassBuilder, FieldBuilder, MethodBuilder};
+/// use decompile_rs::types::{ACC_ABSTRACT, ACC_PRIVATE, ACC_PUBLIC};
+///
+/// let class_file = ClassBuilder::new("com/acme/Foo")
+///     .super_class("java/lang/Object")
+///     .interface("java/lang/Runnable")
+///     .field(FieldBuilder::new(ACC_PRIVATE, "count", "I"))
+///     .method(MethodBuilder::new(ACC_PUBLIC | ACC_ABSTRACT, "run", "()V"))
+///     .build();
+/// assert_eq!(class_file.this_class_name(), Some("com/acme/Foo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClassBuilder {
+    pool: ConstantPoolBuilder,
+    major_version: u16,
+    minor_version: u16,
+    access_flags: u16,
+    this_class: u16,
+    super_class: u16,
+    interfaces: Vec<u16>,
+    fields: Vec<FieldInfo>,
+    methods: Vec<MethodInfo>,
+    attributes: Vec<Attribute>,
+}
+
+impl ClassBuilder {
+    /// Starts a `public` class extending `java.lang.Object` with the class
+    /// file version of Java 17.
+    pub fn new(name: &str) -> Self {
+        Self::with_pool(name, ConstantPoolBuilder::new())
+    }
+
+    /// Like [`ClassBuilder::new`], starting from an existing constant pool.
+    pub fn with_pool(name: &str, mut pool: ConstantPoolBuilder) -> Self {
+        let this_class = pool.class(name);
+        let super_class = pool.class("java/lang/Object");
+        Self {
+            pool,
+            major_version: 61,
+            minor_version: 0,
+            access_flags: ACC_PUBLIC | ACC_SUPER,
+            this_class,
+            super_class,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    /// The constant pool, for creating the entries referenced from code.
+    pub fn pool(&mut self) -> &mut ConstantPoolBuilder {
+        &mut self.pool
+    }
+
+    pub fn version(mut self, major_version: u16, minor_version: u16) -> Self {
+        self.major_version = major_version;
+        self.minor_version = minor_version;
+        self
+    }
+
+    pub fn access_flags(mut self, access_flags: u16) -> Self {
+        self.access_flags = access_flags;
+        self
+    }
+
+    pub fn super_class(mut self, name: &str) -> Self {
+        self.super_class = self.pool.class(name);
+        self
+    }
+
+    pub fn interface(mut self, name: &str) -> Self {
+        let index = self.pool.class(name);
+        self.interfaces.push(index);
+        self
+    }
+
+    pub fn field(mut self, field: FieldBuilder) -> Self {
+        let field_info = field.build(&mut self.pool);
+        self.fields.push(field_info);
+        self
+    }
+
+    pub fn method(mut self, method: MethodBuilder) -> Self {
+        let method_info = method.build(&mut self.pool);
+        self.methods.push(method_info);
+        self
+    }
+
+    pub fn source_file(mut self, name: &str) -> Self {
+        let attr = Attribute::SourceFile {
+            attribute_name_index: self.pool.utf8("SourceFile"),
+            attribute_length: 2,
+            sourcefile_index: self.pool.utf8(name),
+        };
+        self.attributes.push(attr);
+        self
+    }
+
+    /// Adds an already built class attribute as is.
+    pub fn attribute(mut self, attr: Attribute) -> Self {
+        self.attributes.push(attr);
+        self
+    }
+
+    pub fn build(self) -> ClassFile {
+        let mut class_file = ClassFile::new(CAFE_BABE);
+        class_file.major_version = self.major_version;
+        class_file.minor_version = self.minor_version;
+        class_file.set_constant_pool(self.pool.build());
+        class_file.access_flags = self.access_flags;
+        class_file.this_class = self.this_class;
+        class_file.super_class = self.super_class;
+        class_file.interfaces_count = self.interfaces.len() as u16;
+        class_file.interfaces = self.interfaces;
+        class_file.fields_count = self.fields.len() as u16;
+        class_file.fields = self.fields;
+        class_file.methods_count = self.methods.len() as u16;
+        class_file.methods = self.methods;
+        class_file.attributes_count = self.attributes.len() as u16;
+        class_file.attributes = self.attributes;
+        class_file
+    }
+}
+
+fn signature_attribute(pool: &mut ConstantPoolBuilder, signature: &str) -> Attribute {
+    Attribute::Signature {
+        attribute_name_index: pool.utf8("Signature"),
+        attribute_length: 2,
+        signature_index: pool.utf8(signature),
+    }
+}
+
+pub(crate) fn set_attribute_length(attr: &mut Attribute) {
+    let length = writer::attribute_length(attr).expect("writing to a Vec can't fail");
+    match attr {
+        Attribute::Code {
+            attribute_length, ..
+        }
+        | Attribute::Exceptions {
+            attribute_length, ..
+        }
+        | Attribute::StackMapTable {
+            attribute_length, ..
+        } => *attribute_length = length,
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decompile::read_class_file;
+    use std::io::Cursor;
+
+    #[test]
+    fn entries_are_interned() {
+        let mut pool = ConstantPoolBuilder::new();
+        let method = pool.method_ref("java/io/PrintStream", "println", "(I)V");
+        let field = pool.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;");
+        let len = pool.pool().len();
+        assert_eq!(
+            method,
+            pool.method_ref("java/io/PrintStream", "println", "(I)V")
+        );
+        assert_eq!(
+            field,
+            pool.field_ref("java/lang/System", "out", "Ljava/io/PrintStream;")
+        );
+        assert_eq!(
+            pool.class("java/io/PrintStream"),
+            pool.class("java/io/PrintStream")
+        );
+        assert_eq!(pool.pool().len(), len);
+        // the same name in a string and a class shares its utf8 entry
+        let string = pool.string("java/lang/System");
+        assert_eq!(pool.pool().len(), len + 1);
+        assert_ne!(string, pool.class("java/lang/System"));
+        assert_ne!(pool.integer(1), pool.float(f32::from_bits(1)));
+    }
+
+    #[test]
+    fn longs_and_doubles_take_two_slots() {
+        let mut pool = ConstantPoolBuilder::new();
+        assert_eq!(pool.long(1), 1);
+        assert_eq!(pool.integer(1), 3);
+        assert_eq!(pool.double(1.0), 4);
+        assert_eq!(pool.utf8("next"), 6);
+        assert_eq!(pool.long(1), 1);
+        assert_eq!(pool.double(1.0), 4);
+        assert_eq!(pool.constant_pool_count(), 7);
+        assert!(pool.pool().entry(2).is_none());
+        assert!(pool.pool().entry(5).is_none());
+    }
+
+    #[test]
+    fn zeros_of_either_sign_are_kept_apart() {
+        let mut pool = ConstantPoolBuilder::new();
+        let zero = pool.double(0.0);
+        assert_ne!(zero, pool.double(-0.0));
+        assert_eq!(zero, pool.double(0.0));
+        let zero = pool.float(0.0);
+        assert_ne!(zero, pool.float(-0.0));
+        // NaNs with the same bits are one entry, though they aren't equal
+        let nan = pool.double(f64::NAN);
+        assert_eq!(nan, pool.double(f64::NAN));
+    }
+
+    #[test]
+    fn existing_entries_are_reused() {
+        let class_file = ClassBuilder::new("Foo")
+            .field(FieldBuilder::new(ACC_PUBLIC, "count", "J"))
+            .build();
+        let bytes = writer::to_bytes(&class_file).unwrap();
+        let class_file = read_class_file(&mut Cursor::new(bytes)).unwrap();
+        let mut pool = ConstantPoolBuilder::from_class_file(&class_file);
+        let count = pool.constant_pool_count();
+        assert_eq!(pool.class("Foo"), class_file.this_class);
+        assert_eq!(pool.utf8("count"), class_file.fields[0].name_index);
+        assert_eq!(pool.constant_pool_count(), count);
+        assert_eq!(pool.utf8("new"), count);
+    }
+}
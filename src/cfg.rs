@@ -0,0 +1,500 @@
+use crate::decompile::DecompileResult;
+use crate::error::DecompileError;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::types::{Attribute, ExceptionTable, MethodInfo};
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+pub type BlockId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EdgeKind {
+    /// Control runs off the end of the block into the next one, including
+    /// when a conditional branch is not taken.
+    Fallthrough,
+    /// A `goto`.
+    Goto,
+    /// The taken side of a conditional branch.
+    Conditional,
+    /// A `tableswitch` or `lookupswitch` case with this key.
+    SwitchCase(i32),
+    SwitchDefault,
+    /// A `jsr` to a subroutine. The subroutine's `ret` is not followed; the
+    /// `jsr` also gets a fallthrough edge to where it returns to.
+    Jsr,
+    /// An exception thrown in the block caught by a handler. `catch_type` is
+    /// the constant pool index of the caught class, 0 for `finally`.
+    Exceptional {
+        catch_type: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Edge {
+    pub from: BlockId,
+    pub to: BlockId,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+    pub id: BlockId,
+    pub start_pc: u32,
+    /// The pc just past the last instruction.
+    pub end_pc: u32,
+    /// Indexes into [`Cfg::instructions`].
+    pub instructions: Range<usize>,
+    // indexes into Cfg.edges
+    successors: Vec<usize>,
+    predecessors: Vec<usize>,
+}
+
+/// The control flow graph of a method body.
+///
+/// A block starts at the method entry, at every branch, switch and handler
+/// target, after every instruction that ends or redirects control flow, and at
+/// the boundaries of exception table ranges, so each block is either wholly
+/// inside or wholly outside every `try` range. Block 0 is the entry and blocks
+/// are in pc order.
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    instructions: Vec<(u32, Instruction)>,
+    exception_table: Vec<ExceptionTable>,
+    blocks: Vec<BasicBlock>,
+    edges: Vec<Edge>,
+}
+
+impl Cfg {
+    pub fn new(
+        instructions: Vec<(u32, Instruction)>,
+        exception_table: &[ExceptionTable],
+        code_length: u32,
+    ) -> DecompileResult<Cfg> {
+        let index_of = |pc: u32| {
+            instructions
+                .binary_search_by_key(&pc, |(pc, _)| *pc)
+                .map_err(|_| DecompileError::InvalidBranchTarget(pc))
+        };
+
+        let mut leaders = BTreeSet::new();
+        if !instructions.is_empty() {
+            leaders.insert(0);
+        }
+        for (i, (_, instruction)) in instructions.iter().enumerate() {
+            let targets = instruction.targets();
+            for target in &targets {
+                leaders.insert(index_of(**target)?);
+            }
+            if (!targets.is_empty() || instruction.opcode.ends_flow()) && i + 1 < instructions.len()
+            {
+                leaders.insert(i + 1);
+            }
+        }
+        for entry in exception_table {
+            leaders.insert(index_of(entry.handler_pc as u32)?);
+            for pc in [entry.start_pc as u32, entry.end_pc as u32] {
+                if pc != code_length {
+                    leaders.insert(index_of(pc)?);
+                }
+            }
+        }
+
+        let leaders: Vec<usize> = leaders.into_iter().collect();
+        let mut blocks = Vec::with_capacity(leaders.len());
+        for (id, start) in leaders.iter().enumerate() {
+            let end = leaders.get(id + 1).copied().unwrap_or(instructions.len());
+            blocks.push(BasicBlock {
+                id,
+                start_pc: instructions[*start].0,
+                end_pc: instructions.get(end).map_or(code_length, |(pc, _)| *pc),
+                instructions: *start..end,
+                successors: Vec::new(),
+                predecessors: Vec::new(),
+            });
+        }
+
+        let block_at = |pc: u32| {
+            blocks
+                .binary_search_by_key(&pc, |block: &BasicBlock| block.start_pc)
+                .map_err(|_| DecompileError::InvalidBranchTarget(pc))
+        };
+        let mut edges = Vec::new();
+        for block in &blocks {
+            let (_, last) = &instructions[block.instructions.end - 1];
+            let next = block.id + 1;
+            let mut add = |to: BlockId, kind: EdgeKind| {
+                edges.push(Edge {
+                    from: block.id,
+                    to,
+                    kind,
+                })
+            };
+
+            match &last.operand {
+                Operand::Branch(target) => {
+                    let kind = match last.opcode {
+                        Opcode::Goto | Opcode::GotoW => EdgeKind::Goto,
+                        Opcode::Jsr | Opcode::JsrW => EdgeKind::Jsr,
+                        _ => EdgeKind::Conditional,
+                    };
+                    add(block_at(*target)?, kind);
+                }
+                Operand::TableSwitch {
+                    default,
+                    low,
+                    targets,
+                    ..
+                } => {
+                    for (key, target) in (*low..).zip(targets) {
+                        add(block_at(*target)?, EdgeKind::SwitchCase(key));
+                    }
+                    add(block_at(*default)?, EdgeKind::SwitchDefault);
+                }
+                Operand::LookupSwitch { default, pairs } => {
+                    for (key, target) in pairs {
+                        add(block_at(*target)?, EdgeKind::SwitchCase(*key));
+                    }
+                    add(block_at(*default)?, EdgeKind::SwitchDefault);
+                }
+                _ => {}
+            }
+            if !last.opcode.ends_flow() && next < blocks.len() {
+                add(next, EdgeKind::Fallthrough);
+            }
+
+            for entry in exception_table {
+                let range = entry.start_pc as u32..entry.end_pc as u32;
+                if range.contains(&block.start_pc) {
+                    add(
+                        block_at(entry.handler_pc as u32)?,
+                        EdgeKind::Exceptional {
+                            catch_type: entry.catch_type,
+                        },
+                    );
+                }
+            }
+        }
+
+        for (index, edge) in edges.iter().enumerate() {
+            blocks[edge.from].successors.push(index);
+            blocks[edge.to].predecessors.push(index);
+        }
+
+        Ok(Cfg {
+            instructions,
+            exception_table: exception_table.to_vec(),
+            blocks,
+            edges,
+        })
+    }
+
+    /// Decodes `code` and builds its graph.
+    pub fn from_code(code: &[u8], exception_table: &[ExceptionTable]) -> DecompileResult<Cfg> {
+        Cfg::new(
+            instruction::decode(code)?,
+            exception_table,
+            code.len() as u32,
+        )
+    }
+
+    /// The graph of a method's code, `None` for abstract and native methods.
+    pub fn from_method(method: &MethodInfo) -> DecompileResult<Option<Cfg>> {
+        match method.code() {
+            Some(Attribute::Code {
+                code,
+                exception_table,
+                ..
+            }) => Cfg::from_code(code, exception_table).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether some edge leads back to the entry, as when a method starts
+    /// with a loop whose condition is tested first.
+    pub fn has_entry_predecessors(&self) -> bool {
+        self.blocks
+            .first()
+            .is_some_and(|entry| !entry.predecessors.is_empty())
+    }
+
+    /// The same graph with an empty block in front of the entry, so nothing
+    /// branches to block 0. Every other block's id goes up by one.
+    pub fn with_entry_block(self) -> Cfg {
+        let mut edges = vec![Edge {
+            from: 0,
+            to: 1,
+            kind: EdgeKind::Fallthrough,
+        }];
+        edges.extend(self.edges.iter().map(|edge| Edge {
+            from: edge.from + 1,
+            to: edge.to + 1,
+            kind: edge.kind,
+        }));
+        let mut blocks = vec![BasicBlock {
+            id: 0,
+            start_pc: 0,
+            end_pc: 0,
+            instructions: 0..0,
+            successors: vec![0],
+            predecessors: Vec::new(),
+        }];
+        blocks.extend(self.blocks.into_iter().map(|block| BasicBlock {
+            id: block.id + 1,
+            successors: block.successors.iter().map(|index| index + 1).collect(),
+            predecessors: block.predecessors.iter().map(|index| index + 1).collect(),
+            ..block
+        }));
+        blocks[1].predecessors.push(0);
+        Cfg {
+            instructions: self.instructions,
+            exception_table: self.exception_table,
+            blocks,
+            edges,
+        }
+    }
+
+    pub fn blocks(&self) -> &[BasicBlock] {
+        &self.blocks
+    }
+
+    pub fn block(&self, id: BlockId) -> &BasicBlock {
+        &self.blocks[id]
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Every instruction with its pc.
+    pub fn instructions(&self) -> &[(u32, Instruction)] {
+        &self.instructions
+    }
+
+    pub fn exception_table(&self) -> &[ExceptionTable] {
+        &self.exception_table
+    }
+
+    /// The instructions of one block with their pcs.
+    pub fn block_instructions(&self, id: BlockId) -> &[(u32, Instruction)] {
+        &self.instructions[self.blocks[id].instructions.clone()]
+    }
+
+    /// The block starting at `pc`.
+    pub fn block_at(&self, pc: u32) -> Option<BlockId> {
+        self.block_containing(pc)
+            .filter(|id| self.blocks[*id].start_pc == pc)
+    }
+
+    /// The block holding the instruction at `pc`.
+    pub fn block_containing(&self, pc: u32) -> Option<BlockId> {
+        // an empty entry block ends where it starts, so it is never found
+        let id = self.blocks.partition_point(|block| block.end_pc <= pc);
+        self.blocks
+            .get(id)
+            .filter(|block| block.start_pc <= pc)
+            .map(|block| block.id)
+    }
+
+    /// The edges leaving a block, in the order the branches appear followed by
+    /// the exception edges in exception table order.
+    pub fn successors(&self, id: BlockId) -> impl Iterator<Item = &Edge> {
+        self.blocks[id]
+            .successors
+            .iter()
+            .map(|index| &self.edges[*index])
+    }
+
+    pub fn predecessors(&self, id: BlockId) -> impl Iterator<Item = &Edge> {
+        self.blocks[id]
+            .predecessors
+            .iter()
+            .map(|index| &self.edges[*index])
+    }
+
+    /// Which blocks can be reached from the entry, following every kind of
+    /// edge.
+    pub fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut worklist = Vec::new();
+        if !self.blocks.is_empty() {
+            reachable[0] = true;
+            worklist.push(0);
+        }
+        while let Some(id) = worklist.pop() {
+            for edge in self.successors(id) {
+                if !reachable[edge.to] {
+                    reachable[edge.to] = true;
+                    worklist.push(edge.to);
+                }
+            }
+        }
+        reachable
+    }
+
+    /// The blocks that can never run.
+    pub fn unreachable_blocks(&self) -> Vec<BlockId> {
+        self.reachable()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, reachable)| !reachable)
+            .map(|(id, _)| id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    fn cfg(asm: &Assembler) -> Cfg {
+        let code = asm.assemble(0, 0).unwrap();
+        Cfg::from_code(&code.code, &code.exception_table).unwrap()
+    }
+
+    fn successors(cfg: &Cfg, id: BlockId) -> Vec<(BlockId, EdgeKind)> {
+        cfg.successors(id)
+            .map(|edge| (edge.to, edge.kind))
+            .collect()
+    }
+
+    fn local(asm: &mut Assembler, opcode: Opcode, index: u16) {
+        asm.emit(Instruction::new(opcode, Operand::Local(index)));
+    }
+
+    #[test]
+    fn branches_end_blocks() {
+        let mut asm = Assembler::new();
+        let (one, two, done) = (asm.new_label(), asm.new_label(), asm.new_label());
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, done);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.emit(Instruction::new(
+            Opcode::Tableswitch,
+            Operand::TableSwitch {
+                default: done,
+                low: 1,
+                high: 2,
+                targets: vec![one, two],
+            },
+        ));
+        asm.place(one);
+        asm.branch(Opcode::Goto, done);
+        asm.place(two);
+        asm.op(Opcode::Iconst0);
+        asm.op(Opcode::Ireturn);
+        asm.place(done);
+        asm.op(Opcode::Return);
+        let cfg = cfg(&asm);
+
+        let starts: Vec<u32> = cfg.blocks().iter().map(|block| block.start_pc).collect();
+        assert_eq!(starts, [0, 4, 28, 31, 33]);
+        use EdgeKind::*;
+        assert_eq!(successors(&cfg, 0), [(4, Conditional), (1, Fallthrough)]);
+        assert_eq!(
+            successors(&cfg, 1),
+            [(2, SwitchCase(1)), (3, SwitchCase(2)), (4, SwitchDefault)]
+        );
+        assert_eq!(successors(&cfg, 2), [(4, Goto)]);
+        assert_eq!(successors(&cfg, 3), []);
+        let mut into_done: Vec<BlockId> = cfg.predecessors(4).map(|edge| edge.from).collect();
+        into_done.sort();
+        assert_eq!(into_done, [0, 1, 2]);
+        assert!(cfg.unreachable_blocks().is_empty());
+    }
+
+    #[test]
+    fn try_ranges_split_blocks() {
+        let mut asm = Assembler::new();
+        let (start, end, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.op(Opcode::Iconst0);
+        asm.place(start);
+        local(&mut asm, Opcode::Istore, 0);
+        asm.emit(Instruction::new(
+            Opcode::Iinc,
+            Operand::Iinc { index: 0, value: 1 },
+        ));
+        asm.place(end);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.op(Opcode::Ireturn);
+        asm.place(handler);
+        asm.op(Opcode::Pop);
+        asm.op(Opcode::IconstM1);
+        asm.op(Opcode::Ireturn);
+        asm.try_catch(start, end, handler, 7);
+        let cfg = cfg(&asm);
+
+        let ranges: Vec<(u32, u32)> = cfg
+            .blocks()
+            .iter()
+            .map(|block| (block.start_pc, block.end_pc))
+            .collect();
+        assert_eq!(ranges, [(0, 1), (1, 5), (5, 7), (7, 10)]);
+        assert_eq!(successors(&cfg, 0), [(1, EdgeKind::Fallthrough)]);
+        assert_eq!(
+            successors(&cfg, 1),
+            [
+                (2, EdgeKind::Fallthrough),
+                (3, EdgeKind::Exceptional { catch_type: 7 })
+            ]
+        );
+        assert_eq!(cfg.block_at(5), Some(2));
+        assert_eq!(cfg.block_at(2), None);
+        assert_eq!(cfg.block_containing(2), Some(1));
+        assert_eq!(cfg.block_instructions(1).len(), 2);
+    }
+
+    #[test]
+    fn code_after_returns_is_unreachable() {
+        let mut asm = Assembler::new();
+        let (start, end, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.op(Opcode::Return);
+        asm.place(start);
+        asm.op(Opcode::Iconst0);
+        asm.op(Opcode::Pop);
+        asm.place(end);
+        asm.op(Opcode::Return);
+        // only the dead code throws to it
+        asm.place(handler);
+        asm.op(Opcode::Athrow);
+        asm.try_catch(start, end, handler, 0);
+        let cfg = cfg(&asm);
+
+        assert_eq!(cfg.blocks().len(), 4);
+        assert_eq!(cfg.unreachable_blocks(), [1, 2, 3]);
+        assert_eq!(cfg.reachable(), [true, false, false, false]);
+    }
+
+    #[test]
+    fn loops_back_to_the_entry_get_a_block_in_front() {
+        let mut asm = Assembler::new();
+        let top = asm.new_label();
+        asm.place(top);
+        asm.emit(Instruction::new(
+            Opcode::Iinc,
+            Operand::Iinc { index: 0, value: 1 },
+        ));
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifne, top);
+        asm.op(Opcode::Return);
+        let cfg = cfg(&asm);
+        assert!(cfg.has_entry_predecessors());
+        assert_eq!(
+            successors(&cfg, 0),
+            [(0, EdgeKind::Conditional), (1, EdgeKind::Fallthrough)]
+        );
+
+        let cfg = cfg.with_entry_block();
+        assert!(!cfg.has_entry_predecessors());
+        assert_eq!(cfg.blocks().len(), 3);
+        assert_eq!(successors(&cfg, 0), [(1, EdgeKind::Fallthrough)]);
+        assert_eq!(
+            successors(&cfg, 1),
+            [(1, EdgeKind::Conditional), (2, EdgeKind::Fallthrough)]
+        );
+        let mut into_loop: Vec<BlockId> = cfg.predecessors(1).map(|edge| edge.from).collect();
+        into_loop.sort();
+        assert_eq!(into_loop, [0, 1]);
+        assert_eq!(cfg.block_containing(0), Some(1));
+    }
+}
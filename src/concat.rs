@@ -0,0 +1,233 @@
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::infer;
+use crate::ir::{BinaryOp, Body, Const, Expr, InvokeKind, JavaString, Terminator};
+
+const STRING_CONCAT_FACTORY: &str = "java/lang/invoke/StringConcatFactory";
+
+// The argument and constant placeholders of a recipe.
+const ARGUMENT: u16 = 1;
+const CONSTANT: u16 = 2;
+
+/// Turns string concatenation back into `+`, from either way javac compiles
+/// it. Up to Java 8, it appends each operand to a builder:
+///   new StringBuilder().append("sum is ").append(sum).toString()
+/// From Java 9 on, it calls a method `StringConcatFactory` makes for a
+/// recipe, where `\1` stands for the next argument and `\2` for the next
+/// constant after the recipe, which is for strings holding those characters:
+///   invokedynamic makeConcatWithConstants(sum) ["sum is \1"]
+/// `makeConcat` has no recipe and concatenates its arguments. This runs once
+/// conditions are merged, which puts operands that branch back together.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.18.1
+pub fn rebuild_concatenations(body: &mut Body) {
+    for id in 0..body.blocks.len() {
+        let mut stmts = std::mem::take(&mut body.blocks[id].stmts);
+        let mut terminator =
+            std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+        let exprs = stmts
+            .iter_mut()
+            .flat_map(|stmt| stmt.exprs_mut())
+            .chain(terminator.exprs_mut());
+        for expr in exprs {
+            expr.walk_mut(&mut |expr| {
+                if let Some(operands) =
+                    builder_operands(body, expr).or_else(|| recipe_operands(body, expr))
+                {
+                    *expr = concatenation(body, operands);
+                }
+            });
+        }
+        body.blocks[id].stmts = stmts;
+        body.blocks[id].terminator = terminator;
+    }
+}
+
+// The operands of `toString` on a chain of appends to a new builder.
+fn builder_operands(body: &Body, expr: &Expr) -> Option<Vec<Expr>> {
+    let Expr::Invoke {
+        kind: InvokeKind::Virtual,
+        owner,
+        name,
+        descriptor,
+        object: Some(object),
+        args,
+    } = expr
+    else {
+        return None;
+    };
+    if !is_builder(owner)
+        || name != "toString"
+        || descriptor != "()Ljava/lang/String;"
+        || !args.is_empty()
+    {
+        return None;
+    }
+    let operands = appended(body, object)?;
+    (!operands.is_empty()).then_some(operands)
+}
+
+fn appended(body: &Body, expr: &Expr) -> Option<Vec<Expr>> {
+    match expr {
+        Expr::Invoke {
+            kind: InvokeKind::Virtual,
+            owner,
+            name,
+            descriptor,
+            object: Some(object),
+            args,
+        } if is_builder(owner) && name == "append" => {
+            // appending a char[] appends its chars, where `+` would append
+            // the array's `toString`
+            let [arg] = args.as_slice() else {
+                return None;
+            };
+            if descriptor.starts_with("([C") {
+                return None;
+            }
+            let parameter = MethodDescriptor::parse(descriptor)?.parameters.pop()?;
+            let mut operands = appended(body, object)?;
+            operands.push(passed_as(body, arg, &parameter));
+            Some(operands)
+        }
+        Expr::New {
+            class,
+            descriptor,
+            args,
+        } if is_builder(class) => match (descriptor.as_str(), args.as_slice()) {
+            ("()V", []) => Some(Vec::new()),
+            // a builder starting out with a string throws for `null`, so
+            // only one javac made is an operand
+            ("(Ljava/lang/String;)V", [first]) => match first {
+                Expr::Const(Const::String(_)) => Some(vec![first.clone()]),
+                first => value_of(first).map(|value| vec![value.clone()]),
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// What `String.valueOf` is called on, which javac does for objects it
+// concatenates so that their `toString` is called right away.
+fn value_of(expr: &Expr) -> Option<&Expr> {
+    match expr {
+        Expr::Invoke {
+            kind: InvokeKind::Static,
+            owner,
+            name,
+            descriptor,
+            args,
+            ..
+        } if owner == "java/lang/String"
+            && name == "valueOf"
+            && descriptor == "(Ljava/lang/Object;)Ljava/lang/String;" =>
+        {
+            args.first()
+        }
+        _ => None,
+    }
+}
+
+// An operand as the primitive type javac passed it as, which it only casts
+// to with an instruction when the types' values differ on the stack: `"" +
+// (int) c` appends the number a `char` is, and needs the cast kept.
+fn passed_as(body: &Body, operand: &Expr, parameter: &FieldType) -> Expr {
+    let numeric = |field_type: &FieldType| {
+        matches!(
+            field_type,
+            FieldType::Byte
+                | FieldType::Char
+                | FieldType::Short
+                | FieldType::Int
+                | FieldType::Long
+                | FieldType::Float
+                | FieldType::Double
+        )
+    };
+    match infer::type_of(body, operand) {
+        Some(own) if own != *parameter && numeric(&own) && numeric(parameter) => Expr::Cast {
+            field_type: parameter.clone(),
+            operand: Box::new(operand.clone()),
+        },
+        _ => operand.clone(),
+    }
+}
+
+fn is_builder(class: &str) -> bool {
+    class == "java/lang/StringBuilder" || class == "java/lang/StringBuffer"
+}
+
+// The operands of a call of the method `StringConcatFactory` makes.
+fn recipe_operands(body: &Body, expr: &Expr) -> Option<Vec<Expr>> {
+    let Expr::InvokeDynamic {
+        bootstrap,
+        descriptor,
+        args,
+        ..
+    } = expr
+    else {
+        return None;
+    };
+    let parameters = MethodDescriptor::parse(descriptor)?.parameters;
+    if parameters.len() != args.len() {
+        return None;
+    }
+    let passed = args
+        .iter()
+        .zip(&parameters)
+        .map(|(arg, parameter)| passed_as(body, arg, parameter));
+    if bootstrap.is(STRING_CONCAT_FACTORY, "makeConcat") {
+        return Some(passed.collect());
+    }
+    if !bootstrap.is(STRING_CONCAT_FACTORY, "makeConcatWithConstants") {
+        return None;
+    }
+    let Some((Const::String(recipe), constants)) = bootstrap.arguments.split_first() else {
+        return None;
+    };
+    let mut args = passed;
+    let mut constants = constants.iter();
+    let mut operands = Vec::new();
+    let mut literal = Vec::new();
+    for unit in &recipe.0 {
+        let operand = match *unit {
+            ARGUMENT => {
+                let arg = args.next()?;
+                value_of(&arg).cloned().unwrap_or(arg)
+            }
+            CONSTANT => Expr::Const(constants.next()?.clone()),
+            unit => {
+                literal.push(unit);
+                continue;
+            }
+        };
+        if !literal.is_empty() {
+            let string = JavaString(std::mem::take(&mut literal));
+            operands.push(Expr::Const(Const::String(string)));
+        }
+        operands.push(operand);
+    }
+    if !literal.is_empty() {
+        operands.push(Expr::Const(Const::String(JavaString(literal))));
+    }
+    args.next().is_none().then_some(operands)
+}
+
+// The operands joined by `+`, which only concatenates once one of the
+// first two is a string; an empty string goes first otherwise.
+fn concatenation(body: &Body, mut operands: Vec<Expr>) -> Expr {
+    let string = FieldType::Object("java/lang/String".to_string());
+    let concatenates = operands.len() > 1
+        && operands[..2]
+            .iter()
+            .any(|operand| infer::type_of(body, operand).as_ref() == Some(&string));
+    if !concatenates {
+        operands.insert(0, Expr::Const(Const::String(JavaString(Vec::new()))));
+    }
+    let mut operands = operands.into_iter();
+    let first = operands.next().expect("an operand was added");
+    operands.fold(first, |left, right| Expr::Binary {
+        op: BinaryOp::Add,
+        left: Box::new(left),
+        right: Box::new(right),
+    })
+}
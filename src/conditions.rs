@@ -0,0 +1,832 @@
+use crate::cfg::{BlockId, EdgeKind};
+use crate::descriptor::FieldType;
+use crate::infer;
+use crate::ir::{
+    Block, Body, CompareOp, Const, Expr, LogicalOp, Phi, Stmt, Terminator, UnaryOp, Var,
+};
+use std::collections::BTreeSet;
+
+/// Rebuilds the `&&`, `||` and `?:` expressions javac compiles into
+/// branches, in a body out of SSA form.
+///
+/// A block whose only code is a branch, entered only from another branch,
+/// joins that branch's condition. Two blocks that each store one value in
+/// the same variable before meeting again become a conditional expression,
+/// as do two branches on the same targets and two returns of `true` and
+/// `false`. The temporaries javac's stack values were carried across blocks
+/// in are then put back into the expressions that read them.
+///
+/// Blocks are only merged when the same exception handlers cover them, so
+/// the `try` ranges don't change. Merged blocks are left unreachable.
+pub fn merge_conditions(body: &mut Body) {
+    loop {
+        let mut changed = false;
+        changed |= skip_empty_blocks(body);
+        let mut predecessors = predecessors(body);
+        for id in 0..body.blocks.len() {
+            // blocks jump threading left behind still jump to live ones
+            if id != 0 && predecessors[id] == 0 {
+                continue;
+            }
+            if merge_short_circuit(body, id, &predecessors)
+                || merge_conditional(body, id, &predecessors)
+                || merge_boolean_return(body, id, &predecessors)
+                || merge_straight_line(body, id, &predecessors)
+            {
+                changed = true;
+                predecessors = self::predecessors(body);
+            }
+        }
+        changed |= inline_temporaries(body);
+        if !changed {
+            break;
+        }
+    }
+    simplify_booleans(body);
+}
+
+/// Rebuilds the `?:` expressions whose value javac leaves on the stack for
+/// the code after them, in a body still in SSA form.
+///
+/// The two operands meet in a phi of a stack temporary, but an operand that
+/// is just a variable has no store of its own once copies are propagated,
+/// and [`crate::ssa::destruct`] then merges the temporary with that
+/// variable, leaving an `if` that changes it rather than an expression. So
+/// the branch is merged before, from the phi: into a store of the phi's
+/// target when nothing else meets there, or of a new temporary the phi
+/// takes instead of the two operands when a conditional it is an operand of
+/// does.
+pub fn merge_stack_conditionals(body: &mut Body) {
+    loop {
+        let mut changed = false;
+        let mut predecessors = predecessors(body);
+        for id in 0..body.blocks.len() {
+            if id != 0 && predecessors[id] == 0 {
+                continue;
+            }
+            if merge_phi_short_circuit(body, id, &predecessors)
+                || merge_phi_conditional(body, id, &predecessors)
+            {
+                changed = true;
+                predecessors = self::predecessors(body);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+// `merge_short_circuit`, unless a phi takes a value from the block it would
+// leave unreachable.
+fn merge_phi_short_circuit(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    let Terminator::If {
+        then, otherwise, ..
+    } = body.blocks[id].terminator
+    else {
+        return false;
+    };
+    let feeds_phi = |block: BlockId| {
+        body.blocks.iter().any(|other| {
+            other
+                .phis
+                .iter()
+                .any(|phi| phi.sources.iter().any(|(from, _)| *from == block))
+        })
+    };
+    if [then, otherwise]
+        .into_iter()
+        .any(|inner| is_bare_branch(body, inner, id, predecessors) && feeds_phi(inner))
+    {
+        return false;
+    }
+    merge_short_circuit(body, id, predecessors)
+}
+
+fn merge_phi_conditional(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    let Terminator::If {
+        then, otherwise, ..
+    } = body.blocks[id].terminator
+    else {
+        return false;
+    };
+    // the join both operands go to, and the variable each leaves there
+    let operand = |block: BlockId| -> Option<(BlockId, Option<Var>)> {
+        if !only_entered_from(block, id, predecessors)
+            || !same_handlers(body, id, block)
+            || !body.blocks[block].phis.is_empty()
+        {
+            return None;
+        }
+        let Terminator::Goto(join) = body.blocks[block].terminator else {
+            return None;
+        };
+        match body.blocks[block].stmts.as_slice() {
+            [] => Some((join, None)),
+            [Stmt::Assign {
+                target: Expr::Var(var),
+                ..
+            }] => Some((join, Some(*var))),
+            _ => None,
+        }
+    };
+    if then == otherwise {
+        return false;
+    }
+    let (Some((join, then_store)), Some((other_join, otherwise_store))) =
+        (operand(then), operand(otherwise))
+    else {
+        return false;
+    };
+    if join != other_join || join == id {
+        return false;
+    }
+    let source = |phi: &Phi, block: BlockId| {
+        phi.sources
+            .iter()
+            .find(|(from, _)| *from == block)
+            .map(|(_, var)| *var)
+    };
+    // the one phi the operands differ in, the others passing on a variable
+    // set before the branch
+    let mut found = None;
+    for (index, phi) in body.blocks[join].phis.iter().enumerate() {
+        let (Some(x), Some(y)) = (source(phi, then), source(phi, otherwise)) else {
+            return false;
+        };
+        if x == y && then_store != Some(x) {
+            continue;
+        }
+        if found.is_some() || body.variable(phi.target).slot.is_some() {
+            return false;
+        }
+        found = Some((index, x, y));
+    }
+    let Some((index, x, y)) = found else {
+        return false;
+    };
+    if then_store.is_some_and(|store| store != x) || otherwise_store.is_some_and(|store| store != y)
+    {
+        return false;
+    }
+
+    let value = |body: &mut Body, block: BlockId, var: Var| {
+        let (mut stmts, _) = take_block(body, block);
+        match stmts.pop() {
+            Some(Stmt::Assign { value, .. }) => value,
+            _ => Expr::Var(var),
+        }
+    };
+    let x = value(body, then, x);
+    let y = value(body, otherwise, y);
+    let Terminator::If { condition, .. } =
+        std::mem::replace(&mut body.blocks[id].terminator, Terminator::Goto(join))
+    else {
+        unreachable!("the block ends in a branch");
+    };
+    let condition = negate(body, condition);
+    let target = if predecessors[join] == 2 {
+        body.blocks[join].phis.remove(index).target
+    } else {
+        let temporary = body.variable(body.blocks[join].phis[index].target).clone();
+        let target = body.new_variable(temporary);
+        body.blocks[join].phis[index]
+            .sources
+            .retain(|(from, _)| *from != then && *from != otherwise);
+        body.blocks[join].phis[index].sources.push((id, target));
+        target
+    };
+    for phi in &mut body.blocks[join].phis {
+        for (from, _) in &mut phi.sources {
+            if *from == then {
+                *from = id;
+            }
+        }
+        phi.sources.retain(|(from, _)| *from != otherwise);
+    }
+    body.blocks[id].stmts.push(Stmt::assign(
+        Expr::Var(target),
+        Expr::conditional(condition, y, x),
+    ));
+    true
+}
+
+/// The condition that holds exactly when `condition` doesn't. Comparisons of
+/// floating point values stay negated as a whole, since `!(a < b)` and
+/// `a >= b` differ for NaN.
+pub fn negate(body: &Body, condition: Expr) -> Expr {
+    let floating = |expr: &Expr| {
+        matches!(
+            infer::type_of(body, expr),
+            Some(FieldType::Float | FieldType::Double)
+        )
+    };
+    match condition {
+        Expr::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => *operand,
+        Expr::Const(Const::Boolean(value)) => Expr::Const(Const::Boolean(!value)),
+        Expr::Compare { op, left, right } if !floating(&left) && !floating(&right) => {
+            simplify_condition(body, Expr::compare(op.negate(), *left, *right))
+        }
+        // https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.23
+        Expr::Logical { op, left, right } => {
+            let op = match op {
+                LogicalOp::And => LogicalOp::Or,
+                LogicalOp::Or => LogicalOp::And,
+            };
+            Expr::logical(op, negate(body, *left), negate(body, *right))
+        }
+        condition => Expr::logical_not(condition),
+    }
+}
+
+/// Comparisons of booleans with constants become the boolean or its
+/// negation.
+pub fn simplify_condition(body: &Body, condition: Expr) -> Expr {
+    match condition {
+        Expr::Compare { op, left, right } => match (op, *right) {
+            (CompareOp::Eq, Expr::Const(Const::Boolean(true)))
+            | (CompareOp::Ne, Expr::Const(Const::Boolean(false))) => *left,
+            (CompareOp::Eq, Expr::Const(Const::Boolean(false)))
+            | (CompareOp::Ne, Expr::Const(Const::Boolean(true))) => negate(body, *left),
+            (op, right) => Expr::compare(op, *left, right),
+        },
+        condition => condition,
+    }
+}
+
+// How many times each block is jumped to from a reachable block, reached
+// by jumps or through the handlers of the blocks jumps reach.
+fn predecessors(body: &Body) -> Vec<usize> {
+    let mut counts = vec![0; body.blocks.len()];
+    let mut reached = vec![false; body.blocks.len()];
+    let mut worklist = vec![0];
+    reached[0] = true;
+    while let Some(id) = worklist.pop() {
+        for target in body.blocks[id].terminator.targets() {
+            counts[target] += 1;
+            if !reached[target] {
+                reached[target] = true;
+                worklist.push(target);
+            }
+        }
+        for edge in body.cfg.successors(id) {
+            if matches!(edge.kind, EdgeKind::Exceptional { .. }) && !reached[edge.to] {
+                reached[edge.to] = true;
+                worklist.push(edge.to);
+            }
+        }
+    }
+    counts
+}
+
+// Whether code can move between two blocks without leaving or entering a
+// `try`.
+fn same_handlers(body: &Body, a: BlockId, b: BlockId) -> bool {
+    let handlers = |id: BlockId| -> BTreeSet<(BlockId, u16)> {
+        body.cfg
+            .successors(id)
+            .filter_map(|edge| match edge.kind {
+                EdgeKind::Exceptional { catch_type } => Some((edge.to, catch_type)),
+                _ => None,
+            })
+            .collect()
+    };
+    handlers(a) == handlers(b)
+}
+
+// Whether control only gets to a block by a single jump from `from`.
+fn only_entered_from(id: BlockId, from: BlockId, predecessors: &[usize]) -> bool {
+    id != from && id != 0 && predecessors[id] == 1
+}
+
+// A block that only branches, that control can only reach from `from`.
+fn is_bare_branch(body: &Body, id: BlockId, from: BlockId, predecessors: &[usize]) -> bool {
+    only_entered_from(id, from, predecessors)
+        && body.blocks[id].stmts.is_empty()
+        && same_handlers(body, from, id)
+}
+
+// Jumps to a block that only jumps on go straight to where it leads.
+fn skip_empty_blocks(body: &mut Body) -> bool {
+    let forward = |body: &Body, mut target: BlockId| {
+        for _ in 0..body.blocks.len() {
+            match body.blocks[target] {
+                Block {
+                    ref stmts,
+                    terminator: Terminator::Goto(next),
+                    ..
+                } if stmts.is_empty() && next != target => target = next,
+                _ => break,
+            }
+        }
+        target
+    };
+    let mut changed = false;
+    for id in 0..body.blocks.len() {
+        let mut terminator = body.blocks[id].terminator.clone();
+        match &mut terminator {
+            Terminator::Goto(target) => *target = forward(body, *target),
+            Terminator::If {
+                then, otherwise, ..
+            } => {
+                *then = forward(body, *then);
+                *otherwise = forward(body, *otherwise);
+            }
+            Terminator::Switch { cases, default, .. } => {
+                for (_, target) in cases {
+                    *target = forward(body, *target);
+                }
+                *default = forward(body, *default);
+            }
+            Terminator::Return(_) | Terminator::Throw(_) | Terminator::Unreachable => {}
+        }
+        // comparing targets rather than terminators, which differ from
+        // themselves when they hold a NaN constant
+        if terminator.targets() != body.blocks[id].terminator.targets() {
+            body.blocks[id].terminator = terminator;
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn take_block(body: &mut Body, id: BlockId) -> (Vec<Stmt>, Terminator) {
+    let block = &mut body.blocks[id];
+    (
+        std::mem::take(&mut block.stmts),
+        std::mem::replace(&mut block.terminator, Terminator::Unreachable),
+    )
+}
+
+// `if (a) X else B` where B is `if (b) ...` and goes to X on one side
+// becomes a single branch on `a || b`, and likewise for `&&`.
+fn merge_short_circuit(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    let Terminator::If {
+        then, otherwise, ..
+    } = body.blocks[id].terminator
+    else {
+        return false;
+    };
+    for (inner, outer, is_or) in [(otherwise, then, true), (then, otherwise, false)] {
+        if !is_bare_branch(body, inner, id, predecessors) {
+            continue;
+        }
+        let Terminator::If {
+            then: inner_then,
+            otherwise: inner_otherwise,
+            ..
+        } = body.blocks[inner].terminator
+        else {
+            continue;
+        };
+        // which way the inner branch has to go to meet the outer one
+        let inner_negated = if outer == inner_then {
+            !is_or
+        } else if outer == inner_otherwise {
+            is_or
+        } else {
+            continue;
+        };
+        let (_, inner_terminator) = take_block(body, inner);
+        let Terminator::If {
+            condition: inner_condition,
+            ..
+        } = inner_terminator
+        else {
+            unreachable!("the inner block ends in a branch");
+        };
+        let inner_condition = if inner_negated {
+            negate(body, inner_condition)
+        } else {
+            inner_condition
+        };
+        // the inner branch's other target, where `a || b` goes when false
+        // and `a && b` when true
+        let other = if outer == inner_then {
+            inner_otherwise
+        } else {
+            inner_then
+        };
+        let terminator =
+            std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+        let Terminator::If { condition, .. } = terminator else {
+            unreachable!("the outer block ends in a branch");
+        };
+        body.blocks[id].terminator = if is_or {
+            Terminator::If {
+                condition: Expr::logical(LogicalOp::Or, condition, inner_condition),
+                then: outer,
+                otherwise: other,
+            }
+        } else {
+            Terminator::If {
+                condition: Expr::logical(LogicalOp::And, condition, inner_condition),
+                then: other,
+                otherwise: outer,
+            }
+        };
+        return true;
+    }
+    false
+}
+
+// `if (c) { v = x; } else { v = y; }` becomes `v = c ? x : y`, and a branch
+// to two blocks that branch to the same places becomes a branch on a
+// conditional. javac jumps to the second operand when the condition is
+// false, so the condition is negated back.
+fn merge_conditional(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    let Terminator::If {
+        then, otherwise, ..
+    } = body.blocks[id].terminator
+    else {
+        return false;
+    };
+    let movable = |block: BlockId| {
+        only_entered_from(block, id, predecessors) && same_handlers(body, id, block)
+    };
+    if then == otherwise || !movable(then) || !movable(otherwise) {
+        return false;
+    }
+
+    let (taken, not_taken) = (&body.blocks[then], &body.blocks[otherwise]);
+    match (
+        taken.stmts.as_slice(),
+        &taken.terminator,
+        not_taken.stmts.as_slice(),
+        &not_taken.terminator,
+    ) {
+        (
+            [Stmt::Assign {
+                target: Expr::Var(var),
+                ..
+            }],
+            Terminator::Goto(join),
+            [Stmt::Assign {
+                target: Expr::Var(other_var),
+                ..
+            }],
+            Terminator::Goto(other_join),
+        ) if var == other_var && join == other_join => {
+            let (var, join) = (*var, *join);
+            let (mut taken, _) = take_block(body, then);
+            let (mut not_taken, _) = take_block(body, otherwise);
+            let (Some(Stmt::Assign { value: x, .. }), Some(Stmt::Assign { value: y, .. })) =
+                (taken.pop(), not_taken.pop())
+            else {
+                unreachable!("both blocks are a single store");
+            };
+            let Terminator::If { condition, .. } =
+                std::mem::replace(&mut body.blocks[id].terminator, Terminator::Goto(join))
+            else {
+                unreachable!("the block ends in a branch");
+            };
+            let condition = negate(body, condition);
+            body.blocks[id].stmts.push(Stmt::assign(
+                Expr::Var(var),
+                Expr::conditional(condition, y, x),
+            ));
+            true
+        }
+        (
+            [],
+            Terminator::If {
+                then: a_then,
+                otherwise: a_otherwise,
+                ..
+            },
+            [],
+            Terminator::If {
+                then: b_then,
+                otherwise: b_otherwise,
+                ..
+            },
+        ) if (a_then, a_otherwise) == (b_then, b_otherwise)
+            || (a_then, a_otherwise) == (b_otherwise, b_then) =>
+        {
+            let swapped = a_then != b_then;
+            let (targets_then, targets_otherwise) = (*a_then, *a_otherwise);
+            let (_, taken) = take_block(body, then);
+            let (_, not_taken) = take_block(body, otherwise);
+            let (Terminator::If { condition: x, .. }, Terminator::If { condition: y, .. }) =
+                (taken, not_taken)
+            else {
+                unreachable!("both blocks end in a branch");
+            };
+            let y = if swapped { negate(body, y) } else { y };
+            let terminator =
+                std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+            let Terminator::If { condition, .. } = terminator else {
+                unreachable!("the block ends in a branch");
+            };
+            body.blocks[id].terminator = Terminator::If {
+                condition: Expr::conditional(negate(body, condition), y, x),
+                then: targets_then,
+                otherwise: targets_otherwise,
+            };
+            true
+        }
+        _ => false,
+    }
+}
+
+// `if (c) return true; else return false;`, from the `iconst_1` and
+// `iconst_0` of a boolean method, becomes `return c`.
+fn merge_boolean_return(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    if body.return_type != Some(FieldType::Boolean) {
+        return false;
+    }
+    let Terminator::If {
+        then, otherwise, ..
+    } = body.blocks[id].terminator
+    else {
+        return false;
+    };
+    let boolean = |block: BlockId| match &body.blocks[block].terminator {
+        Terminator::Return(Some(Expr::Const(Const::Boolean(value)))) => Some(*value),
+        Terminator::Return(Some(Expr::Const(Const::Int(value @ (0 | 1))))) => Some(*value == 1),
+        _ => None,
+    };
+    let bare_return = |block: BlockId| {
+        only_entered_from(block, id, predecessors) && body.blocks[block].stmts.is_empty()
+    };
+    if then == otherwise || !bare_return(then) || !bare_return(otherwise) {
+        return false;
+    }
+    let (Some(taken), Some(not_taken)) = (boolean(then), boolean(otherwise)) else {
+        return false;
+    };
+    if taken == not_taken {
+        return false;
+    }
+    take_block(body, then);
+    take_block(body, otherwise);
+    let Terminator::If { condition, .. } =
+        std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable)
+    else {
+        unreachable!("the block ends in a branch");
+    };
+    let condition = if taken {
+        condition
+    } else {
+        negate(body, condition)
+    };
+    body.blocks[id].terminator = Terminator::Return(Some(condition));
+    true
+}
+
+// A block that only `from` jumps to, and that `from` always goes on to,
+// joins it.
+fn merge_straight_line(body: &mut Body, id: BlockId, predecessors: &[usize]) -> bool {
+    let Terminator::Goto(next) = body.blocks[id].terminator else {
+        return false;
+    };
+    if !only_entered_from(next, id, predecessors) || !same_handlers(body, id, next) {
+        return false;
+    }
+    let (mut stmts, terminator) = take_block(body, next);
+    body.blocks[id].stmts.append(&mut stmts);
+    body.blocks[id].terminator = terminator;
+    true
+}
+
+// Stores to temporaries that are read once, by the next statement and
+// before anything it evaluates could tell the difference, are put back into
+// the expression reading them.
+fn inline_temporaries(body: &mut Body) -> bool {
+    let count = body.variables.len();
+    let mut stores = vec![0; count];
+    let mut reads = vec![0; count];
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            if let Some(var) = stmt.defined_var() {
+                stores[var.0] += 1;
+            }
+            for expr in stmt.uses() {
+                count_vars(expr, &mut stores, &mut reads);
+            }
+        }
+        for expr in block.terminator.exprs() {
+            count_vars(expr, &mut stores, &mut reads);
+        }
+    }
+
+    let mut changed = false;
+    for id in 0..body.blocks.len() {
+        let mut index = body.blocks[id].stmts.len();
+        while index > 0 {
+            index -= 1;
+            let block = &body.blocks[id];
+            let Stmt::Assign {
+                target: Expr::Var(var),
+                value,
+            } = &block.stmts[index]
+            else {
+                continue;
+            };
+            let var = *var;
+            if body.variables[var.0].slot.is_some() || stores[var.0] != 1 || reads[var.0] != 1 {
+                continue;
+            }
+            // the statement reading the temporary, past stores the value can
+            // be evaluated after
+            let mut reader = index + 1;
+            while let Some(stmt) = block.stmts.get(reader) {
+                if stmt.uses().iter().any(|expr| expr.uses(var)) || !can_move_past(value, stmt) {
+                    break;
+                }
+                reader += 1;
+            }
+            let next: Vec<&Expr> = match block.stmts.get(reader) {
+                Some(stmt) => stmt.uses(),
+                None => block.terminator.exprs(),
+            };
+            if !can_move_to_read(value, &next, var) {
+                continue;
+            }
+            let Stmt::Assign { value, .. } = body.blocks[id].stmts.remove(index) else {
+                unreachable!("the statement is a store");
+            };
+            let block = &mut body.blocks[id];
+            let next: Vec<&mut Expr> = match block.stmts.get_mut(reader - 1) {
+                Some(stmt) => stmt.uses_mut(),
+                None => block.terminator.exprs_mut(),
+            };
+            let mut value = Some(value);
+            for expr in next {
+                expr.walk_mut(&mut |expr| {
+                    if *expr == Expr::Var(var) {
+                        if let Some(value) = value.take() {
+                            *expr = value;
+                        }
+                    }
+                });
+            }
+            changed = true;
+        }
+    }
+    changed
+}
+
+fn count_vars(expr: &Expr, stores: &mut [usize], reads: &mut [usize]) {
+    match expr {
+        Expr::Var(var) => reads[var.0] += 1,
+        Expr::Assign { target, value } => {
+            match &**target {
+                Expr::Var(var) => stores[var.0] += 1,
+                target => {
+                    for child in target.children() {
+                        count_vars(child, stores, reads);
+                    }
+                }
+            }
+            count_vars(value, stores, reads);
+        }
+        expr => {
+            for child in expr.children() {
+                count_vars(child, stores, reads);
+            }
+        }
+    }
+}
+
+/// Whether `value` can be evaluated where `exprs`, evaluated in order, read
+/// `var` instead of before them: the read has to be certain to happen, and
+/// nothing evaluated before it may have a side effect, nor read memory when
+/// `value` has one.
+pub fn can_move_to_read(value: &Expr, exprs: &[&Expr], var: Var) -> bool {
+    let mut before = Vec::new();
+    let mut found = false;
+    for expr in exprs {
+        match find_read(expr, var, &mut before) {
+            Some(true) => {
+                found = true;
+                break;
+            }
+            Some(false) => before.push(*expr),
+            None => return false,
+        }
+    }
+    if !found {
+        return false;
+    }
+    let value_has_effects = value.has_side_effects();
+    !before
+        .iter()
+        .any(|expr| expr.has_side_effects() || (value_has_effects && reads_memory(expr)))
+}
+
+// Whether `value` can be evaluated after `stmt` instead of before it: `stmt`
+// has to be a store to a variable `value` doesn't read, and neither may
+// have a side effect the other could see.
+fn can_move_past(value: &Expr, stmt: &Stmt) -> bool {
+    let Stmt::Assign {
+        target: Expr::Var(stored),
+        value: other,
+    } = stmt
+    else {
+        return false;
+    };
+    let assigns = |expr: &Expr| {
+        let mut assigns = false;
+        expr.walk(&mut |expr| assigns |= matches!(expr, Expr::Assign { .. }));
+        assigns
+    };
+    if value.uses(*stored) || assigns(value) || assigns(other) {
+        return false;
+    }
+    match (value.has_side_effects(), other.has_side_effects()) {
+        (true, true) => false,
+        (true, false) => !reads_memory(other),
+        (false, true) => !reads_memory(value),
+        (false, false) => true,
+    }
+}
+
+// Looks for the read of `var` in an expression, adding the subexpressions
+// fully evaluated before it to `before`. `None` if the read might not be
+// evaluated.
+fn find_read<'a>(expr: &'a Expr, var: Var, before: &mut Vec<&'a Expr>) -> Option<bool> {
+    if *expr == Expr::Var(var) {
+        return Some(true);
+    }
+    // only the first operand of these is always evaluated
+    let short_circuits = matches!(expr, Expr::Logical { .. } | Expr::Conditional { .. });
+    for (index, child) in expr.children().into_iter().enumerate() {
+        if !child.uses(var) {
+            before.push(child);
+        } else if index > 0 && short_circuits {
+            return None;
+        } else {
+            return find_read(child, var, before);
+        }
+    }
+    Some(false)
+}
+
+fn reads_memory(expr: &Expr) -> bool {
+    let mut reads = false;
+    expr.walk(&mut |expr| {
+        reads |= matches!(
+            expr,
+            Expr::Field { .. }
+                | Expr::ArrayElement { .. }
+                | Expr::ArrayLength(_)
+                | Expr::Invoke { .. }
+                | Expr::InvokeDynamic { .. }
+        )
+    });
+    reads
+}
+
+// Conditionals between boolean constants become the condition or a logical
+// operator, and comparisons with boolean constants go.
+fn simplify_booleans(body: &mut Body) {
+    for id in 0..body.blocks.len() {
+        let mut stmts = std::mem::take(&mut body.blocks[id].stmts);
+        let mut terminator =
+            std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+        let exprs = stmts
+            .iter_mut()
+            .flat_map(Stmt::exprs_mut)
+            .chain(terminator.exprs_mut());
+        for expr in exprs {
+            expr.walk_mut(&mut |expr| simplify_boolean(body, expr));
+        }
+        body.blocks[id].stmts = stmts;
+        body.blocks[id].terminator = terminator;
+    }
+}
+
+fn simplify_boolean(body: &Body, expr: &mut Expr) {
+    let boolean = |expr: &Expr| match expr {
+        Expr::Const(Const::Boolean(value)) => Some(*value),
+        _ => None,
+    };
+    let simplified = match std::mem::replace(expr, Expr::Const(Const::Null)) {
+        Expr::Conditional {
+            condition,
+            then,
+            otherwise,
+        } => match (boolean(&then), boolean(&otherwise)) {
+            (Some(true), Some(false)) => *condition,
+            (Some(false), Some(true)) => negate(body, *condition),
+            (Some(true), None) => Expr::logical(LogicalOp::Or, *condition, *otherwise),
+            (Some(false), None) => {
+                Expr::logical(LogicalOp::And, negate(body, *condition), *otherwise)
+            }
+            (None, Some(false)) => Expr::logical(LogicalOp::And, *condition, *then),
+            (None, Some(true)) => Expr::logical(LogicalOp::Or, negate(body, *condition), *then),
+            _ => Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            },
+        },
+        compare @ Expr::Compare { .. } => simplify_condition(body, compare),
+        other => other,
+    };
+    *expr = simplified;
+}
@@ -1,20 +1,32 @@
 use crate::error::DecompileError;
 use crate::error::DecompileError::{InvalidMagicNumber, NoSuchFile};
+use crate::frames::SuperclassMap;
+use crate::mutf8;
+use crate::printer::Style;
+use crate::source;
 use crate::types::{
-    Attribute, ClassFile, ConstantPoolType, CpInfo, ExceptionTable, FieldInfo, InnerClassInfo,
-    LineNumberTableEntry, MethodInfo, MethodParameter,
+    Annotation, AnnotationElementPair, Attribute, BootstrapMethod, ClassFile, ConstantPoolType,
+    CpInfo, ElementValue, ExceptionTable, FieldInfo, InnerClassInfo, LineNumberTableEntry,
+    LocalVarTable, LocalVariableTableEntry, LocalVariableTypeTableEntry, MethodInfo,
+    MethodParameter, ModuleExport, ModuleOpens, ModuleProvides, ModuleRequirement,
+    ParameterAnnotations, RecordComponentInfo, StackMapFrame, TargetInfo, TypeAnnotation, TypePath,
+    TypePathElement, VerificationTypeInfo,
 };
 use log::{debug, trace};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read, Seek};
 use std::path::PathBuf;
+use std::rc::Rc;
 
-const CAFE_BABE: u32 = 0xCAFE_BABE;
+pub const CAFE_BABE: u32 = 0xCAFE_BABE;
 
 pub type DecompileResult<T> = Result<T, DecompileError>;
 
 pub struct Decompile {
     path: PathBuf,
+    style: Style,
 }
 
 impl Decompile {
@@ -23,162 +35,267 @@ impl Decompile {
             return Err(NoSuchFile(path.clone()));
         }
 
-        Ok(Self { path })
+        Ok(Self {
+            path,
+            style: Style::default(),
+        })
     }
 
+    /// Sets how the source is laid out.
+    pub fn with_style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Decompiles the class and prints its source.
     pub fn decompile(&mut self) -> DecompileResult<()> {
+        print!("{}", self.source()?);
+        Ok(())
+    }
+
+    /// Decompiles the class into Java source, looking up the classes it
+    /// uses in the directory it is in.
+    pub fn source(&self) -> DecompileResult<String> {
+        let class_file = self.read_class_file()?;
+
+        trace!("class file: {:?}", class_file);
+
+        // TODO: validate class file e.g. indexes into constant pool are valid
+        // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.8
+
+        let mut hierarchy = SuperclassMap::new();
+        hierarchy.add_class_file(&class_file);
+        let directory = self.path.parent().map(PathBuf::from).unwrap_or_default();
+        let package = match class_file.this_class_name() {
+            Some(name) => name.rsplit_once('/').map_or("", |(package, _)| package),
+            None => "",
+        };
+        let classes = PackageDirectory::new(directory, package);
+        source::write_class(&class_file, &hierarchy, &classes, &self.style)
+    }
+
+    pub fn read_class_file(&self) -> DecompileResult<ClassFile> {
         let file = File::open(&self.path).map_err(DecompileError::IOError)?;
 
         let mut reader = BufReader::new(file);
 
-        let magic = read_u32(&mut reader);
-        if magic != CAFE_BABE {
-            return Err(InvalidMagicNumber(magic));
-        }
+        read_class_file(&mut reader)
+    }
+}
 
-        let mut class_file = ClassFile::new(magic);
-        class_file.minor_version = read_u16(&mut reader);
-        class_file.major_version = read_u16(&mut reader);
-
-        debug!(
-            "Class Version: {}.{}",
-            class_file.major_version, class_file.minor_version
-        );
-
-        let constant_pool_count = read_u16(&mut reader);
-
-        debug!("constant pool count: {}", constant_pool_count);
-
-        for _ in 0..constant_pool_count - 2 {
-            let pos = reader.stream_position()?;
-            let cp_info_tag = read_u8(&mut reader);
-            let cp_info_type = match cp_info_tag {
-                1 => cp_utf8(&mut reader)?,
-                3 => cp_integer(&mut reader)?,
-                4 => cp_float(&mut reader)?,
-                5 => cp_long(&mut reader)?,
-                6 => cp_double(&mut reader)?,
-                7 => ConstantPoolType::ConstantClass {
-                    name_idx: read_u16(&mut reader),
-                },
-                8 => ConstantPoolType::ConstantString {
-                    string_idx: read_u16(&mut reader),
-                },
-                9 => ConstantPoolType::ConstantFieldRef {
-                    class_index: read_u16(&mut reader),
-                    name_and_type_idx: read_u16(&mut reader),
-                },
-                10 => ConstantPoolType::ConstantMethodRef {
-                    class_index: read_u16(&mut reader),
-                    name_and_type_idx: read_u16(&mut reader),
-                },
-                11 => ConstantPoolType::ConstantInterfaceMethodRef {
-                    class_index: read_u16(&mut reader),
-                    name_and_type_idx: read_u16(&mut reader),
-                },
-                12 => ConstantPoolType::ConstantNameAndType {
-                    name_idx: read_u16(&mut reader),
-                    desc_idx: read_u16(&mut reader),
-                },
-                15 => ConstantPoolType::ConstantMethodHandle {
-                    ref_kind: read_u8(&mut reader),
-                    ref_idx: read_u16(&mut reader),
-                },
-                16 => ConstantPoolType::ConstantMethodType {
-                    desc_idx: read_u16(&mut reader),
-                },
-                17 => ConstantPoolType::ConstantDynamic {
-                    bootstrap_method_attr_index: read_u16(&mut reader),
-                    name_and_type_index: read_u16(&mut reader),
-                },
-                18 => ConstantPoolType::ConstantInvokeDynamic {
-                    bootstrap_method_attr_index: read_u16(&mut reader),
-                    name_and_type_index: read_u16(&mut reader),
-                },
-                19 => ConstantPoolType::ConstantModule {
-                    name_idx: read_u16(&mut reader),
-                },
-                20 => ConstantPoolType::ConstantPackage {
-                    name_idx: read_u16(&mut reader),
-                },
-                _ => {
-                    debug!("class_file:\n{class_file}");
-                    return Err(DecompileError::InvalidConstantPoolTag(cp_info_tag, pos));
-                }
-            };
+/// Finds the class files of other classes, such as the synthetic classes
+/// javac adds next to the class being decompiled.
+///
+/// Any `Fn(&str) -> Option<Rc<ClassFile>>` can be used as a class path.
+pub trait ClassPath {
+    /// The class file of a class given by internal name, if it can be found
+    /// and read.
+    fn class_file(&self, name: &str) -> Option<Rc<ClassFile>>;
+}
 
-            let info = CpInfo {
-                tag: cp_info_tag,
-                info: Some(cp_info_type),
-            };
+impl<F: Fn(&str) -> Option<Rc<ClassFile>>> ClassPath for F {
+    fn class_file(&self, name: &str) -> Option<Rc<ClassFile>> {
+        self(name)
+    }
+}
+
+/// A [`ClassPath`] of the classes of one package, read from the directory
+/// its class files are in. Each class is read once.
+#[derive(Debug)]
+pub struct PackageDirectory {
+    directory: PathBuf,
+    // the internal name of the package, empty for the unnamed package
+    package: String,
+    read: RefCell<HashMap<String, Option<Rc<ClassFile>>>>,
+}
+
+impl PackageDirectory {
+    pub fn new(directory: PathBuf, package: &str) -> Self {
+        Self {
+            directory,
+            package: package.to_string(),
+            read: RefCell::new(HashMap::new()),
+        }
+    }
+}
 
-            class_file.add_constant_pool_entry(info);
+impl ClassPath for PackageDirectory {
+    fn class_file(&self, name: &str) -> Option<Rc<ClassFile>> {
+        let simple = match name.rsplit_once('/') {
+            Some((package, simple)) if package == self.package => simple,
+            None if self.package.is_empty() => name,
+            _ => return None,
+        };
+        if let Some(class_file) = self.read.borrow().get(name) {
+            return class_file.clone();
         }
+        let path = self.directory.join(format!("{simple}.class"));
+        let class_file = File::open(path)
+            .ok()
+            .and_then(|file| read_class_file(&mut BufReader::new(file)).ok())
+            .map(Rc::new);
+        self.read
+            .borrow_mut()
+            .insert(name.to_string(), class_file.clone());
+        class_file
+    }
+}
 
-        debug!(
-            "read {} constant pool items",
-            class_file.get_constant_pool_size()
-        );
+/// Parses a class file. Everything in the class file is kept, including
+/// attributes this crate doesn't interpret, so writing the result back out
+/// with [`crate::writer::write_class_file`] gives the same bytes.
+pub fn read_class_file<R: Read + Seek>(reader: &mut R) -> DecompileResult<ClassFile> {
+    let magic = read_u32(reader)?;
+    if magic != CAFE_BABE {
+        return Err(InvalidMagicNumber(magic));
+    }
 
-        class_file.access_flags = read_u16(&mut reader);
-        debug!("access_flags: {:#x}", class_file.access_flags);
+    let mut class_file = ClassFile::new(magic);
+    class_file.minor_version = read_u16(reader)?;
+    class_file.major_version = read_u16(reader)?;
+
+    debug!(
+        "Class Version: {}.{}",
+        class_file.major_version, class_file.minor_version
+    );
+
+    let constant_pool_count = read_u16(reader)?;
+
+    debug!("constant pool count: {}", constant_pool_count);
+
+    // long and double entries take two slots, which add_constant_pool_entry()
+    // accounts for, so loop on the pool size rather than a counter
+    while class_file.get_constant_pool_size() + 1 < constant_pool_count as usize {
+        let pos = reader.stream_position()?;
+        let cp_info_tag = read_u8(reader)?;
+        let cp_info_type = match cp_info_tag {
+            1 => cp_utf8(reader)?,
+            3 => cp_integer(reader)?,
+            4 => cp_float(reader)?,
+            5 => cp_long(reader)?,
+            6 => cp_double(reader)?,
+            7 => ConstantPoolType::ConstantClass {
+                name_idx: read_u16(reader)?,
+            },
+            8 => ConstantPoolType::ConstantString {
+                string_idx: read_u16(reader)?,
+            },
+            9 => ConstantPoolType::ConstantFieldRef {
+                class_index: read_u16(reader)?,
+                name_and_type_idx: read_u16(reader)?,
+            },
+            10 => ConstantPoolType::ConstantMethodRef {
+                class_index: read_u16(reader)?,
+                name_and_type_idx: read_u16(reader)?,
+            },
+            11 => ConstantPoolType::ConstantInterfaceMethodRef {
+                class_index: read_u16(reader)?,
+                name_and_type_idx: read_u16(reader)?,
+            },
+            12 => ConstantPoolType::ConstantNameAndType {
+                name_idx: read_u16(reader)?,
+                desc_idx: read_u16(reader)?,
+            },
+            15 => ConstantPoolType::ConstantMethodHandle {
+                ref_kind: read_u8(reader)?,
+                ref_idx: read_u16(reader)?,
+            },
+            16 => ConstantPoolType::ConstantMethodType {
+                desc_idx: read_u16(reader)?,
+            },
+            17 => ConstantPoolType::ConstantDynamic {
+                bootstrap_method_attr_index: read_u16(reader)?,
+                name_and_type_index: read_u16(reader)?,
+            },
+            18 => ConstantPoolType::ConstantInvokeDynamic {
+                bootstrap_method_attr_index: read_u16(reader)?,
+                name_and_type_index: read_u16(reader)?,
+            },
+            19 => ConstantPoolType::ConstantModule {
+                name_idx: read_u16(reader)?,
+            },
+            20 => ConstantPoolType::ConstantPackage {
+                name_idx: read_u16(reader)?,
+            },
+            _ => {
+                debug!("class_file:\n{class_file}");
+                return Err(DecompileError::InvalidConstantPoolTag(cp_info_tag, pos));
+            }
+        };
 
-        class_file.this_class = read_u16(&mut reader);
-        debug!("this_class idx: {}", class_file.this_class);
+        let info = CpInfo {
+            tag: cp_info_tag,
+            info: Some(cp_info_type),
+        };
 
-        class_file.super_class = read_u16(&mut reader);
-        debug!("super_class idx: {}", class_file.super_class);
+        class_file.add_constant_pool_entry(info);
+    }
 
-        class_file.interfaces_count = read_u16(&mut reader);
-        debug!("interfaces_count: {}", class_file.interfaces_count);
+    debug!(
+        "read {} constant pool items",
+        class_file.get_constant_pool_size()
+    );
 
-        for _ in 0..class_file.interfaces_count {
-            let value = read_u8(&mut reader);
-            debug!("interface idx: {value}");
-            class_file.interfaces.push(value);
-        }
+    class_file.access_flags = read_u16(reader)?;
+    debug!("access_flags: {:#x}", class_file.access_flags);
 
-        class_file.fields_count = read_u16(&mut reader);
-        debug!("fields_count: {}", class_file.fields_count);
+    class_file.this_class = read_u16(reader)?;
+    debug!("this_class idx: {}", class_file.this_class);
 
-        for _ in 0..class_file.fields_count {
-            let field_info = read_field_info(&mut reader, &class_file)?;
-            debug!("adding {:?}", field_info);
-            class_file.fields.push(field_info);
-        }
+    class_file.super_class = read_u16(reader)?;
+    debug!("super_class idx: {}", class_file.super_class);
 
-        class_file.methods_count = read_u16(&mut reader);
-        debug!("methods_count: {}", class_file.methods_count);
+    class_file.interfaces_count = read_u16(reader)?;
+    debug!("interfaces_count: {}", class_file.interfaces_count);
 
-        for _ in 0..class_file.methods_count {
-            let method_info = read_method_info(&mut reader, &class_file)?;
-            debug!("adding {:?}", method_info);
-            class_file.methods.push(method_info);
-        }
+    for _ in 0..class_file.interfaces_count {
+        let value = read_u16(reader)?;
+        debug!("interface idx: {value}");
+        class_file.interfaces.push(value);
+    }
 
-        trace!("class file: {:?}", class_file);
+    class_file.fields_count = read_u16(reader)?;
+    debug!("fields_count: {}", class_file.fields_count);
 
-        // TODO: validate class file e.g. indexes into constant pool are valid
-        // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.8
+    for _ in 0..class_file.fields_count {
+        let field_info = read_field_info(reader, &class_file)?;
+        debug!("adding {:?}", field_info);
+        class_file.fields.push(field_info);
+    }
 
-        // TODO: print disassembly
+    class_file.methods_count = read_u16(reader)?;
+    debug!("methods_count: {}", class_file.methods_count);
 
-        Ok(())
+    for _ in 0..class_file.methods_count {
+        let method_info = read_method_info(reader, &class_file)?;
+        debug!("adding {:?}", method_info);
+        class_file.methods.push(method_info);
+    }
+
+    class_file.attributes_count = read_u16(reader)?;
+    debug!("attributes_count: {}", class_file.attributes_count);
+
+    for _ in 0..class_file.attributes_count {
+        let attr = read_attribute_info(reader, &class_file)?;
+        class_file.attributes.push(attr);
     }
+
+    Ok(class_file)
 }
 
-fn cp_utf8(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
+fn cp_utf8<R: Read + Seek>(reader: &mut R) -> DecompileResult<ConstantPoolType> {
     trace!("cp_utf8()");
 
-    let len = read_u16(reader);
-    let bytes = read_variable(reader, len as usize);
+    let pos = reader.stream_position()?;
+    let len = read_u16(reader)?;
+    let bytes = read_variable(reader, len as usize)?;
     debug!("utf8: len({len}) bytes: {:x?}", bytes);
-    let value = std::str::from_utf8(&bytes).unwrap().to_string();
+    let value = mutf8::decode(&bytes).ok_or(DecompileError::InvalidModifiedUtf8(pos))?;
 
-    Ok(ConstantPoolType::ConstantUtf8 { len, value })
+    Ok(ConstantPoolType::ConstantUtf8 { len, value, bytes })
 }
 
-fn cp_integer(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
+fn cp_integer<R: Read + Seek>(reader: &mut R) -> DecompileResult<ConstantPoolType> {
     trace!("cp_integer()");
     let mut buf = [0u8; 4];
     reader.read_exact(&mut buf)?;
@@ -187,7 +304,7 @@ fn cp_integer(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType>
     Ok(ConstantPoolType::ConstantInteger { value })
 }
 
-fn cp_long(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
+fn cp_long<R: Read + Seek>(reader: &mut R) -> DecompileResult<ConstantPoolType> {
     trace!("cp_long()");
     let mut buf = [0u8; 8];
     reader.read_exact(&mut buf)?;
@@ -196,7 +313,7 @@ fn cp_long(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
     Ok(ConstantPoolType::ConstantLong { value })
 }
 
-fn cp_float(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
+fn cp_float<R: Read + Seek>(reader: &mut R) -> DecompileResult<ConstantPoolType> {
     trace!("cp_float()");
     let mut buf = [0u8; 4];
     reader.read_exact(&mut buf)?;
@@ -205,7 +322,7 @@ fn cp_float(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
     Ok(ConstantPoolType::ConstantFloat { value })
 }
 
-fn cp_double(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType> {
+fn cp_double<R: Read + Seek>(reader: &mut R) -> DecompileResult<ConstantPoolType> {
     trace!("cp_double()");
     let mut buf = [0u8; 8];
     reader.read_exact(&mut buf)?;
@@ -214,43 +331,48 @@ fn cp_double(reader: &mut BufReader<File>) -> DecompileResult<ConstantPoolType>
     Ok(ConstantPoolType::ConstantDouble { value })
 }
 
-fn read_u8(reader: &mut BufReader<File>) -> u8 {
-    trace!("read_utf8()");
+fn read_u8<R: Read + Seek>(reader: &mut R) -> DecompileResult<u8> {
+    trace!("read_u8()");
     let mut buf = [0u8; 1];
-    reader.read_exact(&mut buf).expect("invalid class file"); // todo: better error
-    u8::from_be_bytes(buf)
+    reader.read_exact(&mut buf)?;
+    Ok(u8::from_be_bytes(buf))
 }
 
-fn read_u16(reader: &mut BufReader<File>) -> u16 {
+fn read_u16<R: Read + Seek>(reader: &mut R) -> DecompileResult<u16> {
     trace!("read_u16()");
     let mut buf = [0u8; 2];
-    reader.read_exact(&mut buf).expect("invalid class file"); // todo: better error
-    u16::from_be_bytes(buf)
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
 }
 
-fn read_u32(reader: &mut BufReader<File>) -> u32 {
+fn read_u32<R: Read + Seek>(reader: &mut R) -> DecompileResult<u32> {
     trace!("read_u32()");
     let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf).expect("invalid class file"); // todo: better error
-    u32::from_be_bytes(buf)
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
 }
 
-fn read_variable(reader: &mut BufReader<File>, len: usize) -> Vec<u8> {
+fn read_variable<R: Read + Seek>(reader: &mut R, len: usize) -> DecompileResult<Vec<u8>> {
     trace!("read_variable({len})");
-    let mut buf = vec![0; len];
-    reader.read_exact(&mut buf).expect("invalid class file"); // todo: better error
-    buf
+    // read as far as the file goes rather than allocating the length up
+    // front, which a truncated or corrupt file may make up
+    let mut buf = Vec::new();
+    reader.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    if buf.len() < len {
+        return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(buf)
 }
 
-fn read_field_info(
-    reader: &mut BufReader<File>,
+fn read_field_info<R: Read + Seek>(
+    reader: &mut R,
     class_file: &ClassFile,
 ) -> DecompileResult<FieldInfo> {
     trace!("read_field_info()");
-    let access_flags = read_u16(reader);
-    let name_index = read_u16(reader);
-    let descriptor_index = read_u16(reader);
-    let attributes_count = read_u16(reader);
+    let access_flags = read_u16(reader)?;
+    let name_index = read_u16(reader)?;
+    let descriptor_index = read_u16(reader)?;
+    let attributes_count = read_u16(reader)?;
     debug!("field_info: name_index {name_index} descriptor_index {descriptor_index} attributes_count {attributes_count}");
 
     let field_name = resolve_utf8_cp_entry(reader, class_file, name_index)?;
@@ -260,6 +382,9 @@ fn read_field_info(
 
     let mut field_info = FieldInfo {
         access_flags,
+        name_index,
+        descriptor_index,
+        attributes_count,
         name: field_name,
         descriptor: field_descriptor,
         value: None,
@@ -280,7 +405,9 @@ fn read_field_info(
 
                 // TODO: check that the field is a static field
                 if field_info.value.is_some() {
-                    todo!("only one value allowed, so this is an error");
+                    return Err(DecompileError::DuplicateConstantValue(
+                        reader.stream_position()?,
+                    ));
                 }
 
                 field_info.value = if let Some(cp_info) =
@@ -299,7 +426,7 @@ fn read_field_info(
                             if let Some(info) =
                                 class_file.get_constant_pool_entry(*string_idx as usize)
                             {
-                                if let Some(ConstantPoolType::ConstantUtf8 { value, len: _ }) =
+                                if let Some(ConstantPoolType::ConstantUtf8 { value, .. }) =
                                     &info.info
                                 {
                                     Some(value.clone())
@@ -315,7 +442,12 @@ fn read_field_info(
                                 ));
                             }
                         }
-                        _ => todo!("invalid field value type"),
+                        _ => {
+                            return Err(DecompileError::InvalidConstantValue(
+                                constant_value_index,
+                                reader.stream_position()?,
+                            ))
+                        }
                     }
                 } else {
                     return Err(DecompileError::NoSuchConstantPoolEntry(
@@ -335,15 +467,15 @@ fn read_field_info(
     Ok(field_info)
 }
 
-fn read_method_info(
-    reader: &mut BufReader<File>,
+fn read_method_info<R: Read + Seek>(
+    reader: &mut R,
     class_file: &ClassFile,
 ) -> DecompileResult<MethodInfo> {
     trace!("read_method_info()");
-    let access_flags = read_u16(reader);
-    let name_index = read_u16(reader);
-    let descriptor_index = read_u16(reader);
-    let attributes_count = read_u16(reader);
+    let access_flags = read_u16(reader)?;
+    let name_index = read_u16(reader)?;
+    let descriptor_index = read_u16(reader)?;
+    let attributes_count = read_u16(reader)?;
 
     let mut method_info = MethodInfo {
         access_flags,
@@ -362,14 +494,14 @@ fn read_method_info(
     Ok(method_info)
 }
 
-fn read_attribute_info(
-    reader: &mut BufReader<File>,
+fn read_attribute_info<R: Read + Seek>(
+    reader: &mut R,
     class_file: &ClassFile,
 ) -> DecompileResult<Attribute> {
     trace!("read_attribute_info()");
 
-    let index = read_u16(reader);
-    let length = read_u32(reader);
+    let index = read_u16(reader)?;
+    let length = read_u32(reader)?;
 
     debug!("attr_info: index {index} len {length}");
 
@@ -381,7 +513,7 @@ fn read_attribute_info(
             // attribute_length
             //     The value of the attribute_length item must be two.
             assert_eq!(length, 2);
-            let constant_value_index = read_u16(reader);
+            let constant_value_index = read_u16(reader)?;
             Attribute::ConstantValue {
                 attribute_name_index: index,
                 attribute_length: length,
@@ -389,21 +521,21 @@ fn read_attribute_info(
             }
         }
         "Code" => {
-            let max_stack = read_u16(reader);
-            let max_locals = read_u16(reader);
-            let code_length = read_u32(reader);
-            let code = read_variable(reader, code_length as usize);
-            let exception_table_length = read_u16(reader);
+            let max_stack = read_u16(reader)?;
+            let max_locals = read_u16(reader)?;
+            let code_length = read_u32(reader)?;
+            let code = read_variable(reader, code_length as usize)?;
+            let exception_table_length = read_u16(reader)?;
             let mut exception_table = Vec::with_capacity(exception_table_length as usize);
             for _ in 0..exception_table_length {
                 exception_table.push(ExceptionTable {
-                    start_pc: read_u16(reader),
-                    end_pc: read_u16(reader),
-                    handler_pc: read_u16(reader),
-                    catch_type: read_u16(reader),
+                    start_pc: read_u16(reader)?,
+                    end_pc: read_u16(reader)?,
+                    handler_pc: read_u16(reader)?,
+                    catch_type: read_u16(reader)?,
                 })
             }
-            let attributes_count = read_u16(reader);
+            let attributes_count = read_u16(reader)?;
             let mut attributes = Vec::with_capacity(attributes_count as usize);
             for _ in 0..attributes_count {
                 attributes.push(read_attribute_info(reader, class_file)?);
@@ -422,12 +554,12 @@ fn read_attribute_info(
             }
         }
         "LineNumberTable" => {
-            let line_number_table_length = read_u16(reader);
+            let line_number_table_length = read_u16(reader)?;
             let mut line_number_table = Vec::with_capacity(line_number_table_length as usize);
             for _ in 0..line_number_table_length {
                 line_number_table.push(LineNumberTableEntry {
-                    start_pc: read_u16(reader),
-                    line_number: read_u16(reader),
+                    start_pc: read_u16(reader)?,
+                    line_number: read_u16(reader)?,
                 });
             }
 
@@ -441,15 +573,15 @@ fn read_attribute_info(
         "SourceFile" => Attribute::SourceFile {
             attribute_name_index: index,
             attribute_length: length,
-            sourcefile_index: read_u16(reader),
+            sourcefile_index: read_u16(reader)?,
         },
         "MethodParameters" => {
-            let parameters_count = read_u8(reader);
+            let parameters_count = read_u8(reader)?;
             let mut parameters = Vec::with_capacity(parameters_count as usize);
             for _ in 0..parameters_count {
                 parameters.push(MethodParameter {
-                    name_index: read_u16(reader),
-                    access_flags: read_u16(reader),
+                    name_index: read_u16(reader)?,
+                    access_flags: read_u16(reader)?,
                 });
             }
             Attribute::MethodParameters {
@@ -460,14 +592,14 @@ fn read_attribute_info(
             }
         }
         "InnerClasses" => {
-            let number_of_classes = read_u16(reader);
+            let number_of_classes = read_u16(reader)?;
             let mut classes = Vec::with_capacity(number_of_classes as usize);
             for _ in 0..number_of_classes {
                 classes.push(InnerClassInfo {
-                    inner_class_info_index: read_u16(reader),
-                    outer_class_info_index: read_u16(reader),
-                    inner_name_index: read_u16(reader),
-                    inner_class_access_flags: read_u16(reader),
+                    inner_class_info_index: read_u16(reader)?,
+                    outer_class_info_index: read_u16(reader)?,
+                    inner_name_index: read_u16(reader)?,
+                    inner_class_access_flags: read_u16(reader)?,
                 })
             }
             Attribute::InnerClasses {
@@ -477,21 +609,335 @@ fn read_attribute_info(
                 classes,
             }
         }
-        // "StackMapTable" => {}
-        // "Exceptions" => {}
-        // "EnclosingMethod" => {}
-        // "Synthetic" => {}
-        // "Signature" => {}
-        // "SourceDebugExtension" => {}
-        // "LocalVariableTable" => {}
-        // "LocalVariableTypeTable" => {}
-        // "Deprecated" => {}
-        // "Module" => {}
-        // "ModulePackages" => {}
-        // "ModuleMainClass" => {}
-        // "Record" => {}
-        // "PermittedSubclasses" => {}
-        _ => todo!("ignoring {attr_name} for now"),
+        "StackMapTable" => {
+            let number_of_entries = read_u16(reader)?;
+            let mut entries = Vec::with_capacity(number_of_entries as usize);
+            for _ in 0..number_of_entries {
+                entries.push(read_stack_map_frame(reader)?);
+            }
+            Attribute::StackMapTable {
+                attribute_name_index: index,
+                attribute_length: length,
+                number_of_entries,
+                entries,
+            }
+        }
+        "Exceptions" => {
+            let number_of_exceptions = read_u16(reader)?;
+            Attribute::Exceptions {
+                attribute_name_index: index,
+                attribute_length: length,
+                number_of_exceptions,
+                exception_index_table: read_u16_table(reader, number_of_exceptions)?,
+            }
+        }
+        "EnclosingMethod" => Attribute::EnclosingMethod {
+            attribute_name_index: index,
+            attribute_length: length,
+            class_index: read_u16(reader)?,
+            method_index: read_u16(reader)?,
+        },
+        "Synthetic" => Attribute::Synthetic {
+            attribute_name_index: index,
+            attribute_length: length,
+        },
+        "Signature" => Attribute::Signature {
+            attribute_name_index: index,
+            attribute_length: length,
+            signature_index: read_u16(reader)?,
+        },
+        "SourceDebugExtension" => Attribute::SourceDebugExtension {
+            attribute_name_index: index,
+            attribute_length: length,
+            debug_extension: read_variable(reader, length as usize)?,
+        },
+        "LocalVariableTable" => {
+            let local_variable_table_length = read_u16(reader)?;
+            let mut local_variable_table = Vec::with_capacity(local_variable_table_length as usize);
+            for _ in 0..local_variable_table_length {
+                local_variable_table.push(LocalVariableTableEntry {
+                    start_pc: read_u16(reader)?,
+                    length: read_u16(reader)?,
+                    name_index: read_u16(reader)?,
+                    descriptor_index: read_u16(reader)?,
+                    index: read_u16(reader)?,
+                });
+            }
+            Attribute::LocalVariableTable {
+                attribute_name_index: index,
+                attribute_length: length,
+                local_variable_table_length,
+                local_variable_table,
+            }
+        }
+        "LocalVariableTypeTable" => {
+            let local_variable_type_table_length = read_u16(reader)?;
+            let mut local_variable_type_table =
+                Vec::with_capacity(local_variable_type_table_length as usize);
+            for _ in 0..local_variable_type_table_length {
+                local_variable_type_table.push(LocalVariableTypeTableEntry {
+                    start_pc: read_u16(reader)?,
+                    length: read_u16(reader)?,
+                    name_index: read_u16(reader)?,
+                    signature_index: read_u16(reader)?,
+                    index: read_u16(reader)?,
+                });
+            }
+            Attribute::LocalVariableTypeTable {
+                attribute_name_index: index,
+                attribute_length: length,
+                local_variable_type_table_length,
+                local_variable_type_table,
+            }
+        }
+        "Deprecated" => Attribute::Deprecated {
+            attribute_name_index: index,
+            attribute_length: length,
+        },
+        "RuntimeVisibleAnnotations" | "RuntimeInvisibleAnnotations" => {
+            let num_annotations = read_u16(reader)?;
+            let mut annotations = Vec::with_capacity(num_annotations as usize);
+            for _ in 0..num_annotations {
+                annotations.push(read_annotation(reader)?);
+            }
+            if attr_name == "RuntimeVisibleAnnotations" {
+                Attribute::RuntimeVisibleAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_annotations,
+                    annotations,
+                }
+            } else {
+                Attribute::RuntimeInvisibleAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_annotations,
+                    annotations,
+                }
+            }
+        }
+        "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+            let num_parameters = read_u8(reader)?;
+            let mut parameter_annotations = Vec::with_capacity(num_parameters as usize);
+            for _ in 0..num_parameters {
+                let num_annotations = read_u16(reader)?;
+                let mut annotations = Vec::with_capacity(num_annotations as usize);
+                for _ in 0..num_annotations {
+                    annotations.push(read_annotation(reader)?);
+                }
+                parameter_annotations.push(ParameterAnnotations {
+                    num_annotations,
+                    annotations,
+                });
+            }
+            if attr_name == "RuntimeVisibleParameterAnnotations" {
+                Attribute::RuntimeVisibleParameterAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_parameters,
+                    parameter_annotations,
+                }
+            } else {
+                Attribute::RuntimeInvisibleParameterAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_parameters,
+                    parameter_annotations,
+                }
+            }
+        }
+        "RuntimeVisibleTypeAnnotations" | "RuntimeInvisibleTypeAnnotations" => {
+            let num_annotations = read_u16(reader)?;
+            let mut annotations = Vec::with_capacity(num_annotations as usize);
+            for _ in 0..num_annotations {
+                annotations.push(read_type_annotation(reader)?);
+            }
+            if attr_name == "RuntimeVisibleTypeAnnotations" {
+                Attribute::RuntimeVisibleTypeAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_annotations,
+                    annotations,
+                }
+            } else {
+                Attribute::RuntimeInvisibleTypeAnnotations {
+                    attribute_name_index: index,
+                    attribute_length: length,
+                    num_annotations,
+                    annotations,
+                }
+            }
+        }
+        "AnnotationDefault" => Attribute::AnnotationDefault {
+            attribute_name_index: index,
+            attribute_length: length,
+            default_value: read_element_value(reader)?,
+        },
+        "BootstrapMethods" => {
+            let num_bootstrap_methods = read_u16(reader)?;
+            let mut bootstrap_methods = Vec::with_capacity(num_bootstrap_methods as usize);
+            for _ in 0..num_bootstrap_methods {
+                let bootstrap_method_ref = read_u16(reader)?;
+                let num_bootstrap_arguments = read_u16(reader)?;
+                bootstrap_methods.push(BootstrapMethod {
+                    bootstrap_method_ref,
+                    num_bootstrap_arguments,
+                    bootstrap_arguments: read_u16_table(reader, num_bootstrap_arguments)?,
+                });
+            }
+            Attribute::BootstrapMethods {
+                attribute_name_index: index,
+                attribute_length: length,
+                num_bootstrap_methods,
+                bootstrap_methods,
+            }
+        }
+        "Module" => {
+            let module_name_index = read_u16(reader)?;
+            let module_flags = read_u16(reader)?;
+            let module_version_index = read_u16(reader)?;
+
+            let requires_count = read_u16(reader)?;
+            let mut requires = Vec::with_capacity(requires_count as usize);
+            for _ in 0..requires_count {
+                requires.push(ModuleRequirement {
+                    requires_index: read_u16(reader)?,
+                    requires_flags: read_u16(reader)?,
+                    requires_version_index: read_u16(reader)?,
+                });
+            }
+
+            let exports_count = read_u16(reader)?;
+            let mut exports = Vec::with_capacity(exports_count as usize);
+            for _ in 0..exports_count {
+                let exports_index = read_u16(reader)?;
+                let exports_flags = read_u16(reader)?;
+                let exports_to_count = read_u16(reader)?;
+                exports.push(ModuleExport {
+                    exports_index,
+                    exports_flags,
+                    exports_to_count,
+                    exports_to_index: read_u16_table(reader, exports_to_count)?,
+                });
+            }
+
+            let opens_count = read_u16(reader)?;
+            let mut opens = Vec::with_capacity(opens_count as usize);
+            for _ in 0..opens_count {
+                let opens_index = read_u16(reader)?;
+                let opens_flags = read_u16(reader)?;
+                let opens_to_count = read_u16(reader)?;
+                opens.push(ModuleOpens {
+                    opens_index,
+                    opens_flags,
+                    opens_to_count,
+                    opens_to_index: read_u16_table(reader, opens_to_count)?,
+                });
+            }
+
+            let uses_count = read_u16(reader)?;
+            let uses_index = read_u16_table(reader, uses_count)?;
+
+            let provides_count = read_u16(reader)?;
+            let mut provides = Vec::with_capacity(provides_count as usize);
+            for _ in 0..provides_count {
+                let provides_index = read_u16(reader)?;
+                let provides_with_count = read_u16(reader)?;
+                provides.push(ModuleProvides {
+                    provides_index,
+                    provides_with_count,
+                    provides_with_index: read_u16_table(reader, provides_with_count)?,
+                });
+            }
+
+            Attribute::Module {
+                attribute_name_index: index,
+                attribute_length: length,
+                module_name_index,
+                module_flags,
+                module_version_index,
+                requires_count,
+                requires,
+                exports_count,
+                exports,
+                opens_count,
+                opens,
+                uses_count,
+                uses_index,
+                provides_count,
+                provides,
+            }
+        }
+        "ModulePackages" => {
+            let package_count = read_u16(reader)?;
+            Attribute::ModulePackages {
+                attribute_name_index: index,
+                attribute_length: length,
+                package_count,
+                package_index: read_u16_table(reader, package_count)?,
+            }
+        }
+        "ModuleMainClass" => Attribute::ModuleMainClass {
+            attribute_name_index: index,
+            attribute_length: length,
+            main_class_index: read_u16(reader)?,
+        },
+        "NestHost" => Attribute::NestHost {
+            attribute_name_index: index,
+            attribute_length: length,
+            host_class_index: read_u16(reader)?,
+        },
+        "NestMembers" => {
+            let number_of_classes = read_u16(reader)?;
+            Attribute::NestMembers {
+                attribute_name_index: index,
+                attribute_length: length,
+                number_of_classes,
+                classes: read_u16_table(reader, number_of_classes)?,
+            }
+        }
+        "Record" => {
+            let component_count = read_u16(reader)?;
+            let mut components = Vec::with_capacity(component_count as usize);
+            for _ in 0..component_count {
+                let name_index = read_u16(reader)?;
+                let descriptor_index = read_u16(reader)?;
+                let attributes_count = read_u16(reader)?;
+                let mut attributes = Vec::with_capacity(attributes_count as usize);
+                for _ in 0..attributes_count {
+                    attributes.push(read_attribute_info(reader, class_file)?);
+                }
+                components.push(RecordComponentInfo {
+                    name_index,
+                    descriptor_index,
+                    attributes_count,
+                    attributes,
+                });
+            }
+            Attribute::Record {
+                attribute_name_index: index,
+                attribute_length: length,
+                component_count,
+                components,
+            }
+        }
+        "PermittedSubclasses" => {
+            let number_of_classes = read_u16(reader)?;
+            Attribute::PermittedSubclasses {
+                attribute_name_index: index,
+                attribute_length: length,
+                number_of_classes,
+                classes: read_u16_table(reader, number_of_classes)?,
+            }
+        }
+        _ => {
+            debug!("keeping unknown attribute {attr_name} as raw bytes");
+            Attribute::Unknown {
+                attribute_name_index: index,
+                attribute_length: length,
+                info: read_variable(reader, length as usize)?,
+            }
+        }
     };
 
     debug!("adding attr: {:?}", attr);
@@ -499,13 +945,221 @@ fn read_attribute_info(
     Ok(attr)
 }
 
-fn resolve_utf8_cp_entry(
-    reader: &mut BufReader<File>,
+fn read_u16_table<R: Read + Seek>(reader: &mut R, len: u16) -> DecompileResult<Vec<u16>> {
+    (0..len).map(|_| read_u16(reader)).collect()
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.4
+fn read_stack_map_frame<R: Read + Seek>(reader: &mut R) -> DecompileResult<StackMapFrame> {
+    let pos = reader.stream_position()?;
+    let frame_type = read_u8(reader)?;
+    let frame = match frame_type {
+        0..=63 => StackMapFrame::SameFrame { frame_type },
+        64..=127 => StackMapFrame::SameLocals1StackItemFrame {
+            frame_type,
+            stack: read_verification_type_info(reader)?,
+        },
+        247 => StackMapFrame::SameLocals1StackItemFrameExtended {
+            offset_delta: read_u16(reader)?,
+            stack: read_verification_type_info(reader)?,
+        },
+        248..=250 => StackMapFrame::ChopFrame {
+            frame_type,
+            offset_delta: read_u16(reader)?,
+        },
+        251 => StackMapFrame::SameFrameExtended {
+            offset_delta: read_u16(reader)?,
+        },
+        252..=254 => {
+            let offset_delta = read_u16(reader)?;
+            let mut locals = Vec::with_capacity((frame_type - 251) as usize);
+            for _ in 0..frame_type - 251 {
+                locals.push(read_verification_type_info(reader)?);
+            }
+            StackMapFrame::AppendFrame {
+                frame_type,
+                offset_delta,
+                locals,
+            }
+        }
+        255 => {
+            let offset_delta = read_u16(reader)?;
+            let number_of_locals = read_u16(reader)?;
+            let mut locals = Vec::with_capacity(number_of_locals as usize);
+            for _ in 0..number_of_locals {
+                locals.push(read_verification_type_info(reader)?);
+            }
+            let number_of_stack_items = read_u16(reader)?;
+            let mut stack = Vec::with_capacity(number_of_stack_items as usize);
+            for _ in 0..number_of_stack_items {
+                stack.push(read_verification_type_info(reader)?);
+            }
+            StackMapFrame::FullFrame {
+                offset_delta,
+                number_of_locals,
+                locals,
+                number_of_stack_items,
+                stack,
+            }
+        }
+        _ => return Err(DecompileError::InvalidStackMapFrameType(frame_type, pos)),
+    };
+
+    Ok(frame)
+}
+
+fn read_verification_type_info<R: Read + Seek>(
+    reader: &mut R,
+) -> DecompileResult<VerificationTypeInfo> {
+    let pos = reader.stream_position()?;
+    let tag = read_u8(reader)?;
+    let info = match tag {
+        0 => VerificationTypeInfo::Top,
+        1 => VerificationTypeInfo::Integer,
+        2 => VerificationTypeInfo::Float,
+        3 => VerificationTypeInfo::Double,
+        4 => VerificationTypeInfo::Long,
+        5 => VerificationTypeInfo::Null,
+        6 => VerificationTypeInfo::UninitializedThis,
+        7 => VerificationTypeInfo::Object {
+            cpool_index: read_u16(reader)?,
+        },
+        8 => VerificationTypeInfo::Uninitialized {
+            offset: read_u16(reader)?,
+        },
+        _ => return Err(DecompileError::InvalidVerificationTypeTag(tag, pos)),
+    };
+
+    Ok(info)
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.16
+fn read_annotation<R: Read + Seek>(reader: &mut R) -> DecompileResult<Annotation> {
+    let type_index = read_u16(reader)?;
+    let num_element_value_pairs = read_u16(reader)?;
+    Ok(Annotation {
+        type_index,
+        num_element_value_pairs,
+        element_value_pairs: read_element_value_pairs(reader, num_element_value_pairs)?,
+    })
+}
+
+fn read_element_value_pairs<R: Read + Seek>(
+    reader: &mut R,
+    num_element_value_pairs: u16,
+) -> DecompileResult<Vec<AnnotationElementPair>> {
+    (0..num_element_value_pairs)
+        .map(|_| {
+            Ok(AnnotationElementPair {
+                element_name_index: read_u16(reader)?,
+                value: read_element_value(reader)?,
+            })
+        })
+        .collect()
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.16.1
+fn read_element_value<R: Read + Seek>(reader: &mut R) -> DecompileResult<ElementValue> {
+    let tag = read_u8(reader)?;
+    let value = match tag {
+        b'e' => ElementValue::EnumConstantValue {
+            type_name_index: read_u16(reader)?,
+            const_name_index: read_u16(reader)?,
+        },
+        b'c' => ElementValue::ClassInfoIndex(read_u16(reader)?),
+        b'@' => ElementValue::AnnotationValue(read_annotation(reader)?),
+        b'[' => {
+            let num_values = read_u16(reader)?;
+            ElementValue::ArrayValue {
+                num_values,
+                values: (0..num_values)
+                    .map(|_| read_element_value(reader))
+                    .collect::<DecompileResult<_>>()?,
+            }
+        }
+        // B C D F I J S Z s
+        _ => ElementValue::ConstValueIndex {
+            tag,
+            const_value_index: read_u16(reader)?,
+        },
+    };
+    Ok(value)
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.20
+fn read_type_annotation<R: Read + Seek>(reader: &mut R) -> DecompileResult<TypeAnnotation> {
+    let pos = reader.stream_position()?;
+    let target_type = read_u8(reader)?;
+    let target_info = match target_type {
+        0x00 | 0x01 => TargetInfo::TypeParameter(read_u8(reader)?),
+        0x10 => TargetInfo::SuperType(read_u16(reader)?),
+        0x11 | 0x12 => TargetInfo::TypeParameterBound {
+            type_parameter_index: read_u8(reader)?,
+            bound_index: read_u8(reader)?,
+        },
+        0x13..=0x15 => TargetInfo::Empty,
+        0x16 => TargetInfo::FormalParameter(read_u8(reader)?),
+        0x17 => TargetInfo::Throws(read_u16(reader)?),
+        0x40 | 0x41 => {
+            let table_length = read_u16(reader)?;
+            let table = (0..table_length)
+                .map(|_| {
+                    Ok(LocalVarTable {
+                        start_pc: read_u16(reader)?,
+                        length: read_u16(reader)?,
+                        index: read_u16(reader)?,
+                    })
+                })
+                .collect::<DecompileResult<_>>()?;
+            TargetInfo::LocalVar {
+                table_length,
+                table,
+            }
+        }
+        0x42 => TargetInfo::Catch(read_u16(reader)?),
+        0x43..=0x46 => TargetInfo::Offset(read_u16(reader)?),
+        0x47..=0x4B => TargetInfo::TypeArgument {
+            offset: read_u16(reader)?,
+            type_argument_index: read_u8(reader)?,
+        },
+        _ => {
+            return Err(DecompileError::InvalidTypeAnnotationTarget(
+                target_type,
+                pos,
+            ))
+        }
+    };
+
+    let path_length = read_u8(reader)?;
+    let path = (0..path_length)
+        .map(|_| {
+            Ok(TypePathElement {
+                type_path_kind: read_u8(reader)?,
+                type_argument_index: read_u8(reader)?,
+            })
+        })
+        .collect::<DecompileResult<_>>()?;
+
+    let type_index = read_u16(reader)?;
+    let num_element_value_pairs = read_u16(reader)?;
+
+    Ok(TypeAnnotation {
+        target_type,
+        target_info,
+        target_path: TypePath { path_length, path },
+        type_index,
+        num_element_value_pairs,
+        element_value_pairs: read_element_value_pairs(reader, num_element_value_pairs)?,
+    })
+}
+
+fn resolve_utf8_cp_entry<R: Read + Seek>(
+    reader: &mut R,
     class_file: &ClassFile,
     index: u16,
 ) -> DecompileResult<String> {
     let value = if let Some(cp_info) = class_file.get_constant_pool_entry(index as usize) {
-        if let Some(ConstantPoolType::ConstantUtf8 { value, len: _ }) = &cp_info.info {
+        if let Some(ConstantPoolType::ConstantUtf8 { value, .. }) = &cp_info.info {
             value.clone()
         } else {
             return Err(DecompileError::InvalidUtf8ConstantPoolEntry(index));
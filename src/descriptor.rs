@@ -0,0 +1,170 @@
+use std::fmt::Display;
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.3.2
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    // internal name, e.g. java/lang/String
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    /// Parses a complete field descriptor such as `I` or `[Ljava/lang/String;`.
+    pub fn parse(descriptor: &str) -> Option<FieldType> {
+        match FieldType::parse_prefix(descriptor)? {
+            (field_type, "") => Some(field_type),
+            _ => None,
+        }
+    }
+
+    /// Parses a field type from the start of `s`, returning it and the rest.
+    fn parse_prefix(s: &str) -> Option<(FieldType, &str)> {
+        let rest = s.get(1..)?;
+        let field_type = match s.as_bytes().first()? {
+            b'B' => FieldType::Byte,
+            b'C' => FieldType::Char,
+            b'D' => FieldType::Double,
+            b'F' => FieldType::Float,
+            b'I' => FieldType::Int,
+            b'J' => FieldType::Long,
+            b'S' => FieldType::Short,
+            b'Z' => FieldType::Boolean,
+            b'L' => {
+                let end = rest.find(';')?;
+                if end == 0 {
+                    return None;
+                }
+                return Some((FieldType::Object(rest[..end].to_string()), &rest[end + 1..]));
+            }
+            b'[' => {
+                let (component, rest) = FieldType::parse_prefix(rest)?;
+                return Some((FieldType::Array(Box::new(component)), rest));
+            }
+            _ => return None,
+        };
+
+        Some((field_type, rest))
+    }
+
+    /// The type named by a `CONSTANT_Class`, which is an internal name for
+    /// classes and a descriptor for arrays.
+    pub fn from_class_name(name: &str) -> Option<FieldType> {
+        if name.starts_with('[') {
+            FieldType::parse(name)
+        } else if name.is_empty() {
+            None
+        } else {
+            Some(FieldType::Object(name.to_string()))
+        }
+    }
+
+    /// The number of local variable or operand stack slots a value of this
+    /// type takes.
+    pub fn slots(&self) -> u16 {
+        match self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1,
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    /// The name used in Java source, e.g. `int[]` or `java.lang.String`.
+    /// Nested classes keep their `$`.
+    pub fn java_name(&self) -> String {
+        match self {
+            FieldType::Byte => "byte".to_string(),
+            FieldType::Char => "char".to_string(),
+            FieldType::Double => "double".to_string(),
+            FieldType::Float => "float".to_string(),
+            FieldType::Int => "int".to_string(),
+            FieldType::Long => "long".to_string(),
+            FieldType::Short => "short".to_string(),
+            FieldType::Boolean => "boolean".to_string(),
+            FieldType::Object(name) => name.replace('/', "."),
+            FieldType::Array(component) => format!("{}[]", component.java_name()),
+        }
+    }
+}
+
+impl Display for FieldType {
+    /// Writes the descriptor.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(name) => write!(f, "L{name};"),
+            FieldType::Array(component) => write!(f, "[{component}"),
+        }
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.3.3
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    // None for void
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Option<MethodDescriptor> {
+        let mut rest = descriptor.strip_prefix('(')?;
+        let mut parameters = Vec::new();
+        while !rest.starts_with(')') {
+            let (parameter, next) = FieldType::parse_prefix(rest)?;
+            parameters.push(parameter);
+            rest = next;
+        }
+
+        let return_type = match &rest[1..] {
+            "V" => None,
+            ret => Some(FieldType::parse(ret)?),
+        };
+
+        Some(MethodDescriptor {
+            parameters,
+            return_type,
+        })
+    }
+
+    /// The number of local variable slots the parameters take, not counting
+    /// `this`.
+    pub fn parameter_slots(&self) -> u16 {
+        self.parameters.iter().map(FieldType::slots).sum()
+    }
+
+    pub fn return_slots(&self) -> u16 {
+        self.return_type.as_ref().map_or(0, FieldType::slots)
+    }
+}
+
+impl Display for MethodDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(")?;
+        for parameter in &self.parameters {
+            write!(f, "{parameter}")?;
+        }
+        write!(f, ")")?;
+        match &self.return_type {
+            Some(return_type) => write!(f, "{return_type}"),
+            None => write!(f, "V"),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use crate::cfg::{BlockId, Cfg};
+use std::collections::BTreeSet;
+
+/// A dominator or post-dominator tree over the blocks of a [`Cfg`].
+///
+/// Every kind of edge counts, exceptional ones included. The post-dominator
+/// tree is rooted at a virtual exit block with id `cfg.blocks().len()`, which
+/// every block without successors (returns, uncaught throws) leads to.
+///
+/// Computed with the iterative algorithm from Cooper, Harvey and Kennedy, "A
+/// Simple, Fast Dominance Algorithm".
+#[derive(Debug, Clone)]
+pub struct DominatorTree {
+    root: BlockId,
+    idom: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+    // predecessors in the direction of the analysis, reversed for
+    // post-dominators
+    predecessors: Vec<Vec<BlockId>>,
+    reverse_postorder: Vec<BlockId>,
+    // tree preorder and postorder numbers for constant time dominance checks
+    preorder: Vec<usize>,
+    postorder: Vec<usize>,
+}
+
+impl DominatorTree {
+    pub fn dominators(cfg: &Cfg) -> DominatorTree {
+        let successors = (0..cfg.blocks().len())
+            .map(|id| cfg.successors(id).map(|edge| edge.to).collect())
+            .collect();
+        DominatorTree::from_successors(0, successors)
+    }
+
+    pub fn post_dominators(cfg: &Cfg) -> DominatorTree {
+        let exit = cfg.blocks().len();
+        let mut successors: Vec<Vec<BlockId>> = vec![Vec::new(); exit + 1];
+        for id in 0..exit {
+            let mut has_successor = false;
+            for edge in cfg.successors(id) {
+                successors[edge.to].push(id);
+                has_successor = true;
+            }
+            if !has_successor {
+                successors[exit].push(id);
+            }
+        }
+        DominatorTree::from_successors(exit, successors)
+    }
+
+    /// The dominator tree of any graph, given the successors of each node.
+    /// Used for graphs derived from a method's blocks rather than its [`Cfg`].
+    pub fn from_successors(root: BlockId, successors: Vec<Vec<BlockId>>) -> DominatorTree {
+        let count = successors.len();
+        let mut predecessors = vec![Vec::new(); count];
+        for (from, tos) in successors.iter().enumerate() {
+            for to in tos {
+                predecessors[*to].push(from);
+            }
+        }
+
+        let reverse_postorder = if count == 0 {
+            Vec::new()
+        } else {
+            reverse_postorder(root, &successors)
+        };
+        let mut order = vec![usize::MAX; count];
+        for (index, id) in reverse_postorder.iter().enumerate() {
+            order[*id] = index;
+        }
+
+        let mut idom: Vec<Option<BlockId>> = vec![None; count];
+        if count > 0 {
+            idom[root] = Some(root);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in reverse_postorder.iter().skip(1) {
+                let mut new_idom = None;
+                for predecessor in &predecessors[*id] {
+                    if idom[*predecessor].is_none() {
+                        continue;
+                    }
+                    new_idom = Some(match new_idom {
+                        None => *predecessor,
+                        Some(current) => intersect(&idom, &order, *predecessor, current),
+                    });
+                }
+                if new_idom.is_some() && idom[*id] != new_idom {
+                    idom[*id] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        if count > 0 {
+            idom[root] = None;
+        }
+
+        let mut children = vec![Vec::new(); count];
+        for (id, parent) in idom.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(id);
+            }
+        }
+
+        let mut preorder = vec![usize::MAX; count];
+        let mut postorder = vec![usize::MAX; count];
+        if count > 0 {
+            let (mut pre, mut post) = (0, 0);
+            let mut stack = vec![(root, 0)];
+            preorder[root] = pre;
+            pre += 1;
+            while let Some((id, child)) = stack.pop() {
+                if let Some(next) = children[id].get(child) {
+                    stack.push((id, child + 1));
+                    preorder[*next] = pre;
+                    pre += 1;
+                    stack.push((*next, 0));
+                } else {
+                    postorder[id] = post;
+                    post += 1;
+                }
+            }
+        }
+
+        DominatorTree {
+            root,
+            idom,
+            children,
+            predecessors,
+            reverse_postorder,
+            preorder,
+            postorder,
+        }
+    }
+
+    /// The entry block, or the virtual exit for post-dominators.
+    pub fn root(&self) -> BlockId {
+        self.root
+    }
+
+    /// `None` for the root and for blocks the analysis can't reach.
+    pub fn immediate_dominator(&self, id: BlockId) -> Option<BlockId> {
+        self.idom[id]
+    }
+
+    pub fn children(&self, id: BlockId) -> &[BlockId] {
+        &self.children[id]
+    }
+
+    pub fn is_reachable(&self, id: BlockId) -> bool {
+        self.preorder[id] != usize::MAX
+    }
+
+    /// Whether every path from the root to `b` goes through `a`. Every block
+    /// dominates itself.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        self.is_reachable(a)
+            && self.is_reachable(b)
+            && self.preorder[a] <= self.preorder[b]
+            && self.postorder[b] <= self.postorder[a]
+    }
+
+    pub fn strictly_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The reachable blocks in reverse postorder of the flow graph, so every
+    /// block comes before its successors apart from along back edges.
+    pub fn reverse_postorder(&self) -> &[BlockId] {
+        &self.reverse_postorder
+    }
+
+    /// The dominance frontier of every block: the blocks where its dominance
+    /// ends. For post-dominators these are the blocks it is control dependent
+    /// on.
+    pub fn frontiers(&self) -> Vec<BTreeSet<BlockId>> {
+        let mut frontiers = vec![BTreeSet::new(); self.idom.len()];
+        for (id, predecessors) in self.predecessors.iter().enumerate() {
+            if predecessors.len() < 2 || !self.is_reachable(id) {
+                continue;
+            }
+            for predecessor in predecessors {
+                let mut runner = *predecessor;
+                while self.is_reachable(runner) && Some(runner) != self.idom[id] {
+                    frontiers[runner].insert(id);
+                    match self.idom[runner] {
+                        Some(parent) => runner = parent,
+                        None => break,
+                    }
+                }
+            }
+        }
+        frontiers
+    }
+}
+
+fn intersect(idom: &[Option<BlockId>], order: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while order[a] > order[b] {
+            a = idom[a].expect("processed blocks have a dominator");
+        }
+        while order[b] > order[a] {
+            b = idom[b].expect("processed blocks have a dominator");
+        }
+    }
+    a
+}
+
+fn reverse_postorder(root: BlockId, successors: &[Vec<BlockId>]) -> Vec<BlockId> {
+    let mut visited = vec![false; successors.len()];
+    let mut postorder = Vec::with_capacity(successors.len());
+    let mut stack = vec![(root, 0)];
+    visited[root] = true;
+    while let Some((id, next)) = stack.pop() {
+        if let Some(successor) = successors[id].get(next) {
+            stack.push((id, next + 1));
+            if !visited[*successor] {
+                visited[*successor] = true;
+                stack.push((*successor, 0));
+            }
+        } else {
+            postorder.push(id);
+        }
+    }
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+    use crate::instruction::{Instruction, Opcode, Operand};
+
+    fn cfg(asm: &Assembler) -> Cfg {
+        let code = asm.assemble(0, 0).unwrap();
+        Cfg::from_code(&code.code, &code.exception_table).unwrap()
+    }
+
+    fn iinc(asm: &mut Assembler, value: i16) {
+        asm.emit(Instruction::new(
+            Opcode::Iinc,
+            Operand::Iinc { index: 0, value },
+        ));
+    }
+
+    // 0 branches to 1 or 2, which both go on to 3
+    fn diamond() -> Cfg {
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(0)));
+        asm.branch(Opcode::Ifeq, other);
+        iinc(&mut asm, 1);
+        asm.branch(Opcode::Goto, join);
+        asm.place(other);
+        iinc(&mut asm, -1);
+        asm.place(join);
+        asm.op(Opcode::Return);
+        cfg(&asm)
+    }
+
+    #[test]
+    fn diamonds_join_where_dominance_ends() {
+        let cfg = diamond();
+        let tree = DominatorTree::dominators(&cfg);
+        assert_eq!(tree.root(), 0);
+        let idoms: Vec<_> = (0..4).map(|id| tree.immediate_dominator(id)).collect();
+        assert_eq!(idoms, [None, Some(0), Some(0), Some(0)]);
+        assert!(tree.dominates(0, 3));
+        assert!(tree.dominates(3, 3));
+        assert!(!tree.strictly_dominates(3, 3));
+        assert!(!tree.dominates(1, 3));
+        assert_eq!(tree.reverse_postorder().first(), Some(&0));
+        assert_eq!(tree.reverse_postorder().last(), Some(&3));
+
+        let frontiers = tree.frontiers();
+        assert_eq!(
+            frontiers,
+            [
+                BTreeSet::new(),
+                BTreeSet::from([3]),
+                BTreeSet::from([3]),
+                BTreeSet::new()
+            ]
+        );
+
+        let post = DominatorTree::post_dominators(&cfg);
+        assert_eq!(post.root(), 4);
+        let ipdoms: Vec<_> = (0..4).map(|id| post.immediate_dominator(id)).collect();
+        assert_eq!(ipdoms, [Some(3), Some(3), Some(3), Some(4)]);
+        // each arm runs depending on the branch in 0
+        let dependences = post.frontiers();
+        assert_eq!(dependences[1], BTreeSet::from([0]));
+        assert_eq!(dependences[2], BTreeSet::from([0]));
+        assert!(dependences[3].is_empty());
+    }
+
+    #[test]
+    fn every_exit_leads_to_the_virtual_one() {
+        let mut asm = Assembler::new();
+        let throw = asm.new_label();
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(0)));
+        asm.branch(Opcode::Ifeq, throw);
+        asm.op(Opcode::Iconst0);
+        asm.op(Opcode::Ireturn);
+        asm.place(throw);
+        asm.op(Opcode::AconstNull);
+        asm.op(Opcode::Athrow);
+        let cfg = cfg(&asm);
+
+        let post = DominatorTree::post_dominators(&cfg);
+        assert_eq!(post.root(), 3);
+        let ipdoms: Vec<_> = (0..3).map(|id| post.immediate_dominator(id)).collect();
+        assert_eq!(ipdoms, [Some(3), Some(3), Some(3)]);
+        assert_eq!(post.immediate_dominator(3), None);
+        assert!(!post.strictly_dominates(1, 0));
+        let dependences = post.frontiers();
+        assert_eq!(dependences[1], BTreeSet::from([0]));
+        assert_eq!(dependences[2], BTreeSet::from([0]));
+    }
+
+    #[test]
+    fn infinite_loops_never_reach_the_exit() {
+        let mut asm = Assembler::new();
+        let spin = asm.new_label();
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(0)));
+        asm.branch(Opcode::Ifeq, spin);
+        asm.op(Opcode::Return);
+        asm.place(spin);
+        asm.branch(Opcode::Goto, spin);
+        let cfg = cfg(&asm);
+
+        let tree = DominatorTree::dominators(&cfg);
+        assert_eq!(tree.immediate_dominator(2), Some(0));
+
+        let post = DominatorTree::post_dominators(&cfg);
+        assert!(!post.is_reachable(2));
+        assert_eq!(post.immediate_dominator(2), None);
+        assert!(!post.dominates(2, 2));
+        // the only way out of 0 that ends goes through the return
+        assert_eq!(post.immediate_dominator(0), Some(1));
+        assert!(post.frontiers()[2].is_empty());
+    }
+
+    #[test]
+    fn unreached_blocks_have_no_dominator() {
+        let tree = DominatorTree::from_successors(0, vec![vec![1], vec![], vec![1]]);
+        assert!(tree.is_reachable(1));
+        assert!(!tree.is_reachable(2));
+        assert_eq!(tree.immediate_dominator(2), None);
+        assert_eq!(tree.children(0), [1]);
+        assert_eq!(tree.reverse_postorder(), [0, 1]);
+    }
+}
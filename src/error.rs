@@ -14,4 +14,46 @@ pub enum DecompileError {
     NoSuchConstantPoolEntry(u16, u64),
     #[error("invalid Constant_UTF8 at '{0}'")]
     InvalidUtf8ConstantPoolEntry(u16),
+    #[error("invalid modified UTF-8 at offset {0}")]
+    InvalidModifiedUtf8(u64),
+    #[error("invalid stack_map_frame type '{0}' at offset {1}")]
+    InvalidStackMapFrameType(u8, u64),
+    #[error("invalid verification_type_info tag '{0}' at offset {1}")]
+    InvalidVerificationTypeTag(u8, u64),
+    #[error("field has more than one ConstantValue attribute at offset {0}")]
+    DuplicateConstantValue(u64),
+    #[error("ConstantValue '{0}' at offset {1} is not a field value")]
+    InvalidConstantValue(u16, u64),
+    #[error("invalid type_annotation target_type '{0:#x}' at offset {1}")]
+    InvalidTypeAnnotationTarget(u8, u64),
+    #[error("invalid opcode '{0:#x}' at pc {1}")]
+    InvalidOpcode(u8, u32),
+    #[error("truncated instruction at pc {0}")]
+    TruncatedCode(u32),
+    #[error("invalid switch at pc {0}")]
+    InvalidSwitch(u32),
+    #[error("branch at pc {0} to {1} is out of range")]
+    BranchOutOfRange(u32, u32),
+    #[error("label {0} is used but never placed")]
+    UnboundLabel(usize),
+    #[error("code is {0} bytes, more than the 65535 allowed")]
+    CodeTooLarge(usize),
+    #[error("no such bootstrap method '{0}'")]
+    NoSuchBootstrapMethod(u16),
+    #[error("invalid descriptor '{0}'")]
+    InvalidDescriptor(String),
+    #[error("branch at pc {0} does not target the start of an instruction")]
+    InvalidBranchTarget(u32),
+    #[error("operand stack underflow at pc {0}")]
+    StackUnderflow(u32),
+    #[error("inconsistent stack height at pc {0}: {1} and {2}")]
+    InconsistentStackHeight(u32, u16, u16),
+    #[error("jsr/ret at pc {0} can't be given stack map frames")]
+    UnsupportedSubroutine(u32),
+    #[error("exception handlers at pc {0} and {1} cover overlapping code")]
+    OverlappingTry(u32, u32),
+    #[error("exception handler at pc {0} covers code with more than one entry")]
+    UnstructuredTry(u32),
+    #[error("monitor entered or exited at pc {0} outside a synchronized statement")]
+    UnstructuredMonitor(u32),
 }
@@ -0,0 +1,1127 @@
+use crate::cfg::{BlockId, EdgeKind};
+use crate::decompile::DecompileResult;
+use crate::dominators::DominatorTree;
+use crate::error::DecompileError;
+use crate::ir::{Block, Body, CompareOp, Const, Expr, Kind, Stmt, Terminator, Var, Variable};
+use crate::types::ConstantPool;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The code a `try` statement covers and the handlers it has.
+#[derive(Debug, Clone)]
+pub struct TryRegion {
+    /// The block the `try` starts with, which dominates the others.
+    pub entry: BlockId,
+    pub blocks: BTreeSet<BlockId>,
+    pub catches: Vec<CatchClause>,
+    pub finally: Option<Finally>,
+    /// The resource of a try-with-resources statement, which javac nests
+    /// one of for each resource.
+    pub resource: Option<Resource>,
+    /// The lock of a `synchronized` statement, which the region is instead
+    /// of a `try` statement.
+    pub lock: Option<Expr>,
+}
+
+/// A handler, with the classes it catches; more than one for a multi-catch.
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+    pub handler: BlockId,
+    /// Internal names, empty for a handler that catches everything.
+    pub types: Vec<String>,
+    /// The catch parameter.
+    pub var: Var,
+}
+
+/// A resource of a try-with-resources statement, with the value it is
+/// declared with, or none for a variable declared before.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    pub var: Var,
+    pub value: Option<Expr>,
+}
+
+/// The catch-any handler javac compiles a `finally` block into. Its code
+/// stores the exception, runs the block and throws the exception again.
+#[derive(Debug, Clone)]
+pub struct Finally {
+    pub handler: BlockId,
+    /// The block ending in the rethrow, where the `finally` block ends.
+    pub rethrow: BlockId,
+}
+
+/// Finds the `try` statements of a body out of SSA form, from the exception
+/// table entries its blocks are covered by.
+///
+/// Entries for the same handler are one `catch`, however many ranges javac
+/// split them into, and the classes of all of them are a multi-catch.
+/// Handlers covering the same blocks belong to the same `try`. A catch-any
+/// handler that rethrows after running some code is a `finally` block when
+/// every way out of the blocks it covers runs a copy of that code first, as
+/// javac compiles it; the copies are removed, leaving each jump going where
+/// its copy went. The statements storing the caught exceptions are removed
+/// too, their variables becoming the catch parameters.
+///
+/// A catch-any handler that only releases a monitor or closes a resource
+/// before rethrowing is the `synchronized` or try-with-resources statement
+/// javac compiles that way, and its copies of the release or close on the
+/// ways out are removed the same way. The older shapes of try-with-resources
+/// are `finally` blocks, left to [`structure`](crate::structure).
+///
+/// The regions are returned largest first, so one is always before the
+/// regions nested in it. A monitor entered or exited anywhere but in a
+/// `synchronized` statement is an error, as Java has no other way to.
+pub fn find_try_regions(
+    body: &mut Body,
+    constant_pool: &ConstantPool,
+) -> DecompileResult<Vec<TryRegion>> {
+    if body.cfg.exception_table().is_empty() {
+        check_monitors(body, &dominators(body, None))?;
+        return Ok(Vec::new());
+    }
+    let dominators = dominators(body, None);
+
+    // the handlers in exception table order, with what they cover
+    let mut handlers: Vec<(BlockId, Vec<String>, BTreeSet<BlockId>)> = Vec::new();
+    for entry in body.cfg.exception_table() {
+        let Some(handler) = body.cfg.block_at(entry.handler_pc as u32) else {
+            continue;
+        };
+        let index = match handlers.iter().position(|(id, ..)| *id == handler) {
+            Some(index) => index,
+            None => {
+                handlers.push((handler, Vec::new(), BTreeSet::new()));
+                handlers.len() - 1
+            }
+        };
+        if entry.catch_type != 0 {
+            let name = constant_pool
+                .class_name(entry.catch_type)
+                .unwrap_or("java/lang/Throwable")
+                .to_string();
+            if !handlers[index].1.contains(&name) {
+                handlers[index].1.push(name);
+            }
+        }
+    }
+    for id in 0..body.blocks.len() {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        for edge in body.cfg.successors(id) {
+            if let EdgeKind::Exceptional { .. } = edge.kind {
+                // javac can have a handler cover its own first instructions
+                if dominators.dominates(edge.to, id) {
+                    continue;
+                }
+                if let Some((.., blocks)) = handlers.iter_mut().find(|(h, ..)| *h == edge.to) {
+                    blocks.insert(id);
+                }
+            }
+        }
+    }
+    // a handler catching anything along with some classes catches anything
+    for (_, types, _) in &mut handlers {
+        if types.iter().any(|name| name == "java/lang/Throwable") {
+            types.clear();
+        }
+    }
+
+    let mut regions: Vec<TryRegion> = Vec::new();
+    for (handler, types, blocks) in handlers {
+        if blocks.is_empty() {
+            continue;
+        }
+        let catch = CatchClause {
+            handler,
+            types,
+            var: Var(usize::MAX),
+        };
+        match regions.iter_mut().find(|region| region.blocks == blocks) {
+            Some(region) => region.catches.push(catch),
+            None => regions.push(TryRegion {
+                entry: 0,
+                blocks,
+                catches: vec![catch],
+                finally: None,
+                resource: None,
+                lock: None,
+            }),
+        }
+    }
+
+    // inner `finally` blocks first, since their copies are inside the
+    // outer ones' and would be copied along with them
+    regions.sort_by_key(|region| region.blocks.len());
+    for region in &mut regions {
+        let dominators = self::dominators(body, None);
+        if !collapse_synchronized(body, &dominators, region)
+            && !collapse_resource(body, constant_pool, &dominators, region)
+        {
+            collapse_finally(body, &dominators, region);
+        }
+    }
+
+    // the copies are now unreachable, and so are the handlers of the
+    // regions that aren't `try` statements with handlers any more
+    let handlers: BTreeSet<BlockId> = regions
+        .iter()
+        .flat_map(|region| {
+            let catches = region.catches.iter().map(|catch| catch.handler);
+            catches.chain(region.finally.iter().map(|finally| finally.handler))
+        })
+        .collect();
+    let dominators = self::dominators(body, Some(&handlers));
+    check_monitors(body, &dominators)?;
+    for region in &mut regions {
+        region.blocks.retain(|id| dominators.is_reachable(*id));
+    }
+    regions.retain(|region| !region.blocks.is_empty());
+    regions.sort_by_key(|region| std::cmp::Reverse(region.blocks.len()));
+    let handler_pc = |region: &TryRegion| {
+        let handler = match (&region.finally, region.catches.first()) {
+            (Some(finally), _) => finally.handler,
+            (None, Some(catch)) => catch.handler,
+            (None, None) => *region.blocks.first().expect("regions have blocks"),
+        };
+        body.cfg.block(handler).start_pc
+    };
+    for (index, region) in regions.iter().enumerate() {
+        for outer in &regions[..index] {
+            if !region.blocks.is_disjoint(&outer.blocks) && !region.blocks.is_subset(&outer.blocks)
+            {
+                return Err(DecompileError::OverlappingTry(
+                    handler_pc(outer),
+                    handler_pc(region),
+                ));
+            }
+        }
+    }
+    let predecessors = predecessors(body, &dominators);
+    for region in &mut regions {
+        let entry = dominators
+            .reverse_postorder()
+            .iter()
+            .copied()
+            .find(|id| region.blocks.contains(id))
+            .expect("regions have reachable blocks");
+        let handlers = region
+            .catches
+            .iter()
+            .map(|catch| catch.handler)
+            .chain(region.finally.iter().map(|finally| finally.handler));
+        let single_entry = region
+            .blocks
+            .iter()
+            .all(|id| dominators.dominates(entry, *id));
+        // a handler is only entered by exceptions
+        let entered_by_jumps = handlers
+            .clone()
+            .any(|handler| !predecessors[handler].is_empty());
+        if !single_entry || entered_by_jumps {
+            return Err(DecompileError::UnstructuredTry(handler_pc(region)));
+        }
+        region.entry = entry;
+    }
+
+    for region in &mut regions {
+        for catch in &mut region.catches {
+            catch.var = catch_parameter(body, &dominators, catch.handler);
+        }
+        if let Some(finally) = &region.finally {
+            body.blocks[finally.handler].stmts.remove(0);
+        }
+    }
+    Ok(regions)
+}
+
+// Fails when a reachable block still enters or exits a monitor.
+fn check_monitors(body: &Body, dominators: &DominatorTree) -> DecompileResult<()> {
+    let unreleased = (0..body.blocks.len()).find(|id| {
+        dominators.is_reachable(*id)
+            && body.blocks[*id]
+                .stmts
+                .iter()
+                .any(|stmt| matches!(stmt, Stmt::MonitorEnter(_) | Stmt::MonitorExit(_)))
+    });
+    match unreleased {
+        Some(id) => Err(DecompileError::UnstructuredMonitor(
+            body.cfg.block(id).start_pc,
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The dominator tree of the blocks, with exceptions going to the handlers
+/// that catch them, or only to those of `handlers` if given.
+pub fn dominators(body: &Body, handlers: Option<&BTreeSet<BlockId>>) -> DominatorTree {
+    let successors = (0..body.blocks.len())
+        .map(|id| {
+            let mut successors = body.blocks[id].terminator.targets();
+            for edge in body.cfg.successors(id) {
+                if let EdgeKind::Exceptional { .. } = edge.kind {
+                    if handlers.is_none_or(|handlers| handlers.contains(&edge.to)) {
+                        successors.push(edge.to);
+                    }
+                }
+            }
+            successors
+        })
+        .collect();
+    DominatorTree::from_successors(0, successors)
+}
+
+// The reachable blocks jumping to each block.
+fn predecessors(body: &Body, dominators: &DominatorTree) -> Vec<Vec<BlockId>> {
+    let mut predecessors = vec![Vec::new(); body.blocks.len()];
+    for (id, block) in body.blocks.iter().enumerate() {
+        if dominators.is_reachable(id) {
+            for target in block.terminator.targets() {
+                if !predecessors[target].contains(&id) {
+                    predecessors[target].push(id);
+                }
+            }
+        }
+    }
+    predecessors
+}
+
+// The variable a handler's first statement stores the exception in. It
+// becomes the catch parameter, unless the code outside the catch block uses
+// it too, in which case the parameter is a new variable stored in it.
+fn catch_parameter(body: &mut Body, dominators: &DominatorTree, handler: BlockId) -> Var {
+    let Some(Stmt::Assign {
+        target: Expr::Var(var),
+        value: Expr::CaughtException,
+    }) = body.blocks[handler].stmts.first()
+    else {
+        let variable = caught_variable(body, None);
+        return body.new_variable(variable);
+    };
+    let var = *var;
+    let mut stores = 0;
+    let mut outside = false;
+    for (id, block) in body.blocks.iter().enumerate() {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        let inside = dominators.dominates(handler, id);
+        for stmt in &block.stmts {
+            stores += usize::from(stmt.defined_var() == Some(var));
+            outside |= !inside && stmt.uses().iter().any(|expr| expr.uses(var));
+        }
+        outside |= !inside && block.terminator.exprs().iter().any(|expr| expr.uses(var));
+    }
+    if stores == 1 && !outside {
+        body.blocks[handler].stmts.remove(0);
+        return var;
+    }
+    let variable = caught_variable(body, Some(var));
+    let parameter = body.new_variable(variable);
+    body.blocks[handler].stmts[0] = Stmt::assign(Expr::Var(var), Expr::Var(parameter));
+    parameter
+}
+
+fn caught_variable(body: &Body, like: Option<Var>) -> Variable {
+    let field_type = like.and_then(|var| body.variable(var).field_type.clone());
+    Variable {
+        kind: Kind::Reference,
+        slot: None,
+        name: None,
+        field_type,
+    }
+}
+
+// Turns a catch-any handler into a `finally` block if the code it runs
+// before rethrowing is copied onto every way out of the region. The handler
+// has no code of its own when the region has none, as javac leaves empty
+// `finally` blocks out.
+//
+// Other handlers covering the same blocks are `catch` clauses whose blocks
+// are only the copy and a jump, which javac doesn't cover with the catch-any
+// handler, like `catch (E e) { continue; }`; the copy starts after their
+// store of the exception.
+fn collapse_finally(body: &mut Body, dominators: &DominatorTree, region: &mut TryRegion) {
+    let Some((catch, typed)) = region.catches.split_last() else {
+        return;
+    };
+    if !catch.types.is_empty()
+        || typed
+            .iter()
+            .any(|typed| typed.types.is_empty() || region.blocks.contains(&typed.handler))
+    {
+        return;
+    }
+    let typed: Vec<BlockId> = typed.iter().map(|typed| typed.handler).collect();
+    let handler = catch.handler;
+    let Some(Stmt::Assign {
+        target: Expr::Var(caught),
+        value: Expr::CaughtException,
+    }) = body.blocks[handler].stmts.first()
+    else {
+        return;
+    };
+    let caught = *caught;
+
+    // the handler's code, with the handlers of any `try` in it, and the one
+    // block rethrowing the exception
+    let mut code = BTreeSet::from([handler]);
+    let mut worklist = vec![handler];
+    while let Some(id) = worklist.pop() {
+        let handlers = handlers(body, id).into_iter().map(|(_, handler)| handler);
+        for target in body.blocks[id]
+            .terminator
+            .targets()
+            .into_iter()
+            .chain(handlers)
+        {
+            if dominators.dominates(handler, target) && code.insert(target) {
+                worklist.push(target);
+            }
+        }
+    }
+    let rethrows: Vec<BlockId> = code
+        .iter()
+        .copied()
+        .filter(|id| body.blocks[*id].terminator == Terminator::Throw(Expr::Var(caught)))
+        .collect();
+    let [rethrow] = rethrows.as_slice() else {
+        return;
+    };
+    let stores_caught = code.iter().any(|id| {
+        body.blocks[*id]
+            .stmts
+            .iter()
+            .skip(usize::from(*id == handler))
+            .any(|stmt| stmt.defined_var() == Some(caught))
+    });
+    let has_code = code.iter().any(|id| {
+        let stmts = &body.blocks[*id].stmts;
+        stmts.len() > usize::from(*id == handler)
+            || !matches!(
+                body.blocks[*id].terminator,
+                Terminator::Goto(_) | Terminator::Throw(_)
+            )
+    });
+    if stores_caught || !has_code {
+        return;
+    }
+    region.blocks.retain(|id| !code.contains(id));
+
+    let predecessors = predecessors(body, dominators);
+    let exits: BTreeSet<BlockId> = region
+        .blocks
+        .iter()
+        .flat_map(|id| body.blocks[*id].terminator.targets())
+        .filter(|target| !region.blocks.contains(target) && !code.contains(target))
+        .collect();
+    let copies = Copies {
+        body,
+        handler,
+        rethrow: *rethrow,
+        code: &code,
+    };
+    let mut rewrites = Vec::new();
+    for exit in exits {
+        if predecessors[exit]
+            .iter()
+            .any(|id| !region.blocks.contains(id))
+        {
+            return;
+        }
+        match copies.find(exit, 0, &predecessors) {
+            Some(rest) => rewrites.push((exit, 0, rest)),
+            None => return,
+        }
+    }
+    for id in typed {
+        let stores_exception = matches!(
+            body.blocks[id].stmts.first(),
+            Some(Stmt::Assign {
+                value: Expr::CaughtException,
+                ..
+            })
+        );
+        match copies.find(id, 1, &predecessors) {
+            Some(rest) if stores_exception => rewrites.push((id, 1, rest)),
+            _ => return,
+        }
+    }
+
+    for (exit, kept, (stmts, terminator)) in rewrites {
+        match terminator {
+            // straight to where the copy went
+            Terminator::Goto(target) if kept == 0 && stmts.is_empty() => {
+                for id in &region.blocks {
+                    retarget(&mut body.blocks[*id].terminator, exit, target);
+                }
+            }
+            terminator => {
+                let block = &mut body.blocks[exit];
+                block.stmts.truncate(kept);
+                block.stmts.extend(stmts);
+                block.terminator = terminator;
+            }
+        }
+    }
+    region.finally = Some(Finally {
+        handler,
+        rethrow: *rethrow,
+    });
+    region.catches.pop();
+}
+
+// Turns a catch-any handler that releases a monitor and rethrows into the
+// `synchronized` statement javac compiles that way: the monitor is entered
+// just before the region with the lock kept in a variable, and every way
+// out of the region but a throw releases it last. A `return` inside the
+// statement leaves the region too, and the value it returns can have been
+// kept on the stack past the release, which lifting stores in a temporary
+// after it.
+// See https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-3.html#jvms-3.14
+fn collapse_synchronized(
+    body: &mut Body,
+    dominators: &DominatorTree,
+    region: &mut TryRegion,
+) -> bool {
+    let [catch] = region.catches.as_slice() else {
+        return false;
+    };
+    let handler = &body.blocks[catch.handler];
+    let [Stmt::Assign {
+        target: Expr::Var(caught),
+        value: Expr::CaughtException,
+    }, Stmt::MonitorExit(Expr::Var(lock))] = handler.stmts.as_slice()
+    else {
+        return false;
+    };
+    let lock = *lock;
+    let rethrow = match handler.terminator {
+        Terminator::Goto(next) => skip(body, next),
+        _ => catch.handler,
+    };
+    let rethrows = body.blocks[rethrow].terminator == Terminator::Throw(Expr::Var(*caught))
+        && (rethrow == catch.handler || body.blocks[rethrow].stmts.is_empty());
+    if !catch.types.is_empty() || !rethrows {
+        return false;
+    }
+
+    let predecessors = predecessors(body, dominators);
+    let [before] = entered_from(region, &predecessors)[..] else {
+        return false;
+    };
+    let value = match body.blocks[before].stmts.as_slice() {
+        [.., Stmt::Assign {
+            target: Expr::Var(var),
+            value,
+        }, Stmt::MonitorEnter(entered)]
+            if *var == lock && (entered == value || *entered == Expr::Var(lock)) =>
+        {
+            value.clone()
+        }
+        _ => return false,
+    };
+    if !matches!(body.blocks[before].terminator, Terminator::Goto(_)) {
+        return false;
+    }
+    let release = Stmt::MonitorExit(Expr::Var(lock));
+    let mut exits = Vec::new();
+    for id in &region.blocks {
+        let block = &body.blocks[*id];
+        let leaves = block
+            .terminator
+            .targets()
+            .iter()
+            .any(|target| !region.blocks.contains(target));
+        let released = block.stmts.iter().rposition(|stmt| *stmt == release);
+        let releases = released.is_some_and(|index| {
+            block.stmts[index + 1..].iter().all(|stmt| match stmt {
+                Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                } => {
+                    body.variable(*var).slot.is_none()
+                        && !value.has_side_effects()
+                        && !value.uses(lock)
+                }
+                _ => false,
+            })
+        });
+        match (&block.terminator, leaves, releases) {
+            (Terminator::Goto(_), true, true) | (Terminator::Return(_), false, true) => {
+                exits.push((*id, released.expect("the block releases the lock")))
+            }
+            (_, false, false) if released.is_none() => {}
+            _ => return false,
+        }
+    }
+    // the lock is kept for nothing else
+    let inside: usize = region
+        .blocks
+        .iter()
+        .map(|id| block_mentions(&body.blocks[*id], lock))
+        .sum();
+    let after = exits
+        .iter()
+        .flat_map(|(id, _)| body.blocks[*id].terminator.targets())
+        .map(|target| (target, 0))
+        .collect();
+    if inside != exits.len() || is_live(body, after, lock) {
+        return false;
+    }
+
+    let stmts = &mut body.blocks[before].stmts;
+    stmts.truncate(stmts.len() - 2);
+    for (id, released) in exits {
+        body.blocks[id].stmts.remove(released);
+    }
+    region.lock = Some(value);
+    region.catches.clear();
+    true
+}
+
+// Turns a catch-any handler that closes a resource and rethrows into the
+// try-with-resources statement javac 11 and later compile that way: the
+// handler closes the resource in a `try` of its own, adding what that
+// throws to the exception as suppressed, and every way out of the region
+// closes it first. Both check the resource for null first unless it's a
+// new object.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.20.3.1
+fn collapse_resource(
+    body: &mut Body,
+    constant_pool: &ConstantPool,
+    dominators: &DominatorTree,
+    region: &mut TryRegion,
+) -> bool {
+    let [catch] = region.catches.as_slice() else {
+        return false;
+    };
+    let handler = &body.blocks[catch.handler];
+    let [Stmt::Assign {
+        target: Expr::Var(caught),
+        value: Expr::CaughtException,
+    }] = handler.stmts.as_slice()
+    else {
+        return false;
+    };
+    let caught = *caught;
+    let (close, checked) = match &handler.terminator {
+        Terminator::Goto(next) => (skip(body, *next), None),
+        Terminator::If {
+            condition,
+            then,
+            otherwise,
+        } => match null_check(condition) {
+            Some((var, true)) => (*otherwise, Some((var, skip(body, *then)))),
+            Some((var, false)) => (*then, Some((var, skip(body, *otherwise)))),
+            None => return false,
+        },
+        _ => return false,
+    };
+    let resource = match body.blocks[close].stmts.as_slice() {
+        [Stmt::Expr(expr)] => closed(expr),
+        _ => None,
+    };
+    let (Some(resource), Terminator::Goto(next)) = (resource, &body.blocks[close].terminator)
+    else {
+        return false;
+    };
+    let rethrow = skip(body, *next);
+
+    // the handler of the close's own `try`, which the handler isn't in
+    let outer = handlers(body, catch.handler);
+    let inner: Vec<(u16, BlockId)> = handlers(body, close)
+        .into_iter()
+        .filter(|handler| !outer.contains(handler))
+        .collect();
+    let [(catch_type, suppress)] = inner[..] else {
+        return false;
+    };
+    let catches_anything =
+        catch_type == 0 || constant_pool.class_name(catch_type) == Some("java/lang/Throwable");
+    let suppresses = match body.blocks[suppress].stmts.as_slice() {
+        [Stmt::Assign {
+            target: Expr::Var(suppressed),
+            value: Expr::CaughtException,
+        }, Stmt::Expr(Expr::Invoke {
+            name,
+            object: Some(object),
+            args,
+            ..
+        })] => {
+            name == "addSuppressed"
+                && **object == Expr::Var(caught)
+                && args[..] == [Expr::Var(*suppressed)]
+        }
+        _ => false,
+    };
+    let suppresses = suppresses
+        && matches!(body.blocks[suppress].terminator,
+            Terminator::Goto(next) if skip(body, next) == rethrow);
+    let rethrows = body.blocks[rethrow].stmts.is_empty()
+        && body.blocks[rethrow].terminator == Terminator::Throw(Expr::Var(caught));
+    let checks = checked.is_none_or(|(var, null)| var == resource && null == rethrow);
+    if !catch.types.is_empty() || !catches_anything || !suppresses || !rethrows || !checks {
+        return false;
+    }
+
+    // every way out closes the resource, checking it the same way
+    let predecessors = predecessors(body, dominators);
+    let exits: BTreeSet<BlockId> = region
+        .blocks
+        .iter()
+        .flat_map(|id| body.blocks[*id].terminator.targets())
+        .filter(|target| !region.blocks.contains(target))
+        .collect();
+    let mut rewrites = Vec::new();
+    for exit in exits {
+        if predecessors[exit]
+            .iter()
+            .any(|id| !region.blocks.contains(id))
+        {
+            return false;
+        }
+        let block = &body.blocks[exit];
+        if checked.is_none() {
+            match block.stmts.first() {
+                Some(Stmt::Expr(expr)) if closed(expr) == Some(resource) => {}
+                _ => return false,
+            }
+            rewrites.push((exit, None));
+            continue;
+        }
+        // `if (r == null) goto after; r.close(); after:`
+        let Terminator::If {
+            condition,
+            then,
+            otherwise,
+        } = &block.terminator
+        else {
+            return false;
+        };
+        let (close, after) = match null_check(condition) {
+            Some((var, true)) if var == resource => (*otherwise, *then),
+            Some((var, false)) if var == resource => (*then, *otherwise),
+            _ => return false,
+        };
+        let closes = match body.blocks[close].stmts.as_slice() {
+            [Stmt::Expr(expr)] => closed(expr) == Some(resource),
+            _ => false,
+        };
+        if !block.stmts.is_empty()
+            || !closes
+            || body.blocks[close].terminator != Terminator::Goto(after)
+            || predecessors[close] != [exit]
+        {
+            return false;
+        }
+        rewrites.push((exit, Some(after)));
+    }
+
+    // the resource is declared just before the region, unless it's a
+    // variable declared earlier
+    let [before] = entered_from(region, &predecessors)[..] else {
+        return false;
+    };
+    let value = match (
+        body.blocks[before].stmts.last(),
+        &body.blocks[before].terminator,
+    ) {
+        (
+            Some(Stmt::Assign {
+                target: Expr::Var(var),
+                value,
+            }),
+            Terminator::Goto(_),
+        ) if *var == resource => Some(value.clone()),
+        _ => None,
+    };
+    if value.is_some() {
+        // which makes it a variable of the `try` statement's own
+        let stored = region.blocks.iter().any(|id| {
+            let stmts = &body.blocks[*id].stmts;
+            stmts
+                .iter()
+                .any(|stmt| stmt.defined_var() == Some(resource))
+        });
+        let after = rewrites
+            .iter()
+            .map(|(exit, after)| match after {
+                Some(after) => (*after, 0),
+                None => (*exit, 1),
+            })
+            .collect();
+        if stored || is_live(body, after, resource) {
+            return false;
+        }
+        body.blocks[before].stmts.pop();
+    }
+
+    for (exit, after) in rewrites {
+        let after = match after {
+            Some(after) => after,
+            None => {
+                let block = &mut body.blocks[exit];
+                block.stmts.remove(0);
+                match block.terminator {
+                    Terminator::Goto(next) if block.stmts.is_empty() => next,
+                    _ => continue,
+                }
+            }
+        };
+        for id in &region.blocks {
+            retarget(&mut body.blocks[*id].terminator, exit, after);
+        }
+    }
+    region.resource = Some(Resource {
+        var: resource,
+        value,
+    });
+    region.catches.clear();
+    true
+}
+
+/// The resource an expression closes.
+pub fn closed(expr: &Expr) -> Option<Var> {
+    match expr {
+        Expr::Invoke {
+            name,
+            descriptor,
+            object: Some(object),
+            ..
+        } if name == "close" && descriptor == "()V" => match **object {
+            Expr::Var(var) => Some(var),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The variable a condition compares with null, and whether it holds when
+/// the variable is null.
+pub fn null_check(condition: &Expr) -> Option<(Var, bool)> {
+    let Expr::Compare { op, left, right } = condition else {
+        return None;
+    };
+    let var = match (&**left, &**right) {
+        (Expr::Var(var), Expr::Const(Const::Null)) | (Expr::Const(Const::Null), Expr::Var(var)) => {
+            *var
+        }
+        _ => return None,
+    };
+    match op {
+        CompareOp::Eq => Some((var, true)),
+        CompareOp::Ne => Some((var, false)),
+        _ => None,
+    }
+}
+
+// The blocks outside a region that jump into it.
+fn entered_from(region: &TryRegion, predecessors: &[Vec<BlockId>]) -> Vec<BlockId> {
+    let outside: BTreeSet<BlockId> = region
+        .blocks
+        .iter()
+        .flat_map(|id| predecessors[*id].iter().copied())
+        .filter(|id| !region.blocks.contains(id))
+        .collect();
+    outside.into_iter().collect()
+}
+
+// The handlers covering a block, with what they catch.
+fn handlers(body: &Body, id: BlockId) -> Vec<(u16, BlockId)> {
+    body.cfg
+        .successors(id)
+        .filter_map(|edge| match edge.kind {
+            EdgeKind::Exceptional { catch_type } => Some((catch_type, edge.to)),
+            _ => None,
+        })
+        .collect()
+}
+
+// The first block from `id` on with code, past blocks that only jump.
+fn skip(body: &Body, id: BlockId) -> BlockId {
+    skip_while(body, id, |id| body.blocks[id].stmts.is_empty())
+}
+
+// The first block from `id` on that isn't a `goto` or that `only_jumps`
+// doesn't hold for. Gives up after going round a cycle of them.
+fn skip_while(body: &Body, mut id: BlockId, only_jumps: impl Fn(BlockId) -> bool) -> BlockId {
+    for _ in 0..body.blocks.len() {
+        match body.blocks[id].terminator {
+            Terminator::Goto(next) if only_jumps(id) => id = next,
+            _ => break,
+        }
+    }
+    id
+}
+
+// The number of statements and terminators of a block that mention a
+// variable.
+fn block_mentions(block: &Block, var: Var) -> usize {
+    let stmts = block
+        .stmts
+        .iter()
+        .filter(|stmt| stmt.exprs().iter().any(|expr| expr.uses(var)))
+        .count();
+    stmts + usize::from(block.terminator.exprs().iter().any(|expr| expr.uses(var)))
+}
+
+// Whether a variable can be read from any of the points on before it is
+// stored to again, a point being a block and the statement to start at.
+fn is_live(body: &Body, mut points: Vec<(BlockId, usize)>, var: Var) -> bool {
+    let mut visited = BTreeSet::new();
+    while let Some((id, start)) = points.pop() {
+        if start == 0 && !visited.insert(id) {
+            continue;
+        }
+        // an exception can be thrown before the store
+        points.extend(
+            handlers(body, id)
+                .into_iter()
+                .map(|(_, handler)| (handler, 0)),
+        );
+        let block = &body.blocks[id];
+        let mut stored = false;
+        for stmt in &block.stmts[start.min(block.stmts.len())..] {
+            if stmt.uses().iter().any(|expr| expr.uses(var)) {
+                return true;
+            }
+            if stmt.defined_var() == Some(var) {
+                stored = true;
+                break;
+            }
+        }
+        if stored {
+            continue;
+        }
+        if block.terminator.exprs().iter().any(|expr| expr.uses(var)) {
+            return true;
+        }
+        points.extend(
+            block
+                .terminator
+                .targets()
+                .into_iter()
+                .map(|target| (target, 0)),
+        );
+    }
+    false
+}
+
+fn retarget(terminator: &mut Terminator, from: BlockId, to: BlockId) {
+    let swap = |target: &mut BlockId| {
+        if *target == from {
+            *target = to;
+        }
+    };
+    match terminator {
+        Terminator::Goto(target) => swap(target),
+        Terminator::If {
+            then, otherwise, ..
+        } => {
+            swap(then);
+            swap(otherwise);
+        }
+        Terminator::Switch { cases, default, .. } => {
+            for (_, target) in cases {
+                swap(target);
+            }
+            swap(default);
+        }
+        Terminator::Return(_) | Terminator::Throw(_) | Terminator::Unreachable => {}
+    }
+}
+
+// Matches the code of a `finally` handler against the copies javac made of
+// it.
+struct Copies<'a> {
+    body: &'a Body,
+    handler: BlockId,
+    rethrow: BlockId,
+    code: &'a BTreeSet<BlockId>,
+}
+
+impl Copies<'_> {
+    // A block's statements, without the handler's store of the exception.
+    fn stmts(&self, id: BlockId) -> &[Stmt] {
+        let stmts = &self.body.blocks[id].stmts;
+        if id == self.handler {
+            &stmts[1..]
+        } else {
+            stmts
+        }
+    }
+
+    // The first block from `id` on with code, past blocks that only jump,
+    // stopping at the rethrow.
+    fn skip(&self, id: BlockId) -> BlockId {
+        skip_while(self.body, id, |id| {
+            self.stmts(id).is_empty() && id != self.rethrow
+        })
+    }
+
+    // The handlers of a block but the `finally` one, with what they catch.
+    fn handlers(&self, id: BlockId) -> Vec<(u16, BlockId)> {
+        let mut handlers = handlers(self.body, id);
+        handlers.retain(|(_, handler)| *handler != self.handler);
+        handlers
+    }
+
+    // Whether the code starting at `start`, after its first `skipped`
+    // statements, is a copy of the handler's. If it is, returns what comes
+    // after the copy's last statement: the rest of the statements of its
+    // last block and how that block ends.
+    fn find(
+        &self,
+        start: BlockId,
+        skipped: usize,
+        predecessors: &[Vec<BlockId>],
+    ) -> Option<(Vec<Stmt>, Terminator)> {
+        let mut vars = VarMap::default();
+        let mut blocks: HashMap<BlockId, BlockId> = HashMap::new();
+        let mut end = None;
+        let first = if skipped == 0 {
+            self.skip(start)
+        } else {
+            start
+        };
+        let mut worklist = vec![(self.skip(self.handler), first)];
+        while let Some((original, copy)) = worklist.pop() {
+            // only the first block of the copy starts after some statements
+            let offset = if copy == first { skipped } else { 0 };
+            if let Some(previous) = blocks.get(&original) {
+                if *previous != copy {
+                    return None;
+                }
+                continue;
+            }
+            blocks.insert(original, copy);
+            // code the handler shares with what follows, as after a `break`
+            // in the `finally` block
+            if !self.code.contains(&original) {
+                if original != copy {
+                    return None;
+                }
+                continue;
+            }
+            // a `try` in the `finally` block has its handlers copied too
+            let (a, b) = (self.handlers(original), self.handlers(copy));
+            if !a.iter().map(|(a, _)| a).eq(b.iter().map(|(b, _)| b)) {
+                return None;
+            }
+            worklist.extend(a.into_iter().zip(b).map(|((_, a), (_, b))| (a, b)));
+            let stmts = self.stmts(original);
+            let copied = self.body.blocks[copy]
+                .stmts
+                .get(offset..)
+                .unwrap_or_default();
+            if copied.len() < stmts.len()
+                || !stmts.iter().zip(copied).all(|(a, b)| vars.same_stmt(a, b))
+            {
+                return None;
+            }
+            if original == self.rethrow {
+                end = Some((copy, offset + stmts.len()));
+                continue;
+            }
+            if copied.len() != stmts.len() {
+                return None;
+            }
+            let (a, b) = (
+                &self.body.blocks[original].terminator,
+                &self.body.blocks[copy].terminator,
+            );
+            let same = match (a, b) {
+                (Terminator::Goto(_), Terminator::Goto(_)) => true,
+                (Terminator::If { condition: a, .. }, Terminator::If { condition: b, .. }) => {
+                    vars.same_expr(a, b)
+                }
+                (
+                    Terminator::Switch {
+                        value: a,
+                        cases: a_cases,
+                        ..
+                    },
+                    Terminator::Switch {
+                        value: b,
+                        cases: b_cases,
+                        ..
+                    },
+                ) => {
+                    vars.same_expr(a, b)
+                        && a_cases
+                            .iter()
+                            .map(|(key, _)| key)
+                            .eq(b_cases.iter().map(|(key, _)| key))
+                }
+                (Terminator::Return(None), Terminator::Return(None)) => true,
+                (Terminator::Return(Some(a)), Terminator::Return(Some(b)))
+                | (Terminator::Throw(a), Terminator::Throw(b)) => vars.same_expr(a, b),
+                _ => false,
+            };
+            if !same {
+                return None;
+            }
+            for (a, b) in a.targets().into_iter().zip(b.targets()) {
+                worklist.push((self.skip(a), self.skip(b)));
+            }
+        }
+
+        // the copy's last block can't be shared with other code
+        let (last, copied) = end?;
+        let copies: BTreeSet<BlockId> = blocks.values().copied().collect();
+        if last != start && !predecessors[last].iter().all(|id| copies.contains(id)) {
+            return None;
+        }
+        let block = &self.body.blocks[last];
+        Some((block.stmts[copied..].to_vec(), block.terminator.clone()))
+    }
+}
+
+// Which variables of a `finally` handler are which of a copy of it.
+#[derive(Default)]
+struct VarMap {
+    forward: BTreeMap<Var, Var>,
+    backward: BTreeMap<Var, Var>,
+}
+
+impl VarMap {
+    fn same_stmt(&mut self, a: &Stmt, b: &Stmt) -> bool {
+        std::mem::discriminant(a) == std::mem::discriminant(b)
+            && a.exprs()
+                .into_iter()
+                .zip(b.exprs())
+                .all(|(a, b)| self.same_expr(a, b))
+    }
+
+    // The expressions are the same but for the variables, which are paired
+    // up consistently.
+    fn same_expr(&mut self, a: &Expr, b: &Expr) -> bool {
+        let vars = |expr: &Expr| {
+            let mut vars = Vec::new();
+            expr.walk(&mut |expr| {
+                if let Expr::Var(var) = expr {
+                    vars.push(*var);
+                }
+            });
+            vars
+        };
+        let (a_vars, b_vars) = (vars(a), vars(b));
+        if a_vars.len() != b_vars.len() {
+            return false;
+        }
+        for (a, b) in a_vars.into_iter().zip(b_vars) {
+            let forward = *self.forward.entry(a).or_insert(b);
+            let backward = *self.backward.entry(b).or_insert(a);
+            if forward != b || backward != a {
+                return false;
+            }
+        }
+        let mut renamed = a.clone();
+        renamed.walk_mut(&mut |expr| {
+            if let Expr::Var(var) = expr {
+                *var = self.forward[var];
+            }
+        });
+        renamed == *b
+    }
+}
@@ -0,0 +1,1189 @@
+use crate::builder::{Code, ConstantPoolBuilder};
+use crate::decompile::DecompileResult;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::DecompileError;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::types::{
+    Attribute, ClassFile, ConstantPool, ConstantPoolType, ExceptionTable, StackMapFrame,
+    VerificationTypeInfo, ACC_INTERFACE,
+};
+use std::collections::{BTreeSet, HashMap};
+
+const OBJECT: &str = "java/lang/Object";
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// Answers the one question about the class hierarchy that frame computation
+/// needs: which class two reference types merge to where control flow joins.
+///
+/// Any `Fn(&str, &str) -> String` can be used as a hierarchy, which makes it
+/// easy to plug in a class path lookup.
+pub trait ClassHierarchy {
+    /// The closest common superclass of two classes, given by internal name.
+    /// Interfaces merge to `java/lang/Object`, as the verifier treats them.
+    fn common_superclass(&self, a: &str, b: &str) -> String;
+}
+
+impl<F: Fn(&str, &str) -> String> ClassHierarchy for F {
+    fn common_superclass(&self, a: &str, b: &str) -> String {
+        self(a, b)
+    }
+}
+
+/// A [`ClassHierarchy`] built from the super classes of known classes.
+/// Classes it doesn't know are taken to extend `java/lang/Object` directly.
+#[derive(Debug, Clone, Default)]
+pub struct SuperclassMap {
+    // internal name -> (super class, is interface)
+    classes: HashMap<String, (Option<String>, bool)>,
+}
+
+impl SuperclassMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, super_name: Option<&str>, is_interface: bool) {
+        self.classes.insert(
+            name.to_string(),
+            (super_name.map(str::to_string), is_interface),
+        );
+    }
+
+    pub fn add_class_file(&mut self, class_file: &ClassFile) {
+        if let Some(name) = class_file.this_class_name() {
+            self.add(
+                name,
+                class_file.super_class_name(),
+                class_file.access_flags & ACC_INTERFACE != 0,
+            );
+        }
+    }
+
+    fn is_interface(&self, name: &str) -> bool {
+        self.classes
+            .get(name)
+            .is_some_and(|(_, interface)| *interface)
+    }
+
+    // the class followed by all its known super classes
+    fn superclasses<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        let mut chain = vec![name];
+        let mut current = name;
+        while let Some((Some(super_name), _)) = self.classes.get(current) {
+            // guard against cycles in hand-built maps
+            if chain.contains(&super_name.as_str()) {
+                break;
+            }
+            chain.push(super_name);
+            current = super_name;
+        }
+        chain
+    }
+}
+
+impl ClassHierarchy for SuperclassMap {
+    fn common_superclass(&self, a: &str, b: &str) -> String {
+        if a == b {
+            return a.to_string();
+        }
+        if self.is_interface(a) || self.is_interface(b) {
+            return OBJECT.to_string();
+        }
+
+        let ancestors = self.superclasses(a);
+        self.superclasses(b)
+            .into_iter()
+            .find(|class| ancestors.contains(class))
+            .unwrap_or(OBJECT)
+            .to_string()
+    }
+}
+
+// The verification type of one local variable or operand stack slot. Long and
+// double take two slots, the second of which is `Top`.
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.10.1.2
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Type {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    UninitializedThis,
+    // created by the `new` at this pc
+    Uninitialized(u32),
+    // internal name for classes, descriptor for arrays
+    Object(String),
+}
+
+impl Type {
+    fn from_field_type(field_type: &FieldType) -> Type {
+        match field_type {
+            FieldType::Boolean
+            | FieldType::Byte
+            | FieldType::Char
+            | FieldType::Short
+            | FieldType::Int => Type::Integer,
+            FieldType::Float => Type::Float,
+            FieldType::Long => Type::Long,
+            FieldType::Double => Type::Double,
+            FieldType::Object(name) => Type::Object(name.clone()),
+            FieldType::Array(_) => Type::Object(field_type.to_string()),
+        }
+    }
+
+    fn from_descriptor(descriptor: &str) -> DecompileResult<Type> {
+        FieldType::parse(descriptor)
+            .map(|field_type| Type::from_field_type(&field_type))
+            .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))
+    }
+
+    fn is_wide(&self) -> bool {
+        matches!(self, Type::Long | Type::Double)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Frame {
+    locals: Vec<Type>,
+    stack: Vec<Type>,
+}
+
+impl Frame {
+    fn push(&mut self, value: Type) {
+        let wide = value.is_wide();
+        self.stack.push(value);
+        if wide {
+            self.stack.push(Type::Top);
+        }
+    }
+
+    fn pop(&mut self, slots: u16, pc: u32) -> DecompileResult<()> {
+        for _ in 0..slots {
+            self.pop_slot(pc)?;
+        }
+        Ok(())
+    }
+
+    fn pop_slot(&mut self, pc: u32) -> DecompileResult<Type> {
+        self.stack.pop().ok_or(DecompileError::StackUnderflow(pc))
+    }
+
+    fn set_local(&mut self, index: u16, value: Type) {
+        let index = index as usize;
+        let needed = index + if value.is_wide() { 2 } else { 1 };
+        if self.locals.len() < needed {
+            self.locals.resize(needed, Type::Top);
+        }
+        // overwriting the second half of a long or double invalidates it
+        if index > 0 && self.locals[index - 1].is_wide() {
+            self.locals[index - 1] = Type::Top;
+        }
+        if value.is_wide() {
+            self.locals[index + 1] = Type::Top;
+        }
+        self.locals[index] = value;
+    }
+
+    // Replaces an uninitialized type once its constructor has run.
+    fn initialize(&mut self, uninitialized: &Type, initialized: Type) {
+        for value in self.locals.iter_mut().chain(self.stack.iter_mut()) {
+            if value == uninitialized {
+                *value = initialized.clone();
+            }
+        }
+    }
+}
+
+/// Computes the `StackMapTable` of `code` and stores it in `code.attributes`,
+/// replacing any existing one, much like ASM's `COMPUTE_FRAMES`.
+///
+/// A frame is written at every branch target and exception handler. Unreachable
+/// code gets no types to check against, so, like ASM, it is replaced with
+/// `nop`s ending in an `athrow` and taken out of the exception table ranges.
+pub fn compute_frames(
+    code: &mut Code,
+    class_name: &str,
+    method_name: &str,
+    descriptor: &str,
+    is_static: bool,
+    pool: &mut ConstantPoolBuilder,
+    hierarchy: &dyn ClassHierarchy,
+) -> DecompileResult<()> {
+    let method = MethodDescriptor::parse(descriptor)
+        .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+
+    let mut initial = Frame {
+        locals: Vec::new(),
+        stack: Vec::new(),
+    };
+    if !is_static {
+        let this = if method_name == "<init>" && class_name != OBJECT {
+            Type::UninitializedThis
+        } else {
+            Type::Object(class_name.to_string())
+        };
+        initial.locals.push(this);
+    }
+    for parameter in &method.parameters {
+        let index = initial.locals.len() as u16;
+        initial.set_local(index, Type::from_field_type(parameter));
+    }
+    if initial.locals.len() < code.max_locals as usize {
+        initial.locals.resize(code.max_locals as usize, Type::Top);
+    }
+
+    let instructions = instruction::decode(&code.code)?;
+    let analysis = Analysis {
+        instructions: &instructions,
+        exception_table: &code.exception_table,
+        class_name,
+        pool: pool.pool(),
+        hierarchy,
+    };
+    let frames = analysis.run(initial.clone())?;
+
+    // frames are needed wherever control arrives other than by falling through
+    let mut frame_pcs = BTreeSet::new();
+    for ((_, instruction), frame) in instructions.iter().zip(&frames) {
+        if frame.is_some() {
+            frame_pcs.extend(instruction.targets().into_iter().copied());
+        }
+    }
+    for entry in &code.exception_table {
+        frame_pcs.insert(entry.handler_pc as u32);
+    }
+
+    // replace each run of unreachable code with nop ... athrow
+    let mut dead = Vec::new();
+    let mut i = 0;
+    while i < instructions.len() {
+        if frames[i].is_some() {
+            i += 1;
+            continue;
+        }
+        let start = instructions[i].0;
+        while i < instructions.len() && frames[i].is_none() {
+            i += 1;
+        }
+        let end = instructions
+            .get(i)
+            .map_or(code.code.len() as u32, |(pc, _)| *pc);
+        code.code[start as usize..end as usize].fill(Opcode::Nop as u8);
+        code.code[end as usize - 1] = Opcode::Athrow as u8;
+        dead.push((start, end));
+    }
+
+    let dead_frame = Frame {
+        locals: Vec::new(),
+        stack: vec![Type::Object(THROWABLE.to_string())],
+    };
+    let mut pc_frames: Vec<(u32, &Frame)> = Vec::new();
+    for pc in frame_pcs {
+        let Ok(i) = instructions.binary_search_by_key(&pc, |(pc, _)| *pc) else {
+            return Err(DecompileError::InvalidBranchTarget(pc));
+        };
+        if let Some(frame) = &frames[i] {
+            pc_frames.push((pc, frame));
+        }
+    }
+    pc_frames.extend(dead.iter().map(|(start, _)| (*start, &dead_frame)));
+    pc_frames.sort_by_key(|(pc, _)| *pc);
+
+    code.exception_table = remove_dead_ranges(&code.exception_table, &dead);
+
+    let mut entries = Vec::with_capacity(pc_frames.len());
+    let mut previous_locals = verification_types(&initial.locals, true, pool);
+    let mut previous_pc = None;
+    for (pc, frame) in pc_frames {
+        let offset_delta = match previous_pc {
+            None => pc,
+            Some(previous) => pc - previous - 1,
+        } as u16;
+        let locals = verification_types(&frame.locals, true, pool);
+        let stack = verification_types(&frame.stack, false, pool);
+        entries.push(compress(offset_delta, &previous_locals, &locals, stack));
+        previous_locals = locals;
+        previous_pc = Some(pc);
+    }
+
+    code.attributes
+        .retain(|attr| !matches!(attr, Attribute::StackMapTable { .. }));
+    if !entries.is_empty() {
+        let mut attr = Attribute::StackMapTable {
+            attribute_name_index: pool.utf8("StackMapTable"),
+            attribute_length: 0,
+            number_of_entries: entries.len() as u16,
+            entries,
+        };
+        crate::builder::set_attribute_length(&mut attr);
+        code.attributes.insert(0, attr);
+    }
+
+    Ok(())
+}
+
+/// Recomputes the frames of every method in a class of version 50 or later,
+/// for use after its code has been changed.
+pub fn recompute_frames(
+    class_file: &mut ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+) -> DecompileResult<()> {
+    if class_file.major_version < 50 {
+        return Ok(());
+    }
+
+    let class_name = class_file
+        .this_class_name()
+        .ok_or(DecompileError::NoSuchConstantPoolEntry(
+            class_file.this_class,
+            0,
+        ))?
+        .to_string();
+    let mut pool = ConstantPoolBuilder::from_class_file(class_file);
+    for method in &mut class_file.methods {
+        let Some(position) = method
+            .attributes
+            .iter()
+            .position(|attr| matches!(attr, Attribute::Code { .. }))
+        else {
+            continue;
+        };
+        let name = pool
+            .pool()
+            .utf8(method.name_index)
+            .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+                method.name_index,
+            ))?
+            .to_string();
+        let descriptor = pool
+            .pool()
+            .utf8(method.descriptor_index)
+            .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+                method.descriptor_index,
+            ))?
+            .to_string();
+
+        let mut code = Code::from_attribute(method.attributes[position].clone())
+            .expect("found a Code attribute above");
+        compute_frames(
+            &mut code,
+            &class_name,
+            &name,
+            &descriptor,
+            method.is_static(),
+            &mut pool,
+            hierarchy,
+        )?;
+        method.attributes[position] = code.into_attribute(&mut pool);
+    }
+    class_file.set_constant_pool(pool.build());
+
+    Ok(())
+}
+
+struct Analysis<'a> {
+    instructions: &'a [(u32, Instruction)],
+    exception_table: &'a [ExceptionTable],
+    class_name: &'a str,
+    pool: &'a ConstantPool,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl Analysis<'_> {
+    // The frame on entry to every instruction, `None` for unreachable ones.
+    fn run(&self, initial: Frame) -> DecompileResult<Vec<Option<Frame>>> {
+        let mut frames: Vec<Option<Frame>> = vec![None; self.instructions.len()];
+        let mut worklist = Vec::new();
+        if self.instructions.is_empty() {
+            return Ok(frames);
+        }
+        frames[0] = Some(initial);
+        worklist.push(0);
+
+        while let Some(i) = worklist.pop() {
+            let (pc, instruction) = &self.instructions[i];
+            let before = frames[i]
+                .clone()
+                .expect("only visited instructions are queued");
+            let after = self.execute(*pc, instruction, &before)?;
+
+            for entry in self.exception_table {
+                if !(entry.start_pc as u32..entry.end_pc as u32).contains(pc) {
+                    continue;
+                }
+                let catch_type = match entry.catch_type {
+                    0 => THROWABLE,
+                    index => self
+                        .pool
+                        .class_name(index)
+                        .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?,
+                };
+                let handler = self.index_of(entry.handler_pc as u32)?;
+                // the exception can be thrown before or after the locals are
+                // written
+                for locals in [&before.locals, &after.locals] {
+                    let frame = Frame {
+                        locals: locals.clone(),
+                        stack: vec![Type::Object(catch_type.to_string())],
+                    };
+                    self.merge_into(&mut frames, &mut worklist, handler, frame)?;
+                }
+            }
+
+            for target in instruction.targets() {
+                let target = self.index_of(*target)?;
+                self.merge_into(&mut frames, &mut worklist, target, after.clone())?;
+            }
+            if !instruction.opcode.ends_flow() && i + 1 < self.instructions.len() {
+                self.merge_into(&mut frames, &mut worklist, i + 1, after)?;
+            }
+        }
+
+        Ok(frames)
+    }
+
+    fn index_of(&self, pc: u32) -> DecompileResult<usize> {
+        self.instructions
+            .binary_search_by_key(&pc, |(pc, _)| *pc)
+            .map_err(|_| DecompileError::InvalidBranchTarget(pc))
+    }
+
+    fn merge_into(
+        &self,
+        frames: &mut [Option<Frame>],
+        worklist: &mut Vec<usize>,
+        i: usize,
+        incoming: Frame,
+    ) -> DecompileResult<()> {
+        let merged = match &frames[i] {
+            None => incoming,
+            Some(existing) => {
+                if existing.stack.len() != incoming.stack.len() {
+                    return Err(DecompileError::InconsistentStackHeight(
+                        self.instructions[i].0,
+                        existing.stack.len() as u16,
+                        incoming.stack.len() as u16,
+                    ));
+                }
+                let locals_len = existing.locals.len().min(incoming.locals.len());
+                let merged = Frame {
+                    locals: (0..locals_len)
+                        .map(|n| self.merge(&existing.locals[n], &incoming.locals[n]))
+                        .collect(),
+                    stack: existing
+                        .stack
+                        .iter()
+                        .zip(&incoming.stack)
+                        .map(|(a, b)| self.merge(a, b))
+                        .collect(),
+                };
+                if &merged == existing {
+                    return Ok(());
+                }
+                merged
+            }
+        };
+
+        frames[i] = Some(merged);
+        worklist.push(i);
+        Ok(())
+    }
+
+    fn merge(&self, a: &Type, b: &Type) -> Type {
+        match (a, b) {
+            _ if a == b => a.clone(),
+            (Type::Null, Type::Object(_)) => b.clone(),
+            (Type::Object(_), Type::Null) => a.clone(),
+            (Type::Object(a), Type::Object(b)) => Type::Object(self.merge_reference(a, b)),
+            _ => Type::Top,
+        }
+    }
+
+    fn merge_reference(&self, a: &str, b: &str) -> String {
+        match (a.strip_prefix('['), b.strip_prefix('[')) {
+            (Some(a), Some(b)) => match (component_class(a), component_class(b)) {
+                (Some(a), Some(b)) => array_of(&self.merge_reference(a, b)),
+                // arrays of different primitives, or of a primitive and a
+                // reference
+                _ => OBJECT.to_string(),
+            },
+            (None, None) => self.hierarchy.common_superclass(a, b),
+            _ => OBJECT.to_string(),
+        }
+    }
+
+    fn class_name(&self, index: u16) -> DecompileResult<&str> {
+        self.pool
+            .class_name(index)
+            .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))
+    }
+
+    // The frame after `instruction` runs.
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-6.html#jvms-6.5
+    fn execute(&self, pc: u32, instruction: &Instruction, frame: &Frame) -> DecompileResult<Frame> {
+        use Opcode::*;
+
+        let mut frame = frame.clone();
+        let local = instruction.local_index().unwrap_or(0);
+        let index = instruction.constant_index().unwrap_or(0);
+
+        match instruction.opcode {
+            Nop | Goto | GotoW | Return | Iinc => {}
+            AconstNull => frame.push(Type::Null),
+            IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 | Bipush
+            | Sipush => frame.push(Type::Integer),
+            Lconst0 | Lconst1 => frame.push(Type::Long),
+            Fconst0 | Fconst1 | Fconst2 => frame.push(Type::Float),
+            Dconst0 | Dconst1 => frame.push(Type::Double),
+            Ldc | LdcW | Ldc2W => {
+                let value = match self.pool.entry(index) {
+                    Some(ConstantPoolType::ConstantInteger { .. }) => Type::Integer,
+                    Some(ConstantPoolType::ConstantFloat { .. }) => Type::Float,
+                    Some(ConstantPoolType::ConstantLong { .. }) => Type::Long,
+                    Some(ConstantPoolType::ConstantDouble { .. }) => Type::Double,
+                    Some(ConstantPoolType::ConstantString { .. }) => {
+                        Type::Object("java/lang/String".to_string())
+                    }
+                    Some(ConstantPoolType::ConstantClass { .. }) => {
+                        Type::Object("java/lang/Class".to_string())
+                    }
+                    Some(ConstantPoolType::ConstantMethodType { .. }) => {
+                        Type::Object("java/lang/invoke/MethodType".to_string())
+                    }
+                    Some(ConstantPoolType::ConstantMethodHandle { .. }) => {
+                        Type::Object("java/lang/invoke/MethodHandle".to_string())
+                    }
+                    Some(ConstantPoolType::ConstantDynamic { .. }) => {
+                        let (_, _, descriptor) = self
+                            .pool
+                            .dynamic(index)
+                            .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                        Type::from_descriptor(descriptor)?
+                    }
+                    _ => return Err(DecompileError::NoSuchConstantPoolEntry(index, 0)),
+                };
+                frame.push(value);
+            }
+            Iload => frame.push(Type::Integer),
+            Lload => frame.push(Type::Long),
+            Fload => frame.push(Type::Float),
+            Dload => frame.push(Type::Double),
+            Aload => {
+                let value = frame
+                    .locals
+                    .get(local as usize)
+                    .cloned()
+                    .unwrap_or(Type::Top);
+                frame.push(value);
+            }
+            Iaload | Baload | Caload | Saload => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Integer);
+            }
+            Laload => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Long);
+            }
+            Faload => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Float);
+            }
+            Daload => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Double);
+            }
+            Aaload => {
+                frame.pop_slot(pc)?;
+                let component = match frame.pop_slot(pc)? {
+                    Type::Object(array) => match array.strip_prefix('[') {
+                        Some(component) => Type::from_descriptor(component)?,
+                        None => Type::Object(OBJECT.to_string()),
+                    },
+                    _ => Type::Null,
+                };
+                frame.push(component);
+            }
+            Istore | Fstore | Astore => {
+                let value = frame.pop_slot(pc)?;
+                frame.set_local(local, value);
+            }
+            Lstore | Dstore => {
+                frame.pop_slot(pc)?;
+                let value = frame.pop_slot(pc)?;
+                frame.set_local(local, value);
+            }
+            Iastore | Fastore | Aastore | Bastore | Castore | Sastore => frame.pop(3, pc)?,
+            Lastore | Dastore => frame.pop(4, pc)?,
+            Pop | Ifeq | Ifne | Iflt | Ifge | Ifgt | Ifle | Ifnull | Ifnonnull | Tableswitch
+            | Lookupswitch | Ireturn | Freturn | Areturn | Athrow | Monitorenter | Monitorexit => {
+                frame.pop(1, pc)?
+            }
+            Pop2 | IfIcmpeq | IfIcmpne | IfIcmplt | IfIcmpge | IfIcmpgt | IfIcmple | IfAcmpeq
+            | IfAcmpne | Lreturn | Dreturn => frame.pop(2, pc)?,
+            Dup | DupX1 | DupX2 | Dup2 | Dup2X1 | Dup2X2 | Swap => {
+                // these move slots around without looking at their types
+                let (taken, layout): (usize, &[usize]) = match instruction.opcode {
+                    Dup => (1, &[0, 0]),
+                    DupX1 => (2, &[1, 0, 1]),
+                    DupX2 => (3, &[2, 0, 1, 2]),
+                    Dup2 => (2, &[0, 1, 0, 1]),
+                    Dup2X1 => (3, &[1, 2, 0, 1, 2]),
+                    Dup2X2 => (4, &[2, 3, 0, 1, 2, 3]),
+                    _ => (2, &[1, 0]),
+                };
+                if frame.stack.len() < taken {
+                    return Err(DecompileError::StackUnderflow(pc));
+                }
+                let top = frame.stack.split_off(frame.stack.len() - taken);
+                frame
+                    .stack
+                    .extend(layout.iter().map(|slot| top[*slot].clone()));
+            }
+            Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor | Fcmpl
+            | Fcmpg => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Integer);
+            }
+            Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor => {
+                frame.pop(4, pc)?;
+                frame.push(Type::Long);
+            }
+            Lshl | Lshr | Lushr => {
+                frame.pop(3, pc)?;
+                frame.push(Type::Long);
+            }
+            Fadd | Fsub | Fmul | Fdiv | Frem => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Float);
+            }
+            Dadd | Dsub | Dmul | Ddiv | Drem => {
+                frame.pop(4, pc)?;
+                frame.push(Type::Double);
+            }
+            Lcmp | Dcmpl | Dcmpg => {
+                frame.pop(4, pc)?;
+                frame.push(Type::Integer);
+            }
+            Ineg | I2b | I2c | I2s | Arraylength | Instanceof => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Integer);
+            }
+            F2i => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Integer);
+            }
+            L2i | D2i => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Integer);
+            }
+            Lneg | D2l => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Long);
+            }
+            I2l | F2l => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Long);
+            }
+            Fneg | I2f => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Float);
+            }
+            L2f | D2f => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Float);
+            }
+            Dneg | L2d => {
+                frame.pop(2, pc)?;
+                frame.push(Type::Double);
+            }
+            I2d | F2d => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Double);
+            }
+            Getstatic | Putstatic | Getfield | Putfield => {
+                let member = self
+                    .pool
+                    .member_ref(index)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                let value = Type::from_descriptor(member.descriptor)?;
+                let slots = if value.is_wide() { 2 } else { 1 };
+                match instruction.opcode {
+                    Getstatic => frame.push(value),
+                    Putstatic => frame.pop(slots, pc)?,
+                    Getfield => {
+                        frame.pop(1, pc)?;
+                        frame.push(value);
+                    }
+                    _ => frame.pop(slots + 1, pc)?,
+                }
+            }
+            Invokevirtual | Invokespecial | Invokestatic | Invokeinterface | Invokedynamic => {
+                let (name, descriptor) = if instruction.opcode == Invokedynamic {
+                    let (_, name, descriptor) = self
+                        .pool
+                        .dynamic(index)
+                        .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                    (name, descriptor)
+                } else {
+                    let member = self
+                        .pool
+                        .member_ref(index)
+                        .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                    (member.name, member.descriptor)
+                };
+                let method = MethodDescriptor::parse(descriptor)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+                frame.pop(method.parameter_slots(), pc)?;
+                if !matches!(instruction.opcode, Invokestatic | Invokedynamic) {
+                    let receiver = frame.pop_slot(pc)?;
+                    if instruction.opcode == Invokespecial && name == "<init>" {
+                        let initialized = match &receiver {
+                            Type::UninitializedThis => self.class_name.to_string(),
+                            Type::Uninitialized(new_pc) => {
+                                let new = self.index_of(*new_pc)?;
+                                let new_index =
+                                    self.instructions[new].1.constant_index().unwrap_or(0);
+                                self.class_name(new_index)?.to_string()
+                            }
+                            _ => String::new(),
+                        };
+                        if !initialized.is_empty() {
+                            frame.initialize(&receiver, Type::Object(initialized));
+                        }
+                    }
+                }
+                if let Some(return_type) = &method.return_type {
+                    frame.push(Type::from_field_type(return_type));
+                }
+            }
+            New => frame.push(Type::Uninitialized(pc)),
+            Newarray => {
+                frame.pop(1, pc)?;
+                let component = match instruction.operand {
+                    Operand::ArrayType(4) => "Z",
+                    Operand::ArrayType(5) => "C",
+                    Operand::ArrayType(6) => "F",
+                    Operand::ArrayType(7) => "D",
+                    Operand::ArrayType(8) => "B",
+                    Operand::ArrayType(9) => "S",
+                    Operand::ArrayType(10) => "I",
+                    _ => "J",
+                };
+                frame.push(Type::Object(format!("[{component}")));
+            }
+            Anewarray => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Object(array_of(self.class_name(index)?)));
+            }
+            Checkcast => {
+                frame.pop(1, pc)?;
+                frame.push(Type::Object(self.class_name(index)?.to_string()));
+            }
+            Multianewarray => {
+                let Operand::MultiANewArray { dimensions, .. } = instruction.operand else {
+                    unreachable!("multianewarray always has dimensions");
+                };
+                frame.pop(dimensions as u16, pc)?;
+                frame.push(Type::Object(self.class_name(index)?.to_string()));
+            }
+            // subroutines were removed from class files that carry frames
+            Jsr | JsrW | Ret => return Err(DecompileError::UnsupportedSubroutine(pc)),
+            _ => unreachable!("instruction::decode folds short and wide forms"),
+        }
+
+        Ok(frame)
+    }
+}
+
+// The class name of an array component given as a descriptor, `None` for
+// primitives.
+fn component_class(descriptor: &str) -> Option<&str> {
+    if descriptor.starts_with('[') {
+        Some(descriptor)
+    } else {
+        descriptor.strip_prefix('L')?.strip_suffix(';')
+    }
+}
+
+// The array type with the given component class.
+fn array_of(class_name: &str) -> String {
+    if class_name.starts_with('[') {
+        format!("[{class_name}")
+    } else {
+        format!("[L{class_name};")
+    }
+}
+
+// Turns slots into verification types, where long and double are a single
+// entry. Trailing `Top` locals are left out.
+fn verification_types(
+    slots: &[Type],
+    trim: bool,
+    pool: &mut ConstantPoolBuilder,
+) -> Vec<VerificationTypeInfo> {
+    let mut types = Vec::new();
+    let mut slots = slots.iter().peekable();
+    while let Some(slot) = slots.next() {
+        let info = match slot {
+            Type::Top => VerificationTypeInfo::Top,
+            Type::Integer => VerificationTypeInfo::Integer,
+            Type::Float => VerificationTypeInfo::Float,
+            Type::Long => VerificationTypeInfo::Long,
+            Type::Double => VerificationTypeInfo::Double,
+            Type::Null => VerificationTypeInfo::Null,
+            Type::UninitializedThis => VerificationTypeInfo::UninitializedThis,
+            Type::Uninitialized(offset) => VerificationTypeInfo::Uninitialized {
+                offset: *offset as u16,
+            },
+            Type::Object(name) => VerificationTypeInfo::Object {
+                cpool_index: pool.class(name),
+            },
+        };
+        if slot.is_wide() {
+            slots.next_if_eq(&&Type::Top);
+        }
+        types.push(info);
+    }
+    if trim {
+        while types.last() == Some(&VerificationTypeInfo::Top) {
+            types.pop();
+        }
+    }
+    types
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.4
+fn compress(
+    offset_delta: u16,
+    previous_locals: &[VerificationTypeInfo],
+    locals: &[VerificationTypeInfo],
+    mut stack: Vec<VerificationTypeInfo>,
+) -> StackMapFrame {
+    let same_locals = locals == previous_locals;
+    if same_locals && stack.is_empty() {
+        return if offset_delta < 64 {
+            StackMapFrame::SameFrame {
+                frame_type: offset_delta as u8,
+            }
+        } else {
+            StackMapFrame::SameFrameExtended { offset_delta }
+        };
+    }
+    if same_locals && stack.len() == 1 {
+        let stack = stack.remove(0);
+        return if offset_delta < 64 {
+            StackMapFrame::SameLocals1StackItemFrame {
+                frame_type: 64 + offset_delta as u8,
+                stack,
+            }
+        } else {
+            StackMapFrame::SameLocals1StackItemFrameExtended {
+                offset_delta,
+                stack,
+            }
+        };
+    }
+    if stack.is_empty() {
+        if locals.len() < previous_locals.len()
+            && previous_locals.len() - locals.len() <= 3
+            && previous_locals.starts_with(locals)
+        {
+            return StackMapFrame::ChopFrame {
+                frame_type: 251 - (previous_locals.len() - locals.len()) as u8,
+                offset_delta,
+            };
+        }
+        if locals.len() > previous_locals.len()
+            && locals.len() - previous_locals.len() <= 3
+            && locals.starts_with(previous_locals)
+        {
+            return StackMapFrame::AppendFrame {
+                frame_type: 251 + (locals.len() - previous_locals.len()) as u8,
+                offset_delta,
+                locals: locals[previous_locals.len()..].to_vec(),
+            };
+        }
+    }
+
+    StackMapFrame::FullFrame {
+        offset_delta,
+        number_of_locals: locals.len() as u16,
+        locals: locals.to_vec(),
+        number_of_stack_items: stack.len() as u16,
+        stack,
+    }
+}
+
+// Splits exception table entries around the unreachable code, which is
+// never covered by a handler.
+fn remove_dead_ranges(
+    exception_table: &[ExceptionTable],
+    dead: &[(u32, u32)],
+) -> Vec<ExceptionTable> {
+    let mut live = Vec::with_capacity(exception_table.len());
+    for entry in exception_table {
+        let mut start = entry.start_pc as u32;
+        let end = entry.end_pc as u32;
+        for (dead_start, dead_end) in dead {
+            if *dead_end <= start || *dead_start >= end {
+                continue;
+            }
+            if *dead_start > start {
+                live.push(ExceptionTable {
+                    start_pc: start as u16,
+                    end_pc: *dead_start as u16,
+                    ..entry.clone()
+                });
+            }
+            start = start.max(*dead_end);
+        }
+        if start < end {
+            live.push(ExceptionTable {
+                start_pc: start as u16,
+                ..entry.clone()
+            });
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+
+    fn frames(code: &Code) -> &[StackMapFrame] {
+        code.attributes
+            .iter()
+            .find_map(|attr| match attr {
+                Attribute::StackMapTable { entries, .. } => Some(entries.as_slice()),
+                _ => None,
+            })
+            .unwrap_or_default()
+    }
+
+    fn object(pool: &mut ConstantPoolBuilder, name: &str) -> VerificationTypeInfo {
+        VerificationTypeInfo::Object {
+            cpool_index: pool.class(name),
+        }
+    }
+
+    // `flag ? B.b : C.c`, where B and C are merged by `hierarchy`
+    fn merge_fields(pool: &mut ConstantPoolBuilder, hierarchy: &dyn ClassHierarchy) -> Code {
+        let b = pool.field_ref("Main", "b", "LB;");
+        let c = pool.field_ref("Main", "c", "LC;");
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(0)));
+        asm.branch(Opcode::Ifeq, other);
+        asm.emit(Instruction::new(Opcode::Getstatic, Operand::Constant(b)));
+        asm.branch(Opcode::Goto, join);
+        asm.place(other);
+        asm.emit(Instruction::new(Opcode::Getstatic, Operand::Constant(c)));
+        asm.place(join);
+        asm.op(Opcode::Areturn);
+        let mut code = asm.assemble(1, 1).unwrap();
+        compute_frames(&mut code, "Main", "pick", "(Z)LA;", true, pool, hierarchy).unwrap();
+        code
+    }
+
+    #[test]
+    fn references_merge_through_the_hierarchy() {
+        let mut pool = ConstantPoolBuilder::new();
+        let base = |a: &str, b: &str| match (a, b) {
+            ("B", "C") | ("C", "B") => "Base".to_string(),
+            _ => OBJECT.to_string(),
+        };
+        let code = merge_fields(&mut pool, &base);
+        let joined = object(&mut pool, "Base");
+        assert_eq!(
+            frames(&code)[1],
+            StackMapFrame::SameLocals1StackItemFrame {
+                frame_type: 64 + 2,
+                stack: joined.clone(),
+            }
+        );
+
+        let mut hierarchy = SuperclassMap::new();
+        hierarchy.add("Base", Some(OBJECT), false);
+        hierarchy.add("B", Some("Base"), false);
+        hierarchy.add("C", Some("Base"), false);
+        let code = merge_fields(&mut pool, &hierarchy);
+        assert!(matches!(
+            &frames(&code)[1],
+            StackMapFrame::SameLocals1StackItemFrame { stack, .. } if *stack == joined
+        ));
+        // classes the map doesn't know extend Object
+        let code = merge_fields(&mut pool, &SuperclassMap::new());
+        let object = object(&mut pool, OBJECT);
+        assert!(matches!(
+            &frames(&code)[1],
+            StackMapFrame::SameLocals1StackItemFrame { stack, .. } if *stack == object
+        ));
+    }
+
+    #[test]
+    fn objects_are_uninitialized_until_constructed() {
+        let mut pool = ConstantPoolBuilder::new();
+        let object_init = pool.method_ref(OBJECT, "<init>", "()V");
+        let foo = pool.class("Foo");
+        let foo_init = pool.method_ref("Foo", "<init>", "()V");
+        let mut asm = Assembler::new();
+        let (constructed, created) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::new(Opcode::Aload, Operand::Local(0)));
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(1)));
+        asm.branch(Opcode::Ifeq, constructed);
+        asm.op(Opcode::Nop);
+        asm.place(constructed);
+        asm.emit(Instruction::new(
+            Opcode::Invokespecial,
+            Operand::Constant(object_init),
+        ));
+        // at pc 9
+        asm.emit(Instruction::new(Opcode::New, Operand::Constant(foo)));
+        asm.op(Opcode::Dup);
+        asm.emit(Instruction::new(Opcode::Iload, Operand::Local(1)));
+        asm.branch(Opcode::Ifeq, created);
+        asm.op(Opcode::Nop);
+        asm.place(created);
+        asm.emit(Instruction::new(
+            Opcode::Invokespecial,
+            Operand::Constant(foo_init),
+        ));
+        asm.op(Opcode::Pop);
+        asm.op(Opcode::Return);
+        let mut code = asm.assemble(3, 2).unwrap();
+        let hierarchy = SuperclassMap::new();
+        compute_frames(
+            &mut code, "Foo", "<init>", "(Z)V", false, &mut pool, &hierarchy,
+        )
+        .unwrap();
+
+        let this = object(&mut pool, "Foo");
+        let uninitialized = VerificationTypeInfo::Uninitialized { offset: 9 };
+        assert_eq!(
+            frames(&code),
+            [
+                StackMapFrame::SameLocals1StackItemFrame {
+                    frame_type: 64 + 6,
+                    stack: VerificationTypeInfo::UninitializedThis,
+                },
+                StackMapFrame::FullFrame {
+                    offset_delta: 18 - 6 - 1,
+                    number_of_locals: 2,
+                    locals: vec![this, VerificationTypeInfo::Integer],
+                    number_of_stack_items: 2,
+                    stack: vec![uninitialized.clone(), uninitialized],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dead_code_becomes_nops_and_athrow() {
+        let mut pool = ConstantPoolBuilder::new();
+        let mut asm = Assembler::new();
+        let (start, dead, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.place(start);
+        asm.op(Opcode::Iconst0);
+        asm.op(Opcode::Ireturn);
+        asm.place(dead);
+        asm.op(Opcode::Iconst1);
+        asm.op(Opcode::Ireturn);
+        asm.place(handler);
+        asm.op(Opcode::Pop);
+        asm.op(Opcode::IconstM1);
+        asm.op(Opcode::Ireturn);
+        asm.try_catch(start, handler, handler, 0);
+        let mut code = asm.assemble(1, 1).unwrap();
+        let hierarchy = SuperclassMap::new();
+        compute_frames(
+            &mut code, "Main", "run", "(I)I", true, &mut pool, &hierarchy,
+        )
+        .unwrap();
+
+        assert_eq!(code.code[2..4], [Opcode::Nop as u8, Opcode::Athrow as u8]);
+        // the handler no longer covers it
+        assert_eq!(code.exception_table.len(), 1);
+        assert_eq!(
+            (
+                code.exception_table[0].start_pc,
+                code.exception_table[0].end_pc
+            ),
+            (0, 2)
+        );
+        // with nothing in its locals, as nothing reaches it
+        let throwable = object(&mut pool, THROWABLE);
+        assert_eq!(
+            frames(&code),
+            [
+                StackMapFrame::FullFrame {
+                    offset_delta: 2,
+                    number_of_locals: 0,
+                    locals: Vec::new(),
+                    number_of_stack_items: 1,
+                    stack: vec![throwable.clone()],
+                },
+                StackMapFrame::FullFrame {
+                    offset_delta: 1,
+                    number_of_locals: 1,
+                    locals: vec![VerificationTypeInfo::Integer],
+                    number_of_stack_items: 1,
+                    stack: vec![throwable],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn frames_are_compressed() {
+        use VerificationTypeInfo::{Integer, Long};
+        let locals = [Integer, Long];
+        assert_eq!(
+            compress(5, &locals, &locals, Vec::new()),
+            StackMapFrame::SameFrame { frame_type: 5 }
+        );
+        assert_eq!(
+            compress(64, &locals, &locals, Vec::new()),
+            StackMapFrame::SameFrameExtended { offset_delta: 64 }
+        );
+        assert_eq!(
+            compress(3, &locals, &locals, vec![Integer]),
+            StackMapFrame::SameLocals1StackItemFrame {
+                frame_type: 67,
+                stack: Integer,
+            }
+        );
+        assert_eq!(
+            compress(100, &locals, &locals, vec![Integer]),
+            StackMapFrame::SameLocals1StackItemFrameExtended {
+                offset_delta: 100,
+                stack: Integer,
+            }
+        );
+        assert_eq!(
+            compress(7, &locals, &locals[..1], Vec::new()),
+            StackMapFrame::ChopFrame {
+                frame_type: 250,
+                offset_delta: 7,
+            }
+        );
+        assert_eq!(
+            compress(7, &locals[..1], &locals, Vec::new()),
+            StackMapFrame::AppendFrame {
+                frame_type: 252,
+                offset_delta: 7,
+                locals: vec![Long],
+            }
+        );
+        // more than three locals apart, or a stack with other locals
+        let many = [Integer, Integer, Integer, Integer, Long];
+        assert_eq!(
+            compress(7, &locals[..1], &many, Vec::new()).frame_type(),
+            255
+        );
+        assert_eq!(compress(7, &locals, &many, vec![Integer]).frame_type(), 255);
+    }
+}
@@ -0,0 +1,779 @@
+use crate::cfg::EdgeKind;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::frames::ClassHierarchy;
+use crate::ir::{BinaryOp, Body, CompareOp, Const, Expr, Kind, Stmt, Terminator, UnaryOp, Var};
+use crate::types::ConstantPool;
+use std::collections::HashMap;
+
+const OBJECT: &str = "java/lang/Object";
+
+/// Gives every variable of a body a Java type, for when there is no
+/// `LocalVariableTable` to take them from.
+///
+/// Variables joined by a phi or a copy into or out of a temporary get one
+/// type. Its lower bound is what is stored in it: descriptors of the fields,
+/// methods and array opcodes it comes from, casts, `new` and the types of
+/// other variables. Its upper bound is where it is used. `boolean`, `byte`,
+/// `char` and `short`, which the bytecode all treats as `int`, come from the
+/// descriptors and array opcodes, and a variable only ever set to 0 or 1 and
+/// only tested against zero is a `boolean`. Reference types are joined with
+/// `hierarchy`.
+///
+/// Int constants used as a `boolean` or `char` are rewritten into
+/// [`Const::Boolean`] and [`Const::Char`]. Works on a body in SSA form or
+/// not; in SSA form it should run before [`crate::ssa::destruct`], which
+/// keeps variables of different types apart.
+pub fn infer_types(body: &mut Body, constant_pool: &ConstantPool, hierarchy: &dyn ClassHierarchy) {
+    let count = body.variables.len();
+    let mut inference = Inference {
+        parent: (0..count).collect(),
+        lower: vec![None; count],
+        fixed: vec![false; count],
+        hierarchy,
+    };
+
+    // a store to a parameter's slot is always to the parameter
+    let mut parameter_slots = HashMap::new();
+    for parameter in &body.parameters {
+        let variable = body.variable(*parameter);
+        if let (Some(slot), Some(field_type)) = (variable.slot, &variable.field_type) {
+            parameter_slots.insert((slot, variable.kind), field_type.clone());
+        }
+    }
+    for (var, variable) in body.variables.iter().enumerate() {
+        if let Some(field_type) = variable
+            .slot
+            .and_then(|slot| parameter_slots.get(&(slot, variable.kind)))
+        {
+            inference.lower[var] = Some(field_type.clone());
+            inference.fixed[var] = true;
+        }
+    }
+
+    for block in &body.blocks {
+        for phi in &block.phis {
+            for (_, source) in &phi.sources {
+                inference.union(phi.target, *source);
+            }
+        }
+        for stmt in &block.stmts {
+            if let Stmt::Assign {
+                target: Expr::Var(target),
+                value: Expr::Var(source),
+            } = stmt
+            {
+                let (a, b) = (body.variable(*target), body.variable(*source));
+                if a.slot.is_none() || b.slot.is_none() || a.slot == b.slot {
+                    inference.union(*target, *source);
+                }
+            }
+        }
+    }
+
+    let handler_types = handler_types(body, constant_pool, hierarchy);
+
+    // lower bounds, until they stop changing
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (id, block) in body.blocks.iter().enumerate() {
+            for phi in &block.phis {
+                for (_, source) in &phi.sources {
+                    let source = inference.lower_of(*source);
+                    changed |= inference.raise(phi.target, source);
+                }
+            }
+            for stmt in &block.stmts {
+                if let Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                } = stmt
+                {
+                    let field_type = match value {
+                        Expr::CaughtException => handler_types[id].clone(),
+                        value => inference.type_of(value),
+                    };
+                    changed |= inference.raise(*var, field_type);
+                }
+            }
+        }
+    }
+
+    // upper bounds and constants
+    let mut uses: HashMap<usize, Vec<Expected>> = HashMap::new();
+    let mut constants: HashMap<usize, Vec<i32>> = HashMap::new();
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            match stmt {
+                Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                } => {
+                    if let Expr::Const(Const::Int(value)) = value {
+                        let root = inference.find(var.0);
+                        constants.entry(root).or_default().push(*value);
+                    }
+                    inference.expect(value, Expected::Any, &mut uses);
+                }
+                Stmt::Assign { target, value } => {
+                    let expected = match inference.type_of(target) {
+                        Some(field_type) => Expected::Type(field_type),
+                        None => Expected::Any,
+                    };
+                    for child in target.children() {
+                        inference.expect(child, Expected::Any, &mut uses);
+                    }
+                    inference.expect_children(target, &mut uses);
+                    inference.expect(value, expected, &mut uses);
+                }
+                Stmt::Expr(expr) | Stmt::MonitorEnter(expr) | Stmt::MonitorExit(expr) => {
+                    inference.expect(expr, Expected::Any, &mut uses)
+                }
+            }
+        }
+        match &block.terminator {
+            Terminator::If { condition, .. } => {
+                inference.expect(condition, Expected::Type(FieldType::Boolean), &mut uses)
+            }
+            Terminator::Switch { value, .. } => {
+                inference.expect(value, Expected::Numeric, &mut uses)
+            }
+            Terminator::Return(Some(value)) => {
+                let expected = match &body.return_type {
+                    Some(field_type) => Expected::Type(field_type.clone()),
+                    None => Expected::Any,
+                };
+                inference.expect(value, expected, &mut uses)
+            }
+            Terminator::Throw(value) => inference.expect(value, Expected::Any, &mut uses),
+            _ => {}
+        }
+    }
+
+    // a version of a slot that nothing gives a type, such as the first `c`
+    // of `char c = 'a'; c++;`, takes the byte, char or short type the
+    // slot's other versions all have, so they stay one variable
+    let mut slot_types: HashMap<u16, Vec<FieldType>> = HashMap::new();
+    for var in 0..count {
+        let variable = &body.variables[var];
+        if let (Kind::Int, Some(slot)) = (variable.kind, variable.slot) {
+            let root = inference.find(var);
+            if let Some(field_type) = &inference.lower[root] {
+                let types = slot_types.entry(slot).or_default();
+                if !types.contains(field_type) {
+                    types.push(field_type.clone());
+                }
+            }
+        }
+    }
+    for var in 0..count {
+        let variable = &body.variables[var];
+        let root = inference.find(var);
+        let (Kind::Int, Some(slot)) = (variable.kind, variable.slot) else {
+            continue;
+        };
+        if inference.lower[root].is_some() || inference.fixed[root] {
+            continue;
+        }
+        let Some([field_type @ (FieldType::Byte | FieldType::Char | FieldType::Short)]) =
+            slot_types.get(&slot).map(Vec::as_slice)
+        else {
+            continue;
+        };
+        let uses = uses.get(&root).map_or(&[][..], Vec::as_slice);
+        let untyped = uses.iter().all(|expected| match expected {
+            Expected::Type(used) => used == field_type,
+            Expected::Numeric | Expected::Any => true,
+            Expected::Condition => false,
+        });
+        let constants = constants.get(&root).map_or(&[][..], Vec::as_slice);
+        if untyped && constants.iter().all(|value| fits(*value, field_type)) {
+            let field_type = field_type.clone();
+            inference.raise(Var(root), Some(field_type));
+        }
+    }
+
+    let mut chosen: HashMap<usize, FieldType> = HashMap::new();
+    for var in 0..count {
+        let root = inference.find(var);
+        if chosen.contains_key(&root) {
+            continue;
+        }
+        let field_type = choose(
+            body.variables[var].kind,
+            inference.lower[root].clone(),
+            inference.fixed[root],
+            uses.get(&root).map_or(&[], Vec::as_slice),
+            constants.get(&root).map_or(&[], Vec::as_slice),
+        );
+        chosen.insert(root, field_type);
+    }
+    for var in 0..count {
+        let root = inference.find(var);
+        body.variables[var].field_type = chosen.get(&root).cloned();
+    }
+
+    retype_constants(body);
+}
+
+// What a use of a value needs it to be.
+#[derive(Debug, Clone, PartialEq)]
+enum Expected {
+    Type(FieldType),
+    // an operand of arithmetic, which a boolean can't be
+    Numeric,
+    // compared against zero, which both booleans and ints can be
+    Condition,
+    Any,
+}
+
+struct Inference<'a> {
+    // union-find over variables that must have the same type
+    parent: Vec<usize>,
+    lower: Vec<Option<FieldType>>,
+    fixed: Vec<bool>,
+    hierarchy: &'a dyn ClassHierarchy,
+}
+
+impl Inference<'_> {
+    fn find(&mut self, mut var: usize) -> usize {
+        while self.parent[var] != var {
+            self.parent[var] = self.parent[self.parent[var]];
+            var = self.parent[var];
+        }
+        var
+    }
+
+    fn union(&mut self, a: Var, b: Var) {
+        let (a, b) = (self.find(a.0), self.find(b.0));
+        if a == b {
+            return;
+        }
+        // a parameter's type wins
+        let (root, other) = if self.fixed[b] { (b, a) } else { (a, b) };
+        self.parent[other] = root;
+        if !self.fixed[root] {
+            let lower = self.lower[other].take();
+            self.raise(Var(root), lower);
+        }
+    }
+
+    fn lower_of(&mut self, var: Var) -> Option<FieldType> {
+        let root = self.find(var.0);
+        self.lower[root].clone()
+    }
+
+    // Widens the lower bound of a variable to also hold `field_type`.
+    fn raise(&mut self, var: Var, field_type: Option<FieldType>) -> bool {
+        let root = self.find(var.0);
+        let Some(field_type) = field_type else {
+            return false;
+        };
+        if self.fixed[root] {
+            return false;
+        }
+        let joined = match &self.lower[root] {
+            None => field_type,
+            Some(current) => join(current, &field_type, self.hierarchy),
+        };
+        if self.lower[root].as_ref() == Some(&joined) {
+            return false;
+        }
+        self.lower[root] = Some(joined);
+        true
+    }
+
+    fn type_of(&mut self, expr: &Expr) -> Option<FieldType> {
+        expr_type(expr, &mut |var| self.lower_of(var))
+    }
+
+    // Records what the variables in an expression are used as.
+    fn expect(
+        &mut self,
+        expr: &Expr,
+        expected: Expected,
+        uses: &mut HashMap<usize, Vec<Expected>>,
+    ) {
+        if let Expr::Var(var) = expr {
+            let root = self.find(var.0);
+            uses.entry(root).or_default().push(expected);
+            return;
+        }
+        self.expect_children(expr, uses);
+    }
+
+    fn expect_children(&mut self, expr: &Expr, uses: &mut HashMap<usize, Vec<Expected>>) {
+        let parameters = |descriptor: &str| {
+            MethodDescriptor::parse(descriptor).map_or_else(Vec::new, |method| method.parameters)
+        };
+        match expr {
+            Expr::Field {
+                owner,
+                object: Some(object),
+                ..
+            } => {
+                let expected =
+                    FieldType::from_class_name(owner).map_or(Expected::Any, Expected::Type);
+                self.expect(object, expected, uses);
+            }
+            Expr::ArrayElement { array, index } => {
+                self.expect(array, Expected::Any, uses);
+                self.expect(index, Expected::Numeric, uses);
+            }
+            Expr::Binary {
+                op: BinaryOp::And | BinaryOp::Or | BinaryOp::Xor,
+                left,
+                right,
+            } => {
+                self.expect(left, Expected::Any, uses);
+                self.expect(right, Expected::Any, uses);
+            }
+            Expr::Binary { left, right, .. } => {
+                self.expect(left, Expected::Numeric, uses);
+                self.expect(right, Expected::Numeric, uses);
+            }
+            Expr::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => self.expect(operand, Expected::Type(FieldType::Boolean), uses),
+            Expr::Unary { operand, .. } => self.expect(operand, Expected::Numeric, uses),
+            Expr::Logical { left, right, .. } => {
+                self.expect(left, Expected::Type(FieldType::Boolean), uses);
+                self.expect(right, Expected::Type(FieldType::Boolean), uses);
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => {
+                self.expect(condition, Expected::Type(FieldType::Boolean), uses);
+                self.expect(then, Expected::Any, uses);
+                self.expect(otherwise, Expected::Any, uses);
+            }
+            Expr::Compare { op, left, right } => {
+                let against_zero = matches!(**right, Expr::Const(Const::Int(0)));
+                let expected = match op {
+                    CompareOp::Eq | CompareOp::Ne if against_zero => Expected::Condition,
+                    CompareOp::Eq | CompareOp::Ne => Expected::Any,
+                    _ => Expected::Numeric,
+                };
+                self.expect(left, expected.clone(), uses);
+                self.expect(right, expected, uses);
+            }
+            Expr::Invoke {
+                owner,
+                descriptor,
+                object,
+                args,
+                ..
+            } => {
+                if let Some(object) = object {
+                    let expected =
+                        FieldType::from_class_name(owner).map_or(Expected::Any, Expected::Type);
+                    self.expect(object, expected, uses);
+                }
+                self.expect_arguments(args, parameters(descriptor), uses);
+            }
+            Expr::InvokeDynamic {
+                descriptor, args, ..
+            }
+            | Expr::New {
+                descriptor, args, ..
+            } => self.expect_arguments(args, parameters(descriptor), uses),
+            Expr::NewArray { dimensions, .. } => {
+                for dimension in dimensions {
+                    self.expect(dimension, Expected::Numeric, uses);
+                }
+            }
+            Expr::ArrayInit {
+                field_type,
+                elements,
+            } => {
+                let expected = match field_type {
+                    FieldType::Array(element) => Expected::Type((**element).clone()),
+                    _ => Expected::Any,
+                };
+                for element in elements {
+                    self.expect(element, expected.clone(), uses);
+                }
+            }
+            expr => {
+                for child in expr.children() {
+                    self.expect(child, Expected::Any, uses);
+                }
+            }
+        }
+    }
+
+    fn expect_arguments(
+        &mut self,
+        args: &[Expr],
+        parameters: Vec<FieldType>,
+        uses: &mut HashMap<usize, Vec<Expected>>,
+    ) {
+        let mut parameters = parameters.into_iter();
+        for arg in args {
+            let expected = parameters.next().map_or(Expected::Any, Expected::Type);
+            self.expect(arg, expected, uses);
+        }
+    }
+}
+
+/// The Java type of an expression, from the types of the body's variables.
+pub fn type_of(body: &Body, expr: &Expr) -> Option<FieldType> {
+    match expr {
+        Expr::Const(Const::Int(_)) => Some(FieldType::Int),
+        expr => expr_type(expr, &mut |var| body.variable(var).field_type.clone()),
+    }
+}
+
+// The type of an expression, `None` for `null`, constants that could be
+// several types, and variables nothing is known about yet.
+fn expr_type(expr: &Expr, var_type: &mut dyn FnMut(Var) -> Option<FieldType>) -> Option<FieldType> {
+    let field_type = match expr {
+        Expr::Const(constant) => match constant {
+            Const::Null | Const::Int(_) => return None,
+            Const::Boolean(_) => FieldType::Boolean,
+            Const::Char(_) => FieldType::Char,
+            Const::Long(_) => FieldType::Long,
+            Const::Float(_) => FieldType::Float,
+            Const::Double(_) => FieldType::Double,
+            Const::String(_) => object("java/lang/String"),
+            Const::Class(_) => object("java/lang/Class"),
+            Const::MethodType(_) => object("java/lang/invoke/MethodType"),
+            Const::MethodHandle { .. } => object("java/lang/invoke/MethodHandle"),
+            Const::Dynamic { descriptor, .. } => FieldType::parse(descriptor)?,
+        },
+        Expr::Var(var) => return var_type(*var),
+        Expr::Field { descriptor, .. } => FieldType::parse(descriptor)?,
+        Expr::ArrayElement { array, .. } => match expr_type(array, var_type) {
+            Some(FieldType::Array(element)) => *element,
+            _ => return None,
+        },
+        Expr::ArrayLength(_) | Expr::Cmp { .. } => FieldType::Int,
+        Expr::Binary {
+            op: BinaryOp::And | BinaryOp::Or | BinaryOp::Xor,
+            left,
+            right,
+        } => {
+            let (left, right) = (expr_type(left, var_type), expr_type(right, var_type));
+            match (left, right) {
+                (Some(FieldType::Boolean), Some(FieldType::Boolean) | None)
+                | (None, Some(FieldType::Boolean)) => FieldType::Boolean,
+                (Some(FieldType::Long), _) | (_, Some(FieldType::Long)) => FieldType::Long,
+                _ => FieldType::Int,
+            }
+        }
+        // string concatenation
+        Expr::Binary {
+            op: BinaryOp::Add,
+            left,
+            right,
+        } if [left, right]
+            .iter()
+            .any(|operand| expr_type(operand, var_type) == Some(object("java/lang/String"))) =>
+        {
+            object("java/lang/String")
+        }
+        Expr::Binary { left, right, .. } => {
+            match (expr_type(left, var_type), expr_type(right, var_type)) {
+                (Some(FieldType::Long), _) => FieldType::Long,
+                (Some(FieldType::Double), _) | (_, Some(FieldType::Double)) => FieldType::Double,
+                (Some(FieldType::Float), _) | (_, Some(FieldType::Float)) => FieldType::Float,
+                _ => FieldType::Int,
+            }
+        }
+        Expr::Unary {
+            op: UnaryOp::Not, ..
+        }
+        | Expr::Logical { .. }
+        | Expr::Compare { .. }
+        | Expr::InstanceOf { .. } => FieldType::Boolean,
+        Expr::Conditional {
+            then, otherwise, ..
+        } => {
+            return expr_type(then, var_type).or_else(|| expr_type(otherwise, var_type));
+        }
+        Expr::Unary { operand, .. } => match expr_type(operand, var_type) {
+            Some(FieldType::Long) => FieldType::Long,
+            Some(FieldType::Float) => FieldType::Float,
+            Some(FieldType::Double) => FieldType::Double,
+            _ => FieldType::Int,
+        },
+        Expr::Cast { field_type, .. } => field_type.clone(),
+        Expr::Invoke { descriptor, .. } | Expr::InvokeDynamic { descriptor, .. } => {
+            MethodDescriptor::parse(descriptor)?.return_type?
+        }
+        Expr::New { class, .. } => FieldType::from_class_name(class)?,
+        Expr::NewArray { field_type, .. } | Expr::ArrayInit { field_type, .. } => {
+            field_type.clone()
+        }
+        Expr::Assign { target, .. } => return expr_type(target, var_type),
+        Expr::Switch { .. } => return None,
+        Expr::Lambda { interface, .. } | Expr::MethodRef { interface, .. } => object(interface),
+        Expr::CaughtException => object("java/lang/Throwable"),
+        Expr::Uninitialized { class, .. } => FieldType::from_class_name(class)?,
+    };
+    Some(field_type)
+}
+
+fn object(name: &str) -> FieldType {
+    FieldType::Object(name.to_string())
+}
+
+// The types of the exceptions each handler catches, joined for a handler of
+// several exception table entries.
+fn handler_types(
+    body: &Body,
+    constant_pool: &ConstantPool,
+    hierarchy: &dyn ClassHierarchy,
+) -> Vec<Option<FieldType>> {
+    (0..body.blocks.len())
+        .map(|id| {
+            let mut caught: Option<FieldType> = None;
+            for edge in body.cfg.predecessors(id) {
+                let EdgeKind::Exceptional { catch_type } = edge.kind else {
+                    continue;
+                };
+                let field_type = match catch_type {
+                    0 => object("java/lang/Throwable"),
+                    index => constant_pool
+                        .class_name(index)
+                        .and_then(FieldType::from_class_name)
+                        .unwrap_or_else(|| object("java/lang/Throwable")),
+                };
+                caught = Some(match caught {
+                    None => field_type,
+                    Some(current) => join(&current, &field_type, hierarchy),
+                });
+            }
+            // catching exceptions the hierarchy doesn't know
+            match caught {
+                Some(FieldType::Object(name)) if name == OBJECT => {
+                    Some(object("java/lang/Throwable"))
+                }
+                caught => caught,
+            }
+        })
+        .collect()
+}
+
+// The narrowest type that holds values of both types.
+fn join(a: &FieldType, b: &FieldType, hierarchy: &dyn ClassHierarchy) -> FieldType {
+    use FieldType::*;
+
+    match (a, b) {
+        (a, b) if a == b => a.clone(),
+        (Byte, Short) | (Short, Byte) => Short,
+        (Byte | Short | Char | Int, Byte | Short | Char | Int) => Int,
+        // a boolean merged with a number isn't valid Java; int can at least
+        // hold both
+        (Boolean, Byte | Short | Char | Int) | (Byte | Short | Char | Int, Boolean) => Int,
+        (Object(a), Object(b)) if a == OBJECT || b == OBJECT => object(OBJECT),
+        (Object(a), Object(b)) => object(&hierarchy.common_superclass(a, b)),
+        (Array(a), Array(b)) if a.is_reference() && b.is_reference() => {
+            Array(Box::new(join(a, b, hierarchy)))
+        }
+        _ if a.is_reference() && b.is_reference() => object(OBJECT),
+        _ => a.clone(),
+    }
+}
+
+fn fits(value: i32, field_type: &FieldType) -> bool {
+    match field_type {
+        FieldType::Boolean => value == 0 || value == 1,
+        FieldType::Byte => i8::try_from(value).is_ok(),
+        FieldType::Char => u16::try_from(value).is_ok(),
+        FieldType::Short => i16::try_from(value).is_ok(),
+        _ => true,
+    }
+}
+
+fn choose(
+    kind: Kind,
+    lower: Option<FieldType>,
+    fixed: bool,
+    uses: &[Expected],
+    constants: &[i32],
+) -> FieldType {
+    if fixed {
+        if let Some(field_type) = lower {
+            return field_type;
+        }
+    }
+    match kind {
+        Kind::Long => FieldType::Long,
+        Kind::Float => FieldType::Float,
+        Kind::Double => FieldType::Double,
+        Kind::Int => {
+            let mut field_type = match lower {
+                Some(field_type) => field_type,
+                None if uses.contains(&Expected::Type(FieldType::Boolean)) => FieldType::Boolean,
+                // flags only set to true or false and tested
+                None if !constants.is_empty()
+                    && !uses.is_empty()
+                    && constants
+                        .iter()
+                        .all(|value| fits(*value, &FieldType::Boolean))
+                    && uses.iter().all(|expected| {
+                        matches!(
+                            expected,
+                            Expected::Condition | Expected::Type(FieldType::Boolean)
+                        )
+                    }) =>
+                {
+                    FieldType::Boolean
+                }
+                None => {
+                    // the narrowest type every use takes
+                    let mut narrowest = FieldType::Int;
+                    for expected in uses {
+                        if let Expected::Type(
+                            field_type @ (FieldType::Byte | FieldType::Short | FieldType::Char),
+                        ) = expected
+                        {
+                            narrowest = match (&narrowest, field_type) {
+                                (FieldType::Int, field_type) => field_type.clone(),
+                                (FieldType::Short, FieldType::Byte) => FieldType::Byte,
+                                (current, _) => current.clone(),
+                            };
+                        }
+                    }
+                    narrowest
+                }
+            };
+            // every constant stored has to fit
+            while !constants.iter().all(|value| fits(*value, &field_type)) {
+                field_type = match field_type {
+                    FieldType::Byte => FieldType::Short,
+                    _ => FieldType::Int,
+                };
+            }
+            field_type
+        }
+        Kind::Reference => {
+            let used_as: Vec<&FieldType> = uses
+                .iter()
+                .filter_map(|expected| match expected {
+                    Expected::Type(field_type) if field_type.is_reference() => Some(field_type),
+                    _ => None,
+                })
+                .collect();
+            let single_use = match used_as.split_first() {
+                Some((first, rest)) if rest.iter().all(|other| other == first) => {
+                    Some((*first).clone())
+                }
+                _ => None,
+            };
+            match (lower, single_use) {
+                (Some(FieldType::Object(name)), Some(used)) if name == OBJECT => used,
+                (Some(field_type), _) => field_type,
+                (None, Some(used)) => used,
+                (None, None) => object(OBJECT),
+            }
+        }
+    }
+}
+
+// Rewrites int constants stored in or compared with a boolean or char
+// variable, or passed or put in an array where one is expected, into
+// boolean and char constants.
+fn retype_constants(body: &mut Body) {
+    let types: Vec<Option<FieldType>> = body
+        .variables
+        .iter()
+        .map(|variable| variable.field_type.clone())
+        .collect();
+    let var_type = |expr: &Expr| match expr {
+        Expr::Var(var) => types[var.0].clone(),
+        Expr::Field { descriptor, .. } => FieldType::parse(descriptor),
+        Expr::ArrayElement { array, .. } => match &**array {
+            Expr::Var(var) => match &types[var.0] {
+                Some(FieldType::Array(element)) => Some(*element.clone()),
+                _ => None,
+            },
+            Expr::Field { descriptor, .. } => match FieldType::parse(descriptor) {
+                Some(FieldType::Array(element)) => Some(*element),
+                _ => None,
+            },
+            _ => None,
+        },
+        Expr::Invoke { descriptor, .. } => MethodDescriptor::parse(descriptor)?.return_type,
+        Expr::InstanceOf { .. } => Some(FieldType::Boolean),
+        _ => None,
+    };
+    let return_type = body.return_type.clone();
+
+    let mut retype_arguments = |expr: &mut Expr| {
+        let (descriptor, args) = match expr {
+            Expr::Invoke {
+                descriptor, args, ..
+            }
+            | Expr::InvokeDynamic {
+                descriptor, args, ..
+            }
+            | Expr::New {
+                descriptor, args, ..
+            } => (descriptor, args),
+            Expr::Compare {
+                op: CompareOp::Eq | CompareOp::Ne,
+                left,
+                right,
+            } => {
+                if let Some(field_type) = var_type(left) {
+                    retype(right, &field_type);
+                }
+                return;
+            }
+            Expr::ArrayInit {
+                field_type: FieldType::Array(element),
+                elements,
+            } => {
+                for value in elements {
+                    retype(value, element);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if let Some(method) = MethodDescriptor::parse(descriptor) {
+            for (arg, parameter) in args.iter_mut().zip(&method.parameters) {
+                retype(arg, parameter);
+            }
+        }
+    };
+    for block in &mut body.blocks {
+        for stmt in &mut block.stmts {
+            if let Stmt::Assign { target, value } = stmt {
+                if let Some(field_type) = var_type(target) {
+                    retype(value, &field_type);
+                }
+            }
+            for expr in stmt.exprs_mut() {
+                expr.walk_mut(&mut retype_arguments);
+            }
+        }
+        if let (Terminator::Return(Some(value)), Some(return_type)) =
+            (&mut block.terminator, &return_type)
+        {
+            retype(value, return_type);
+        }
+        for expr in block.terminator.exprs_mut() {
+            expr.walk_mut(&mut retype_arguments);
+        }
+    }
+}
+
+fn retype(expr: &mut Expr, field_type: &FieldType) {
+    if let Expr::Const(Const::Int(value)) = expr {
+        match field_type {
+            FieldType::Boolean => *expr = Expr::Const(Const::Boolean(*value != 0)),
+            FieldType::Char => {
+                if let Ok(value) = u16::try_from(*value) {
+                    *expr = Expr::Const(Const::Char(value))
+                }
+            }
+            _ => {}
+        }
+    }
+}
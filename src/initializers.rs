@@ -0,0 +1,168 @@
+use crate::ir::{Body, Expr, InvokeKind, Stmt, Var};
+use crate::structure::Statement;
+
+/// A constructor as [`instance_initializers`] sees it.
+pub struct Constructor<'a> {
+    pub body: &'a Body,
+    pub statements: &'a mut Vec<Statement>,
+    /// The parameter the enclosing instance of an inner class is passed in.
+    pub enclosing: Option<Var>,
+}
+
+/// Takes the initializers of static fields back out of a `<clinit>`, into
+/// which javac compiles them in the order they are declared, along with
+/// the static initializers between them:
+///   static int count = 1;   ->   `<clinit>`: Outer.count = 1;
+/// These are the leading assignments of `fields`, the static fields of
+/// `this_class` in the order they are declared, which come in that order
+/// and depend on no variables. Each is removed from `statements` and given
+/// with the field it initializes.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-12.html#jls-12.4.2
+pub fn static_initializers(
+    this_class: &str,
+    fields: &[&str],
+    statements: &mut Vec<Statement>,
+) -> Vec<(String, Expr)> {
+    let initializers = leading_assignments(this_class, fields, None, &[], statements, 0);
+    statements.drain(..initializers.len());
+    initializers
+}
+
+/// Takes the initializers of instance fields back out of constructors.
+/// javac copies them into every constructor that doesn't start by calling
+/// another of the class's with `this(...)`, right after the call of the
+/// superclass's:
+///   int count = 1;   ->   Outer() { super(); this.count = 1; }
+/// These are the leading assignments of `fields`, the instance fields of
+/// `this_class` in the order they are declared, which come in that order,
+/// depend on nothing but `this` and the enclosing instance, and are the
+/// same in each of those constructors. Each is removed from them all, and
+/// given in terms of the variables of the constructor given by the index
+/// returned, which is `None` when there is no such constructor.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-12.html#jls-12.5
+pub fn instance_initializers(
+    this_class: &str,
+    fields: &[&str],
+    constructors: &mut [Constructor],
+) -> Option<(usize, Vec<(String, Expr)>)> {
+    let mut candidates = Vec::new();
+    let mut first = None;
+    for (index, constructor) in constructors.iter().enumerate() {
+        let this = constructor.body.this()?;
+        // the call of the superclass's constructor is left out of those of
+        // anonymous classes and enums
+        let start = match constructor.statements.first() {
+            Some(Statement::Stmt(Stmt::Expr(Expr::Invoke {
+                kind: InvokeKind::Special,
+                owner,
+                name,
+                object,
+                ..
+            }))) if name == "<init>" && object.as_deref() == Some(&Expr::Var(this)) => {
+                if owner == this_class {
+                    continue;
+                }
+                1
+            }
+            _ => 0,
+        };
+        let allowed: Vec<Var> = std::iter::once(this).chain(constructor.enclosing).collect();
+        let mut assignments = leading_assignments(
+            this_class,
+            fields,
+            Some(this),
+            &allowed,
+            constructor.statements,
+            start,
+        );
+        // in terms of the first constructor's variables
+        let (first_index, first_allowed) = first.get_or_insert((index, allowed.clone()));
+        if *first_index != index {
+            for (_, value) in &mut assignments {
+                value.walk_mut(&mut |expr| {
+                    if let Expr::Var(var) = expr {
+                        if let Some(position) = allowed.iter().position(|allowed| allowed == var) {
+                            if let Some(first) = first_allowed.get(position) {
+                                *var = *first;
+                            }
+                        }
+                    }
+                });
+            }
+        }
+        candidates.push((index, start, assignments));
+    }
+    let (first, _) = first?;
+    let (_, _, shared) = candidates.first()?;
+    let count = candidates
+        .iter()
+        .map(|(_, _, assignments)| {
+            shared
+                .iter()
+                .zip(assignments)
+                .take_while(|(shared, assignment)| shared == assignment)
+                .count()
+        })
+        .min()
+        .unwrap_or(0);
+    let initializers = shared[..count].to_vec();
+    for (index, start, _) in candidates {
+        constructors[index].statements.drain(start..start + count);
+    }
+    Some((first, initializers))
+}
+
+// The assignments of fields of `this_class` that `statements` start with
+// from `start`, in the order of `fields`, of values with no variables but
+// `allowed`. `object` is the object of instance fields.
+fn leading_assignments(
+    this_class: &str,
+    fields: &[&str],
+    object: Option<Var>,
+    allowed: &[Var],
+    statements: &[Statement],
+    start: usize,
+) -> Vec<(String, Expr)> {
+    let object = object.map(Expr::Var);
+    let mut assignments = Vec::new();
+    let mut next = 0;
+    for statement in statements.iter().skip(start) {
+        let Statement::Stmt(Stmt::Assign {
+            target:
+                Expr::Field {
+                    owner,
+                    name,
+                    object: assigned,
+                    ..
+                },
+            value,
+        }) = statement
+        else {
+            break;
+        };
+        if owner != this_class || assigned.as_deref() != object.as_ref() {
+            break;
+        }
+        let Some(position) = fields[next..].iter().position(|field| field == name) else {
+            break;
+        };
+        if !uses_only(value, allowed) {
+            break;
+        }
+        next += position + 1;
+        assignments.push((name.clone(), value.clone()));
+    }
+    assignments
+}
+
+// Whether an expression uses no variables but `allowed`. A switch
+// expression is taken to use others, as its cases are statements.
+fn uses_only(value: &Expr, allowed: &[Var]) -> bool {
+    let mut only = true;
+    value.walk(&mut |expr| match expr {
+        Expr::Var(var) if !allowed.contains(var) => only = false,
+        Expr::Switch { .. } => only = false,
+        _ => {}
+    });
+    only
+}
@@ -0,0 +1,854 @@
+use crate::decompile::DecompileResult;
+use crate::error::DecompileError;
+use std::fmt::Display;
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-6.html#jvms-6.5
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-7.html
+macro_rules! opcodes {
+    ($($name:ident = $value:literal => $mnemonic:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $value,)*
+        }
+
+        impl Opcode {
+            pub fn from_u8(value: u8) -> Option<Opcode> {
+                match value {
+                    $($value => Some(Opcode::$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn mnemonic(&self) -> &'static str {
+                match self {
+                    $(Opcode::$name => $mnemonic,)*
+                }
+            }
+        }
+    };
+}
+
+opcodes! {
+    Nop = 0x00 => "nop",
+    AconstNull = 0x01 => "aconst_null",
+    IconstM1 = 0x02 => "iconst_m1",
+    Iconst0 = 0x03 => "iconst_0",
+    Iconst1 = 0x04 => "iconst_1",
+    Iconst2 = 0x05 => "iconst_2",
+    Iconst3 = 0x06 => "iconst_3",
+    Iconst4 = 0x07 => "iconst_4",
+    Iconst5 = 0x08 => "iconst_5",
+    Lconst0 = 0x09 => "lconst_0",
+    Lconst1 = 0x0a => "lconst_1",
+    Fconst0 = 0x0b => "fconst_0",
+    Fconst1 = 0x0c => "fconst_1",
+    Fconst2 = 0x0d => "fconst_2",
+    Dconst0 = 0x0e => "dconst_0",
+    Dconst1 = 0x0f => "dconst_1",
+    Bipush = 0x10 => "bipush",
+    Sipush = 0x11 => "sipush",
+    Ldc = 0x12 => "ldc",
+    LdcW = 0x13 => "ldc_w",
+    Ldc2W = 0x14 => "ldc2_w",
+    Iload = 0x15 => "iload",
+    Lload = 0x16 => "lload",
+    Fload = 0x17 => "fload",
+    Dload = 0x18 => "dload",
+    Aload = 0x19 => "aload",
+    Iload0 = 0x1a => "iload_0",
+    Iload1 = 0x1b => "iload_1",
+    Iload2 = 0x1c => "iload_2",
+    Iload3 = 0x1d => "iload_3",
+    Lload0 = 0x1e => "lload_0",
+    Lload1 = 0x1f => "lload_1",
+    Lload2 = 0x20 => "lload_2",
+    Lload3 = 0x21 => "lload_3",
+    Fload0 = 0x22 => "fload_0",
+    Fload1 = 0x23 => "fload_1",
+    Fload2 = 0x24 => "fload_2",
+    Fload3 = 0x25 => "fload_3",
+    Dload0 = 0x26 => "dload_0",
+    Dload1 = 0x27 => "dload_1",
+    Dload2 = 0x28 => "dload_2",
+    Dload3 = 0x29 => "dload_3",
+    Aload0 = 0x2a => "aload_0",
+    Aload1 = 0x2b => "aload_1",
+    Aload2 = 0x2c => "aload_2",
+    Aload3 = 0x2d => "aload_3",
+    Iaload = 0x2e => "iaload",
+    Laload = 0x2f => "laload",
+    Faload = 0x30 => "faload",
+    Daload = 0x31 => "daload",
+    Aaload = 0x32 => "aaload",
+    Baload = 0x33 => "baload",
+    Caload = 0x34 => "caload",
+    Saload = 0x35 => "saload",
+    Istore = 0x36 => "istore",
+    Lstore = 0x37 => "lstore",
+    Fstore = 0x38 => "fstore",
+    Dstore = 0x39 => "dstore",
+    Astore = 0x3a => "astore",
+    Istore0 = 0x3b => "istore_0",
+    Istore1 = 0x3c => "istore_1",
+    Istore2 = 0x3d => "istore_2",
+    Istore3 = 0x3e => "istore_3",
+    Lstore0 = 0x3f => "lstore_0",
+    Lstore1 = 0x40 => "lstore_1",
+    Lstore2 = 0x41 => "lstore_2",
+    Lstore3 = 0x42 => "lstore_3",
+    Fstore0 = 0x43 => "fstore_0",
+    Fstore1 = 0x44 => "fstore_1",
+    Fstore2 = 0x45 => "fstore_2",
+    Fstore3 = 0x46 => "fstore_3",
+    Dstore0 = 0x47 => "dstore_0",
+    Dstore1 = 0x48 => "dstore_1",
+    Dstore2 = 0x49 => "dstore_2",
+    Dstore3 = 0x4a => "dstore_3",
+    Astore0 = 0x4b => "astore_0",
+    Astore1 = 0x4c => "astore_1",
+    Astore2 = 0x4d => "astore_2",
+    Astore3 = 0x4e => "astore_3",
+    Iastore = 0x4f => "iastore",
+    Lastore = 0x50 => "lastore",
+    Fastore = 0x51 => "fastore",
+    Dastore = 0x52 => "dastore",
+    Aastore = 0x53 => "aastore",
+    Bastore = 0x54 => "bastore",
+    Castore = 0x55 => "castore",
+    Sastore = 0x56 => "sastore",
+    Pop = 0x57 => "pop",
+    Pop2 = 0x58 => "pop2",
+    Dup = 0x59 => "dup",
+    DupX1 = 0x5a => "dup_x1",
+    DupX2 = 0x5b => "dup_x2",
+    Dup2 = 0x5c => "dup2",
+    Dup2X1 = 0x5d => "dup2_x1",
+    Dup2X2 = 0x5e => "dup2_x2",
+    Swap = 0x5f => "swap",
+    Iadd = 0x60 => "iadd",
+    Ladd = 0x61 => "ladd",
+    Fadd = 0x62 => "fadd",
+    Dadd = 0x63 => "dadd",
+    Isub = 0x64 => "isub",
+    Lsub = 0x65 => "lsub",
+    Fsub = 0x66 => "fsub",
+    Dsub = 0x67 => "dsub",
+    Imul = 0x68 => "imul",
+    Lmul = 0x69 => "lmul",
+    Fmul = 0x6a => "fmul",
+    Dmul = 0x6b => "dmul",
+    Idiv = 0x6c => "idiv",
+    Ldiv = 0x6d => "ldiv",
+    Fdiv = 0x6e => "fdiv",
+    Ddiv = 0x6f => "ddiv",
+    Irem = 0x70 => "irem",
+    Lrem = 0x71 => "lrem",
+    Frem = 0x72 => "frem",
+    Drem = 0x73 => "drem",
+    Ineg = 0x74 => "ineg",
+    Lneg = 0x75 => "lneg",
+    Fneg = 0x76 => "fneg",
+    Dneg = 0x77 => "dneg",
+    Ishl = 0x78 => "ishl",
+    Lshl = 0x79 => "lshl",
+    Ishr = 0x7a => "ishr",
+    Lshr = 0x7b => "lshr",
+    Iushr = 0x7c => "iushr",
+    Lushr = 0x7d => "lushr",
+    Iand = 0x7e => "iand",
+    Land = 0x7f => "land",
+    Ior = 0x80 => "ior",
+    Lor = 0x81 => "lor",
+    Ixor = 0x82 => "ixor",
+    Lxor = 0x83 => "lxor",
+    Iinc = 0x84 => "iinc",
+    I2l = 0x85 => "i2l",
+    I2f = 0x86 => "i2f",
+    I2d = 0x87 => "i2d",
+    L2i = 0x88 => "l2i",
+    L2f = 0x89 => "l2f",
+    L2d = 0x8a => "l2d",
+    F2i = 0x8b => "f2i",
+    F2l = 0x8c => "f2l",
+    F2d = 0x8d => "f2d",
+    D2i = 0x8e => "d2i",
+    D2l = 0x8f => "d2l",
+    D2f = 0x90 => "d2f",
+    I2b = 0x91 => "i2b",
+    I2c = 0x92 => "i2c",
+    I2s = 0x93 => "i2s",
+    Lcmp = 0x94 => "lcmp",
+    Fcmpl = 0x95 => "fcmpl",
+    Fcmpg = 0x96 => "fcmpg",
+    Dcmpl = 0x97 => "dcmpl",
+    Dcmpg = 0x98 => "dcmpg",
+    Ifeq = 0x99 => "ifeq",
+    Ifne = 0x9a => "ifne",
+    Iflt = 0x9b => "iflt",
+    Ifge = 0x9c => "ifge",
+    Ifgt = 0x9d => "ifgt",
+    Ifle = 0x9e => "ifle",
+    IfIcmpeq = 0x9f => "if_icmpeq",
+    IfIcmpne = 0xa0 => "if_icmpne",
+    IfIcmplt = 0xa1 => "if_icmplt",
+    IfIcmpge = 0xa2 => "if_icmpge",
+    IfIcmpgt = 0xa3 => "if_icmpgt",
+    IfIcmple = 0xa4 => "if_icmple",
+    IfAcmpeq = 0xa5 => "if_acmpeq",
+    IfAcmpne = 0xa6 => "if_acmpne",
+    Goto = 0xa7 => "goto",
+    Jsr = 0xa8 => "jsr",
+    Ret = 0xa9 => "ret",
+    Tableswitch = 0xaa => "tableswitch",
+    Lookupswitch = 0xab => "lookupswitch",
+    Ireturn = 0xac => "ireturn",
+    Lreturn = 0xad => "lreturn",
+    Freturn = 0xae => "freturn",
+    Dreturn = 0xaf => "dreturn",
+    Areturn = 0xb0 => "areturn",
+    Return = 0xb1 => "return",
+    Getstatic = 0xb2 => "getstatic",
+    Putstatic = 0xb3 => "putstatic",
+    Getfield = 0xb4 => "getfield",
+    Putfield = 0xb5 => "putfield",
+    Invokevirtual = 0xb6 => "invokevirtual",
+    Invokespecial = 0xb7 => "invokespecial",
+    Invokestatic = 0xb8 => "invokestatic",
+    Invokeinterface = 0xb9 => "invokeinterface",
+    Invokedynamic = 0xba => "invokedynamic",
+    New = 0xbb => "new",
+    Newarray = 0xbc => "newarray",
+    Anewarray = 0xbd => "anewarray",
+    Arraylength = 0xbe => "arraylength",
+    Athrow = 0xbf => "athrow",
+    Checkcast = 0xc0 => "checkcast",
+    Instanceof = 0xc1 => "instanceof",
+    Monitorenter = 0xc2 => "monitorenter",
+    Monitorexit = 0xc3 => "monitorexit",
+    Wide = 0xc4 => "wide",
+    Multianewarray = 0xc5 => "multianewarray",
+    Ifnull = 0xc6 => "ifnull",
+    Ifnonnull = 0xc7 => "ifnonnull",
+    GotoW = 0xc8 => "goto_w",
+    JsrW = 0xc9 => "jsr_w",
+}
+
+impl Opcode {
+    /// True for the two-way branches, i.e. every `if*` instruction.
+    pub fn is_conditional_branch(&self) -> bool {
+        matches!(*self as u8, 0x99..=0xa6 | 0xc6 | 0xc7)
+    }
+
+    /// The conditional branch taken exactly when this one isn't.
+    pub fn inverted(&self) -> Option<Opcode> {
+        let inverted = match self {
+            Opcode::Ifeq => Opcode::Ifne,
+            Opcode::Ifne => Opcode::Ifeq,
+            Opcode::Iflt => Opcode::Ifge,
+            Opcode::Ifge => Opcode::Iflt,
+            Opcode::Ifgt => Opcode::Ifle,
+            Opcode::Ifle => Opcode::Ifgt,
+            Opcode::IfIcmpeq => Opcode::IfIcmpne,
+            Opcode::IfIcmpne => Opcode::IfIcmpeq,
+            Opcode::IfIcmplt => Opcode::IfIcmpge,
+            Opcode::IfIcmpge => Opcode::IfIcmplt,
+            Opcode::IfIcmpgt => Opcode::IfIcmple,
+            Opcode::IfIcmple => Opcode::IfIcmpgt,
+            Opcode::IfAcmpeq => Opcode::IfAcmpne,
+            Opcode::IfAcmpne => Opcode::IfAcmpeq,
+            Opcode::Ifnull => Opcode::Ifnonnull,
+            Opcode::Ifnonnull => Opcode::Ifnull,
+            _ => return None,
+        };
+        Some(inverted)
+    }
+
+    /// True for the instructions after which execution never falls through
+    /// to the next instruction.
+    pub fn ends_flow(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Goto
+                | Opcode::GotoW
+                | Opcode::Ret
+                | Opcode::Tableswitch
+                | Opcode::Lookupswitch
+                | Opcode::Ireturn
+                | Opcode::Lreturn
+                | Opcode::Freturn
+                | Opcode::Dreturn
+                | Opcode::Areturn
+                | Opcode::Return
+                | Opcode::Athrow
+        )
+    }
+
+    pub fn is_return(&self) -> bool {
+        matches!(*self as u8, 0xac..=0xb1)
+    }
+}
+
+impl Display for Opcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+/// The operand of an instruction. Branch targets are of type `T`, which is
+/// the absolute pc of the target for decoded code and a
+/// [`crate::assembler::Label`] for code that is being assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand<T> {
+    None,
+    // iload, lload, fload, dload, aload, the stores and ret
+    Local(u16),
+    // bipush
+    Byte(i8),
+    // sipush
+    Short(i16),
+    // ldc, ldc2_w, field and method instructions, new, anewarray, checkcast, instanceof
+    Constant(u16),
+    Iinc {
+        index: u16,
+        value: i16,
+    },
+    Branch(T),
+    TableSwitch {
+        default: T,
+        low: i32,
+        high: i32,
+        targets: Vec<T>,
+    },
+    LookupSwitch {
+        default: T,
+        pairs: Vec<(i32, T)>,
+    },
+    InvokeInterface {
+        index: u16,
+        count: u8,
+    },
+    // the atype of newarray
+    ArrayType(u8),
+    MultiANewArray {
+        index: u16,
+        dimensions: u8,
+    },
+}
+
+/// A single JVM instruction.
+///
+/// The short forms of the local variable instructions (`iload_0`...), `wide`,
+/// `ldc_w`, `goto_w` and `jsr_w` are not kept: decoding folds them into
+/// `iload`, `ldc`, `goto` and `jsr`, and encoding picks the shortest form that
+/// can hold the operand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction<T = u32> {
+    pub opcode: Opcode,
+    pub operand: Operand<T>,
+}
+
+impl<T> Instruction<T> {
+    pub fn new(opcode: Opcode, operand: Operand<T>) -> Self {
+        Self { opcode, operand }
+    }
+
+    /// An instruction without operands, such as `iadd` or `return`.
+    pub fn simple(opcode: Opcode) -> Self {
+        Self::new(opcode, Operand::None)
+    }
+
+    /// Converts the branch targets, keeping everything else.
+    pub fn map_targets<U>(self, mut f: impl FnMut(T) -> U) -> Instruction<U> {
+        let operand = match self.operand {
+            Operand::None => Operand::None,
+            Operand::Local(index) => Operand::Local(index),
+            Operand::Byte(value) => Operand::Byte(value),
+            Operand::Short(value) => Operand::Short(value),
+            Operand::Constant(index) => Operand::Constant(index),
+            Operand::Iinc { index, value } => Operand::Iinc { index, value },
+            Operand::Branch(target) => Operand::Branch(f(target)),
+            Operand::TableSwitch {
+                default,
+                low,
+                high,
+                targets,
+            } => Operand::TableSwitch {
+                default: f(default),
+                low,
+                high,
+                targets: targets.into_iter().map(&mut f).collect(),
+            },
+            Operand::LookupSwitch { default, pairs } => Operand::LookupSwitch {
+                default: f(default),
+                pairs: pairs.into_iter().map(|(k, t)| (k, f(t))).collect(),
+            },
+            Operand::InvokeInterface { index, count } => Operand::InvokeInterface { index, count },
+            Operand::ArrayType(atype) => Operand::ArrayType(atype),
+            Operand::MultiANewArray { index, dimensions } => {
+                Operand::MultiANewArray { index, dimensions }
+            }
+        };
+
+        Instruction {
+            opcode: self.opcode,
+            operand,
+        }
+    }
+
+    /// All the branch targets of this instruction, the default of a switch
+    /// first.
+    pub fn targets(&self) -> Vec<&T> {
+        match &self.operand {
+            Operand::Branch(target) => vec![target],
+            Operand::TableSwitch {
+                default, targets, ..
+            } => std::iter::once(default).chain(targets.iter()).collect(),
+            Operand::LookupSwitch { default, pairs } => std::iter::once(default)
+                .chain(pairs.iter().map(|(_, t)| t))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The constant pool index this instruction refers to, if any.
+    pub fn constant_index(&self) -> Option<u16> {
+        match self.operand {
+            Operand::Constant(index)
+            | Operand::InvokeInterface { index, .. }
+            | Operand::MultiANewArray { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The local variable this instruction reads or writes, if any.
+    pub fn local_index(&self) -> Option<u16> {
+        match self.operand {
+            Operand::Local(index) | Operand::Iinc { index, .. } => Some(index),
+            _ => None,
+        }
+    }
+
+    /// The encoded length at `pc`, with branches in their short form.
+    pub fn len_at(&self, pc: u32) -> usize {
+        match &self.operand {
+            Operand::None => 1,
+            Operand::Local(index) => {
+                if *index <= 3 && self.opcode != Opcode::Ret {
+                    1
+                } else if *index <= 0xff {
+                    2
+                } else {
+                    4
+                }
+            }
+            Operand::Byte(_) | Operand::ArrayType(_) => 2,
+            Operand::Short(_) | Operand::Branch(_) => 3,
+            Operand::Constant(index) => match self.opcode {
+                Opcode::Ldc if *index <= 0xff => 2,
+                Opcode::Invokedynamic => 5,
+                _ => 3,
+            },
+            Operand::Iinc { index, value } => {
+                if *index <= 0xff && i8::try_from(*value).is_ok() {
+                    3
+                } else {
+                    6
+                }
+            }
+            Operand::TableSwitch { targets, .. } => 1 + switch_padding(pc) + 12 + 4 * targets.len(),
+            Operand::LookupSwitch { pairs, .. } => 1 + switch_padding(pc) + 8 + 8 * pairs.len(),
+            Operand::InvokeInterface { .. } => 5,
+            Operand::MultiANewArray { .. } => 4,
+        }
+    }
+}
+
+/// The number of padding bytes after a `tableswitch` or `lookupswitch` at
+/// `pc`, so the operands start at a multiple of four from the start of the
+/// code.
+pub fn switch_padding(pc: u32) -> usize {
+    (3 - (pc as usize % 4)) % 4
+}
+
+impl Instruction<u32> {
+    /// Encodes the instruction at `pc`. `goto` and `jsr` use their wide
+    /// forms if the target is out of range of a 16 bit offset.
+    pub fn encode(&self, pc: u32, out: &mut Vec<u8>) -> DecompileResult<()> {
+        let offset = |target: u32| target as i64 - pc as i64;
+        let offset16 = |target: u32| {
+            i16::try_from(offset(target)).map_err(|_| DecompileError::BranchOutOfRange(pc, target))
+        };
+        let offset32 = |target: u32| offset(target) as i32;
+
+        match &self.operand {
+            Operand::None => out.push(self.opcode as u8),
+            Operand::Local(index) => {
+                let index = *index;
+                let base = match self.opcode {
+                    Opcode::Iload => Some(Opcode::Iload0),
+                    Opcode::Lload => Some(Opcode::Lload0),
+                    Opcode::Fload => Some(Opcode::Fload0),
+                    Opcode::Dload => Some(Opcode::Dload0),
+                    Opcode::Aload => Some(Opcode::Aload0),
+                    Opcode::Istore => Some(Opcode::Istore0),
+                    Opcode::Lstore => Some(Opcode::Lstore0),
+                    Opcode::Fstore => Some(Opcode::Fstore0),
+                    Opcode::Dstore => Some(Opcode::Dstore0),
+                    Opcode::Astore => Some(Opcode::Astore0),
+                    _ => None,
+                };
+                match base {
+                    Some(base) if index <= 3 => out.push(base as u8 + index as u8),
+                    _ if index <= 0xff => out.extend([self.opcode as u8, index as u8]),
+                    _ => {
+                        out.extend([Opcode::Wide as u8, self.opcode as u8]);
+                        out.extend(index.to_be_bytes());
+                    }
+                }
+            }
+            Operand::Byte(value) => out.extend([self.opcode as u8, *value as u8]),
+            Operand::Short(value) => {
+                out.push(self.opcode as u8);
+                out.extend(value.to_be_bytes());
+            }
+            Operand::Constant(index) => match self.opcode {
+                Opcode::Ldc | Opcode::LdcW if *index <= 0xff => {
+                    out.extend([Opcode::Ldc as u8, *index as u8])
+                }
+                Opcode::Ldc | Opcode::LdcW => {
+                    out.push(Opcode::LdcW as u8);
+                    out.extend(index.to_be_bytes());
+                }
+                Opcode::Invokedynamic => {
+                    out.push(self.opcode as u8);
+                    out.extend(index.to_be_bytes());
+                    out.extend([0, 0]);
+                }
+                _ => {
+                    out.push(self.opcode as u8);
+                    out.extend(index.to_be_bytes());
+                }
+            },
+            Operand::Iinc { index, value } => {
+                if *index <= 0xff && i8::try_from(*value).is_ok() {
+                    out.extend([self.opcode as u8, *index as u8, *value as i8 as u8]);
+                } else {
+                    out.extend([Opcode::Wide as u8, self.opcode as u8]);
+                    out.extend(index.to_be_bytes());
+                    out.extend(value.to_be_bytes());
+                }
+            }
+            Operand::Branch(target) => match self.opcode {
+                Opcode::Goto | Opcode::GotoW | Opcode::Jsr | Opcode::JsrW => {
+                    let short = matches!(self.opcode, Opcode::Goto | Opcode::Jsr)
+                        && offset16(*target).is_ok();
+                    let jump = matches!(self.opcode, Opcode::Goto | Opcode::GotoW);
+                    match (short, jump) {
+                        (true, true) => out.push(Opcode::Goto as u8),
+                        (true, false) => out.push(Opcode::Jsr as u8),
+                        (false, true) => out.push(Opcode::GotoW as u8),
+                        (false, false) => out.push(Opcode::JsrW as u8),
+                    }
+                    if short {
+                        out.extend(offset16(*target)?.to_be_bytes());
+                    } else {
+                        out.extend(offset32(*target).to_be_bytes());
+                    }
+                }
+                _ => {
+                    out.push(self.opcode as u8);
+                    out.extend(offset16(*target)?.to_be_bytes());
+                }
+            },
+            Operand::TableSwitch {
+                default,
+                low,
+                high,
+                targets,
+            } => {
+                out.push(self.opcode as u8);
+                out.extend(std::iter::repeat_n(0, switch_padding(pc)));
+                out.extend(offset32(*default).to_be_bytes());
+                out.extend(low.to_be_bytes());
+                out.extend(high.to_be_bytes());
+                for target in targets {
+                    out.extend(offset32(*target).to_be_bytes());
+                }
+            }
+            Operand::LookupSwitch { default, pairs } => {
+                out.push(self.opcode as u8);
+                out.extend(std::iter::repeat_n(0, switch_padding(pc)));
+                out.extend(offset32(*default).to_be_bytes());
+                out.extend((pairs.len() as i32).to_be_bytes());
+                for (key, target) in pairs {
+                    out.extend(key.to_be_bytes());
+                    out.extend(offset32(*target).to_be_bytes());
+                }
+            }
+            Operand::InvokeInterface { index, count } => {
+                out.push(self.opcode as u8);
+                out.extend(index.to_be_bytes());
+                out.extend([*count, 0]);
+            }
+            Operand::ArrayType(atype) => out.extend([self.opcode as u8, *atype]),
+            Operand::MultiANewArray { index, dimensions } => {
+                out.push(self.opcode as u8);
+                out.extend(index.to_be_bytes());
+                out.push(*dimensions);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Display> Display for Instruction<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        match &self.operand {
+            Operand::None => Ok(()),
+            Operand::Local(index) => write!(f, " {index}"),
+            Operand::Byte(value) => write!(f, " {value}"),
+            Operand::Short(value) => write!(f, " {value}"),
+            Operand::Constant(index) => write!(f, " #{index}"),
+            Operand::Iinc { index, value } => write!(f, " {index}, {value}"),
+            Operand::Branch(target) => write!(f, " {target}"),
+            Operand::TableSwitch {
+                default,
+                low,
+                high,
+                targets,
+            } => {
+                write!(f, " {{ ")?;
+                for (key, target) in (*low..=*high).zip(targets) {
+                    write!(f, "{key}: {target}, ")?;
+                }
+                write!(f, "default: {default} }}")
+            }
+            Operand::LookupSwitch { default, pairs } => {
+                write!(f, " {{ ")?;
+                for (key, target) in pairs {
+                    write!(f, "{key}: {target}, ")?;
+                }
+                write!(f, "default: {default} }}")
+            }
+            Operand::InvokeInterface { index, count } => write!(f, " #{index}, {count}"),
+            Operand::ArrayType(atype) => write!(f, " {}", array_type_name(*atype)),
+            Operand::MultiANewArray { index, dimensions } => write!(f, " #{index}, {dimensions}"),
+        }
+    }
+}
+
+/// The element type named by the `atype` operand of `newarray`.
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-6.html#jvms-6.5.newarray
+pub fn array_type_name(atype: u8) -> &'static str {
+    match atype {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "?",
+    }
+}
+
+/// Decodes the `code` array of a `Code` attribute into instructions, each
+/// with the pc it starts at.
+pub fn decode(code: &[u8]) -> DecompileResult<Vec<(u32, Instruction)>> {
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while pc < code.len() {
+        let (instruction, len) = decode_one(code, pc)?;
+        instructions.push((pc as u32, instruction));
+        pc += len;
+    }
+
+    Ok(instructions)
+}
+
+fn decode_one(code: &[u8], pc: usize) -> DecompileResult<(Instruction, usize)> {
+    let u8_at = |at: usize| -> DecompileResult<u8> {
+        code.get(at)
+            .copied()
+            .ok_or(DecompileError::TruncatedCode(pc as u32))
+    };
+    let u16_at = |at: usize| -> DecompileResult<u16> {
+        Ok(u16::from_be_bytes([u8_at(at)?, u8_at(at + 1)?]))
+    };
+    let i32_at = |at: usize| -> DecompileResult<i32> {
+        Ok(i32::from_be_bytes([
+            u8_at(at)?,
+            u8_at(at + 1)?,
+            u8_at(at + 2)?,
+            u8_at(at + 3)?,
+        ]))
+    };
+    let target = |offset: i32| (pc as i64 + offset as i64) as u32;
+
+    let byte = u8_at(pc)?;
+    let opcode = Opcode::from_u8(byte).ok_or(DecompileError::InvalidOpcode(byte, pc as u32))?;
+
+    use Opcode::*;
+    let decoded = match opcode {
+        Iload0 | Iload1 | Iload2 | Iload3 => short_form(Iload, byte - Iload0 as u8),
+        Lload0 | Lload1 | Lload2 | Lload3 => short_form(Lload, byte - Lload0 as u8),
+        Fload0 | Fload1 | Fload2 | Fload3 => short_form(Fload, byte - Fload0 as u8),
+        Dload0 | Dload1 | Dload2 | Dload3 => short_form(Dload, byte - Dload0 as u8),
+        Aload0 | Aload1 | Aload2 | Aload3 => short_form(Aload, byte - Aload0 as u8),
+        Istore0 | Istore1 | Istore2 | Istore3 => short_form(Istore, byte - Istore0 as u8),
+        Lstore0 | Lstore1 | Lstore2 | Lstore3 => short_form(Lstore, byte - Lstore0 as u8),
+        Fstore0 | Fstore1 | Fstore2 | Fstore3 => short_form(Fstore, byte - Fstore0 as u8),
+        Dstore0 | Dstore1 | Dstore2 | Dstore3 => short_form(Dstore, byte - Dstore0 as u8),
+        Astore0 | Astore1 | Astore2 | Astore3 => short_form(Astore, byte - Astore0 as u8),
+        Iload | Lload | Fload | Dload | Aload | Istore | Lstore | Fstore | Dstore | Astore
+        | Ret => (
+            Instruction::new(opcode, Operand::Local(u8_at(pc + 1)? as u16)),
+            2,
+        ),
+        Bipush => (
+            Instruction::new(opcode, Operand::Byte(u8_at(pc + 1)? as i8)),
+            2,
+        ),
+        Sipush => (
+            Instruction::new(opcode, Operand::Short(u16_at(pc + 1)? as i16)),
+            3,
+        ),
+        Ldc => (
+            Instruction::new(Ldc, Operand::Constant(u8_at(pc + 1)? as u16)),
+            2,
+        ),
+        LdcW => (Instruction::new(Ldc, Operand::Constant(u16_at(pc + 1)?)), 3),
+        Ldc2W | Getstatic | Putstatic | Getfield | Putfield | Invokevirtual | Invokespecial
+        | Invokestatic | New | Anewarray | Checkcast | Instanceof => (
+            Instruction::new(opcode, Operand::Constant(u16_at(pc + 1)?)),
+            3,
+        ),
+        Invokedynamic => (
+            Instruction::new(opcode, Operand::Constant(u16_at(pc + 1)?)),
+            5,
+        ),
+        Invokeinterface => (
+            Instruction::new(
+                opcode,
+                Operand::InvokeInterface {
+                    index: u16_at(pc + 1)?,
+                    count: u8_at(pc + 3)?,
+                },
+            ),
+            5,
+        ),
+        Multianewarray => (
+            Instruction::new(
+                opcode,
+                Operand::MultiANewArray {
+                    index: u16_at(pc + 1)?,
+                    dimensions: u8_at(pc + 3)?,
+                },
+            ),
+            4,
+        ),
+        Newarray => (
+            Instruction::new(opcode, Operand::ArrayType(u8_at(pc + 1)?)),
+            2,
+        ),
+        Iinc => (
+            Instruction::new(
+                opcode,
+                Operand::Iinc {
+                    index: u8_at(pc + 1)? as u16,
+                    value: u8_at(pc + 2)? as i8 as i16,
+                },
+            ),
+            3,
+        ),
+        Ifeq | Ifne | Iflt | Ifge | Ifgt | Ifle | IfIcmpeq | IfIcmpne | IfIcmplt | IfIcmpge
+        | IfIcmpgt | IfIcmple | IfAcmpeq | IfAcmpne | Goto | Jsr | Ifnull | Ifnonnull => (
+            Instruction::new(
+                opcode,
+                Operand::Branch(target(u16_at(pc + 1)? as i16 as i32)),
+            ),
+            3,
+        ),
+        GotoW => (
+            Instruction::new(Goto, Operand::Branch(target(i32_at(pc + 1)?))),
+            5,
+        ),
+        JsrW => (
+            Instruction::new(Jsr, Operand::Branch(target(i32_at(pc + 1)?))),
+            5,
+        ),
+        Tableswitch => {
+            let at = pc + 1 + switch_padding(pc as u32);
+            let default = target(i32_at(at)?);
+            let low = i32_at(at + 4)?;
+            let high = i32_at(at + 8)?;
+            if high < low {
+                return Err(DecompileError::InvalidSwitch(pc as u32));
+            }
+            let count = (high as i64 - low as i64 + 1) as usize;
+            let mut targets = Vec::with_capacity(count);
+            for i in 0..count {
+                targets.push(target(i32_at(at + 12 + 4 * i)?));
+            }
+            (
+                Instruction::new(
+                    opcode,
+                    Operand::TableSwitch {
+                        default,
+                        low,
+                        high,
+                        targets,
+                    },
+                ),
+                at + 12 + 4 * count - pc,
+            )
+        }
+        Lookupswitch => {
+            let at = pc + 1 + switch_padding(pc as u32);
+            let default = target(i32_at(at)?);
+            let npairs = i32_at(at + 4)?;
+            if npairs < 0 {
+                return Err(DecompileError::InvalidSwitch(pc as u32));
+            }
+            let mut pairs = Vec::with_capacity(npairs as usize);
+            for i in 0..npairs as usize {
+                pairs.push((i32_at(at + 8 + 8 * i)?, target(i32_at(at + 12 + 8 * i)?)));
+            }
+            (
+                Instruction::new(opcode, Operand::LookupSwitch { default, pairs }),
+                at + 8 + 8 * npairs as usize - pc,
+            )
+        }
+        Wide => {
+            let byte = u8_at(pc + 1)?;
+            let opcode =
+                Opcode::from_u8(byte).ok_or(DecompileError::InvalidOpcode(byte, pc as u32 + 1))?;
+            match opcode {
+                Iinc => (
+                    Instruction::new(
+                        opcode,
+                        Operand::Iinc {
+                            index: u16_at(pc + 2)?,
+                            value: u16_at(pc + 4)? as i16,
+                        },
+                    ),
+                    6,
+                ),
+                Iload | Lload | Fload | Dload | Aload | Istore | Lstore | Fstore | Dstore
+                | Astore | Ret => (Instruction::new(opcode, Operand::Local(u16_at(pc + 2)?)), 4),
+                _ => return Err(DecompileError::InvalidOpcode(byte, pc as u32 + 1)),
+            }
+        }
+        _ => (Instruction::simple(opcode), 1),
+    };
+
+    Ok(decoded)
+}
+
+fn short_form(opcode: Opcode, index: u8) -> (Instruction, usize) {
+    (Instruction::new(opcode, Operand::Local(index as u16)), 1)
+}
@@ -0,0 +1,708 @@
+use crate::cfg::{BlockId, Cfg};
+use crate::descriptor::FieldType;
+use crate::structure::{Case, Statement};
+
+/// A local variable or a temporary that holds a stack value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Var(pub usize);
+
+/// The JVM's computational types, which is all the bytecode says about a
+/// local or a stack value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl Kind {
+    pub fn of(field_type: &FieldType) -> Kind {
+        match field_type {
+            FieldType::Boolean
+            | FieldType::Byte
+            | FieldType::Char
+            | FieldType::Short
+            | FieldType::Int => Kind::Int,
+            FieldType::Long => Kind::Long,
+            FieldType::Float => Kind::Float,
+            FieldType::Double => Kind::Double,
+            FieldType::Object(_) | FieldType::Array(_) => Kind::Reference,
+        }
+    }
+
+    pub fn is_wide(&self) -> bool {
+        matches!(self, Kind::Long | Kind::Double)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Variable {
+    pub kind: Kind,
+    /// The local variable slot, `None` for stack temporaries.
+    pub slot: Option<u16>,
+    pub name: Option<String>,
+    pub field_type: Option<FieldType>,
+}
+
+/// A Java string as UTF-16 code units, so the unpaired surrogates Modified
+/// UTF-8 can hold aren't lost.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JavaString(pub Vec<u16>);
+
+impl JavaString {
+    pub fn to_string_lossy(&self) -> String {
+        String::from_utf16_lossy(&self.0)
+    }
+}
+
+impl From<&str> for JavaString {
+    fn from(value: &str) -> Self {
+        JavaString(value.encode_utf16().collect())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Const {
+    Null,
+    Int(i32),
+    // ints that type inference found to be used as a boolean or a char
+    Boolean(bool),
+    Char(u16),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(JavaString),
+    /// A class literal such as `String.class` or `int[].class`.
+    Class(FieldType),
+    // the loadable constants Java source can't express
+    MethodType(String),
+    MethodHandle {
+        kind: u8,
+        owner: String,
+        name: String,
+        descriptor: String,
+    },
+    Dynamic {
+        bootstrap: u16,
+        name: String,
+        descriptor: String,
+    },
+}
+
+/// The bootstrap method of an `invokedynamic`, with its static arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bootstrap {
+    /// Its index in the `BootstrapMethods` attribute.
+    pub index: u16,
+    /// The `MethodHandle` constant of the method.
+    pub method: Const,
+    pub arguments: Vec<Const>,
+}
+
+impl Bootstrap {
+    /// Whether the bootstrap method is `owner.name`.
+    pub fn is(&self, owner: &str, name: &str) -> bool {
+        matches!(&self.method, Const::MethodHandle { owner: o, name: n, .. } if o == owner && n == name)
+    }
+}
+
+/// What a pattern binds the value it matches to, in `instanceof` and in
+/// `case` labels.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.30
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `Type name`.
+    Binding(Var),
+    /// `Type(Component name, ...)`, matching each component of a record in
+    /// order.
+    Record(Vec<(FieldType, Pattern)>),
+}
+
+impl Pattern {
+    /// The variables the pattern declares.
+    pub fn bindings(&self) -> Vec<Var> {
+        match self {
+            Pattern::Binding(var) => vec![*var],
+            Pattern::Record(components) => components
+                .iter()
+                .flat_map(|(_, pattern)| pattern.bindings())
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Ushr,
+    And,
+    Or,
+    Xor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+/// `&&` and `||`, which only evaluate their right operand when the left one
+/// doesn't decide the result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogicalOp {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl CompareOp {
+    /// The comparison that holds exactly when this one doesn't, for integers
+    /// and references.
+    pub fn negate(&self) -> CompareOp {
+        match self {
+            CompareOp::Eq => CompareOp::Ne,
+            CompareOp::Ne => CompareOp::Eq,
+            CompareOp::Lt => CompareOp::Ge,
+            CompareOp::Ge => CompareOp::Lt,
+            CompareOp::Gt => CompareOp::Le,
+            CompareOp::Le => CompareOp::Gt,
+        }
+    }
+
+    /// Whether `a op b` holds given the sign of `a - b`.
+    pub fn holds(&self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            CompareOp::Eq => ordering == Equal,
+            CompareOp::Ne => ordering != Equal,
+            CompareOp::Lt => ordering == Less,
+            CompareOp::Ge => ordering != Less,
+            CompareOp::Gt => ordering == Greater,
+            CompareOp::Le => ordering != Greater,
+        }
+    }
+}
+
+/// `lcmp`, `fcmpl`, `fcmpg`, `dcmpl` and `dcmpg`, which push -1, 0 or 1.
+/// The `l` and `g` forms push -1 and 1 for NaN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpKind {
+    Long,
+    FloatL,
+    FloatG,
+    DoubleL,
+    DoubleG,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InvokeKind {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Const(Const),
+    Var(Var),
+    /// A static field when `object` is `None`.
+    Field {
+        owner: String,
+        name: String,
+        descriptor: String,
+        object: Option<Box<Expr>>,
+    },
+    ArrayElement {
+        array: Box<Expr>,
+        index: Box<Expr>,
+    },
+    ArrayLength(Box<Expr>),
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Compare {
+        op: CompareOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    Logical {
+        op: LogicalOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// `condition ? then : otherwise`.
+    Conditional {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    /// A three way comparison left as a value rather than folded into a
+    /// branch condition.
+    Cmp {
+        kind: CmpKind,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    /// A primitive conversion or a `checkcast`.
+    Cast {
+        field_type: FieldType,
+        operand: Box<Expr>,
+    },
+    /// `operand instanceof field_type`, binding the operand to the pattern
+    /// when it has one and the test holds.
+    InstanceOf {
+        operand: Box<Expr>,
+        field_type: FieldType,
+        pattern: Option<Pattern>,
+    },
+    /// A static method when `object` is `None`. A `Special` call to `<init>`
+    /// is a `super(...)` or `this(...)` constructor call.
+    Invoke {
+        kind: InvokeKind,
+        owner: String,
+        name: String,
+        descriptor: String,
+        object: Option<Box<Expr>>,
+        args: Vec<Expr>,
+    },
+    InvokeDynamic {
+        bootstrap: Bootstrap,
+        name: String,
+        descriptor: String,
+        args: Vec<Expr>,
+    },
+    /// `new` with its constructor call.
+    New {
+        class: String,
+        descriptor: String,
+        args: Vec<Expr>,
+    },
+    /// `field_type` is the type of the array created, with a length for
+    /// each of its leading dimensions.
+    NewArray {
+        field_type: FieldType,
+        dimensions: Vec<Expr>,
+    },
+    /// An array creation with an initializer, `new int[]{1, 2, 3}`, where
+    /// `field_type` is the type of the array.
+    ArrayInit {
+        field_type: FieldType,
+        elements: Vec<Expr>,
+    },
+    /// A lambda expression implementing the functional interface
+    /// `interface`. A cast such as `(Runnable & Serializable)` adds the
+    /// `markers`. Until structuring, the first parameters are those the
+    /// `captured` values are passed in, which are then put in their place.
+    Lambda {
+        interface: String,
+        markers: Vec<String>,
+        captured: Vec<Expr>,
+        parameters: Vec<Var>,
+        body: Vec<Statement>,
+    },
+    /// A method reference: `Owner::name` without an `object`, which is
+    /// `object::name`, and `Owner::new` for a constructor.
+    MethodRef {
+        interface: String,
+        markers: Vec<String>,
+        owner: String,
+        name: String,
+        object: Option<Box<Expr>>,
+    },
+    /// A switch expression, whose cases end in `yield` or `throw`.
+    Switch {
+        value: Box<Expr>,
+        cases: Vec<Case>,
+    },
+    /// An assignment used as a value.
+    Assign {
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    /// The exception a handler was entered with.
+    CaughtException,
+    /// The object made by the `new` at `pc` before its constructor has run.
+    /// Only seen while lifting.
+    Uninitialized {
+        class: String,
+        pc: u32,
+    },
+}
+
+impl Expr {
+    pub fn var(var: Var) -> Expr {
+        Expr::Var(var)
+    }
+
+    pub fn int(value: i32) -> Expr {
+        Expr::Const(Const::Int(value))
+    }
+
+    pub fn binary(op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    pub fn compare(op: CompareOp, left: Expr, right: Expr) -> Expr {
+        Expr::Compare {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    pub fn logical(op: LogicalOp, left: Expr, right: Expr) -> Expr {
+        Expr::Logical {
+            op,
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    pub fn conditional(condition: Expr, then: Expr, otherwise: Expr) -> Expr {
+        Expr::Conditional {
+            condition: Box::new(condition),
+            then: Box::new(then),
+            otherwise: Box::new(otherwise),
+        }
+    }
+
+    pub fn logical_not(operand: Expr) -> Expr {
+        Expr::Unary {
+            op: UnaryOp::Not,
+            operand: Box::new(operand),
+        }
+    }
+
+    /// The direct subexpressions, in evaluation order. Only one of the
+    /// branches of a conditional is evaluated, and the right operand of a
+    /// logical operator may not be. A lambda's are the values it captures,
+    /// which are evaluated where it is created.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Const(_) | Expr::Var(_) | Expr::CaughtException | Expr::Uninitialized { .. } => {
+                Vec::new()
+            }
+            Expr::Field { object, .. } | Expr::MethodRef { object, .. } => {
+                object.iter().map(|object| &**object).collect()
+            }
+            Expr::ArrayElement { array, index } => vec![array, index],
+            Expr::ArrayLength(operand)
+            | Expr::Unary { operand, .. }
+            | Expr::Cast { operand, .. }
+            | Expr::InstanceOf { operand, .. } => vec![operand],
+            Expr::Binary { left, right, .. }
+            | Expr::Logical { left, right, .. }
+            | Expr::Compare { left, right, .. }
+            | Expr::Cmp { left, right, .. } => vec![left, right],
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => vec![condition, then, otherwise],
+            Expr::Invoke { object, args, .. } => {
+                object.iter().map(|object| &**object).chain(args).collect()
+            }
+            Expr::InvokeDynamic { args, .. }
+            | Expr::New { args, .. }
+            | Expr::Lambda { captured: args, .. } => args.iter().collect(),
+            Expr::NewArray { dimensions, .. } => dimensions.iter().collect(),
+            Expr::ArrayInit { elements, .. } => elements.iter().collect(),
+            Expr::Switch { value, cases } => std::iter::once(&**value)
+                .chain(cases.iter().filter_map(|case| case.guard.as_ref()))
+                .collect(),
+            Expr::Assign { target, value } => vec![target, value],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Const(_) | Expr::Var(_) | Expr::CaughtException | Expr::Uninitialized { .. } => {
+                Vec::new()
+            }
+            Expr::Field { object, .. } | Expr::MethodRef { object, .. } => {
+                object.iter_mut().map(|object| &mut **object).collect()
+            }
+            Expr::ArrayElement { array, index } => vec![array, index],
+            Expr::ArrayLength(operand)
+            | Expr::Unary { operand, .. }
+            | Expr::Cast { operand, .. }
+            | Expr::InstanceOf { operand, .. } => vec![operand],
+            Expr::Binary { left, right, .. }
+            | Expr::Logical { left, right, .. }
+            | Expr::Compare { left, right, .. }
+            | Expr::Cmp { left, right, .. } => vec![left, right],
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => vec![condition, then, otherwise],
+            Expr::Invoke { object, args, .. } => object
+                .iter_mut()
+                .map(|object| &mut **object)
+                .chain(args)
+                .collect(),
+            Expr::InvokeDynamic { args, .. }
+            | Expr::New { args, .. }
+            | Expr::Lambda { captured: args, .. } => args.iter_mut().collect(),
+            Expr::NewArray { dimensions, .. } => dimensions.iter_mut().collect(),
+            Expr::ArrayInit { elements, .. } => elements.iter_mut().collect(),
+            Expr::Switch { value, cases } => std::iter::once(&mut **value)
+                .chain(cases.iter_mut().filter_map(|case| case.guard.as_mut()))
+                .collect(),
+            Expr::Assign { target, value } => vec![target, value],
+        }
+    }
+
+    /// Calls `f` on this expression and everything inside it, outermost
+    /// first.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        for child in self.children() {
+            child.walk(f);
+        }
+    }
+
+    /// Calls `f` on everything inside this expression and then on the
+    /// expression itself, so `f` sees its children already rewritten.
+    pub fn walk_mut(&mut self, f: &mut impl FnMut(&mut Expr)) {
+        for child in self.children_mut() {
+            child.walk_mut(f);
+        }
+        f(self);
+    }
+
+    /// Whether evaluating the expression can change anything other than
+    /// throwing: calls, object creation, assignments and the statements of
+    /// switch expressions. The children of a switch expression are only its
+    /// value and guards.
+    pub fn has_side_effects(&self) -> bool {
+        let mut effects = false;
+        self.walk(&mut |expr| {
+            effects |= matches!(
+                expr,
+                Expr::Invoke { .. }
+                    | Expr::InvokeDynamic { .. }
+                    | Expr::New { .. }
+                    | Expr::NewArray { .. }
+                    | Expr::ArrayInit { .. }
+                    | Expr::Assign { .. }
+                    | Expr::Switch { .. }
+            )
+        });
+        effects
+    }
+
+    /// Whether the expression reads a variable.
+    pub fn uses(&self, var: Var) -> bool {
+        let mut uses = false;
+        self.walk(&mut |expr| uses |= *expr == Expr::Var(var));
+        uses
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    /// A store to a variable, field or array element.
+    Assign {
+        target: Expr,
+        value: Expr,
+    },
+    /// An expression evaluated for its side effects.
+    Expr(Expr),
+    MonitorEnter(Expr),
+    MonitorExit(Expr),
+}
+
+impl Stmt {
+    pub fn assign(target: Expr, value: Expr) -> Stmt {
+        Stmt::Assign { target, value }
+    }
+
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Assign { target, value } => vec![target, value],
+            Stmt::Expr(expr) | Stmt::MonitorEnter(expr) | Stmt::MonitorExit(expr) => vec![expr],
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Assign { target, value } => vec![target, value],
+            Stmt::Expr(expr) | Stmt::MonitorEnter(expr) | Stmt::MonitorExit(expr) => vec![expr],
+        }
+    }
+
+    /// The expressions the statement reads: everything but a variable it
+    /// stores to.
+    pub fn uses(&self) -> Vec<&Expr> {
+        match self {
+            Stmt::Assign {
+                target: Expr::Var(_),
+                value,
+            } => vec![value],
+            Stmt::Assign { target, value } => {
+                let mut uses = target.children();
+                uses.push(value);
+                uses
+            }
+            _ => self.exprs(),
+        }
+    }
+
+    pub fn uses_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Stmt::Assign {
+                target: Expr::Var(_),
+                value,
+            } => vec![value],
+            Stmt::Assign { target, value } => {
+                let mut uses = target.children_mut();
+                uses.push(value);
+                uses
+            }
+            _ => self.exprs_mut(),
+        }
+    }
+
+    /// The variable this statement stores to, if any.
+    pub fn defined_var(&self) -> Option<Var> {
+        match self {
+            Stmt::Assign {
+                target: Expr::Var(var),
+                ..
+            } => Some(*var),
+            _ => None,
+        }
+    }
+}
+
+/// How control leaves a block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Goto(BlockId),
+    If {
+        condition: Expr,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    Switch {
+        value: Expr,
+        cases: Vec<(i32, BlockId)>,
+        default: BlockId,
+    },
+    Return(Option<Expr>),
+    Throw(Expr),
+    /// The block is never reached, so it wasn't lifted.
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Terminator::If { condition, .. } => vec![condition],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Return(Some(expr)) | Terminator::Throw(expr) => vec![expr],
+            Terminator::Goto(_) | Terminator::Return(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Terminator::If { condition, .. } => vec![condition],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Return(Some(expr)) | Terminator::Throw(expr) => vec![expr],
+            Terminator::Goto(_) | Terminator::Return(None) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+
+    /// The blocks control can go to, not counting exceptions.
+    pub fn targets(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::If {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Switch { cases, default, .. } => cases
+                .iter()
+                .map(|(_, target)| *target)
+                .chain(std::iter::once(*default))
+                .collect(),
+            Terminator::Return(_) | Terminator::Throw(_) | Terminator::Unreachable => Vec::new(),
+        }
+    }
+}
+
+/// An SSA phi function: `target` takes the value of the source for the
+/// predecessor control came from. A handler has a source for every version a
+/// variable has inside a protected block, since the exception can be thrown
+/// anywhere in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Phi {
+    pub target: Var,
+    pub sources: Vec<(BlockId, Var)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Only in SSA form, see [`crate::ssa`].
+    pub phis: Vec<Phi>,
+    pub stmts: Vec<Stmt>,
+    pub terminator: Terminator,
+}
+
+/// A method body lifted from bytecode. Blocks have the same ids as in `cfg`.
+#[derive(Debug, Clone)]
+pub struct Body {
+    pub cfg: Cfg,
+    pub blocks: Vec<Block>,
+    pub variables: Vec<Variable>,
+    /// `this`, if the method isn't static, followed by the parameters.
+    pub parameters: Vec<Var>,
+    /// `None` for `void`.
+    pub return_type: Option<FieldType>,
+    pub is_static: bool,
+}
+
+impl Body {
+    pub fn variable(&self, var: Var) -> &Variable {
+        &self.variables[var.0]
+    }
+
+    pub fn new_variable(&mut self, variable: Variable) -> Var {
+        self.variables.push(variable);
+        Var(self.variables.len() - 1)
+    }
+
+    /// `this`, for instance methods.
+    pub fn this(&self) -> Option<Var> {
+        (!self.is_static).then(|| self.parameters[0])
+    }
+}
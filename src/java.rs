@@ -0,0 +1,452 @@
+/// A Java source file, as [`crate::source`] decompiles a class into and
+/// [`crate::printer`] prints. Names of classes are as they are written in
+/// the source, so imports are already worked out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompilationUnit {
+    /// The package, with dots, or `None` for the unnamed package.
+    pub package: Option<String>,
+    /// The qualified names of the classes imported.
+    pub imports: Vec<String>,
+    pub types: Vec<TypeDeclaration>,
+}
+
+/// A modifier of a class, member or local class, in the order written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Public,
+    Protected,
+    Private,
+    Abstract,
+    Static,
+    Final,
+    Transient,
+    Volatile,
+    Synchronized,
+    Native,
+    Strictfp,
+    /// The `default` of an interface method with a body.
+    Default,
+}
+
+impl Modifier {
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Modifier::Public => "public",
+            Modifier::Protected => "protected",
+            Modifier::Private => "private",
+            Modifier::Abstract => "abstract",
+            Modifier::Static => "static",
+            Modifier::Final => "final",
+            Modifier::Transient => "transient",
+            Modifier::Volatile => "volatile",
+            Modifier::Synchronized => "synchronized",
+            Modifier::Native => "native",
+            Modifier::Strictfp => "strictfp",
+            Modifier::Default => "default",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeKind {
+    Class,
+    Interface,
+    Enum,
+    Annotation,
+    Record,
+}
+
+/// The declaration of a class, interface, enum, annotation interface or
+/// record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDeclaration {
+    pub modifiers: Vec<Modifier>,
+    pub kind: TypeKind,
+    pub name: String,
+    /// The superclass of a class, or the superinterfaces of an interface.
+    pub extends: Vec<Type>,
+    pub implements: Vec<Type>,
+    /// The components of a record, which its header declares.
+    pub components: Vec<Parameter>,
+    /// The constants of an enum, which come before its other members.
+    pub constants: Vec<EnumConstant>,
+    pub members: Vec<Member>,
+}
+
+/// An enum constant, with the arguments of its constructor and its class
+/// body if it has them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumConstant {
+    pub name: String,
+    pub args: Vec<Expr>,
+    pub body: Option<Vec<Member>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Member {
+    Field(Field),
+    Method(Method),
+    /// An instance or static initializer.
+    Initializer {
+        is_static: bool,
+        body: Vec<Statement>,
+    },
+    Type(TypeDeclaration),
+    /// A line comment, standing for a member that couldn't be decompiled.
+    Comment(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub modifiers: Vec<Modifier>,
+    pub field_type: Type,
+    pub name: String,
+    pub value: Option<Expr>,
+}
+
+/// A method or constructor. A method without a body is abstract or
+/// native.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub modifiers: Vec<Modifier>,
+    /// `None` for a constructor, which is named after its class.
+    pub return_type: Option<Type>,
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub throws: Vec<Type>,
+    pub body: Option<Vec<Statement>>,
+}
+
+/// A formal parameter. The type of a variable arity parameter is that of
+/// its elements.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub parameter_type: Type,
+    pub name: String,
+    pub varargs: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Boolean,
+    Byte,
+    Char,
+    Short,
+    Int,
+    Long,
+    Float,
+    Double,
+    Void,
+    /// A class or interface by its name in the source.
+    Class(String),
+    Array(Box<Type>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// An expression statement.
+    Expr(Expr),
+    /// A local variable declaration.
+    Declare {
+        var_type: Type,
+        name: String,
+        value: Option<Expr>,
+    },
+    /// A local class declaration.
+    Class(TypeDeclaration),
+    Block {
+        label: Option<String>,
+        body: Vec<Statement>,
+    },
+    /// An `if` statement, whose `else` is left out when it is empty.
+    If {
+        condition: Expr,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    While {
+        label: Option<String>,
+        condition: Expr,
+        body: Vec<Statement>,
+    },
+    DoWhile {
+        label: Option<String>,
+        body: Vec<Statement>,
+        condition: Expr,
+    },
+    /// A basic `for` statement, which starts with local variable
+    /// declarations or expression statements.
+    For {
+        label: Option<String>,
+        init: Vec<Statement>,
+        condition: Expr,
+        update: Vec<Expr>,
+        body: Vec<Statement>,
+    },
+    /// A `switch` statement, whose cases fall through.
+    Switch {
+        label: Option<String>,
+        value: Expr,
+        cases: Vec<SwitchCase>,
+    },
+    Try {
+        resources: Vec<Resource>,
+        body: Vec<Statement>,
+        catches: Vec<Catch>,
+        finally: Option<Vec<Statement>>,
+    },
+    Synchronized {
+        lock: Expr,
+        body: Vec<Statement>,
+    },
+    Break(Option<String>),
+    Continue(Option<String>),
+    Return(Option<Expr>),
+    Throw(Expr),
+    Yield(Expr),
+    Assert {
+        condition: Expr,
+        message: Option<Expr>,
+    },
+    /// A line comment.
+    Comment(String),
+}
+
+/// A group of switch labels and the statements they lead to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchCase {
+    pub labels: Vec<CaseLabel>,
+    /// What has to hold as well for a pattern to match, after `when`.
+    pub guard: Option<Expr>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseLabel {
+    /// A constant expression or the name of an enum constant.
+    Expr(Expr),
+    Null,
+    Pattern(Pattern),
+    Default,
+}
+
+/// A type pattern, or a record pattern deconstructing a record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// A type, with the variable it binds if it binds one.
+    Type {
+        pattern_type: Type,
+        name: Option<String>,
+    },
+    Record {
+        record_type: Type,
+        components: Vec<Pattern>,
+    },
+}
+
+/// A resource of a `try`-with-resources statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resource {
+    Declare {
+        var_type: Type,
+        name: String,
+        value: Expr,
+    },
+    /// A variable declared before.
+    Expr(Expr),
+}
+
+/// A `catch` clause. A multi-catch has more than one type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catch {
+    pub types: Vec<Type>,
+    pub name: String,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Literal),
+    /// A simple name of a variable or field.
+    Name(String),
+    /// `this`, or `Outer.this` with the class it names.
+    This(Option<String>),
+    /// `super`, as the object of a method call.
+    Super,
+    /// A type as the qualifier of a static member or a method reference.
+    Type(Type),
+    /// `int.class`.
+    Class(Type),
+    Field {
+        object: Box<Expr>,
+        name: String,
+    },
+    ArrayAccess {
+        array: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// A method call, qualified with its object or class if it has one.
+    Call {
+        object: Option<Box<Expr>>,
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `this(...)` or `super(...)` at the start of a constructor, with the
+    /// enclosing instance `super(...)` is qualified with if it is.
+    ConstructorCall {
+        qualifier: Option<Box<Expr>>,
+        is_super: bool,
+        args: Vec<Expr>,
+    },
+    /// A class instance creation, `outer.new Inner()` when it has a
+    /// qualifier, with the body of an anonymous class if it has one.
+    New {
+        qualifier: Option<Box<Expr>>,
+        class: Type,
+        args: Vec<Expr>,
+        body: Option<Vec<Member>>,
+    },
+    /// `new int[2][]`: an array creation with the lengths of its first
+    /// dimensions and how many more there are.
+    NewArray {
+        element: Type,
+        dimensions: Vec<Expr>,
+        extra_dimensions: usize,
+    },
+    /// `new int[]{1, 2}`, or `{1, 2}` without a type as an element of
+    /// another array initializer.
+    ArrayInit {
+        array_type: Option<Type>,
+        elements: Vec<Expr>,
+    },
+    Unary {
+        op: UnaryOp,
+        operand: Box<Expr>,
+    },
+    Binary {
+        op: BinaryOp,
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+    InstanceOf {
+        operand: Box<Expr>,
+        pattern: Pattern,
+    },
+    /// A cast, to an intersection of types when there are several.
+    Cast {
+        types: Vec<Type>,
+        operand: Box<Expr>,
+    },
+    Conditional {
+        condition: Box<Expr>,
+        then: Box<Expr>,
+        otherwise: Box<Expr>,
+    },
+    /// `target = value`, or a compound assignment such as `target += value`
+    /// with its operator.
+    Assign {
+        op: Option<BinaryOp>,
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    Lambda {
+        parameters: Vec<String>,
+        body: LambdaBody,
+    },
+    /// `object::name`, where `name` is `new` for a constructor.
+    MethodRef {
+        object: Box<Expr>,
+        name: String,
+    },
+    /// A `switch` expression, whose cases don't fall through.
+    Switch {
+        value: Box<Expr>,
+        cases: Vec<SwitchCase>,
+    },
+    /// An expression with a `/* ... */` comment before it.
+    Commented {
+        comment: String,
+        expr: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LambdaBody {
+    Expr(Box<Expr>),
+    Block(Vec<Statement>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Plus,
+    Neg,
+    Not,
+    Complement,
+    PreIncrement,
+    PreDecrement,
+    PostIncrement,
+    PostDecrement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Mul,
+    Div,
+    Rem,
+    Add,
+    Sub,
+    Shl,
+    Shr,
+    Ushr,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    BitAnd,
+    Xor,
+    BitOr,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Ushr => ">>>",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::Xor => "^",
+            BinaryOp::BitOr => "|",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        }
+    }
+}
+
+/// A literal. Strings and chars are UTF-16 code units, which may be
+/// unpaired surrogates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Boolean(bool),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Char(u16),
+    String(Vec<u16>),
+}
@@ -0,0 +1,447 @@
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::ir::{Body, Const, Expr, InvokeKind, Kind, Pattern, Stmt, Terminator, Var, Variable};
+use crate::structure::{self, CaseLabel, Statement};
+
+const LAMBDA_METAFACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+
+// The kinds of method handles a lambda can be made of.
+// See https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-5.html#jvms-5.4.3.5
+const REF_INVOKE_VIRTUAL: u8 = 5;
+const REF_INVOKE_STATIC: u8 = 6;
+const REF_INVOKE_SPECIAL: u8 = 7;
+const REF_NEW_INVOKE_SPECIAL: u8 = 8;
+const REF_INVOKE_INTERFACE: u8 = 9;
+
+/// Decompiles the method of the class with a name and descriptor, when a
+/// lambda can be made of it.
+pub type Decompile<'a> = dyn FnMut(&str, &str) -> Option<(Body, Vec<Statement>)> + 'a;
+
+/// Turns the objects `LambdaMetafactory` makes back into lambdas and method
+/// references, in a body out of SSA form. javac compiles the body of a
+/// lambda into a synthetic method of the class, such as `lambda$main$0`,
+/// whose first parameters take what the lambda captures:
+///   invokedynamic apply(prefix) [metafactory, lambda$main$0]
+/// `decompile` is given the name and descriptor of a method of the class
+/// `this_class`, and decompiles it if it is one of these. Its body becomes
+/// the lambda's, and its variables are added to `body`'s.
+///
+/// A method reference is compiled to a handle of the method it names. It
+/// stays one when the interface method's parameters are the method's, and
+/// becomes a lambda calling the method otherwise, as types are written
+/// without their type arguments, which leaves `String::length` nothing to
+/// resolve to as a raw `Function`. For the same reason, the parameters of
+/// lambdas take the types of the interface method's, cast to the types they
+/// had where they differ.
+///
+/// The `Objects.requireNonNull` javac calls on the object of a method
+/// reference is left out, as the reference checks it as well, and so are
+/// the casts of an intersection type, which become the lambda's markers.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.27.4
+pub fn rebuild_lambdas(body: &mut Body, this_class: &str, decompile: &mut Decompile) {
+    for id in 0..body.blocks.len() {
+        let mut stmts = std::mem::take(&mut body.blocks[id].stmts);
+        let mut terminator =
+            std::mem::replace(&mut body.blocks[id].terminator, Terminator::Unreachable);
+        let mut index = 0;
+        while index <= stmts.len() {
+            let mut objects = Vec::new();
+            let exprs = match stmts.get_mut(index) {
+                Some(stmt) => stmt.exprs_mut(),
+                None => terminator.exprs_mut(),
+            };
+            for expr in exprs {
+                expr.walk_mut(&mut |expr| {
+                    if let Some(lambda) = lambda(body, this_class, decompile, expr) {
+                        if let Expr::MethodRef {
+                            object: Some(object),
+                            ..
+                        } = &lambda
+                        {
+                            objects.push((**object).clone());
+                        }
+                        *expr = lambda;
+                    }
+                    strip_cast(expr);
+                });
+            }
+            let checked = index > 0
+                && objects.iter().any(|object| {
+                    let previous = index.checked_sub(2).map(|previous| &stmts[previous]);
+                    checks_null(&stmts[index - 1], object, previous)
+                });
+            if checked {
+                stmts.remove(index - 1);
+            } else {
+                index += 1;
+            }
+        }
+        body.blocks[id].stmts = stmts;
+        body.blocks[id].terminator = terminator;
+    }
+}
+
+// The lambda or method reference an `invokedynamic` makes.
+fn lambda(
+    body: &mut Body,
+    this_class: &str,
+    decompile: &mut Decompile,
+    expr: &Expr,
+) -> Option<Expr> {
+    let Expr::InvokeDynamic {
+        bootstrap,
+        descriptor,
+        args,
+        ..
+    } = expr
+    else {
+        return None;
+    };
+    if !bootstrap.is(LAMBDA_METAFACTORY, "metafactory")
+        && !bootstrap.is(LAMBDA_METAFACTORY, "altMetafactory")
+    {
+        return None;
+    }
+    let [Const::MethodType(method_type), Const::MethodHandle {
+        kind,
+        owner,
+        name,
+        descriptor: target,
+    }, ..] = bootstrap.arguments.as_slice()
+    else {
+        return None;
+    };
+    let Some(FieldType::Object(interface)) = MethodDescriptor::parse(descriptor)?.return_type
+    else {
+        return None;
+    };
+    let method = MethodDescriptor::parse(method_type)?;
+    let handle = Handle {
+        kind: *kind,
+        owner,
+        name,
+        descriptor: target,
+        parameters: handle_parameters(*kind, owner, target)?,
+    };
+    if handle.parameters.len() != args.len() + method.parameters.len() {
+        return None;
+    }
+
+    if owner == this_class {
+        if let Some((lambda, statements)) = decompile(name, target) {
+            return Some(inline(body, lambda, statements, interface, &method, args));
+        }
+    }
+    Some(
+        method_reference(&handle, &interface, &method, args)
+            .unwrap_or_else(|| call(body, &handle, interface, &method, args)),
+    )
+}
+
+// The method handle a lambda calls.
+struct Handle<'a> {
+    kind: u8,
+    owner: &'a str,
+    name: &'a str,
+    descriptor: &'a str,
+    // the object the method is called on first, unless it is static or a
+    // constructor
+    parameters: Vec<FieldType>,
+}
+
+fn handle_parameters(kind: u8, owner: &str, descriptor: &str) -> Option<Vec<FieldType>> {
+    let mut parameters = match kind {
+        REF_INVOKE_STATIC | REF_NEW_INVOKE_SPECIAL => Vec::new(),
+        REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE => {
+            vec![FieldType::from_class_name(owner)?]
+        }
+        _ => return None,
+    };
+    parameters.extend(MethodDescriptor::parse(descriptor)?.parameters);
+    Some(parameters)
+}
+
+// A lambda whose body was decompiled from its method. Its parameters get
+// the types of the interface method's, and are cast to the method's at the
+// start of the body where they differ.
+fn inline(
+    body: &mut Body,
+    lambda: Body,
+    mut statements: Vec<Statement>,
+    interface: String,
+    method: &MethodDescriptor,
+    captured: &[Expr],
+) -> Expr {
+    let offset = body.variables.len();
+    body.variables.extend(lambda.variables);
+    renumber(&mut statements, offset);
+    let mut parameters: Vec<Var> = lambda
+        .parameters
+        .iter()
+        .map(|parameter| Var(parameter.0 + offset))
+        .collect();
+    let mut casts = Vec::new();
+    for (parameter, field_type) in parameters[captured.len()..]
+        .iter_mut()
+        .zip(&method.parameters)
+    {
+        let declared = body.variable(*parameter).field_type.clone();
+        let Some(declared) = declared.filter(|declared| declared != field_type) else {
+            continue;
+        };
+        let erased = body.new_variable(Variable {
+            kind: Kind::of(field_type),
+            slot: None,
+            name: None,
+            field_type: Some(field_type.clone()),
+        });
+        casts.push(Statement::Declare {
+            var: *parameter,
+            value: Some(Expr::Cast {
+                field_type: declared,
+                operand: Box::new(Expr::Var(erased)),
+            }),
+        });
+        *parameter = erased;
+    }
+    statements.splice(..0, casts);
+    Expr::Lambda {
+        interface,
+        markers: Vec::new(),
+        captured: captured.to_vec(),
+        parameters,
+        body: statements,
+    }
+}
+
+// Moves the variables of a lambda's body to after those of the method it is
+// in.
+fn renumber(statements: &mut [Statement], offset: usize) {
+    let shift = |var: &mut Var| var.0 += offset;
+    structure::walk_statements_mut(statements, &mut |statement| {
+        match statement {
+            Statement::Declare { var, .. } => shift(var),
+            Statement::Switch { cases, .. } => {
+                for label in cases.iter_mut().flat_map(|case| &mut case.labels) {
+                    if let CaseLabel::Pattern(_, pattern) = label {
+                        renumber_pattern(pattern, offset);
+                    }
+                }
+            }
+            Statement::Try {
+                resources, catches, ..
+            } => {
+                resources
+                    .iter_mut()
+                    .for_each(|resource| shift(&mut resource.var));
+                catches.iter_mut().for_each(|catch| shift(&mut catch.var));
+            }
+            _ => {}
+        }
+        for expr in statement.exprs_mut() {
+            expr.walk_mut(&mut |expr| match expr {
+                Expr::Var(var) => shift(var),
+                Expr::InstanceOf {
+                    pattern: Some(pattern),
+                    ..
+                } => renumber_pattern(pattern, offset),
+                Expr::Switch { cases, .. } => {
+                    for label in cases.iter_mut().flat_map(|case| &mut case.labels) {
+                        if let CaseLabel::Pattern(_, pattern) = label {
+                            renumber_pattern(pattern, offset);
+                        }
+                    }
+                }
+                Expr::Lambda { parameters, .. } => parameters.iter_mut().for_each(shift),
+                _ => {}
+            });
+        }
+    });
+}
+
+fn renumber_pattern(pattern: &mut Pattern, offset: usize) {
+    match pattern {
+        Pattern::Binding(var) => var.0 += offset,
+        Pattern::Record(components) => {
+            for (_, component) in components {
+                renumber_pattern(component, offset);
+            }
+        }
+    }
+}
+
+// `Owner::name`, `object::name` or `Owner::new`, when it resolves to the
+// handle's method for the interface method's parameters. An object it is
+// bound to can't be left to a lambda, which would evaluate it each time.
+fn method_reference(
+    handle: &Handle,
+    interface: &str,
+    method: &MethodDescriptor,
+    captured: &[Expr],
+) -> Option<Expr> {
+    let object = match (handle.kind, captured) {
+        (REF_INVOKE_VIRTUAL | REF_INVOKE_SPECIAL | REF_INVOKE_INTERFACE, [object]) => {
+            Some(Box::new(object.clone()))
+        }
+        (_, []) if handle.parameters == method.parameters => None,
+        _ => return None,
+    };
+    Some(Expr::MethodRef {
+        interface: interface.to_string(),
+        markers: Vec::new(),
+        owner: handle.owner.to_string(),
+        name: handle.name.to_string(),
+        object,
+    })
+}
+
+// A lambda calling the handle's method with its parameters.
+fn call(
+    body: &mut Body,
+    handle: &Handle,
+    interface: String,
+    method: &MethodDescriptor,
+    captured: &[Expr],
+) -> Expr {
+    let parameters: Vec<Var> = handle
+        .parameters
+        .iter()
+        .enumerate()
+        .map(|(index, field_type)| {
+            let field_type = match index.checked_sub(captured.len()) {
+                Some(index) => &method.parameters[index],
+                None => field_type,
+            };
+            body.new_variable(Variable {
+                kind: Kind::of(field_type),
+                slot: None,
+                name: None,
+                field_type: Some(field_type.clone()),
+            })
+        })
+        .collect();
+    // the interface method's parameters are cast to the method's where
+    // they differ
+    let mut args = parameters
+        .iter()
+        .zip(&handle.parameters)
+        .map(|(parameter, field_type)| {
+            let value = Expr::Var(*parameter);
+            match body.variable(*parameter).field_type.as_ref() == Some(field_type) {
+                true => value,
+                false => Expr::Cast {
+                    field_type: field_type.clone(),
+                    operand: Box::new(value),
+                },
+            }
+        })
+        .collect::<Vec<Expr>>()
+        .into_iter();
+    let (owner, name, descriptor) = (
+        handle.owner.to_string(),
+        handle.name.to_string(),
+        handle.descriptor.to_string(),
+    );
+    let call = match handle.kind {
+        REF_NEW_INVOKE_SPECIAL => Expr::New {
+            class: owner,
+            descriptor,
+            args: args.collect(),
+        },
+        REF_INVOKE_STATIC => Expr::Invoke {
+            kind: InvokeKind::Static,
+            owner,
+            name,
+            descriptor,
+            object: None,
+            args: args.collect(),
+        },
+        kind => Expr::Invoke {
+            kind: match kind {
+                REF_INVOKE_INTERFACE => InvokeKind::Interface,
+                REF_INVOKE_SPECIAL => InvokeKind::Special,
+                _ => InvokeKind::Virtual,
+            },
+            owner,
+            name,
+            descriptor,
+            object: args.next().map(Box::new),
+            args: args.collect(),
+        },
+    };
+    let statement = match method.return_type {
+        Some(_) => Statement::Return(Some(call)),
+        None => Statement::Stmt(Stmt::Expr(call)),
+    };
+    Expr::Lambda {
+        interface,
+        markers: Vec::new(),
+        captured: captured.to_vec(),
+        parameters,
+        body: vec![statement],
+    }
+}
+
+// Leaves out a cast of a lambda, adding the type to its markers unless it
+// is the interface.
+fn strip_cast(expr: &mut Expr) {
+    let Expr::Cast {
+        field_type: FieldType::Object(class),
+        operand,
+    } = expr
+    else {
+        return;
+    };
+    let (Expr::Lambda {
+        interface, markers, ..
+    }
+    | Expr::MethodRef {
+        interface, markers, ..
+    }) = &mut **operand
+    else {
+        return;
+    };
+    if class != interface && !markers.contains(class) {
+        markers.push(class.clone());
+    }
+    let lambda = std::mem::replace(&mut **operand, Expr::Const(Const::Null));
+    *expr = lambda;
+}
+
+// Whether a statement is javac's check that the object of a method
+// reference isn't null: `Objects.requireNonNull(object)`, or
+// `object.getClass()` before Java 9. The object may have been stored in a
+// temporary by the statement before.
+fn checks_null(stmt: &Stmt, object: &Expr, previous: Option<&Stmt>) -> bool {
+    let Stmt::Expr(Expr::Invoke {
+        owner,
+        name,
+        descriptor,
+        object: receiver,
+        args,
+        ..
+    }) = stmt
+    else {
+        return false;
+    };
+    let checked = match (receiver, args.as_slice()) {
+        (None, [checked])
+            if owner == "java/util/Objects"
+                && name == "requireNonNull"
+                && descriptor == "(Ljava/lang/Object;)Ljava/lang/Object;" =>
+        {
+            checked
+        }
+        (Some(checked), []) if name == "getClass" && descriptor == "()Ljava/lang/Class;" => {
+            &**checked
+        }
+        _ => return false,
+    };
+    if checked == object {
+        return true;
+    }
+    matches!(
+        (object, previous),
+        (Expr::Var(var), Some(Stmt::Assign { target: Expr::Var(stored), value }))
+            if var == stored && value == checked
+    )
+}
@@ -0,0 +1,31 @@
+pub mod accessors;
+pub mod assembler;
+pub mod asserts;
+pub mod builder;
+pub mod cfg;
+pub mod concat;
+pub mod conditions;
+pub mod decompile;
+pub mod descriptor;
+pub mod dominators;
+pub mod error;
+pub mod exceptions;
+pub mod frames;
+pub mod infer;
+pub mod initializers;
+pub mod instruction;
+pub mod ir;
+pub mod java;
+pub mod lambdas;
+pub mod lift;
+pub mod loops;
+pub mod maxs;
+pub mod mutf8;
+pub mod patterns;
+pub mod printer;
+pub mod source;
+pub mod ssa;
+pub mod structure;
+pub mod switches;
+pub mod types;
+pub mod writer;
@@ -0,0 +1,1484 @@
+use crate::cfg::{BlockId, Cfg, EdgeKind};
+use crate::decompile::DecompileResult;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::dominators::DominatorTree;
+use crate::error::DecompileError;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::ir::{
+    BinaryOp, Block, Body, Bootstrap, CmpKind, CompareOp, Const, Expr, InvokeKind, JavaString,
+    Kind, Stmt, Terminator, UnaryOp, Var, Variable,
+};
+use crate::mutf8;
+use crate::types::{
+    Attribute, BootstrapMethod, ClassFile, ConstantPool, ConstantPoolType, ExceptionTable,
+    MethodInfo,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Lifts a method's code into the IR, `None` for abstract and native methods.
+pub fn lift_method(class_file: &ClassFile, method: &MethodInfo) -> DecompileResult<Option<Body>> {
+    let Some(Attribute::Code {
+        code,
+        exception_table,
+        ..
+    }) = method.code()
+    else {
+        return Ok(None);
+    };
+    let constant_pool = class_file.constant_pool();
+    let instructions = instruction::decode(code)?;
+    let exception_table: Vec<ExceptionTable> = exception_table
+        .iter()
+        .filter(|entry| !wraps_match_exception(&instructions, constant_pool, entry.handler_pc))
+        .cloned()
+        .collect();
+    let mut cfg = Cfg::new(instructions, &exception_table, code.len() as u32)?;
+    // SSA construction needs an entry no edge leads back to, to have somewhere
+    // for the values coming into a loop at the start of the method
+    if cfg.has_entry_predecessors() {
+        cfg = cfg.with_entry_block();
+    }
+    let descriptor =
+        method
+            .descriptor(constant_pool)
+            .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+                method.descriptor_index,
+            ))?;
+
+    let mut body = lift(
+        cfg,
+        constant_pool,
+        class_file.bootstrap_methods(),
+        descriptor,
+        method.is_static(),
+    )?;
+    if let Some(this) = body.this() {
+        body.variables[this.0].field_type = class_file
+            .this_class_name()
+            .and_then(FieldType::from_class_name);
+    }
+    Ok(Some(body))
+}
+
+// Whether a handler is the one javac 21 covers the accessor calls of record
+// patterns with, which throws what they throw wrapped in a MatchException:
+//   astore t; new MatchException; dup; aload t; invokevirtual toString;
+//   aload t; invokespecial <init>; athrow
+// The record pattern does that itself.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.30.2
+fn wraps_match_exception(
+    instructions: &[(u32, Instruction)],
+    constant_pool: &ConstantPool,
+    handler_pc: u16,
+) -> bool {
+    use Opcode::*;
+
+    let Ok(start) = instructions.binary_search_by_key(&u32::from(handler_pc), |(pc, _)| *pc) else {
+        return false;
+    };
+    let Some(window) = instructions.get(start..start + 8) else {
+        return false;
+    };
+    let opcodes: Vec<Opcode> = window
+        .iter()
+        .map(|(_, instruction)| instruction.opcode)
+        .collect();
+    let creates = window[1]
+        .1
+        .constant_index()
+        .and_then(|index| constant_pool.class_name(index))
+        == Some("java/lang/MatchException");
+    opcodes
+        == [
+            Astore,
+            New,
+            Dup,
+            Aload,
+            Invokevirtual,
+            Aload,
+            Invokespecial,
+            Athrow,
+        ]
+        && creates
+}
+
+/// Turns the stack machine code of every block into statements over
+/// expression trees.
+///
+/// Values stay on a simulated stack as expressions until something consumes
+/// them. Where a statement would change what a pending value reads, or run a
+/// side effect out of order, the value is first stored in a temporary. Values
+/// still on the stack at the end of a block are passed to its successors in
+/// temporaries shared by every block flowing into the same place. `new`,
+/// `dup` and the `invokespecial <init>` that follows become a single
+/// [`Expr::New`].
+pub fn lift(
+    cfg: Cfg,
+    constant_pool: &ConstantPool,
+    bootstrap_methods: &[BootstrapMethod],
+    descriptor: &str,
+    is_static: bool,
+) -> DecompileResult<Body> {
+    let method = MethodDescriptor::parse(descriptor)
+        .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+
+    let mut lifter = Lifter {
+        pool: constant_pool,
+        bootstrap_methods,
+        cfg: &cfg,
+        variables: Vec::new(),
+        locals: HashMap::new(),
+        parent: Vec::new(),
+        entry_stacks: vec![None; cfg.blocks().len()],
+        arrays: HashMap::new(),
+    };
+
+    let mut parameters = Vec::new();
+    let mut slot = 0;
+    if !is_static {
+        let this = lifter.local(0, Kind::Reference);
+        lifter.variables[this.0].name = Some("this".to_string());
+        parameters.push(this);
+        slot += 1;
+    }
+    for parameter in &method.parameters {
+        let var = lifter.local(slot, Kind::of(parameter));
+        lifter.variables[var.0].field_type = Some(parameter.clone());
+        parameters.push(var);
+        slot += parameter.slots();
+    }
+
+    let mut blocks: Vec<Block> = (0..cfg.blocks().len())
+        .map(|_| Block {
+            phis: Vec::new(),
+            stmts: Vec::new(),
+            terminator: Terminator::Unreachable,
+        })
+        .collect();
+    let dominators = DominatorTree::dominators(&cfg);
+    for id in dominators.reverse_postorder() {
+        blocks[*id] = lifter.lift_block(*id)?;
+    }
+
+    // temporaries that were merged at block boundaries become one variable
+    let mut rename = |expr: &mut Expr| {
+        if let Expr::Var(var) = expr {
+            *var = Var(find(&mut lifter.parent, var.0));
+        }
+    };
+    for block in &mut blocks {
+        for stmt in &mut block.stmts {
+            for expr in stmt.exprs_mut() {
+                expr.walk_mut(&mut rename);
+            }
+        }
+        for expr in block.terminator.exprs_mut() {
+            expr.walk_mut(&mut rename);
+        }
+    }
+
+    Ok(Body {
+        blocks,
+        variables: lifter.variables,
+        parameters,
+        return_type: method.return_type,
+        is_static,
+        cfg,
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Value {
+    expr: Expr,
+    kind: Kind,
+}
+
+impl Value {
+    fn new(expr: Expr, kind: Kind) -> Value {
+        Value { expr, kind }
+    }
+
+    fn slots(&self) -> u16 {
+        if self.kind.is_wide() {
+            2
+        } else {
+            1
+        }
+    }
+}
+
+struct Lifter<'a> {
+    pool: &'a ConstantPool,
+    bootstrap_methods: &'a [BootstrapMethod],
+    cfg: &'a Cfg,
+    variables: Vec<Variable>,
+    locals: HashMap<(u16, Kind), Var>,
+    // union-find over variables, for temporaries merged at block boundaries
+    parent: Vec<usize>,
+    entry_stacks: Vec<Option<Vec<Value>>>,
+    // the temporaries holding arrays created with a constant length, with
+    // that length and how many of their elements have been put in the
+    // creation
+    arrays: HashMap<Var, (usize, usize)>,
+}
+
+struct State {
+    stack: Vec<Value>,
+    stmts: Vec<Stmt>,
+}
+
+impl Lifter<'_> {
+    fn new_variable(&mut self, kind: Kind, slot: Option<u16>) -> Var {
+        self.variables.push(Variable {
+            kind,
+            slot,
+            name: None,
+            field_type: None,
+        });
+        self.parent.push(self.parent.len());
+        Var(self.variables.len() - 1)
+    }
+
+    fn temporary(&mut self, kind: Kind) -> Var {
+        self.new_variable(kind, None)
+    }
+
+    // Every use of a slot with the same kind is one variable until SSA
+    // construction splits them up.
+    fn local(&mut self, slot: u16, kind: Kind) -> Var {
+        if let Some(var) = self.locals.get(&(slot, kind)) {
+            return *var;
+        }
+        let var = self.new_variable(kind, Some(slot));
+        self.locals.insert((slot, kind), var);
+        var
+    }
+
+    fn block_at(&self, pc: u32) -> DecompileResult<BlockId> {
+        self.cfg
+            .block_at(pc)
+            .ok_or(DecompileError::InvalidBranchTarget(pc))
+    }
+
+    fn lift_block(&mut self, id: BlockId) -> DecompileResult<Block> {
+        let mut state = State {
+            stack: Vec::new(),
+            stmts: Vec::new(),
+        };
+        let is_handler = self
+            .cfg
+            .predecessors(id)
+            .any(|edge| matches!(edge.kind, EdgeKind::Exceptional { .. }));
+        if is_handler {
+            let exception = self.temporary(Kind::Reference);
+            state
+                .stmts
+                .push(Stmt::assign(Expr::Var(exception), Expr::CaughtException));
+            state
+                .stack
+                .push(Value::new(Expr::Var(exception), Kind::Reference));
+        } else if let Some(stack) = &self.entry_stacks[id] {
+            state.stack = stack.clone();
+        }
+
+        let instructions = self.cfg.block_instructions(id);
+        let Some((last_pc, last)) = instructions.last() else {
+            // the empty block Cfg::with_entry_block adds
+            return Ok(Block {
+                phis: Vec::new(),
+                stmts: state.stmts,
+                terminator: Terminator::Goto(id + 1),
+            });
+        };
+        for (pc, instruction) in &instructions[..instructions.len() - 1] {
+            self.lift_instruction(&mut state, *pc, instruction)?;
+        }
+
+        let next = id + 1;
+        let terminator = match (&last.opcode, &last.operand) {
+            (Opcode::Goto | Opcode::GotoW, Operand::Branch(target)) => {
+                Terminator::Goto(self.block_at(*target)?)
+            }
+            (Opcode::Jsr | Opcode::JsrW | Opcode::Ret, _) => {
+                return Err(DecompileError::UnsupportedSubroutine(*last_pc))
+            }
+            (opcode, Operand::Branch(target)) => {
+                let condition = self.branch_condition(&mut state, *last_pc, *opcode)?;
+                Terminator::If {
+                    condition,
+                    then: self.block_at(*target)?,
+                    otherwise: next,
+                }
+            }
+            (
+                _,
+                Operand::TableSwitch {
+                    default,
+                    low,
+                    targets,
+                    ..
+                },
+            ) => {
+                let value = self.pop(&mut state, *last_pc)?.expr;
+                let mut cases = Vec::with_capacity(targets.len());
+                for (key, target) in (*low..).zip(targets) {
+                    cases.push((key, self.block_at(*target)?));
+                }
+                Terminator::Switch {
+                    value,
+                    cases,
+                    default: self.block_at(*default)?,
+                }
+            }
+            (_, Operand::LookupSwitch { default, pairs }) => {
+                let value = self.pop(&mut state, *last_pc)?.expr;
+                let mut cases = Vec::with_capacity(pairs.len());
+                for (key, target) in pairs {
+                    cases.push((*key, self.block_at(*target)?));
+                }
+                Terminator::Switch {
+                    value,
+                    cases,
+                    default: self.block_at(*default)?,
+                }
+            }
+            (Opcode::Return, _) => Terminator::Return(None),
+            (opcode, _) if opcode.is_return() => {
+                Terminator::Return(Some(self.pop(&mut state, *last_pc)?.expr))
+            }
+            (Opcode::Athrow, _) => Terminator::Throw(self.pop(&mut state, *last_pc)?.expr),
+            _ => {
+                self.lift_instruction(&mut state, *last_pc, last)?;
+                Terminator::Goto(next)
+            }
+        };
+
+        let mut terminator = terminator;
+        let successors = terminator.targets();
+        if !state.stack.is_empty() && !successors.is_empty() {
+            // the condition or switch value is evaluated after the values
+            // passed on, so it goes on top while they are moved
+            let operand = match &mut terminator {
+                Terminator::If { condition, .. } => Some(condition),
+                Terminator::Switch { value, .. } => Some(value),
+                _ => None,
+            };
+            if let Some(operand) = operand {
+                let value = std::mem::replace(operand, Expr::int(0));
+                state.stack.push(Value::new(value, Kind::Int));
+                let operand_value = state.stack.len() - 1;
+                self.pass_on(&mut state, *last_pc, &successors, operand_value)?;
+                *terminator.exprs_mut()[0] = state.stack.pop().expect("pushed above").expr;
+            } else {
+                let count = state.stack.len();
+                self.pass_on(&mut state, *last_pc, &successors, count)?;
+            }
+        } else {
+            for successor in &successors {
+                self.check_entry(*last_pc, *successor, &[])?;
+            }
+        }
+
+        Ok(Block {
+            phis: Vec::new(),
+            stmts: state.stmts,
+            terminator,
+        })
+    }
+
+    // Moves the bottom `count` stack values into the entry temporaries of the
+    // successors. Anything above them is the terminator's operand, evaluated
+    // after they are passed on.
+    fn pass_on(
+        &mut self,
+        state: &mut State,
+        pc: u32,
+        successors: &[BlockId],
+        count: usize,
+    ) -> DecompileResult<()> {
+        let mut targets = Vec::with_capacity(count);
+        for depth in 0..count {
+            let value = &state.stack[depth];
+            if let Expr::Uninitialized { .. } = value.expr {
+                targets.push(None);
+                continue;
+            }
+            let kind = value.kind;
+            let existing: Vec<Var> = successors
+                .iter()
+                .filter_map(|successor| {
+                    match self.entry_stacks[*successor].as_ref()?.get(depth)?.expr {
+                        Expr::Var(var) => Some(var),
+                        _ => None,
+                    }
+                })
+                .collect();
+            let var = match existing.first() {
+                Some(var) => *var,
+                None => self.temporary(kind),
+            };
+            for other in existing.iter().skip(1) {
+                union(&mut self.parent, var.0, other.0);
+            }
+            targets.push(Some(var));
+        }
+
+        // when a value reads a temporary another one is about to be stored
+        // in, as when a loop passes values around in a different order,
+        // everything is evaluated into fresh temporaries first
+        let clobbered =
+            targets.iter().enumerate().any(|(depth, target)| {
+                let Some(target) = target else {
+                    return false;
+                };
+                let target = find(&mut self.parent, target.0);
+                state.stack[depth].expr != Expr::Var(Var(target))
+                    && state.stack.iter().enumerate().any(|(other, value)| {
+                        other != depth && self.reads_merged(&value.expr, target)
+                    })
+            });
+        if clobbered {
+            for depth in 0..state.stack.len() {
+                let kind = state.stack[depth].kind;
+                let temporary = self.temporary(kind);
+                let value = std::mem::replace(&mut state.stack[depth].expr, Expr::Var(temporary));
+                state.stmts.push(Stmt::assign(Expr::Var(temporary), value));
+            }
+        }
+
+        let mut entry = Vec::with_capacity(count);
+        for (depth, target) in targets.into_iter().enumerate() {
+            let value = state.stack[depth].clone();
+            let Some(var) = target else {
+                entry.push(value);
+                continue;
+            };
+            if value.expr != Expr::Var(var) {
+                let above: Vec<Value> = state.stack.drain(depth + 1..).collect();
+                state.stack.pop();
+                self.emit(state, Stmt::assign(Expr::Var(var), value.expr));
+                state.stack.push(Value::new(Expr::Var(var), value.kind));
+                state.stack.extend(above);
+            }
+            entry.push(Value::new(Expr::Var(var), value.kind));
+        }
+
+        for successor in successors {
+            self.check_entry(pc, *successor, &entry)?;
+        }
+        Ok(())
+    }
+
+    fn reads_merged(&mut self, expr: &Expr, target: usize) -> bool {
+        let mut reads = false;
+        expr.walk(&mut |expr| {
+            if let Expr::Var(var) = expr {
+                reads |= find(&mut self.parent, var.0) == target;
+            }
+        });
+        reads
+    }
+
+    fn check_entry(&mut self, pc: u32, successor: BlockId, entry: &[Value]) -> DecompileResult<()> {
+        match &self.entry_stacks[successor] {
+            None => self.entry_stacks[successor] = Some(entry.to_vec()),
+            Some(existing) if existing.len() != entry.len() => {
+                return Err(DecompileError::InconsistentStackHeight(
+                    pc,
+                    existing.len() as u16,
+                    entry.len() as u16,
+                ))
+            }
+            Some(_) => {}
+        }
+        Ok(())
+    }
+
+    fn pop(&mut self, state: &mut State, pc: u32) -> DecompileResult<Value> {
+        state.stack.pop().ok_or(DecompileError::StackUnderflow(pc))
+    }
+
+    fn pop_expr(&mut self, state: &mut State, pc: u32) -> DecompileResult<Expr> {
+        let expr = self.pop(state, pc)?.expr;
+        Ok(self.inline_array(state, expr))
+    }
+
+    // Pops values in the order they were pushed.
+    fn pop_args(&mut self, state: &mut State, pc: u32, count: usize) -> DecompileResult<Vec<Expr>> {
+        if state.stack.len() < count {
+            return Err(DecompileError::StackUnderflow(pc));
+        }
+        let at = state.stack.len() - count;
+        let mut args: Vec<Expr> = state.stack.drain(at..).map(|value| value.expr).collect();
+        for index in (0..args.len()).rev() {
+            let arg = std::mem::replace(&mut args[index], Expr::Const(Const::Null));
+            let copied = match &arg {
+                Expr::Var(var) => args.iter().any(|other| other.uses(*var)),
+                _ => false,
+            };
+            args[index] = if copied {
+                arg
+            } else {
+                self.inline_array(state, arg)
+            };
+        }
+        Ok(args)
+    }
+
+    // The creation of an array a temporary holds in place of its last
+    // copy, when it is the last statement, so that it isn't declared apart
+    // from where it is used.
+    fn inline_array(&mut self, state: &mut State, expr: Expr) -> Expr {
+        let Expr::Var(var) = expr else {
+            return expr;
+        };
+        let defined = matches!(state.stmts.last(), Some(Stmt::Assign {
+            target: Expr::Var(target),
+            ..
+        }) if *target == var);
+        let copied = state.stack.iter().any(|value| value.expr.uses(var));
+        if !self.arrays.contains_key(&var) || !defined || copied {
+            return expr;
+        }
+        match state.stmts.pop() {
+            Some(Stmt::Assign { value, .. }) => value,
+            _ => unreachable!("the last statement stores the temporary"),
+        }
+    }
+
+    // Puts a value stored in an array into the creation of the array, when
+    // it is the next element of a temporary's array created with a
+    // constant length and nothing has happened since, which is how javac
+    // compiles an array initializer:
+    //   newarray int; dup; iconst_0; iconst_1; iastore; dup; iconst_1; ...
+    // Gives the value back otherwise.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.10.1
+    fn fill(&mut self, state: &mut State, array: &Expr, index: &Expr, value: Expr) -> Option<Expr> {
+        let (Expr::Var(var), Expr::Const(Const::Int(index))) = (array, index) else {
+            return Some(value);
+        };
+        let Some(&(length, filled)) = self.arrays.get(var) else {
+            return Some(value);
+        };
+        let defined = matches!(state.stmts.last(), Some(Stmt::Assign {
+            target: Expr::Var(target),
+            ..
+        }) if target == var);
+        if usize::try_from(*index) != Ok(filled) || filled >= length || !defined || value.uses(*var)
+        {
+            return Some(value);
+        }
+        let (field_type, mut elements) = match state.stmts.pop() {
+            Some(Stmt::Assign {
+                value:
+                    Expr::ArrayInit {
+                        field_type,
+                        elements,
+                    },
+                ..
+            }) => (field_type, elements),
+            Some(Stmt::Assign {
+                value: Expr::NewArray { field_type, .. },
+                ..
+            }) => {
+                // the elements not stored yet keep their default values
+                let default = match &field_type {
+                    FieldType::Array(element) => match **element {
+                        FieldType::Long => Const::Long(0),
+                        FieldType::Float => Const::Float(0.0),
+                        FieldType::Double => Const::Double(0.0),
+                        FieldType::Object(_) | FieldType::Array(_) => Const::Null,
+                        _ => Const::Int(0),
+                    },
+                    _ => Const::Null,
+                };
+                (field_type, vec![Expr::Const(default); length])
+            }
+            _ => unreachable!("only array creations are filled"),
+        };
+        // the element is evaluated after what is below the array
+        self.spill(state, &Effects::of_expr(&value));
+        elements[filled] = value;
+        state.stmts.push(Stmt::assign(
+            Expr::Var(*var),
+            Expr::ArrayInit {
+                field_type,
+                elements,
+            },
+        ));
+        self.arrays.insert(*var, (length, filled + 1));
+        None
+    }
+
+    fn push(&mut self, state: &mut State, expr: Expr, kind: Kind) {
+        state.stack.push(Value::new(expr, kind));
+    }
+
+    // Adds a statement, first moving any stack value it would disturb into a
+    // temporary.
+    fn emit(&mut self, state: &mut State, stmt: Stmt) {
+        self.spill(state, &Effects::of_stmt(&stmt));
+        state.stmts.push(stmt);
+    }
+
+    // Moves the stack values something with `effects` would disturb into
+    // temporaries.
+    fn spill(&mut self, state: &mut State, effects: &Effects) {
+        let count = state.stack.len();
+        let pending: Vec<Effects> = state
+            .stack
+            .iter()
+            .map(|value| Effects::of_expr(&value.expr))
+            .collect();
+        let mut needed: Vec<bool> = pending
+            .iter()
+            .map(|pending| pending.conflicts(effects))
+            .collect();
+        // a value that now runs earlier must not overtake one below it
+        for i in (0..count).rev() {
+            if needed[i] {
+                for j in 0..i {
+                    needed[j] |= pending[j].conflicts(&pending[i]);
+                }
+            }
+        }
+
+        for (i, needed) in needed.into_iter().enumerate() {
+            if needed {
+                let kind = state.stack[i].kind;
+                let temporary = self.temporary(kind);
+                let value = std::mem::replace(&mut state.stack[i].expr, Expr::Var(temporary));
+                state.stmts.push(Stmt::assign(Expr::Var(temporary), value));
+            }
+        }
+    }
+
+    // Moves the value at `index` into a temporary unless it is already a
+    // constant or a variable.
+    fn make_atomic(&mut self, state: &mut State, index: usize) {
+        let value = &state.stack[index];
+        if matches!(
+            value.expr,
+            Expr::Const(_) | Expr::Var(_) | Expr::Uninitialized { .. }
+        ) {
+            return;
+        }
+        let kind = value.kind;
+        let temporary = self.temporary(kind);
+        let above: Vec<Value> = state.stack.drain(index + 1..).collect();
+        let value = state.stack.pop().expect("index is in the stack");
+        if let Expr::NewArray { dimensions, .. } = &value.expr {
+            if let [Expr::Const(Const::Int(length))] = dimensions.as_slice() {
+                if let Ok(length) = usize::try_from(*length) {
+                    self.arrays.insert(temporary, (length, 0));
+                }
+            }
+        }
+        self.emit(state, Stmt::assign(Expr::Var(temporary), value.expr));
+        state.stack.push(Value::new(Expr::Var(temporary), kind));
+        state.stack.extend(above);
+    }
+
+    // Makes the value at `index` safe to evaluate twice in a row.
+    fn make_duplicable(&mut self, state: &mut State, index: usize) {
+        if !is_duplicable(&state.stack[index].expr) {
+            self.make_atomic(state, index);
+        }
+    }
+
+    // Applies one of the dup or swap instructions. `take` is the number of
+    // stack values it looks at, and `layout` lists the values it leaves,
+    // bottom first, as indexes into those.
+    fn shuffle(
+        &mut self,
+        state: &mut State,
+        pc: u32,
+        take: usize,
+        layout: &[usize],
+    ) -> DecompileResult<()> {
+        if state.stack.len() < take {
+            return Err(DecompileError::StackUnderflow(pc));
+        }
+        let base = state.stack.len() - take;
+        let copies: Vec<usize> = (0..take)
+            .filter(|i| layout.iter().filter(|slot| **slot == *i).count() > 1)
+            .collect();
+        let moves_past = layout.first() != Some(&0);
+        for i in 0..take {
+            if moves_past {
+                // values end up in a new order, so they must not depend on
+                // when they are evaluated
+                self.make_atomic(state, base + i);
+            } else if copies.contains(&i) {
+                self.make_duplicable(state, base + i);
+            }
+        }
+        let values: Vec<Value> = state.stack.drain(base..).collect();
+        state
+            .stack
+            .extend(layout.iter().map(|slot| values[*slot].clone()));
+        Ok(())
+    }
+
+    fn top_slots(&self, state: &State, pc: u32, count: usize) -> DecompileResult<Vec<u16>> {
+        if state.stack.len() < count {
+            return Err(DecompileError::StackUnderflow(pc));
+        }
+        Ok(state.stack[state.stack.len() - count..]
+            .iter()
+            .map(Value::slots)
+            .collect())
+    }
+
+    fn store(&mut self, state: &mut State, pc: u32, slot: u16, kind: Kind) -> DecompileResult<()> {
+        let value = self.pop_expr(state, pc)?;
+        let var = self.local(slot, kind);
+        self.emit(state, Stmt::assign(Expr::Var(var), value));
+        Ok(())
+    }
+
+    fn load(&mut self, state: &mut State, slot: u16, kind: Kind) {
+        let var = self.local(slot, kind);
+        self.push(state, Expr::Var(var), kind);
+    }
+
+    fn binary(
+        &mut self,
+        state: &mut State,
+        pc: u32,
+        op: BinaryOp,
+        kind: Kind,
+    ) -> DecompileResult<()> {
+        let right = self.pop_expr(state, pc)?;
+        let left = self.pop_expr(state, pc)?;
+        self.push(state, Expr::binary(op, left, right), kind);
+        Ok(())
+    }
+
+    fn cast(&mut self, state: &mut State, pc: u32, field_type: FieldType) -> DecompileResult<()> {
+        let operand = self.pop_expr(state, pc)?;
+        let kind = Kind::of(&field_type);
+        self.push(
+            state,
+            Expr::Cast {
+                field_type,
+                operand: Box::new(operand),
+            },
+            kind,
+        );
+        Ok(())
+    }
+
+    fn cmp(&mut self, state: &mut State, pc: u32, kind: CmpKind) -> DecompileResult<()> {
+        let right = self.pop_expr(state, pc)?;
+        let left = self.pop_expr(state, pc)?;
+        self.push(
+            state,
+            Expr::Cmp {
+                kind,
+                left: Box::new(left),
+                right: Box::new(right),
+            },
+            Kind::Int,
+        );
+        Ok(())
+    }
+
+    fn array_load(&mut self, state: &mut State, pc: u32, kind: Kind) -> DecompileResult<()> {
+        let index = self.pop_expr(state, pc)?;
+        let array = self.pop_expr(state, pc)?;
+        self.push(
+            state,
+            Expr::ArrayElement {
+                array: Box::new(array),
+                index: Box::new(index),
+            },
+            kind,
+        );
+        Ok(())
+    }
+
+    fn array_store(&mut self, state: &mut State, pc: u32) -> DecompileResult<()> {
+        let value = self.pop_expr(state, pc)?;
+        let index = self.pop_expr(state, pc)?;
+        let array = self.pop_expr(state, pc)?;
+        let Some(value) = self.fill(state, &array, &index, value) else {
+            return Ok(());
+        };
+        let target = Expr::ArrayElement {
+            array: Box::new(array),
+            index: Box::new(index),
+        };
+        self.emit(state, Stmt::assign(target, value));
+        Ok(())
+    }
+
+    // Discards a value, keeping it as a statement if evaluating it does
+    // something.
+    fn discard(&mut self, state: &mut State, pc: u32) -> DecompileResult<u16> {
+        let value = self.pop(state, pc)?;
+        let slots = value.slots();
+        if checks_enclosing_instance(&state.stack, &value.expr) {
+            // the check is kept on the instance, for writing the creation
+            // to leave out
+            if let Some(instance) = state.stack.last_mut() {
+                instance.expr = value.expr;
+            }
+            return Ok(slots);
+        }
+        if value.expr.has_side_effects() {
+            self.emit(state, Stmt::Expr(value.expr));
+        }
+        Ok(slots)
+    }
+
+    fn class_name(&self, index: u16) -> DecompileResult<&str> {
+        self.pool
+            .class_name(index)
+            .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))
+    }
+
+    fn constant(&self, index: u16) -> DecompileResult<(Const, Kind)> {
+        let missing = DecompileError::NoSuchConstantPoolEntry(index, 0);
+        let constant = match self.pool.entry(index).ok_or(missing)? {
+            ConstantPoolType::ConstantInteger { value } => (Const::Int(*value), Kind::Int),
+            ConstantPoolType::ConstantFloat { value } => (Const::Float(*value), Kind::Float),
+            ConstantPoolType::ConstantLong { value } => (Const::Long(*value), Kind::Long),
+            ConstantPoolType::ConstantDouble { value } => (Const::Double(*value), Kind::Double),
+            ConstantPoolType::ConstantString { string_idx } => {
+                let units = match self.pool.entry(*string_idx) {
+                    Some(ConstantPoolType::ConstantUtf8 { bytes, .. }) => {
+                        mutf8::decode_utf16(bytes)
+                    }
+                    _ => None,
+                }
+                .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(*string_idx))?;
+                (Const::String(JavaString(units)), Kind::Reference)
+            }
+            ConstantPoolType::ConstantClass { .. } => {
+                let name = self.class_name(index)?;
+                let field_type = FieldType::from_class_name(name)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(name.to_string()))?;
+                (Const::Class(field_type), Kind::Reference)
+            }
+            ConstantPoolType::ConstantMethodType { desc_idx } => {
+                let descriptor = self
+                    .pool
+                    .utf8(*desc_idx)
+                    .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(*desc_idx))?;
+                (Const::MethodType(descriptor.to_string()), Kind::Reference)
+            }
+            ConstantPoolType::ConstantMethodHandle { ref_kind, ref_idx } => {
+                let member = self
+                    .pool
+                    .member_ref(*ref_idx)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(*ref_idx, 0))?;
+                let handle = Const::MethodHandle {
+                    kind: *ref_kind,
+                    owner: member.class_name.to_string(),
+                    name: member.name.to_string(),
+                    descriptor: member.descriptor.to_string(),
+                };
+                (handle, Kind::Reference)
+            }
+            ConstantPoolType::ConstantDynamic { .. } => {
+                let (bootstrap, name, descriptor) = self
+                    .pool
+                    .dynamic(index)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                let field_type = FieldType::parse(descriptor)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+                let dynamic = Const::Dynamic {
+                    bootstrap,
+                    name: name.to_string(),
+                    descriptor: descriptor.to_string(),
+                };
+                (dynamic, Kind::of(&field_type))
+            }
+            _ => return Err(DecompileError::NoSuchConstantPoolEntry(index, 0)),
+        };
+        Ok(constant)
+    }
+
+    fn bootstrap(&self, index: u16) -> DecompileResult<Bootstrap> {
+        let method = self
+            .bootstrap_methods
+            .get(usize::from(index))
+            .ok_or(DecompileError::NoSuchBootstrapMethod(index))?;
+        let (handle, _) = self.constant(method.bootstrap_method_ref)?;
+        let arguments = method
+            .bootstrap_arguments
+            .iter()
+            .map(|argument| self.constant(*argument).map(|(constant, _)| constant))
+            .collect::<DecompileResult<_>>()?;
+        Ok(Bootstrap {
+            index,
+            method: handle,
+            arguments,
+        })
+    }
+
+    // The condition under which a conditional branch is taken.
+    fn branch_condition(
+        &mut self,
+        state: &mut State,
+        pc: u32,
+        opcode: Opcode,
+    ) -> DecompileResult<Expr> {
+        use Opcode::*;
+
+        let op = match opcode {
+            Ifeq | IfIcmpeq | IfAcmpeq | Ifnull => CompareOp::Eq,
+            Ifne | IfIcmpne | IfAcmpne | Ifnonnull => CompareOp::Ne,
+            Iflt | IfIcmplt => CompareOp::Lt,
+            Ifge | IfIcmpge => CompareOp::Ge,
+            Ifgt | IfIcmpgt => CompareOp::Gt,
+            _ => CompareOp::Le,
+        };
+        let condition = match opcode {
+            Ifnull | Ifnonnull => {
+                let value = self.pop_expr(state, pc)?;
+                Expr::compare(op, value, Expr::Const(Const::Null))
+            }
+            Ifeq | Ifne | Iflt | Ifge | Ifgt | Ifle => match self.pop_expr(state, pc)? {
+                Expr::Cmp { kind, left, right } => {
+                    // fcmpl and dcmpl give -1 for NaN, fcmpg and dcmpg 1,
+                    // where in Java every comparison with NaN but != is false
+                    let nan = match kind {
+                        CmpKind::Long => None,
+                        CmpKind::FloatL | CmpKind::DoubleL => {
+                            Some(op.holds(std::cmp::Ordering::Less))
+                        }
+                        CmpKind::FloatG | CmpKind::DoubleG => {
+                            Some(op.holds(std::cmp::Ordering::Greater))
+                        }
+                    };
+                    match nan {
+                        Some(nan) if nan != (op == CompareOp::Ne) => {
+                            Expr::logical_not(Expr::compare(op.negate(), *left, *right))
+                        }
+                        _ => Expr::compare(op, *left, *right),
+                    }
+                }
+                value => Expr::compare(op, value, Expr::int(0)),
+            },
+            _ => {
+                let right = self.pop_expr(state, pc)?;
+                let left = self.pop_expr(state, pc)?;
+                Expr::compare(op, left, right)
+            }
+        };
+        Ok(condition)
+    }
+
+    fn lift_instruction(
+        &mut self,
+        state: &mut State,
+        pc: u32,
+        instruction: &Instruction,
+    ) -> DecompileResult<()> {
+        use Opcode::*;
+
+        let local = instruction.local_index().unwrap_or(0);
+        let index = instruction.constant_index().unwrap_or(0);
+        match instruction.opcode {
+            Nop => {}
+            AconstNull => self.push(state, Expr::Const(Const::Null), Kind::Reference),
+            IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5 => {
+                let value = instruction.opcode as i32 - Iconst0 as i32;
+                self.push(state, Expr::int(value), Kind::Int);
+            }
+            Lconst0 | Lconst1 => {
+                let value = (instruction.opcode as u8 - Lconst0 as u8) as i64;
+                self.push(state, Expr::Const(Const::Long(value)), Kind::Long);
+            }
+            Fconst0 | Fconst1 | Fconst2 => {
+                let value = (instruction.opcode as u8 - Fconst0 as u8) as f32;
+                self.push(state, Expr::Const(Const::Float(value)), Kind::Float);
+            }
+            Dconst0 | Dconst1 => {
+                let value = (instruction.opcode as u8 - Dconst0 as u8) as f64;
+                self.push(state, Expr::Const(Const::Double(value)), Kind::Double);
+            }
+            Bipush | Sipush => {
+                let value = match instruction.operand {
+                    Operand::Byte(value) => value as i32,
+                    Operand::Short(value) => value as i32,
+                    _ => 0,
+                };
+                self.push(state, Expr::int(value), Kind::Int);
+            }
+            Ldc | LdcW | Ldc2W => {
+                let (constant, kind) = self.constant(index)?;
+                self.push(state, Expr::Const(constant), kind);
+            }
+            Iload => self.load(state, local, Kind::Int),
+            Lload => self.load(state, local, Kind::Long),
+            Fload => self.load(state, local, Kind::Float),
+            Dload => self.load(state, local, Kind::Double),
+            Aload => self.load(state, local, Kind::Reference),
+            Iaload | Baload | Caload | Saload => self.array_load(state, pc, Kind::Int)?,
+            Laload => self.array_load(state, pc, Kind::Long)?,
+            Faload => self.array_load(state, pc, Kind::Float)?,
+            Daload => self.array_load(state, pc, Kind::Double)?,
+            Aaload => self.array_load(state, pc, Kind::Reference)?,
+            Istore => self.store(state, pc, local, Kind::Int)?,
+            Lstore => self.store(state, pc, local, Kind::Long)?,
+            Fstore => self.store(state, pc, local, Kind::Float)?,
+            Dstore => self.store(state, pc, local, Kind::Double)?,
+            Astore => self.store(state, pc, local, Kind::Reference)?,
+            Iastore | Lastore | Fastore | Dastore | Aastore | Bastore | Castore | Sastore => {
+                self.array_store(state, pc)?
+            }
+            Pop => {
+                self.discard(state, pc)?;
+            }
+            Pop2 => {
+                if self.discard(state, pc)? == 1 {
+                    self.discard(state, pc)?;
+                }
+            }
+            // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-6.html#jvms-6.5.dup
+            Dup => self.shuffle(state, pc, 1, &[0, 0])?,
+            DupX1 => self.shuffle(state, pc, 2, &[1, 0, 1])?,
+            DupX2 => match self.top_slots(state, pc, 2)?.as_slice() {
+                [2, 1] => self.shuffle(state, pc, 2, &[1, 0, 1])?,
+                _ => self.shuffle(state, pc, 3, &[2, 0, 1, 2])?,
+            },
+            Dup2 => match self.top_slots(state, pc, 1)?.as_slice() {
+                [2] => self.shuffle(state, pc, 1, &[0, 0])?,
+                _ => self.shuffle(state, pc, 2, &[0, 1, 0, 1])?,
+            },
+            Dup2X1 => match self.top_slots(state, pc, 1)?.as_slice() {
+                [2] => self.shuffle(state, pc, 2, &[1, 0, 1])?,
+                _ => self.shuffle(state, pc, 3, &[1, 2, 0, 1, 2])?,
+            },
+            Dup2X2 => {
+                let top = self.top_slots(state, pc, 1)?[0];
+                let below = if top == 2 {
+                    self.top_slots(state, pc, 2)?[0]
+                } else {
+                    self.top_slots(state, pc, 3)?[0]
+                };
+                match (top, below) {
+                    (2, 2) => self.shuffle(state, pc, 2, &[1, 0, 1])?,
+                    (2, _) => self.shuffle(state, pc, 3, &[2, 0, 1, 2])?,
+                    (_, 2) => self.shuffle(state, pc, 3, &[1, 2, 0, 1, 2])?,
+                    _ => self.shuffle(state, pc, 4, &[2, 3, 0, 1, 2, 3])?,
+                }
+            }
+            Swap => self.shuffle(state, pc, 2, &[1, 0])?,
+            Iadd => self.binary(state, pc, BinaryOp::Add, Kind::Int)?,
+            Ladd => self.binary(state, pc, BinaryOp::Add, Kind::Long)?,
+            Fadd => self.binary(state, pc, BinaryOp::Add, Kind::Float)?,
+            Dadd => self.binary(state, pc, BinaryOp::Add, Kind::Double)?,
+            Isub => self.binary(state, pc, BinaryOp::Sub, Kind::Int)?,
+            Lsub => self.binary(state, pc, BinaryOp::Sub, Kind::Long)?,
+            Fsub => self.binary(state, pc, BinaryOp::Sub, Kind::Float)?,
+            Dsub => self.binary(state, pc, BinaryOp::Sub, Kind::Double)?,
+            Imul => self.binary(state, pc, BinaryOp::Mul, Kind::Int)?,
+            Lmul => self.binary(state, pc, BinaryOp::Mul, Kind::Long)?,
+            Fmul => self.binary(state, pc, BinaryOp::Mul, Kind::Float)?,
+            Dmul => self.binary(state, pc, BinaryOp::Mul, Kind::Double)?,
+            Idiv => self.binary(state, pc, BinaryOp::Div, Kind::Int)?,
+            Ldiv => self.binary(state, pc, BinaryOp::Div, Kind::Long)?,
+            Fdiv => self.binary(state, pc, BinaryOp::Div, Kind::Float)?,
+            Ddiv => self.binary(state, pc, BinaryOp::Div, Kind::Double)?,
+            Irem => self.binary(state, pc, BinaryOp::Rem, Kind::Int)?,
+            Lrem => self.binary(state, pc, BinaryOp::Rem, Kind::Long)?,
+            Frem => self.binary(state, pc, BinaryOp::Rem, Kind::Float)?,
+            Drem => self.binary(state, pc, BinaryOp::Rem, Kind::Double)?,
+            Ineg | Lneg | Fneg | Dneg => {
+                let value = self.pop(state, pc)?;
+                let negated = Expr::Unary {
+                    op: UnaryOp::Neg,
+                    operand: Box::new(value.expr),
+                };
+                self.push(state, negated, value.kind);
+            }
+            Ishl => self.binary(state, pc, BinaryOp::Shl, Kind::Int)?,
+            Lshl => self.binary(state, pc, BinaryOp::Shl, Kind::Long)?,
+            Ishr => self.binary(state, pc, BinaryOp::Shr, Kind::Int)?,
+            Lshr => self.binary(state, pc, BinaryOp::Shr, Kind::Long)?,
+            Iushr => self.binary(state, pc, BinaryOp::Ushr, Kind::Int)?,
+            Lushr => self.binary(state, pc, BinaryOp::Ushr, Kind::Long)?,
+            Iand => self.binary(state, pc, BinaryOp::And, Kind::Int)?,
+            Land => self.binary(state, pc, BinaryOp::And, Kind::Long)?,
+            Ior => self.binary(state, pc, BinaryOp::Or, Kind::Int)?,
+            Lor => self.binary(state, pc, BinaryOp::Or, Kind::Long)?,
+            Ixor => self.binary(state, pc, BinaryOp::Xor, Kind::Int)?,
+            Lxor => self.binary(state, pc, BinaryOp::Xor, Kind::Long)?,
+            Iinc => {
+                let Operand::Iinc { index, value } = instruction.operand else {
+                    unreachable!("iinc always has an iinc operand");
+                };
+                let var = self.local(index, Kind::Int);
+                let sum = Expr::binary(BinaryOp::Add, Expr::Var(var), Expr::int(value as i32));
+                self.emit(state, Stmt::assign(Expr::Var(var), sum));
+            }
+            I2l | F2l | D2l => self.cast(state, pc, FieldType::Long)?,
+            I2f | L2f | D2f => self.cast(state, pc, FieldType::Float)?,
+            I2d | L2d | F2d => self.cast(state, pc, FieldType::Double)?,
+            L2i | F2i | D2i => self.cast(state, pc, FieldType::Int)?,
+            I2b => self.cast(state, pc, FieldType::Byte)?,
+            I2c => self.cast(state, pc, FieldType::Char)?,
+            I2s => self.cast(state, pc, FieldType::Short)?,
+            Lcmp => self.cmp(state, pc, CmpKind::Long)?,
+            Fcmpl => self.cmp(state, pc, CmpKind::FloatL)?,
+            Fcmpg => self.cmp(state, pc, CmpKind::FloatG)?,
+            Dcmpl => self.cmp(state, pc, CmpKind::DoubleL)?,
+            Dcmpg => self.cmp(state, pc, CmpKind::DoubleG)?,
+            Getstatic | Putstatic | Getfield | Putfield => {
+                let member = self
+                    .pool
+                    .member_ref(index)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                let field_type = FieldType::parse(member.descriptor).ok_or_else(|| {
+                    DecompileError::InvalidDescriptor(member.descriptor.to_string())
+                })?;
+                let (owner, name, descriptor) = (
+                    member.class_name.to_string(),
+                    member.name.to_string(),
+                    member.descriptor.to_string(),
+                );
+                let value = match instruction.opcode {
+                    Putstatic | Putfield => Some(self.pop_expr(state, pc)?),
+                    _ => None,
+                };
+                let object = match instruction.opcode {
+                    Getfield | Putfield => Some(Box::new(self.pop_expr(state, pc)?)),
+                    _ => None,
+                };
+                let field = Expr::Field {
+                    owner,
+                    name,
+                    descriptor,
+                    object,
+                };
+                match value {
+                    Some(value) => self.emit(state, Stmt::assign(field, value)),
+                    None => self.push(state, field, Kind::of(&field_type)),
+                }
+            }
+            Invokevirtual | Invokespecial | Invokestatic | Invokeinterface => {
+                let member = self
+                    .pool
+                    .member_ref(index)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                let (owner, name, descriptor) = (
+                    member.class_name.to_string(),
+                    member.name.to_string(),
+                    member.descriptor.to_string(),
+                );
+                let method = MethodDescriptor::parse(&descriptor)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.clone()))?;
+                let args = self.pop_args(state, pc, method.parameters.len())?;
+                let object = match instruction.opcode {
+                    Invokestatic => None,
+                    _ => Some(self.pop_expr(state, pc)?),
+                };
+
+                if let Some(Expr::Uninitialized { class, pc: new_pc }) = &object {
+                    if name == "<init>" {
+                        return self.construct(state, class.clone(), *new_pc, descriptor, args);
+                    }
+                }
+
+                let kind = match instruction.opcode {
+                    Invokevirtual => InvokeKind::Virtual,
+                    Invokespecial => InvokeKind::Special,
+                    Invokestatic => InvokeKind::Static,
+                    _ => InvokeKind::Interface,
+                };
+                let invoke = Expr::Invoke {
+                    kind,
+                    owner,
+                    name,
+                    descriptor,
+                    object: object.map(Box::new),
+                    args,
+                };
+                match &method.return_type {
+                    Some(return_type) => self.push(state, invoke, Kind::of(return_type)),
+                    None => self.emit(state, Stmt::Expr(invoke)),
+                }
+            }
+            Invokedynamic => {
+                let (bootstrap, name, descriptor) = self
+                    .pool
+                    .dynamic(index)
+                    .ok_or(DecompileError::NoSuchConstantPoolEntry(index, 0))?;
+                let (name, descriptor) = (name.to_string(), descriptor.to_string());
+                let method = MethodDescriptor::parse(&descriptor)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.clone()))?;
+                let bootstrap = self.bootstrap(bootstrap)?;
+                let args = self.pop_args(state, pc, method.parameters.len())?;
+                let invoke = Expr::InvokeDynamic {
+                    bootstrap,
+                    name,
+                    descriptor,
+                    args,
+                };
+                match &method.return_type {
+                    Some(return_type) => self.push(state, invoke, Kind::of(return_type)),
+                    None => self.emit(state, Stmt::Expr(invoke)),
+                }
+            }
+            New => {
+                let class = self.class_name(index)?.to_string();
+                self.push(state, Expr::Uninitialized { class, pc }, Kind::Reference);
+            }
+            Newarray => {
+                let Operand::ArrayType(atype) = instruction.operand else {
+                    unreachable!("newarray always has an array type");
+                };
+                let element = match atype {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    _ => FieldType::Long,
+                };
+                let length = self.pop_expr(state, pc)?;
+                let array = Expr::NewArray {
+                    field_type: FieldType::Array(Box::new(element)),
+                    dimensions: vec![length],
+                };
+                self.push(state, array, Kind::Reference);
+            }
+            Anewarray => {
+                let name = self.class_name(index)?;
+                let element = FieldType::from_class_name(name)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(name.to_string()))?;
+                let length = self.pop_expr(state, pc)?;
+                let array = Expr::NewArray {
+                    field_type: FieldType::Array(Box::new(element)),
+                    dimensions: vec![length],
+                };
+                self.push(state, array, Kind::Reference);
+            }
+            Multianewarray => {
+                let Operand::MultiANewArray { dimensions, .. } = instruction.operand else {
+                    unreachable!("multianewarray always has dimensions");
+                };
+                let name = self.class_name(index)?;
+                let field_type = FieldType::from_class_name(name)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(name.to_string()))?;
+                let dimensions = self.pop_args(state, pc, dimensions as usize)?;
+                let array = Expr::NewArray {
+                    field_type,
+                    dimensions,
+                };
+                self.push(state, array, Kind::Reference);
+            }
+            Arraylength => {
+                let array = self.pop_expr(state, pc)?;
+                self.push(state, Expr::ArrayLength(Box::new(array)), Kind::Int);
+            }
+            Checkcast => {
+                let name = self.class_name(index)?;
+                let field_type = FieldType::from_class_name(name)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(name.to_string()))?;
+                self.cast(state, pc, field_type)?;
+            }
+            Instanceof => {
+                let name = self.class_name(index)?;
+                let field_type = FieldType::from_class_name(name)
+                    .ok_or_else(|| DecompileError::InvalidDescriptor(name.to_string()))?;
+                let operand = self.pop_expr(state, pc)?;
+                let instance_of = Expr::InstanceOf {
+                    operand: Box::new(operand),
+                    field_type,
+                    pattern: None,
+                };
+                self.push(state, instance_of, Kind::Int);
+            }
+            Monitorenter => {
+                let object = self.pop_expr(state, pc)?;
+                self.emit(state, Stmt::MonitorEnter(object));
+            }
+            Monitorexit => {
+                let object = self.pop_expr(state, pc)?;
+                self.emit(state, Stmt::MonitorExit(object));
+            }
+            Jsr | JsrW | Ret => return Err(DecompileError::UnsupportedSubroutine(pc)),
+            // control flow only ends a block, and decoding folds the short and
+            // wide forms
+            opcode => unreachable!("{opcode} doesn't appear inside a block"),
+        }
+        Ok(())
+    }
+
+    // Runs the constructor of the object made by the `new` at `new_pc`. The
+    // copies `dup` left on the stack become the constructed object.
+    fn construct(
+        &mut self,
+        state: &mut State,
+        class: String,
+        new_pc: u32,
+        descriptor: String,
+        args: Vec<Expr>,
+    ) -> DecompileResult<()> {
+        let new = Expr::New {
+            class: class.clone(),
+            descriptor,
+            args,
+        };
+        let uninitialized = Expr::Uninitialized { class, pc: new_pc };
+        let copies: Vec<usize> = (0..state.stack.len())
+            .filter(|i| state.stack[*i].expr == uninitialized)
+            .collect();
+        match copies.as_slice() {
+            [] => self.emit(state, Stmt::Expr(new)),
+            [copy] => state.stack[*copy].expr = new,
+            _ => {
+                let temporary = self.temporary(Kind::Reference);
+                self.emit(state, Stmt::assign(Expr::Var(temporary), new));
+                for copy in copies {
+                    state.stack[copy].expr = Expr::Var(temporary);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether a discarded value is javac's check that the enclosing instance of
+// an inner class it is about to construct isn't null, which `q.new Inner()`
+// compiles to:
+//   new Inner; dup; aload q; dup; invokestatic Objects.requireNonNull; pop
+fn checks_enclosing_instance(stack: &[Value], discarded: &Expr) -> bool {
+    let Expr::Invoke {
+        owner,
+        name,
+        descriptor,
+        object: None,
+        args,
+        ..
+    } = discarded
+    else {
+        return false;
+    };
+    let [.., Value {
+        expr: Expr::Uninitialized { .. },
+        ..
+    }, instance] = stack
+    else {
+        return false;
+    };
+    owner == "java/util/Objects"
+        && name == "requireNonNull"
+        && descriptor == "(Ljava/lang/Object;)Ljava/lang/Object;"
+        && args.as_slice() == std::slice::from_ref(&instance.expr)
+}
+
+// Whether two copies of the value can be evaluated one after the other with
+// the same result.
+fn is_duplicable(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(_) | Expr::Var(_) | Expr::Uninitialized { .. } => true,
+        Expr::Field { object, .. } => object.as_deref().is_none_or(is_duplicable),
+        _ => false,
+    }
+}
+
+// What evaluating an expression or running a statement reads and changes.
+#[derive(Debug, Default)]
+struct Effects {
+    reads: HashSet<Var>,
+    writes: HashSet<Var>,
+    heap_read: bool,
+    heap_write: bool,
+}
+
+impl Effects {
+    fn of_expr(expr: &Expr) -> Effects {
+        let mut effects = Effects::default();
+        effects.add_expr(expr);
+        effects
+    }
+
+    fn of_stmt(stmt: &Stmt) -> Effects {
+        let mut effects = Effects::default();
+        match stmt {
+            Stmt::Assign { target, value } => {
+                effects.add_target(target);
+                effects.add_expr(value);
+            }
+            Stmt::Expr(expr) => effects.add_expr(expr),
+            Stmt::MonitorEnter(expr) | Stmt::MonitorExit(expr) => {
+                effects.add_expr(expr);
+                effects.heap_write = true;
+            }
+        }
+        effects
+    }
+
+    fn add_target(&mut self, target: &Expr) {
+        match target {
+            Expr::Var(var) => {
+                self.writes.insert(*var);
+            }
+            _ => {
+                for child in target.children() {
+                    self.add_expr(child);
+                }
+                self.heap_write = true;
+            }
+        }
+    }
+
+    fn add_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Var(var) => {
+                self.reads.insert(*var);
+            }
+            Expr::Assign { target, value } => {
+                self.add_target(target);
+                self.add_expr(value);
+                return;
+            }
+            Expr::Field { .. } | Expr::ArrayElement { .. } | Expr::ArrayLength(_) => {
+                self.heap_read = true
+            }
+            Expr::Invoke { .. } | Expr::InvokeDynamic { .. } | Expr::New { .. } => {
+                self.heap_read = true;
+                self.heap_write = true;
+            }
+            _ => {}
+        }
+        for child in expr.children() {
+            self.add_expr(child);
+        }
+    }
+
+    // Whether running `self` and `later` in the other order could give a
+    // different result.
+    fn conflicts(&self, later: &Effects) -> bool {
+        self.reads.iter().any(|var| later.writes.contains(var))
+            || self
+                .writes
+                .iter()
+                .any(|var| later.reads.contains(var) || later.writes.contains(var))
+            || (self.heap_write && (later.heap_read || later.heap_write))
+            || (self.heap_read && later.heap_write)
+    }
+}
+
+fn find(parent: &mut [usize], mut x: usize) -> usize {
+    while parent[x] != x {
+        parent[x] = parent[parent[x]];
+        x = parent[x];
+    }
+    x
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    if a != b {
+        parent[b] = a;
+    }
+}
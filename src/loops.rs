@@ -0,0 +1,353 @@
+use crate::cfg::{BlockId, Cfg};
+use crate::dominators::DominatorTree;
+use std::collections::BTreeSet;
+
+/// A natural loop: a header that dominates the blocks jumping back to it, and
+/// every block that can reach one of those without going through the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Loop {
+    pub header: BlockId,
+    /// The blocks with a back edge to the header.
+    pub latches: Vec<BlockId>,
+    /// Every block of the loop, the header and nested loops included.
+    pub body: BTreeSet<BlockId>,
+    /// The edges leaving the loop, as (block inside, block outside).
+    pub exits: Vec<(BlockId, BlockId)>,
+    /// The index of the innermost enclosing loop.
+    pub parent: Option<usize>,
+    /// 1 for an outermost loop.
+    pub depth: usize,
+}
+
+/// A cycle with more than one way in, so no block dominates the rest and it
+/// can't be written as a structured loop without duplicating code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IrreducibleRegion {
+    /// The blocks of the cycle with an edge into them from outside.
+    pub entries: BTreeSet<BlockId>,
+    pub body: BTreeSet<BlockId>,
+}
+
+/// The loops of a method, found from the back edges in its dominator tree.
+#[derive(Debug, Clone)]
+pub struct LoopForest {
+    // outer loops come before the loops nested in them
+    loops: Vec<Loop>,
+    irreducible: Vec<IrreducibleRegion>,
+    innermost: Vec<Option<usize>>,
+}
+
+impl LoopForest {
+    pub fn new(cfg: &Cfg, dominators: &DominatorTree) -> LoopForest {
+        let count = cfg.blocks().len();
+
+        let mut headers: Vec<(BlockId, Vec<BlockId>)> = Vec::new();
+        let mut retreating = Vec::new();
+        let successors: Vec<Vec<BlockId>> = (0..count)
+            .map(|id| cfg.successors(id).map(|edge| edge.to).collect())
+            .collect();
+        let (preorder, postorder) = depth_first_numbering(&successors);
+        for edge in cfg.edges() {
+            if !dominators.is_reachable(edge.from) {
+                continue;
+            }
+            if dominators.dominates(edge.to, edge.from) {
+                match headers.iter_mut().find(|(header, _)| *header == edge.to) {
+                    Some((_, latches)) => {
+                        if !latches.contains(&edge.from) {
+                            latches.push(edge.from);
+                        }
+                    }
+                    None => headers.push((edge.to, vec![edge.from])),
+                }
+            } else if preorder[edge.to] <= preorder[edge.from]
+                && postorder[edge.from] <= postorder[edge.to]
+            {
+                // jumps back to a block on the current depth first path
+                // without it dominating
+                retreating.push((edge.from, edge.to));
+            }
+        }
+
+        let mut loops: Vec<Loop> = headers
+            .into_iter()
+            .map(|(header, latches)| {
+                let body = natural_loop_body(cfg, header, &latches);
+                let exits = body
+                    .iter()
+                    .flat_map(|id| cfg.successors(*id))
+                    .filter(|edge| !body.contains(&edge.to))
+                    .map(|edge| (edge.from, edge.to))
+                    .collect();
+                Loop {
+                    header,
+                    latches,
+                    body,
+                    exits,
+                    parent: None,
+                    depth: 1,
+                }
+            })
+            .collect();
+
+        // a loop nested in another has a strictly smaller body, so sorting
+        // largest first puts parents before their children
+        loops.sort_by(|a, b| {
+            b.body
+                .len()
+                .cmp(&a.body.len())
+                .then(a.header.cmp(&b.header))
+        });
+        for i in 0..loops.len() {
+            let parent = (0..i).rev().find(|j| {
+                loops[*j].body.len() > loops[i].body.len()
+                    && loops[*j].body.contains(&loops[i].header)
+            });
+            loops[i].parent = parent;
+            loops[i].depth = parent.map_or(1, |parent| loops[parent].depth + 1);
+        }
+
+        let mut innermost = vec![None; count];
+        for (index, lp) in loops.iter().enumerate() {
+            for id in &lp.body {
+                innermost[*id] = Some(index);
+            }
+        }
+
+        let mut irreducible: Vec<IrreducibleRegion> = Vec::new();
+        for (from, to) in retreating {
+            if irreducible.iter().any(|region| region.body.contains(&to)) {
+                continue;
+            }
+            // look for the cycle inside the innermost loop holding both ends,
+            // leaving out its header so the loop itself isn't the cycle
+            let enclosing = innermost[to].filter(|index| loops[*index].body.contains(&from));
+            let nodes: BTreeSet<BlockId> = match enclosing {
+                Some(index) => loops[index]
+                    .body
+                    .iter()
+                    .copied()
+                    .filter(|id| *id != loops[index].header)
+                    .collect(),
+                None => (0..count)
+                    .filter(|id| dominators.is_reachable(*id))
+                    .collect(),
+            };
+            let body = strongly_connected(cfg, &nodes, to);
+            if !body.contains(&from) {
+                continue;
+            }
+            let entries = body
+                .iter()
+                .copied()
+                .filter(|id| {
+                    *id == 0 || cfg.predecessors(*id).any(|edge| !body.contains(&edge.from))
+                })
+                .collect();
+            irreducible.push(IrreducibleRegion { entries, body });
+        }
+
+        LoopForest {
+            loops,
+            irreducible,
+            innermost,
+        }
+    }
+
+    pub fn loops(&self) -> &[Loop] {
+        &self.loops
+    }
+
+    pub fn irreducible_regions(&self) -> &[IrreducibleRegion] {
+        &self.irreducible
+    }
+
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+
+    /// The index of the innermost loop containing the block.
+    pub fn innermost_loop(&self, id: BlockId) -> Option<usize> {
+        self.innermost[id]
+    }
+
+    /// The number of loops the block is in.
+    pub fn depth(&self, id: BlockId) -> usize {
+        self.innermost[id].map_or(0, |index| self.loops[index].depth)
+    }
+
+    /// The loop with this header.
+    pub fn loop_with_header(&self, header: BlockId) -> Option<&Loop> {
+        self.loops.iter().find(|lp| lp.header == header)
+    }
+}
+
+fn natural_loop_body(cfg: &Cfg, header: BlockId, latches: &[BlockId]) -> BTreeSet<BlockId> {
+    let mut body = BTreeSet::from([header]);
+    let mut worklist: Vec<BlockId> = latches.to_vec();
+    while let Some(id) = worklist.pop() {
+        if body.insert(id) {
+            worklist.extend(cfg.predecessors(id).map(|edge| edge.from));
+        }
+    }
+    body
+}
+
+/// Preorder and postorder numbers of a depth first walk from block 0, given
+/// the successors of every block; unreached blocks get `usize::MAX`.
+pub(crate) fn depth_first_numbering(successors: &[Vec<BlockId>]) -> (Vec<usize>, Vec<usize>) {
+    let count = successors.len();
+    let mut preorder = vec![usize::MAX; count];
+    let mut postorder = vec![usize::MAX; count];
+    if count == 0 {
+        return (preorder, postorder);
+    }
+
+    let (mut pre, mut post) = (1, 0);
+    let mut stack = vec![(0, 0)];
+    preorder[0] = 0;
+    while let Some((id, next)) = stack.pop() {
+        if let Some(successor) = successors[id].get(next) {
+            stack.push((id, next + 1));
+            if preorder[*successor] == usize::MAX {
+                preorder[*successor] = pre;
+                pre += 1;
+                stack.push((*successor, 0));
+            }
+        } else {
+            postorder[id] = post;
+            post += 1;
+        }
+    }
+    (preorder, postorder)
+}
+
+// The blocks of `nodes` that `start` reaches and that reach `start` back,
+// only going through `nodes`.
+fn strongly_connected(cfg: &Cfg, nodes: &BTreeSet<BlockId>, start: BlockId) -> BTreeSet<BlockId> {
+    let walk = |forward: bool| {
+        let mut seen = BTreeSet::from([start]);
+        let mut worklist = vec![start];
+        while let Some(id) = worklist.pop() {
+            let next: Vec<BlockId> = if forward {
+                cfg.successors(id).map(|edge| edge.to).collect()
+            } else {
+                cfg.predecessors(id).map(|edge| edge.from).collect()
+            };
+            for next in next {
+                if nodes.contains(&next) && seen.insert(next) {
+                    worklist.push(next);
+                }
+            }
+        }
+        seen
+    };
+    walk(true).intersection(&walk(false)).copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+    use crate::instruction::{Instruction, Opcode, Operand};
+
+    fn forest(asm: &Assembler) -> LoopForest {
+        let code = asm.assemble(0, 0).unwrap();
+        let cfg = Cfg::from_code(&code.code, &code.exception_table).unwrap();
+        LoopForest::new(&cfg, &DominatorTree::dominators(&cfg))
+    }
+
+    fn local(asm: &mut Assembler, opcode: Opcode, index: u16) {
+        asm.emit(Instruction::new(opcode, Operand::Local(index)));
+    }
+
+    fn iinc(asm: &mut Assembler, index: u16, value: i16) {
+        asm.emit(Instruction::new(
+            Opcode::Iinc,
+            Operand::Iinc { index, value },
+        ));
+    }
+
+    #[test]
+    fn nested_loops_know_their_parent() {
+        let mut asm = Assembler::new();
+        let (outer, inner, latch, end) = (
+            asm.new_label(),
+            asm.new_label(),
+            asm.new_label(),
+            asm.new_label(),
+        );
+        asm.op(Opcode::Iconst0);
+        local(&mut asm, Opcode::Istore, 0);
+        asm.place(outer);
+        local(&mut asm, Opcode::Iload, 0);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.branch(Opcode::IfIcmpge, end);
+        asm.place(inner);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.branch(Opcode::Ifeq, latch);
+        iinc(&mut asm, 1, -1);
+        asm.branch(Opcode::Goto, inner);
+        asm.place(latch);
+        iinc(&mut asm, 0, 1);
+        asm.branch(Opcode::Goto, outer);
+        asm.place(end);
+        asm.op(Opcode::Return);
+        let forest = forest(&asm);
+
+        assert!(forest.is_reducible());
+        assert_eq!(
+            forest.loops(),
+            [
+                Loop {
+                    header: 1,
+                    latches: vec![4],
+                    body: BTreeSet::from([1, 2, 3, 4]),
+                    exits: vec![(1, 5)],
+                    parent: None,
+                    depth: 1,
+                },
+                Loop {
+                    header: 2,
+                    latches: vec![3],
+                    body: BTreeSet::from([2, 3]),
+                    exits: vec![(2, 4)],
+                    parent: Some(0),
+                    depth: 2,
+                },
+            ]
+        );
+        let depths: Vec<_> = (0..6).map(|id| forest.depth(id)).collect();
+        assert_eq!(depths, [0, 1, 2, 2, 1, 0]);
+        assert_eq!(forest.innermost_loop(3), Some(1));
+        assert_eq!(forest.innermost_loop(4), Some(0));
+        assert_eq!(forest.innermost_loop(5), None);
+        assert_eq!(forest.loop_with_header(2).map(|lp| lp.depth), Some(2));
+        assert!(forest.loop_with_header(3).is_none());
+    }
+
+    #[test]
+    fn cycles_entered_twice_are_irreducible() {
+        let mut asm = Assembler::new();
+        let (first, second) = (asm.new_label(), asm.new_label());
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, second);
+        asm.place(first);
+        iinc(&mut asm, 0, 1);
+        asm.place(second);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifne, first);
+        asm.op(Opcode::Return);
+        let forest = forest(&asm);
+
+        assert!(forest.loops().is_empty());
+        assert!(!forest.is_reducible());
+        assert_eq!(
+            forest.irreducible_regions(),
+            [IrreducibleRegion {
+                entries: BTreeSet::from([1, 2]),
+                body: BTreeSet::from([1, 2]),
+            }]
+        );
+    }
+}
@@ -1,14 +1,27 @@
 use clap::Parser;
+use decompile_rs::printer::{Braces, Indent, Style};
+use decompile_rs::{decompile, maxs};
 use std::path::PathBuf;
 use std::process::ExitCode;
 
-mod decompile;
-mod error;
-mod types;
-
 #[derive(Debug, Parser)]
 struct Cli {
     file: PathBuf,
+    /// Report methods whose declared max_stack or max_locals is too small
+    #[arg(long)]
+    check_maxs: bool,
+    /// Spaces to indent by
+    #[arg(long, default_value_t = 4)]
+    indent: usize,
+    /// Indent with tabs instead of spaces
+    #[arg(long)]
+    tabs: bool,
+    /// Columns lines are broken to fit within
+    #[arg(long, default_value_t = 100)]
+    line_width: usize,
+    /// Put opening braces on their own line
+    #[arg(long)]
+    next_line_braces: bool,
 }
 
 fn main() -> ExitCode {
@@ -16,9 +29,38 @@ fn main() -> ExitCode {
 
     let args = Cli::parse();
 
+    let style = Style {
+        indent: match args.tabs {
+            true => Indent::Tab,
+            false => Indent::Spaces(args.indent),
+        },
+        braces: match args.next_line_braces {
+            true => Braces::NextLine,
+            false => Braces::EndOfLine,
+        },
+        line_width: args.line_width,
+    };
     let mut dec = decompile::Decompile::new(args.file)
         .map_err(|e| eprintln!("{}", e))
-        .unwrap();
+        .unwrap()
+        .with_style(style);
+
+    if args.check_maxs {
+        let violations = dec.read_class_file().and_then(|c| maxs::check_maxs(&c));
+        return match violations {
+            Ok(violations) if violations.is_empty() => ExitCode::SUCCESS,
+            Ok(violations) => {
+                for violation in violations {
+                    println!("{}", violation);
+                }
+                ExitCode::FAILURE
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                ExitCode::FAILURE
+            }
+        };
+    }
 
     if let Err(e) = dec.decompile() {
         eprintln!("{}", e);
@@ -0,0 +1,486 @@
+use crate::decompile::DecompileResult;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::DecompileError;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::types::{
+    Attribute, ClassFile, ConstantPool, ConstantPoolType, ExceptionTable, MethodInfo,
+};
+use std::collections::BTreeMap;
+
+/// The `max_stack` and `max_locals` of a `Code` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Maxs {
+    pub max_stack: u16,
+    pub max_locals: u16,
+}
+
+/// A method whose `Code` attribute declares a smaller `max_stack` or
+/// `max_locals` than its code needs.
+#[derive(Debug, Clone)]
+pub struct MaxsViolation {
+    pub method_name: String,
+    pub descriptor: String,
+    pub declared: Maxs,
+    pub computed: Maxs,
+}
+
+impl std::fmt::Display for MaxsViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}: declared stack={} locals={}, needs stack={} locals={}",
+            self.method_name,
+            self.descriptor,
+            self.declared.max_stack,
+            self.declared.max_locals,
+            self.computed.max_stack,
+            self.computed.max_locals
+        )
+    }
+}
+
+/// Computes `max_stack` and `max_locals` for a method body.
+///
+/// `max_locals` covers the parameters (and `this` unless the method is
+/// static) and every local variable the code touches, with long and double
+/// taking two slots. `max_stack` comes from following every path through the
+/// code, including into exception handlers, which start with just the
+/// exception on the stack.
+pub fn compute_maxs(
+    instructions: &[(u32, Instruction)],
+    exception_table: &[ExceptionTable],
+    descriptor: &str,
+    is_static: bool,
+    constant_pool: &ConstantPool,
+) -> DecompileResult<Maxs> {
+    let method = MethodDescriptor::parse(descriptor)
+        .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+
+    let mut max_locals = method.parameter_slots() + if is_static { 0 } else { 1 };
+    for (_, instruction) in instructions {
+        if let Some(index) = instruction.local_index() {
+            let width = match instruction.opcode {
+                Opcode::Lload | Opcode::Dload | Opcode::Lstore | Opcode::Dstore => 2,
+                _ => 1,
+            };
+            max_locals = max_locals.max(index + width);
+        }
+    }
+
+    Ok(Maxs {
+        max_stack: compute_max_stack(instructions, exception_table, constant_pool)?,
+        max_locals,
+    })
+}
+
+fn compute_max_stack(
+    instructions: &[(u32, Instruction)],
+    exception_table: &[ExceptionTable],
+    constant_pool: &ConstantPool,
+) -> DecompileResult<u16> {
+    let index_of: BTreeMap<u32, usize> = instructions
+        .iter()
+        .enumerate()
+        .map(|(i, (pc, _))| (*pc, i))
+        .collect();
+    let lookup = |pc: u32| {
+        index_of
+            .get(&pc)
+            .copied()
+            .ok_or(DecompileError::InvalidBranchTarget(pc))
+    };
+
+    let mut depth_at: Vec<Option<u16>> = vec![None; instructions.len()];
+    let mut worklist = Vec::new();
+    let mut max_stack = 0;
+
+    if !instructions.is_empty() {
+        visit(instructions, &mut depth_at, &mut worklist, 0, 0)?;
+    }
+
+    while let Some(i) = worklist.pop() {
+        let (pc, instruction) = &instructions[i];
+        let depth = depth_at[i].expect("only visited instructions are queued");
+        max_stack = max_stack.max(depth);
+
+        // handlers covering this instruction start with the exception alone
+        // on the stack
+        for entry in exception_table {
+            if (entry.start_pc as u32..entry.end_pc as u32).contains(pc) {
+                max_stack = max_stack.max(1);
+                let handler = lookup(entry.handler_pc as u32)?;
+                visit(instructions, &mut depth_at, &mut worklist, handler, 1)?;
+            }
+        }
+
+        let (pops, pushes) = stack_effect(instruction, constant_pool)?;
+        let after = depth
+            .checked_sub(pops)
+            .ok_or(DecompileError::StackUnderflow(*pc))?
+            + pushes;
+        max_stack = max_stack.max(after);
+
+        for target in instruction.targets() {
+            let target = lookup(*target)?;
+            visit(instructions, &mut depth_at, &mut worklist, target, after)?;
+        }
+        if i + 1 < instructions.len() {
+            match instruction.opcode {
+                // the subroutine returns to the next instruction with the
+                // return address consumed
+                Opcode::Jsr | Opcode::JsrW => {
+                    visit(instructions, &mut depth_at, &mut worklist, i + 1, depth)?
+                }
+                opcode if !opcode.ends_flow() => {
+                    visit(instructions, &mut depth_at, &mut worklist, i + 1, after)?
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok(max_stack)
+}
+
+// Records the stack depth on entry to instruction `i`, queueing it the first
+// time it is reached.
+fn visit(
+    instructions: &[(u32, Instruction)],
+    depth_at: &mut [Option<u16>],
+    worklist: &mut Vec<usize>,
+    i: usize,
+    depth: u16,
+) -> DecompileResult<()> {
+    match depth_at[i] {
+        None => {
+            depth_at[i] = Some(depth);
+            worklist.push(i);
+            Ok(())
+        }
+        Some(existing) if existing == depth => Ok(()),
+        Some(existing) => Err(DecompileError::InconsistentStackHeight(
+            instructions[i].0,
+            existing,
+            depth,
+        )),
+    }
+}
+
+/// The number of operand stack slots an instruction pops and pushes.
+pub fn stack_effect(
+    instruction: &Instruction,
+    constant_pool: &ConstantPool,
+) -> DecompileResult<(u16, u16)> {
+    use Opcode::*;
+
+    // a missing operand resolves as the invalid index 0
+    let constant = || -> DecompileResult<u16> { Ok(instruction.constant_index().unwrap_or(0)) };
+    let invalid_ref = |index: u16| DecompileError::NoSuchConstantPoolEntry(index, 0);
+    let field_slots = |index: u16| -> DecompileResult<u16> {
+        let member = constant_pool
+            .member_ref(index)
+            .ok_or_else(|| invalid_ref(index))?;
+        let field_type = FieldType::parse(member.descriptor)
+            .ok_or_else(|| DecompileError::InvalidDescriptor(member.descriptor.to_string()))?;
+        Ok(field_type.slots())
+    };
+    let method = |descriptor: &str| {
+        MethodDescriptor::parse(descriptor)
+            .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))
+    };
+
+    let effect = match instruction.opcode {
+        Nop | Iinc | Goto | GotoW | Ret | Return => (0, 0),
+        AconstNull | IconstM1 | Iconst0 | Iconst1 | Iconst2 | Iconst3 | Iconst4 | Iconst5
+        | Fconst0 | Fconst1 | Fconst2 | Bipush | Sipush | Iload | Fload | Aload | Iload0
+        | Iload1 | Iload2 | Iload3 | Fload0 | Fload1 | Fload2 | Fload3 | Aload0 | Aload1
+        | Aload2 | Aload3 | New | Jsr | JsrW => (0, 1),
+        Lconst0 | Lconst1 | Dconst0 | Dconst1 | Ldc2W | Lload | Dload | Lload0 | Lload1
+        | Lload2 | Lload3 | Dload0 | Dload1 | Dload2 | Dload3 => (0, 2),
+        Ldc | LdcW => {
+            let index = constant()?;
+            match constant_pool.entry(index) {
+                Some(ConstantPoolType::ConstantDynamic { .. }) => {
+                    let (_, _, descriptor) = constant_pool
+                        .dynamic(index)
+                        .ok_or_else(|| invalid_ref(index))?;
+                    let field_type = FieldType::parse(descriptor)
+                        .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.to_string()))?;
+                    (0, field_type.slots())
+                }
+                _ => (0, 1),
+            }
+        }
+        Iaload | Faload | Aaload | Baload | Caload | Saload => (2, 1),
+        Laload | Daload => (2, 2),
+        Istore | Fstore | Astore | Istore0 | Istore1 | Istore2 | Istore3 | Fstore0 | Fstore1
+        | Fstore2 | Fstore3 | Astore0 | Astore1 | Astore2 | Astore3 | Pop | Ifeq | Ifne | Iflt
+        | Ifge | Ifgt | Ifle | Ifnull | Ifnonnull | Tableswitch | Lookupswitch | Ireturn
+        | Freturn | Areturn | Athrow | Monitorenter | Monitorexit => (1, 0),
+        Lstore | Dstore | Lstore0 | Lstore1 | Lstore2 | Lstore3 | Dstore0 | Dstore1 | Dstore2
+        | Dstore3 | Pop2 | IfIcmpeq | IfIcmpne | IfIcmplt | IfIcmpge | IfIcmpgt | IfIcmple
+        | IfAcmpeq | IfAcmpne | Lreturn | Dreturn => (2, 0),
+        Iastore | Fastore | Aastore | Bastore | Castore | Sastore => (3, 0),
+        Lastore | Dastore => (4, 0),
+        Dup => (1, 2),
+        DupX1 => (2, 3),
+        DupX2 => (3, 4),
+        Dup2 => (2, 4),
+        Dup2X1 => (3, 5),
+        Dup2X2 => (4, 6),
+        Swap => (2, 2),
+        Iadd | Isub | Imul | Idiv | Irem | Ishl | Ishr | Iushr | Iand | Ior | Ixor | Fadd
+        | Fsub | Fmul | Fdiv | Frem | Fcmpl | Fcmpg => (2, 1),
+        Ladd | Lsub | Lmul | Ldiv | Lrem | Land | Lor | Lxor | Dadd | Dsub | Dmul | Ddiv | Drem => {
+            (4, 2)
+        }
+        Lshl | Lshr | Lushr => (3, 2),
+        Lcmp | Dcmpl | Dcmpg => (4, 1),
+        Ineg | Fneg | I2f | F2i | I2b | I2c | I2s | Newarray | Anewarray | Arraylength
+        | Checkcast | Instanceof => (1, 1),
+        Lneg | Dneg | L2d | D2l => (2, 2),
+        I2l | I2d | F2l | F2d => (1, 2),
+        L2i | L2f | D2i | D2f => (2, 1),
+        Getstatic => (0, field_slots(constant()?)?),
+        Putstatic => (field_slots(constant()?)?, 0),
+        Getfield => (1, field_slots(constant()?)?),
+        Putfield => (1 + field_slots(constant()?)?, 0),
+        Invokevirtual | Invokespecial | Invokestatic | Invokeinterface => {
+            let index = constant()?;
+            let member = constant_pool
+                .member_ref(index)
+                .ok_or_else(|| invalid_ref(index))?;
+            let descriptor = method(member.descriptor)?;
+            let receiver = if instruction.opcode == Invokestatic {
+                0
+            } else {
+                1
+            };
+            (
+                descriptor.parameter_slots() + receiver,
+                descriptor.return_slots(),
+            )
+        }
+        Invokedynamic => {
+            let index = constant()?;
+            let (_, _, descriptor) = constant_pool
+                .dynamic(index)
+                .ok_or_else(|| invalid_ref(index))?;
+            let descriptor = method(descriptor)?;
+            (descriptor.parameter_slots(), descriptor.return_slots())
+        }
+        Multianewarray => match instruction.operand {
+            Operand::MultiANewArray { dimensions, .. } => (dimensions as u16, 1),
+            _ => unreachable!("multianewarray always has dimensions"),
+        },
+        Wide => unreachable!("wide is folded into the instruction it modifies"),
+    };
+
+    Ok(effect)
+}
+
+/// Computes the maxs of a method, `None` if it has no code.
+pub fn compute_method_maxs(
+    class_file: &ClassFile,
+    method: &MethodInfo,
+) -> DecompileResult<Option<Maxs>> {
+    let Some(Attribute::Code {
+        code,
+        exception_table,
+        ..
+    }) = method.code()
+    else {
+        return Ok(None);
+    };
+
+    let constant_pool = class_file.constant_pool();
+    let descriptor =
+        method
+            .descriptor(constant_pool)
+            .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+                method.descriptor_index,
+            ))?;
+    let instructions = instruction::decode(code)?;
+
+    compute_maxs(
+        &instructions,
+        exception_table,
+        descriptor,
+        method.is_static(),
+        constant_pool,
+    )
+    .map(Some)
+}
+
+/// Reports every method whose declared `max_stack` or `max_locals` is smaller
+/// than what its code needs. Declared values that are larger than needed are
+/// legal and not reported.
+pub fn check_maxs(class_file: &ClassFile) -> DecompileResult<Vec<MaxsViolation>> {
+    let constant_pool = class_file.constant_pool();
+    let mut violations = Vec::new();
+    for method in &class_file.methods {
+        let Some(Attribute::Code {
+            max_stack,
+            max_locals,
+            ..
+        }) = method.code()
+        else {
+            continue;
+        };
+        let Some(computed) = compute_method_maxs(class_file, method)? else {
+            continue;
+        };
+
+        if computed.max_stack > *max_stack || computed.max_locals > *max_locals {
+            violations.push(MaxsViolation {
+                method_name: method.name(constant_pool).unwrap_or_default().to_string(),
+                descriptor: method
+                    .descriptor(constant_pool)
+                    .unwrap_or_default()
+                    .to_string(),
+                declared: Maxs {
+                    max_stack: *max_stack,
+                    max_locals: *max_locals,
+                },
+                computed,
+            });
+        }
+    }
+
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+    use crate::builder::{ClassBuilder, ConstantPoolBuilder, MethodBuilder};
+    use crate::types::{ACC_PUBLIC, ACC_STATIC};
+
+    fn local(asm: &mut Assembler, opcode: Opcode, index: u16) {
+        asm.emit(Instruction::new(opcode, Operand::Local(index)));
+    }
+
+    fn maxs(asm: &Assembler, descriptor: &str, is_static: bool) -> Maxs {
+        let code = asm.assemble(0, 0).unwrap();
+        let instructions = instruction::decode(&code.code).unwrap();
+        let pool = ConstantPoolBuilder::new();
+        compute_maxs(
+            &instructions,
+            &code.exception_table,
+            descriptor,
+            is_static,
+            pool.pool(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn longs_and_doubles_take_two_slots() {
+        let mut asm = Assembler::new();
+        local(&mut asm, Opcode::Lload, 0);
+        asm.op(Opcode::L2d);
+        local(&mut asm, Opcode::Dstore, 5);
+        asm.op(Opcode::Return);
+        let computed = maxs(&asm, "(JD)V", true);
+        assert_eq!(
+            computed,
+            Maxs {
+                max_stack: 2,
+                max_locals: 7
+            }
+        );
+        // the parameters and `this` count when the code doesn't touch them
+        let mut asm = Assembler::new();
+        asm.op(Opcode::Return);
+        assert_eq!(maxs(&asm, "(JI)V", false).max_locals, 4);
+    }
+
+    #[test]
+    fn handlers_start_with_the_exception() {
+        let mut asm = Assembler::new();
+        let (start, end, handler) = (asm.new_label(), asm.new_label(), asm.new_label());
+        asm.place(start);
+        asm.op(Opcode::Iconst0);
+        asm.op(Opcode::Pop);
+        asm.place(end);
+        asm.op(Opcode::Return);
+        asm.place(handler);
+        asm.op(Opcode::Dup);
+        asm.op(Opcode::Pop2);
+        asm.op(Opcode::Return);
+        asm.try_catch(start, end, handler, 0);
+        assert_eq!(maxs(&asm, "()V", true).max_stack, 2);
+    }
+
+    #[test]
+    fn effects_count_slots() {
+        let mut pool = ConstantPoolBuilder::new();
+        let method = pool.method_ref("A", "m", "(JI)D");
+        let long_field = pool.field_ref("A", "l", "J");
+        let double_field = pool.field_ref("A", "d", "D");
+        let array = pool.class("[[[I");
+        let effect = |pool: &ConstantPoolBuilder, opcode, operand| {
+            stack_effect(&Instruction::new(opcode, operand), pool.pool()).unwrap()
+        };
+        let constant = Operand::Constant;
+        assert_eq!(
+            effect(&pool, Opcode::Invokevirtual, constant(method)),
+            (4, 2)
+        );
+        assert_eq!(
+            effect(&pool, Opcode::Invokestatic, constant(method)),
+            (3, 2)
+        );
+        assert_eq!(
+            effect(&pool, Opcode::Getfield, constant(long_field)),
+            (1, 2)
+        );
+        assert_eq!(
+            effect(&pool, Opcode::Putfield, constant(long_field)),
+            (3, 0)
+        );
+        assert_eq!(
+            effect(&pool, Opcode::Putstatic, constant(double_field)),
+            (2, 0)
+        );
+        let dimensions = Operand::MultiANewArray {
+            index: array,
+            dimensions: 3,
+        };
+        assert_eq!(effect(&pool, Opcode::Multianewarray, dimensions), (3, 1));
+        assert_eq!(effect(&pool, Opcode::Dup2X2, Operand::None), (4, 6));
+        assert_eq!(effect(&pool, Opcode::Lcmp, Operand::None), (4, 1));
+        // a reference to nothing is an error rather than a guess
+        let missing = stack_effect(
+            &Instruction::new(Opcode::Getstatic, Operand::Constant(100)),
+            pool.pool(),
+        );
+        assert!(matches!(
+            missing,
+            Err(DecompileError::NoSuchConstantPoolEntry(100, 0))
+        ));
+    }
+
+    #[test]
+    fn too_small_declarations_are_reported() {
+        let mut asm = Assembler::new();
+        local(&mut asm, Opcode::Lload, 0);
+        asm.op(Opcode::Lreturn);
+        let mut class = ClassBuilder::new("Maxs");
+        let declared = [("small", 1, 2), ("exact", 2, 2), ("large", 10, 10)];
+        for (name, max_stack, max_locals) in declared {
+            let code = asm.assemble(max_stack, max_locals).unwrap();
+            let method = MethodBuilder::new(ACC_PUBLIC | ACC_STATIC, name, "(J)J").code(code);
+            class = class.method(method);
+        }
+        let violations = check_maxs(&class.build()).unwrap();
+        let [violation] = violations.as_slice() else {
+            panic!("{violations:?}");
+        };
+        assert_eq!(
+            violation.to_string(),
+            "small(J)J: declared stack=1 locals=2, needs stack=2 locals=2"
+        );
+    }
+}
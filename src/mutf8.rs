@@ -0,0 +1,116 @@
+//! Modified UTF-8, the string encoding used by `CONSTANT_Utf8_info`.
+//!
+//! It differs from standard UTF-8 in two ways: the null character is encoded
+//! as two bytes (`0xC0 0x80`) and supplementary characters are encoded as a
+//! surrogate pair, each surrogate taking three bytes.
+//!
+//! see https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4.7
+
+/// Decodes Modified UTF-8 into UTF-16 code units. Unpaired surrogates are
+/// kept as they are, which is why this doesn't produce a `String`.
+pub fn decode_utf16(bytes: &[u8]) -> Option<Vec<u16>> {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let x = bytes[i] as u16;
+        if x & 0x80 == 0 {
+            // "Code points in the range '\u0001' to '\u007F' are represented by a single byte"
+            if x == 0 {
+                return None;
+            }
+            units.push(x);
+            i += 1;
+        } else if x & 0xE0 == 0xC0 {
+            let y = *bytes.get(i + 1)? as u16;
+            if y & 0xC0 != 0x80 {
+                return None;
+            }
+            units.push(((x & 0x1F) << 6) | (y & 0x3F));
+            i += 2;
+        } else if x & 0xF0 == 0xE0 {
+            let y = *bytes.get(i + 1)? as u16;
+            let z = *bytes.get(i + 2)? as u16;
+            if y & 0xC0 != 0x80 || z & 0xC0 != 0x80 {
+                return None;
+            }
+            units.push(((x & 0xF) << 12) | ((y & 0x3F) << 6) | (z & 0x3F));
+            i += 3;
+        } else {
+            // "No byte may have the value (byte)0 or lie in the range (byte)0xf0 to (byte)0xff."
+            return None;
+        }
+    }
+
+    Some(units)
+}
+
+/// Decodes Modified UTF-8 into a `String`. Unpaired surrogates, which a
+/// `String` cannot hold, are replaced with U+FFFD.
+pub fn decode(bytes: &[u8]) -> Option<String> {
+    let units = decode_utf16(bytes)?;
+    Some(String::from_utf16_lossy(&units))
+}
+
+/// Encodes UTF-16 code units, including unpaired surrogates, as Modified UTF-8.
+pub fn encode_utf16(units: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(units.len());
+    for &unit in units {
+        match unit {
+            0x0001..=0x007F => bytes.push(unit as u8),
+            0x0000 | 0x0080..=0x07FF => {
+                bytes.push(0xC0 | ((unit >> 6) & 0x1F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | ((unit >> 12) & 0x0F) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+
+    bytes
+}
+
+pub fn encode(value: &str) -> Vec<u8> {
+    encode_utf16(&value.encode_utf16().collect::<Vec<_>>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nul_takes_two_bytes() {
+        assert_eq!(encode("a\0b"), [b'a', 0xC0, 0x80, b'b']);
+        assert_eq!(decode(&[b'a', 0xC0, 0x80, b'b']).unwrap(), "a\0b");
+        // a raw zero byte isn't allowed
+        assert_eq!(decode_utf16(&[b'a', 0]), None);
+    }
+
+    #[test]
+    fn supplementary_characters_are_surrogate_pairs() {
+        let bytes = [0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80];
+        assert_eq!(encode("😀"), bytes);
+        assert_eq!(decode_utf16(&bytes).unwrap(), [0xD83D, 0xDE00]);
+        assert_eq!(decode(&bytes).unwrap(), "😀");
+        // the four byte form of standard UTF-8 isn't allowed
+        assert_eq!(decode_utf16("😀".as_bytes()), None);
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_kept() {
+        for units in [&[0xD800][..], &[0xDC00, 0x41], &[0xDE00, 0xD83D]] {
+            let bytes = encode_utf16(units);
+            assert_eq!(decode_utf16(&bytes).unwrap(), units);
+        }
+        assert_eq!(decode(&encode_utf16(&[0x41, 0xD800])).unwrap(), "A\u{FFFD}");
+    }
+
+    #[test]
+    fn truncated_sequences_are_invalid() {
+        assert_eq!(decode_utf16(&[0xC3]), None);
+        assert_eq!(decode_utf16(&[0xE2, 0x82]), None);
+        assert_eq!(decode_utf16(&[0xE2, 0x41, 0x82]), None);
+    }
+}
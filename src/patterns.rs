@@ -0,0 +1,249 @@
+use crate::cfg::BlockId;
+use crate::decompile::ClassPath;
+use crate::descriptor::FieldType;
+use crate::dominators::DominatorTree;
+use crate::ir::{Body, Const, Expr, InvokeKind, Pattern, Stmt, Terminator, UnaryOp, Var};
+use crate::types::Attribute;
+use crate::{conditions, exceptions};
+use std::collections::BTreeSet;
+
+/// Turns the `instanceof` patterns javac compiles into a test, a cast and a
+/// store back into patterns, in a body out of SSA form:
+///   if (o instanceof String) { s = (String) o; ... }
+/// becomes `if (o instanceof String s) { ... }` when the code the test
+/// leads to is the only place using the variable. Record patterns are
+/// deconstructed from the accessor calls that follow, see [`deconstruct`].
+///
+/// This runs before [`merge_conditions`](crate::conditions::merge_conditions),
+/// so that a block only the store kept apart from a branch joins the
+/// condition, as in `o instanceof Integer i && i > 3`.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.20.2
+pub fn bind_patterns(body: &mut Body, classes: &dyn ClassPath) {
+    let dominators = exceptions::dominators(body, None);
+    let mut entries = vec![0; body.blocks.len()];
+    for (id, block) in body.blocks.iter().enumerate() {
+        if dominators.is_reachable(id) {
+            for target in block.terminator.targets() {
+                entries[target] += 1;
+            }
+        }
+    }
+
+    for id in 0..body.blocks.len() {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        // the test is still compared with a boolean constant here
+        if let Terminator::If { condition, .. } = &mut body.blocks[id].terminator {
+            let test = std::mem::replace(condition, Expr::Const(Const::Null));
+            let test = conditions::simplify_condition(body, test);
+            if let Terminator::If { condition, .. } = &mut body.blocks[id].terminator {
+                *condition = test;
+            }
+        }
+        let Terminator::If {
+            condition,
+            then,
+            otherwise,
+        } = &body.blocks[id].terminator
+        else {
+            continue;
+        };
+        let (test, holds) = match condition {
+            Expr::Unary {
+                op: UnaryOp::Not,
+                operand,
+            } => (&**operand, *otherwise),
+            condition => (condition, *then),
+        };
+        let Expr::InstanceOf {
+            operand,
+            field_type,
+            pattern: None,
+        } = test
+        else {
+            continue;
+        };
+        if holds == id || entries[holds] != 1 || !matches!(**operand, Expr::Var(_)) {
+            continue;
+        }
+        let Some(binding) = cast_store(&body.blocks[holds].stmts, operand, field_type) else {
+            continue;
+        };
+        if !is_scoped(body, &dominators, binding, holds) {
+            continue;
+        }
+
+        let field_type = field_type.clone();
+        let pattern = deconstruct(body, &dominators, holds, 1, binding, &field_type, classes)
+            .unwrap_or(Pattern::Binding(binding));
+        body.blocks[holds]
+            .stmts
+            .drain(..1 + accessor_calls(&pattern));
+        if let Terminator::If { condition, .. } = &mut body.blocks[id].terminator {
+            let test = match condition {
+                Expr::Unary {
+                    op: UnaryOp::Not,
+                    operand,
+                } => &mut **operand,
+                condition => condition,
+            };
+            if let Expr::InstanceOf { pattern: bound, .. } = test {
+                *bound = Some(pattern);
+            }
+        }
+    }
+}
+
+/// The variable the first of the statements stores `value` cast to
+/// `field_type` in, as javac does after a type test.
+pub fn cast_store(stmts: &[Stmt], value: &Expr, field_type: &FieldType) -> Option<Var> {
+    match stmts.first()? {
+        Stmt::Assign {
+            target: Expr::Var(binding),
+            value:
+                Expr::Cast {
+                    field_type: cast,
+                    operand,
+                },
+        } if cast == field_type && **operand == *value && Expr::Var(*binding) != *value => {
+            Some(*binding)
+        }
+        _ => None,
+    }
+}
+
+/// Whether a variable can be a pattern variable in scope from the start of
+/// `block` on: it is stored once, and only the blocks `block` dominates
+/// mention it.
+pub fn is_scoped(body: &Body, dominators: &DominatorTree, var: Var, block: BlockId) -> bool {
+    if body.parameters.contains(&var) {
+        return false;
+    }
+    let (stores, _, blocks) = mentions(body, var);
+    stores == 1
+        && blocks
+            .iter()
+            .all(|id| dominators.dominates(block, *id) || !dominators.is_reachable(*id))
+}
+
+/// The record pattern a record's type test is, when the block it leads to
+/// starts with javac's calls of the accessors of every component on the
+/// variable the record was stored in, storing each in a variable:
+///   p = (Point) o; x = p.x(); y = p.y();
+/// is `Point(int x, int y)`, with the calls starting at `start`. The
+/// variable can't be used for anything else, since record patterns don't
+/// name the record. The handler javac wraps what the calls throw in a
+/// `MatchException` with is left out when lifting; the calls are left to
+/// the caller to remove.
+///
+/// Only records whose class can be read are deconstructed, as the
+/// components have to be known.
+pub fn deconstruct(
+    body: &Body,
+    dominators: &DominatorTree,
+    block: BlockId,
+    start: usize,
+    record: Var,
+    field_type: &FieldType,
+    classes: &dyn ClassPath,
+) -> Option<Pattern> {
+    let FieldType::Object(name) = field_type else {
+        return None;
+    };
+    let class_file = classes.class_file(name)?;
+    let constant_pool = class_file.constant_pool();
+    let components = class_file
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Record { components, .. } => Some(components),
+            _ => None,
+        })?;
+
+    let stmts = &body.blocks[block].stmts;
+    let mut pattern = Vec::new();
+    for (index, component) in components.iter().enumerate() {
+        let accessor = constant_pool.utf8(component.name_index)?;
+        let descriptor = constant_pool.utf8(component.descriptor_index)?;
+        let Some(Stmt::Assign {
+            target: Expr::Var(var),
+            value:
+                Expr::Invoke {
+                    kind: InvokeKind::Virtual,
+                    owner,
+                    name: called,
+                    descriptor: call,
+                    object: Some(object),
+                    args,
+                },
+        }) = stmts.get(start + index)
+        else {
+            return None;
+        };
+        let matches = owner == name
+            && called == accessor
+            && *call == format!("(){descriptor}")
+            && **object == Expr::Var(record)
+            && args.is_empty()
+            && *var != record;
+        if !matches || !is_scoped(body, dominators, *var, block) {
+            return None;
+        }
+        pattern.push((FieldType::parse(descriptor)?, Pattern::Binding(*var)));
+    }
+    let (_, reads, _) = mentions(body, record);
+    if reads != components.len() {
+        return None;
+    }
+    Some(Pattern::Record(pattern))
+}
+
+/// How many statements javac's calls of a record's accessors, matched by
+/// [`deconstruct`], take up.
+pub fn accessor_calls(pattern: &Pattern) -> usize {
+    match pattern {
+        Pattern::Binding(_) => 0,
+        Pattern::Record(components) => components.len(),
+    }
+}
+
+/// How many times a variable is stored and read, and the blocks doing
+/// either.
+pub fn mentions(body: &Body, var: Var) -> (usize, usize, BTreeSet<BlockId>) {
+    let (mut stores, mut reads) = (0, 0);
+    let mut blocks = BTreeSet::new();
+    for (id, block) in body.blocks.iter().enumerate() {
+        let before = (stores, reads);
+        for stmt in &block.stmts {
+            if stmt.defined_var() == Some(var) {
+                stores += 1;
+            }
+            for expr in stmt.uses() {
+                count(expr, var, &mut stores, &mut reads);
+            }
+        }
+        for expr in block.terminator.exprs() {
+            count(expr, var, &mut stores, &mut reads);
+        }
+        if (stores, reads) != before {
+            blocks.insert(id);
+        }
+    }
+    (stores, reads, blocks)
+}
+
+fn count(expr: &Expr, var: Var, stores: &mut usize, reads: &mut usize) {
+    match expr {
+        Expr::Var(read) if *read == var => *reads += 1,
+        Expr::Assign { target, value } if **target == Expr::Var(var) => {
+            *stores += 1;
+            count(value, var, stores, reads);
+        }
+        expr => {
+            for child in expr.children() {
+                count(child, var, stores, reads);
+            }
+        }
+    }
+}
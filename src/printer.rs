@@ -0,0 +1,1151 @@
+use crate::java::{
+    BinaryOp, CaseLabel, Catch, CompilationUnit, EnumConstant, Expr, Field, LambdaBody, Literal,
+    Member, Method, Modifier, Parameter, Pattern, Resource, Statement, SwitchCase, Type,
+    TypeDeclaration, TypeKind, UnaryOp,
+};
+use std::fmt::Write;
+
+/// How [`print()`] lays out source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Style {
+    pub indent: Indent,
+    pub braces: Braces,
+    /// The width lines are kept to where they can be broken: between
+    /// arguments, parameters and array elements, after binary operators and
+    /// before the branches of `?:`. A line broken is continued two levels of
+    /// indentation further in.
+    pub line_width: usize,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            indent: Indent::Spaces(4),
+            braces: Braces::EndOfLine,
+            line_width: 100,
+        }
+    }
+}
+
+/// What each level of indentation is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    Spaces(usize),
+    /// A tab, taken to be four columns wide.
+    Tab,
+}
+
+/// Where the opening brace of a declaration or statement goes. Those of
+/// lambdas, anonymous classes, switch expressions and array initializers
+/// stay at the end of the line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Braces {
+    EndOfLine,
+    NextLine,
+}
+
+const TAB_WIDTH: usize = 4;
+// the levels of indentation a broken line is continued at
+const CONTINUATION: usize = 2;
+
+/// Prints a compilation unit as Java source in `style`.
+pub fn print(unit: &CompilationUnit, style: &Style) -> String {
+    render(&Printer { style }.unit(unit), style)
+}
+
+/// Prints an expression as Java source in `style`.
+pub fn print_expr(expr: &Expr, style: &Style) -> String {
+    render(&Printer { style }.expr(expr), style)
+}
+
+// Source to be laid out, in the manner of Wadler's "A prettier printer":
+// the lines of a group are broken only when it doesn't fit on what is left
+// of the line, and then all of them are.
+enum Doc {
+    Text(String),
+    // a space, or a line break
+    Line,
+    HardLine,
+    // indented more where its lines are broken
+    Nest(usize, Box<Doc>),
+    Group(Box<Doc>),
+    Concat(Vec<Doc>),
+}
+
+fn text(text: impl Into<String>) -> Doc {
+    Doc::Text(text.into())
+}
+
+fn concat(docs: impl IntoIterator<Item = Doc>) -> Doc {
+    Doc::Concat(docs.into_iter().collect())
+}
+
+fn nest(levels: usize, doc: Doc) -> Doc {
+    Doc::Nest(levels, Box::new(doc))
+}
+
+fn group(doc: Doc) -> Doc {
+    Doc::Group(Box::new(doc))
+}
+
+// A space, or a break continuing the line.
+fn line() -> Doc {
+    nest(CONTINUATION, Doc::Line)
+}
+
+// Docs each on a line of its own.
+fn lines(docs: impl IntoIterator<Item = Doc>) -> Doc {
+    concat(docs.into_iter().flat_map(|doc| [Doc::HardLine, doc]))
+}
+
+// Docs separated by `separator` and a line break if they don't fit.
+fn separated(docs: impl IntoIterator<Item = Doc>, separator: &str) -> Doc {
+    let mut separated = Vec::new();
+    for (index, doc) in docs.into_iter().enumerate() {
+        if index > 0 {
+            separated.push(text(separator));
+            separated.push(line());
+        }
+        separated.push(doc);
+    }
+    concat(separated)
+}
+
+fn render(doc: &Doc, style: &Style) -> String {
+    let (unit, unit_width) = match style.indent {
+        Indent::Spaces(spaces) => (" ".repeat(spaces), spaces),
+        Indent::Tab => ("\t".to_string(), TAB_WIDTH),
+    };
+    let mut out = String::new();
+    let mut column = 0;
+    // the indentation of the line started, which is written with its first
+    // text so that blank lines are empty
+    let mut pending = None;
+    let mut stack = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                if text.is_empty() {
+                    continue;
+                }
+                if let Some(indent) = pending.take() {
+                    out.push_str(&unit.repeat(indent));
+                    column = indent * unit_width;
+                }
+                out.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line if flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::Line | Doc::HardLine => {
+                out.push('\n');
+                pending = Some(indent);
+                column = indent * unit_width;
+            }
+            Doc::Nest(levels, doc) => stack.push((indent + levels, flat, doc)),
+            Doc::Group(doc) => {
+                let width = style.line_width.saturating_sub(column);
+                let flat = flat || fits(width, doc, &stack);
+                stack.push((indent, flat, doc));
+            }
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (indent, flat, doc))),
+        }
+    }
+    out
+}
+
+// Whether a group fits in `width` columns laid out flat, along with what
+// comes after it up to where the line can next be broken.
+fn fits(width: usize, doc: &Doc, rest: &[(usize, bool, &Doc)]) -> bool {
+    let mut width = width as isize;
+    let mut stack = vec![(true, doc)];
+    let mut rest = rest.iter().rev();
+    loop {
+        let (flat, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, flat, doc)) => (flat, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line if flat => width -= 1,
+            Doc::Line | Doc::HardLine => return true,
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((flat, doc)),
+            Doc::Concat(docs) => stack.extend(docs.iter().rev().map(|doc| (flat, doc))),
+        }
+        if width < 0 {
+            return false;
+        }
+    }
+}
+
+// The precedence of Java's operators, from loosest to tightest.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html
+const ASSIGNMENT: u8 = 1;
+const CONDITIONAL: u8 = 2;
+const OR: u8 = 3;
+const AND: u8 = 4;
+const BIT_OR: u8 = 5;
+const XOR: u8 = 6;
+const BIT_AND: u8 = 7;
+const EQUALITY: u8 = 8;
+const RELATIONAL: u8 = 9;
+const SHIFT: u8 = 10;
+const ADDITIVE: u8 = 11;
+const MULTIPLICATIVE: u8 = 12;
+const UNARY: u8 = 13;
+const POSTFIX: u8 = 14;
+const PRIMARY: u8 = 15;
+
+// The operands of a chain of operators that bind alike, each with the
+// operator before it and how tightly it has to bind. Operators group to the
+// left, so right operands bind more tightly, except those of an operator
+// that is associative whichever way it groups.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.7.3
+fn chain<'e>(expr: &'e Expr, precedence: u8, operands: &mut Vec<(Option<BinaryOp>, &'e Expr, u8)>) {
+    let Expr::Binary { op, left, right } = expr else {
+        operands.push((None, expr, precedence));
+        return;
+    };
+    if binary_precedence(*op) != precedence {
+        operands.push((None, expr, precedence));
+        return;
+    }
+    chain(left, precedence, operands);
+    let associative = matches!(
+        op,
+        BinaryOp::BitAnd | BinaryOp::Xor | BinaryOp::BitOr | BinaryOp::And | BinaryOp::Or
+    );
+    match right.as_ref() {
+        Expr::Binary { op: inner, .. } if associative && inner == op => {
+            let start = operands.len();
+            chain(right, precedence, operands);
+            operands[start].0 = Some(*op);
+        }
+        _ => operands.push((Some(*op), right, precedence + 1)),
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => MULTIPLICATIVE,
+        BinaryOp::Add | BinaryOp::Sub => ADDITIVE,
+        BinaryOp::Shl | BinaryOp::Shr | BinaryOp::Ushr => SHIFT,
+        BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => RELATIONAL,
+        BinaryOp::Eq | BinaryOp::Ne => EQUALITY,
+        BinaryOp::BitAnd => BIT_AND,
+        BinaryOp::Xor => XOR,
+        BinaryOp::BitOr => BIT_OR,
+        BinaryOp::And => AND,
+        BinaryOp::Or => OR,
+    }
+}
+
+// How tightly an expression binds. An array creation can't be indexed
+// without parentheses, so it binds less tightly than a primary expression.
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Literal(literal) if literal_text(literal).starts_with('-') => UNARY,
+        Expr::Unary {
+            op: UnaryOp::PostIncrement | UnaryOp::PostDecrement,
+            ..
+        } => POSTFIX,
+        Expr::Unary { .. } | Expr::Cast { .. } | Expr::Switch { .. } => UNARY,
+        Expr::Binary { op, .. } => binary_precedence(*op),
+        Expr::InstanceOf { .. } => RELATIONAL,
+        Expr::Conditional { .. } => CONDITIONAL,
+        Expr::Assign { .. } | Expr::Lambda { .. } => ASSIGNMENT,
+        Expr::NewArray { .. }
+        | Expr::ArrayInit {
+            array_type: Some(_),
+            ..
+        } => POSTFIX,
+        Expr::Commented { expr, .. } => precedence(expr),
+        _ => PRIMARY,
+    }
+}
+
+// Whether an expression starts with `+` or `-`, so that after a cast or
+// another sign it would be read as a binary operator or `--`.
+fn signed(expr: &Expr) -> bool {
+    match expr {
+        Expr::Literal(literal) => literal_text(literal).starts_with('-'),
+        Expr::Unary { op, .. } => matches!(
+            op,
+            UnaryOp::Plus | UnaryOp::Neg | UnaryOp::PreIncrement | UnaryOp::PreDecrement
+        ),
+        _ => false,
+    }
+}
+
+struct Printer<'s> {
+    style: &'s Style,
+}
+
+impl Printer<'_> {
+    fn unit(&self, unit: &CompilationUnit) -> Doc {
+        let mut docs = Vec::new();
+        if let Some(package) = &unit.package {
+            docs.extend([text(format!("package {package};")), Doc::HardLine]);
+            docs.push(Doc::HardLine);
+        }
+        for import in &unit.imports {
+            docs.extend([text(format!("import {import};")), Doc::HardLine]);
+        }
+        if !unit.imports.is_empty() {
+            docs.push(Doc::HardLine);
+        }
+        for (index, declaration) in unit.types.iter().enumerate() {
+            if index > 0 {
+                docs.push(Doc::HardLine);
+            }
+            docs.extend([self.type_declaration(declaration), Doc::HardLine]);
+        }
+        concat(docs)
+    }
+
+    // The opening brace of a declaration or statement after its head.
+    fn open(&self) -> Doc {
+        match self.style.braces {
+            Braces::EndOfLine => text(" {"),
+            Braces::NextLine => concat([Doc::HardLine, text("{")]),
+        }
+    }
+
+    // What follows the closing brace of a block in the same statement, such
+    // as `else`.
+    fn after_brace(&self, head: String) -> Doc {
+        match self.style.braces {
+            Braces::EndOfLine => text(format!(" {head}")),
+            Braces::NextLine => concat([Doc::HardLine, text(head)]),
+        }
+    }
+
+    fn braced(&self, head: Doc, open: Doc, inner: Doc) -> Doc {
+        concat([head, open, nest(1, inner), Doc::HardLine, text("}")])
+    }
+
+    fn block(&self, head: Doc, statements: &[Statement]) -> Doc {
+        self.braced(head, self.open(), self.statements(statements))
+    }
+
+    fn statements(&self, statements: &[Statement]) -> Doc {
+        lines(statements.iter().map(|statement| self.statement(statement)))
+    }
+
+    fn type_declaration(&self, declaration: &TypeDeclaration) -> Doc {
+        let mut head = modifiers(&declaration.modifiers);
+        head.push_str(match declaration.kind {
+            TypeKind::Class => "class ",
+            TypeKind::Interface => "interface ",
+            TypeKind::Enum => "enum ",
+            TypeKind::Annotation => "@interface ",
+            TypeKind::Record => "record ",
+        });
+        head.push_str(&declaration.name);
+        let mut head = vec![text(head)];
+        if declaration.kind == TypeKind::Record {
+            head.push(self.parameters(&declaration.components));
+        }
+        let mut clauses = String::new();
+        for (keyword, types) in [
+            ("extends", &declaration.extends),
+            ("implements", &declaration.implements),
+        ] {
+            if !types.is_empty() {
+                let _ = write!(clauses, " {keyword} {}", type_list(types, ", "));
+            }
+        }
+        head.push(text(clauses));
+        let body = self.class_body(
+            &declaration.constants,
+            &declaration.members,
+            declaration.kind == TypeKind::Enum,
+        );
+        self.braced(concat(head), self.open(), body)
+    }
+
+    // The constants and members of a class, each on a line of its own.
+    // Members are a blank line apart, except for fields declared together.
+    fn class_body(&self, constants: &[EnumConstant], members: &[Member], is_enum: bool) -> Doc {
+        let mut docs = Vec::new();
+        for (index, constant) in constants.iter().enumerate() {
+            let separator = if index + 1 < constants.len() {
+                ","
+            } else {
+                ";"
+            };
+            docs.push(concat([self.enum_constant(constant), text(separator)]));
+        }
+        // the constants of an enum end with a semicolon when it has members
+        if is_enum && constants.is_empty() && !members.is_empty() {
+            docs.push(text(";"));
+        }
+        if !docs.is_empty() && !members.is_empty() {
+            docs.push(text(""));
+        }
+        for (index, member) in members.iter().enumerate() {
+            let together = matches!(
+                (index.checked_sub(1).map(|index| &members[index]), member),
+                (Some(Member::Field(_)), Member::Field(_))
+            );
+            if index > 0 && !together {
+                docs.push(text(""));
+            }
+            docs.push(self.member(member));
+        }
+        lines(docs)
+    }
+
+    // The body of an anonymous class or enum constant, after its head.
+    fn anonymous_body(&self, members: &[Member]) -> Doc {
+        match members {
+            [] => text(" {}"),
+            members => self.braced(text(""), text(" {"), self.class_body(&[], members, false)),
+        }
+    }
+
+    fn enum_constant(&self, constant: &EnumConstant) -> Doc {
+        let mut docs = vec![text(&constant.name)];
+        if !constant.args.is_empty() {
+            docs.push(self.args(&constant.args));
+        }
+        if let Some(body) = &constant.body {
+            docs.push(self.anonymous_body(body));
+        }
+        concat(docs)
+    }
+
+    fn member(&self, member: &Member) -> Doc {
+        match member {
+            Member::Field(field) => self.field(field),
+            Member::Method(method) => self.method(method),
+            Member::Initializer {
+                is_static: true,
+                body,
+            } => self.block(text("static"), body),
+            Member::Initializer {
+                is_static: false,
+                body,
+            } => self.braced(text(""), text("{"), self.statements(body)),
+            Member::Type(declaration) => self.type_declaration(declaration),
+            Member::Comment(comment) => line_comment(comment),
+        }
+    }
+
+    fn field(&self, field: &Field) -> Doc {
+        let head = format!(
+            "{}{} {}",
+            modifiers(&field.modifiers),
+            type_name(&field.field_type),
+            field.name
+        );
+        match &field.value {
+            Some(value) => concat([text(head), text(" = "), self.expr(value), text(";")]),
+            None => text(format!("{head};")),
+        }
+    }
+
+    fn method(&self, method: &Method) -> Doc {
+        let mut head = modifiers(&method.modifiers);
+        if let Some(return_type) = &method.return_type {
+            let _ = write!(head, "{} ", type_name(return_type));
+        }
+        head.push_str(&method.name);
+        let mut header = vec![text(head), self.parameters(&method.parameters)];
+        if !method.throws.is_empty() {
+            header.push(text(format!(" throws {}", type_list(&method.throws, ", "))));
+        }
+        match &method.body {
+            Some(body) => self.block(concat(header), body),
+            None => concat(header.into_iter().chain([text(";")])),
+        }
+    }
+
+    // The parameters of a method, or the components of a record.
+    fn parameters(&self, parameters: &[Parameter]) -> Doc {
+        self.parenthesized(parameters.iter().map(|parameter| {
+            let parameter_type = type_name(&parameter.parameter_type);
+            let dots = if parameter.varargs { "..." } else { "" };
+            text(format!("{parameter_type}{dots} {}", parameter.name))
+        }))
+    }
+
+    fn statement(&self, statement: &Statement) -> Doc {
+        match statement {
+            Statement::Expr(expr) => concat([self.expr(expr), text(";")]),
+            Statement::Declare {
+                var_type,
+                name,
+                value,
+            } => concat([
+                self.declaration(Some(var_type), name, value.as_ref()),
+                text(";"),
+            ]),
+            Statement::Class(declaration) => self.type_declaration(declaration),
+            Statement::Block { label, body } => match label {
+                Some(label) => self.block(text(format!("{label}:")), body),
+                None => self.braced(text(""), text("{"), self.statements(body)),
+            },
+            Statement::If { .. } => self.if_statement(statement),
+            Statement::While {
+                label,
+                condition,
+                body,
+            } => {
+                let head = concat([
+                    text(format!("{}while (", label_prefix(label))),
+                    self.expr(condition),
+                    text(")"),
+                ]);
+                self.block(head, body)
+            }
+            Statement::DoWhile {
+                label,
+                body,
+                condition,
+            } => concat([
+                self.block(text(format!("{}do", label_prefix(label))), body),
+                self.after_brace("while (".to_string()),
+                self.expr(condition),
+                text(");"),
+            ]),
+            Statement::For {
+                label,
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                // the variables after the first are declared with its type
+                let mut declared = false;
+                let init = init.iter().map(|statement| match statement {
+                    Statement::Declare {
+                        var_type,
+                        name,
+                        value,
+                    } => {
+                        let var_type = Some(var_type).filter(|_| !declared);
+                        declared = true;
+                        self.declaration(var_type, name, value.as_ref())
+                    }
+                    Statement::Expr(expr) => self.expr(expr),
+                    statement => self.statement(statement),
+                });
+                let init = separated(init.collect::<Vec<_>>(), ",");
+                let update = separated(update.iter().map(|expr| self.expr(expr)), ",");
+                let head = concat([
+                    text(format!("{}for (", label_prefix(label))),
+                    group(concat([
+                        init,
+                        text(";"),
+                        line(),
+                        self.expr(condition),
+                        text(";"),
+                        line(),
+                        update,
+                    ])),
+                    text(")"),
+                ]);
+                self.block(head, body)
+            }
+            Statement::Switch {
+                label,
+                value,
+                cases,
+            } => {
+                let head = concat([
+                    text(format!("{}switch (", label_prefix(label))),
+                    self.expr(value),
+                    text(")"),
+                ]);
+                let cases = concat(cases.iter().map(|case| self.case(case)));
+                self.braced(head, self.open(), cases)
+            }
+            Statement::Try {
+                resources,
+                body,
+                catches,
+                finally,
+            } => self.try_statement(resources, body, catches, finally.as_deref()),
+            Statement::Synchronized { lock, body } => {
+                let head = concat([text("synchronized ("), self.expr(lock), text(")")]);
+                self.block(head, body)
+            }
+            Statement::Break(label) => text(jump("break", label)),
+            Statement::Continue(label) => text(jump("continue", label)),
+            Statement::Return(None) => text("return;"),
+            Statement::Return(Some(value)) => {
+                concat([text("return "), self.expr(value), text(";")])
+            }
+            Statement::Throw(value) => concat([text("throw "), self.expr(value), text(";")]),
+            Statement::Yield(value) => concat([text("yield "), self.expr(value), text(";")]),
+            Statement::Assert { condition, message } => {
+                let mut docs = vec![text("assert "), self.expr(condition)];
+                if let Some(message) = message {
+                    docs.extend([text(" :"), line(), self.expr(message)]);
+                }
+                docs.push(text(";"));
+                group(concat(docs))
+            }
+            Statement::Comment(comment) => line_comment(comment),
+        }
+    }
+
+    // `T name = value`, or `name = value` for a variable declared along with
+    // one before it.
+    fn declaration(&self, var_type: Option<&Type>, name: &str, value: Option<&Expr>) -> Doc {
+        let head = match var_type {
+            Some(var_type) => format!("{} {name}", type_name(var_type)),
+            None => name.to_string(),
+        };
+        match value {
+            Some(value) => concat([text(head), text(" = "), self.expr(value)]),
+            None => text(head),
+        }
+    }
+
+    // An `if` statement, with the `if`s that are all its `else` has as
+    // `else if`.
+    fn if_statement(&self, statement: &Statement) -> Doc {
+        let mut docs = Vec::new();
+        let mut current = statement;
+        while let Statement::If {
+            condition,
+            then,
+            otherwise,
+        } = current
+        {
+            let head = concat([text("if ("), self.expr(condition), text(")")]);
+            docs.push(self.block(head, then));
+            match otherwise.as_slice() {
+                [] => break,
+                [next @ Statement::If { .. }] => {
+                    current = next;
+                    docs.push(self.after_brace("else ".to_string()));
+                }
+                otherwise => {
+                    docs.push(self.block(self.after_brace("else".to_string()), otherwise));
+                    break;
+                }
+            }
+        }
+        concat(docs)
+    }
+
+    fn try_statement(
+        &self,
+        resources: &[Resource],
+        body: &[Statement],
+        catches: &[Catch],
+        finally: Option<&[Statement]>,
+    ) -> Doc {
+        let head = match resources {
+            [] => text("try"),
+            resources => {
+                let resources = resources.iter().map(|resource| match resource {
+                    Resource::Declare {
+                        var_type,
+                        name,
+                        value,
+                    } => self.declaration(Some(var_type), name, Some(value)),
+                    Resource::Expr(expr) => self.expr(expr),
+                });
+                concat([text("try ("), group(separated(resources, ";")), text(")")])
+            }
+        };
+        let mut docs = vec![self.block(head, body)];
+        for catch in catches {
+            let head = format!("catch ({} {})", type_list(&catch.types, " | "), catch.name);
+            docs.push(self.block(self.after_brace(head), &catch.body));
+        }
+        if let Some(finally) = finally {
+            docs.push(self.block(self.after_brace("finally".to_string()), finally));
+        }
+        concat(docs)
+    }
+
+    // A case of a switch statement: each label on a line of its own, with
+    // `case null, default` together, and the statements they lead to.
+    fn case(&self, case: &SwitchCase) -> Doc {
+        let guard = self.guard(case);
+        let is_default = case.labels.contains(&CaseLabel::Default);
+        let mut heads = Vec::new();
+        for label in &case.labels {
+            match label {
+                CaseLabel::Default => {}
+                CaseLabel::Null if is_default => {}
+                label => heads.push(concat([
+                    text("case "),
+                    self.case_label(label),
+                    guard.clone().map_or(text(""), text),
+                    text(":"),
+                ])),
+            }
+        }
+        if is_default {
+            heads.push(text(format!("{}:", default_head(case))));
+        }
+        concat([lines(heads), nest(1, self.statements(&case.body))])
+    }
+
+    // A case of a switch expression, with its labels together before the
+    // arrow and what it leads to.
+    fn arrow_case(&self, case: &SwitchCase) -> Doc {
+        let mut head = match case.labels.contains(&CaseLabel::Default) {
+            true => text(default_head(case)),
+            false => {
+                let labels = case.labels.iter().map(|label| self.case_label(label));
+                concat([text("case "), group(separated(labels, ","))])
+            }
+        };
+        if let Some(guard) = self.guard(case) {
+            head = concat([head, text(guard)]);
+        }
+        match case.body.as_slice() {
+            [Statement::Yield(value)] => concat([head, text(" -> "), self.expr(value), text(";")]),
+            [Statement::Throw(value)] => {
+                concat([head, text(" -> throw "), self.expr(value), text(";")])
+            }
+            body => self.braced(head, text(" -> {"), self.statements(body)),
+        }
+    }
+
+    fn guard(&self, case: &SwitchCase) -> Option<String> {
+        let guard = case.guard.as_ref()?;
+        Some(format!(" when {}", print_expr(guard, self.style)))
+    }
+
+    fn case_label(&self, label: &CaseLabel) -> Doc {
+        match label {
+            CaseLabel::Expr(expr) => self.expr(expr),
+            CaseLabel::Null => text("null"),
+            CaseLabel::Pattern(pattern) => text(pattern_text(pattern)),
+            CaseLabel::Default => text("default"),
+        }
+    }
+
+    // Arguments in parentheses, each on a line of its own if they don't fit
+    // on one.
+    fn args(&self, args: &[Expr]) -> Doc {
+        self.parenthesized(args.iter().map(|arg| self.operand(arg, ASSIGNMENT)))
+    }
+
+    fn parenthesized(&self, docs: impl IntoIterator<Item = Doc>) -> Doc {
+        let docs: Vec<Doc> = docs.into_iter().collect();
+        match docs.is_empty() {
+            true => text("()"),
+            false => group(concat([text("("), separated(docs, ","), text(")")])),
+        }
+    }
+
+    // An operand, in parentheses unless it binds at least as tightly as
+    // `min`.
+    fn operand(&self, expr: &Expr, min: u8) -> Doc {
+        match precedence(expr) >= min {
+            true => self.expr(expr),
+            false => parenthesized(self.expr(expr)),
+        }
+    }
+
+    // The operand of a cast or unary operator, also in parentheses when it
+    // has a sign of its own.
+    fn unary_operand(&self, expr: &Expr) -> Doc {
+        match signed(expr) {
+            true => parenthesized(self.expr(expr)),
+            false => self.operand(expr, UNARY),
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> Doc {
+        match expr {
+            Expr::Literal(literal) => text(literal_text(literal)),
+            Expr::Name(name) => text(name),
+            Expr::This(None) => text("this"),
+            Expr::This(Some(class)) => text(format!("{class}.this")),
+            Expr::Super => text("super"),
+            Expr::Type(expr_type) => text(type_name(expr_type)),
+            Expr::Class(class) => text(format!("{}.class", type_name(class))),
+            Expr::Field { object, name } => {
+                concat([self.operand(object, PRIMARY), text(format!(".{name}"))])
+            }
+            Expr::ArrayAccess { array, index } => concat([
+                self.operand(array, PRIMARY),
+                text("["),
+                self.expr(index),
+                text("]"),
+            ]),
+            Expr::Call { object, name, args } => {
+                let mut docs = Vec::new();
+                if let Some(object) = object {
+                    docs.extend([self.operand(object, PRIMARY), text(".")]);
+                }
+                docs.extend([text(name), self.args(args)]);
+                concat(docs)
+            }
+            Expr::ConstructorCall {
+                qualifier,
+                is_super,
+                args,
+            } => {
+                let mut docs = Vec::new();
+                if let Some(qualifier) = qualifier {
+                    docs.extend([self.operand(qualifier, PRIMARY), text(".")]);
+                }
+                docs.push(text(if *is_super { "super" } else { "this" }));
+                docs.push(self.args(args));
+                concat(docs)
+            }
+            Expr::New {
+                qualifier,
+                class,
+                args,
+                body,
+            } => {
+                let mut docs = Vec::new();
+                if let Some(qualifier) = qualifier {
+                    docs.extend([self.operand(qualifier, PRIMARY), text(".")]);
+                }
+                docs.extend([text(format!("new {}", type_name(class))), self.args(args)]);
+                if let Some(body) = body {
+                    docs.push(self.anonymous_body(body));
+                }
+                concat(docs)
+            }
+            Expr::NewArray {
+                element,
+                dimensions,
+                extra_dimensions,
+            } => {
+                let mut docs = vec![text(format!("new {}", type_name(element)))];
+                for dimension in dimensions {
+                    docs.extend([text("["), self.expr(dimension), text("]")]);
+                }
+                docs.push(text("[]".repeat(*extra_dimensions)));
+                concat(docs)
+            }
+            Expr::ArrayInit {
+                array_type,
+                elements,
+            } => {
+                // filled, with as many elements on each line as fit
+                let mut docs = Vec::new();
+                if let Some(array_type) = array_type {
+                    docs.push(text(format!("new {}", type_name(array_type))));
+                }
+                docs.push(text("{"));
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        docs.extend([text(","), group(line())]);
+                    }
+                    docs.push(self.operand(element, ASSIGNMENT));
+                }
+                docs.push(text("}"));
+                concat(docs)
+            }
+            Expr::Unary { op, operand } => {
+                let (prefix, postfix) = match op {
+                    UnaryOp::Plus => ("+", ""),
+                    UnaryOp::Neg => ("-", ""),
+                    UnaryOp::Not => ("!", ""),
+                    UnaryOp::Complement => ("~", ""),
+                    UnaryOp::PreIncrement => ("++", ""),
+                    UnaryOp::PreDecrement => ("--", ""),
+                    UnaryOp::PostIncrement => ("", "++"),
+                    UnaryOp::PostDecrement => ("", "--"),
+                };
+                let operand = match postfix {
+                    "" => self.unary_operand(operand),
+                    _ => self.operand(operand, POSTFIX),
+                };
+                concat([text(prefix), operand, text(postfix)])
+            }
+            Expr::Binary { op, .. } => {
+                // a chain of operators that bind alike, such as `a + b - c`,
+                // is filled like an array initializer, broken only before
+                // the operands that don't fit on the line
+                let precedence = binary_precedence(*op);
+                let mut operands = Vec::new();
+                chain(expr, precedence, &mut operands);
+                let mut docs = Vec::new();
+                for (op, operand, precedence) in operands {
+                    let operand = self.operand(operand, precedence);
+                    match op {
+                        // an operand is kept whole on the line it's broken to
+                        Some(op) => docs.extend([
+                            text(format!(" {}", op.symbol())),
+                            group(concat([line(), operand])),
+                        ]),
+                        None => docs.push(operand),
+                    }
+                }
+                concat(docs)
+            }
+            Expr::InstanceOf { operand, pattern } => concat([
+                self.operand(operand, RELATIONAL),
+                text(format!(" instanceof {}", pattern_text(pattern))),
+            ]),
+            Expr::Cast { types, operand } => {
+                let cast = text(format!("({}) ", type_list(types, " & ")));
+                // a lambda can be cast without parentheses
+                let operand = match &**operand {
+                    operand @ (Expr::Lambda { .. } | Expr::MethodRef { .. }) => self.expr(operand),
+                    operand => self.unary_operand(operand),
+                };
+                concat([cast, operand])
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => group(concat([
+                self.operand(condition, OR),
+                line(),
+                text("? "),
+                self.operand(then, OR),
+                line(),
+                text(": "),
+                self.operand(otherwise, CONDITIONAL),
+            ])),
+            Expr::Assign { op, target, value } => {
+                let symbol = op.map_or("", BinaryOp::symbol);
+                concat([
+                    self.operand(target, POSTFIX),
+                    text(format!(" {symbol}= ")),
+                    self.operand(value, ASSIGNMENT),
+                ])
+            }
+            Expr::Lambda { parameters, body } => {
+                let head = match parameters.as_slice() {
+                    [parameter] => parameter.clone(),
+                    parameters => format!("({})", parameters.join(", ")),
+                };
+                match body {
+                    LambdaBody::Expr(expr) => {
+                        concat([text(format!("{head} -> ")), self.operand(expr, ASSIGNMENT)])
+                    }
+                    LambdaBody::Block(body) if body.is_empty() => text(format!("{head} -> {{}}")),
+                    LambdaBody::Block(body) => {
+                        self.braced(text(head), text(" -> {"), self.statements(body))
+                    }
+                }
+            }
+            Expr::MethodRef { object, name } => {
+                concat([self.operand(object, PRIMARY), text(format!("::{name}"))])
+            }
+            Expr::Switch { value, cases } => {
+                let head = concat([text("switch ("), self.expr(value), text(")")]);
+                let cases = lines(cases.iter().map(|case| self.arrow_case(case)));
+                self.braced(head, text(" {"), cases)
+            }
+            Expr::Commented { comment, expr } => {
+                concat([text(format!("/* {comment} */ ")), self.expr(expr)])
+            }
+        }
+    }
+}
+
+fn parenthesized(doc: Doc) -> Doc {
+    concat([text("("), doc, text(")")])
+}
+
+fn line_comment(comment: &str) -> Doc {
+    let comments = comment.lines().map(|line| text(format!("// {line}")));
+    let mut docs = Vec::new();
+    for (index, comment) in comments.enumerate() {
+        if index > 0 {
+            docs.push(Doc::HardLine);
+        }
+        docs.push(comment);
+    }
+    concat(docs)
+}
+
+fn label_prefix(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{label}: "),
+        None => String::new(),
+    }
+}
+
+fn jump(keyword: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{keyword} {label};"),
+        None => format!("{keyword};"),
+    }
+}
+
+// `default`, which a `case null` goes along with when it leads to the same
+// code.
+fn default_head(case: &SwitchCase) -> &'static str {
+    match case.labels.contains(&CaseLabel::Null) {
+        true => "case null, default",
+        false => "default",
+    }
+}
+
+fn modifiers(modifiers: &[Modifier]) -> String {
+    modifiers
+        .iter()
+        .map(|modifier| format!("{} ", modifier.keyword()))
+        .collect()
+}
+
+pub fn type_name(name: &Type) -> String {
+    match name {
+        Type::Boolean => "boolean".to_string(),
+        Type::Byte => "byte".to_string(),
+        Type::Char => "char".to_string(),
+        Type::Short => "short".to_string(),
+        Type::Int => "int".to_string(),
+        Type::Long => "long".to_string(),
+        Type::Float => "float".to_string(),
+        Type::Double => "double".to_string(),
+        Type::Void => "void".to_string(),
+        Type::Class(name) => name.clone(),
+        Type::Array(element) => format!("{}[]", type_name(element)),
+    }
+}
+
+fn type_list(types: &[Type], separator: &str) -> String {
+    let names: Vec<String> = types.iter().map(type_name).collect();
+    names.join(separator)
+}
+
+fn pattern_text(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Type {
+            pattern_type,
+            name: Some(name),
+        } => format!("{} {name}", type_name(pattern_type)),
+        Pattern::Type {
+            pattern_type,
+            name: None,
+        } => type_name(pattern_type),
+        Pattern::Record {
+            record_type,
+            components,
+        } => {
+            let components: Vec<String> = components.iter().map(pattern_text).collect();
+            format!("{}({})", type_name(record_type), components.join(", "))
+        }
+    }
+}
+
+fn literal_text(literal: &Literal) -> String {
+    match literal {
+        Literal::Null => "null".to_string(),
+        Literal::Boolean(value) => value.to_string(),
+        Literal::Int(value) => value.to_string(),
+        Literal::Long(value) => format!("{value}L"),
+        Literal::Float(value) => float_literal(*value),
+        Literal::Double(value) => double_literal(*value),
+        Literal::Char(value) => format!("'{}'", escape(&[*value], '\'')),
+        Literal::String(value) => format!("\"{}\"", escape(value, '"')),
+    }
+}
+
+/// The field of `Float` a value is printed as, if it is one of its
+/// constants or their negation.
+pub fn float_field(value: f32) -> Option<&'static str> {
+    named_value(value.into(), f32::from_bits(1).into(), f32::MAX.into())
+}
+
+/// The field of `Double` a value is printed as, like [`float_field`].
+pub fn double_field(value: f64) -> Option<&'static str> {
+    named_value(value, f64::from_bits(1), f64::MAX)
+}
+
+fn named_value(value: f64, min_value: f64, max_value: f64) -> Option<&'static str> {
+    match value.abs() {
+        _ if value.is_nan() => Some("NaN"),
+        _ if value == f64::INFINITY => Some("POSITIVE_INFINITY"),
+        _ if value == f64::NEG_INFINITY => Some("NEGATIVE_INFINITY"),
+        magnitude if magnitude == min_value => Some("MIN_VALUE"),
+        magnitude if magnitude == max_value => Some("MAX_VALUE"),
+        _ => None,
+    }
+}
+
+// A float exactly: as one of `Float`'s constants, as the shortest decimal
+// that reads back as it, or in hexadecimal when it is subnormal, whose
+// decimals don't show how few bits it has.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-3.html#jls-3.10.2
+fn float_literal(value: f32) -> String {
+    if let Some(name) = float_field(value) {
+        let sign = if value.is_sign_negative() && value.is_finite() {
+            "-"
+        } else {
+            ""
+        };
+        return format!("{sign}Float.{name}");
+    }
+    if value.is_subnormal() {
+        // the 23 bits of the fraction as 6 hex digits
+        let fraction = (value.to_bits() & 0x7f_ffff) << 1;
+        return format!(
+            "{}p-126f",
+            hex_fraction(value.is_sign_negative(), fraction.into(), 6)
+        );
+    }
+    format!("{value:?}f")
+}
+
+fn double_literal(value: f64) -> String {
+    if let Some(name) = double_field(value) {
+        let sign = if value.is_sign_negative() && value.is_finite() {
+            "-"
+        } else {
+            ""
+        };
+        return format!("{sign}Double.{name}");
+    }
+    if value.is_subnormal() {
+        let fraction = value.to_bits() & 0xf_ffff_ffff_ffff;
+        return format!(
+            "{}p-1022",
+            hex_fraction(value.is_sign_negative(), fraction, 13)
+        );
+    }
+    format!("{value:?}")
+}
+
+// `0x0.` and the hex digits of a fraction, without trailing zeros.
+fn hex_fraction(negative: bool, fraction: u64, digits: usize) -> String {
+    let hex = format!("{fraction:0digits$x}");
+    let sign = if negative { "-" } else { "" };
+    format!("{sign}0x0.{}", hex.trim_end_matches('0'))
+}
+
+// Escapes UTF-16 code units for a string or char literal quoted with
+// `quote`. Unpaired surrogates, which Modified UTF-8 can hold, and control
+// characters become unicode escapes.
+fn escape(units: &[u16], quote: char) -> String {
+    let mut out = String::new();
+    for decoded in char::decode_utf16(units.iter().copied()) {
+        match decoded {
+            Ok('\n') => out.push_str("\\n"),
+            Ok('\r') => out.push_str("\\r"),
+            Ok('\t') => out.push_str("\\t"),
+            Ok('\u{8}') => out.push_str("\\b"),
+            Ok('\u{c}') => out.push_str("\\f"),
+            Ok('\\') => out.push_str("\\\\"),
+            Ok(c) if c == quote => {
+                out.push('\\');
+                out.push(c);
+            }
+            Ok(c) if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            Ok(c) => out.push(c),
+            Err(err) => {
+                let _ = write!(out, "\\u{:04x}", err.unpaired_surrogate());
+            }
+        }
+    }
+    out
+}
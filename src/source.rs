@@ -0,0 +1,3206 @@
+use crate::accessors;
+use crate::asserts;
+use crate::concat;
+use crate::conditions;
+use crate::decompile::{ClassPath, DecompileResult};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::error::DecompileError;
+use crate::exceptions;
+use crate::frames::ClassHierarchy;
+use crate::infer;
+use crate::initializers;
+use crate::ir::{
+    BinaryOp, Body, CmpKind, CompareOp, Const, Expr, InvokeKind, JavaString, LogicalOp, Pattern,
+    Stmt, UnaryOp, Var,
+};
+use crate::java::{
+    self, Catch, CompilationUnit, EnumConstant, LambdaBody, Literal, Member, Modifier, Parameter,
+    Resource, SwitchCase, Type, TypeDeclaration, TypeKind,
+};
+use crate::lambdas;
+use crate::lift::lift_method;
+use crate::mutf8;
+use crate::patterns;
+use crate::printer::{self, Style};
+use crate::ssa;
+use crate::structure::{self, Case, CaseLabel, Label, Statement};
+use crate::switches;
+use crate::types::{
+    Attribute, ClassFile, ConstantPoolType, FieldInfo, InnerClassInfo, MethodInfo, ACC_ABSTRACT,
+    ACC_ANNOTATION, ACC_BRIDGE, ACC_ENUM, ACC_FINAL, ACC_INTERFACE, ACC_NATIVE, ACC_PRIVATE,
+    ACC_PROTECTED, ACC_PUBLIC, ACC_STATIC, ACC_STRICT, ACC_SYNCHRONIZED, ACC_SYNTHETIC,
+    ACC_TRANSIENT, ACC_VARARGS, ACC_VOLATILE,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+
+const ENUM: &str = "java/lang/Enum";
+const RECORD: &str = "java/lang/Record";
+const OBJECT_METHODS: &str = "java/lang/runtime/ObjectMethods";
+
+/// Decompiles a class into a Java compilation unit.
+///
+/// Method bodies go through [`lift_method`], SSA form and its optimizations,
+/// [`infer::infer_types`], [`exceptions::find_try_regions`] and
+/// [`structure::structure`], which looks up what enum switches switch on in
+/// `classes`. A method that can't be decompiled keeps its declaration, with
+/// a body that throws and says why.
+///
+/// Synthetic and bridge members are left out, as the source doesn't have
+/// them: the methods [`lambdas::rebuild_lambdas`] makes into lambdas, the
+/// accessors [`accessors::inline_accessors`] inlines, and javac's fields
+/// for enclosing instances and captured variables. A bridge taking other
+/// parameters than the methods of its name is kept though, since with the
+/// erased types the source is written with nothing else would implement the
+/// supertype's method.
+///
+/// Member classes found in `classes` are declared inside the class, with
+/// the modifiers `InnerClasses` gives them. The enclosing instance of an
+/// inner class is `Outer.this` rather than its synthetic `this$0` field,
+/// and isn't a parameter of its constructors.
+///
+/// An enum is declared with its constants, which its `<clinit>` creates,
+/// and without `values()`, `valueOf(String)` and the name and ordinal its
+/// constructors are passed.
+///
+/// A record is declared with its components, and without the fields, the
+/// accessors and the `toString()`, `hashCode()` and `equals(Object)` that
+/// it has for them implicitly.
+///
+/// Fields are declared with the initializers javac compiled into `<clinit>`
+/// and the constructors, which [`initializers`] takes back out of them, and
+/// static ones with their `ConstantValue`. The `super()` javac adds to a
+/// constructor is left out, as is the constructor it adds to a class that
+/// declares none, and a `<clinit>` with nothing left in it.
+///
+/// [`asserts::rebuild_asserts`] turns checks of `$assertionsDisabled` back
+/// into `assert` statements. The calls boxing and unboxing values are left
+/// out where Java does the conversion itself: in assignments, returns and
+/// array initializers, and the operands of numeric operators. Assignments
+/// of an operation on what they assign to are printed as `x += y`, `x++`
+/// and `x--`.
+pub fn decompile_class(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+) -> DecompileResult<CompilationUnit> {
+    let this_class = class_file
+        .this_class_name()
+        .ok_or(DecompileError::NoSuchConstantPoolEntry(
+            class_file.this_class,
+            0,
+        ))?
+        .to_string();
+    let mut names = Names::new(class_file, &this_class);
+    let declaration = write_declaration(
+        class_file,
+        hierarchy,
+        classes,
+        &mut names,
+        class_file.access_flags,
+    )?;
+    Ok(CompilationUnit {
+        package: Some(names.package.replace('/', ".")).filter(|package| !package.is_empty()),
+        imports: names.imports.into_values().collect(),
+        types: vec![declaration],
+    })
+}
+
+/// Decompiles a class with [`decompile_class`] and prints it in `style`.
+pub fn write_class(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    style: &Style,
+) -> DecompileResult<String> {
+    let unit = decompile_class(class_file, hierarchy, classes)?;
+    Ok(printer::print(&unit, style))
+}
+
+// The declaration of the class `names` is writing, with its members.
+fn write_declaration(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+    flags: u16,
+) -> DecompileResult<TypeDeclaration> {
+    let (constants, members) = write_members(class_file, hierarchy, classes, names, flags)?;
+    let mut declaration = write_header(class_file, names, flags);
+    declaration.constants = constants;
+    declaration.members = members;
+    Ok(declaration)
+}
+
+// The constants of the enum `names` is writing, with the arguments they are
+// created with and their bodies.
+fn write_enum_constants(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+) -> DecompileResult<Vec<EnumConstant>> {
+    let constant_pool = class_file.constant_pool();
+    let initializer = class_file
+        .methods
+        .iter()
+        .find(|method| method.name(constant_pool) == Some("<clinit>"));
+    let Some(initializer) = initializer else {
+        return Ok(Vec::new());
+    };
+    let decompiled = decompile_body(
+        class_file,
+        hierarchy,
+        classes,
+        initializer,
+        &mut HashSet::new(),
+    )?;
+    let Some((body, statements)) = decompiled else {
+        return Ok(Vec::new());
+    };
+    let this_class = names.this_class.clone();
+    let (_, constants) = enum_constants(&this_class, &statements);
+    let mut translator = Translator::new(names, hierarchy, classes, &body, &statements);
+    let mut declarations = Vec::new();
+    for (name, constant) in constants {
+        let Expr::New {
+            class,
+            descriptor,
+            args,
+        } = constant
+        else {
+            continue;
+        };
+        let args = translator
+            .arg_list(descriptor, args)
+            .into_iter()
+            .skip(2)
+            .collect();
+        let mut body = None;
+        if *class != this_class {
+            if let Some(class_file) = translator.classes.class_file(class) {
+                let declared = translator.names.declared.clone();
+                let members =
+                    translator.nested_class(class, &declared, |names, hierarchy, classes| {
+                        names.add_member_classes(&class_file);
+                        let (_, members) = write_members(
+                            &class_file,
+                            hierarchy,
+                            classes,
+                            names,
+                            class_file.access_flags,
+                        )?;
+                        Ok(members)
+                    });
+                body = Some(members.unwrap_or_else(|err| vec![Member::Comment(err)]));
+            }
+        }
+        declarations.push(EnumConstant {
+            name: name.to_string(),
+            args,
+            body,
+        });
+    }
+    Ok(declarations)
+}
+
+// The constants and members of the class `names` is writing, with `flags`
+// the modifiers of the class: its fields, methods and member classes.
+fn write_members(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+    flags: u16,
+) -> DecompileResult<(Vec<EnumConstant>, Vec<Member>)> {
+    let mut constants = Vec::new();
+    let is_enum = is_enum(class_file);
+    if is_enum {
+        names.enums.insert(names.this_class.clone());
+        constants = write_enum_constants(class_file, hierarchy, classes, names)?;
+    }
+    let constant_pool = class_file.constant_pool();
+    let this_type = format!("L{};", names.this_class);
+    let mut methods = Vec::new();
+    for method in &class_file.methods {
+        if method.access_flags & ACC_BRIDGE != 0 {
+            if !needs_bridge(class_file, method) {
+                continue;
+            }
+        } else if method.access_flags & ACC_SYNTHETIC != 0 {
+            continue;
+        }
+        // an enum's `values()` and `valueOf(String)` are implicitly declared
+        let implicit = match method.name(constant_pool) {
+            Some("values") => format!("()[{this_type}"),
+            Some("valueOf") => format!("(Ljava/lang/String;){this_type}"),
+            _ => String::new(),
+        };
+        if is_enum
+            && method.access_flags & ACC_STATIC != 0
+            && method.descriptor(constant_pool) == Some(&implicit)
+        {
+            continue;
+        }
+        methods.push(decompile_method(
+            class_file, hierarchy, classes, names, method,
+        )?);
+    }
+    let mut initializers = field_initializers(class_file, hierarchy, classes, names, &mut methods);
+    let components = record_components(class_file);
+    // a record's instance fields are its components
+    let mut members: Vec<Member> = class_file
+        .fields
+        .iter()
+        .filter(|field| components.is_none() || field.access_flags & ACC_STATIC != 0)
+        .filter_map(|field| write_field(names, field, initializers.remove(&field.name)))
+        .collect();
+    let this_class = names.this_class.clone();
+    remove_default_constructor(&mut methods, &this_class, flags, is_enum);
+    if let Some(components) = &components {
+        remove_record_members(&mut methods, components);
+    }
+    for method in methods {
+        members.extend(write_method(class_file, hierarchy, classes, names, method)?);
+    }
+    members.extend(write_member_classes(class_file, hierarchy, classes, names)?);
+    Ok((constants, members))
+}
+
+// The declaration of the class `names` is writing without its members,
+// with `flags` its modifiers.
+fn write_header(class_file: &ClassFile, names: &mut Names, flags: u16) -> TypeDeclaration {
+    let constant_pool = class_file.constant_pool();
+    let is_interface = flags & ACC_INTERFACE != 0;
+    // an enum is final or abstract, and static when nested, as its
+    // constants say
+    let is_enum = is_enum(class_file);
+    // and a record is final, and static when nested
+    let components = record_components(class_file);
+    let flags = match (is_enum, &components) {
+        (true, _) => flags & !(ACC_ABSTRACT | ACC_STATIC | ACC_FINAL),
+        (false, Some(_)) => flags & !(ACC_STATIC | ACC_FINAL),
+        (false, None) => flags,
+    };
+    let mut modifiers = modifiers(
+        flags,
+        &[
+            (ACC_PUBLIC, Modifier::Public),
+            (ACC_PRIVATE, Modifier::Private),
+            (ACC_PROTECTED, Modifier::Protected),
+        ],
+    );
+    if flags & ACC_ABSTRACT != 0 && !is_interface {
+        modifiers.push(Modifier::Abstract);
+    }
+    // only member classes can be static, and interfaces are anyway
+    if flags & ACC_STATIC != 0 && !is_interface {
+        modifiers.push(Modifier::Static);
+    }
+    if flags & ACC_FINAL != 0 {
+        modifiers.push(Modifier::Final);
+    }
+    let kind = match flags {
+        _ if flags & ACC_ANNOTATION != 0 => TypeKind::Annotation,
+        _ if is_interface => TypeKind::Interface,
+        _ if is_enum => TypeKind::Enum,
+        _ if components.is_some() => TypeKind::Record,
+        _ => TypeKind::Class,
+    };
+    let interfaces: Vec<Type> = class_file
+        .interfaces
+        .iter()
+        .filter_map(|index| constant_pool.class_name(*index))
+        .map(|name| Type::Class(names.class(name)))
+        .collect();
+    let mut extends = Vec::new();
+    match class_file.super_class_name() {
+        Some(super_name)
+            if super_name != "java/lang/Object"
+                && !is_interface
+                && !is_enum
+                && components.is_none() =>
+        {
+            extends.push(Type::Class(names.class(super_name)));
+        }
+        _ => {}
+    }
+    let (extends, implements) = match is_interface {
+        true => (interfaces, Vec::new()),
+        false => (extends, interfaces),
+    };
+    TypeDeclaration {
+        modifiers,
+        kind,
+        name: names.declared.clone(),
+        extends,
+        implements,
+        components: components
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(name, descriptor)| Parameter {
+                parameter_type: FieldType::parse(&descriptor).map_or_else(
+                    || Type::Class(descriptor),
+                    |field_type| names.field_type(&field_type),
+                ),
+                name,
+                varargs: false,
+            })
+            .collect(),
+        constants: Vec::new(),
+        members: Vec::new(),
+    }
+}
+
+// The names and descriptors of the components of a record, as its `Record`
+// attribute gives them, or `None` when the class isn't one.
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.30
+fn record_components(class_file: &ClassFile) -> Option<Vec<(String, String)>> {
+    if class_file.super_class_name() != Some(RECORD) {
+        return None;
+    }
+    let constant_pool = class_file.constant_pool();
+    let components = class_file
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::Record { components, .. } => Some(components),
+            _ => None,
+        })?;
+    components
+        .iter()
+        .map(|component| {
+            let name = constant_pool.utf8(component.name_index)?;
+            let descriptor = constant_pool.utf8(component.descriptor_index)?;
+            Some((name.to_string(), descriptor.to_string()))
+        })
+        .collect()
+}
+
+// Whether a class is an enum, rather than the class of the body of one of
+// an enum's constants, which extends the enum.
+// Whether a bridge overrides a method of an erased supertype that no other
+// method of the class overrides, because they take other parameters: a
+// bridge that only returns a more general type is made again by javac.
+fn needs_bridge(class_file: &ClassFile, bridge: &MethodInfo) -> bool {
+    let constant_pool = class_file.constant_pool();
+    let parameters = |method: &MethodInfo| {
+        method
+            .descriptor(constant_pool)
+            .and_then(|descriptor| descriptor.split_once(')'))
+            .map(|(parameters, _)| parameters.to_string())
+    };
+    let name = bridge.name(constant_pool);
+    !class_file.methods.iter().any(|method| {
+        method.access_flags & (ACC_SYNTHETIC | ACC_BRIDGE) == 0
+            && method.name(constant_pool) == name
+            && parameters(method) == parameters(bridge)
+    })
+}
+
+fn is_enum(class_file: &ClassFile) -> bool {
+    class_file.access_flags & ACC_ENUM != 0 && class_file.super_class_name() == Some(ENUM)
+}
+
+// The constants an enum's `<clinit>` starts by creating, with the statements
+// that do it and put them in `$VALUES`: javac 11 on calls `$values()` for
+// the array, and before that creates it in place.
+//   RED = new Color("RED", 0);
+//   GREEN = new Color$1("GREEN", 1, "g");
+//   $VALUES = $values();
+// https://docs.oracle.com/javase/specs/jls/se21/html/jls-8.html#jls-8.9.3
+fn enum_constants<'s>(
+    this_class: &str,
+    statements: &'s [Statement],
+) -> (usize, Vec<(&'s str, &'s Expr)>) {
+    let mut constants = Vec::new();
+    let mut index = 0;
+    while let Some(Statement::Stmt(Stmt::Assign {
+        target:
+            Expr::Field {
+                owner,
+                name,
+                object: None,
+                ..
+            },
+        value: value @ Expr::New { args, .. },
+    })) = statements.get(index)
+    {
+        let named = matches!(args.first(), Some(Expr::Const(Const::String(string))) if string.to_string_lossy() == *name);
+        if owner != this_class || !named {
+            break;
+        }
+        constants.push((name.as_str(), value));
+        index += 1;
+    }
+    let values = statements[index..]
+        .iter()
+        .position(|statement| match statement {
+            Statement::Stmt(Stmt::Assign {
+                target: Expr::Field { name, .. },
+                ..
+            }) => name == "$VALUES",
+            _ => false,
+        });
+    let array = |statement: &Statement| match statement {
+        Statement::Declare {
+            value: Some(Expr::NewArray { .. } | Expr::ArrayInit { .. }),
+            ..
+        } => true,
+        Statement::Stmt(Stmt::Assign { target, .. }) => matches!(target, Expr::ArrayElement { .. }),
+        _ => false,
+    };
+    match values {
+        Some(values) if statements[index..index + values].iter().all(array) => {
+            (index + values + 1, constants)
+        }
+        _ => (index, constants),
+    }
+}
+
+// The declarations of a class's member classes. Those whose class files
+// can't be found are left out.
+fn write_member_classes(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+) -> DecompileResult<Vec<Member>> {
+    let constant_pool = class_file.constant_pool();
+    let this_class = names.this_class.clone();
+    let mut declarations = Vec::new();
+    for class in inner_classes(class_file) {
+        if class.inner_name_index == 0
+            || constant_pool.class_name(class.outer_class_info_index) != Some(&this_class)
+        {
+            continue;
+        }
+        let (Some(inner), Some(simple)) = (
+            constant_pool.class_name(class.inner_class_info_index),
+            constant_pool.utf8(class.inner_name_index),
+        ) else {
+            continue;
+        };
+        if inner == this_class {
+            continue;
+        }
+        let Some(member) = classes.class_file(inner) else {
+            continue;
+        };
+        names.add_member_classes(&member);
+        let declared = std::mem::replace(&mut names.declared, simple.to_string());
+        names.this_class = inner.to_string();
+        let declaration = write_declaration(
+            &member,
+            hierarchy,
+            classes,
+            names,
+            class.inner_class_access_flags,
+        );
+        names.this_class = this_class.clone();
+        names.declared = declared;
+        declarations.push(Member::Type(declaration?));
+    }
+    Ok(declarations)
+}
+
+fn inner_classes(class_file: &ClassFile) -> impl Iterator<Item = &InnerClassInfo> {
+    class_file
+        .attributes
+        .iter()
+        .filter_map(|attribute| match attribute {
+            Attribute::InnerClasses { classes, .. } => Some(classes),
+            _ => None,
+        })
+        .flatten()
+}
+
+// How classes are named in the source, and the imports that needs.
+struct Names {
+    // the internal name of the package, empty for the unnamed package
+    package: String,
+    // the class being written, which may be nested in the top level class
+    this_class: String,
+    // the enums among the classes written
+    enums: HashSet<String>,
+    // the name the class being written is declared with
+    declared: String,
+    // the simple name of the top level class
+    top: String,
+    // member classes, with the class they are members of and their simple
+    // name
+    members: HashMap<String, (String, String)>,
+    // member classes, as their name relative to their top level class
+    nested: HashMap<String, String>,
+    // inner member classes, and local and anonymous classes with an
+    // enclosing instance, with the class of that instance
+    inner: HashMap<String, String>,
+    // local classes with their simple names, and anonymous classes
+    locals: HashMap<String, Option<String>>,
+    // the class or interface each anonymous class extends or implements
+    supertypes: HashMap<String, String>,
+    // the synthetic parameters of the constructors of local and anonymous
+    // classes, by class and descriptor
+    constructors: HashMap<String, HashMap<String, Vec<Synthetic>>>,
+    // what the `val$x` fields of local and anonymous classes are, by class
+    // and field
+    captured: HashMap<(String, String), java::Expr>,
+    // simple names imported, with the class they name
+    imports: BTreeMap<String, String>,
+    // simple names of classes used without an import
+    unimported: HashSet<String>,
+    // the variables of the methods a local or anonymous class being written
+    // is in, which its own variables would hide from the captured values
+    // that are put in place of its `val$x` fields
+    reserved: HashSet<String>,
+}
+
+impl Names {
+    fn new(class_file: &ClassFile, this_class: &str) -> Names {
+        let (package, declared) = match this_class.rsplit_once('/') {
+            Some((package, simple)) => (package.to_string(), simple.to_string()),
+            None => (String::new(), this_class.to_string()),
+        };
+        let mut names = Names {
+            package,
+            this_class: this_class.to_string(),
+            enums: HashSet::new(),
+            top: declared.clone(),
+            declared,
+            members: HashMap::new(),
+            nested: HashMap::new(),
+            inner: HashMap::new(),
+            locals: HashMap::new(),
+            supertypes: HashMap::new(),
+            constructors: HashMap::new(),
+            captured: HashMap::new(),
+            imports: BTreeMap::new(),
+            unimported: HashSet::new(),
+            reserved: HashSet::new(),
+        };
+        names.add_member_classes(class_file);
+        names
+    }
+
+    // Learns the member classes named in a class's `InnerClasses`.
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.6
+    fn add_member_classes(&mut self, class_file: &ClassFile) {
+        let constant_pool = class_file.constant_pool();
+        for class in inner_classes(class_file) {
+            if class.outer_class_info_index == 0 {
+                if let Some(inner) = constant_pool.class_name(class.inner_class_info_index) {
+                    let simple = constant_pool.utf8(class.inner_name_index);
+                    self.locals
+                        .insert(inner.to_string(), simple.map(str::to_string));
+                }
+                continue;
+            }
+            if class.inner_name_index == 0 {
+                continue;
+            }
+            let names = (
+                constant_pool.class_name(class.inner_class_info_index),
+                constant_pool.class_name(class.outer_class_info_index),
+                constant_pool.utf8(class.inner_name_index),
+            );
+            if let (Some(inner), Some(outer), Some(simple)) = names {
+                self.members
+                    .insert(inner.to_string(), (outer.to_string(), simple.to_string()));
+                // interfaces, enums and records are implicitly static
+                if class.inner_class_access_flags & (ACC_STATIC | ACC_INTERFACE) == 0 {
+                    self.inner.insert(inner.to_string(), outer.to_string());
+                }
+            }
+        }
+        self.nested.clear();
+        for inner in self.members.keys() {
+            let mut parts = Vec::new();
+            let mut current = inner.as_str();
+            while let Some((outer, simple)) = self.members.get(current) {
+                if parts.len() > self.members.len() {
+                    break;
+                }
+                parts.push(simple.as_str());
+                current = outer;
+            }
+            let top = current
+                .rsplit_once('/')
+                .map_or(current, |(_, simple)| simple);
+            parts.push(top);
+            parts.reverse();
+            self.nested.insert(inner.clone(), parts.join("."));
+        }
+    }
+
+    // The name of a class in the source, importing it if it needs to be.
+    fn class(&mut self, internal: &str) -> String {
+        if internal == self.this_class {
+            return self.declared.clone();
+        }
+        match self.locals.get(internal) {
+            Some(Some(simple)) => return simple.clone(),
+            Some(None) => {
+                if let Some(supertype) = self.supertypes.get(internal).cloned() {
+                    return self.class(&supertype);
+                }
+            }
+            None => {}
+        }
+        let (package, simple) = internal.rsplit_once('/').unwrap_or(("", internal));
+        let name = self
+            .nested
+            .get(internal)
+            .cloned()
+            .unwrap_or_else(|| simple.to_string());
+        let top = name.split('.').next().unwrap_or(&name).to_string();
+        if package == self.package && top == self.top {
+            return name;
+        }
+        let qualified = match package {
+            "" => top.clone(),
+            package => format!("{}.{top}", package.replace('/', ".")),
+        };
+        let in_scope = package == self.package || package == "java/lang";
+        match self.imports.get(&top) {
+            Some(imported) if *imported == qualified => return name,
+            None if in_scope && top != self.top => {
+                self.unimported.insert(top);
+                return name;
+            }
+            None if !package.is_empty() && !self.unimported.contains(&top) && top != self.top => {
+                self.imports.insert(top, qualified);
+                return name;
+            }
+            _ => {}
+        }
+        match package {
+            "" => name,
+            package => format!("{}.{name}", package.replace('/', ".")),
+        }
+    }
+
+    fn field_type(&mut self, field_type: &FieldType) -> Type {
+        match field_type {
+            FieldType::Byte => Type::Byte,
+            FieldType::Char => Type::Char,
+            FieldType::Double => Type::Double,
+            FieldType::Float => Type::Float,
+            FieldType::Int => Type::Int,
+            FieldType::Long => Type::Long,
+            FieldType::Short => Type::Short,
+            FieldType::Boolean => Type::Boolean,
+            FieldType::Object(name) => Type::Class(self.class(name)),
+            FieldType::Array(element) => Type::Array(Box::new(self.field_type(element))),
+        }
+    }
+}
+
+fn modifiers(flags: u16, allowed: &[(u16, Modifier)]) -> Vec<Modifier> {
+    allowed
+        .iter()
+        .filter(|(flag, _)| flags & flag != 0)
+        .map(|(_, modifier)| *modifier)
+        .collect()
+}
+
+// Whether a field is javac's `this$0` holding an inner class's enclosing
+// instance, numbered by how deeply the enclosing class is nested.
+fn is_enclosing_field(name: &str) -> bool {
+    name.strip_prefix("this$")
+        .is_some_and(|depth| !depth.is_empty() && depth.bytes().all(|b| b.is_ascii_digit()))
+}
+
+// A parameter javac adds to the constructors of an inner, local or
+// anonymous class, with the synthetic field it is stored in: `this$0` for
+// the enclosing instance, or `val$x` for a local variable `x` the class
+// captures.
+#[derive(Debug, Clone)]
+struct Synthetic {
+    // the index of the parameter, not counting `this`
+    index: usize,
+    field: String,
+    descriptor: String,
+}
+
+// The synthetic parameters of a constructor, from the statements storing
+// them, which javac puts before anything else.
+fn synthetic_parameters(body: &Body, statements: &[Statement]) -> Vec<Synthetic> {
+    let this = body.this().map(Expr::Var);
+    let mut synthetic = Vec::new();
+    for statement in statements {
+        let Statement::Stmt(Stmt::Assign {
+            target:
+                Expr::Field {
+                    name,
+                    descriptor,
+                    object,
+                    ..
+                },
+            value: Expr::Var(value),
+        }) = statement
+        else {
+            break;
+        };
+        let index = body.parameters.iter().skip(1).position(|p| p == value);
+        match index {
+            Some(index)
+                if object.as_deref() == this.as_ref()
+                    && (is_enclosing_field(name) || name.starts_with("val$")) =>
+            {
+                synthetic.push(Synthetic {
+                    index,
+                    field: name.clone(),
+                    descriptor: descriptor.clone(),
+                });
+            }
+            _ => break,
+        }
+    }
+    synthetic
+}
+
+// The local classes of a class declared in the methods given by their
+// names and descriptors, which `EnclosingMethod` tells.
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.7
+fn local_classes(
+    class_file: &ClassFile,
+    classes: &dyn ClassPath,
+    methods: &[String],
+) -> Vec<String> {
+    let constant_pool = class_file.constant_pool();
+    let this_class = class_file.this_class_name();
+    let mut locals = Vec::new();
+    for class in inner_classes(class_file) {
+        if class.outer_class_info_index != 0 || class.inner_name_index == 0 {
+            continue;
+        }
+        let Some(inner) = constant_pool.class_name(class.inner_class_info_index) else {
+            continue;
+        };
+        let Some(local) = classes.class_file(inner) else {
+            continue;
+        };
+        let local_pool = local.constant_pool();
+        let enclosed =
+            local.attributes.iter().any(|attribute| match attribute {
+                Attribute::EnclosingMethod {
+                    class_index,
+                    method_index,
+                    ..
+                } => {
+                    local_pool.class_name(*class_index) == this_class
+                        && local_pool.name_and_type(*method_index).is_some_and(
+                            |(name, descriptor)| methods.contains(&format!("{name}{descriptor}")),
+                        )
+                }
+                _ => false,
+            });
+        if enclosed && Some(inner) != this_class && !locals.iter().any(|local| local == inner) {
+            locals.push(inner.to_string());
+        }
+    }
+    locals
+}
+
+// How many times statements create an instance of a class.
+fn instantiations(statements: &[Statement], class: &str) -> usize {
+    let mut count = 0;
+    for statement in statements {
+        for expr in statement.exprs() {
+            expr.walk(&mut |expr| {
+                if matches!(expr, Expr::New { class: created, .. } if created == class) {
+                    count += 1;
+                }
+            });
+        }
+        for child in statement.children() {
+            count += instantiations(child, class);
+        }
+    }
+    count
+}
+
+// Learns what the anonymous classes instantiated in statements extend or
+// implement, which is how they are named.
+fn add_supertypes(statements: &[Statement], names: &mut Names, classes: &dyn ClassPath) {
+    let mut anonymous = Vec::new();
+    for statement in statements {
+        for expr in statement.exprs() {
+            expr.walk(&mut |expr| match expr {
+                Expr::New { class, .. } if names.locals.get(class) == Some(&None) => {
+                    anonymous.push(class.clone());
+                }
+                _ => {}
+            });
+        }
+        for child in statement.children() {
+            add_supertypes(child, names, classes);
+        }
+    }
+    for class in anonymous {
+        if names.supertypes.contains_key(&class) {
+            continue;
+        }
+        let Some(class_file) = classes.class_file(&class) else {
+            continue;
+        };
+        let super_class = class_file.super_class_name().unwrap_or("java/lang/Object");
+        let supertype = match class_file.interfaces.as_slice() {
+            [interface] if super_class == "java/lang/Object" => class_file
+                .constant_pool()
+                .class_name(*interface)
+                .unwrap_or(super_class),
+            _ => super_class,
+        };
+        names.supertypes.insert(class, supertype.to_string());
+    }
+}
+
+// The descriptor and arguments of the first instance creation of a class
+// in statements.
+fn first_instantiation<'s>(
+    statements: &'s [Statement],
+    class: &str,
+) -> Option<(&'s str, &'s [Expr])> {
+    for statement in statements {
+        let mut found = None;
+        for expr in statement.exprs() {
+            expr.walk(&mut |expr| match expr {
+                Expr::New {
+                    class: created,
+                    descriptor,
+                    args,
+                } if created == class && found.is_none() => {
+                    found = Some((descriptor.as_str(), args.as_slice()));
+                }
+                _ => {}
+            });
+        }
+        if found.is_some() {
+            return found;
+        }
+        for child in statement.children() {
+            if let Some(found) = first_instantiation(child, class) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+// The initializers of the fields of the class `names` is writing, by
+// name: the constant values of static fields, and the assignments javac
+// compiled the others into, which are taken out of `methods`.
+fn field_initializers(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+    methods: &mut [Method],
+) -> HashMap<String, java::Expr> {
+    let this_class = names.this_class.clone();
+    let mut initializers = HashMap::new();
+    let mut field_types = HashMap::new();
+    let mut fields: [Vec<&str>; 2] = Default::default();
+    for field in &class_file.fields {
+        let Some(field_type) = FieldType::parse(&field.descriptor) else {
+            continue;
+        };
+        let is_static = field.access_flags & ACC_STATIC != 0;
+        if is_static {
+            if let Some(value) = constant_value(class_file, field, &field_type) {
+                initializers.insert(field.name.clone(), constant(names, &value));
+            }
+        }
+        if field.access_flags & (ACC_SYNTHETIC | ACC_ENUM) == 0 {
+            fields[usize::from(is_static)].push(field.name.as_str());
+        }
+        field_types.insert(field.name.as_str(), field_type);
+    }
+    let [instance_fields, static_fields] = fields;
+
+    let mut moved = Vec::new();
+    let mut constructors = Vec::new();
+    let mut contexts = Vec::new();
+    for method in methods.iter_mut() {
+        let Ok(Some((body, statements))) = &mut method.decompiled else {
+            continue;
+        };
+        match method.name.as_str() {
+            "<clinit>" => {
+                let values =
+                    initializers::static_initializers(&this_class, &static_fields, statements);
+                moved.push((&*body, None, Vec::new(), values));
+            }
+            "<init>" => {
+                constructors.push(initializers::Constructor {
+                    body,
+                    statements,
+                    enclosing: method.enclosing.as_ref().map(|(parameter, _)| *parameter),
+                });
+                contexts.push((&*body, method.enclosing.clone(), method.captured.clone()));
+            }
+            _ => {}
+        }
+    }
+    let values =
+        initializers::instance_initializers(&this_class, &instance_fields, &mut constructors);
+    if let Some((index, values)) = values {
+        let (body, enclosing, captured) = contexts.swap_remove(index);
+        moved.push((body, enclosing, captured, values));
+    }
+
+    for (body, enclosing, captured, values) in moved {
+        // whose variables, such as those of lambdas, are named apart from
+        // the rest of the method's
+        let assignments: Vec<Statement> = values
+            .iter()
+            .map(|(_, value)| Statement::Stmt(Stmt::Expr(value.clone())))
+            .collect();
+        let mut translator = Translator::new(names, hierarchy, classes, body, &assignments);
+        translator.enclosing = enclosing;
+        translator.captured.extend(captured);
+        for (field, value) in values {
+            let value = match field_types.get(field.as_str()) {
+                Some(field_type) => translator.assigned(&value, field_type),
+                None => translator.expr(&value),
+            };
+            initializers.insert(field, value);
+        }
+    }
+    initializers
+}
+
+// The value a `ConstantValue` attribute gives a field, as a constant of its
+// type.
+// See https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.2
+fn constant_value(
+    class_file: &ClassFile,
+    field: &FieldInfo,
+    field_type: &FieldType,
+) -> Option<Const> {
+    let constant_pool = class_file.constant_pool();
+    let index = field
+        .attributes
+        .iter()
+        .find_map(|attribute| match attribute {
+            Attribute::ConstantValue {
+                constant_value_index,
+                ..
+            } => Some(*constant_value_index),
+            _ => None,
+        })?;
+    let constant = match (constant_pool.entry(index)?, field_type) {
+        (ConstantPoolType::ConstantInteger { value }, FieldType::Boolean) => {
+            Const::Boolean(*value != 0)
+        }
+        (ConstantPoolType::ConstantInteger { value }, FieldType::Char) => {
+            Const::Char(*value as u16)
+        }
+        (ConstantPoolType::ConstantInteger { value }, _) => Const::Int(*value),
+        (ConstantPoolType::ConstantFloat { value }, _) => Const::Float(*value),
+        (ConstantPoolType::ConstantLong { value }, _) => Const::Long(*value),
+        (ConstantPoolType::ConstantDouble { value }, _) => Const::Double(*value),
+        (ConstantPoolType::ConstantString { string_idx }, _) => {
+            match constant_pool.entry(*string_idx)? {
+                ConstantPoolType::ConstantUtf8 { bytes, .. } => {
+                    Const::String(JavaString(mutf8::decode_utf16(bytes)?))
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    Some(constant)
+}
+
+// Leaves out the call of the superclass's constructor with no arguments
+// that javac adds to constructors without one, and the constructor it adds
+// to classes without any, which is one with no parameters, no body and the
+// access of the class, `flags`, or is private in an enum.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-8.html#jls-8.8.9
+fn remove_default_constructor(
+    methods: &mut Vec<Method>,
+    this_class: &str,
+    flags: u16,
+    is_enum: bool,
+) {
+    for method in methods.iter_mut() {
+        let Ok(Some((body, statements))) = &mut method.decompiled else {
+            continue;
+        };
+        if method.name != "<init>" {
+            continue;
+        }
+        let this = body.this().map(Expr::Var);
+        if let Some(Statement::Stmt(Stmt::Expr(Expr::Invoke {
+            kind: InvokeKind::Special,
+            owner,
+            name,
+            descriptor,
+            object,
+            ..
+        }))) = statements.first()
+        {
+            if owner != this_class
+                && name == "<init>"
+                && descriptor == "()V"
+                && object.as_deref() == this.as_ref()
+            {
+                statements.remove(0);
+            }
+        }
+    }
+    let mut constructors = methods
+        .iter()
+        .enumerate()
+        .filter(|(_, method)| method.name == "<init>");
+    let (Some((index, constructor)), None) = (constructors.next(), constructors.next()) else {
+        return;
+    };
+    let access = ACC_PUBLIC | ACC_PROTECTED | ACC_PRIVATE;
+    let parameters = MethodDescriptor::parse(&constructor.descriptor)
+        .map_or(usize::MAX, |descriptor| descriptor.parameters.len());
+    let throws = constructor
+        .info
+        .attributes
+        .iter()
+        .any(|attribute| matches!(attribute, Attribute::Exceptions { .. }));
+    let is_default = matches!(&constructor.decompiled, Ok(Some((_, statements))) if statements.is_empty())
+        && parameters == constructor.hidden.len()
+        && !throws
+        && !constructor.anonymous
+        && (is_enum || constructor.info.access_flags & access == flags & access);
+    if is_default {
+        methods.remove(index);
+    }
+}
+
+// Leaves out the members a record declares implicitly: the `toString()`,
+// `hashCode()` and `equals(Object)` javac implements with an
+// `ObjectMethods` bootstrap, the accessors returning its fields, and a
+// canonical constructor doing no more than assigning them. The parameters
+// of a canonical constructor that does more are named after the
+// components, as they must be.
+// https://docs.oracle.com/javase/specs/jls/se21/html/jls-8.html#jls-8.10.3
+fn remove_record_members(methods: &mut Vec<Method>, components: &[(String, String)]) {
+    let canonical: String = components
+        .iter()
+        .map(|(_, descriptor)| descriptor.as_str())
+        .collect();
+    let canonical = format!("({canonical})V");
+    methods.retain_mut(|method| {
+        let Ok(Some((body, statements))) = &mut method.decompiled else {
+            return true;
+        };
+        let this = body.this().map(Expr::Var);
+        match statements.as_slice() {
+            [Statement::Return(Some(Expr::InvokeDynamic { bootstrap, .. }))]
+                if bootstrap.is(OBJECT_METHODS, "bootstrap") =>
+            {
+                return false;
+            }
+            [Statement::Return(Some(Expr::Field {
+                name,
+                object: Some(object),
+                ..
+            }))] if *name == method.name
+                && method.descriptor.starts_with("()")
+                && Some(&**object) == this.as_ref()
+                && components.iter().any(|(component, _)| component == name) =>
+            {
+                return false;
+            }
+            _ => {}
+        }
+        if method.name != "<init>" || method.descriptor != canonical {
+            return true;
+        }
+        let parameters = &body.parameters[1..];
+        for (parameter, (component, _)) in parameters.iter().zip(components) {
+            body.variables[parameter.0].name = Some(component.clone());
+        }
+        let assigns = statements.len() == components.len()
+            && statements.iter().zip(parameters).zip(components).all(
+                |((statement, parameter), (component, _))| {
+                    matches!(statement, Statement::Stmt(Stmt::Assign {
+                        target: Expr::Field { name, object: Some(object), .. },
+                        value: Expr::Var(var),
+                    }) if name == component
+                        && Some(&**object) == this.as_ref()
+                        && var == parameter)
+                },
+            );
+        !assigns
+    });
+}
+
+fn write_field(names: &mut Names, field: &FieldInfo, value: Option<java::Expr>) -> Option<Member> {
+    // an enum's constants are declared with its `<clinit>`
+    if field.access_flags & (ACC_SYNTHETIC | ACC_ENUM) != 0 {
+        return None;
+    }
+    let modifiers = modifiers(
+        field.access_flags,
+        &[
+            (ACC_PUBLIC, Modifier::Public),
+            (ACC_PRIVATE, Modifier::Private),
+            (ACC_PROTECTED, Modifier::Protected),
+            (ACC_STATIC, Modifier::Static),
+            (ACC_FINAL, Modifier::Final),
+            (ACC_TRANSIENT, Modifier::Transient),
+            (ACC_VOLATILE, Modifier::Volatile),
+        ],
+    );
+    let field_type = FieldType::parse(&field.descriptor).map_or_else(
+        || Type::Class(field.descriptor.clone()),
+        |field_type| names.field_type(&field_type),
+    );
+    Some(Member::Field(java::Field {
+        modifiers,
+        field_type,
+        name: field.name.clone(),
+        value,
+    }))
+}
+
+// A method decompiled, with what its declaration leaves out worked out.
+struct Method<'m> {
+    info: &'m MethodInfo,
+    name: String,
+    descriptor: String,
+    decompiled: DecompileResult<Option<(Body, Vec<Statement>)>>,
+    // the indices of the parameters that aren't declared
+    hidden: Vec<usize>,
+    // the parameter an inner class's enclosing instance is passed in, with
+    // the enclosing class
+    enclosing: Option<(Var, String)>,
+    // the parameters local and anonymous classes are passed the variables
+    // they capture in, with those variables
+    captured: Vec<(Var, java::Expr)>,
+    // the local classes declared in the method
+    locals: Vec<String>,
+    anonymous: bool,
+}
+
+fn decompile_method<'m>(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+    method: &'m MethodInfo,
+) -> DecompileResult<Method<'m>> {
+    let constant_pool = class_file.constant_pool();
+    let name = method
+        .name(constant_pool)
+        .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+            method.name_index,
+        ))?;
+    let descriptor =
+        method
+            .descriptor(constant_pool)
+            .ok_or(DecompileError::InvalidUtf8ConstantPoolEntry(
+                method.descriptor_index,
+            ))?;
+
+    // a lambda javac copied into several constructors with a field
+    // initializer is made into one in each
+    let mut inlined = HashSet::new();
+    let mut decompiled = decompile_body(class_file, hierarchy, classes, method, &mut inlined);
+    let this_class = names.this_class.clone();
+    let local = names.locals.get(&this_class).cloned();
+    let anonymous = name == "<init>" && local == Some(None);
+    // the synthetic parameters of constructors aren't in the source, and
+    // neither is the `super(...)` of an anonymous class's, whose arguments
+    // are those of the class instance creation
+    let mut hidden = Vec::new();
+    let mut enclosing = None;
+    let mut captured = Vec::new();
+    let nested = names.inner.contains_key(&this_class) || local.is_some();
+    match &mut decompiled {
+        Ok(Some((body, statements))) if name == "<init>" && nested => {
+            let synthetic = synthetic_parameters(body, statements);
+            statements.drain(..synthetic.len());
+            for Synthetic {
+                index,
+                field,
+                descriptor,
+            } in synthetic
+            {
+                let parameter = body.parameters[index + 1];
+                hidden.push(index);
+                if is_enclosing_field(&field) {
+                    if let Some(FieldType::Object(outer)) = FieldType::parse(&descriptor) {
+                        names.inner.insert(this_class.clone(), outer.clone());
+                        enclosing = Some((parameter, outer));
+                    }
+                } else {
+                    let value = names.captured.get(&(this_class.clone(), field.clone()));
+                    let value = value
+                        .cloned()
+                        .unwrap_or_else(|| java::Expr::Name(field["val$".len()..].to_string()));
+                    captured.push((parameter, value));
+                }
+            }
+            if anonymous {
+                let this = body.this().map(Expr::Var);
+                let super_call = statements.iter().position(|statement| {
+                    matches!(statement, Statement::Stmt(Stmt::Expr(Expr::Invoke { name, object, .. }))
+                        if name == "<init>" && object.as_deref() == this.as_ref())
+                });
+                if let Some(super_call) = super_call {
+                    statements.remove(super_call);
+                }
+            }
+        }
+        // javac gives an inner class's enclosing instance first
+        _ if name == "<init>" && names.inner.contains_key(&this_class) => hidden.push(0),
+        _ => {}
+    }
+    // an enum's constructors are given the name and ordinal of a constant
+    // first, which they pass on to `Enum`'s, and its `<clinit>` creates the
+    // constants
+    if is_enum(class_file) {
+        match &mut decompiled {
+            Ok(Some((body, statements))) if name == "<init>" => {
+                // named as javac does, so they don't take the names the
+                // declared parameters would get
+                for (parameter, hidden) in body.parameters[1..]
+                    .iter()
+                    .zip(["$enum$name", "$enum$ordinal"])
+                {
+                    body.variables[parameter.0].name = Some(hidden.to_string());
+                }
+                let this = body.this().map(Expr::Var);
+                statements.retain(|statement| {
+                    !matches!(statement, Statement::Stmt(Stmt::Expr(Expr::Invoke {
+                        owner,
+                        name,
+                        object,
+                        ..
+                    })) if owner == ENUM && name == "<init>" && object.as_deref() == this.as_ref())
+                });
+            }
+            Ok(Some((_, statements))) if name == "<clinit>" => {
+                let (count, _) = enum_constants(&this_class, statements);
+                statements.drain(..count);
+            }
+            _ => {}
+        }
+        if name == "<init>" {
+            hidden.extend([0, 1]);
+        }
+    }
+    // and local classes are declared where they are used
+    let mut locals = Vec::new();
+    if let Ok(Some(_)) = &decompiled {
+        let mut enclosing_methods: Vec<String> = inlined.into_iter().collect();
+        enclosing_methods.push(format!("{name}{descriptor}"));
+        locals = local_classes(class_file, classes, &enclosing_methods);
+    }
+    Ok(Method {
+        info: method,
+        name: name.to_string(),
+        descriptor: descriptor.to_string(),
+        decompiled,
+        hidden,
+        enclosing,
+        captured,
+        locals,
+        anonymous,
+    })
+}
+
+fn write_method(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    names: &mut Names,
+    method: Method,
+) -> DecompileResult<Option<Member>> {
+    let constant_pool = class_file.constant_pool();
+    let Method {
+        info: method,
+        name,
+        descriptor,
+        decompiled,
+        hidden,
+        enclosing,
+        captured,
+        locals,
+        anonymous,
+    } = method;
+    let parsed = MethodDescriptor::parse(&descriptor)
+        .ok_or_else(|| DecompileError::InvalidDescriptor(descriptor.clone()))?;
+    let body = match &decompiled {
+        Ok(Some((body, statements))) => {
+            let mut translator = Translator::new(names, hierarchy, classes, body, statements);
+            translator.enclosing = enclosing;
+            translator.captured.extend(captured);
+            translator.declare_locals(locals, statements);
+            translator.statements(statements)
+        }
+        Ok(None) => Vec::new(),
+        Err(err) => {
+            let exception = names.class("java/lang/UnsupportedOperationException");
+            vec![
+                java::Statement::Comment(format!("could not decompile: {err}")),
+                java::Statement::Throw(java::Expr::New {
+                    qualifier: None,
+                    class: Type::Class(exception),
+                    args: Vec::new(),
+                    body: None,
+                }),
+            ]
+        }
+    };
+    let is_empty = matches!(&decompiled, Ok(Some((_, statements))) if statements.is_empty());
+
+    if anonymous {
+        // the rest of an anonymous class's constructor is an instance
+        // initializer
+        return Ok((!is_empty).then_some(Member::Initializer {
+            is_static: false,
+            body,
+        }));
+    }
+    if name == "<clinit>" {
+        // which is left out when its field initializers are all it had
+        return Ok((!is_empty).then_some(Member::Initializer {
+            is_static: true,
+            body,
+        }));
+    }
+    let is_interface = class_file.access_flags & ACC_INTERFACE != 0;
+    let flags = method.access_flags;
+    let mut modifiers = modifiers(
+        flags,
+        &[
+            (ACC_PUBLIC, Modifier::Public),
+            (ACC_PRIVATE, Modifier::Private),
+            (ACC_PROTECTED, Modifier::Protected),
+            (ACC_ABSTRACT, Modifier::Abstract),
+            (ACC_STATIC, Modifier::Static),
+            (ACC_FINAL, Modifier::Final),
+            (ACC_SYNCHRONIZED, Modifier::Synchronized),
+            (ACC_NATIVE, Modifier::Native),
+            (ACC_STRICT, Modifier::Strictfp),
+        ],
+    );
+    if is_interface && flags & (ACC_ABSTRACT | ACC_STATIC | ACC_PRIVATE) == 0 {
+        modifiers.push(Modifier::Default);
+    }
+    let (return_type, name) = match name.as_str() {
+        "<init>" => (None, names.declared.clone()),
+        _ => {
+            let return_type = match &parsed.return_type {
+                Some(return_type) => names.field_type(return_type),
+                None => Type::Void,
+            };
+            (Some(return_type), name)
+        }
+    };
+
+    let parameter_names = match &decompiled {
+        Ok(Some((body, statements))) => {
+            let variables = variable_names(body, statements, &names.reserved);
+            body.parameters
+                .iter()
+                .skip(usize::from(!body.is_static))
+                .map(|parameter| variables[parameter].clone())
+                .collect()
+        }
+        _ => (0..parsed.parameters.len())
+            .map(|index| format!("arg{index}"))
+            .collect::<Vec<String>>(),
+    };
+    let count = parsed.parameters.len();
+    let parameters = parsed
+        .parameters
+        .iter()
+        .zip(parameter_names)
+        .enumerate()
+        .filter(|(index, _)| !hidden.contains(index))
+        .map(|(index, (field_type, name))| match field_type {
+            FieldType::Array(element) if index + 1 == count && flags & ACC_VARARGS != 0 => {
+                Parameter {
+                    parameter_type: names.field_type(element),
+                    name,
+                    varargs: true,
+                }
+            }
+            field_type => Parameter {
+                parameter_type: names.field_type(field_type),
+                name,
+                varargs: false,
+            },
+        })
+        .collect();
+
+    let throws = method
+        .attributes
+        .iter()
+        .filter_map(|attribute| match attribute {
+            Attribute::Exceptions {
+                exception_index_table,
+                ..
+            } => Some(exception_index_table),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|index| constant_pool.class_name(*index))
+        .map(|name| Type::Class(names.class(name)))
+        .collect();
+
+    let body = (flags & (ACC_ABSTRACT | ACC_NATIVE) == 0).then_some(body);
+    Ok(Some(Member::Method(java::Method {
+        modifiers,
+        return_type,
+        name,
+        parameters,
+        throws,
+        body,
+    })))
+}
+
+// `inlined` gets the synthetic methods made into lambdas, by their names
+// and descriptors.
+fn decompile_body(
+    class_file: &ClassFile,
+    hierarchy: &dyn ClassHierarchy,
+    classes: &dyn ClassPath,
+    method: &MethodInfo,
+    inlined: &mut HashSet<String>,
+) -> DecompileResult<Option<(Body, Vec<Statement>)>> {
+    let Some(mut body) = lift_method(class_file, method)? else {
+        return Ok(None);
+    };
+    ssa::construct(&mut body);
+    ssa::optimize(&mut body);
+    infer::infer_types(&mut body, class_file.constant_pool(), hierarchy);
+    conditions::merge_stack_conditionals(&mut body);
+    ssa::destruct(&mut body);
+    let constant_pool = class_file.constant_pool();
+    let this_class = class_file.this_class_name().unwrap_or_default();
+    lambdas::rebuild_lambdas(&mut body, this_class, &mut |name, descriptor| {
+        let key = format!("{name}{descriptor}");
+        let method = class_file.methods.iter().find(|method| {
+            method.access_flags & ACC_SYNTHETIC != 0
+                && method.name(constant_pool) == Some(name)
+                && method.descriptor(constant_pool) == Some(descriptor)
+        })?;
+        // a lambda can't contain itself
+        if !inlined.insert(key.clone()) {
+            return None;
+        }
+        match decompile_body(class_file, hierarchy, classes, method, inlined) {
+            Ok(Some(decompiled)) => Some(decompiled),
+            _ => {
+                inlined.remove(&key);
+                None
+            }
+        }
+    });
+    accessors::inline_accessors(&mut body, &mut |owner, name, descriptor| {
+        let other;
+        let owner_file = if owner == this_class {
+            class_file
+        } else {
+            other = classes.class_file(owner)?;
+            &other
+        };
+        let constant_pool = owner_file.constant_pool();
+        let method = owner_file.methods.iter().find(|method| {
+            method.access_flags & ACC_SYNTHETIC != 0
+                && method.name(constant_pool) == Some(name)
+                && method.descriptor(constant_pool) == Some(descriptor)
+        })?;
+        // nor can an accessor
+        let key = format!("{owner}.{name}{descriptor}");
+        if !inlined.insert(key.clone()) {
+            return None;
+        }
+        let decompiled = decompile_body(owner_file, hierarchy, classes, method, inlined);
+        inlined.remove(&key);
+        decompiled.ok().flatten()
+    });
+    patterns::bind_patterns(&mut body, classes);
+    conditions::merge_conditions(&mut body);
+    concat::rebuild_concatenations(&mut body);
+    let pattern_switches = switches::pattern_switches(&mut body, classes);
+    let regions = exceptions::find_try_regions(&mut body, class_file.constant_pool())?;
+    let switch_maps = switches::switch_maps(&body, classes);
+    let mut statements = structure::structure(
+        &mut body,
+        &regions,
+        &switch_maps,
+        &pattern_switches,
+        class_file.major_version,
+    );
+    asserts::rebuild_asserts(&body, &mut statements);
+    Ok(Some((body, statements)))
+}
+
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "try",
+    "void",
+    "volatile",
+    "while",
+    "true",
+    "false",
+    "null",
+    "var",
+    "record",
+    "yield",
+];
+
+// A name for a variable from its type: `i` for an int, `string` for a
+// String, `bytes` for a byte array.
+fn base_name(field_type: Option<&FieldType>) -> String {
+    let primitive = |field_type: &FieldType| match field_type {
+        FieldType::Byte => Some("b"),
+        FieldType::Char => Some("c"),
+        FieldType::Double => Some("d"),
+        FieldType::Float => Some("f"),
+        FieldType::Int => Some("i"),
+        FieldType::Long => Some("l"),
+        FieldType::Short => Some("s"),
+        FieldType::Boolean => Some("flag"),
+        _ => None,
+    };
+    let simple = |name: &str| {
+        let simple = name.rsplit(['/', '$']).next().unwrap_or(name);
+        let mut chars = simple.chars();
+        match chars.next() {
+            Some(first) if first.is_alphabetic() => {
+                first.to_lowercase().chain(chars).collect::<String>()
+            }
+            _ => "obj".to_string(),
+        }
+    };
+    let name = match field_type {
+        None => "obj".to_string(),
+        Some(FieldType::Object(name)) if name == "java/lang/String" => "str".to_string(),
+        Some(FieldType::Object(name)) if name == "java/lang/Object" => "obj".to_string(),
+        Some(FieldType::Object(name)) => simple(name),
+        Some(FieldType::Array(element)) => match &**element {
+            FieldType::Byte => "bytes".to_string(),
+            FieldType::Char => "chars".to_string(),
+            FieldType::Double => "doubles".to_string(),
+            FieldType::Float => "floats".to_string(),
+            FieldType::Int => "ints".to_string(),
+            FieldType::Long => "longs".to_string(),
+            FieldType::Short => "shorts".to_string(),
+            FieldType::Boolean => "flags".to_string(),
+            element => format!("{}Array", base_name(Some(element))),
+        },
+        Some(field_type) => primitive(field_type).unwrap_or("obj").to_string(),
+    };
+    if KEYWORDS.contains(&name.as_str()) {
+        format!("{name}_")
+    } else {
+        name
+    }
+}
+
+// Unique names for the parameters and the variables declared in the
+// statements, other than the `reserved` ones.
+fn variable_names(
+    body: &Body,
+    statements: &[Statement],
+    reserved: &HashSet<String>,
+) -> HashMap<Var, String> {
+    let mut order: Vec<Var> = body.parameters.clone();
+    collect_declared(statements, &mut order);
+
+    let mut names = HashMap::new();
+    let mut taken: HashMap<String, usize> = HashMap::new();
+    for var in order {
+        if names.contains_key(&var) {
+            continue;
+        }
+        let variable = body.variable(var);
+        if Some(var) == body.this() {
+            names.insert(var, "this".to_string());
+            continue;
+        }
+        let base = variable
+            .name
+            .clone()
+            .unwrap_or_else(|| base_name(variable.field_type.as_ref()));
+        let count = taken.entry(base.clone()).or_insert(0);
+        let name = loop {
+            *count += 1;
+            let name = match *count {
+                1 => base.clone(),
+                count => format!("{base}{count}"),
+            };
+            if !reserved.contains(&name) {
+                break name;
+            }
+        };
+        names.insert(var, name);
+    }
+    names
+}
+
+fn collect_declared(statements: &[Statement], order: &mut Vec<Var>) {
+    for statement in statements {
+        order.extend(statement.bindings());
+        match statement {
+            Statement::Declare { var, .. } => order.push(*var),
+            Statement::Try {
+                resources, catches, ..
+            } => {
+                let declared = resources.iter().filter(|resource| resource.value.is_some());
+                order.extend(declared.map(|resource| resource.var));
+                order.extend(catches.iter().map(|catch| catch.var));
+            }
+            _ => {}
+        }
+        for child in statement.children() {
+            collect_declared(child, order);
+        }
+    }
+}
+
+// What `break` and `continue` with no label get to.
+#[derive(Clone, Copy)]
+struct Enclosing {
+    label: Label,
+    is_loop: bool,
+    is_switch: bool,
+}
+
+// The labels some jump has to name, because it isn't to the innermost loop
+// or switch.
+fn named_labels(
+    statements: &[Statement],
+    enclosing: &mut Vec<Enclosing>,
+    named: &mut HashSet<Label>,
+) {
+    for statement in statements {
+        match statement {
+            Statement::Break(label) => {
+                let innermost = enclosing.iter().rev().find(|e| e.is_loop || e.is_switch);
+                if innermost.is_none_or(|e| e.label != *label) {
+                    named.insert(*label);
+                }
+            }
+            Statement::Continue(label) => {
+                let innermost = enclosing.iter().rev().find(|e| e.is_loop);
+                if innermost.is_none_or(|e| e.label != *label) {
+                    named.insert(*label);
+                }
+            }
+            statement => {
+                if let Some(label) = statement.label() {
+                    enclosing.push(Enclosing {
+                        label,
+                        is_loop: matches!(
+                            statement,
+                            Statement::While { .. }
+                                | Statement::DoWhile { .. }
+                                | Statement::For { .. }
+                        ),
+                        is_switch: matches!(statement, Statement::Switch { .. }),
+                    });
+                }
+                for child in statement.children() {
+                    named_labels(child, enclosing, named);
+                }
+                if statement.label().is_some() {
+                    enclosing.pop();
+                }
+            }
+        }
+    }
+}
+
+// Turns the statements of a method body into Java statements, naming its
+// variables and labels and declaring the local classes it uses.
+struct Translator<'a> {
+    names: &'a mut Names,
+    // for the local and anonymous classes declared in the body
+    hierarchy: &'a dyn ClassHierarchy,
+    classes: &'a dyn ClassPath,
+    body: &'a Body,
+    // the constructor parameter that is an inner class's enclosing
+    // instance, with its class
+    enclosing: Option<(Var, String)>,
+    // the constructor parameters local and anonymous classes are passed the
+    // variables they capture in, with those variables
+    captured: HashMap<Var, java::Expr>,
+    // local classes yet to be declared, with how many times they are
+    // instantiated in the body
+    locals: Vec<(String, usize)>,
+    // what `return` returns, which is left to the interface in a lambda
+    return_type: Option<FieldType>,
+    variables: HashMap<Var, String>,
+    labels: HashMap<Label, String>,
+}
+
+impl<'a> Translator<'a> {
+    fn new(
+        names: &'a mut Names,
+        hierarchy: &'a dyn ClassHierarchy,
+        classes: &'a dyn ClassPath,
+        body: &'a Body,
+        statements: &[Statement],
+    ) -> Translator<'a> {
+        let mut named = HashSet::new();
+        named_labels(statements, &mut Vec::new(), &mut named);
+        let mut named: Vec<Label> = named.into_iter().collect();
+        named.sort();
+        let labels = named
+            .into_iter()
+            .enumerate()
+            .map(|(index, label)| (label, format!("label{}", index + 1)))
+            .collect();
+        add_supertypes(statements, names, classes);
+        let variables = variable_names(body, statements, &names.reserved);
+        Translator {
+            names,
+            hierarchy,
+            classes,
+            body,
+            enclosing: None,
+            captured: HashMap::new(),
+            locals: Vec::new(),
+            return_type: body.return_type.clone(),
+            variables,
+            labels,
+        }
+    }
+
+    fn statements(&mut self, statements: &[Statement]) -> Vec<java::Statement> {
+        let declarations = self.local_declarations(statements);
+        let mut translated = Vec::new();
+        for (index, statement) in statements.iter().enumerate() {
+            for (at, class) in &declarations {
+                if *at == index {
+                    translated.extend(self.local_declaration(class, statements));
+                }
+            }
+            translated.push(self.statement(statement));
+        }
+        for (_, class) in declarations
+            .iter()
+            .filter(|(at, _)| *at >= statements.len())
+        {
+            translated.extend(self.local_declaration(class, statements));
+        }
+        translated
+    }
+
+    fn declare_locals(&mut self, classes: Vec<String>, statements: &[Statement]) {
+        self.locals = classes
+            .into_iter()
+            .map(|class| {
+                let count = instantiations(statements, &class);
+                (class, count)
+            })
+            .collect();
+    }
+
+    // The local classes to declare among `statements`, with the index of
+    // the statement each goes before: the first to instantiate it in the
+    // innermost statements all its instantiations are in, so that it is in
+    // scope for each and the local variables it captures are in scope for
+    // it.
+    fn local_declarations(&mut self, statements: &[Statement]) -> Vec<(usize, String)> {
+        let mut declarations = Vec::new();
+        self.locals.retain(|(class, count)| {
+            if instantiations(statements, class) != *count {
+                return true;
+            }
+            let deeper = *count > 0
+                && statements.iter().any(|statement| {
+                    statement
+                        .children()
+                        .into_iter()
+                        .any(|child| instantiations(child, class) == *count)
+                });
+            if deeper {
+                return true;
+            }
+            let at = statements
+                .iter()
+                .position(|statement| instantiations(std::slice::from_ref(statement), class) > 0)
+                .unwrap_or(0);
+            declarations.push((at, class.clone()));
+            false
+        });
+        declarations
+    }
+
+    // The declaration of a local class, with what its `val$x` fields hold
+    // taken from the first of `statements` to instantiate it.
+    fn local_declaration(
+        &mut self,
+        class: &str,
+        statements: &[Statement],
+    ) -> Option<java::Statement> {
+        let class_file = self.local_class(class)?;
+        if let Some((descriptor, args)) = first_instantiation(statements, class) {
+            self.capture(class, descriptor, args);
+        }
+        let flags = inner_classes(&class_file)
+            .find(|inner| {
+                class_file
+                    .constant_pool()
+                    .class_name(inner.inner_class_info_index)
+                    == Some(class)
+            })
+            .map_or(0, |inner| inner.inner_class_access_flags);
+        let simple = self.names.class(class);
+        let declaration = self.nested_class(class, &simple, |names, hierarchy, classes| {
+            write_declaration(
+                &class_file,
+                hierarchy,
+                classes,
+                names,
+                flags & (ACC_ABSTRACT | ACC_FINAL),
+            )
+        });
+        Some(match declaration {
+            Ok(declaration) => java::Statement::Class(declaration),
+            Err(comment) => java::Statement::Comment(comment),
+        })
+    }
+
+    // The class file of a local or anonymous class, with the synthetic
+    // parameters of its constructors worked out.
+    fn local_class(&mut self, class: &str) -> Option<Rc<ClassFile>> {
+        let class_file = self.classes.class_file(class)?;
+        if self.names.constructors.contains_key(class) {
+            return Some(class_file);
+        }
+        self.names.add_member_classes(&class_file);
+        let constant_pool = class_file.constant_pool();
+        let mut constructors = HashMap::new();
+        for method in &class_file.methods {
+            let (Some("<init>"), Some(descriptor)) =
+                (method.name(constant_pool), method.descriptor(constant_pool))
+            else {
+                continue;
+            };
+            let decompiled = decompile_body(
+                &class_file,
+                self.hierarchy,
+                self.classes,
+                method,
+                &mut HashSet::new(),
+            );
+            let synthetic = match decompiled {
+                Ok(Some((body, statements))) => synthetic_parameters(&body, &statements),
+                _ => Vec::new(),
+            };
+            for parameter in &synthetic {
+                if let (true, Some(FieldType::Object(outer))) = (
+                    is_enclosing_field(&parameter.field),
+                    FieldType::parse(&parameter.descriptor),
+                ) {
+                    self.names.inner.insert(class.to_string(), outer);
+                }
+            }
+            constructors.insert(descriptor.to_string(), synthetic);
+        }
+        self.names
+            .constructors
+            .insert(class.to_string(), constructors);
+        Some(class_file)
+    }
+
+    // Learns what the `val$x` fields of a local or anonymous class hold from
+    // the arguments of an instance creation.
+    fn capture(&mut self, class: &str, descriptor: &str, args: &[Expr]) {
+        let synthetic = self.synthetic(class, descriptor);
+        for Synthetic { index, field, .. } in synthetic {
+            let key = (class.to_string(), field);
+            if key.1.starts_with("val$") && !self.names.captured.contains_key(&key) {
+                if let Some(arg) = args.get(index) {
+                    let value = self.expr(arg);
+                    self.names.captured.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn synthetic(&self, class: &str, descriptor: &str) -> Vec<Synthetic> {
+        self.names
+            .constructors
+            .get(class)
+            .and_then(|constructors| constructors.get(descriptor))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    // Writes a local or anonymous class with `write`, as the class `names`
+    // is writing, or says why it couldn't be.
+    fn nested_class<T>(
+        &mut self,
+        class: &str,
+        declared: &str,
+        write: impl FnOnce(&mut Names, &dyn ClassHierarchy, &dyn ClassPath) -> DecompileResult<T>,
+    ) -> Result<T, String> {
+        let this_class = std::mem::replace(&mut self.names.this_class, class.to_string());
+        let declared = std::mem::replace(&mut self.names.declared, declared.to_string());
+        let reserved = self.names.reserved.clone();
+        self.names.reserved.extend(self.variables.values().cloned());
+        let written = write(self.names, self.hierarchy, self.classes);
+        self.names.this_class = this_class;
+        self.names.declared = declared;
+        self.names.reserved = reserved;
+        written.map_err(|err| format!("could not decompile {class}: {err}"))
+    }
+
+    // The creation of an instance of a local or anonymous class, without
+    // the arguments of its synthetic parameters, with the body of an
+    // anonymous class.
+    fn local_instance(&mut self, class: &str, descriptor: &str, args: &[Expr]) -> java::Expr {
+        let Some(class_file) = self.local_class(class) else {
+            let args = self.arg_list(descriptor, args);
+            return new_instance(Type::Class(self.names.class(class)), args, None);
+        };
+        self.capture(class, descriptor, args);
+        let synthetic = self.synthetic(class, descriptor);
+        let args: Vec<java::Expr> = self
+            .arg_list(descriptor, args)
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !synthetic.iter().any(|parameter| parameter.index == *index))
+            .map(|(_, arg)| arg)
+            .collect();
+        if let Some(Some(_)) = self.names.locals.get(class) {
+            return new_instance(Type::Class(self.names.class(class)), args, None);
+        }
+
+        let supertype = self.names.class(class);
+        let members = self.nested_class(class, &supertype, |names, hierarchy, classes| {
+            let (_, members) = write_members(
+                &class_file,
+                hierarchy,
+                classes,
+                names,
+                class_file.access_flags,
+            )?;
+            Ok(members)
+        });
+        let members = members.unwrap_or_else(|comment| vec![Member::Comment(comment)]);
+        new_instance(Type::Class(supertype), args, Some(members))
+    }
+
+    fn label(&self, label: Label) -> Option<String> {
+        self.labels.get(&label).cloned()
+    }
+
+    fn variable(&self, var: Var) -> String {
+        self.variables
+            .get(&var)
+            .cloned()
+            .unwrap_or_else(|| format!("v{}", var.0))
+    }
+
+    fn statement(&mut self, statement: &Statement) -> java::Statement {
+        match statement {
+            Statement::Stmt(stmt) => self.stmt(stmt),
+            Statement::Declare { var, value } => {
+                let field_type = self.body.variable(*var).field_type.clone();
+                let var_type = self.declared_type(field_type.as_ref());
+                let value = value.as_ref().map(|value| match (value, &field_type) {
+                    (Expr::Lambda { .. } | Expr::MethodRef { .. }, Some(field_type)) => {
+                        self.coerce(value, field_type)
+                    }
+                    (_, Some(field_type)) => self.assigned(value, field_type),
+                    _ => self.expr(value),
+                });
+                java::Statement::Declare {
+                    var_type,
+                    name: self.variable(*var),
+                    value,
+                }
+            }
+            Statement::Block { label, body } => java::Statement::Block {
+                label: self.label(*label),
+                body: self.statements(body),
+            },
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => java::Statement::If {
+                condition: self.expr(condition),
+                then: self.statements(then),
+                otherwise: self.statements(otherwise),
+            },
+            Statement::While {
+                label,
+                condition,
+                body,
+            } => java::Statement::While {
+                label: self.label(*label),
+                condition: self.expr(condition),
+                body: self.statements(body),
+            },
+            Statement::DoWhile {
+                label,
+                body,
+                condition,
+            } => java::Statement::DoWhile {
+                label: self.label(*label),
+                body: self.statements(body),
+                condition: self.expr(condition),
+            },
+            Statement::For {
+                label,
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                let init = init
+                    .iter()
+                    .map(|statement| match statement {
+                        Statement::Declare {
+                            var,
+                            value: Some(value),
+                        } => {
+                            let field_type = self.body.variable(*var).field_type.clone();
+                            java::Statement::Declare {
+                                var_type: self.declared_type(field_type.as_ref()),
+                                name: self.variable(*var),
+                                value: Some(self.expr(value)),
+                            }
+                        }
+                        Statement::Stmt(stmt) => self.stmt(stmt),
+                        _ => unreachable!("for loops only start with assignments"),
+                    })
+                    .collect();
+                java::Statement::For {
+                    label: self.label(*label),
+                    init,
+                    condition: self.expr(condition),
+                    update: update.iter().map(|stmt| self.stmt_expr(stmt)).collect(),
+                    body: self.statements(body),
+                }
+            }
+            Statement::Switch {
+                label,
+                value,
+                cases,
+            } => java::Statement::Switch {
+                label: self.label(*label),
+                value: self.expr(value),
+                cases: cases.iter().map(|case| self.case(case)).collect(),
+            },
+            Statement::Try {
+                resources,
+                body,
+                catches,
+                finally,
+            } => {
+                let resources: Vec<Resource> = resources
+                    .iter()
+                    .map(|resource| {
+                        let name = self.variable(resource.var);
+                        let Some(value) = &resource.value else {
+                            return Resource::Expr(java::Expr::Name(name));
+                        };
+                        let field_type = self.body.variable(resource.var).field_type.clone();
+                        Resource::Declare {
+                            var_type: self.declared_type(field_type.as_ref()),
+                            name,
+                            value: self.expr(value),
+                        }
+                    })
+                    .collect();
+                let body = self.statements(body);
+                let catches: Vec<Catch> = catches
+                    .iter()
+                    .map(|catch| {
+                        let types = match catch.types.as_slice() {
+                            [] => vec![Type::Class(self.names.class("java/lang/Throwable"))],
+                            types => types
+                                .iter()
+                                .map(|name| Type::Class(self.names.class(name)))
+                                .collect(),
+                        };
+                        Catch {
+                            types,
+                            name: self.variable(catch.var),
+                            body: self.statements(&catch.body),
+                        }
+                    })
+                    .collect();
+                let finally = (!finally.is_empty() || catches.is_empty() && resources.is_empty())
+                    .then(|| self.statements(finally));
+                java::Statement::Try {
+                    resources,
+                    body,
+                    catches,
+                    finally,
+                }
+            }
+            Statement::Synchronized { lock, body } => java::Statement::Synchronized {
+                lock: self.expr(lock),
+                body: self.statements(body),
+            },
+            Statement::Break(label) => java::Statement::Break(self.label(*label)),
+            Statement::Continue(label) => java::Statement::Continue(self.label(*label)),
+            Statement::Return(None) => java::Statement::Return(None),
+            Statement::Return(Some(value)) => java::Statement::Return(Some(self.returned(value))),
+            Statement::Throw(value) => java::Statement::Throw(self.expr(value)),
+            Statement::Yield(value) => java::Statement::Yield(self.expr(value)),
+            Statement::Assert { condition, message } => java::Statement::Assert {
+                condition: self.expr(condition),
+                message: message.as_ref().map(|message| self.expr(message)),
+            },
+        }
+    }
+
+    fn case(&mut self, case: &Case) -> SwitchCase {
+        let mut labels: Vec<java::CaseLabel> = case
+            .labels
+            .iter()
+            .map(|label| self.case_label(label))
+            .collect();
+        if case.is_default {
+            labels.push(java::CaseLabel::Default);
+        }
+        SwitchCase {
+            labels,
+            guard: case.guard.as_ref().map(|guard| self.expr(guard)),
+            body: self.statements(&case.body),
+        }
+    }
+
+    fn case_label(&mut self, label: &CaseLabel) -> java::CaseLabel {
+        match label {
+            CaseLabel::Const(value) => java::CaseLabel::Expr(self.constant(value)),
+            CaseLabel::Enum(name) => java::CaseLabel::Expr(java::Expr::Name(name.clone())),
+            CaseLabel::Null => java::CaseLabel::Null,
+            CaseLabel::Pattern(field_type, pattern) => {
+                java::CaseLabel::Pattern(self.pattern(field_type, pattern))
+            }
+        }
+    }
+
+    fn pattern(&mut self, field_type: &FieldType, pattern: &Pattern) -> java::Pattern {
+        let field_type = self.names.field_type(field_type);
+        match pattern {
+            Pattern::Binding(var) => java::Pattern::Type {
+                pattern_type: field_type,
+                name: Some(self.variable(*var)),
+            },
+            Pattern::Record(components) => java::Pattern::Record {
+                record_type: field_type,
+                components: components
+                    .iter()
+                    .map(|(field_type, pattern)| self.pattern(field_type, pattern))
+                    .collect(),
+            },
+        }
+    }
+
+    // The value of a `return`. A lambda's interface method returns an
+    // erased type, so lambdas it returns are cast to their interfaces.
+    fn returned(&mut self, value: &Expr) -> java::Expr {
+        let object = FieldType::Object("java/lang/Object".to_string());
+        match self.return_type.clone() {
+            Some(return_type) => self.assigned(value, &return_type),
+            None => self.assigned(value, &object),
+        }
+    }
+
+    // A lambda, whose body is an expression when it returns one or is a
+    // single call.
+    fn lambda(&mut self, parameters: &[Var], body: &[Statement]) -> java::Expr {
+        let parameters = parameters
+            .iter()
+            .map(|parameter| self.variable(*parameter))
+            .collect();
+        let return_type = self.return_type.take();
+        let body = match body {
+            [Statement::Return(Some(value))] => LambdaBody::Expr(Box::new(self.returned(value))),
+            body => match <[java::Statement; 1]>::try_from(self.statements(body)) {
+                Ok([java::Statement::Expr(expr)])
+                    if matches!(body, [Statement::Stmt(Stmt::Expr(_))]) =>
+                {
+                    LambdaBody::Expr(Box::new(expr))
+                }
+                Ok(statement) => LambdaBody::Block(statement.into()),
+                Err(statements) => LambdaBody::Block(statements),
+            },
+        };
+        self.return_type = return_type;
+        java::Expr::Lambda { parameters, body }
+    }
+
+    fn declared_type(&mut self, field_type: Option<&FieldType>) -> Type {
+        match field_type {
+            Some(field_type) => self.names.field_type(field_type),
+            None => Type::Class(self.names.class("java/lang/Object")),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> java::Statement {
+        match stmt {
+            Stmt::Assign { .. }
+            | Stmt::Expr(
+                Expr::Invoke { .. }
+                | Expr::New { .. }
+                | Expr::Assign { .. }
+                | Expr::InvokeDynamic { .. },
+            ) => java::Statement::Expr(self.stmt_expr(stmt)),
+            // Java only allows calls, assignments and `new` as statements,
+            // so anything else is evaluated into a variable in a block of
+            // its own
+            Stmt::Expr(expr) => {
+                let var_type = match infer::type_of(self.body, expr) {
+                    Some(field_type) => self.names.field_type(&field_type),
+                    None => Type::Class(self.names.class("java/lang/Object")),
+                };
+                let declaration = java::Statement::Declare {
+                    var_type,
+                    name: "unused".to_string(),
+                    value: Some(self.expr(expr)),
+                };
+                java::Statement::Block {
+                    label: None,
+                    body: vec![declaration],
+                }
+            }
+            Stmt::MonitorEnter(lock) | Stmt::MonitorExit(lock) => {
+                let lock = printer::print_expr(&self.expr(lock), &Style::default());
+                java::Statement::Comment(format!("{} {lock}", monitor(stmt)))
+            }
+        }
+    }
+
+    // A statement as an expression, which it is unless it enters or exits a
+    // monitor, as in the update of a `for` loop.
+    fn stmt_expr(&mut self, stmt: &Stmt) -> java::Expr {
+        match stmt {
+            Stmt::Assign {
+                target: assigned,
+                value,
+            } => {
+                let expected = infer::type_of(self.body, assigned);
+                let target = match assigned {
+                    // a blank final is assigned by its simple name
+                    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-16.html
+                    Expr::Field {
+                        owner,
+                        name,
+                        object: None,
+                        ..
+                    } if *owner == self.names.this_class => java::Expr::Name(name.clone()),
+                    target => self.expr(target),
+                };
+                if let Some(compound) = self.compound(&target, assigned, value, expected.as_ref()) {
+                    return compound;
+                }
+                let value = match expected {
+                    Some(expected) => self.assigned(value, &expected),
+                    None => self.expr(value),
+                };
+                java::Expr::Assign {
+                    op: None,
+                    target: Box::new(target),
+                    value: Box::new(value),
+                }
+            }
+            Stmt::Expr(expr) => self.expr(expr),
+            Stmt::MonitorEnter(lock) | Stmt::MonitorExit(lock) => java::Expr::Commented {
+                comment: monitor(stmt).to_string(),
+                expr: Box::new(self.expr(lock)),
+            },
+        }
+    }
+
+    // An expression where a value of type `expected` is needed, with the
+    // cast Java needs for a variable whose type couldn't be narrowed past
+    // `Object` or an int constant passed as a byte or short.
+    fn coerce(&mut self, expr: &Expr, expected: &FieldType) -> java::Expr {
+        if let Some(types) = self.lambda_cast(expr, expected) {
+            cast(types, self.expr(expr))
+        } else if self.needs_cast(expr, expected) {
+            let field_type = self.names.field_type(expected);
+            cast(vec![field_type], self.expr(expr))
+        } else {
+            self.expr(expr)
+        }
+    }
+
+    // The elements of the initializer of an array of type `field_type`,
+    // `{1, 2, 3}`, with those of arrays within it nested as `{{1}, {2, 3}}`.
+    // Its elements are converted as they are in assignments, so constants
+    // need no casts.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-10.html#jls-10.6
+    fn array_init(&mut self, field_type: &FieldType, elements: &[Expr]) -> Vec<java::Expr> {
+        let element = match field_type {
+            FieldType::Array(element) => &**element,
+            _ => field_type,
+        };
+        elements
+            .iter()
+            .map(|expr| match expr {
+                Expr::ArrayInit {
+                    field_type,
+                    elements,
+                } if field_type == element => java::Expr::ArrayInit {
+                    array_type: None,
+                    elements: self.array_init(field_type, elements),
+                },
+                Expr::Const(Const::Int(_)) => self.expr(expr),
+                expr => self.assigned(expr, element),
+            })
+            .collect()
+    }
+
+    // `x += y` for `x = x + y`, which narrows the result to the type of `x`
+    // itself, and `x++` and `x--` for adding and subtracting one, when `x`
+    // is the same whichever way it is evaluated. `target` is `x` translated.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.26.2
+    fn compound(
+        &mut self,
+        target: &java::Expr,
+        assigned: &Expr,
+        value: &Expr,
+        expected: Option<&FieldType>,
+    ) -> Option<java::Expr> {
+        let operation = match value {
+            Expr::Cast {
+                field_type,
+                operand,
+            } if Some(field_type) == expected => operand,
+            value => value,
+        };
+        let Expr::Binary { op, left, right } = operation else {
+            return None;
+        };
+        if **left != *assigned || !repeatable(assigned) {
+            return None;
+        }
+        let one = matches!(**right, Expr::Const(Const::Int(1) | Const::Long(1)))
+            || matches!(**right, Expr::Const(Const::Float(value)) if value == 1.0)
+            || matches!(**right, Expr::Const(Const::Double(value)) if value == 1.0);
+        let minus_one = matches!(**right, Expr::Const(Const::Int(-1) | Const::Long(-1)));
+        let negated = match **right {
+            Expr::Const(Const::Int(value)) if value < 0 => value.checked_neg().map(Const::Int),
+            Expr::Const(Const::Long(value)) if value < 0 => value.checked_neg().map(Const::Long),
+            _ => None,
+        };
+        let target = Box::new(target.clone());
+        let (op, value) = match op {
+            BinaryOp::Add if one => return Some(unary(java::UnaryOp::PostIncrement, *target)),
+            BinaryOp::Sub if one => return Some(unary(java::UnaryOp::PostDecrement, *target)),
+            BinaryOp::Add if minus_one => {
+                return Some(unary(java::UnaryOp::PostDecrement, *target));
+            }
+            BinaryOp::Add if negated.is_some() => {
+                let negated = negated.unwrap_or(Const::Null);
+                (java::BinaryOp::Sub, self.constant(&negated))
+            }
+            op => {
+                let right = match unboxed_object(right) {
+                    Some((object, wrapper, _)) => self.receiver(object, wrapper),
+                    None => self.expr(right),
+                };
+                (binary_op(*op), right)
+            }
+        };
+        Some(java::Expr::Assign {
+            op: Some(op),
+            target,
+            value: Box::new(value),
+        })
+    }
+
+    // A value where one of type `expected` is assigned, without the call
+    // boxing or unboxing it that Java does itself there.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-5.html#jls-5.2
+    fn assigned(&mut self, expr: &Expr, expected: &FieldType) -> java::Expr {
+        // a constant that fits is narrowed without a cast
+        if let Expr::Const(Const::Int(value)) = expr {
+            let fits = match expected {
+                FieldType::Byte => i8::try_from(*value).is_ok(),
+                FieldType::Short => i16::try_from(*value).is_ok(),
+                FieldType::Char => u16::try_from(*value).is_ok(),
+                _ => true,
+            };
+            if fits {
+                return self.expr(expr);
+            }
+        }
+        if let Some((value, primitive)) = self.boxed_value(expr) {
+            let wrapper = boxing_class(&primitive).unwrap_or_default();
+            let supertypes = [
+                wrapper,
+                "java/lang/Object",
+                "java/lang/Comparable",
+                "java/io/Serializable",
+            ];
+            let numeric = !matches!(primitive, FieldType::Boolean | FieldType::Char);
+            let boxes = match expected {
+                FieldType::Object(class) => {
+                    supertypes.contains(&class.as_str()) || (numeric && class == "java/lang/Number")
+                }
+                _ => false,
+            };
+            if boxes {
+                let value = value.clone();
+                return self.coerce(&value, &primitive);
+            }
+        }
+        if let Some((object, wrapper, primitive)) = unboxed_object(expr) {
+            if primitive == *expected {
+                let wrapper = FieldType::Object(wrapper.to_string());
+                return self.coerce(object, &wrapper);
+            }
+        }
+        self.coerce(expr, expected)
+    }
+
+    // The primitive value a call of `valueOf` boxes, with its type, when it
+    // is of the type the wrapper class holds.
+    fn boxed_value<'e>(&self, expr: &'e Expr) -> Option<(&'e Expr, FieldType)> {
+        let Expr::Invoke {
+            kind: InvokeKind::Static,
+            owner,
+            name,
+            descriptor,
+            args,
+            ..
+        } = expr
+        else {
+            return None;
+        };
+        let primitive = boxed_type(owner)?;
+        let [value] = args.as_slice() else {
+            return None;
+        };
+        let boxing = format!("({primitive})L{owner};");
+        let typed = infer::type_of(self.body, value).as_ref() == Some(&primitive);
+        (name == "valueOf" && *descriptor == boxing && typed).then_some((value, primitive))
+    }
+
+    // An operand of a numeric operator, without the call unboxing it, which
+    // the operator does itself.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-5.html#jls-5.6
+    fn numeric_operand(&mut self, expr: &Expr) -> java::Expr {
+        match unboxed_object(expr) {
+            Some((object, wrapper, _)) => self.receiver(object, wrapper),
+            None => self.expr(expr),
+        }
+    }
+
+    fn needs_cast(&self, expr: &Expr, expected: &FieldType) -> bool {
+        let actual = infer::type_of(self.body, expr);
+        match (expected, expr, &actual) {
+            (FieldType::Byte | FieldType::Short, Expr::Const(Const::Int(_)), _) => true,
+            (FieldType::Object(name), Expr::Var(_), Some(FieldType::Object(actual))) => {
+                name != "java/lang/Object" && actual == "java/lang/Object"
+            }
+            (FieldType::Array(_), Expr::Var(_), Some(FieldType::Object(actual))) => {
+                actual == "java/lang/Object"
+            }
+            _ => false,
+        }
+    }
+
+    // The types of the cast a lambda or method reference needs where a
+    // value of type `expected` is, to tell it the interface it implements
+    // and its markers.
+    fn lambda_cast(&mut self, expr: &Expr, expected: &FieldType) -> Option<Vec<Type>> {
+        let (Expr::Lambda {
+            interface, markers, ..
+        }
+        | Expr::MethodRef {
+            interface, markers, ..
+        }) = expr
+        else {
+            return None;
+        };
+        if markers.is_empty() && *expected == FieldType::Object(interface.clone()) {
+            return None;
+        }
+        let types = std::iter::once(interface)
+            .chain(markers)
+            .map(|name| Type::Class(self.names.class(name)))
+            .collect();
+        Some(types)
+    }
+
+    fn arg_list(&mut self, descriptor: &str, args: &[Expr]) -> Vec<java::Expr> {
+        let parameters = MethodDescriptor::parse(descriptor)
+            .map_or_else(Vec::new, |descriptor| descriptor.parameters);
+        args.iter()
+            .enumerate()
+            .map(|(index, arg)| match parameters.get(index) {
+                Some(parameter) if self.converts(arg, parameter) => {
+                    let parameter_type = self.names.field_type(parameter);
+                    cast(vec![parameter_type], self.expr(arg))
+                }
+                Some(parameter) => self.coerce(arg, parameter),
+                None => self.expr(arg),
+            })
+            .collect()
+    }
+
+    // Whether an argument is a `byte`, `char`, `short` or `int` the method
+    // takes as another of them, which the bytecode doesn't convert but
+    // which picks a different overload in the source, such as
+    // `println(char)` for `println((int) c)`.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.12.2.5
+    fn converts(&self, arg: &Expr, parameter: &FieldType) -> bool {
+        use FieldType::{Byte, Char, Int, Short};
+        let actual = infer::type_of(self.body, arg);
+        match (parameter, &actual) {
+            (Byte | Char | Short | Int, Some(actual @ (Byte | Char | Short | Int))) => {
+                actual != parameter
+            }
+            _ => false,
+        }
+    }
+
+    // The class whose instance an expression is when it is `this` or one of
+    // its enclosing instances.
+    fn enclosing_instance(&self, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Var(var) if Some(*var) == self.body.this() => Some(self.names.this_class.clone()),
+            Expr::Var(var) => match &self.enclosing {
+                Some((parameter, class)) if parameter == var => Some(class.clone()),
+                _ => None,
+            },
+            Expr::Field {
+                owner,
+                name,
+                object: Some(object),
+                ..
+            } if is_enclosing_field(name) => {
+                let outer = self.names.inner.get(owner)?;
+                (self.enclosing_instance(object).as_ref() == Some(owner)).then(|| outer.clone())
+            }
+            _ => None,
+        }
+    }
+
+    // Whether an expression is the instance of an enclosing anonymous
+    // class, which has no name to say `Outer.this` with, so its members are
+    // used by their simple names.
+    fn anonymous_enclosing(&self, expr: &Expr) -> bool {
+        self.enclosing_instance(expr).is_some_and(|class| {
+            class != self.names.this_class && self.names.locals.get(&class) == Some(&None)
+        })
+    }
+
+    // The arguments of a constructor of `class`, or its inner class's
+    // constructor with its enclosing instance as a qualifier if it has to
+    // be given.
+    fn constructor_args(
+        &mut self,
+        class: &str,
+        descriptor: &str,
+        args: &[Expr],
+    ) -> (Option<java::Expr>, Vec<java::Expr>) {
+        // the qualifier is checked for null, so javac's check of it isn't
+        // written
+        let unchecked: Vec<Expr>;
+        let args = match args.split_first() {
+            Some((
+                Expr::Invoke {
+                    owner,
+                    name,
+                    args: checked,
+                    ..
+                },
+                rest,
+            )) if self.names.inner.contains_key(class)
+                && owner == "java/util/Objects"
+                && name == "requireNonNull"
+                && checked.len() == 1 =>
+            {
+                unchecked = checked.iter().chain(rest).cloned().collect();
+                &unchecked[..]
+            }
+            _ => args,
+        };
+        let mut list = self.arg_list(descriptor, args);
+        // nor are the name and ordinal of an enum's constants
+        if self.names.enums.contains(class) {
+            list.drain(..list.len().min(2));
+        }
+        let (Some(outer), Some(first)) = (self.names.inner.get(class).cloned(), args.first())
+        else {
+            return (None, list);
+        };
+        list.remove(0);
+        let qualifier = match self.enclosing_instance(first) {
+            Some(instance) if instance == outer => None,
+            _ => Some(self.expr(first)),
+        };
+        (qualifier, list)
+    }
+
+    // An object whose member is used, cast when its type isn't known to be
+    // `owner`.
+    fn receiver(&mut self, object: &Expr, owner: &str) -> java::Expr {
+        let object_type = FieldType::Object("java/lang/Object".to_string());
+        if let Some(types) = self.lambda_cast(object, &object_type) {
+            return cast(types, self.expr(object));
+        }
+        match FieldType::from_class_name(owner) {
+            Some(owner) if self.needs_cast(object, &owner) => self.coerce(object, &owner),
+            _ => self.expr(object),
+        }
+    }
+
+    // A constant, as a literal or the field that has its value.
+    fn constant(&mut self, value: &Const) -> java::Expr {
+        constant(self.names, value)
+    }
+
+    // The class an object or static member belongs to, as a qualifier.
+    fn class_name(&mut self, class: &str) -> java::Expr {
+        java::Expr::Type(Type::Class(self.names.class(class)))
+    }
+
+    fn expr(&mut self, expr: &Expr) -> java::Expr {
+        match expr {
+            Expr::Const(value) => self.constant(value),
+            Expr::Var(_) | Expr::Field { .. }
+                if self
+                    .enclosing_instance(expr)
+                    .is_some_and(|class| class != self.names.this_class) =>
+            {
+                let class = self.enclosing_instance(expr).unwrap_or_default();
+                java::Expr::This(Some(self.names.class(&class)))
+            }
+            Expr::Field {
+                owner,
+                name,
+                object: Some(object),
+                ..
+            } if name.starts_with("val$")
+                && self.enclosing_instance(object).as_ref() == Some(owner)
+                && self
+                    .names
+                    .captured
+                    .contains_key(&(owner.clone(), name.clone())) =>
+            {
+                self.names.captured[&(owner.clone(), name.clone())].clone()
+            }
+            Expr::Var(var) if Some(*var) == self.body.this() => java::Expr::This(None),
+            Expr::Var(var) => match self.captured.get(var) {
+                Some(captured) => captured.clone(),
+                None => java::Expr::Name(self.variable(*var)),
+            },
+            Expr::Field {
+                owner,
+                name,
+                object,
+                ..
+            } => {
+                let object = match object {
+                    Some(object) if self.anonymous_enclosing(object) => {
+                        return java::Expr::Name(name.clone());
+                    }
+                    Some(object) => self.receiver(object, owner),
+                    None => self.class_name(owner),
+                };
+                field(object, name)
+            }
+            Expr::ArrayElement { array, index } => java::Expr::ArrayAccess {
+                array: Box::new(self.expr(array)),
+                index: Box::new(self.expr(index)),
+            },
+            Expr::ArrayLength(array) => field(self.expr(array), "length"),
+            Expr::Binary { op, left, right } => binary(
+                binary_op(*op),
+                self.numeric_operand(left),
+                self.numeric_operand(right),
+            ),
+            Expr::Unary { op, operand } => {
+                let op = match op {
+                    UnaryOp::Neg => java::UnaryOp::Neg,
+                    UnaryOp::Not => java::UnaryOp::Not,
+                };
+                unary(op, self.numeric_operand(operand))
+            }
+            Expr::Compare { op, left, right } => {
+                let op = match op {
+                    CompareOp::Eq => java::BinaryOp::Eq,
+                    CompareOp::Ne => java::BinaryOp::Ne,
+                    CompareOp::Lt => java::BinaryOp::Lt,
+                    CompareOp::Ge => java::BinaryOp::Ge,
+                    CompareOp::Gt => java::BinaryOp::Gt,
+                    CompareOp::Le => java::BinaryOp::Le,
+                };
+                // `==` only unboxes one side, as it compares two objects
+                // by reference
+                let (left, right) = match op {
+                    java::BinaryOp::Eq | java::BinaryOp::Ne if unboxed_object(right).is_some() => {
+                        (self.expr(left), self.numeric_operand(right))
+                    }
+                    java::BinaryOp::Eq | java::BinaryOp::Ne => {
+                        (self.numeric_operand(left), self.expr(right))
+                    }
+                    _ => (self.numeric_operand(left), self.numeric_operand(right)),
+                };
+                binary(op, left, right)
+            }
+            Expr::Logical { op, left, right } => {
+                let op = match op {
+                    LogicalOp::And => java::BinaryOp::And,
+                    LogicalOp::Or => java::BinaryOp::Or,
+                };
+                binary(op, self.expr(left), self.expr(right))
+            }
+            Expr::Conditional {
+                condition,
+                then,
+                otherwise,
+            } => conditional(self.expr(condition), self.expr(then), self.expr(otherwise)),
+            // lcmp, fcmp and dcmp used as values
+            Expr::Cmp { kind, left, right } => {
+                let (left, right) = (self.expr(left), self.expr(right));
+                let int = |value| java::Expr::Literal(Literal::Int(value));
+                let equal = binary(java::BinaryOp::Eq, left.clone(), right.clone());
+                match kind {
+                    CmpKind::Long => java::Expr::Call {
+                        object: Some(Box::new(self.class_name("java/lang/Long"))),
+                        name: "compare".to_string(),
+                        args: vec![left, right],
+                    },
+                    CmpKind::FloatL | CmpKind::DoubleL => conditional(
+                        binary(java::BinaryOp::Gt, left, right),
+                        int(1),
+                        conditional(equal, int(0), int(-1)),
+                    ),
+                    CmpKind::FloatG | CmpKind::DoubleG => conditional(
+                        binary(java::BinaryOp::Lt, left, right),
+                        int(-1),
+                        conditional(equal, int(0), int(1)),
+                    ),
+                }
+            }
+            Expr::Cast {
+                field_type,
+                operand,
+            } => {
+                let field_type = self.names.field_type(field_type);
+                cast(vec![field_type], self.expr(operand))
+            }
+            Expr::InstanceOf {
+                operand,
+                field_type,
+                pattern,
+            } => {
+                let operand = Box::new(self.expr(operand));
+                let pattern = match pattern {
+                    Some(pattern) => self.pattern(field_type, pattern),
+                    None => java::Pattern::Type {
+                        pattern_type: self.names.field_type(field_type),
+                        name: None,
+                    },
+                };
+                java::Expr::InstanceOf { operand, pattern }
+            }
+            Expr::Invoke {
+                kind,
+                owner,
+                name,
+                descriptor,
+                object,
+                args,
+            } => {
+                let this = self.body.this().map(Expr::Var);
+                let on_this = object.as_deref() == this.as_ref();
+                if name == "<init>" && on_this {
+                    let (qualifier, args) = self.constructor_args(owner, descriptor, args);
+                    let is_super = *owner != self.names.this_class;
+                    return java::Expr::ConstructorCall {
+                        qualifier: qualifier.filter(|_| is_super).map(Box::new),
+                        is_super,
+                        args,
+                    };
+                }
+                let args = self.arg_list(descriptor, args);
+                let object = match object {
+                    Some(_)
+                        if *kind == InvokeKind::Special
+                            && on_this
+                            && *owner != self.names.this_class =>
+                    {
+                        Some(java::Expr::Super)
+                    }
+                    Some(object) if self.anonymous_enclosing(object) => None,
+                    Some(object) => Some(self.receiver(object, owner)),
+                    None => Some(self.class_name(owner)),
+                };
+                java::Expr::Call {
+                    object: object.map(Box::new),
+                    name: name.clone(),
+                    args,
+                }
+            }
+            Expr::InvokeDynamic {
+                bootstrap,
+                name,
+                descriptor,
+                args,
+            } => java::Expr::Commented {
+                comment: format!("invokedynamic #{}", bootstrap.index),
+                expr: Box::new(java::Expr::Call {
+                    object: None,
+                    name: name.clone(),
+                    args: self.arg_list(descriptor, args),
+                }),
+            },
+            Expr::New {
+                class,
+                descriptor,
+                args,
+            } if self.names.locals.contains_key(class) => {
+                self.local_instance(class, descriptor, args)
+            }
+            Expr::New {
+                class,
+                descriptor,
+                args,
+            } => match self.constructor_args(class, descriptor, args) {
+                (Some(qualifier), args) => {
+                    let simple = self.names.members.get(class).map(|(_, simple)| simple);
+                    java::Expr::New {
+                        qualifier: Some(Box::new(qualifier)),
+                        class: Type::Class(simple.cloned().unwrap_or_default()),
+                        args,
+                        body: None,
+                    }
+                }
+                (None, args) => new_instance(Type::Class(self.names.class(class)), args, None),
+            },
+            Expr::NewArray {
+                field_type,
+                dimensions,
+            } => {
+                let mut element = field_type;
+                let mut depth = 0;
+                while let FieldType::Array(inner) = element {
+                    element = inner;
+                    depth += 1;
+                }
+                java::Expr::NewArray {
+                    element: self.names.field_type(element),
+                    dimensions: dimensions
+                        .iter()
+                        .map(|dimension| self.expr(dimension))
+                        .collect(),
+                    extra_dimensions: depth - dimensions.len().min(depth),
+                }
+            }
+            Expr::ArrayInit {
+                field_type,
+                elements,
+            } => java::Expr::ArrayInit {
+                elements: self.array_init(field_type, elements),
+                array_type: Some(self.names.field_type(field_type)),
+            },
+            Expr::Lambda {
+                parameters, body, ..
+            } => self.lambda(parameters, body),
+            Expr::MethodRef {
+                owner,
+                name,
+                object,
+                ..
+            } => {
+                let name = if name == "<init>" { "new" } else { name };
+                let object = match object {
+                    Some(object) => self.expr(object),
+                    None => match FieldType::from_class_name(owner) {
+                        Some(owner) => java::Expr::Type(self.names.field_type(&owner)),
+                        None => self.class_name(owner),
+                    },
+                };
+                java::Expr::MethodRef {
+                    object: Box::new(object),
+                    name: name.to_string(),
+                }
+            }
+            // in the arrow form, since its cases can't fall through
+            Expr::Switch { value, cases } => java::Expr::Switch {
+                value: Box::new(self.expr(value)),
+                cases: cases.iter().map(|case| self.case(case)).collect(),
+            },
+            Expr::Assign { target, value } => java::Expr::Assign {
+                op: None,
+                target: Box::new(self.expr(target)),
+                value: Box::new(self.expr(value)),
+            },
+            Expr::CaughtException => commented_null("caught exception".to_string()),
+            Expr::Uninitialized { class, .. } => {
+                let class = printer::type_name(&Type::Class(self.names.class(class)));
+                commented_null(format!("uninitialized {class}"))
+            }
+        }
+    }
+}
+
+fn binary_op(op: BinaryOp) -> java::BinaryOp {
+    match op {
+        BinaryOp::Add => java::BinaryOp::Add,
+        BinaryOp::Sub => java::BinaryOp::Sub,
+        BinaryOp::Mul => java::BinaryOp::Mul,
+        BinaryOp::Div => java::BinaryOp::Div,
+        BinaryOp::Rem => java::BinaryOp::Rem,
+        BinaryOp::Shl => java::BinaryOp::Shl,
+        BinaryOp::Shr => java::BinaryOp::Shr,
+        BinaryOp::Ushr => java::BinaryOp::Ushr,
+        BinaryOp::And => java::BinaryOp::BitAnd,
+        BinaryOp::Or => java::BinaryOp::BitOr,
+        BinaryOp::Xor => java::BinaryOp::Xor,
+    }
+}
+
+// What a statement entering or exiting a monitor does, which Java has no
+// statement for outside of `synchronized`.
+fn monitor(stmt: &Stmt) -> &'static str {
+    match stmt {
+        Stmt::MonitorEnter(_) => "monitorenter",
+        _ => "monitorexit",
+    }
+}
+
+fn field(object: java::Expr, name: &str) -> java::Expr {
+    java::Expr::Field {
+        object: Box::new(object),
+        name: name.to_string(),
+    }
+}
+
+fn unary(op: java::UnaryOp, operand: java::Expr) -> java::Expr {
+    java::Expr::Unary {
+        op,
+        operand: Box::new(operand),
+    }
+}
+
+fn binary(op: java::BinaryOp, left: java::Expr, right: java::Expr) -> java::Expr {
+    java::Expr::Binary {
+        op,
+        left: Box::new(left),
+        right: Box::new(right),
+    }
+}
+
+fn conditional(condition: java::Expr, then: java::Expr, otherwise: java::Expr) -> java::Expr {
+    java::Expr::Conditional {
+        condition: Box::new(condition),
+        then: Box::new(then),
+        otherwise: Box::new(otherwise),
+    }
+}
+
+fn cast(types: Vec<Type>, operand: java::Expr) -> java::Expr {
+    java::Expr::Cast {
+        types,
+        operand: Box::new(operand),
+    }
+}
+
+fn new_instance(class: Type, args: Vec<java::Expr>, body: Option<Vec<Member>>) -> java::Expr {
+    java::Expr::New {
+        qualifier: None,
+        class,
+        args,
+        body,
+    }
+}
+
+// `null` for a value the source has no expression for, with a comment
+// saying what it is.
+fn commented_null(comment: String) -> java::Expr {
+    java::Expr::Commented {
+        comment,
+        expr: Box::new(java::Expr::Literal(Literal::Null)),
+    }
+}
+
+// Whether an expression gives the same value each time and has no side
+// effects, so it can be left to be evaluated once instead of twice.
+fn repeatable(expr: &Expr) -> bool {
+    match expr {
+        Expr::Const(_) | Expr::Var(_) => true,
+        Expr::Field { object, .. } => object.as_deref().is_none_or(repeatable),
+        Expr::ArrayElement { array, index } => repeatable(array) && repeatable(index),
+        _ => false,
+    }
+}
+
+// The wrapper classes of the primitive types, which box and unbox them.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-5.html#jls-5.1.7
+const BOXES: &[(&str, FieldType, &str)] = &[
+    ("java/lang/Boolean", FieldType::Boolean, "booleanValue"),
+    ("java/lang/Byte", FieldType::Byte, "byteValue"),
+    ("java/lang/Character", FieldType::Char, "charValue"),
+    ("java/lang/Short", FieldType::Short, "shortValue"),
+    ("java/lang/Integer", FieldType::Int, "intValue"),
+    ("java/lang/Long", FieldType::Long, "longValue"),
+    ("java/lang/Float", FieldType::Float, "floatValue"),
+    ("java/lang/Double", FieldType::Double, "doubleValue"),
+];
+
+// The primitive type a wrapper class holds.
+fn boxed_type(class: &str) -> Option<FieldType> {
+    BOXES
+        .iter()
+        .find(|(wrapper, ..)| *wrapper == class)
+        .map(|(_, primitive, _)| primitive.clone())
+}
+
+fn boxing_class(primitive: &FieldType) -> Option<&'static str> {
+    BOXES
+        .iter()
+        .find(|(_, boxed, _)| boxed == primitive)
+        .map(|(wrapper, ..)| *wrapper)
+}
+
+// The object a call such as `intValue()` unboxes, with its class and the
+// type it holds, when the call gives that type rather than converting it.
+fn unboxed_object(expr: &Expr) -> Option<(&Expr, &str, FieldType)> {
+    let Expr::Invoke {
+        kind: InvokeKind::Virtual,
+        owner,
+        name,
+        object: Some(object),
+        args,
+        ..
+    } = expr
+    else {
+        return None;
+    };
+    let (wrapper, primitive, _) = BOXES
+        .iter()
+        .find(|(wrapper, _, unboxing)| wrapper == owner && unboxing == name)?;
+    args.is_empty()
+        .then(|| (&**object, *wrapper, primitive.clone()))
+}
+
+// The literal of a constant, or the field or expression that has its value.
+fn constant(names: &mut Names, constant: &Const) -> java::Expr {
+    let literal = match constant {
+        Const::Null => Literal::Null,
+        Const::Int(value) => Literal::Int(*value),
+        Const::Boolean(value) => Literal::Boolean(*value),
+        Const::Char(value) => Literal::Char(*value),
+        Const::Long(value) => Literal::Long(*value),
+        Const::Float(value) => {
+            let name = printer::float_field(*value);
+            let negative = value.is_sign_negative() && value.is_finite();
+            return floating(
+                names,
+                "java/lang/Float",
+                name,
+                negative,
+                Literal::Float(*value),
+            );
+        }
+        Const::Double(value) => {
+            let name = printer::double_field(*value);
+            let negative = value.is_sign_negative() && value.is_finite();
+            return floating(
+                names,
+                "java/lang/Double",
+                name,
+                negative,
+                Literal::Double(*value),
+            );
+        }
+        Const::String(value) => Literal::String(value.0.clone()),
+        Const::Class(field_type) => return java::Expr::Class(names.field_type(field_type)),
+        Const::MethodType(descriptor) => {
+            return commented_null(format!("MethodType {descriptor}"));
+        }
+        Const::MethodHandle {
+            owner,
+            name,
+            descriptor,
+            ..
+        } => return commented_null(format!("MethodHandle {owner}.{name}{descriptor}")),
+        Const::Dynamic { name, .. } => return commented_null(format!("dynamic constant {name}")),
+    };
+    java::Expr::Literal(literal)
+}
+
+// A float or double, which is printed as the field `name` of its wrapper
+// `class` when it has one. That is qualified here, rather than printed as
+// the literal, when the class's simple name means another class.
+fn floating(
+    names: &mut Names,
+    class: &str,
+    name: Option<&str>,
+    negative: bool,
+    literal: Literal,
+) -> java::Expr {
+    let Some(name) = name else {
+        return java::Expr::Literal(literal);
+    };
+    let qualified = names.class(class);
+    if class.rsplit('/').next() == Some(qualified.as_str()) {
+        return java::Expr::Literal(literal);
+    }
+    let value = field(java::Expr::Type(Type::Class(qualified)), name);
+    match negative {
+        true => unary(java::UnaryOp::Neg, value),
+        false => value,
+    }
+}
@@ -0,0 +1,1347 @@
+use crate::cfg::{BlockId, Cfg, EdgeKind};
+use crate::descriptor::FieldType;
+use crate::dominators::DominatorTree;
+use crate::ir::{BinaryOp, Body, Const, Expr, Kind, Phi, Stmt, UnaryOp, Var};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Where a variable is defined or read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Location {
+    /// Parameters are defined on entry to the method.
+    Entry,
+    Phi {
+        block: BlockId,
+        index: usize,
+    },
+    Stmt {
+        block: BlockId,
+        index: usize,
+    },
+    Terminator(BlockId),
+}
+
+/// The definition and the reads of every variable of a body in SSA form.
+#[derive(Debug, Clone)]
+pub struct DefUse {
+    definitions: Vec<Option<Location>>,
+    uses: Vec<Vec<Location>>,
+}
+
+impl DefUse {
+    pub fn new(body: &Body) -> DefUse {
+        let count = body.variables.len();
+        let mut definitions = vec![None; count];
+        let mut uses = vec![Vec::new(); count];
+        for parameter in &body.parameters {
+            definitions[parameter.0] = Some(Location::Entry);
+        }
+        for (id, block) in body.blocks.iter().enumerate() {
+            for (index, phi) in block.phis.iter().enumerate() {
+                let location = Location::Phi { block: id, index };
+                definitions[phi.target.0] = Some(location);
+                for (_, source) in &phi.sources {
+                    uses[source.0].push(location);
+                }
+            }
+            for (index, stmt) in block.stmts.iter().enumerate() {
+                let location = Location::Stmt { block: id, index };
+                if let Some(var) = stmt.defined_var() {
+                    definitions[var.0] = Some(location);
+                }
+                for expr in stmt.uses() {
+                    add_uses(expr, location, &mut uses);
+                }
+            }
+            for expr in block.terminator.exprs() {
+                add_uses(expr, Location::Terminator(id), &mut uses);
+            }
+        }
+        DefUse { definitions, uses }
+    }
+
+    /// `None` for a variable nothing stores to.
+    pub fn definition(&self, var: Var) -> Option<Location> {
+        self.definitions[var.0]
+    }
+
+    /// Every read of the variable.
+    pub fn uses(&self, var: Var) -> &[Location] {
+        &self.uses[var.0]
+    }
+}
+
+fn add_uses(expr: &Expr, location: Location, uses: &mut [Vec<Location>]) {
+    expr.walk(&mut |expr| {
+        if let Expr::Var(var) = expr {
+            uses[var.0].push(location);
+        }
+    });
+}
+
+/// Puts a body into SSA form, then simplifies it and turns it back into
+/// ordinary variables.
+pub fn simplify(body: &mut Body) {
+    construct(body);
+    optimize(body);
+    destruct(body);
+}
+
+/// Runs the passes over a body in SSA form until none of them changes
+/// anything.
+pub fn optimize(body: &mut Body) {
+    loop {
+        let mut changed = propagate_copies(body);
+        changed |= propagate_constants(body);
+        changed |= eliminate_dead_stores(body);
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// Gives every store a new version of the variable it stores to, with phis
+/// joining versions at the iterated dominance frontiers of the stores.
+/// Handlers also get a phi for every variable stored to in the blocks they
+/// protect, with a source for each version the variable has there.
+///
+/// Versions keep the kind, slot and name of the variable they came from.
+/// Parameters keep their variables, as do reads that no store reaches.
+pub fn construct(body: &mut Body) {
+    let dominators = DominatorTree::dominators(&body.cfg);
+    let frontiers = dominators.frontiers();
+    let count = body.blocks.len();
+    let originals = body.variables.len();
+
+    let mut stored_in = vec![BTreeSet::new(); originals];
+    for id in 0..count {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        for stmt in &body.blocks[id].stmts {
+            if let Some(var) = stmt.defined_var() {
+                stored_in[var.0].insert(id);
+            }
+        }
+    }
+
+    let handlers: Vec<Vec<BlockId>> = (0..count).map(|id| handlers(&body.cfg, id)).collect();
+    let mut phi_vars: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); count];
+    for (var, blocks) in stored_in.iter().enumerate() {
+        let mut worklist: Vec<BlockId> = blocks.iter().copied().collect();
+        let mut placed = BTreeSet::new();
+        while let Some(id) = worklist.pop() {
+            for join in frontiers[id].iter().chain(&handlers[id]) {
+                if placed.insert(*join) {
+                    phi_vars[*join].insert(var);
+                    if !blocks.contains(join) {
+                        worklist.push(*join);
+                    }
+                }
+            }
+        }
+    }
+    let phi_vars: Vec<Vec<usize>> = phi_vars
+        .into_iter()
+        .map(|vars| vars.into_iter().collect())
+        .collect();
+    for (id, vars) in phi_vars.iter().enumerate() {
+        body.blocks[id].phis = vars
+            .iter()
+            .map(|var| Phi {
+                target: Var(*var),
+                sources: Vec::new(),
+            })
+            .collect();
+    }
+
+    enum Step {
+        Enter(BlockId),
+        Leave(Vec<usize>),
+    }
+    let Body {
+        blocks, variables, ..
+    } = body;
+    let mut versions: Vec<Vec<Var>> = vec![Vec::new(); originals];
+    let current = |versions: &Vec<Vec<Var>>, var: usize| *versions[var].last().unwrap_or(&Var(var));
+    let mut new_version = |versions: &mut Vec<Vec<Var>>, var: usize| {
+        variables.push(variables[var].clone());
+        let version = Var(variables.len() - 1);
+        versions[var].push(version);
+        version
+    };
+
+    let mut steps = vec![Step::Enter(dominators.root())];
+    while let Some(step) = steps.pop() {
+        let id = match step {
+            Step::Enter(id) => id,
+            Step::Leave(pushed) => {
+                for var in pushed {
+                    versions[var].pop();
+                }
+                continue;
+            }
+        };
+        let mut pushed = Vec::new();
+        for (phi, var) in blocks[id].phis.iter_mut().zip(&phi_vars[id]) {
+            phi.target = new_version(&mut versions, *var);
+            pushed.push(*var);
+        }
+
+        // the versions a handler can see: the one on entry and every store
+        let caught: BTreeSet<usize> = handlers[id]
+            .iter()
+            .flat_map(|handler| phi_vars[*handler].iter().copied())
+            .collect();
+        let mut seen: Vec<(usize, Var)> = caught
+            .iter()
+            .map(|var| (*var, current(&versions, *var)))
+            .collect();
+
+        let block = &mut blocks[id];
+        for stmt in &mut block.stmts {
+            for expr in stmt.uses_mut() {
+                rename(expr, &versions);
+            }
+            if let Stmt::Assign {
+                target: Expr::Var(var),
+                ..
+            } = stmt
+            {
+                let original = var.0;
+                *var = new_version(&mut versions, original);
+                pushed.push(original);
+                if caught.contains(&original) {
+                    seen.push((original, *var));
+                }
+            }
+        }
+        for expr in block.terminator.exprs_mut() {
+            rename(expr, &versions);
+        }
+
+        let mut successors = block.terminator.targets();
+        successors.sort_unstable();
+        successors.dedup();
+        for successor in successors {
+            for (phi, var) in blocks[successor].phis.iter_mut().zip(&phi_vars[successor]) {
+                phi.sources.push((id, current(&versions, *var)));
+            }
+        }
+        for handler in &handlers[id] {
+            for (phi, var) in blocks[*handler].phis.iter_mut().zip(&phi_vars[*handler]) {
+                for (original, version) in &seen {
+                    if original == var {
+                        phi.sources.push((id, *version));
+                    }
+                }
+            }
+        }
+
+        steps.push(Step::Leave(pushed));
+        for child in dominators.children(id).iter().rev() {
+            steps.push(Step::Enter(*child));
+        }
+    }
+}
+
+fn rename(expr: &mut Expr, versions: &[Vec<Var>]) {
+    expr.walk_mut(&mut |expr| {
+        if let Expr::Var(var) = expr {
+            if let Some(version) = versions[var.0].last() {
+                *var = *version;
+            }
+        }
+    });
+}
+
+// The handlers of the exception table entries covering a block.
+fn handlers(cfg: &Cfg, id: BlockId) -> Vec<BlockId> {
+    let mut handlers: Vec<BlockId> = cfg
+        .successors(id)
+        .filter(|edge| matches!(edge.kind, EdgeKind::Exceptional { .. }))
+        .map(|edge| edge.to)
+        .collect();
+    handlers.sort_unstable();
+    handlers.dedup();
+    handlers
+}
+
+fn is_exceptional(cfg: &Cfg, from: BlockId, to: BlockId) -> bool {
+    cfg.successors(from)
+        .any(|edge| edge.to == to && matches!(edge.kind, EdgeKind::Exceptional { .. }))
+}
+
+// The phi sources coming in through exception edges.
+fn exceptional_sources(body: &Body) -> HashSet<Var> {
+    let mut sources = HashSet::new();
+    for (id, block) in body.blocks.iter().enumerate() {
+        for phi in &block.phis {
+            for (predecessor, source) in &phi.sources {
+                if is_exceptional(&body.cfg, *predecessor, id) {
+                    sources.insert(*source);
+                }
+            }
+        }
+    }
+    sources
+}
+
+// Follows a substitution to its end, `None` if it goes round in a circle.
+fn resolve(substitution: &HashMap<Var, Var>, var: Var) -> Option<Var> {
+    let mut current = var;
+    for _ in 0..=substitution.len() {
+        match substitution.get(&current) {
+            Some(next) => current = *next,
+            None => return Some(current),
+        }
+    }
+    None
+}
+
+// Replaces reads of the variables in `substitution`, phi sources included
+// unless they come through an exception edge and `exceptional` is false.
+fn substitute(body: &mut Body, substitution: &HashMap<Var, Var>, exceptional: bool) {
+    let mut replace = |expr: &mut Expr| {
+        if let Expr::Var(var) = expr {
+            if let Some(replacement) = substitution.get(var) {
+                *var = *replacement;
+            }
+        }
+    };
+    for id in 0..body.blocks.len() {
+        for phi_index in 0..body.blocks[id].phis.len() {
+            for source_index in 0..body.blocks[id].phis[phi_index].sources.len() {
+                let (predecessor, source) = body.blocks[id].phis[phi_index].sources[source_index];
+                if let Some(replacement) = substitution.get(&source) {
+                    if exceptional || !is_exceptional(&body.cfg, predecessor, id) {
+                        body.blocks[id].phis[phi_index].sources[source_index].1 = *replacement;
+                    }
+                }
+            }
+        }
+        let block = &mut body.blocks[id];
+        for stmt in &mut block.stmts {
+            for expr in stmt.uses_mut() {
+                expr.walk_mut(&mut replace);
+            }
+        }
+        for expr in block.terminator.exprs_mut() {
+            expr.walk_mut(&mut replace);
+        }
+    }
+}
+
+/// Removes phis whose sources are all the same variable and copies into
+/// temporaries, reading the copied variable directly instead.
+///
+/// Copies of a local are only propagated when nothing else is stored in
+/// that local, so a read doesn't move past a store to the variable it
+/// reads, which would keep the versions from being merged back into one
+/// variable. Copies a handler's phi reads are kept, since they mark where
+/// the value changes inside the protected code.
+pub fn propagate_copies(body: &mut Body) -> bool {
+    let mut trivial = HashMap::new();
+    for block in &body.blocks {
+        for phi in &block.phis {
+            let sources: BTreeSet<Var> = phi
+                .sources
+                .iter()
+                .map(|(_, source)| *source)
+                .filter(|source| *source != phi.target)
+                .collect();
+            if let [source] = sources.into_iter().collect::<Vec<_>>()[..] {
+                trivial.insert(phi.target, source);
+            }
+        }
+    }
+    let trivial: HashMap<Var, Var> = trivial
+        .keys()
+        .filter_map(|var| Some((*var, resolve(&trivial, *var)?)))
+        .collect();
+    for block in &mut body.blocks {
+        block.phis.retain(|phi| !trivial.contains_key(&phi.target));
+    }
+    substitute(body, &trivial, true);
+
+    let mut stores: HashMap<(Option<u16>, Kind), usize> = HashMap::new();
+    for parameter in &body.parameters {
+        let variable = body.variable(*parameter);
+        *stores.entry((variable.slot, variable.kind)).or_default() += 1;
+    }
+    for block in &body.blocks {
+        for var in block
+            .phis
+            .iter()
+            .map(|phi| phi.target)
+            .chain(block.stmts.iter().filter_map(Stmt::defined_var))
+        {
+            let variable = body.variable(var);
+            *stores.entry((variable.slot, variable.kind)).or_default() += 1;
+        }
+    }
+    let exceptional = exceptional_sources(body);
+    let mut copies = HashMap::new();
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            let Stmt::Assign {
+                target: Expr::Var(target),
+                value: Expr::Var(source),
+            } = stmt
+            else {
+                continue;
+            };
+            let source_variable = body.variable(*source);
+            let reassigned = source_variable.slot.is_some()
+                && stores
+                    .get(&(source_variable.slot, source_variable.kind))
+                    .is_some_and(|count| *count > 1);
+            if target != source
+                && body.variable(*target).slot.is_none()
+                && !exceptional.contains(target)
+                && !reassigned
+            {
+                copies.insert(*target, *source);
+            }
+        }
+    }
+    let copies: HashMap<Var, Var> = copies
+        .keys()
+        .filter_map(|var| Some((*var, resolve(&copies, *var)?)))
+        .collect();
+    for block in &mut body.blocks {
+        block.stmts.retain(|stmt| {
+            !stmt
+                .defined_var()
+                .is_some_and(|var| copies.contains_key(&var))
+        });
+    }
+    substitute(body, &copies, false);
+
+    !trivial.is_empty() || !copies.is_empty()
+}
+
+/// Replaces reads of temporaries that hold a constant with the constant, and
+/// folds integer arithmetic on constants.
+pub fn propagate_constants(body: &mut Body) -> bool {
+    let mut constants = HashMap::new();
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            if let Stmt::Assign {
+                target: Expr::Var(var),
+                value: Expr::Const(constant),
+            } = stmt
+            {
+                if body.variable(*var).slot.is_none() {
+                    constants.insert(*var, constant.clone());
+                }
+            }
+        }
+    }
+
+    let mut changed = false;
+    let mut replace = |expr: &mut Expr| {
+        if let Expr::Var(var) = expr {
+            if let Some(constant) = constants.get(var) {
+                *expr = Expr::Const(constant.clone());
+                changed = true;
+            }
+        }
+        if let Some(folded) = fold(expr) {
+            *expr = Expr::Const(folded);
+            changed = true;
+        }
+    };
+    for block in &mut body.blocks {
+        for stmt in &mut block.stmts {
+            for expr in stmt.uses_mut() {
+                expr.walk_mut(&mut replace);
+            }
+        }
+        for expr in block.terminator.exprs_mut() {
+            expr.walk_mut(&mut replace);
+        }
+    }
+    changed
+}
+
+// https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.17
+fn fold(expr: &Expr) -> Option<Const> {
+    match expr {
+        Expr::Unary {
+            op: UnaryOp::Neg,
+            operand,
+        } => match **operand {
+            Expr::Const(Const::Int(value)) => Some(Const::Int(value.wrapping_neg())),
+            Expr::Const(Const::Long(value)) => Some(Const::Long(value.wrapping_neg())),
+            _ => None,
+        },
+        Expr::Binary { op, left, right } => match (&**left, &**right) {
+            (Expr::Const(Const::Int(left)), Expr::Const(Const::Int(right))) => {
+                let value = match op {
+                    BinaryOp::Add => left.wrapping_add(*right),
+                    BinaryOp::Sub => left.wrapping_sub(*right),
+                    BinaryOp::Mul => left.wrapping_mul(*right),
+                    BinaryOp::Div if *right != 0 => left.wrapping_div(*right),
+                    BinaryOp::Rem if *right != 0 => left.wrapping_rem(*right),
+                    BinaryOp::Div | BinaryOp::Rem => return None,
+                    BinaryOp::Shl => left.wrapping_shl(*right as u32),
+                    BinaryOp::Shr => left.wrapping_shr(*right as u32),
+                    BinaryOp::Ushr => (*left as u32).wrapping_shr(*right as u32) as i32,
+                    BinaryOp::And => left & right,
+                    BinaryOp::Or => left | right,
+                    BinaryOp::Xor => left ^ right,
+                };
+                Some(Const::Int(value))
+            }
+            (Expr::Const(Const::Long(left)), Expr::Const(Const::Long(right))) => {
+                let value = match op {
+                    BinaryOp::Add => left.wrapping_add(*right),
+                    BinaryOp::Sub => left.wrapping_sub(*right),
+                    BinaryOp::Mul => left.wrapping_mul(*right),
+                    BinaryOp::Div if *right != 0 => left.wrapping_div(*right),
+                    BinaryOp::Rem if *right != 0 => left.wrapping_rem(*right),
+                    BinaryOp::And => left & right,
+                    BinaryOp::Or => left | right,
+                    BinaryOp::Xor => left ^ right,
+                    _ => return None,
+                };
+                Some(Const::Long(value))
+            }
+            (Expr::Const(Const::Long(left)), Expr::Const(Const::Int(right))) => {
+                let value = match op {
+                    BinaryOp::Shl => left.wrapping_shl(*right as u32),
+                    BinaryOp::Shr => left.wrapping_shr(*right as u32),
+                    BinaryOp::Ushr => (*left as u64).wrapping_shr(*right as u32) as i64,
+                    _ => return None,
+                };
+                Some(Const::Long(value))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Removes stores nothing reads and phis no statement depends on. A store of
+/// a call or object creation is kept as an expression statement, and the
+/// variable a handler's exception is stored in is always kept, as are the
+/// stores of catch-any handlers: a `finally` block javac copied there is
+/// only matched against its other copies by
+/// [`find_try_regions`](crate::exceptions::find_try_regions) later, and the
+/// stores in it are dead whenever the variables aren't read after a
+/// rethrow.
+pub fn eliminate_dead_stores(body: &mut Body) -> bool {
+    let finally_code = finally_code(body);
+    let mut changed = false;
+    loop {
+        // phis are live when a statement reads them, directly or through
+        // other phis
+        let mut live = HashSet::new();
+        let mut worklist = Vec::new();
+        for block in &body.blocks {
+            for expr in block
+                .stmts
+                .iter()
+                .flat_map(Stmt::uses)
+                .chain(block.terminator.exprs())
+            {
+                expr.walk(&mut |expr| {
+                    if let Expr::Var(var) = expr {
+                        if live.insert(*var) {
+                            worklist.push(*var);
+                        }
+                    }
+                });
+            }
+        }
+        let phis: HashMap<Var, &Phi> = body
+            .blocks
+            .iter()
+            .flat_map(|block| &block.phis)
+            .map(|phi| (phi.target, phi))
+            .collect();
+        while let Some(var) = worklist.pop() {
+            if let Some(phi) = phis.get(&var) {
+                for (_, source) in &phi.sources {
+                    if live.insert(*source) {
+                        worklist.push(*source);
+                    }
+                }
+            }
+        }
+
+        let mut removed = false;
+        for (id, block) in body.blocks.iter_mut().enumerate() {
+            let before = block.phis.len();
+            block.phis.retain(|phi| live.contains(&phi.target));
+            removed |= block.phis.len() != before;
+
+            let stmts = std::mem::take(&mut block.stmts);
+            for stmt in stmts {
+                match stmt {
+                    Stmt::Assign {
+                        target: Expr::Var(var),
+                        value,
+                    } if !live.contains(&var)
+                        && value != Expr::CaughtException
+                        && !finally_code.contains(&id) =>
+                    {
+                        match value {
+                            Expr::Invoke { .. } | Expr::InvokeDynamic { .. } | Expr::New { .. } => {
+                                block.stmts.push(Stmt::Expr(value));
+                                removed = true;
+                            }
+                            value if value.has_side_effects() => {
+                                block.stmts.push(Stmt::assign(Expr::Var(var), value));
+                            }
+                            _ => removed = true,
+                        }
+                    }
+                    stmt => block.stmts.push(stmt),
+                }
+            }
+        }
+        if !removed {
+            return changed;
+        }
+        changed = true;
+    }
+}
+
+// The blocks the catch-any handlers dominate.
+fn finally_code(body: &Body) -> HashSet<BlockId> {
+    let handlers: Vec<BlockId> = body
+        .cfg
+        .exception_table()
+        .iter()
+        .filter(|entry| entry.catch_type == 0)
+        .filter_map(|entry| body.cfg.block_at(entry.handler_pc as u32))
+        .collect();
+    if handlers.is_empty() {
+        return HashSet::new();
+    }
+    let dominators = DominatorTree::dominators(&body.cfg);
+    (0..body.blocks.len())
+        .filter(|id| {
+            handlers
+                .iter()
+                .any(|handler| dominators.dominates(*handler, *id))
+        })
+        .collect()
+}
+
+/// Takes a body out of SSA form, merging versions back into as few
+/// variables as their live ranges allow.
+///
+/// The versions a phi joins share one variable when none of them are live
+/// at the same time, and copies between variables that don't interfere are
+/// coalesced. Other phis become copies into a fresh variable at the end of
+/// each predecessor, or after each store for a handler. Finally the
+/// versions of each local variable slot that don't interfere are merged.
+/// Variables in different slots or of different types are never merged, nor
+/// are two parameters.
+pub fn destruct(body: &mut Body) {
+    let count = body.blocks.len();
+    let variables = body.variables.len();
+    let liveness = Liveness::new(body);
+    let mut classes = Classes::new(body, &liveness.interference);
+
+    let mut split = Vec::new();
+    for id in 0..count {
+        for phi in std::mem::take(&mut body.blocks[id].phis) {
+            let members: Vec<Var> = std::iter::once(phi.target)
+                .chain(phi.sources.iter().map(|(_, source)| *source))
+                .collect();
+            if !classes.union_all(&members) {
+                split.push((id, phi));
+            }
+        }
+    }
+    coalesce_copies(body, &mut classes);
+    let mut by_slot: HashMap<(u16, Kind), Vec<Var>> = HashMap::new();
+    for var in 0..variables {
+        if let Some(slot) = body.variables[var].slot {
+            by_slot
+                .entry((slot, body.variables[var].kind))
+                .or_default()
+                .push(Var(var));
+        }
+    }
+    let mut slots: Vec<_> = by_slot.into_iter().collect();
+    slots.sort_unstable_by_key(|(slot, _)| slot.0);
+    for (_, vars) in slots {
+        for i in 0..vars.len() {
+            for j in 0..i {
+                classes.union_all(&[vars[j], vars[i]]);
+            }
+        }
+    }
+
+    // the phis that couldn't be merged get copies through a fresh variable
+    let mut joined_vars = Vec::new();
+    let mut at_start: Vec<Vec<Stmt>> = vec![Vec::new(); count];
+    let mut at_end: Vec<Vec<Stmt>> = vec![Vec::new(); count];
+    let mut after_store: Vec<Vec<(Var, Stmt)>> = vec![Vec::new(); count];
+    for (id, phi) in split {
+        let joined = body.new_variable(body.variable(phi.target).clone());
+        joined_vars.push((phi.target, joined));
+        at_start[id].push(Stmt::assign(Expr::Var(phi.target), Expr::Var(joined)));
+        for (predecessor, source) in phi.sources {
+            let copy = Stmt::assign(Expr::Var(joined), Expr::Var(source));
+            if is_exceptional(&body.cfg, predecessor, id) {
+                after_store[predecessor].push((source, copy));
+            } else {
+                at_end[predecessor].push(copy);
+            }
+        }
+    }
+    for id in 0..count {
+        let old = std::mem::take(&mut body.blocks[id].stmts);
+        let mut stmts = Vec::with_capacity(old.len());
+        let mut old = old.into_iter().peekable();
+        // the handler's exception stays first
+        if let Some(Stmt::Assign {
+            value: Expr::CaughtException,
+            ..
+        }) = old.peek()
+        {
+            stmts.extend(old.next());
+        }
+        let entry: Vec<Stmt> = std::mem::take(&mut at_start[id]);
+        let sequence: Vec<Stmt> = entry.into_iter().chain(old).collect();
+        let stored: HashSet<Var> = stmts
+            .iter()
+            .chain(&sequence)
+            .filter_map(Stmt::defined_var)
+            .collect();
+        let mut after = std::mem::take(&mut after_store[id]);
+        for (source, copy) in &after {
+            if !stored.contains(source) {
+                stmts.push(copy.clone());
+            }
+        }
+        after.retain(|(source, _)| stored.contains(source));
+        for stmt in sequence {
+            let defined = stmt.defined_var();
+            stmts.push(stmt);
+            for (source, copy) in &after {
+                if Some(*source) == defined {
+                    stmts.push(copy.clone());
+                }
+            }
+        }
+        stmts.append(&mut at_end[id]);
+        body.blocks[id].stmts = stmts;
+    }
+
+    let representatives = classes.representatives(body);
+    rewrite(body, &representatives);
+
+    // the variable a split phi was joined in can often replace its target
+    if !joined_vars.is_empty() {
+        let liveness = Liveness::new(body);
+        let mut classes = Classes::new(body, &liveness.interference);
+        for (target, joined) in joined_vars {
+            classes.union_all(&[representatives[target.0], joined]);
+        }
+        let representatives = classes.representatives(body);
+        rewrite(body, &representatives);
+    }
+}
+
+fn coalesce_copies(body: &Body, classes: &mut Classes) {
+    for block in &body.blocks {
+        for stmt in &block.stmts {
+            if let Stmt::Assign {
+                target: Expr::Var(target),
+                value: Expr::Var(source),
+            } = stmt
+            {
+                classes.union_all(&[*target, *source]);
+            }
+        }
+    }
+}
+
+// Replaces every variable with the one standing for its class and drops the
+// copies that leaves from a variable to itself.
+fn rewrite(body: &mut Body, representatives: &[Var]) {
+    let mut replace = |expr: &mut Expr| {
+        if let Expr::Var(var) = expr {
+            if let Some(representative) = representatives.get(var.0) {
+                *var = *representative;
+            }
+        }
+    };
+    for block in &mut body.blocks {
+        for stmt in &mut block.stmts {
+            for expr in stmt.exprs_mut() {
+                expr.walk_mut(&mut replace);
+            }
+        }
+        for expr in block.terminator.exprs_mut() {
+            expr.walk_mut(&mut replace);
+        }
+        block.stmts.retain(|stmt| {
+            !matches!(stmt, Stmt::Assign {
+                target: Expr::Var(target),
+                value: Expr::Var(source),
+            } if target == source)
+        });
+    }
+}
+
+// A set of variables as a bit vector.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct VarSet(Vec<u64>);
+
+impl VarSet {
+    fn new(count: usize) -> VarSet {
+        VarSet(vec![0; count.div_ceil(64)])
+    }
+
+    fn insert(&mut self, var: Var) {
+        self.0[var.0 / 64] |= 1 << (var.0 % 64);
+    }
+
+    fn remove(&mut self, var: Var) {
+        self.0[var.0 / 64] &= !(1 << (var.0 % 64));
+    }
+
+    fn union_with(&mut self, other: &VarSet) {
+        for (word, other) in self.0.iter_mut().zip(&other.0) {
+            *word |= other;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = Var> + '_ {
+        self.0.iter().enumerate().flat_map(|(index, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| Var(index * 64 + bit))
+        })
+    }
+}
+
+// Which variables of an SSA body are live at the same time.
+//
+// A phi reads its sources at the end of their predecessors, except that a
+// handler's phi reads whichever of a protected block's versions was the
+// latest when the exception was thrown: each is live from its store until
+// the next one, and the version on entry until the first.
+struct Liveness {
+    interference: Vec<HashSet<usize>>,
+}
+
+impl Liveness {
+    fn new(body: &Body) -> Liveness {
+        let count = body.blocks.len();
+        let variables = body.variables.len();
+        let cfg = &body.cfg;
+
+        let mut phi_targets = vec![VarSet::new(variables); count];
+        // sources read at the end of each block, and for each store of a
+        // handler's source the version it replaces
+        let mut read_at_end = vec![VarSet::new(variables); count];
+        let mut replaces: Vec<HashMap<Var, Vec<Var>>> = vec![HashMap::new(); count];
+        for (id, block) in body.blocks.iter().enumerate() {
+            for phi in &block.phis {
+                phi_targets[id].insert(phi.target);
+                let mut by_predecessor: HashMap<BlockId, Vec<Var>> = HashMap::new();
+                for (predecessor, source) in &phi.sources {
+                    by_predecessor
+                        .entry(*predecessor)
+                        .or_default()
+                        .push(*source);
+                }
+                for (predecessor, sources) in by_predecessor {
+                    if !is_exceptional(cfg, predecessor, id) {
+                        for source in sources {
+                            read_at_end[predecessor].insert(source);
+                        }
+                        continue;
+                    }
+                    let position = |var: &Var| {
+                        body.blocks[predecessor]
+                            .stmts
+                            .iter()
+                            .position(|stmt| stmt.defined_var() == Some(*var))
+                    };
+                    let mut sources = sources;
+                    sources.sort_by_key(position);
+                    sources.dedup();
+                    for pair in sources.windows(2) {
+                        replaces[predecessor]
+                            .entry(pair[1])
+                            .or_default()
+                            .push(pair[0]);
+                    }
+                    read_at_end[predecessor].insert(*sources.last().expect("phis have sources"));
+                }
+            }
+        }
+
+        let handlers: Vec<Vec<BlockId>> = (0..count).map(|id| handlers(cfg, id)).collect();
+        let normal: Vec<Vec<BlockId>> = body
+            .blocks
+            .iter()
+            .map(|block| block.terminator.targets())
+            .collect();
+
+        let mut live_in = vec![VarSet::new(variables); count];
+        let entry_sets = |live_in: &[VarSet], id: BlockId| {
+            let mut live_out = read_at_end[id].clone();
+            let mut throughout = VarSet::new(variables);
+            for successor in &normal[id] {
+                let mut live = live_in[*successor].clone();
+                for target in phi_targets[*successor].iter() {
+                    live.remove(target);
+                }
+                live_out.union_with(&live);
+            }
+            for handler in &handlers[id] {
+                let mut live = live_in[*handler].clone();
+                for target in phi_targets[*handler].iter() {
+                    live.remove(target);
+                }
+                throughout.union_with(&live);
+            }
+            live_out.union_with(&throughout);
+            (live_out, throughout)
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for id in (0..count).rev() {
+                let (live_out, throughout) = entry_sets(&live_in, id);
+                let live = scan(body, id, live_out, &throughout, &replaces[id], |_, _, _| {});
+                if live != live_in[id] {
+                    live_in[id] = live;
+                    changed = true;
+                }
+            }
+        }
+
+        let mut interference = vec![HashSet::new(); variables];
+        let mut interfere = |a: Var, b: Var| {
+            if a != b {
+                interference[a.0].insert(b.0);
+                interference[b.0].insert(a.0);
+            }
+        };
+        for id in 0..count {
+            let (live_out, throughout) = entry_sets(&live_in, id);
+            scan(
+                body,
+                id,
+                live_out,
+                &throughout,
+                &replaces[id],
+                |defined, live, copied| {
+                    for var in live.iter() {
+                        if Some(var) != copied {
+                            interfere(defined, var);
+                        }
+                    }
+                },
+            );
+            // phis are all defined together at the start of their block
+            for target in phi_targets[id].iter() {
+                for var in live_in[id].iter() {
+                    interfere(target, var);
+                }
+                for other in phi_targets[id].iter() {
+                    interfere(target, other);
+                }
+            }
+        }
+        if count > 0 {
+            for parameter in &body.parameters {
+                for var in live_in[0].iter() {
+                    interfere(*parameter, var);
+                }
+                for other in &body.parameters {
+                    interfere(*parameter, *other);
+                }
+            }
+        }
+
+        Liveness { interference }
+    }
+}
+
+// Walks a block backwards from what is live at its end, calling `on_store`
+// with each stored variable, what is live just after the store, and the
+// variable it copies if it's a copy. Gives what is live at the start of the
+// block, after its phis.
+fn scan(
+    body: &Body,
+    id: BlockId,
+    mut live: VarSet,
+    throughout: &VarSet,
+    replaces: &HashMap<Var, Vec<Var>>,
+    mut on_store: impl FnMut(Var, &VarSet, Option<Var>),
+) -> VarSet {
+    let read = |expr: &Expr, live: &mut VarSet| {
+        expr.walk(&mut |expr| {
+            if let Expr::Var(var) = expr {
+                live.insert(*var);
+            }
+        })
+    };
+    let block = &body.blocks[id];
+    for expr in block.terminator.exprs() {
+        read(expr, &mut live);
+    }
+    for stmt in block.stmts.iter().rev() {
+        if let Some(defined) = stmt.defined_var() {
+            let copied = match stmt {
+                Stmt::Assign {
+                    value: Expr::Var(source),
+                    ..
+                } => Some(*source),
+                _ => None,
+            };
+            on_store(defined, &live, copied);
+            live.remove(defined);
+            for replaced in replaces.get(&defined).into_iter().flatten() {
+                live.insert(*replaced);
+            }
+        }
+        for expr in stmt.uses() {
+            read(expr, &mut live);
+        }
+        live.union_with(throughout);
+    }
+    live
+}
+
+// Union-find over variables, tracking which merged groups interfere.
+struct Classes {
+    parent: Vec<usize>,
+    interference: Vec<HashSet<usize>>,
+    // the slot, whether a parameter is in it, and the kind and type of each
+    // class
+    slot: Vec<Option<u16>>,
+    parameter: Vec<bool>,
+    kind: Vec<Kind>,
+    field_type: Vec<Option<FieldType>>,
+}
+
+impl Classes {
+    fn new(body: &Body, interference: &[HashSet<usize>]) -> Classes {
+        let mut parameter = vec![false; body.variables.len()];
+        for var in &body.parameters {
+            parameter[var.0] = true;
+        }
+        Classes {
+            parent: (0..body.variables.len()).collect(),
+            interference: interference.to_vec(),
+            slot: body
+                .variables
+                .iter()
+                .map(|variable| variable.slot)
+                .collect(),
+            parameter,
+            kind: body
+                .variables
+                .iter()
+                .map(|variable| variable.kind)
+                .collect(),
+            field_type: body
+                .variables
+                .iter()
+                .map(|variable| variable.field_type.clone())
+                .collect(),
+        }
+    }
+
+    fn find(&mut self, mut var: usize) -> usize {
+        while self.parent[var] != var {
+            self.parent[var] = self.parent[self.parent[var]];
+            var = self.parent[var];
+        }
+        var
+    }
+
+    fn interferes(&mut self, a: usize, b: usize) -> bool {
+        let neighbours: Vec<usize> = self.interference[a].iter().copied().collect();
+        neighbours.into_iter().any(|var| self.find(var) == b)
+    }
+
+    // Merges the classes of all the variables if no two of them interfere
+    // and they can share a variable.
+    fn union_all(&mut self, vars: &[Var]) -> bool {
+        let mut roots: Vec<usize> = vars.iter().map(|var| self.find(var.0)).collect();
+        roots.sort_unstable();
+        roots.dedup();
+        for (i, a) in roots.iter().enumerate() {
+            for b in &roots[..i] {
+                let slots_differ =
+                    matches!((self.slot[*a], self.slot[*b]), (Some(x), Some(y)) if x != y);
+                if slots_differ
+                    || (self.parameter[*a] && self.parameter[*b])
+                    || self.kind[*a] != self.kind[*b]
+                    || matches!((&self.field_type[*a], &self.field_type[*b]), (Some(x), Some(y)) if x != y)
+                    || self.interferes(*a, *b)
+                {
+                    return false;
+                }
+            }
+        }
+        let root = roots[0];
+        for other in roots.into_iter().skip(1) {
+            self.parent[other] = root;
+            let merged = std::mem::take(&mut self.interference[other]);
+            self.interference[root].extend(merged);
+            self.slot[root] = self.slot[root].or(self.slot[other]);
+            self.parameter[root] |= self.parameter[other];
+            if self.field_type[root].is_none() {
+                self.field_type[root] = self.field_type[other].take();
+            }
+        }
+        true
+    }
+
+    // The variable standing for each class: its parameter, or else its
+    // first variable with a slot, or else its first variable.
+    fn representatives(&mut self, body: &Body) -> Vec<Var> {
+        let count = self.parent.len();
+        let mut chosen: Vec<Option<usize>> = vec![None; count];
+        let rank = |var: usize| {
+            if body.parameters.contains(&Var(var)) {
+                0
+            } else if body.variables[var].slot.is_some() {
+                1
+            } else {
+                2
+            }
+        };
+        for var in 0..count {
+            let root = self.find(var);
+            if chosen[root].is_none_or(|current| rank(var) < rank(current)) {
+                chosen[root] = Some(var);
+            }
+        }
+        (0..count)
+            .map(|var| {
+                let root = self.find(var);
+                Var(chosen[root].expect("every class has a member"))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Assembler;
+    use crate::builder::ConstantPoolBuilder;
+    use crate::instruction::{self, Instruction, Opcode, Operand};
+    use crate::ir::Terminator;
+
+    fn lift(asm: &Assembler, pool: &ConstantPoolBuilder, descriptor: &str) -> Body {
+        let code = asm.assemble(0, 0).unwrap();
+        let instructions = instruction::decode(&code.code).unwrap();
+        let cfg = Cfg::new(instructions, &code.exception_table, code.code.len() as u32).unwrap();
+        crate::lift::lift(cfg, pool.pool(), &[], descriptor, true).unwrap()
+    }
+
+    fn local(asm: &mut Assembler, opcode: Opcode, index: u16) {
+        asm.emit(Instruction::new(opcode, Operand::Local(index)));
+    }
+
+    fn returned(body: &Body, id: BlockId) -> &Expr {
+        match &body.blocks[id].terminator {
+            Terminator::Return(Some(expr)) => expr,
+            terminator => panic!("{terminator:?}"),
+        }
+    }
+
+    fn stored(stmt: &Stmt) -> (Var, &Expr) {
+        match stmt {
+            Stmt::Assign {
+                target: Expr::Var(var),
+                value,
+            } => (*var, value),
+            stmt => panic!("{stmt:?}"),
+        }
+    }
+
+    #[test]
+    fn phis_join_stores_at_the_frontier() {
+        let pool = ConstantPoolBuilder::new();
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, other);
+        asm.op(Opcode::Iconst1);
+        local(&mut asm, Opcode::Istore, 1);
+        asm.branch(Opcode::Goto, join);
+        asm.place(other);
+        asm.op(Opcode::Iconst2);
+        local(&mut asm, Opcode::Istore, 1);
+        asm.place(join);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.op(Opcode::Ireturn);
+        let mut body = lift(&asm, &pool, "(I)I");
+        construct(&mut body);
+
+        let (one, value) = stored(&body.blocks[1].stmts[0]);
+        assert_eq!(value, &Expr::int(1));
+        let (two, value) = stored(&body.blocks[2].stmts[0]);
+        assert_eq!(value, &Expr::int(2));
+        assert_ne!(one, two);
+        assert!(body.blocks[..3].iter().all(|block| block.phis.is_empty()));
+        let [phi] = &body.blocks[3].phis[..] else {
+            panic!("{:?}", body.blocks[3].phis);
+        };
+        assert_eq!(phi.sources, [(1, one), (2, two)]);
+        assert_eq!(returned(&body, 3), &Expr::var(phi.target));
+        // versions keep the slot of the local they came from
+        for var in [one, two, phi.target] {
+            assert_eq!(body.variable(var).slot, Some(1));
+        }
+        assert_eq!(body.parameters, [Var(0)]);
+    }
+
+    #[test]
+    fn loops_get_a_phi_in_their_header() {
+        let pool = ConstantPoolBuilder::new();
+        let mut asm = Assembler::new();
+        let (header, end) = (asm.new_label(), asm.new_label());
+        asm.op(Opcode::Iconst0);
+        local(&mut asm, Opcode::Istore, 1);
+        asm.place(header);
+        local(&mut asm, Opcode::Iload, 1);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::IfIcmpge, end);
+        asm.emit(Instruction::new(
+            Opcode::Iinc,
+            Operand::Iinc { index: 1, value: 1 },
+        ));
+        asm.branch(Opcode::Goto, header);
+        asm.place(end);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.op(Opcode::Ireturn);
+        let mut body = lift(&asm, &pool, "(I)I");
+        construct(&mut body);
+
+        let (initial, _) = stored(&body.blocks[0].stmts[0]);
+        let (next, value) = stored(&body.blocks[2].stmts[0]);
+        let [phi] = &body.blocks[1].phis[..] else {
+            panic!("{:?}", body.blocks[1].phis);
+        };
+        assert_eq!(phi.sources, [(0, initial), (2, next)]);
+        assert_eq!(
+            value,
+            &Expr::binary(BinaryOp::Add, Expr::var(phi.target), Expr::int(1))
+        );
+        assert!(body.blocks[3].phis.is_empty());
+        assert_eq!(returned(&body, 3), &Expr::var(phi.target));
+    }
+
+    #[test]
+    fn copies_into_temporaries_are_read_through() {
+        // both arms leave the second parameter on the stack for the join
+        let pool = ConstantPoolBuilder::new();
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, other);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.branch(Opcode::Goto, join);
+        asm.place(other);
+        local(&mut asm, Opcode::Iload, 1);
+        asm.place(join);
+        asm.op(Opcode::Ireturn);
+        let mut body = lift(&asm, &pool, "(II)I");
+        construct(&mut body);
+        assert_eq!(body.blocks[3].phis.len(), 1);
+
+        let parameter = body.parameters[1];
+        assert!(propagate_copies(&mut body));
+        assert!(body.blocks.iter().all(|block| block.stmts.is_empty()));
+        // which leaves a phi with the same source on every side
+        assert_eq!(
+            body.blocks[3].phis[0].sources,
+            [(1, parameter), (2, parameter)]
+        );
+        assert!(propagate_copies(&mut body));
+        assert!(body.blocks[3].phis.is_empty());
+        assert_eq!(returned(&body, 3), &Expr::var(parameter));
+        assert!(!propagate_copies(&mut body));
+    }
+
+    #[test]
+    fn constants_are_folded_into_their_reads() {
+        // the 5 waits on the stack across the branch in a temporary
+        let pool = ConstantPoolBuilder::new();
+        let mut asm = Assembler::new();
+        let join = asm.new_label();
+        asm.op(Opcode::Iconst5);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, join);
+        asm.place(join);
+        asm.op(Opcode::Iconst1);
+        asm.op(Opcode::Iadd);
+        asm.op(Opcode::Iconst2);
+        asm.op(Opcode::Imul);
+        asm.op(Opcode::Ireturn);
+        let mut body = lift(&asm, &pool, "(I)I");
+        construct(&mut body);
+
+        assert!(propagate_constants(&mut body));
+        assert_eq!(returned(&body, 1), &Expr::int(12));
+        assert!(eliminate_dead_stores(&mut body));
+        assert!(body.blocks[0].stmts.is_empty());
+        assert!(!propagate_constants(&mut body));
+    }
+
+    #[test]
+    fn dead_stores_keep_their_side_effects() {
+        let mut pool = ConstantPoolBuilder::new();
+        let next = pool.method_ref("Main", "next", "()I");
+        let mut asm = Assembler::new();
+        let (other, join) = (asm.new_label(), asm.new_label());
+        asm.emit(Instruction::new(
+            Opcode::Invokestatic,
+            Operand::Constant(next),
+        ));
+        local(&mut asm, Opcode::Istore, 1);
+        asm.op(Opcode::Iconst1);
+        local(&mut asm, Opcode::Istore, 2);
+        asm.op(Opcode::Iconst2);
+        local(&mut asm, Opcode::Istore, 2);
+        local(&mut asm, Opcode::Iload, 0);
+        asm.branch(Opcode::Ifeq, other);
+        asm.op(Opcode::Iconst3);
+        local(&mut asm, Opcode::Istore, 3);
+        asm.branch(Opcode::Goto, join);
+        asm.place(other);
+        asm.op(Opcode::Iconst4);
+        local(&mut asm, Opcode::Istore, 3);
+        asm.place(join);
+        local(&mut asm, Opcode::Iload, 2);
+        asm.op(Opcode::Ireturn);
+        let mut body = lift(&asm, &pool, "(I)I");
+        construct(&mut body);
+        assert_eq!(body.blocks[3].phis.len(), 1);
+
+        assert!(eliminate_dead_stores(&mut body));
+        let stmts = &body.blocks[0].stmts;
+        assert_eq!(stmts.len(), 2, "{stmts:?}");
+        assert!(
+            matches!(&stmts[0], Stmt::Expr(Expr::Invoke { name, .. }) if name == "next"),
+            "{stmts:?}"
+        );
+        let (two, value) = stored(&stmts[1]);
+        assert_eq!(value, &Expr::int(2));
+        assert!(body.blocks[1].stmts.is_empty());
+        assert!(body.blocks[2].stmts.is_empty());
+        assert!(body.blocks[3].phis.is_empty());
+        assert_eq!(returned(&body, 3), &Expr::var(two));
+        assert!(!eliminate_dead_stores(&mut body));
+    }
+}
@@ -0,0 +1,2844 @@
+use crate::cfg::BlockId;
+use crate::conditions::{self, negate};
+use crate::descriptor::FieldType;
+use crate::dominators::DominatorTree;
+use crate::exceptions::{self, CatchClause, Resource, TryRegion};
+use crate::infer;
+use crate::ir::{
+    Block, Body, Const, Expr, InvokeKind, JavaString, Kind, Pattern, Stmt, Terminator, UnaryOp,
+    Var, Variable,
+};
+use crate::loops::depth_first_numbering;
+use crate::switches::{PatternCase, PatternSwitches, SwitchMaps};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A label on a block, loop or switch, which `break` and `continue` name.
+/// Printing decides which labels need to be written out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Label(pub usize);
+
+/// A Java statement whose control flow is structured, holding the IR's
+/// expressions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Stmt(Stmt),
+    /// A local variable declaration, with its first value if it has one.
+    Declare {
+        var: Var,
+        value: Option<Expr>,
+    },
+    /// A `{ ... }` block, only there to be left with `break`.
+    Block {
+        label: Label,
+        body: Vec<Statement>,
+    },
+    If {
+        condition: Expr,
+        then: Vec<Statement>,
+        otherwise: Vec<Statement>,
+    },
+    While {
+        label: Label,
+        condition: Expr,
+        body: Vec<Statement>,
+    },
+    DoWhile {
+        label: Label,
+        body: Vec<Statement>,
+        condition: Expr,
+    },
+    For {
+        label: Label,
+        init: Vec<Statement>,
+        condition: Expr,
+        update: Vec<Stmt>,
+        body: Vec<Statement>,
+    },
+    Switch {
+        label: Label,
+        value: Expr,
+        cases: Vec<Case>,
+    },
+    /// A `try` statement, whose `finally` block is empty when it has none.
+    Try {
+        resources: Vec<Resource>,
+        body: Vec<Statement>,
+        catches: Vec<Catch>,
+        finally: Vec<Statement>,
+    },
+    Synchronized {
+        lock: Expr,
+        body: Vec<Statement>,
+    },
+    Break(Label),
+    Continue(Label),
+    Return(Option<Expr>),
+    Throw(Expr),
+    /// The value of the switch expression a case is in.
+    Yield(Expr),
+    /// An `assert` statement, with the message it fails with if it has one.
+    Assert {
+        condition: Expr,
+        message: Option<Expr>,
+    },
+}
+
+/// A group of switch labels and the statements they lead to, which fall
+/// through into the next group unless they end in a jump.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Case {
+    pub labels: Vec<CaseLabel>,
+    pub is_default: bool,
+    /// What has to hold as well for a pattern to match, after `when`.
+    pub guard: Option<Expr>,
+    pub body: Vec<Statement>,
+}
+
+/// What a `case` label matches.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaseLabel {
+    Const(Const),
+    /// An enum constant, which labels name without their class.
+    Enum(String),
+    Null,
+    /// A type pattern, or a record pattern deconstructing the type.
+    Pattern(FieldType, Pattern),
+}
+
+/// A `catch` clause. A multi-catch has more than one class, and a clause
+/// catching anything has none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Catch {
+    pub types: Vec<String>,
+    pub var: Var,
+    pub body: Vec<Statement>,
+}
+
+impl Statement {
+    /// The statement lists nested directly in this one, those of the switch
+    /// expressions and lambdas in its expressions last.
+    pub fn children(&self) -> Vec<&Vec<Statement>> {
+        let mut children = self.own_children();
+        for expr in self.exprs() {
+            nested_statements(expr, &mut children);
+        }
+        children
+    }
+
+    pub fn children_mut(&mut self) -> Vec<&mut Vec<Statement>> {
+        let (mut children, exprs) = self.split_mut();
+        for expr in exprs {
+            nested_statements_mut(expr, &mut children);
+        }
+        children
+    }
+
+    fn own_children(&self) -> Vec<&Vec<Statement>> {
+        match self {
+            Statement::Block { body, .. }
+            | Statement::While { body, .. }
+            | Statement::DoWhile { body, .. }
+            | Statement::Synchronized { body, .. } => vec![body],
+            Statement::For { init, body, .. } => vec![init, body],
+            Statement::If {
+                then, otherwise, ..
+            } => vec![then, otherwise],
+            Statement::Switch { cases, .. } => cases.iter().map(|case| &case.body).collect(),
+            Statement::Try {
+                body,
+                catches,
+                finally,
+                ..
+            } => std::iter::once(body)
+                .chain(catches.iter().map(|catch| &catch.body))
+                .chain([finally])
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // The statement lists nested directly in this one, and its
+    // expressions, in the same order as `own_children` and `exprs`.
+    fn split_mut(&mut self) -> (Vec<&mut Vec<Statement>>, Vec<&mut Expr>) {
+        match self {
+            Statement::Stmt(stmt) => (Vec::new(), stmt.exprs_mut()),
+            Statement::Declare { value, .. } => (Vec::new(), value.iter_mut().collect()),
+            Statement::Block { body, .. } => (vec![body], Vec::new()),
+            Statement::While {
+                condition, body, ..
+            }
+            | Statement::DoWhile {
+                condition, body, ..
+            } => (vec![body], vec![condition]),
+            Statement::Synchronized { lock, body } => (vec![body], vec![lock]),
+            Statement::For {
+                init,
+                condition,
+                update,
+                body,
+                ..
+            } => (
+                vec![init, body],
+                std::iter::once(condition)
+                    .chain(update.iter_mut().flat_map(Stmt::exprs_mut))
+                    .collect(),
+            ),
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => (vec![then, otherwise], vec![condition]),
+            Statement::Switch { value, cases, .. } => {
+                let mut children = Vec::new();
+                let mut exprs = vec![value];
+                for case in cases {
+                    exprs.extend(case.guard.as_mut());
+                    children.push(&mut case.body);
+                }
+                (children, exprs)
+            }
+            Statement::Try {
+                resources,
+                body,
+                catches,
+                finally,
+            } => (
+                std::iter::once(body)
+                    .chain(catches.iter_mut().map(|catch| &mut catch.body))
+                    .chain([finally])
+                    .collect(),
+                resources
+                    .iter_mut()
+                    .filter_map(|resource| resource.value.as_mut())
+                    .collect(),
+            ),
+            Statement::Return(Some(expr)) | Statement::Throw(expr) | Statement::Yield(expr) => {
+                (Vec::new(), vec![expr])
+            }
+            Statement::Assert { condition, message } => (
+                Vec::new(),
+                std::iter::once(condition).chain(message.as_mut()).collect(),
+            ),
+            Statement::Break(_) | Statement::Continue(_) | Statement::Return(None) => {
+                (Vec::new(), Vec::new())
+            }
+        }
+    }
+
+    /// The expressions of the statement itself, not of nested statements.
+    pub fn exprs(&self) -> Vec<&Expr> {
+        match self {
+            Statement::Stmt(stmt) => stmt.exprs(),
+            Statement::Declare { value, .. } => value.iter().collect(),
+            Statement::If { condition, .. }
+            | Statement::While { condition, .. }
+            | Statement::DoWhile { condition, .. } => vec![condition],
+            Statement::For {
+                condition, update, ..
+            } => std::iter::once(condition)
+                .chain(update.iter().flat_map(Stmt::exprs))
+                .collect(),
+            Statement::Switch { value, cases, .. } => std::iter::once(value)
+                .chain(cases.iter().filter_map(|case| case.guard.as_ref()))
+                .collect(),
+            Statement::Synchronized { lock, .. } => vec![lock],
+            Statement::Try { resources, .. } => resources
+                .iter()
+                .filter_map(|resource| resource.value.as_ref())
+                .collect(),
+            Statement::Return(Some(expr)) | Statement::Throw(expr) | Statement::Yield(expr) => {
+                vec![expr]
+            }
+            Statement::Assert { condition, message } => {
+                std::iter::once(condition).chain(message.as_ref()).collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn exprs_mut(&mut self) -> Vec<&mut Expr> {
+        self.split_mut().1
+    }
+
+    /// The variables the patterns of the statement itself declare, in its
+    /// expressions and its `case` labels, and the parameters of the lambdas
+    /// in its expressions.
+    pub fn bindings(&self) -> Vec<Var> {
+        let mut bindings = Vec::new();
+        if let Statement::Switch { cases, .. } = self {
+            bindings.extend(case_bindings(cases));
+        }
+        for expr in self.exprs() {
+            expr.walk(&mut |expr| match expr {
+                Expr::InstanceOf {
+                    pattern: Some(pattern),
+                    ..
+                } => bindings.extend(pattern.bindings()),
+                Expr::Switch { cases, .. } => bindings.extend(case_bindings(cases)),
+                Expr::Lambda { parameters, .. } => bindings.extend(parameters),
+                _ => {}
+            });
+        }
+        bindings
+    }
+
+    pub fn label(&self) -> Option<Label> {
+        match self {
+            Statement::Block { label, .. }
+            | Statement::While { label, .. }
+            | Statement::DoWhile { label, .. }
+            | Statement::For { label, .. }
+            | Statement::Switch { label, .. } => Some(*label),
+            _ => None,
+        }
+    }
+}
+
+fn case_bindings(cases: &[Case]) -> Vec<Var> {
+    cases
+        .iter()
+        .flat_map(|case| &case.labels)
+        .flat_map(|label| match label {
+            CaseLabel::Pattern(_, pattern) => pattern.bindings(),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+// The cases of the switch expressions and the bodies of the lambdas in an
+// expression, outside of those of the ones in them.
+fn nested_statements<'a>(expr: &'a Expr, nested: &mut Vec<&'a Vec<Statement>>) {
+    match expr {
+        Expr::Switch { value, cases } => {
+            nested_statements(value, nested);
+            for case in cases {
+                if let Some(guard) = &case.guard {
+                    nested_statements(guard, nested);
+                }
+                nested.push(&case.body);
+            }
+        }
+        Expr::Lambda { captured, body, .. } => {
+            for value in captured {
+                nested_statements(value, nested);
+            }
+            nested.push(body);
+        }
+        expr => {
+            for child in expr.children() {
+                nested_statements(child, nested);
+            }
+        }
+    }
+}
+
+fn nested_statements_mut<'a>(expr: &'a mut Expr, nested: &mut Vec<&'a mut Vec<Statement>>) {
+    match expr {
+        Expr::Switch { value, cases } => {
+            nested_statements_mut(value, nested);
+            for case in cases {
+                if let Some(guard) = &mut case.guard {
+                    nested_statements_mut(guard, nested);
+                }
+                nested.push(&mut case.body);
+            }
+        }
+        Expr::Lambda { captured, body, .. } => {
+            for value in captured {
+                nested_statements_mut(value, nested);
+            }
+            nested.push(body);
+        }
+        expr => {
+            for child in expr.children_mut() {
+                nested_statements_mut(child, nested);
+            }
+        }
+    }
+}
+
+/// Turns a body out of SSA form into structured statements.
+///
+/// Uses Norman Ramsey's translation from "Beyond Relooper" (ICFP 2022),
+/// which walks the dominator tree: a block reached from more than one place
+/// follows a labelled block that is left with `break` to get to it, and a
+/// loop header becomes a `while (true)` that is restarted with `continue`.
+/// Blocks reached from one place are placed where the jump to them was.
+/// Loops with more than one entry are first made single entry by copying
+/// blocks, or past a size limit by a `switch` on a new variable. The
+/// result is then tidied into `if`/`else`, `while`, `do`/`while` and `for`
+/// with as few labels as possible, and variables are declared where they
+/// are first needed.
+///
+/// Exceptions count as jumps from the blocks of each of the `regions` to
+/// its handlers, which makes the first block of a `try` dominate its
+/// handlers and everything after it. The `try` statement is placed there,
+/// with the handlers as its `catch` clauses, and the blocks the `try` is
+/// left for are placed after it, where `break` gets to them, so nothing
+/// outside the region ends up in it.
+///
+/// The cases of a switch are in the order of their code, falling through
+/// into the next where the code does. javac's switches on strings and enums
+/// are turned back into them, the latter using the arrays in `switch_maps`,
+/// and pattern switches get the labels in `pattern_switches`. Switches only
+/// become switch expressions in classes of `major_version` 58 (Java 14) and
+/// later, which the source can have them in.
+///
+/// The bodies of lambdas were structured on their own. The values they
+/// capture are put in place of the parameters javac passes them in, and
+/// their labels are numbered after the method's.
+pub fn structure(
+    body: &mut Body,
+    regions: &[TryRegion],
+    switch_maps: &SwitchMaps,
+    pattern_switches: &PatternSwitches,
+    major_version: u16,
+) -> Vec<Statement> {
+    if body.blocks.is_empty() {
+        return Vec::new();
+    }
+    let mut blocks = body.blocks.clone();
+    let mut origins: Vec<BlockId> = (0..blocks.len()).collect();
+    // a `finally` block ends in a jump to an empty block of its own instead
+    // of rethrowing, which becomes a `break` out of it
+    let mut ends = Vec::new();
+    for region in regions {
+        if let Some(finally) = &region.finally {
+            blocks[finally.rethrow].terminator = Terminator::Goto(blocks.len());
+            ends.push(blocks.len());
+            blocks.push(Block {
+                phis: Vec::new(),
+                stmts: Vec::new(),
+                terminator: Terminator::Unreachable,
+            });
+            origins.push(NO_ORIGIN);
+        }
+    }
+    let mut handlers = vec![Vec::new(); body.blocks.len()];
+    for region in regions {
+        for id in &region.blocks {
+            handlers[*id].extend(region.catches.iter().map(|catch| catch.handler));
+            handlers[*id].extend(region.finally.iter().map(|finally| finally.handler));
+        }
+    }
+
+    make_reducible(body, &mut blocks, &mut origins, &handlers);
+    let throws = origins
+        .iter()
+        .map(|origin| handlers.get(*origin).cloned().unwrap_or_default())
+        .collect();
+    let mut structurer = Structurer::new(body, blocks, origins.clone(), throws, pattern_switches);
+    structurer.add_regions(regions, &origins, &ends);
+    let mut statements = structurer.tree(0, &mut Vec::new());
+    close_lambdas(&mut statements, &mut structurer.labels);
+
+    let mut tidier = Tidier {
+        body,
+        switch_maps,
+        major_version,
+        classes: 0,
+        mentions: BTreeMap::new(),
+    };
+    tidier.tidy(&mut statements);
+    declare_variables(body, &mut statements);
+    statements
+}
+
+// The labels of the keys of a case of a pattern switch, and its guard.
+fn pattern_labels<'a>(
+    patterns: &BTreeMap<i32, PatternCase>,
+    keys: impl Iterator<Item = &'a i32>,
+) -> (Vec<CaseLabel>, Option<Expr>) {
+    let cases: Vec<&PatternCase> = keys.filter_map(|key| patterns.get(key)).collect();
+    let labels = cases.iter().map(|case| case.label.clone()).collect();
+    let guard = cases.iter().find_map(|case| case.guard.clone());
+    (labels, guard)
+}
+
+// Blocks are copied once they have reached the number of the method's
+// blocks times this.
+const SPLIT_LIMIT: usize = 4;
+
+// The origin of a block that isn't a copy of one of the body's.
+const NO_ORIGIN: BlockId = usize::MAX;
+
+// Makes every loop of the blocks have a single entry, keeping track of
+// which of the body's blocks each is a copy of. See
+// https://en.wikipedia.org/wiki/Control-flow_graph#Reducibility
+fn make_reducible(
+    body: &mut Body,
+    blocks: &mut Vec<Block>,
+    origins: &mut Vec<BlockId>,
+    handlers: &[Vec<BlockId>],
+) {
+    let limit = blocks.len() * SPLIT_LIMIT;
+    for _ in 0..limit {
+        let throws: Vec<Vec<BlockId>> = origins
+            .iter()
+            .map(|origin| handlers.get(*origin).cloned().unwrap_or_default())
+            .collect();
+        let successors = successors(blocks, &throws);
+        let dominators = DominatorTree::from_successors(0, successors.clone());
+        let (preorder, postorder) = depth_first_numbering(&successors);
+        let retreating = (0..blocks.len())
+            .filter(|id| dominators.is_reachable(*id))
+            .flat_map(|from| successors[from].iter().map(move |to| (from, *to)))
+            .find(|(from, to)| {
+                preorder[*to] <= preorder[*from]
+                    && postorder[*from] <= postorder[*to]
+                    && !dominators.dominates(*to, *from)
+            });
+        let Some((from, to)) = retreating else {
+            return;
+        };
+
+        // the entries of the cycle other than the first reached each get
+        // their own copy of it
+        let predecessors: Vec<BlockId> = (0..blocks.len())
+            .filter(|id| dominators.is_reachable(*id) && successors[*id].contains(&to))
+            .collect();
+        if blocks.len() + predecessors.len() <= limit {
+            let keep = predecessors
+                .iter()
+                .copied()
+                .min_by_key(|id| preorder[*id])
+                .unwrap_or(from);
+            for predecessor in predecessors {
+                if predecessor == keep || predecessor == to {
+                    continue;
+                }
+                let copy = blocks.len();
+                blocks.push(blocks[to].clone());
+                origins.push(origins[to]);
+                retarget(&mut blocks[predecessor].terminator, to, copy);
+            }
+        } else {
+            dispatch(body, blocks, origins, &successors, to);
+        }
+    }
+}
+
+// Gives the cycle through `entry` a single entry: a new block that switches
+// on a new variable, which each jump into the cycle sets first.
+fn dispatch(
+    body: &mut Body,
+    blocks: &mut Vec<Block>,
+    origins: &mut Vec<BlockId>,
+    successors: &[Vec<BlockId>],
+    entry: BlockId,
+) {
+    let forward = reaches(successors, entry, false);
+    let backward = reaches(successors, entry, true);
+    let cycle: BTreeSet<BlockId> = forward.intersection(&backward).copied().collect();
+    let entries: Vec<BlockId> = cycle
+        .iter()
+        .copied()
+        .filter(|id| {
+            *id == 0
+                || (0..blocks.len())
+                    .any(|from| !cycle.contains(&from) && successors[from].contains(id))
+        })
+        .collect();
+
+    let state = body.new_variable(Variable {
+        kind: Kind::Int,
+        slot: None,
+        name: None,
+        field_type: Some(FieldType::Int),
+    });
+    let header = blocks.len();
+    blocks.push(Block {
+        phis: Vec::new(),
+        stmts: Vec::new(),
+        terminator: Terminator::Switch {
+            value: Expr::var(state),
+            cases: Vec::new(),
+            default: entries[0],
+        },
+    });
+    origins.push(NO_ORIGIN);
+    // jumps from outside the cycle get their own setters, so they stay
+    // outside it and the switch is its only entry
+    let mut entry_setter = None;
+    for (key, entry) in entries.iter().enumerate() {
+        if let Terminator::Switch { cases, .. } = &mut blocks[header].terminator {
+            cases.push((key as i32, *entry));
+        }
+        let mut setter = || {
+            blocks.push(Block {
+                phis: Vec::new(),
+                stmts: vec![Stmt::assign(Expr::var(state), Expr::int(key as i32))],
+                terminator: Terminator::Goto(header),
+            });
+            origins.push(NO_ORIGIN);
+            blocks.len() - 1
+        };
+        let (inside, outside) = (setter(), setter());
+        for (id, block) in blocks.iter_mut().enumerate().take(header) {
+            let setter = if cycle.contains(&id) { inside } else { outside };
+            retarget(&mut block.terminator, *entry, setter);
+        }
+        if *entry == 0 {
+            entry_setter = Some(outside);
+        }
+    }
+    // the method has to start at block 0, so if it is in the cycle its code
+    // moves to a new block and it only sets the variable
+    if let Some(setter) = entry_setter {
+        let moved = blocks.len();
+        blocks.push(blocks[0].clone());
+        origins.push(origins[0]);
+        blocks[0] = blocks[setter].clone();
+        origins[0] = NO_ORIGIN;
+        if let Terminator::Switch { cases, default, .. } = &mut blocks[header].terminator {
+            for (_, target) in cases.iter_mut() {
+                if *target == 0 {
+                    *target = moved;
+                }
+            }
+            if *default == 0 {
+                *default = moved;
+            }
+        }
+    }
+}
+
+// The blocks each block jumps to, followed by the handlers of the
+// exceptions it throws.
+fn successors(blocks: &[Block], throws: &[Vec<BlockId>]) -> Vec<Vec<BlockId>> {
+    blocks
+        .iter()
+        .zip(throws)
+        .map(|(block, throws)| {
+            let mut targets = block.terminator.targets();
+            targets.extend(throws);
+            let mut seen = BTreeSet::new();
+            targets.retain(|target| seen.insert(*target));
+            targets
+        })
+        .collect()
+}
+
+fn retarget(terminator: &mut Terminator, from: BlockId, to: BlockId) {
+    let swap = |target: &mut BlockId| {
+        if *target == from {
+            *target = to;
+        }
+    };
+    match terminator {
+        Terminator::Goto(target) => swap(target),
+        Terminator::If {
+            then, otherwise, ..
+        } => {
+            swap(then);
+            swap(otherwise);
+        }
+        Terminator::Switch { cases, default, .. } => {
+            for (_, target) in cases {
+                swap(target);
+            }
+            swap(default);
+        }
+        Terminator::Return(_) | Terminator::Throw(_) | Terminator::Unreachable => {}
+    }
+}
+
+// The blocks `start` reaches, or that reach it when `backward`.
+fn reaches(successors: &[Vec<BlockId>], start: BlockId, backward: bool) -> BTreeSet<BlockId> {
+    let mut predecessors = vec![Vec::new(); successors.len()];
+    for (from, tos) in successors.iter().enumerate() {
+        for to in tos {
+            predecessors[*to].push(from);
+        }
+    }
+    let edges = if backward { &predecessors } else { successors };
+    let mut seen = BTreeSet::from([start]);
+    let mut worklist = vec![start];
+    while let Some(id) = worklist.pop() {
+        for next in &edges[id] {
+            if seen.insert(*next) {
+                worklist.push(*next);
+            }
+        }
+    }
+    seen
+}
+
+enum Frame {
+    // the block is placed after a labelled block, so `break` gets to it
+    Follow(BlockId, Label),
+    Loop(BlockId, Label),
+}
+
+// A `try` statement, over the structurer's blocks.
+struct Region {
+    entry: BlockId,
+    blocks: BTreeSet<BlockId>,
+    catches: Vec<CatchClause>,
+    // the handler, and the block the end of the `finally` block jumps to
+    finally: Option<(BlockId, BlockId)>,
+    resource: Option<Resource>,
+    lock: Option<Expr>,
+    // the blocks outside the region it jumps to, which come after the `try`
+    exits: Vec<BlockId>,
+}
+
+struct Structurer<'a> {
+    body: &'a Body,
+    blocks: Vec<Block>,
+    // which of the body's blocks each block is a copy of
+    origins: Vec<BlockId>,
+    dominators: DominatorTree,
+    // position in reverse postorder
+    order: Vec<usize>,
+    // the blocks of the loop each loop header starts
+    loops: Vec<Option<BTreeSet<BlockId>>>,
+    // the blocks jumping to each block other than by a back edge
+    forward: Vec<BTreeSet<BlockId>>,
+    // reached by more than one forward edge
+    merge: Vec<bool>,
+    // outermost first
+    regions: Vec<Region>,
+    // handlers and the exits of regions, which only their `try` places
+    claimed: Vec<bool>,
+    labels: usize,
+    pattern_switches: &'a PatternSwitches,
+}
+
+impl<'a> Structurer<'a> {
+    fn new(
+        body: &'a Body,
+        blocks: Vec<Block>,
+        origins: Vec<BlockId>,
+        throws: Vec<Vec<BlockId>>,
+        pattern_switches: &'a PatternSwitches,
+    ) -> Structurer<'a> {
+        let successors = successors(&blocks, &throws);
+        let count = blocks.len();
+        let dominators = DominatorTree::from_successors(0, successors.clone());
+        let mut order = vec![usize::MAX; count];
+        for (index, id) in dominators.reverse_postorder().iter().enumerate() {
+            order[*id] = index;
+        }
+
+        let mut loops: Vec<Option<BTreeSet<BlockId>>> = vec![None; count];
+        let mut forward = vec![BTreeSet::new(); count];
+        let mut predecessors = vec![Vec::new(); count];
+        for (from, tos) in successors.iter().enumerate() {
+            if !dominators.is_reachable(from) {
+                continue;
+            }
+            for to in tos {
+                predecessors[*to].push(from);
+                if dominators.dominates(*to, from) {
+                    loops[*to].get_or_insert_with(BTreeSet::new).insert(from);
+                } else {
+                    forward[*to].insert(from);
+                }
+            }
+        }
+        // the natural loop of each header, from the blocks jumping back to it
+        for (header, natural) in loops.iter_mut().enumerate() {
+            let Some(latches) = natural.take() else {
+                continue;
+            };
+            let mut body = BTreeSet::from([header]);
+            let mut worklist: Vec<BlockId> = latches.into_iter().collect();
+            while let Some(id) = worklist.pop() {
+                if body.insert(id) {
+                    worklist.extend(predecessors[id].iter().copied());
+                }
+            }
+            *natural = Some(body);
+        }
+
+        Structurer {
+            body,
+            blocks,
+            origins,
+            dominators,
+            order,
+            loops,
+            merge: forward.iter().map(|from| from.len() > 1).collect(),
+            forward,
+            regions: Vec::new(),
+            claimed: vec![false; count],
+            labels: 0,
+            pattern_switches,
+        }
+    }
+
+    // Takes the regions over to the structurer's blocks, given which of the
+    // body's blocks each is, and the blocks the `finally` blocks end at.
+    fn add_regions(&mut self, regions: &[TryRegion], origins: &[BlockId], ends: &[BlockId]) {
+        let mut ends = ends.iter();
+        for region in regions {
+            let blocks: BTreeSet<BlockId> = (0..self.blocks.len())
+                .filter(|id| region.blocks.contains(&origins[*id]))
+                .collect();
+            let finally = region.finally.as_ref().map(|finally| {
+                let end = *ends.next().expect("every finally block has an end");
+                (finally.handler, end)
+            });
+            for handler in region
+                .catches
+                .iter()
+                .map(|catch| catch.handler)
+                .chain(finally.iter().flat_map(|(handler, end)| [*handler, *end]))
+            {
+                self.claimed[handler] = true;
+            }
+            self.regions.push(Region {
+                entry: region.entry,
+                blocks,
+                catches: region.catches.clone(),
+                finally,
+                resource: region.resource.clone(),
+                lock: region.lock.clone(),
+                exits: Vec::new(),
+            });
+        }
+
+        // a block a region jumps out to is placed after the outermost `try`
+        // it has to be outside of; a return of a value that is already
+        // computed can stay inside
+        let mut predecessors = vec![BTreeSet::new(); self.blocks.len()];
+        for (id, block) in self.blocks.iter().enumerate() {
+            if self.dominators.is_reachable(id) {
+                for target in block.terminator.targets() {
+                    predecessors[target].insert(id);
+                }
+            }
+        }
+        for (id, block) in self.blocks.iter().enumerate() {
+            let Some(dominator) = self.dominators.immediate_dominator(id) else {
+                continue;
+            };
+            if self.claimed[id] {
+                continue;
+            }
+            let returns = block.stmts.is_empty()
+                && match &block.terminator {
+                    Terminator::Return(None) => true,
+                    Terminator::Return(Some(value)) => {
+                        matches!(value, Expr::Var(_) | Expr::Const(_))
+                    }
+                    _ => false,
+                };
+            // unless a `finally` block changes the variable after it is read
+            let returned = match &block.terminator {
+                Terminator::Return(Some(Expr::Var(var))) => Some(*var),
+                _ => None,
+            };
+            let changed_by_finally = |region: &Region| {
+                let (Some((handler, _)), Some(var)) = (region.finally, returned) else {
+                    return false;
+                };
+                (0..self.blocks.len()).any(|id| {
+                    self.dominators.dominates(handler, id)
+                        && self.blocks[id]
+                            .stmts
+                            .iter()
+                            .any(|stmt| stmt.defined_var() == Some(var))
+                })
+            };
+            let region = self.regions.iter().position(|region| {
+                region.blocks.contains(&dominator)
+                    && !region.blocks.contains(&id)
+                    && !(returns
+                        && predecessors[id].is_subset(&region.blocks)
+                        && !changed_by_finally(region))
+            });
+            if let Some(region) = region {
+                self.regions[region].exits.push(id);
+                self.claimed[id] = true;
+            }
+        }
+        for region in &mut self.regions {
+            region
+                .exits
+                .sort_by_key(|exit| std::cmp::Reverse(self.order[*exit]));
+        }
+    }
+
+    fn label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels)
+    }
+
+    // The code of a block and every block it dominates.
+    fn tree(&mut self, id: BlockId, context: &mut Vec<Frame>) -> Vec<Statement> {
+        let mut children: Vec<BlockId> = self
+            .dominators
+            .children(id)
+            .iter()
+            .copied()
+            .filter(|child| !self.claimed[*child])
+            .collect();
+        children.sort_by_key(|child| std::cmp::Reverse(self.order[*child]));
+        // the `try` statements starting here go around the loop starting
+        // here if they hold all of it, and inside it if they don't
+        let starting: Vec<usize> = (0..self.regions.len())
+            .filter(|region| self.regions[*region].entry == id)
+            .collect();
+        let around = match &self.loops[id] {
+            Some(body) => starting
+                .iter()
+                .take_while(|region| body.is_subset(&self.regions[**region].blocks))
+                .count(),
+            None => starting.len(),
+        };
+        let (around, within) = starting.split_at(around);
+        // the cases of a switch fallen into are placed in it
+        let fallthroughs = self.fallthroughs(id);
+        children.retain(|child| !fallthroughs.contains(child));
+        self.tries(around, context, &mut |structurer, context| {
+            match structurer.loops[id].clone() {
+                Some(body) => {
+                    // what comes after the loop is placed after it, even
+                    // when only one block jumps there
+                    let (inside, outside): (Vec<BlockId>, Vec<BlockId>) =
+                        children.iter().partition(|child| body.contains(child));
+                    let inside: Vec<BlockId> = inside
+                        .into_iter()
+                        .filter(|child| structurer.merge[*child])
+                        .collect();
+                    structurer.follow(&outside, context, &mut |structurer, context| {
+                        let label = structurer.label();
+                        context.push(Frame::Loop(id, label));
+                        let body =
+                            structurer.follow(&inside, context, &mut |structurer, context| {
+                                structurer.tries(within, context, &mut |structurer, context| {
+                                    structurer.node(id, context)
+                                })
+                            });
+                        context.pop();
+                        vec![Statement::While {
+                            label,
+                            condition: Expr::Const(Const::Boolean(true)),
+                            body,
+                        }]
+                    })
+                }
+                None => {
+                    let merges: Vec<BlockId> = children
+                        .iter()
+                        .copied()
+                        .filter(|child| structurer.merge[*child])
+                        .collect();
+                    structurer.follow(&merges, context, &mut |structurer, context| {
+                        structurer.node(id, context)
+                    })
+                }
+            }
+        })
+    }
+
+    // Nests the code `inner` makes in a `try` statement for each of
+    // `regions`, the first outermost, each followed by the blocks it exits
+    // to.
+    fn tries(
+        &mut self,
+        regions: &[usize],
+        context: &mut Vec<Frame>,
+        inner: &mut dyn FnMut(&mut Self, &mut Vec<Frame>) -> Vec<Statement>,
+    ) -> Vec<Statement> {
+        let Some((region, rest)) = regions.split_first() else {
+            return inner(self, context);
+        };
+        let exits = self.regions[*region].exits.clone();
+        self.follow(&exits, context, &mut |structurer, context| {
+            let body = structurer.tries(rest, context, &mut *inner);
+            let catches = structurer.regions[*region]
+                .catches
+                .clone()
+                .into_iter()
+                .map(|catch| Catch {
+                    types: catch.types,
+                    var: catch.var,
+                    body: structurer.tree(catch.handler, context),
+                })
+                .collect();
+            let finally = match structurer.regions[*region].finally {
+                Some((handler, end)) => {
+                    let label = structurer.label();
+                    context.push(Frame::Follow(end, label));
+                    let body = structurer.tree(handler, context);
+                    context.pop();
+                    vec![Statement::Block { label, body }]
+                }
+                None => Vec::new(),
+            };
+            let region = &structurer.regions[*region];
+            if let Some(lock) = &region.lock {
+                return vec![Statement::Synchronized {
+                    lock: lock.clone(),
+                    body,
+                }];
+            }
+            vec![Statement::Try {
+                resources: region.resource.iter().cloned().collect(),
+                body,
+                catches,
+                finally,
+            }]
+        })
+    }
+
+    // Nests the code `inner` makes in a labelled block for each of
+    // `followers`, the last first, with each follower's code after its
+    // block.
+    fn follow(
+        &mut self,
+        followers: &[BlockId],
+        context: &mut Vec<Frame>,
+        inner: &mut dyn FnMut(&mut Self, &mut Vec<Frame>) -> Vec<Statement>,
+    ) -> Vec<Statement> {
+        let Some((follower, rest)) = followers.split_first() else {
+            return inner(self, context);
+        };
+        let label = self.label();
+        context.push(Frame::Follow(*follower, label));
+        let body = self.follow(rest, context, inner);
+        context.pop();
+        let mut statements = vec![Statement::Block { label, body }];
+        statements.extend(self.tree(*follower, context));
+        statements
+    }
+
+    // The targets of the switch a block ends in with their keys, in the
+    // order of their code. Keys going to the default are left out.
+    fn switch_groups(&self, id: BlockId) -> Vec<(BlockId, Vec<i32>)> {
+        let Terminator::Switch { cases, default, .. } = &self.blocks[id].terminator else {
+            return Vec::new();
+        };
+        let mut groups: Vec<(BlockId, Vec<i32>)> = vec![(*default, Vec::new())];
+        for (key, target) in cases {
+            match groups.iter_mut().find(|(existing, _)| existing == target) {
+                Some((existing, _)) if existing == default => {}
+                Some((_, keys)) => keys.push(*key),
+                None => groups.push((*target, vec![*key])),
+            }
+        }
+        groups.sort_by_key(|(target, _)| (self.origins[*target], self.order[*target]));
+        groups
+    }
+
+    // The targets of the switch a block ends in that the case before them
+    // falls through into, as only it and the switch jump to them. A default
+    // coming last is taken to be the code after the switch instead, unless
+    // the cases jump to other code after it.
+    fn fallthroughs(&self, id: BlockId) -> BTreeSet<BlockId> {
+        let groups = self.switch_groups(id);
+        let default = match &self.blocks[id].terminator {
+            Terminator::Switch { default, .. } => *default,
+            _ => return BTreeSet::new(),
+        };
+        let placed = |target: BlockId| {
+            self.dominators.immediate_dominator(target) == Some(id)
+                && !self.claimed[target]
+                && self.loops[target].is_none()
+        };
+        let elsewhere = self.dominators.children(id).iter().any(|child| {
+            self.merge[*child]
+                && !self.claimed[*child]
+                && !groups.iter().any(|(target, _)| target == child)
+        });
+        let mut fallthroughs = BTreeSet::new();
+        for (index, pair) in groups.windows(2).enumerate() {
+            let [(previous, _), (target, _)] = pair else {
+                continue;
+            };
+            let is_last = index + 2 == groups.len();
+            let in_case =
+                placed(*previous) && (!self.merge[*previous] || fallthroughs.contains(previous));
+            if in_case
+                && placed(*target)
+                && self.merge[*target]
+                && !(is_last && *target == default && !elsewhere)
+                && self.forward[*target]
+                    .iter()
+                    .all(|from| *from == id || self.dominators.dominates(*previous, *from))
+            {
+                fallthroughs.insert(*target);
+            }
+        }
+        fallthroughs
+    }
+
+    // The code of one block, ending in jumps to where it goes next.
+    fn node(&mut self, id: BlockId, context: &mut Vec<Frame>) -> Vec<Statement> {
+        let block = &self.blocks[id];
+        let mut statements: Vec<Statement> =
+            block.stmts.iter().cloned().map(Statement::Stmt).collect();
+        match block.terminator.clone() {
+            Terminator::Goto(target) => statements.extend(self.branch(id, target, context)),
+            Terminator::If {
+                condition,
+                then: taken,
+                otherwise: not_taken,
+            } => {
+                // the branch not taken is what the source had first
+                let condition = negate(self.body, condition);
+                let then = self.branch(id, not_taken, context);
+                let otherwise = self.branch(id, taken, context);
+                statements.push(Statement::If {
+                    condition,
+                    then,
+                    otherwise,
+                });
+            }
+            Terminator::Switch {
+                value,
+                cases: switched,
+                default,
+            } => {
+                let groups = self.switch_groups(id);
+                let fallthroughs = self.fallthroughs(id);
+                let is_char = infer::type_of(self.body, &value) == Some(FieldType::Char);
+                let label = self.label();
+                let mut cases = Vec::new();
+                for (index, (target, keys)) in groups.iter().enumerate() {
+                    let next = groups
+                        .get(index + 1)
+                        .map(|(next, _)| *next)
+                        .filter(|next| fallthroughs.contains(next));
+                    let body = match next {
+                        // jumps to the code of the next case fall through
+                        // to it
+                        Some(next) => {
+                            let inner = self.label();
+                            context.push(Frame::Follow(next, inner));
+                            let body = self.branch(id, *target, context);
+                            context.pop();
+                            vec![Statement::Block { label: inner, body }]
+                        }
+                        None => self.branch(id, *target, context),
+                    };
+                    let is_default = *target == default;
+                    let (labels, guard) = match self.pattern_switches.get(&self.origins[id]) {
+                        Some(patterns) => {
+                            // javac jumps to the default case for a `null`
+                            // it takes, which keeps the key
+                            let null = is_default && switched.contains(&(-1, default));
+                            let keys = keys.iter().chain(null.then_some(&-1));
+                            pattern_labels(patterns, keys)
+                        }
+                        None => {
+                            let labels = keys
+                                .iter()
+                                .map(|key| match is_char {
+                                    true => CaseLabel::Const(Const::Char(*key as u16)),
+                                    false => CaseLabel::Const(Const::Int(*key)),
+                                })
+                                .collect();
+                            (labels, None)
+                        }
+                    };
+                    cases.push(Case {
+                        labels,
+                        is_default,
+                        guard,
+                        body,
+                    });
+                }
+                statements.push(Statement::Switch {
+                    label,
+                    value,
+                    cases,
+                });
+            }
+            Terminator::Return(value) => statements.push(Statement::Return(value)),
+            Terminator::Throw(value) => statements.push(Statement::Throw(value)),
+            Terminator::Unreachable => {}
+        }
+        statements
+    }
+
+    fn branch(&mut self, from: BlockId, to: BlockId, context: &mut Vec<Frame>) -> Vec<Statement> {
+        if self.dominators.dominates(to, from) {
+            let label = context.iter().rev().find_map(|frame| match frame {
+                Frame::Loop(header, label) if *header == to => Some(*label),
+                _ => None,
+            });
+            return label.map(Statement::Continue).into_iter().collect();
+        }
+        let label = context.iter().rev().find_map(|frame| match frame {
+            Frame::Follow(follower, label) if *follower == to => Some(*label),
+            _ => None,
+        });
+        match label {
+            Some(label) => vec![Statement::Break(label)],
+            None if self.dominators.immediate_dominator(to) == Some(from) => self.tree(to, context),
+            // only for graphs that couldn't be made reducible
+            None => Vec::new(),
+        }
+    }
+}
+
+// A set of places that control reaching the end of a statement goes on to.
+// Jumps with the same class as the end of the list they are last in do
+// nothing.
+type Class = usize;
+
+#[derive(Clone, Copy)]
+struct Target {
+    label: Label,
+    // loops and switches, which `break` with no label leaves
+    breakable: bool,
+    exit: Class,
+    next_iteration: Option<Class>,
+}
+
+// Where falling off the end of a void method goes.
+const METHOD_END: Class = 0;
+
+struct Tidier<'a> {
+    body: &'a Body,
+    switch_maps: &'a SwitchMaps,
+    major_version: u16,
+    classes: Class,
+    // how many statements mention each variable as of the round's start
+    mentions: BTreeMap<Var, usize>,
+}
+
+impl Tidier<'_> {
+    fn tidy(&mut self, statements: &mut Vec<Statement>) {
+        // each round can enable more of the others; the limit only guards
+        // against rewrites undoing each other
+        for _ in 0..32 {
+            let before = statements.clone();
+            self.classes = METHOD_END;
+            self.mentions = mentions(statements);
+            self.retarget(statements, METHOD_END, &mut Vec::new());
+            self.rewrite(statements);
+            if *statements == before {
+                break;
+            }
+        }
+    }
+
+    fn class(&mut self) -> Class {
+        self.classes += 1;
+        self.classes
+    }
+
+    // The class of a jump.
+    fn jump_class(&self, statement: &Statement, targets: &[Target]) -> Option<Class> {
+        match statement {
+            Statement::Break(label) => targets
+                .iter()
+                .find(|target| target.label == *label)
+                .map(|target| target.exit),
+            Statement::Continue(label) => targets
+                .iter()
+                .find(|target| target.label == *label)
+                .and_then(|target| target.next_iteration),
+            Statement::Return(None) => Some(METHOD_END),
+            _ => None,
+        }
+    }
+
+    // Points jumps at the innermost loop or switch they are equivalent to
+    // leaving or continuing, and removes jumps to where control would go
+    // anyway.
+    fn retarget(&mut self, statements: &mut Vec<Statement>, end: Class, targets: &mut Vec<Target>) {
+        let count = statements.len();
+        for (index, statement) in statements.iter_mut().enumerate() {
+            let next = if index + 1 == count {
+                end
+            } else {
+                self.class()
+            };
+            match statement {
+                Statement::If {
+                    then, otherwise, ..
+                } => {
+                    self.retarget(then, next, targets);
+                    self.retarget(otherwise, next, targets);
+                }
+                Statement::Block { label, body } => {
+                    targets.push(Target {
+                        label: *label,
+                        breakable: false,
+                        exit: next,
+                        next_iteration: None,
+                    });
+                    self.retarget(body, next, targets);
+                    targets.pop();
+                }
+                Statement::While { label, body, .. }
+                | Statement::DoWhile { label, body, .. }
+                | Statement::For { label, body, .. } => {
+                    let iteration = self.class();
+                    targets.push(Target {
+                        label: *label,
+                        breakable: true,
+                        exit: next,
+                        next_iteration: Some(iteration),
+                    });
+                    self.retarget(body, iteration, targets);
+                    targets.pop();
+                }
+                Statement::Switch { label, cases, .. } => {
+                    targets.push(Target {
+                        label: *label,
+                        breakable: true,
+                        exit: next,
+                        next_iteration: None,
+                    });
+                    let last = cases.len().saturating_sub(1);
+                    for (index, case) in cases.iter_mut().enumerate() {
+                        // anything but the last case falls through
+                        let end = if index == last { next } else { self.class() };
+                        self.retarget(&mut case.body, end, targets);
+                    }
+                    targets.pop();
+                }
+                // leaving the `try` block or a `catch` block runs the
+                // `finally` block the same way falling out of it does, but
+                // where the `finally` block goes next depends on how it was
+                // entered
+                Statement::Synchronized { body, .. } => self.retarget(body, next, targets),
+                Statement::Try {
+                    body,
+                    catches,
+                    finally,
+                    ..
+                } => {
+                    self.retarget(body, next, targets);
+                    for catch in catches {
+                        self.retarget(&mut catch.body, next, targets);
+                    }
+                    let end = self.class();
+                    self.retarget(finally, end, targets);
+                }
+                Statement::Break(_) | Statement::Continue(_) => {
+                    let Some(class) = self.jump_class(statement, targets) else {
+                        continue;
+                    };
+                    let replacement = targets.iter().rev().find_map(|target| {
+                        if target.breakable && target.exit == class {
+                            Some(Statement::Break(target.label))
+                        } else if target.next_iteration == Some(class) {
+                            Some(Statement::Continue(target.label))
+                        } else {
+                            None
+                        }
+                    });
+                    if let Some(replacement) = replacement {
+                        *statement = replacement;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(last) = statements.last() {
+            if self.jump_class(last, targets) == Some(end) {
+                statements.pop();
+            }
+        }
+        // `if (c) <nothing>; jump` at the end becomes `if (!c) jump`
+        if let [.., Statement::If {
+            then, otherwise, ..
+        }, jump] = statements.as_slice()
+        {
+            let redundant = match then.as_slice() {
+                [inner] if otherwise.is_empty() => self.jump_class(inner, targets) == Some(end),
+                _ => false,
+            };
+            if redundant && self.jump_class(jump, targets).is_some() {
+                let jump = statements.pop().expect("matched above");
+                if let Some(Statement::If {
+                    condition, then, ..
+                }) = statements.last_mut()
+                {
+                    let negated = negate(self.body, condition.clone());
+                    *condition = negated;
+                    *then = vec![jump];
+                }
+            }
+        }
+    }
+
+    // The local rewrites into more natural statements.
+    fn rewrite(&mut self, statements: &mut Vec<Statement>) {
+        for statement in statements.iter_mut() {
+            for child in statement.children_mut() {
+                self.rewrite(child);
+            }
+        }
+
+        let mut index = 0;
+        while index < statements.len() {
+            if self.rewrite_at(statements, index) {
+                continue;
+            }
+            index += 1;
+        }
+    }
+
+    // Rewrites the statement at `index`, returning whether it changed so it
+    // can be looked at again.
+    fn rewrite_at(&mut self, statements: &mut Vec<Statement>, index: usize) -> bool {
+        let method = self.body;
+        match &mut statements[index] {
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if then.is_empty() && otherwise.is_empty() {
+                    if !condition.has_side_effects() {
+                        statements.remove(index);
+                        return true;
+                    }
+                    return false;
+                }
+                if then.is_empty() {
+                    *condition = negate(method, condition.clone());
+                    std::mem::swap(then, otherwise);
+                    return true;
+                }
+                if otherwise.is_empty() {
+                    return false;
+                }
+                // an `else` after a branch that can't complete is the code
+                // that follows; the shorter branch stays in the `if`
+                let then_ends = !completes_normally(then);
+                let otherwise_ends = !completes_normally(otherwise);
+                let keep_then = match (then_ends, otherwise_ends) {
+                    (true, true) => size(then) <= size(otherwise),
+                    (then_ends, otherwise_ends) => then_ends && !otherwise_ends,
+                };
+                if keep_then {
+                    let rest = std::mem::take(otherwise);
+                    statements.splice(index + 1..index + 1, rest);
+                    return true;
+                }
+                if otherwise_ends {
+                    *condition = negate(method, condition.clone());
+                    std::mem::swap(then, otherwise);
+                    let rest = std::mem::take(otherwise);
+                    statements.splice(index + 1..index + 1, rest);
+                    return true;
+                }
+                false
+            }
+            Statement::Block { label, body } => {
+                let label = *label;
+                if !jumps_to(body, label) {
+                    let body = std::mem::take(body);
+                    statements.splice(index..index + 1, body);
+                    return true;
+                }
+                // `l: { a; if (c) { b; break l; } d }` is
+                // `a; if (c) { b } else { d }`
+                let position = body.iter().position(|statement| {
+                    matches!(statement, Statement::If { then, otherwise, .. }
+                        if otherwise.is_empty() && then.last() == Some(&Statement::Break(label)))
+                });
+                let Some(position) = position else {
+                    return false;
+                };
+                let rest: Vec<Statement> = body.drain(position + 1..).collect();
+                if let Statement::If {
+                    then, otherwise, ..
+                } = &mut body[position]
+                {
+                    then.pop();
+                    *otherwise = rest;
+                }
+                true
+            }
+            Statement::While {
+                label,
+                condition,
+                body: loop_body,
+            } => {
+                let label = *label;
+                let is_infinite = *condition == Expr::Const(Const::Boolean(true));
+                // `while (true) { if (c) break; ... }` is `while (!c) { ... }`
+                if is_infinite {
+                    if let Some(Statement::If {
+                        condition: exit,
+                        then,
+                        otherwise,
+                    }) = loop_body.first()
+                    {
+                        if otherwise.is_empty() && then.as_slice() == [Statement::Break(label)] {
+                            *condition = negate(method, exit.clone());
+                            loop_body.remove(0);
+                            return true;
+                        }
+                    }
+                    // `while (true) { ...; if (c) break; }` is
+                    // `do { ... } while (!c)`, unless a `continue` would
+                    // skip the test
+                    if let Some(Statement::If {
+                        condition: exit,
+                        then,
+                        otherwise,
+                    }) = loop_body.last()
+                    {
+                        if otherwise.is_empty()
+                            && then.as_slice() == [Statement::Break(label)]
+                            && loop_body.len() > 1
+                            && !continues(loop_body, label)
+                        {
+                            let condition = negate(method, exit.clone());
+                            let mut loop_body = std::mem::take(loop_body);
+                            loop_body.pop();
+                            statements[index] = Statement::DoWhile {
+                                label,
+                                body: loop_body,
+                                condition,
+                            };
+                            return true;
+                        }
+                    }
+                    return false;
+                }
+                // `v = a; while (c) { ...; v = b; }` with `c` reading `v` is
+                // a `for` loop, as long as no `continue` skips the update
+                if index == 0 || loop_body.len() < 2 || continues(loop_body, label) {
+                    return false;
+                }
+                let Some(Statement::Stmt(update @ Stmt::Assign { .. })) = loop_body.last() else {
+                    return false;
+                };
+                let Some(var) = update.defined_var() else {
+                    return false;
+                };
+                if !condition.uses(var) {
+                    return false;
+                }
+                match &statements[index - 1] {
+                    Statement::Stmt(init) if init.defined_var() == Some(var) => {}
+                    _ => return false,
+                }
+                let Statement::Stmt(init) = statements.remove(index - 1) else {
+                    unreachable!("matched above");
+                };
+                let Statement::While {
+                    label,
+                    condition,
+                    body: mut loop_body,
+                } = statements.remove(index - 1)
+                else {
+                    unreachable!("matched above");
+                };
+                let Some(Statement::Stmt(update)) = loop_body.pop() else {
+                    unreachable!("matched above");
+                };
+                statements.insert(
+                    index - 1,
+                    Statement::For {
+                        label,
+                        init: vec![Statement::Stmt(init)],
+                        condition,
+                        update: vec![update],
+                        body: loop_body,
+                    },
+                );
+                true
+            }
+            // javac covers the `try` block and the `catch` blocks with the
+            // handler of the `finally` block, making a `try` with only a
+            // `finally` around one with only `catch` clauses. It nests a
+            // try-with-resources statement for each resource, and one with
+            // `catch` clauses or a `finally` block in a plain `try`.
+            // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.20.3.2
+            Statement::Try {
+                resources,
+                body,
+                catches,
+                finally,
+            } => {
+                let [Statement::Try {
+                    resources: inner_resources,
+                    catches: inner_catches,
+                    finally: inner_finally,
+                    ..
+                }] = body.as_mut_slice()
+                else {
+                    return self.resource_finally(statements, index);
+                };
+                let mergeable = inner_finally.is_empty()
+                    && if resources.is_empty() {
+                        catches.is_empty() || inner_catches.is_empty()
+                    } else {
+                        catches.is_empty() && finally.is_empty() && inner_catches.is_empty()
+                    };
+                if !mergeable {
+                    return self.resource_finally(statements, index);
+                }
+                resources.append(inner_resources);
+                catches.append(inner_catches);
+                let Some(Statement::Try { body: inner, .. }) = body.pop() else {
+                    unreachable!("matched above");
+                };
+                *body = inner;
+                true
+            }
+            Statement::Switch { label, cases, .. } => {
+                // a default that does nothing can go, unless the case
+                // before falls into it
+                let label = *label;
+                let last = cases.len().saturating_sub(1);
+                let idle = cases.iter().enumerate().position(|(case_index, case)| {
+                    let falls_in = case_index
+                        .checked_sub(1)
+                        .is_some_and(|previous| completes_normally(&cases[previous].body));
+                    case.is_default
+                        && match case.body.as_slice() {
+                            [] => case_index == last,
+                            [Statement::Break(broken)] => *broken == label && !falls_in,
+                            _ => false,
+                        }
+                });
+                if let Some(idle) = idle {
+                    cases.remove(idle);
+                    return true;
+                }
+                // switch statements only have to cover every value when
+                // they use patterns or `null`
+                let enhanced = cases.iter().any(|case| {
+                    case.labels
+                        .iter()
+                        .any(|label| matches!(label, CaseLabel::Pattern(..) | CaseLabel::Null))
+                });
+                if let Some(error) = cases.iter().position(is_exhaustion_default) {
+                    let mut rest = cases.clone();
+                    rest.remove(error);
+                    if enhanced && is_exhaustive(&rest) {
+                        cases.remove(error);
+                        return true;
+                    }
+                }
+                self.enum_switch(&mut statements[index])
+                    || self.string_switch(statements, index)
+                    || self.switched_copy(statements, index)
+                    || self.switch_expression(statements, index)
+            }
+            Statement::Stmt(Stmt::Assign {
+                target: Expr::Var(_),
+                value: Expr::Switch { .. },
+            }) => self.switched_copy(statements, index) || self.inline_switch(statements, index),
+            Statement::Return(Some(Expr::Switch { .. })) => self.switched_copy(statements, index),
+            Statement::Stmt(Stmt::Expr(_)) => self.string_switch(statements, index),
+            _ => false,
+        }
+    }
+}
+
+impl Tidier<'_> {
+    // `v = e; switch (v)` with `v` used nowhere else is `switch (e)`, as
+    // javac copies what pattern switches switch on. The switch can be a
+    // switch expression.
+    fn switched_copy(&self, statements: &mut Vec<Statement>, index: usize) -> bool {
+        let Some(previous) = index.checked_sub(1) else {
+            return false;
+        };
+        let (
+            Statement::Stmt(Stmt::Assign {
+                target: Expr::Var(copy),
+                value: copied,
+            }),
+            Some((Expr::Var(switched), _)),
+        ) = (&statements[previous], switch_of(&statements[index]))
+        else {
+            return false;
+        };
+        if copy != switched || self.mentions.get(copy) != Some(&2) {
+            return false;
+        }
+        let copied = copied.clone();
+        if let Some((value, _)) = switch_of_mut(&mut statements[index]) {
+            *value = copied;
+        }
+        statements.remove(previous);
+        true
+    }
+
+    // A switch whose cases all end in storing a value in the same variable
+    // and leaving it, or in throwing, is a switch expression, as javac
+    // leaves the value of the expression on the stack:
+    //   switch (k) { case 1: t = a; break; default: t = b; }
+    // is `t = switch (k) { case 1 -> a; default -> b; }`.
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-15.html#jls-15.28
+    fn switch_expression(&self, statements: &mut [Statement], index: usize) -> bool {
+        if self.major_version < 58 {
+            return false;
+        }
+        let Statement::Switch {
+            label,
+            value,
+            cases,
+        } = &statements[index]
+        else {
+            return false;
+        };
+        let label = *label;
+        let mut cases = cases.clone();
+        cases.retain(|case| !is_exhaustion_default(case));
+        if !cases.iter().any(|case| case.is_default) && !is_exhaustive(&cases) {
+            return false;
+        }
+
+        let mut target = None;
+        let count = cases.len();
+        for (case_index, case) in cases.iter_mut().enumerate() {
+            let mut body = std::mem::take(&mut case.body);
+            if let Some(Statement::Throw(_)) = body.last() {
+                if leaves(&body) {
+                    return false;
+                }
+                case.body = body;
+                continue;
+            }
+            if body.last() == Some(&Statement::Break(label)) {
+                body.pop();
+            } else if case_index + 1 != count {
+                return false;
+            }
+            let Some(Statement::Stmt(Stmt::Assign {
+                target: Expr::Var(var),
+                value,
+            })) = body.pop()
+            else {
+                return false;
+            };
+            if *target.get_or_insert(var) != var || leaves(&body) {
+                return false;
+            }
+            body.push(Statement::Yield(value));
+            case.body = body;
+        }
+        let Some(target) = target else {
+            return false;
+        };
+        // the variable only holds the value of the expression
+        let yields = cases
+            .iter()
+            .filter(|case| !matches!(case.body.last(), Some(Statement::Throw(_))))
+            .count();
+        if mentions(&statements[index..index + 1]).get(&target) != Some(&yields) {
+            return false;
+        }
+        let value = Box::new(value.clone());
+        statements[index] = Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(target),
+            value: Expr::Switch { value, cases },
+        });
+        true
+    }
+
+    // The temporary javac's switch expression leaves its value in goes into
+    // the next statement, when that reads it first thing.
+    fn inline_switch(&self, statements: &mut Vec<Statement>, index: usize) -> bool {
+        let Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(var),
+            value,
+        }) = &statements[index]
+        else {
+            return false;
+        };
+        let var = *var;
+        if self.body.variable(var).slot.is_some() || self.mentions.get(&var) != Some(&2) {
+            return false;
+        }
+        let Some(next) = statements.get(index + 1) else {
+            return false;
+        };
+        let exprs = match next {
+            Statement::Stmt(_)
+            | Statement::If { .. }
+            | Statement::Switch { .. }
+            | Statement::Return(_)
+            | Statement::Throw(_) => next.exprs(),
+            _ => return false,
+        };
+        if !conditions::can_move_to_read(value, &exprs, var) {
+            return false;
+        }
+        let Statement::Stmt(Stmt::Assign { value, .. }) = statements.remove(index) else {
+            unreachable!("matched above");
+        };
+        let mut value = Some(value);
+        for expr in statements[index].split_mut().1 {
+            expr.walk_mut(&mut |expr| {
+                if *expr == Expr::Var(var) {
+                    if let Some(value) = value.take() {
+                        *expr = value;
+                    }
+                }
+            });
+        }
+        true
+    }
+
+    // javac switches on an enum through an array mapping the ordinals of its
+    // constants to keys, see switches::switch_maps.
+    fn enum_switch(&self, statement: &mut Statement) -> bool {
+        let Statement::Switch { value, cases, .. } = statement else {
+            return false;
+        };
+        let Expr::ArrayElement { array, index } = value else {
+            return false;
+        };
+        let (
+            Expr::Field { owner, name, .. },
+            Expr::Invoke {
+                name: method,
+                object: Some(object),
+                args,
+                ..
+            },
+        ) = (&**array, &**index)
+        else {
+            return false;
+        };
+        let Some(map) = self.switch_maps.get(&(owner.clone(), name.clone())) else {
+            return false;
+        };
+        if method != "ordinal" || !args.is_empty() {
+            return false;
+        }
+        let mut constants = Vec::new();
+        for case in cases.iter() {
+            let mut labels = Vec::new();
+            for label in &case.labels {
+                let CaseLabel::Const(Const::Int(key)) = label else {
+                    return false;
+                };
+                let Some(constant) = map.get(key) else {
+                    return false;
+                };
+                labels.push(CaseLabel::Enum(constant.clone()));
+            }
+            constants.push(labels);
+        }
+        for (case, labels) in cases.iter_mut().zip(constants) {
+            case.labels = labels;
+        }
+        *value = (**object).clone();
+        true
+    }
+
+    // javac switches on a string in two steps: on its hash code, to find
+    // the position of the case whose string it equals, and then on that
+    // position:
+    //   s = value; i = -1;
+    //   switch (s.hashCode()) {
+    //       case 2112: if (s.equals("Aa")) i = 0; else if (s.equals("BB")) i = 1; break;
+    //       ...
+    //   }
+    //   switch (i) { case 0: ... }
+    // See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.11
+    fn string_switch(&self, statements: &mut Vec<Statement>, index: usize) -> bool {
+        let Some(start) = index.checked_sub(1) else {
+            return false;
+        };
+        let Some(
+            [Statement::Stmt(Stmt::Assign {
+                target: Expr::Var(position),
+                value: Expr::Const(Const::Int(-1)),
+            }), hashed, next],
+        ) = statements.get(start..index + 2)
+        else {
+            return false;
+        };
+        // the second switch can start a block that its cases break out of,
+        // or be a switch expression
+        let Some((Expr::Var(switched), cases)) = (match next {
+            Statement::Block { body, .. } => body.first(),
+            next => Some(next),
+        })
+        .and_then(switch_of) else {
+            return false;
+        };
+        // with only a default, there is nothing to switch on
+        let (hashed, hash, hash_cases) = match hashed {
+            Statement::Switch {
+                label,
+                value,
+                cases,
+            } => (Some(*label), value, cases.as_slice()),
+            Statement::Stmt(Stmt::Expr(value)) => (None, value, &[][..]),
+            _ => return false,
+        };
+        let Expr::Invoke {
+            kind: InvokeKind::Virtual,
+            owner,
+            name,
+            object: Some(subject),
+            args,
+            ..
+        } = hash
+        else {
+            return false;
+        };
+        let Expr::Var(subject) = **subject else {
+            return false;
+        };
+        if owner != "java/lang/String" || name != "hashCode" || !args.is_empty() {
+            return false;
+        }
+        if position != switched {
+            return false;
+        }
+
+        let mut positions = BTreeMap::new();
+        let last = hash_cases.len().saturating_sub(1);
+        for (case_index, case) in hash_cases.iter().enumerate() {
+            let mut found = Vec::new();
+            let Some(hashed) = hashed else {
+                return false;
+            };
+            let falls = string_positions(&case.body, subject, *position, hashed, None, &mut found);
+            match falls {
+                Some(falls) if !falls || case_index == last => {}
+                _ => return false,
+            }
+            for (string, key) in found {
+                let hash_code = CaseLabel::Const(Const::Int(hash_code(&string)));
+                if case.is_default || !case.labels.contains(&hash_code) {
+                    return false;
+                }
+                positions.insert(key, string);
+            }
+        }
+        let mut labels = Vec::new();
+        for case in cases {
+            let mut strings = Vec::new();
+            for label in &case.labels {
+                let CaseLabel::Const(Const::Int(key)) = label else {
+                    return false;
+                };
+                let Some(string) = positions.get(key) else {
+                    return false;
+                };
+                strings.push(CaseLabel::Const(Const::String(string.clone())));
+            }
+            labels.push(strings);
+        }
+
+        // the position is javac's own variable, used nowhere else
+        let position = *position;
+        let here = mentions(&statements[start..index + 2]);
+        if here.get(&position) != self.mentions.get(&position)
+            || mentions(&statements[index + 1..index + 2]).get(&position) != Some(&1)
+        {
+            return false;
+        }
+        // so is the copy of the string, which copy propagation leaves out
+        // when it is a variable
+        let copied = start
+            .checked_sub(1)
+            .and_then(|copy| match &statements[copy] {
+                Statement::Stmt(Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                }) if *var == subject => {
+                    let here = mentions(&statements[copy..index + 2]);
+                    (here.get(&subject) == self.mentions.get(&subject)).then(|| value.clone())
+                }
+                _ => None,
+            });
+        let first = start - usize::from(copied.is_some());
+        let value = copied.unwrap_or(Expr::Var(subject));
+
+        let switch = match &mut statements[index + 1] {
+            Statement::Block { body, .. } => &mut body[0],
+            switch => switch,
+        };
+        if let Some((switched, cases)) = switch_of_mut(switch) {
+            *switched = value;
+            for (case, labels) in cases.iter_mut().zip(labels) {
+                case.labels = labels;
+            }
+        }
+        statements.drain(first..=index);
+        true
+    }
+}
+
+// What a switch statement, or a switch expression whose value is stored or
+// returned, switches on, and its cases.
+fn switch_of(statement: &Statement) -> Option<(&Expr, &[Case])> {
+    match statement {
+        Statement::Switch { value, cases, .. } => Some((value, cases)),
+        Statement::Stmt(Stmt::Assign {
+            value: Expr::Switch { value, cases },
+            ..
+        })
+        | Statement::Return(Some(Expr::Switch { value, cases })) => Some((value, cases)),
+        _ => None,
+    }
+}
+
+fn switch_of_mut(statement: &mut Statement) -> Option<(&mut Expr, &mut Vec<Case>)> {
+    match statement {
+        Statement::Switch { value, cases, .. } => Some((value, cases)),
+        Statement::Stmt(Stmt::Assign {
+            value: Expr::Switch { value, cases },
+            ..
+        })
+        | Statement::Return(Some(Expr::Switch { value, cases })) => Some((value, cases)),
+        _ => None,
+    }
+}
+
+// Finds the strings a case of javac's switch on hash codes gives positions
+// to, following `if (s.equals("a")) i = 0; else if ...`, given the string
+// it is known to equal. Returns whether control falls out of the end, or
+// none for anything else.
+fn string_positions(
+    statements: &[Statement],
+    subject: Var,
+    position: Var,
+    label: Label,
+    equal: Option<&JavaString>,
+    found: &mut Vec<(JavaString, i32)>,
+) -> Option<bool> {
+    let Some((first, rest)) = statements.split_first() else {
+        return Some(true);
+    };
+    match first {
+        Statement::Break(broken) if *broken == label => Some(false),
+        Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(var),
+            value: Expr::Const(Const::Int(key)),
+        }) if *var == position => {
+            found.push((equal?.clone(), *key));
+            match rest {
+                [] => Some(true),
+                [Statement::Break(broken), ..] if *broken == label => Some(false),
+                _ => None,
+            }
+        }
+        Statement::If {
+            condition,
+            then,
+            otherwise,
+        } if equal.is_none() => {
+            let (string, is_equal) = string_equals(condition, subject)?;
+            let (equal_branch, other) = match is_equal {
+                true => (then, otherwise),
+                false => (otherwise, then),
+            };
+            let mut branch = |branch: &[Statement], equal| {
+                let statements = [branch, rest].concat();
+                string_positions(&statements, subject, position, label, equal, found)
+            };
+            let falls = branch(equal_branch, Some(string))?;
+            Some(branch(other, None)? || falls)
+        }
+        _ => None,
+    }
+}
+
+// The string a condition compares a variable with using `equals`, and
+// whether it is true when they are equal.
+fn string_equals(condition: &Expr, subject: Var) -> Option<(&JavaString, bool)> {
+    match condition {
+        Expr::Unary {
+            op: UnaryOp::Not,
+            operand,
+        } => string_equals(operand, subject).map(|(string, is_equal)| (string, !is_equal)),
+        Expr::Invoke {
+            kind: InvokeKind::Virtual,
+            name,
+            object: Some(object),
+            args,
+            ..
+        } if name == "equals" && **object == Expr::Var(subject) => match args.as_slice() {
+            [Expr::Const(Const::String(string))] => Some((string, true)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// https://docs.oracle.com/javase/8/docs/api/java/lang/String.html#hashCode--
+fn hash_code(string: &JavaString) -> i32 {
+    string.0.iter().fold(0i32, |hash, unit| {
+        hash.wrapping_mul(31).wrapping_add(i32::from(*unit))
+    })
+}
+
+impl Tidier<'_> {
+    // javac 8 to 10 compile a try-with-resources statement into a `try`
+    // statement keeping the exception the body throws, and a `finally`
+    // block closing the resource that adds what closing it throws to the
+    // exception as suppressed:
+    //   r = value; e = null;
+    //   try { ... } catch (Throwable t) { e = t; throw t; }
+    //   finally { if (r != null) { if (e != null) { try { r.close(); }
+    //       catch (Throwable x) { e.addSuppressed(x); } } else { r.close(); } } }
+    // javac 9 and 10 call a `$closeResource(e, r)` method they add to the
+    // class instead of the inner `if`, and leave the null check out for a
+    // new object.
+    fn resource_finally(&self, statements: &mut Vec<Statement>, index: usize) -> bool {
+        let Some(start) = index.checked_sub(2) else {
+            return false;
+        };
+        let [Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(resource),
+            value,
+        }), Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(primary),
+            value: Expr::Const(Const::Null),
+        }), Statement::Try {
+            resources,
+            body,
+            catches,
+            finally,
+        }] = &statements[start..=index]
+        else {
+            return false;
+        };
+        let (resource, primary) = (*resource, *primary);
+        let [Catch {
+            types,
+            var: caught,
+            body: handler,
+        }] = catches.as_slice()
+        else {
+            return false;
+        };
+        let keeps = handler[..]
+            == [
+                Statement::Stmt(Stmt::assign(Expr::Var(primary), Expr::Var(*caught))),
+                Statement::Throw(Expr::Var(*caught)),
+            ];
+        if !resources.is_empty() || !types.is_empty() || !keeps {
+            return false;
+        }
+        if !closes_resource(finally, resource, primary) {
+            return false;
+        }
+        // the variables are javac's own, used nowhere else
+        let here = mentions(&statements[start..=index]);
+        if here.get(&resource) != self.mentions.get(&resource)
+            || here.get(&primary) != self.mentions.get(&primary)
+            || mentions(body).contains_key(&primary)
+        {
+            return false;
+        }
+
+        let value = value.clone();
+        let Statement::Try { body, .. } = statements.remove(index) else {
+            unreachable!("matched above");
+        };
+        statements.splice(
+            start..index,
+            [Statement::Try {
+                resources: vec![Resource {
+                    var: resource,
+                    value: Some(value),
+                }],
+                body,
+                catches: Vec::new(),
+                finally: Vec::new(),
+            }],
+        );
+        true
+    }
+}
+
+// Whether a `finally` block is the one javac 8 to 10 close a resource in,
+// given the variable the exception is kept in.
+fn closes_resource(finally: &[Statement], resource: Var, primary: Var) -> bool {
+    let close = |statement: &Statement| match statement {
+        Statement::Stmt(Stmt::Expr(expr)) => exceptions::closed(expr) == Some(resource),
+        _ => false,
+    };
+    let checked = match finally {
+        [Statement::If {
+            condition,
+            then,
+            otherwise,
+        }] if otherwise.is_empty()
+            && exceptions::null_check(condition) == Some((resource, false)) =>
+        {
+            then.as_slice()
+        }
+        finally => finally,
+    };
+    match checked {
+        // javac 9 and 10
+        [Statement::Stmt(Stmt::Expr(Expr::Invoke {
+            kind: InvokeKind::Static,
+            name,
+            args,
+            ..
+        }))] => name == "$closeResource" && args[..] == [Expr::Var(primary), Expr::Var(resource)],
+        // javac 8
+        [Statement::If {
+            condition,
+            then,
+            otherwise,
+        }] => {
+            let (suppressing, closing) = match exceptions::null_check(condition) {
+                Some((var, false)) if var == primary => (then, otherwise),
+                Some((var, true)) if var == primary => (otherwise, then),
+                _ => return false,
+            };
+            let suppresses = match suppressing.as_slice() {
+                [Statement::Try {
+                    resources,
+                    body,
+                    catches,
+                    finally,
+                }] => match (body.as_slice(), catches.as_slice()) {
+                    ([closed], [catch]) => {
+                        let suppressed = match catch.body.as_slice() {
+                            [Statement::Stmt(Stmt::Expr(Expr::Invoke {
+                                name,
+                                object: Some(object),
+                                args,
+                                ..
+                            }))] => {
+                                name == "addSuppressed"
+                                    && **object == Expr::Var(primary)
+                                    && args[..] == [Expr::Var(catch.var)]
+                            }
+                            _ => false,
+                        };
+                        resources.is_empty()
+                            && finally.is_empty()
+                            && close(closed)
+                            && catch.types.is_empty()
+                            && suppressed
+                    }
+                    _ => false,
+                },
+                _ => false,
+            };
+            suppresses && matches!(closing.as_slice(), [closed] if close(closed))
+        }
+        _ => false,
+    }
+}
+
+// How many statements mention each variable, nested ones included.
+fn mentions(statements: &[Statement]) -> BTreeMap<Var, usize> {
+    let mut mentions = BTreeMap::new();
+    any_statement(statements, &mut |statement| {
+        let mut vars = BTreeSet::new();
+        for expr in statement.exprs() {
+            expr.walk(&mut |expr| {
+                if let Expr::Var(var) = expr {
+                    vars.insert(*var);
+                }
+            });
+        }
+        match statement {
+            Statement::Declare { var, .. } => {
+                vars.insert(*var);
+            }
+            Statement::Try {
+                resources, catches, ..
+            } => {
+                vars.extend(resources.iter().map(|resource| resource.var));
+                vars.extend(catches.iter().map(|catch| catch.var));
+            }
+            _ => {}
+        }
+        vars.extend(statement.bindings());
+        for var in vars {
+            *mentions.entry(var).or_insert(0) += 1;
+        }
+        false
+    });
+    mentions
+}
+
+// The number of statements, nested ones included.
+fn size(statements: &[Statement]) -> usize {
+    statements
+        .iter()
+        .map(|statement| {
+            1 + statement
+                .children()
+                .into_iter()
+                .map(|child| size(child))
+                .sum::<usize>()
+        })
+        .sum()
+}
+
+// Whether Java can see control reaching the end of the statements, which
+// is only false when it can be sure.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.22
+pub fn completes_normally(statements: &[Statement]) -> bool {
+    let Some(last) = statements.last() else {
+        return true;
+    };
+    match last {
+        Statement::Break(_)
+        | Statement::Continue(_)
+        | Statement::Return(_)
+        | Statement::Throw(_) => false,
+        Statement::If {
+            then, otherwise, ..
+        } => otherwise.is_empty() || completes_normally(then) || completes_normally(otherwise),
+        Statement::Block { label, body } => completes_normally(body) || breaks(body, *label),
+        Statement::While {
+            label,
+            condition,
+            body,
+        } => *condition != Expr::Const(Const::Boolean(true)) || breaks(body, *label),
+        Statement::Switch { label, cases, .. } => {
+            !cases.iter().any(|case| case.is_default)
+                || cases
+                    .last()
+                    .is_none_or(|case| completes_normally(&case.body))
+                || cases.iter().any(|case| breaks(&case.body, *label))
+        }
+        Statement::Synchronized { body, .. } => completes_normally(body),
+        Statement::Try {
+            body,
+            catches,
+            finally,
+            ..
+        } => {
+            (completes_normally(body)
+                || catches.iter().any(|catch| completes_normally(&catch.body)))
+                && completes_normally(finally)
+        }
+        _ => true,
+    }
+}
+
+// Puts what each lambda captures in place of its parameters for them, and
+// numbers the labels in its body from `labels` on.
+fn close_lambdas(statements: &mut [Statement], labels: &mut usize) {
+    for statement in statements {
+        let (children, exprs) = statement.split_mut();
+        for expr in exprs {
+            close_lambdas_in(expr, labels);
+        }
+        for child in children {
+            close_lambdas(child, labels);
+        }
+    }
+}
+
+fn close_lambdas_in(expr: &mut Expr, labels: &mut usize) {
+    for child in expr.children_mut() {
+        close_lambdas_in(child, labels);
+    }
+    match expr {
+        Expr::Switch { cases, .. } => {
+            for case in cases {
+                close_lambdas(&mut case.body, labels);
+            }
+        }
+        Expr::Lambda {
+            captured,
+            parameters,
+            body,
+            ..
+        } => {
+            let values: HashMap<Var, Expr> = parameters
+                .drain(..captured.len())
+                .zip(std::mem::take(captured))
+                .collect();
+            let mut last = 0;
+            any_statement(body, &mut |statement| {
+                last = last.max(statement.label().map_or(0, |label| label.0));
+                false
+            });
+            let offset = *labels;
+            *labels += last;
+            walk_statements_mut(body, &mut |statement| {
+                match statement {
+                    Statement::Block { label, .. }
+                    | Statement::While { label, .. }
+                    | Statement::DoWhile { label, .. }
+                    | Statement::For { label, .. }
+                    | Statement::Switch { label, .. }
+                    | Statement::Break(label)
+                    | Statement::Continue(label) => label.0 += offset,
+                    _ => {}
+                }
+                for expr in statement.exprs_mut() {
+                    expr.walk_mut(&mut |expr| {
+                        if let Expr::Var(var) = expr {
+                            if let Some(value) = values.get(var) {
+                                *expr = value.clone();
+                            }
+                        }
+                    });
+                }
+            });
+        }
+        _ => {}
+    }
+}
+
+/// Calls `f` on each of the statements and then on the statements nested
+/// in it.
+pub fn walk_statements_mut(statements: &mut [Statement], f: &mut impl FnMut(&mut Statement)) {
+    for statement in statements {
+        f(statement);
+        for child in statement.children_mut() {
+            walk_statements_mut(child, f);
+        }
+    }
+}
+
+fn any_statement(statements: &[Statement], f: &mut impl FnMut(&Statement) -> bool) -> bool {
+    statements.iter().any(|statement| {
+        f(statement)
+            || statement
+                .children()
+                .into_iter()
+                .any(|child| any_statement(child, f))
+    })
+}
+
+// Whether the statements jump anywhere outside of them, which the cases of
+// switch expressions can't.
+fn leaves(statements: &[Statement]) -> bool {
+    let mut inside = BTreeSet::new();
+    any_statement(statements, &mut |statement| {
+        inside.extend(statement.label());
+        false
+    });
+    any_statement(statements, &mut |statement| match statement {
+        Statement::Break(label) | Statement::Continue(label) => !inside.contains(label),
+        Statement::Return(_) => true,
+        _ => false,
+    })
+}
+
+// The default case javac adds to switches that have to cover every value,
+// throwing an error for an enum constant or a subclass added after
+// compiling them.
+fn is_exhaustion_default(case: &Case) -> bool {
+    case.is_default
+        && case.labels.is_empty()
+        && matches!(
+            case.body.as_slice(),
+            [Statement::Throw(Expr::New { class, args, .. })]
+                if (class == "java/lang/IncompatibleClassChangeError" && args.is_empty())
+                    || (class == "java/lang/MatchException"
+                        && args[..] == [Expr::Const(Const::Null), Expr::Const(Const::Null)])
+        )
+}
+
+// Whether the labels of a switch without a default can cover every value,
+// which only enum constants and patterns can.
+fn is_exhaustive(cases: &[Case]) -> bool {
+    cases.iter().all(|case| {
+        case.labels.iter().all(|label| {
+            matches!(
+                label,
+                CaseLabel::Enum(_) | CaseLabel::Pattern(..) | CaseLabel::Null
+            )
+        })
+    })
+}
+
+fn breaks(statements: &[Statement], label: Label) -> bool {
+    any_statement(statements, &mut |statement| {
+        *statement == Statement::Break(label)
+    })
+}
+
+fn continues(statements: &[Statement], label: Label) -> bool {
+    any_statement(statements, &mut |statement| {
+        *statement == Statement::Continue(label)
+    })
+}
+
+fn jumps_to(statements: &[Statement], label: Label) -> bool {
+    breaks(statements, label) || continues(statements, label)
+}
+
+// Where a variable is used, as the index of each statement down the tree
+// and which of its statement lists the next one is in. The last entry is
+// the statement using it, with `usize::MAX` as the list.
+type Path = Vec<(usize, usize)>;
+
+// Declares every variable but the parameters in the innermost statement list
+// holding all its uses, with its first value when that's where it's first
+// assigned. Variables that Java can't see are assigned before they are read
+// are declared with a default value.
+fn declare_variables(body: &Body, statements: &mut Vec<Statement>) {
+    let mut uses: BTreeMap<Var, Vec<Path>> = BTreeMap::new();
+    find_uses(statements, &mut Vec::new(), &mut uses);
+    // catch parameters and resources are declared by their `try`, pattern
+    // variables by their patterns, and the variables of lambdas were
+    // declared when their bodies were structured
+    let mut parameters: BTreeSet<Var> = body.parameters.iter().copied().collect();
+    any_statement(statements, &mut |statement| {
+        parameters.extend(statement.bindings());
+        if let Statement::Declare { var, .. } = statement {
+            parameters.insert(*var);
+        }
+        if let Statement::Try {
+            resources, catches, ..
+        } = statement
+        {
+            parameters.extend(catches.iter().map(|catch| catch.var));
+            let declared = resources.iter().filter(|resource| resource.value.is_some());
+            parameters.extend(declared.map(|resource| resource.var));
+        }
+        false
+    });
+
+    let mut declarations: Vec<(Path, Var)> = Vec::new();
+    for (var, paths) in uses {
+        if parameters.contains(&var) {
+            continue;
+        }
+        let mut depth = 0;
+        while paths.iter().all(|path| {
+            path.len() > depth + 1 && path[depth] == paths[0][depth] && path[depth].1 != usize::MAX
+        }) {
+            depth += 1;
+        }
+        let first = paths.iter().map(|path| path[depth].0).min().unwrap_or(0);
+        let mut position = paths[0][..depth].to_vec();
+        position.push((first, 0));
+        declarations.push((position, var));
+    }
+
+    // later positions first, so inserting doesn't move the others
+    declarations.sort_by(|a, b| b.0.cmp(&a.0));
+    for (position, var) in declarations {
+        let (index, _) = *position.last().expect("positions end in an index");
+        let list = list_at(statements, &position[..position.len() - 1]);
+        declare(body, list, index, var);
+    }
+}
+
+fn find_uses(statements: &[Statement], path: &mut Path, uses: &mut BTreeMap<Var, Vec<Path>>) {
+    for (index, statement) in statements.iter().enumerate() {
+        let mut vars = BTreeSet::new();
+        for expr in statement.exprs() {
+            expr.walk(&mut |expr| {
+                if let Expr::Var(var) = expr {
+                    vars.insert(*var);
+                }
+            });
+        }
+        match statement {
+            Statement::Declare { var, .. } => {
+                vars.insert(*var);
+            }
+            Statement::Try { resources, .. } => {
+                vars.extend(resources.iter().map(|resource| resource.var));
+            }
+            _ => {}
+        }
+        for var in vars {
+            let mut used = path.clone();
+            used.push((index, usize::MAX));
+            uses.entry(var).or_default().push(used);
+        }
+        for (child, list) in statement.children().into_iter().enumerate() {
+            path.push((index, child));
+            find_uses(list, path, uses);
+            path.pop();
+        }
+    }
+}
+
+fn list_at<'a>(
+    statements: &'a mut Vec<Statement>,
+    path: &[(usize, usize)],
+) -> &'a mut Vec<Statement> {
+    match path.split_first() {
+        None => statements,
+        Some(((index, child), rest)) => {
+            let list = statements[*index]
+                .children_mut()
+                .into_iter()
+                .nth(*child)
+                .expect("paths lead to statement lists");
+            list_at(list, rest)
+        }
+    }
+}
+
+fn declare(body: &Body, statements: &mut Vec<Statement>, index: usize, var: Var) {
+    let assigned_value = |statement: &Statement| match statement {
+        Statement::Stmt(Stmt::Assign {
+            target: Expr::Var(target),
+            value,
+        }) if *target == var && !value.uses(var) => Some(value.clone()),
+        _ => None,
+    };
+
+    if let Some(value) = assigned_value(&statements[index]) {
+        statements[index] = Statement::Declare {
+            var,
+            value: Some(value),
+        };
+        return;
+    }
+    // `for (int i = 0; ...)`, when nothing after the loop uses `i`
+    let only_in_loop = !statements[index + 1..].iter().any(|statement| {
+        any_statement(std::slice::from_ref(statement), &mut |statement| {
+            statement.exprs().iter().any(|expr| expr.uses(var))
+        })
+    });
+    if let Statement::For { init, .. } = &mut statements[index] {
+        if let [first] = init.as_mut_slice() {
+            if let (Some(value), true) = (assigned_value(first), only_in_loop) {
+                *first = Statement::Declare {
+                    var,
+                    value: Some(value),
+                };
+                return;
+            }
+        }
+    }
+
+    let value = match definitely_assigned(&statements[index..], var) {
+        true => None,
+        false => Some(default_value(body.variable(var))),
+    };
+    statements.insert(index, Statement::Declare { var, value });
+}
+
+fn default_value(variable: &Variable) -> Expr {
+    Expr::Const(match (&variable.field_type, variable.kind) {
+        (Some(FieldType::Boolean), _) => Const::Boolean(false),
+        (Some(FieldType::Char), _) => Const::Char(0),
+        (_, Kind::Int) => Const::Int(0),
+        (_, Kind::Long) => Const::Long(0),
+        (_, Kind::Float) => Const::Float(0.0),
+        (_, Kind::Double) => Const::Double(0.0),
+        (_, Kind::Reference) => Const::Null,
+    })
+}
+
+// Whether Java sees `var` as assigned before every read of it in the
+// statements.
+// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-16.html
+fn definitely_assigned(statements: &[Statement], var: Var) -> bool {
+    let mut analysis = Assignment {
+        var,
+        breaks: HashMap::new(),
+        continues: HashMap::new(),
+    };
+    analysis.list(statements, false).is_some()
+}
+
+// The analysis of one variable. States are whether it is definitely
+// assigned, which is vacuously true where control can't reach; `None` is a
+// read where it isn't.
+struct Assignment {
+    var: Var,
+    // the state at every jump to each label, joined
+    breaks: HashMap<Label, bool>,
+    continues: HashMap<Label, bool>,
+}
+
+impl Assignment {
+    fn list(&mut self, statements: &[Statement], mut state: bool) -> Option<bool> {
+        for statement in statements {
+            state = self.statement(statement, state)?;
+        }
+        Some(state)
+    }
+
+    fn statement(&mut self, statement: &Statement, state: bool) -> Option<bool> {
+        match statement {
+            Statement::Stmt(Stmt::Assign {
+                target: Expr::Var(target),
+                value,
+            }) => {
+                let state = self.expr(value, state)?;
+                Some(state || *target == self.var)
+            }
+            Statement::Stmt(stmt) => {
+                let mut state = state;
+                for expr in stmt.exprs() {
+                    state = self.expr(expr, state)?;
+                }
+                Some(state)
+            }
+            Statement::Declare { var, value } => {
+                let state = match value {
+                    Some(value) => self.expr(value, state)?,
+                    None => state,
+                };
+                Some(state || (*var == self.var && value.is_some()))
+            }
+            Statement::Block { label, body } => {
+                let end = self.list(body, state)?;
+                Some(end && self.breaks.remove(label).unwrap_or(true))
+            }
+            Statement::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                let state = self.expr(condition, state)?;
+                let then = self.list(then, state)?;
+                let otherwise = self.list(otherwise, state)?;
+                Some(then && otherwise)
+            }
+            Statement::While {
+                label,
+                condition,
+                body,
+            } => {
+                let state = self.expr(condition, state)?;
+                self.list(body, state)?;
+                self.continues.remove(label);
+                let broken = self.breaks.remove(label).unwrap_or(true);
+                let infinite = *condition == Expr::Const(Const::Boolean(true));
+                Some((infinite || state) && broken)
+            }
+            Statement::DoWhile {
+                label,
+                body,
+                condition,
+            } => {
+                let end = self.list(body, state)?;
+                let end = end && self.continues.remove(label).unwrap_or(true);
+                let state = self.expr(condition, end)?;
+                Some(state && self.breaks.remove(label).unwrap_or(true))
+            }
+            Statement::For {
+                label,
+                init,
+                condition,
+                update,
+                body,
+            } => {
+                let state = self.list(init, state)?;
+                let state = self.expr(condition, state)?;
+                let end = self.list(body, state)?;
+                let mut end = end && self.continues.remove(label).unwrap_or(true);
+                for stmt in update {
+                    end = self.statement(&Statement::Stmt(stmt.clone()), end)?;
+                }
+                Some(state && self.breaks.remove(label).unwrap_or(true))
+            }
+            Statement::Switch {
+                label,
+                value,
+                cases,
+            } => {
+                let state = self.expr(value, state)?;
+                // falling through only keeps what both ways in assign
+                let mut previous = true;
+                for case in cases {
+                    previous = self.list(&case.body, state && previous)?;
+                }
+                let has_default = cases.iter().any(|case| case.is_default);
+                let broken = self.breaks.remove(label).unwrap_or(true);
+                Some((has_default || state) && previous && broken)
+            }
+            Statement::Break(label) => {
+                let joined = self.breaks.entry(*label).or_insert(true);
+                *joined &= state;
+                Some(true)
+            }
+            Statement::Continue(label) => {
+                let joined = self.continues.entry(*label).or_insert(true);
+                *joined &= state;
+                Some(true)
+            }
+            Statement::Return(value) => {
+                if let Some(value) = value {
+                    self.expr(value, state)?;
+                }
+                Some(true)
+            }
+            Statement::Throw(value) | Statement::Yield(value) => {
+                self.expr(value, state)?;
+                Some(true)
+            }
+            // what an assertion assigns isn't assigned after it, as it may
+            // not run
+            Statement::Assert { condition, message } => {
+                let checked = self.expr(condition, state)?;
+                if let Some(message) = message {
+                    self.expr(message, checked)?;
+                }
+                Some(state)
+            }
+            // a `catch` or `finally` block can start before anything in the
+            // `try` block has run
+            Statement::Synchronized { lock, body } => {
+                let state = self.expr(lock, state)?;
+                self.list(body, state)
+            }
+            Statement::Try {
+                resources,
+                body,
+                catches,
+                finally,
+            } => {
+                let mut opened = state;
+                for resource in resources {
+                    opened = match &resource.value {
+                        Some(value) => self.expr(value, opened)? || resource.var == self.var,
+                        None => self.expr(&Expr::Var(resource.var), opened)?,
+                    };
+                }
+                let mut end = self.list(body, opened)?;
+                for catch in catches {
+                    end &= self.list(&catch.body, state)?;
+                }
+                let finally = self.list(finally, state)?;
+                Some(end || finally)
+            }
+        }
+    }
+
+    // Expressions are evaluated left to right, and an assignment inside one
+    // counts from then on.
+    fn expr(&mut self, expr: &Expr, state: bool) -> Option<bool> {
+        match expr {
+            Expr::Var(var) if *var == self.var && !state => None,
+            // what the cases assign only counts in them
+            Expr::Switch { value, cases } => {
+                let state = self.expr(value, state)?;
+                for case in cases {
+                    let guarded = match &case.guard {
+                        Some(guard) => self.expr(guard, state)?,
+                        None => state,
+                    };
+                    self.list(&case.body, guarded)?;
+                }
+                Some(state)
+            }
+            // a lambda's body can only read what is assigned before it,
+            // and what it assigns doesn't count outside of it
+            Expr::Lambda { body, .. } => {
+                self.list(body, state)?;
+                Some(state)
+            }
+            Expr::Assign { target, value } => {
+                let mut state = state;
+                if !matches!(**target, Expr::Var(_)) {
+                    for child in target.children() {
+                        state = self.expr(child, state)?;
+                    }
+                }
+                let state = self.expr(value, state)?;
+                Some(state || **target == Expr::Var(self.var))
+            }
+            expr => {
+                let mut state = state;
+                for child in expr.children() {
+                    state = self.expr(child, state)?;
+                }
+                Some(state)
+            }
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::cfg::BlockId;
+use crate::decompile::ClassPath;
+use crate::descriptor::FieldType;
+use crate::dominators::DominatorTree;
+use crate::instruction::{self, Instruction, Opcode, Operand};
+use crate::ir::{Body, Const, Expr, InvokeKind, Kind, Pattern, Stmt, Terminator, Variable};
+use crate::structure::CaseLabel;
+use crate::types::{Attribute, ClassFile, ConstantPool, ConstantPoolType};
+use crate::{conditions, exceptions, patterns};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The enum constants of the `$SwitchMap$` arrays javac makes for switches
+/// on enums, by the class and name of the array, and then by the number
+/// the ordinal of each constant maps to.
+pub type SwitchMaps = HashMap<(String, String), BTreeMap<i32, String>>;
+
+/// Reads the `$SwitchMap$` arrays the body switches on from the classes
+/// holding them.
+///
+/// javac compiles `switch (e)` on an enum into a switch on
+/// `Outer$1.$SwitchMap$pkg$E[e.ordinal()]`, an array that the static
+/// initializer of a synthetic class fills with a number for each constant
+/// the compilation unit switches on, so the switch keeps working when the
+/// enum's constants are reordered:
+///   getstatic $SwitchMap$pkg$E; getstatic E.RED; invokevirtual ordinal;
+///   iconst_1; iastore
+/// Arrays whose class can't be found are left out.
+pub fn switch_maps(body: &Body, classes: &dyn ClassPath) -> SwitchMaps {
+    let mut fields = Vec::new();
+    for block in &body.blocks {
+        let stmts = block.stmts.iter().flat_map(|stmt| stmt.exprs());
+        for expr in stmts.chain(block.terminator.exprs()) {
+            expr.walk(&mut |expr| {
+                if let Expr::Field {
+                    owner,
+                    name,
+                    object: None,
+                    ..
+                } = expr
+                {
+                    if name.starts_with("$SwitchMap$") {
+                        fields.push((owner.clone(), name.clone()));
+                    }
+                }
+            });
+        }
+    }
+
+    let mut maps = SwitchMaps::new();
+    for (owner, name) in fields {
+        if maps.contains_key(&(owner.clone(), name.clone())) {
+            continue;
+        }
+        let Some(class_file) = classes.class_file(&owner) else {
+            continue;
+        };
+        if let Some(map) = read_switch_map(&class_file, &owner, &name) {
+            maps.insert((owner, name), map);
+        }
+    }
+    maps
+}
+
+fn read_switch_map(
+    class_file: &ClassFile,
+    owner: &str,
+    field: &str,
+) -> Option<BTreeMap<i32, String>> {
+    let constant_pool = class_file.constant_pool();
+    let initializer = class_file
+        .methods
+        .iter()
+        .find(|method| method.name(constant_pool) == Some("<clinit>"))?;
+    let Some(Attribute::Code { code, .. }) = initializer.code() else {
+        return None;
+    };
+    let instructions: Vec<Instruction> = instruction::decode(code)
+        .ok()?
+        .into_iter()
+        .map(|(_, instruction)| instruction)
+        .collect();
+
+    let mut map = BTreeMap::new();
+    for window in instructions.windows(5) {
+        let [array, constant, ordinal, key, store] = window else {
+            continue;
+        };
+        let Some((array_owner, array_name)) = member(constant_pool, array, Opcode::Getstatic)
+        else {
+            continue;
+        };
+        let Some((_, constant)) = member(constant_pool, constant, Opcode::Getstatic) else {
+            continue;
+        };
+        let is_ordinal = member(constant_pool, ordinal, Opcode::Invokevirtual)
+            .is_some_and(|(_, name)| name == "ordinal");
+        let Some(key) = int_constant(constant_pool, key) else {
+            continue;
+        };
+        if array_owner == owner
+            && array_name == field
+            && is_ordinal
+            && store.opcode == Opcode::Iastore
+        {
+            map.insert(key, constant.to_string());
+        }
+    }
+    (!map.is_empty()).then_some(map)
+}
+
+/// The labels of the pattern switches [`pattern_switches`] turned back,
+/// by the block switching and then by key.
+pub type PatternSwitches = HashMap<BlockId, BTreeMap<i32, PatternCase>>;
+
+/// The label a key of a pattern switch stands for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternCase {
+    pub label: CaseLabel,
+    /// The `when` clause of a guarded pattern.
+    pub guard: Option<Expr>,
+}
+
+const SWITCH_BOOTSTRAPS: &str = "java/lang/runtime/SwitchBootstraps";
+
+/// Turns back the switches javac compiles pattern matching `switch`es
+/// into, in a body out of SSA form, leaving the labels of their keys to
+/// the structurer.
+///
+/// javac switches on the number of the first label matching, which a
+/// `SwitchBootstraps` method finds starting from a given label, -1 for
+/// `null`. The code of a label binds its pattern, and starts over from the
+/// next label when its guard doesn't hold:
+///   sel = o; i = 0;
+///   switch (typeSwitch(sel, i)) {
+///       case 0: s = (String) sel; if (!guard) { i = 1; goto switch; } ...
+///   }
+/// `Objects.requireNonNull(o)` comes first unless there is a `case null`.
+/// Switches on enums with patterns among the constants use `enumSwitch`
+/// the same way.
+/// See https://docs.oracle.com/javase/specs/jls/se21/html/jls-14.html#jls-14.11.1
+pub fn pattern_switches(body: &mut Body, classes: &dyn ClassPath) -> PatternSwitches {
+    let mut switches = PatternSwitches::new();
+    let mut dominators = exceptions::dominators(body, None);
+    for id in 0..body.blocks.len() {
+        if !dominators.is_reachable(id) {
+            continue;
+        }
+        if let Some(cases) = pattern_switch(body, &dominators, id, classes) {
+            switches.insert(id, cases);
+            dominators = exceptions::dominators(body, None);
+        }
+    }
+    switches
+}
+
+// A pattern switch being turned back, and the blocks jumping to each block.
+struct Switch<'a> {
+    id: BlockId,
+    selector: &'a Expr,
+    counter: &'a Expr,
+    predecessors: &'a [Vec<BlockId>],
+}
+
+// What the code of a key of a pattern switch does before its case's own.
+struct Binding {
+    key: i32,
+    target: BlockId,
+    field_type: FieldType,
+    // none for a variable the case doesn't use, which javac doesn't store
+    pattern: Option<Pattern>,
+    // the statements binding the pattern
+    stmts: usize,
+    // the condition and target of a guard, and where it starts over
+    guard: Option<(Expr, BlockId, BlockId)>,
+}
+
+fn pattern_switch(
+    body: &mut Body,
+    dominators: &DominatorTree,
+    id: BlockId,
+    classes: &dyn ClassPath,
+) -> Option<BTreeMap<i32, PatternCase>> {
+    let Terminator::Switch {
+        value: Expr::InvokeDynamic {
+            bootstrap, args, ..
+        },
+        cases,
+        ..
+    } = &body.blocks[id].terminator
+    else {
+        return None;
+    };
+    let is_enum = bootstrap.is(SWITCH_BOOTSTRAPS, "enumSwitch");
+    if !is_enum && !bootstrap.is(SWITCH_BOOTSTRAPS, "typeSwitch") {
+        return None;
+    }
+    let [selector, restart] = args.as_slice() else {
+        return None;
+    };
+    let (selector, restart) = (selector.clone(), restart.clone());
+    let cases = cases.clone();
+    let labels = bootstrap.arguments.clone();
+
+    let mut predecessors = vec![Vec::new(); body.blocks.len()];
+    for (from, block) in body.blocks.iter().enumerate() {
+        if dominators.is_reachable(from) {
+            for target in block.terminator.targets() {
+                predecessors[target].push(from);
+            }
+        }
+    }
+
+    let switch = Switch {
+        id,
+        selector: &selector,
+        counter: &restart,
+        predecessors: &predecessors,
+    };
+    // the labels, and what the code of each does to bind them
+    let mut found = BTreeMap::new();
+    let mut bindings = Vec::new();
+    for (key, target) in &cases {
+        let label = match usize::try_from(*key).ok().map(|key| labels.get(key)) {
+            None if *key == -1 => CaseLabel::Null,
+            None | Some(None) => return None,
+            Some(Some(Const::String(name))) if is_enum => CaseLabel::Enum(name.to_string_lossy()),
+            Some(Some(Const::Class(field_type))) => {
+                if predecessors[*target] != [id] {
+                    return None;
+                }
+                bindings.push(bind(
+                    body, dominators, &switch, *key, *target, field_type, classes,
+                )?);
+                continue;
+            }
+            Some(Some(constant @ (Const::String(_) | Const::Int(_)))) => {
+                CaseLabel::Const(constant.clone())
+            }
+            Some(Some(_)) => return None,
+        };
+        found.insert(*key, PatternCase { label, guard: None });
+    }
+
+    // a guard's variable counting labels is only javac's, stored before the
+    // switch and when starting over
+    let restarts: BTreeSet<BlockId> = bindings
+        .iter()
+        .filter_map(|binding| binding.guard.as_ref().map(|(_, _, restart)| *restart))
+        .collect();
+    let entries: Vec<BlockId> = predecessors[id]
+        .iter()
+        .copied()
+        .filter(|from| !restarts.contains(from))
+        .collect();
+    // where the switch is entered, unless the counter starts out as a
+    // constant and that can't be told
+    let entry = match (body.blocks[id].stmts.is_empty(), entries.as_slice()) {
+        (false, _) if restarts.is_empty() => Some(id),
+        (true, [entry]) => Some(*entry),
+        _ if restart == Expr::Const(Const::Int(0)) => None,
+        _ => return None,
+    };
+    let stmts: &[Stmt] = entry.map_or(&[], |entry| &body.blocks[entry].stmts);
+    let mut init = stmts.len();
+    if let Expr::Var(counter) = restart {
+        let Some(Stmt::Assign {
+            target: Expr::Var(var),
+            value: Expr::Const(Const::Int(0)),
+        }) = stmts.last()
+        else {
+            return None;
+        };
+        let (stores, reads, _) = patterns::mentions(body, counter);
+        if *var != counter || stores != restarts.len() + 1 || reads != 1 {
+            return None;
+        }
+        init -= 1;
+    } else if restart != Expr::Const(Const::Int(0)) || !restarts.is_empty() {
+        return None;
+    }
+    // the selector is checked for null after it is evaluated
+    let null_check = (!found.contains_key(&-1))
+        .then(|| {
+            let stmts = &stmts[..init];
+            let copied = match stmts.last() {
+                Some(Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                }) if Expr::Var(*var) == selector => Some(value),
+                _ => None,
+            };
+            let at = stmts.len().checked_sub(1 + usize::from(copied.is_some()))?;
+            let Stmt::Expr(Expr::Invoke {
+                kind: InvokeKind::Static,
+                owner,
+                name,
+                args,
+                ..
+            }) = &stmts[at]
+            else {
+                return None;
+            };
+            let checked = copied.unwrap_or(&selector);
+            (owner == "java/util/Objects"
+                && name == "requireNonNull"
+                && args[..] == [checked.clone()])
+            .then_some(at)
+        })
+        .flatten();
+
+    for binding in bindings {
+        let stmts = &mut body.blocks[binding.target].stmts;
+        stmts.drain(..binding.stmts);
+        let field_type = binding.field_type;
+        let pattern = binding.pattern.unwrap_or_else(|| {
+            Pattern::Binding(body.new_variable(Variable {
+                kind: Kind::Reference,
+                slot: None,
+                name: None,
+                field_type: Some(field_type.clone()),
+            }))
+        });
+        let (target, guard) = match binding.guard {
+            Some((condition, target, restart)) => {
+                body.blocks[binding.target].terminator = Terminator::Unreachable;
+                body.blocks[restart].terminator = Terminator::Unreachable;
+                (target, Some(condition))
+            }
+            None => (binding.target, None),
+        };
+        let label = CaseLabel::Pattern(field_type, pattern);
+        found.insert(binding.key, PatternCase { label, guard });
+        if let Terminator::Switch { cases, .. } = &mut body.blocks[id].terminator {
+            for (key, case) in cases.iter_mut() {
+                if *key == binding.key {
+                    *case = target;
+                }
+            }
+        }
+    }
+    if let Terminator::Switch { value, .. } = &mut body.blocks[id].terminator {
+        *value = selector;
+    }
+    if let Some(entry) = entry {
+        let stmts = &mut body.blocks[entry].stmts;
+        if let Expr::Var(_) = restart {
+            stmts.pop();
+        }
+        if let Some(at) = null_check {
+            stmts.remove(at);
+        }
+    }
+    Some(found)
+}
+
+// Finds the statements binding a type pattern at the start of the code of
+// its key, and its guard.
+fn bind(
+    body: &Body,
+    dominators: &DominatorTree,
+    switch: &Switch,
+    key: i32,
+    target: BlockId,
+    field_type: &FieldType,
+    classes: &dyn ClassPath,
+) -> Option<Binding> {
+    let block = &body.blocks[target];
+    // there is no cast to the type of the selector
+    let var =
+        patterns::cast_store(&block.stmts, switch.selector, field_type).or_else(|| {
+            match block.stmts.first()? {
+                Stmt::Assign {
+                    target: Expr::Var(var),
+                    value,
+                } if value == switch.selector => Some(*var),
+                _ => None,
+            }
+        });
+    let pattern = match var {
+        Some(var) if patterns::is_scoped(body, dominators, var, target) => Some(
+            patterns::deconstruct(body, dominators, target, 1, var, field_type, classes)
+                .unwrap_or(Pattern::Binding(var)),
+        ),
+        Some(_) => return None,
+        None => None,
+    };
+    let mut binding = Binding {
+        key,
+        target,
+        field_type: field_type.clone(),
+        stmts: pattern
+            .as_ref()
+            .map_or(0, |pattern| 1 + patterns::accessor_calls(pattern)),
+        pattern,
+        guard: None,
+    };
+
+    let Terminator::If {
+        condition,
+        then,
+        otherwise,
+    } = &block.terminator
+    else {
+        return Some(binding);
+    };
+    if block.stmts.len() != binding.stmts {
+        return Some(binding);
+    }
+    let starts_over = |restart: BlockId| {
+        let block = &body.blocks[restart];
+        let next = Expr::Const(Const::Int(key + 1));
+        matches!(switch.counter, Expr::Var(_))
+            && switch.predecessors[restart] == [target]
+            && block.terminator == Terminator::Goto(switch.id)
+            && matches!(
+                block.stmts.as_slice(),
+                [Stmt::Assign { target, value }] if target == switch.counter && *value == next
+            )
+    };
+    if starts_over(*then) {
+        binding.guard = Some((
+            conditions::negate(body, condition.clone()),
+            *otherwise,
+            *then,
+        ));
+    } else if starts_over(*otherwise) {
+        binding.guard = Some((condition.clone(), *then, *otherwise));
+    }
+    Some(binding)
+}
+
+// The class and name of the member a field or method instruction refers to.
+fn member<'a>(
+    constant_pool: &'a ConstantPool,
+    instruction: &Instruction,
+    opcode: Opcode,
+) -> Option<(&'a str, &'a str)> {
+    if instruction.opcode != opcode {
+        return None;
+    }
+    let member = constant_pool.member_ref(instruction.constant_index()?)?;
+    Some((member.class_name, member.name))
+}
+
+// The int an instruction pushes, if it only pushes a constant int.
+fn int_constant(constant_pool: &ConstantPool, instruction: &Instruction) -> Option<i32> {
+    match (instruction.opcode, &instruction.operand) {
+        (Opcode::Bipush, Operand::Byte(value)) => Some(i32::from(*value)),
+        (Opcode::Sipush, Operand::Short(value)) => Some(i32::from(*value)),
+        (Opcode::Ldc, Operand::Constant(index)) => match constant_pool.entry(*index)? {
+            ConstantPoolType::ConstantInteger { value } => Some(*value),
+            _ => None,
+        },
+        (opcode, _) => {
+            let value = opcode as i32 - Opcode::Iconst0 as i32;
+            (-1..=5).contains(&value).then_some(value)
+        }
+    }
+}
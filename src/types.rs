@@ -5,7 +5,7 @@ use log::debug;
 
 /// see https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.1
 #[allow(dead_code)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ClassFile {
     pub magic: u32,
     pub major_version: u16,
@@ -17,7 +17,7 @@ pub struct ClassFile {
     pub this_class: u16,
     pub super_class: u16,
     pub interfaces_count: u16,
-    pub interfaces: Vec<u8>,
+    pub interfaces: Vec<u16>,
     pub fields_count: u16,
     pub fields: Vec<FieldInfo>,
     pub methods_count: u16,
@@ -26,7 +26,7 @@ pub struct ClassFile {
     pub attributes: Vec<Attribute>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct ConstantPool {
     // The value of the constant_pool_count item is equal to the number of
     // entries in the constant_pool table plus one. A constant_pool index is
@@ -34,6 +34,10 @@ pub struct ConstantPool {
     // constant_pool_count, with the exception for constants of type long and
     // double noted in
     // https://docs.oracle.com/javase/specs/jvms/se23/html/jvms-4.html#jvms-4.4.5
+    //
+    // The second slot taken by a long or double is kept as an unusable entry
+    // (tag 0, no info) so that a constant pool index is always one more than
+    // the position in this table.
     cp_info: Vec<CpInfo>,
 }
 
@@ -42,13 +46,120 @@ impl ConstantPool {
         self.cp_info.push(cp_info)
     }
 
-    fn len(&self) -> usize {
+    /// Appends an entry and returns its constant pool index. A long or double
+    /// is followed by the unusable entry for its second slot, so the next
+    /// entry lands at the index the JVM expects.
+    pub fn add(&mut self, cp_info: CpInfo) -> u16 {
+        let index = self.len() + 1;
+        let wide = cp_info.is_wide();
+        self.push(cp_info);
+        if wide {
+            self.push(CpInfo::default());
+        }
+        index as u16
+    }
+
+    pub fn len(&self) -> usize {
         self.cp_info.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.cp_info.is_empty()
+    }
+
     fn get(&self, idx: usize) -> Option<&CpInfo> {
         self.cp_info.get(idx)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CpInfo> {
+        self.cp_info.iter()
+    }
+
+    /// The constant at a (one based) constant pool index.
+    pub fn entry(&self, index: u16) -> Option<&ConstantPoolType> {
+        if index == 0 {
+            return None;
+        }
+        self.get(index as usize - 1)?.info.as_ref()
+    }
+
+    pub fn utf8(&self, index: u16) -> Option<&str> {
+        match self.entry(index)? {
+            ConstantPoolType::ConstantUtf8 { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The name of a `CONSTANT_Class`, e.g. `java/lang/Object`.
+    pub fn class_name(&self, index: u16) -> Option<&str> {
+        match self.entry(index)? {
+            ConstantPoolType::ConstantClass { name_idx } => self.utf8(*name_idx),
+            _ => None,
+        }
+    }
+
+    /// The name and descriptor of a `CONSTANT_NameAndType`.
+    pub fn name_and_type(&self, index: u16) -> Option<(&str, &str)> {
+        match self.entry(index)? {
+            ConstantPoolType::ConstantNameAndType { name_idx, desc_idx } => {
+                Some((self.utf8(*name_idx)?, self.utf8(*desc_idx)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Resolves a field, method or interface method ref.
+    pub fn member_ref(&self, index: u16) -> Option<MemberRef<'_>> {
+        let (class_index, name_and_type_idx) = match self.entry(index)? {
+            ConstantPoolType::ConstantFieldRef {
+                class_index,
+                name_and_type_idx,
+            }
+            | ConstantPoolType::ConstantMethodRef {
+                class_index,
+                name_and_type_idx,
+            }
+            | ConstantPoolType::ConstantInterfaceMethodRef {
+                class_index,
+                name_and_type_idx,
+            } => (*class_index, *name_and_type_idx),
+            _ => return None,
+        };
+        let (name, descriptor) = self.name_and_type(name_and_type_idx)?;
+
+        Some(MemberRef {
+            class_name: self.class_name(class_index)?,
+            name,
+            descriptor,
+        })
+    }
+
+    /// The name and descriptor of a `CONSTANT_InvokeDynamic` or
+    /// `CONSTANT_Dynamic`, along with its bootstrap method index.
+    pub fn dynamic(&self, index: u16) -> Option<(u16, &str, &str)> {
+        match self.entry(index)? {
+            ConstantPoolType::ConstantDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            }
+            | ConstantPoolType::ConstantInvokeDynamic {
+                bootstrap_method_attr_index,
+                name_and_type_index,
+            } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type_index)?;
+                Some((*bootstrap_method_attr_index, name, descriptor))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A resolved field, method or interface method ref.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemberRef<'a> {
+    pub class_name: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
 }
 
 impl Display for ConstantPool {
@@ -74,21 +185,61 @@ impl ClassFile {
         }
     }
 
-    pub(crate) fn add_constant_pool_entry(&mut self, cp_info: CpInfo) {
+    pub fn add_constant_pool_entry(&mut self, cp_info: CpInfo) {
         debug!("adding {:?} at {}", cp_info, self.constant_pool.len() + 1);
-        self.constant_pool.push(cp_info);
+        self.constant_pool.add(cp_info);
     }
 
-    pub(crate) fn get_constant_pool_size(&self) -> usize {
+    pub fn set_constant_pool(&mut self, constant_pool: ConstantPool) {
+        self.constant_pool = constant_pool;
+    }
+
+    pub fn get_constant_pool_size(&self) -> usize {
         self.constant_pool.len()
     }
 
+    /// The value written as `constant_pool_count` in the class file.
+    pub fn constant_pool_count(&self) -> u16 {
+        (self.constant_pool.len() + 1) as u16
+    }
+
+    pub fn constant_pool(&self) -> &ConstantPool {
+        &self.constant_pool
+    }
+
     // See https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.1
     // constant_pool[]
     //      "The constant_pool table is indexed from 1 to constant_pool_count - 1."
-    pub(crate) fn get_constant_pool_entry(&self, index: usize) -> Option<&CpInfo> {
+    pub fn get_constant_pool_entry(&self, index: usize) -> Option<&CpInfo> {
+        if index == 0 {
+            return None;
+        }
         self.constant_pool.get(index - 1)
     }
+
+    /// The internal name of this class, e.g. `Sample$MyClass`.
+    pub fn this_class_name(&self) -> Option<&str> {
+        self.constant_pool.class_name(self.this_class)
+    }
+
+    /// The internal name of the super class; `None` only for `java/lang/Object`.
+    pub fn super_class_name(&self) -> Option<&str> {
+        self.constant_pool.class_name(self.super_class)
+    }
+
+    /// The methods of the `BootstrapMethods` attribute, which
+    /// `invokedynamic` instructions and dynamic constants refer to by index.
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethod] {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                Attribute::BootstrapMethods {
+                    bootstrap_methods, ..
+                } => Some(bootstrap_methods.as_slice()),
+                _ => None,
+            })
+            .unwrap_or(&[])
+    }
 }
 
 impl Display for ClassFile {
@@ -101,7 +252,7 @@ impl Display for ClassFile {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum ConstantPoolType {
     ConstantClass {
         name_idx: u16,
@@ -141,6 +292,9 @@ pub enum ConstantPoolType {
         // TODO: do we need to keep len?
         len: u16, // the number of bytes to read in the class file  (not the length of the resulting string).
         value: String,
+        // the Modified UTF-8 bytes as found in the class file; `value` is
+        // decoded from these and cannot represent unpaired surrogates
+        bytes: Vec<u8>,
     },
     ConstantMethodHandle {
         ref_kind: u8,
@@ -198,7 +352,7 @@ impl Display for ConstantPoolType {
             ConstantPoolType::ConstantNameAndType { name_idx, desc_idx } => {
                 write!(f, "NameAndType: name({name_idx}) desc({desc_idx})")
             }
-            ConstantPoolType::ConstantUtf8 { len, value } => {
+            ConstantPoolType::ConstantUtf8 { len, value, .. } => {
                 write!(f, "Utf8: len({len}) value(\"{value}\")")
             }
             ConstantPoolType::ConstantMethodHandle { ref_kind, ref_idx } => {
@@ -221,13 +375,67 @@ impl Display for ConstantPoolType {
     }
 }
 
+impl ConstantPoolType {
+    /// Builds a `ConstantUtf8` from a Rust string, encoding it as Modified UTF-8.
+    pub fn utf8(value: &str) -> Self {
+        let bytes = crate::mutf8::encode(value);
+        ConstantPoolType::ConstantUtf8 {
+            len: bytes.len() as u16,
+            value: value.to_string(),
+            bytes,
+        }
+    }
+
+    /// The `cp_info` tag for this kind of constant.
+    pub fn tag(&self) -> u8 {
+        match self {
+            ConstantPoolType::ConstantUtf8 { .. } => 1,
+            ConstantPoolType::ConstantInteger { .. } => 3,
+            ConstantPoolType::ConstantFloat { .. } => 4,
+            ConstantPoolType::ConstantLong { .. } => 5,
+            ConstantPoolType::ConstantDouble { .. } => 6,
+            ConstantPoolType::ConstantClass { .. } => 7,
+            ConstantPoolType::ConstantString { .. } => 8,
+            ConstantPoolType::ConstantFieldRef { .. } => 9,
+            ConstantPoolType::ConstantMethodRef { .. } => 10,
+            ConstantPoolType::ConstantInterfaceMethodRef { .. } => 11,
+            ConstantPoolType::ConstantNameAndType { .. } => 12,
+            ConstantPoolType::ConstantMethodHandle { .. } => 15,
+            ConstantPoolType::ConstantMethodType { .. } => 16,
+            ConstantPoolType::ConstantDynamic { .. } => 17,
+            ConstantPoolType::ConstantInvokeDynamic { .. } => 18,
+            ConstantPoolType::ConstantModule { .. } => 19,
+            ConstantPoolType::ConstantPackage { .. } => 20,
+        }
+    }
+}
+
 #[allow(dead_code)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
 pub struct CpInfo {
     pub tag: u8,
     pub info: Option<ConstantPoolType>,
 }
 
+impl CpInfo {
+    pub fn new(info: ConstantPoolType) -> Self {
+        CpInfo {
+            tag: info.tag(),
+            info: Some(info),
+        }
+    }
+
+    /// Long and double constants take up two constant pool slots.
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.4.5
+    pub fn is_wide(&self) -> bool {
+        matches!(
+            self.info,
+            Some(ConstantPoolType::ConstantLong { .. })
+                | Some(ConstantPoolType::ConstantDouble { .. })
+        )
+    }
+}
+
 impl Display for CpInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let info = if let Some(cp_type) = &self.info {
@@ -240,19 +448,40 @@ impl Display for CpInfo {
     }
 }
 
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.1-200-E.1
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.5-200-A.1
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.6-200-A.1
+pub const ACC_PUBLIC: u16 = 0x0001;
+pub const ACC_PRIVATE: u16 = 0x0002;
+pub const ACC_PROTECTED: u16 = 0x0004;
+pub const ACC_STATIC: u16 = 0x0008;
+pub const ACC_FINAL: u16 = 0x0010;
+pub const ACC_SUPER: u16 = 0x0020;
+pub const ACC_SYNCHRONIZED: u16 = 0x0020;
+pub const ACC_VOLATILE: u16 = 0x0040;
+pub const ACC_BRIDGE: u16 = 0x0040;
+pub const ACC_TRANSIENT: u16 = 0x0080;
+pub const ACC_VARARGS: u16 = 0x0080;
+pub const ACC_NATIVE: u16 = 0x0100;
+pub const ACC_INTERFACE: u16 = 0x0200;
+pub const ACC_ABSTRACT: u16 = 0x0400;
+pub const ACC_STRICT: u16 = 0x0800;
+pub const ACC_SYNTHETIC: u16 = 0x1000;
+pub const ACC_ANNOTATION: u16 = 0x2000;
+pub const ACC_ENUM: u16 = 0x4000;
+pub const ACC_MODULE: u16 = 0x8000;
+
 #[derive(Debug, Default)]
 pub struct FieldAccessFlags {}
 
 #[allow(dead_code)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct FieldInfo {
     // pub access_flags: FieldAccessFlags,
     pub access_flags: u16,
-    /*
-        pub name_index: u16,
-        pub descriptor_index: u16,
-        pub attributes_count: u16,
-    */
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes_count: u16,
     pub name: String,
     pub descriptor: String,
     pub value: Option<String>,
@@ -263,7 +492,7 @@ pub struct FieldInfo {
 pub struct MethodAccessFlags {}
 
 #[allow(dead_code)]
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct MethodInfo {
     // pub access_flags: MethodAccessFlags,
     pub access_flags: u16,
@@ -273,9 +502,30 @@ pub struct MethodInfo {
     pub attributes: Vec<Attribute>,
 }
 
+impl MethodInfo {
+    pub fn name<'a>(&self, constant_pool: &'a ConstantPool) -> Option<&'a str> {
+        constant_pool.utf8(self.name_index)
+    }
+
+    pub fn descriptor<'a>(&self, constant_pool: &'a ConstantPool) -> Option<&'a str> {
+        constant_pool.utf8(self.descriptor_index)
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags & ACC_STATIC != 0
+    }
+
+    /// The `Code` attribute, absent for abstract and native methods.
+    pub fn code(&self) -> Option<&Attribute> {
+        self.attributes
+            .iter()
+            .find(|attr| matches!(attr, Attribute::Code { .. }))
+    }
+}
+
 // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Attribute {
     //https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.2
     ConstantValue {
@@ -392,14 +642,14 @@ pub enum Attribute {
         attribute_name_index: u16,
         attribute_length: u32,
         num_parameters: u8,
-        parameter_annotations: Vec<Annotation>,
+        parameter_annotations: Vec<ParameterAnnotations>,
     },
     // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.19
     RuntimeInvisibleParameterAnnotations {
         attribute_name_index: u16,
         attribute_length: u32,
         num_parameters: u8,
-        parameter_annotations: Vec<Annotation>,
+        parameter_annotations: Vec<ParameterAnnotations>,
     },
     // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.20
     RuntimeVisibleTypeAnnotations {
@@ -493,88 +743,220 @@ pub enum Attribute {
         number_of_classes: u16,
         classes: Vec<u16>,
     },
+    // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.1
+    // "Java Virtual Machine implementations are required to silently ignore
+    // attributes they do not recognize." We keep the bytes so the class can be
+    // written back out unchanged.
+    Unknown {
+        attribute_name_index: u16,
+        attribute_length: u32,
+        info: Vec<u8>,
+    },
 }
 
-impl From<String> for Attribute {
-    fn from(value: String) -> Self {
-        match value {
-            _ => panic!("invalid attribute name"),
+impl Attribute {
+    pub fn attribute_name_index(&self) -> u16 {
+        match self {
+            Attribute::ConstantValue {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Code {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::StackMapTable {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Exceptions {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::InnerClasses {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::EnclosingMethod {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Synthetic {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Signature {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::SourceFile {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::SourceDebugExtension {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::LineNumberTable {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::LocalVariableTable {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::LocalVariableTypeTable {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Deprecated {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeVisibleAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeInvisibleAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeVisibleParameterAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeInvisibleParameterAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeVisibleTypeAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::RuntimeInvisibleTypeAnnotations {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::AnnotationDefault {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::BootstrapMethods {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::MethodParameters {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Module {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::ModulePackages {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::ModuleMainClass {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::NestHost {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::NestMembers {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Record {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::PermittedSubclasses {
+                attribute_name_index,
+                ..
+            }
+            | Attribute::Unknown {
+                attribute_name_index,
+                ..
+            } => *attribute_name_index,
         }
     }
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RecordComponentInfo {
-    name_index: u16,
-    descriptor_index: u16,
-    attributes_count: u16,
-    attributes: Vec<Attribute>,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub attributes_count: u16,
+    pub attributes: Vec<Attribute>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleProvides {
-    provides_index: u16,
-    provides_with_count: u16,
-    provides_with_index: Vec<u16>,
+    pub provides_index: u16,
+    pub provides_with_count: u16,
+    pub provides_with_index: Vec<u16>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleOpens {
-    opens_index: u16,
-    opens_flags: u16,
-    opens_to_count: u16,
-    opens_to_index: Vec<u16>,
+    pub opens_index: u16,
+    pub opens_flags: u16,
+    pub opens_to_count: u16,
+    pub opens_to_index: Vec<u16>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleRequirement {
-    requires_index: u16,
-    requires_flags: u16,
-    requires_version_index: u16,
+    pub requires_index: u16,
+    pub requires_flags: u16,
+    pub requires_version_index: u16,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ModuleExport {
-    exports_index: u16,
-    exports_flags: u16,
-    exports_to_count: u16,
-    exports_to_index: Vec<u16>,
+    pub exports_index: u16,
+    pub exports_flags: u16,
+    pub exports_to_count: u16,
+    pub exports_to_index: Vec<u16>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MethodParameter {
     pub name_index: u16,
     pub access_flags: u16,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BootstrapMethod {
-    bootstrap_method_ref: u16,
-    num_bootstrap_arguments: u16,
-    bootstrap_arguments: Vec<u16>,
+    pub bootstrap_method_ref: u16,
+    pub num_bootstrap_arguments: u16,
+    pub bootstrap_arguments: Vec<u16>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypeAnnotation {
-    target_type: u8,
-    target_info: TargetInfo,
-    target_path: TypePath,
-    type_index: u16,
-    num_element_value_pairs: u16,
-    element_value_pairs: Vec<AnnotationElementPair>,
+    pub target_type: u8,
+    pub target_info: TargetInfo,
+    pub target_path: TypePath,
+    pub type_index: u16,
+    pub num_element_value_pairs: u16,
+    pub element_value_pairs: Vec<AnnotationElementPair>,
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.20.1
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TargetInfo {
     TypeParameter(u8),
     SuperType(u16),
@@ -598,48 +980,60 @@ pub enum TargetInfo {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LocalVarTable {
-    start_pc: u16,
-    length: u16,
-    index: u16,
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.20.2
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypePath {
-    path_length: u8,
-    path: Vec<TypePathElement>,
+    pub path_length: u8,
+    pub path: Vec<TypePathElement>,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TypePathElement {
-    type_path_kind: u8,
-    type_argument_index: u8,
+    pub type_path_kind: u8,
+    pub type_argument_index: u8,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Annotation {
-    type_index: u16,
-    num_element_value_pairs: u16,
-    element_value_pairs: Vec<AnnotationElementPair>,
+    pub type_index: u16,
+    pub num_element_value_pairs: u16,
+    pub element_value_pairs: Vec<AnnotationElementPair>,
 }
 
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.18
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+pub struct ParameterAnnotations {
+    pub num_annotations: u16,
+    pub annotations: Vec<Annotation>,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
 pub struct AnnotationElementPair {
-    element_name_index: u16,
-    value: ElementValue,
+    pub element_name_index: u16,
+    pub value: ElementValue,
 }
 
 // https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.16.1
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ElementValue {
-    ConstValueIndex(u16),
+    // tag is one of B, C, D, F, I, J, S, Z or s
+    ConstValueIndex {
+        tag: u8,
+        const_value_index: u16,
+    },
     EnumConstantValue {
         type_name_index: u16,
         const_name_index: u16,
@@ -653,34 +1047,34 @@ pub enum ElementValue {
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LocalVariableTypeTableEntry {
-    start_pc: u16,
-    length: u16,
-    name_index: u16,
-    signature_index: u16,
-    index: u16,
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub signature_index: u16,
+    pub index: u16,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LocalVariableTableEntry {
-    start_pc: u16,
-    length: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    index: u16,
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    pub index: u16,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LineNumberTableEntry {
-    pub(crate) start_pc: u16,
-    pub(crate) line_number: u16,
+    pub start_pc: u16,
+    pub line_number: u16,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InnerClassInfo {
     pub inner_class_info_index: u16,
     pub outer_class_info_index: u16,
@@ -688,20 +1082,110 @@ pub struct InnerClassInfo {
     pub inner_class_access_flags: u16,
 }
 
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.4
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum StackMapFrame {
-    SameFrame,
-    SameLocals1StackItemFrame,
-    SameLocals1StackItemFrameExtended,
-    ChopFrame,
-    SameFrameExtended,
-    AppendFrame,
-    FullFrame,
+    // frame_type = SAME; /* 0-63 */
+    SameFrame {
+        frame_type: u8,
+    },
+    // frame_type = SAME_LOCALS_1_STACK_ITEM; /* 64-127 */
+    SameLocals1StackItemFrame {
+        frame_type: u8,
+        stack: VerificationTypeInfo,
+    },
+    // frame_type = SAME_LOCALS_1_STACK_ITEM_EXTENDED; /* 247 */
+    SameLocals1StackItemFrameExtended {
+        offset_delta: u16,
+        stack: VerificationTypeInfo,
+    },
+    // frame_type = CHOP; /* 248-250 */
+    ChopFrame {
+        frame_type: u8,
+        offset_delta: u16,
+    },
+    // frame_type = SAME_FRAME_EXTENDED; /* 251 */
+    SameFrameExtended {
+        offset_delta: u16,
+    },
+    // frame_type = APPEND; /* 252-254 */
+    AppendFrame {
+        frame_type: u8,
+        offset_delta: u16,
+        locals: Vec<VerificationTypeInfo>,
+    },
+    // frame_type = FULL_FRAME; /* 255 */
+    FullFrame {
+        offset_delta: u16,
+        number_of_locals: u16,
+        locals: Vec<VerificationTypeInfo>,
+        number_of_stack_items: u16,
+        stack: Vec<VerificationTypeInfo>,
+    },
+}
+
+impl StackMapFrame {
+    pub fn frame_type(&self) -> u8 {
+        match self {
+            StackMapFrame::SameFrame { frame_type }
+            | StackMapFrame::SameLocals1StackItemFrame { frame_type, .. }
+            | StackMapFrame::ChopFrame { frame_type, .. }
+            | StackMapFrame::AppendFrame { frame_type, .. } => *frame_type,
+            StackMapFrame::SameLocals1StackItemFrameExtended { .. } => 247,
+            StackMapFrame::SameFrameExtended { .. } => 251,
+            StackMapFrame::FullFrame { .. } => 255,
+        }
+    }
+
+    /// The offset delta, which for the compact frame types is encoded in the
+    /// frame type itself.
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::SameFrame { frame_type } => *frame_type as u16,
+            StackMapFrame::SameLocals1StackItemFrame { frame_type, .. } => *frame_type as u16 - 64,
+            StackMapFrame::SameLocals1StackItemFrameExtended { offset_delta, .. }
+            | StackMapFrame::ChopFrame { offset_delta, .. }
+            | StackMapFrame::SameFrameExtended { offset_delta }
+            | StackMapFrame::AppendFrame { offset_delta, .. }
+            | StackMapFrame::FullFrame { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.4
+#[allow(dead_code)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VerificationTypeInfo {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    Object { cpool_index: u16 },
+    Uninitialized { offset: u16 },
+}
+
+impl VerificationTypeInfo {
+    pub fn tag(&self) -> u8 {
+        match self {
+            VerificationTypeInfo::Top => 0,
+            VerificationTypeInfo::Integer => 1,
+            VerificationTypeInfo::Float => 2,
+            VerificationTypeInfo::Double => 3,
+            VerificationTypeInfo::Long => 4,
+            VerificationTypeInfo::Null => 5,
+            VerificationTypeInfo::UninitializedThis => 6,
+            VerificationTypeInfo::Object { .. } => 7,
+            VerificationTypeInfo::Uninitialized { .. } => 8,
+        }
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExceptionTable {
     pub start_pc: u16,
     pub end_pc: u16,
@@ -0,0 +1,569 @@
+use crate::decompile::DecompileResult;
+use crate::types::{
+    Annotation, AnnotationElementPair, Attribute, ClassFile, ConstantPoolType, CpInfo,
+    ElementValue, FieldInfo, MethodInfo, StackMapFrame, TargetInfo, TypeAnnotation,
+    VerificationTypeInfo,
+};
+use log::trace;
+use std::io::Write;
+
+/// Serializes a class file. Lengths and counts are computed from the data
+/// rather than taken from the `*_count` and `attribute_length` fields, so a
+/// `ClassFile` that was modified after parsing is still written correctly.
+///
+/// see https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.1
+pub fn write_class_file<W: Write>(writer: &mut W, class_file: &ClassFile) -> DecompileResult<()> {
+    trace!("write_class_file()");
+
+    write_u32(writer, class_file.magic)?;
+    write_u16(writer, class_file.minor_version)?;
+    write_u16(writer, class_file.major_version)?;
+
+    write_u16(writer, class_file.constant_pool_count())?;
+    for cp_info in class_file.constant_pool().iter() {
+        write_cp_info(writer, cp_info)?;
+    }
+
+    write_u16(writer, class_file.access_flags)?;
+    write_u16(writer, class_file.this_class)?;
+    write_u16(writer, class_file.super_class)?;
+
+    write_u16(writer, class_file.interfaces.len() as u16)?;
+    for interface in &class_file.interfaces {
+        write_u16(writer, *interface)?;
+    }
+
+    write_u16(writer, class_file.fields.len() as u16)?;
+    for field_info in &class_file.fields {
+        write_field_info(writer, field_info)?;
+    }
+
+    write_u16(writer, class_file.methods.len() as u16)?;
+    for method_info in &class_file.methods {
+        write_method_info(writer, method_info)?;
+    }
+
+    write_attributes(writer, &class_file.attributes)
+}
+
+/// Convenience wrapper around [`write_class_file`] that writes into memory.
+pub fn to_bytes(class_file: &ClassFile) -> DecompileResult<Vec<u8>> {
+    let mut bytes = Vec::new();
+    write_class_file(&mut bytes, class_file)?;
+    Ok(bytes)
+}
+
+/// Writes a single constant pool entry. The unusable second slot of a long or
+/// double (an entry with no info) produces no output.
+pub fn write_cp_info<W: Write>(writer: &mut W, cp_info: &CpInfo) -> DecompileResult<()> {
+    let Some(info) = &cp_info.info else {
+        return Ok(());
+    };
+
+    write_u8(writer, cp_info.tag)?;
+    match info {
+        ConstantPoolType::ConstantUtf8 { bytes, .. } => {
+            write_u16(writer, bytes.len() as u16)?;
+            writer.write_all(bytes)?;
+        }
+        ConstantPoolType::ConstantInteger { value } => writer.write_all(&value.to_be_bytes())?,
+        ConstantPoolType::ConstantFloat { value } => writer.write_all(&value.to_be_bytes())?,
+        ConstantPoolType::ConstantLong { value } => writer.write_all(&value.to_be_bytes())?,
+        ConstantPoolType::ConstantDouble { value } => writer.write_all(&value.to_be_bytes())?,
+        ConstantPoolType::ConstantClass { name_idx }
+        | ConstantPoolType::ConstantModule { name_idx }
+        | ConstantPoolType::ConstantPackage { name_idx } => write_u16(writer, *name_idx)?,
+        ConstantPoolType::ConstantString { string_idx } => write_u16(writer, *string_idx)?,
+        ConstantPoolType::ConstantFieldRef {
+            class_index,
+            name_and_type_idx,
+        }
+        | ConstantPoolType::ConstantMethodRef {
+            class_index,
+            name_and_type_idx,
+        }
+        | ConstantPoolType::ConstantInterfaceMethodRef {
+            class_index,
+            name_and_type_idx,
+        } => {
+            write_u16(writer, *class_index)?;
+            write_u16(writer, *name_and_type_idx)?;
+        }
+        ConstantPoolType::ConstantNameAndType { name_idx, desc_idx } => {
+            write_u16(writer, *name_idx)?;
+            write_u16(writer, *desc_idx)?;
+        }
+        ConstantPoolType::ConstantMethodHandle { ref_kind, ref_idx } => {
+            write_u8(writer, *ref_kind)?;
+            write_u16(writer, *ref_idx)?;
+        }
+        ConstantPoolType::ConstantMethodType { desc_idx } => write_u16(writer, *desc_idx)?,
+        ConstantPoolType::ConstantDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        }
+        | ConstantPoolType::ConstantInvokeDynamic {
+            bootstrap_method_attr_index,
+            name_and_type_index,
+        } => {
+            write_u16(writer, *bootstrap_method_attr_index)?;
+            write_u16(writer, *name_and_type_index)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn write_field_info<W: Write>(writer: &mut W, field_info: &FieldInfo) -> DecompileResult<()> {
+    write_u16(writer, field_info.access_flags)?;
+    write_u16(writer, field_info.name_index)?;
+    write_u16(writer, field_info.descriptor_index)?;
+    write_attributes(writer, &field_info.attributes)
+}
+
+pub fn write_method_info<W: Write>(
+    writer: &mut W,
+    method_info: &MethodInfo,
+) -> DecompileResult<()> {
+    write_u16(writer, method_info.access_flags)?;
+    write_u16(writer, method_info.name_index)?;
+    write_u16(writer, method_info.descriptor_index)?;
+    write_attributes(writer, &method_info.attributes)
+}
+
+fn write_attributes<W: Write>(writer: &mut W, attributes: &[Attribute]) -> DecompileResult<()> {
+    write_u16(writer, attributes.len() as u16)?;
+    for attr in attributes {
+        write_attribute(writer, attr)?;
+    }
+
+    Ok(())
+}
+
+/// Writes an attribute, including its name index and a freshly computed
+/// `attribute_length`.
+///
+/// see https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7
+pub fn write_attribute<W: Write>(writer: &mut W, attr: &Attribute) -> DecompileResult<()> {
+    let mut info = Vec::new();
+    write_attribute_info(&mut info, attr)?;
+
+    write_u16(writer, attr.attribute_name_index())?;
+    write_u32(writer, info.len() as u32)?;
+    writer.write_all(&info)?;
+
+    Ok(())
+}
+
+/// The `attribute_length` the attribute will be written with.
+pub fn attribute_length(attr: &Attribute) -> DecompileResult<u32> {
+    let mut info = Vec::new();
+    write_attribute_info(&mut info, attr)?;
+    Ok(info.len() as u32)
+}
+
+fn write_attribute_info(info: &mut Vec<u8>, attr: &Attribute) -> DecompileResult<()> {
+    match attr {
+        Attribute::ConstantValue {
+            constant_value_index,
+            ..
+        } => write_u16(info, *constant_value_index)?,
+        Attribute::Code {
+            max_stack,
+            max_locals,
+            code,
+            exception_table,
+            attributes,
+            ..
+        } => {
+            write_u16(info, *max_stack)?;
+            write_u16(info, *max_locals)?;
+            write_u32(info, code.len() as u32)?;
+            info.write_all(code)?;
+            write_u16(info, exception_table.len() as u16)?;
+            for entry in exception_table {
+                write_u16(info, entry.start_pc)?;
+                write_u16(info, entry.end_pc)?;
+                write_u16(info, entry.handler_pc)?;
+                write_u16(info, entry.catch_type)?;
+            }
+            write_attributes(info, attributes)?;
+        }
+        Attribute::StackMapTable { entries, .. } => {
+            write_u16(info, entries.len() as u16)?;
+            for frame in entries {
+                write_stack_map_frame(info, frame)?;
+            }
+        }
+        Attribute::Exceptions {
+            exception_index_table,
+            ..
+        } => write_u16_table(info, exception_index_table)?,
+        Attribute::InnerClasses { classes, .. } => {
+            write_u16(info, classes.len() as u16)?;
+            for class in classes {
+                write_u16(info, class.inner_class_info_index)?;
+                write_u16(info, class.outer_class_info_index)?;
+                write_u16(info, class.inner_name_index)?;
+                write_u16(info, class.inner_class_access_flags)?;
+            }
+        }
+        Attribute::EnclosingMethod {
+            class_index,
+            method_index,
+            ..
+        } => {
+            write_u16(info, *class_index)?;
+            write_u16(info, *method_index)?;
+        }
+        Attribute::Synthetic { .. } | Attribute::Deprecated { .. } => {}
+        Attribute::Signature {
+            signature_index, ..
+        } => write_u16(info, *signature_index)?,
+        Attribute::SourceFile {
+            sourcefile_index, ..
+        } => write_u16(info, *sourcefile_index)?,
+        Attribute::SourceDebugExtension {
+            debug_extension, ..
+        } => info.write_all(debug_extension)?,
+        Attribute::LineNumberTable {
+            line_number_table, ..
+        } => {
+            write_u16(info, line_number_table.len() as u16)?;
+            for entry in line_number_table {
+                write_u16(info, entry.start_pc)?;
+                write_u16(info, entry.line_number)?;
+            }
+        }
+        Attribute::LocalVariableTable {
+            local_variable_table,
+            ..
+        } => {
+            write_u16(info, local_variable_table.len() as u16)?;
+            for entry in local_variable_table {
+                write_u16(info, entry.start_pc)?;
+                write_u16(info, entry.length)?;
+                write_u16(info, entry.name_index)?;
+                write_u16(info, entry.descriptor_index)?;
+                write_u16(info, entry.index)?;
+            }
+        }
+        Attribute::LocalVariableTypeTable {
+            local_variable_type_table,
+            ..
+        } => {
+            write_u16(info, local_variable_type_table.len() as u16)?;
+            for entry in local_variable_type_table {
+                write_u16(info, entry.start_pc)?;
+                write_u16(info, entry.length)?;
+                write_u16(info, entry.name_index)?;
+                write_u16(info, entry.signature_index)?;
+                write_u16(info, entry.index)?;
+            }
+        }
+        Attribute::RuntimeVisibleAnnotations { annotations, .. }
+        | Attribute::RuntimeInvisibleAnnotations { annotations, .. } => {
+            write_u16(info, annotations.len() as u16)?;
+            for annotation in annotations {
+                write_annotation(info, annotation)?;
+            }
+        }
+        Attribute::RuntimeVisibleParameterAnnotations {
+            parameter_annotations,
+            ..
+        }
+        | Attribute::RuntimeInvisibleParameterAnnotations {
+            parameter_annotations,
+            ..
+        } => {
+            write_u8(info, parameter_annotations.len() as u8)?;
+            for parameter in parameter_annotations {
+                write_u16(info, parameter.annotations.len() as u16)?;
+                for annotation in &parameter.annotations {
+                    write_annotation(info, annotation)?;
+                }
+            }
+        }
+        Attribute::RuntimeVisibleTypeAnnotations { annotations, .. }
+        | Attribute::RuntimeInvisibleTypeAnnotations { annotations, .. } => {
+            write_u16(info, annotations.len() as u16)?;
+            for annotation in annotations {
+                write_type_annotation(info, annotation)?;
+            }
+        }
+        Attribute::AnnotationDefault { default_value, .. } => {
+            write_element_value(info, default_value)?
+        }
+        Attribute::BootstrapMethods {
+            bootstrap_methods, ..
+        } => {
+            write_u16(info, bootstrap_methods.len() as u16)?;
+            for method in bootstrap_methods {
+                write_u16(info, method.bootstrap_method_ref)?;
+                write_u16_table(info, &method.bootstrap_arguments)?;
+            }
+        }
+        Attribute::MethodParameters { parameters, .. } => {
+            write_u8(info, parameters.len() as u8)?;
+            for parameter in parameters {
+                write_u16(info, parameter.name_index)?;
+                write_u16(info, parameter.access_flags)?;
+            }
+        }
+        Attribute::Module {
+            module_name_index,
+            module_flags,
+            module_version_index,
+            requires,
+            exports,
+            opens,
+            uses_index,
+            provides,
+            ..
+        } => {
+            write_u16(info, *module_name_index)?;
+            write_u16(info, *module_flags)?;
+            write_u16(info, *module_version_index)?;
+
+            write_u16(info, requires.len() as u16)?;
+            for requirement in requires {
+                write_u16(info, requirement.requires_index)?;
+                write_u16(info, requirement.requires_flags)?;
+                write_u16(info, requirement.requires_version_index)?;
+            }
+
+            write_u16(info, exports.len() as u16)?;
+            for export in exports {
+                write_u16(info, export.exports_index)?;
+                write_u16(info, export.exports_flags)?;
+                write_u16_table(info, &export.exports_to_index)?;
+            }
+
+            write_u16(info, opens.len() as u16)?;
+            for open in opens {
+                write_u16(info, open.opens_index)?;
+                write_u16(info, open.opens_flags)?;
+                write_u16_table(info, &open.opens_to_index)?;
+            }
+
+            write_u16_table(info, uses_index)?;
+
+            write_u16(info, provides.len() as u16)?;
+            for provide in provides {
+                write_u16(info, provide.provides_index)?;
+                write_u16_table(info, &provide.provides_with_index)?;
+            }
+        }
+        Attribute::ModulePackages { package_index, .. } => write_u16_table(info, package_index)?,
+        Attribute::ModuleMainClass {
+            main_class_index, ..
+        } => write_u16(info, *main_class_index)?,
+        Attribute::NestHost {
+            host_class_index, ..
+        } => write_u16(info, *host_class_index)?,
+        Attribute::NestMembers { classes, .. } | Attribute::PermittedSubclasses { classes, .. } => {
+            write_u16_table(info, classes)?
+        }
+        Attribute::Record { components, .. } => {
+            write_u16(info, components.len() as u16)?;
+            for component in components {
+                write_u16(info, component.name_index)?;
+                write_u16(info, component.descriptor_index)?;
+                write_attributes(info, &component.attributes)?;
+            }
+        }
+        Attribute::Unknown { info: bytes, .. } => info.write_all(bytes)?,
+    }
+
+    Ok(())
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.4
+fn write_stack_map_frame<W: Write>(writer: &mut W, frame: &StackMapFrame) -> DecompileResult<()> {
+    write_u8(writer, frame.frame_type())?;
+    match frame {
+        StackMapFrame::SameFrame { .. } => {}
+        StackMapFrame::SameLocals1StackItemFrame { stack, .. } => {
+            write_verification_type_info(writer, stack)?
+        }
+        StackMapFrame::SameLocals1StackItemFrameExtended {
+            offset_delta,
+            stack,
+        } => {
+            write_u16(writer, *offset_delta)?;
+            write_verification_type_info(writer, stack)?;
+        }
+        StackMapFrame::ChopFrame { offset_delta, .. }
+        | StackMapFrame::SameFrameExtended { offset_delta } => write_u16(writer, *offset_delta)?,
+        StackMapFrame::AppendFrame {
+            offset_delta,
+            locals,
+            ..
+        } => {
+            write_u16(writer, *offset_delta)?;
+            for local in locals {
+                write_verification_type_info(writer, local)?;
+            }
+        }
+        StackMapFrame::FullFrame {
+            offset_delta,
+            locals,
+            stack,
+            ..
+        } => {
+            write_u16(writer, *offset_delta)?;
+            write_u16(writer, locals.len() as u16)?;
+            for local in locals {
+                write_verification_type_info(writer, local)?;
+            }
+            write_u16(writer, stack.len() as u16)?;
+            for item in stack {
+                write_verification_type_info(writer, item)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_verification_type_info<W: Write>(
+    writer: &mut W,
+    info: &VerificationTypeInfo,
+) -> DecompileResult<()> {
+    write_u8(writer, info.tag())?;
+    match info {
+        VerificationTypeInfo::Object { cpool_index } => write_u16(writer, *cpool_index),
+        VerificationTypeInfo::Uninitialized { offset } => write_u16(writer, *offset),
+        _ => Ok(()),
+    }
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.16
+fn write_annotation<W: Write>(writer: &mut W, annotation: &Annotation) -> DecompileResult<()> {
+    write_u16(writer, annotation.type_index)?;
+    write_element_value_pairs(writer, &annotation.element_value_pairs)
+}
+
+fn write_element_value_pairs<W: Write>(
+    writer: &mut W,
+    pairs: &[AnnotationElementPair],
+) -> DecompileResult<()> {
+    write_u16(writer, pairs.len() as u16)?;
+    for pair in pairs {
+        write_u16(writer, pair.element_name_index)?;
+        write_element_value(writer, &pair.value)?;
+    }
+
+    Ok(())
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.16.1
+fn write_element_value<W: Write>(writer: &mut W, value: &ElementValue) -> DecompileResult<()> {
+    match value {
+        ElementValue::ConstValueIndex {
+            tag,
+            const_value_index,
+        } => {
+            write_u8(writer, *tag)?;
+            write_u16(writer, *const_value_index)?;
+        }
+        ElementValue::EnumConstantValue {
+            type_name_index,
+            const_name_index,
+        } => {
+            write_u8(writer, b'e')?;
+            write_u16(writer, *type_name_index)?;
+            write_u16(writer, *const_name_index)?;
+        }
+        ElementValue::ClassInfoIndex(class_info_index) => {
+            write_u8(writer, b'c')?;
+            write_u16(writer, *class_info_index)?;
+        }
+        ElementValue::AnnotationValue(annotation) => {
+            write_u8(writer, b'@')?;
+            write_annotation(writer, annotation)?;
+        }
+        ElementValue::ArrayValue { values, .. } => {
+            write_u8(writer, b'[')?;
+            write_u16(writer, values.len() as u16)?;
+            for value in values {
+                write_element_value(writer, value)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// https://docs.oracle.com/javase/specs/jvms/se21/html/jvms-4.html#jvms-4.7.20
+fn write_type_annotation<W: Write>(
+    writer: &mut W,
+    annotation: &TypeAnnotation,
+) -> DecompileResult<()> {
+    write_u8(writer, annotation.target_type)?;
+    match &annotation.target_info {
+        TargetInfo::TypeParameter(index) | TargetInfo::FormalParameter(index) => {
+            write_u8(writer, *index)?
+        }
+        TargetInfo::SuperType(index)
+        | TargetInfo::Throws(index)
+        | TargetInfo::Catch(index)
+        | TargetInfo::Offset(index) => write_u16(writer, *index)?,
+        TargetInfo::TypeParameterBound {
+            type_parameter_index,
+            bound_index,
+        } => {
+            write_u8(writer, *type_parameter_index)?;
+            write_u8(writer, *bound_index)?;
+        }
+        TargetInfo::Empty => {}
+        TargetInfo::LocalVar { table, .. } => {
+            write_u16(writer, table.len() as u16)?;
+            for entry in table {
+                write_u16(writer, entry.start_pc)?;
+                write_u16(writer, entry.length)?;
+                write_u16(writer, entry.index)?;
+            }
+        }
+        TargetInfo::TypeArgument {
+            offset,
+            type_argument_index,
+        } => {
+            write_u16(writer, *offset)?;
+            write_u8(writer, *type_argument_index)?;
+        }
+    }
+
+    write_u8(writer, annotation.target_path.path.len() as u8)?;
+    for element in &annotation.target_path.path {
+        write_u8(writer, element.type_path_kind)?;
+        write_u8(writer, element.type_argument_index)?;
+    }
+
+    write_u16(writer, annotation.type_index)?;
+    write_element_value_pairs(writer, &annotation.element_value_pairs)
+}
+
+fn write_u16_table<W: Write>(writer: &mut W, table: &[u16]) -> DecompileResult<()> {
+    write_u16(writer, table.len() as u16)?;
+    for value in table {
+        write_u16(writer, *value)?;
+    }
+
+    Ok(())
+}
+
+fn write_u8<W: Write>(writer: &mut W, value: u8) -> DecompileResult<()> {
+    writer.write_all(&[value])?;
+    Ok(())
+}
+
+fn write_u16<W: Write>(writer: &mut W, value: u16) -> DecompileResult<()> {
+    writer.write_all(&value.to_be_bytes())?;
+    Ok(())
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> DecompileResult<()> {
+    writer.write_all(&value.to_be_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+mod common;
+
+use decompile_rs::assembler::{Assembler, Label};
+use decompile_rs::builder::{ClassBuilder, MethodBuilder};
+use decompile_rs::decompile::Decompile;
+use decompile_rs::frames::{compute_frames, SuperclassMap};
+use decompile_rs::instruction::{Instruction, Opcode, Operand};
+use decompile_rs::types::{ACC_PUBLIC, ACC_STATIC};
+use decompile_rs::writer::to_bytes;
+use std::fs;
+
+const DRIVER: &str = "
+import java.lang.reflect.Method;
+
+public class Driver {
+    public static void main(String[] args) throws Exception {
+        Method run = Class.forName(\"Big\").getMethod(\"run\", int.class);
+        for (int i = 0; i <= 12; i++) {
+            System.out.print(run.invoke(null, i) + \" \");
+        }
+    }
+}
+";
+
+fn local(asm: &mut Assembler, opcode: Opcode, index: u16) {
+    asm.emit(Instruction::new(opcode, Operand::Local(index)));
+}
+
+fn push(asm: &mut Assembler, value: i16) {
+    asm.emit(Instruction::new(Opcode::Sipush, Operand::Short(value)));
+}
+
+fn returns(asm: &mut Assembler, label: Label, value: i16) {
+    asm.place(label);
+    push(asm, value);
+    asm.op(Opcode::Ireturn);
+}
+
+// A method of more than 40k bytes with branches across it, switches at
+// every alignment, `ldc_w` and an exception handler, run by a JVM that
+// verifies it.
+#[test]
+fn assembled_code_verifies_and_runs() {
+    let mut class = ClassBuilder::new("Big");
+    let pool = class.pool();
+    let constants: Vec<u16> = (0..300).map(|i| pool.integer(100_000 + i)).collect();
+    let arithmetic = pool.class("java/lang/ArithmeticException");
+
+    let mut asm = Assembler::new();
+    let labels: Vec<Label> = (0..12).map(|_| asm.new_label()).collect();
+    let [back, case1, case2, case3, case4, far, other, ten, eleven, start, end, handler] =
+        labels[..]
+    else {
+        unreachable!()
+    };
+    local(&mut asm, Opcode::Iload, 0);
+    asm.emit(Instruction::new(
+        Opcode::Lookupswitch,
+        Operand::LookupSwitch {
+            default: other,
+            pairs: vec![(1, case1), (2, case2), (3, case3), (4, case4)],
+        },
+    ));
+    returns(&mut asm, back, 42);
+    // forward past the nops with an inverted branch over goto_w
+    asm.place(case1);
+    local(&mut asm, Opcode::Iload, 0);
+    asm.op(Opcode::Iconst1);
+    asm.branch(Opcode::IfIcmpeq, far);
+    asm.op(Opcode::IconstM1);
+    asm.op(Opcode::Ireturn);
+    asm.place(case4);
+    for _ in 0..40_000 {
+        asm.op(Opcode::Nop);
+    }
+    // and back with goto_w
+    asm.place(far);
+    asm.branch(Opcode::Goto, back);
+    asm.place(case2);
+    asm.emit(Instruction::new(
+        Opcode::Ldc,
+        Operand::Constant(constants[299]),
+    ));
+    asm.op(Opcode::Ireturn);
+    asm.place(case3);
+    asm.place(start);
+    local(&mut asm, Opcode::Iload, 0);
+    asm.op(Opcode::Iconst0);
+    asm.op(Opcode::Idiv);
+    asm.op(Opcode::Ireturn);
+    asm.place(end);
+    asm.place(handler);
+    asm.op(Opcode::Pop);
+    push(&mut asm, 1234);
+    asm.op(Opcode::Ireturn);
+    asm.try_catch(start, end, handler, arithmetic);
+    // a table switch, each padding to its own alignment
+    for nops in 0..4 {
+        let next = asm.new_label();
+        if nops == 0 {
+            asm.place(other);
+        }
+        for _ in 0..nops {
+            asm.op(Opcode::Nop);
+        }
+        local(&mut asm, Opcode::Iload, 0);
+        asm.emit(Instruction::new(
+            Opcode::Tableswitch,
+            Operand::TableSwitch {
+                default: next,
+                low: 10 + nops,
+                high: 11 + nops,
+                targets: vec![ten, eleven],
+            },
+        ));
+        asm.place(next);
+    }
+    push(&mut asm, 0);
+    asm.op(Opcode::Ireturn);
+    returns(&mut asm, ten, 10);
+    returns(&mut asm, eleven, 11);
+
+    let mut code = asm
+        .assemble_computing_maxs("(I)I", true, class.pool().pool())
+        .unwrap();
+    assert!(code.code.len() > 40_000);
+    compute_frames(
+        &mut code,
+        "Big",
+        "run",
+        "(I)I",
+        true,
+        class.pool(),
+        &SuperclassMap::new(),
+    )
+    .unwrap();
+    let method = MethodBuilder::new(ACC_PUBLIC | ACC_STATIC, "run", "(I)I").code(code);
+    let bytes = to_bytes(&class.method(method).build()).unwrap();
+
+    let Some(directory) = common::javac("assembler", &[("Driver.java", DRIVER)], &[]) else {
+        return;
+    };
+    fs::write(directory.join("Big.class"), bytes).unwrap();
+    let output = common::java(&directory, "Driver", &["-Xverify:all"]);
+    assert_eq!(output, "0 42 100299 1234 42 0 0 0 0 0 10 11 11 ");
+}
+
+// A monitor entered with no handler to release it can't be written as a
+// `synchronized` statement, so the method is left undecompiled.
+#[test]
+fn unmatched_monitors_fail_the_method() {
+    let mut class = ClassBuilder::new("Monitor");
+    let mut asm = Assembler::new();
+    local(&mut asm, Opcode::Aload, 0);
+    asm.op(Opcode::Monitorenter);
+    asm.op(Opcode::Return);
+    let code = asm
+        .assemble_computing_maxs("(Ljava/lang/Object;)V", true, class.pool().pool())
+        .unwrap();
+    let method =
+        MethodBuilder::new(ACC_PUBLIC | ACC_STATIC, "lock", "(Ljava/lang/Object;)V").code(code);
+    let bytes = to_bytes(&class.method(method).build()).unwrap();
+
+    let directory = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("monitor");
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("Monitor.class");
+    fs::write(&path, bytes).unwrap();
+    let source = Decompile::new(path).unwrap().source().unwrap();
+    assert!(
+        source.contains("// could not decompile: monitor entered"),
+        "{source}"
+    );
+    assert!(
+        source.contains("throw new UnsupportedOperationException();"),
+        "{source}"
+    );
+}
@@ -0,0 +1,42 @@
+use decompile_rs::assembler::Assembler;
+use decompile_rs::builder::{ClassBuilder, MethodBuilder};
+use decompile_rs::instruction::{Instruction, Opcode, Operand};
+use decompile_rs::types::{ACC_PUBLIC, ACC_STATIC};
+use decompile_rs::writer::to_bytes;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// `--check-maxs` prints the methods declaring too small a `max_stack` or
+// `max_locals` and fails, and is silent about a class without any.
+#[test]
+fn check_maxs_reports_methods() {
+    let mut asm = Assembler::new();
+    asm.emit(Instruction::new(Opcode::Dload, Operand::Local(0)));
+    asm.op(Opcode::Dreturn);
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("check_maxs");
+    fs::create_dir_all(&directory).unwrap();
+    for (class, max_stack) in [("Small", 1), ("Enough", 2)] {
+        let code = asm.assemble(max_stack, 2).unwrap();
+        let method = MethodBuilder::new(ACC_PUBLIC | ACC_STATIC, "id", "(D)D").code(code);
+        let bytes = to_bytes(&ClassBuilder::new(class).method(method).build()).unwrap();
+        fs::write(directory.join(format!("{class}.class")), bytes).unwrap();
+    }
+
+    let check = |class: &str| {
+        Command::new(env!("CARGO_BIN_EXE_decompile-rs"))
+            .arg("--check-maxs")
+            .arg(directory.join(format!("{class}.class")))
+            .output()
+            .unwrap()
+    };
+    let small = check("Small");
+    assert!(!small.status.success());
+    assert_eq!(
+        String::from_utf8(small.stdout).unwrap(),
+        "id(D)D: declared stack=1 locals=2, needs stack=2 locals=2\n"
+    );
+    let enough = check("Enough");
+    assert!(enough.status.success());
+    assert!(enough.stdout.is_empty());
+}
@@ -0,0 +1,108 @@
+//! Compiling and running Java for the integration tests. Tests that need a
+//! JDK skip themselves when `javac` isn't on the path.
+
+// each test crate uses only some of these
+#![allow(dead_code)]
+
+use decompile_rs::decompile::Decompile;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Compiles `sources`, given as file names and their contents, into a
+/// directory of their own named `name`, which is returned. `None` if there
+/// is no `javac` to compile them with.
+pub fn javac(name: &str, sources: &[(&str, &str)], options: &[&str]) -> Option<PathBuf> {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let mut files = Vec::new();
+    for (file, source) in sources {
+        let path = directory.join(file);
+        fs::write(&path, source).unwrap();
+        files.push(path);
+    }
+    let output = Command::new("javac")
+        .args(["-nowarn", "-encoding", "UTF-8", "-d"])
+        .arg(&directory)
+        .args(options)
+        .args(&files)
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            eprintln!("skipping {name}: javac not found");
+            return None;
+        }
+        Err(err) => panic!("could not run javac: {err}"),
+    };
+    assert!(
+        output.status.success(),
+        "javac failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(directory)
+}
+
+/// The class files in a directory.
+pub fn class_files(directory: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<_> = fs::read_dir(directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "class")
+        })
+        .collect();
+    files.sort();
+    files
+}
+
+/// Runs the `main` of `class` from `classpath` and returns what it prints,
+/// failing if it doesn't exit normally.
+pub fn java(classpath: &Path, class: &str, options: &[&str]) -> String {
+    let output = Command::new("java")
+        .args(options)
+        .arg("-cp")
+        .arg(classpath)
+        .arg(class)
+        .output()
+        .expect("could not run java");
+    assert!(
+        output.status.success(),
+        "{class} failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Compiles `source`, the file `Main.java`, with `options`, decompiles its
+/// top level classes, compiles what they decompile to and checks that
+/// running `Main` prints the same both times. Returns the decompiled source.
+pub fn check_decompiled(name: &str, source: &str, options: &[&str]) -> Option<String> {
+    let original = javac(name, &[("Main.java", source)], options)?;
+    let expected = java(&original, "Main", &[]);
+    let mut sources = Vec::new();
+    for file in class_files(&original) {
+        let class = file.file_stem().unwrap().to_str().unwrap().to_string();
+        if class.contains('$') {
+            continue;
+        }
+        let decompiled = Decompile::new(file).unwrap().source().unwrap();
+        sources.push((format!("{class}.java"), decompiled));
+    }
+    let sources: Vec<_> = sources
+        .iter()
+        .map(|(file, source)| (file.as_str(), source.as_str()))
+        .collect();
+    let all = sources
+        .iter()
+        .map(|(_, source)| *source)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let recompiled = javac(&format!("{name}_decompiled"), &sources, options)?;
+    let actual = java(&recompiled, "Main", &[]);
+    assert_eq!(actual, expected, "decompiled to\n{all}");
+    Some(all)
+}
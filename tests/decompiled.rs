@@ -0,0 +1,901 @@
+//! Decompiles classes javac compiled, compiles the source they decompile to
+//! and checks that it does the same as the original when run.
+
+mod common;
+
+#[test]
+fn narrow_arguments_keep_their_overload() {
+    let source = "
+public class Main {
+    static String describe(char c) {
+        return \"char \" + c;
+    }
+
+    static String describe(int i) {
+        return \"int \" + i;
+    }
+
+    public static void main(String[] args) {
+        char c = 'A';
+        byte b = (byte) 300;
+        System.out.println((int) c);
+        System.out.println(c);
+        System.out.println((int) b);
+        System.out.println(describe((int) c) + describe(c) + describe('B'));
+        System.out.println(Character.getNumericValue((int) '7'));
+        System.out.println(new StringBuilder().append((int) c).append(c));
+    }
+}
+";
+    common::check_decompiled("narrow_arguments", source, &[]);
+}
+
+#[test]
+fn slot_versions_share_their_type() {
+    let source = "
+public class Main {
+    public static void main(String[] args) {
+        char c = 'a';
+        c++;
+        System.out.println(c);
+        short s = 1;
+        s += 2;
+        s++;
+        System.out.println(s);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("slot_versions", source, &[]) else {
+        return;
+    };
+    assert!(decompiled.contains("char c = 'a';"), "{decompiled}");
+    assert!(decompiled.contains("short s = 1;"), "{decompiled}");
+}
+
+#[test]
+fn conditionals_of_variables_stay_expressions() {
+    let source = "
+public class Main {
+    static int abs(int a) {
+        return a > 0 ? a : -a;
+    }
+
+    static String name(String s) {
+        return s != null ? s : \"none\";
+    }
+
+    static int both(int a, int b) {
+        return a > 0 && b > 0 ? (a > b ? a : b) : -b;
+    }
+
+    static void print(int a) {
+        System.out.println(a > 0 ? a : 0);
+    }
+
+    public static void main(String[] args) {
+        System.out.println(abs(-3) + \" \" + abs(4) + \" \" + name(null) + name(\"x\"));
+        System.out.println(Math.abs(args.length > 3 ? args.length : -7));
+        System.out.println(both(1, 2) + \" \" + both(3, 2) + \" \" + both(-1, 2));
+        print(3);
+        print(-3);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("variable_conditionals", source, &[]) else {
+        return;
+    };
+    assert!(
+        decompiled.contains("return i > 0 ? i : -i;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("return str != null ? str : \"none\";"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("? (i > i2 ? i : i2) : -i2;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("System.out.println(i > 0 ? i : 0);"),
+        "{decompiled}"
+    );
+    assert!(!decompiled.contains("PrintStream"), "{decompiled}");
+}
+
+#[test]
+fn finally_blocks_changing_locals_run_once() {
+    let source = "
+public class Main {
+    static int caught(int x) {
+        try {
+            x = 10 / x;
+        } catch (ArithmeticException e) {
+            x = -1;
+        } finally {
+            x += 100;
+        }
+        return x;
+    }
+
+    static int skipped() {
+        int r = 0;
+        for (int i = 0; i < 10; i++) {
+            try {
+                if (i == 3) {
+                    throw new RuntimeException();
+                }
+                r += 10;
+            } catch (RuntimeException e) {
+                continue;
+            } finally {
+                r++;
+            }
+            r += 1000;
+        }
+        return r;
+    }
+
+    static int rethrown(int x) {
+        int i = 0;
+        try {
+            x = 10 / x;
+        } finally {
+            i++;
+        }
+        return x + i;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(caught(0) + \" \" + caught(5) + \" \" + skipped() + \" \" + rethrown(2));
+        try {
+            rethrown(0);
+        } catch (ArithmeticException e) {
+            System.out.println(\"thrown\");
+        }
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("finally_locals", source, &[]) else {
+        return;
+    };
+    assert_eq!(decompiled.matches("i += 100;").count(), 1, "{decompiled}");
+    assert!(!decompiled.contains("finally {\n        }"), "{decompiled}");
+    assert!(!decompiled.contains("Throwable"), "{decompiled}");
+}
+
+#[test]
+fn old_classes_keep_switch_statements() {
+    let source = "
+public class Main {
+    static int score(String s) {
+        int r;
+        switch (s) {
+            case \"one\":
+                r = 1;
+                break;
+            case \"two\":
+                r = 2;
+                break;
+            default:
+                r = -1;
+        }
+        return r;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(score(\"one\") + score(\"two\") + score(\"three\"));
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("old_switches", source, &["--release", "8"])
+    else {
+        return;
+    };
+    assert!(!decompiled.contains("= switch"), "{decompiled}");
+}
+
+#[test]
+fn concatenated_operands_keep_their_casts() {
+    let source = "
+public class Main {
+    public static void main(String[] args) {
+        char c = 'A';
+        int i = 66;
+        long l = 5;
+        System.out.println(\"x\" + (int) c + c + (char) i + (long) i + (float) l);
+        System.out.println((int) c + \"y\" + (char) (c + 1));
+    }
+}
+";
+    for (name, options) in [
+        ("concatenated_casts", &[][..]),
+        ("concatenated_casts_8", &["--release", "8"][..]),
+    ] {
+        let Some(decompiled) = common::check_decompiled(name, source, options) else {
+            return;
+        };
+        assert!(decompiled.contains("(int) c"), "{decompiled}");
+    }
+}
+
+#[test]
+fn qualified_creations_check_their_instance() {
+    let source = "
+import java.util.Objects;
+
+public class Main {
+    int v = 3;
+
+    class In {
+        int w = v + 1;
+    }
+
+    static class Nested {
+        int v;
+
+        Nested(Main m) {
+            v = m == null ? 0 : m.v;
+        }
+    }
+
+    static int make(Main q) {
+        Main.In in = q.new In();
+        return in.w;
+    }
+
+    static int nested(Main m) {
+        Objects.requireNonNull(m);
+        return new Nested(m).v;
+    }
+
+    static Main get(Main q) {
+        return q;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(make(new Main()) + get(new Main()).new In().w + nested(new Main()));
+        try {
+            make(null);
+        } catch (NullPointerException e) {
+            System.out.println(\"inner\");
+        }
+        try {
+            nested(null);
+        } catch (NullPointerException e) {
+            System.out.println(\"nested\");
+        }
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("qualified_creations", source, &[]) else {
+        return;
+    };
+    assert_eq!(
+        decompiled.matches("requireNonNull").count(),
+        1,
+        "{decompiled}"
+    );
+    assert!(
+        decompiled
+            .contains("Objects.requireNonNull(main);\n        return new Main.Nested(main).v;"),
+        "{decompiled}"
+    );
+    assert!(decompiled.contains("main.new In()"), "{decompiled}");
+    assert!(
+        decompiled.contains("Main.get(new Main()).new In()"),
+        "{decompiled}"
+    );
+
+    // nor imported for
+    let source = "
+public class Main {
+    class In {
+        int w = 5;
+    }
+
+    public static void main(String[] args) {
+        Main m = new Main();
+        System.out.println(m.new In().w);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("unchecked_creations", source, &[]) else {
+        return;
+    };
+    assert!(!decompiled.contains("Objects"), "{decompiled}");
+}
+
+#[test]
+fn long_chains_are_filled() {
+    let source = "
+public class Main {
+    public static void main(String[] args) {
+        int a = args.length;
+        System.out.println(\"alpha \" + a + \" beta \" + (a + 1) + \" gamma \" + (a + 2) + \" delta \" + (a + 3) + \" epsilon \" + (a + 4));
+        System.out.println(a * 1000 + a * 2000 + a * 3000 + a * 4000 + a * 5000 + a * 6000 + a * 7000 + a * 8000 + a);
+        System.out.println(\"alpha beta gamma delta epsilon \" + a + \" zeta eta \" + (a > 0 ? \"theta iota\" : \"kappa lambda\") + \" mu\");
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("filled_chains", source, &[]) else {
+        return;
+    };
+    let line = decompiled
+        .lines()
+        .find(|line| line.contains("println"))
+        .unwrap_or_default();
+    assert!(
+        line.contains("\"alpha \" + i + \" beta \" + (i + 1)"),
+        "{decompiled}"
+    );
+    assert!(
+        !decompiled.contains("+\n                \" gamma \" +\n"),
+        "{decompiled}"
+    );
+    // nor inside a tighter operand
+    for line in decompiled.lines().map(str::trim) {
+        assert!(!line.ends_with(" *"), "{decompiled}");
+        assert!(
+            !line.starts_with('?') && !line.starts_with(':'),
+            "{decompiled}"
+        );
+    }
+}
+
+#[test]
+fn local_class_variables_keep_captured_ones_visible() {
+    let source = "
+public class Main {
+    static int local(int seed) {
+        class Counter {
+            int c = 1;
+
+            int next() {
+                int t = c;
+                c = t + 1;
+                return t * 10 + seed;
+            }
+        }
+        return new Counter().next();
+    }
+
+    static int anonymous(int seed) {
+        return new Object() {
+            public int hashCode() {
+                int t = seed * 2;
+                return t + seed;
+            }
+        }.hashCode();
+    }
+
+    public static void main(String[] args) {
+        System.out.println(local(7) + \" \" + anonymous(5));
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("captured_names", source, &[]) else {
+        return;
+    };
+    assert!(decompiled.contains("return i2 * 10 + i;"), "{decompiled}");
+    assert!(decompiled.contains("int i2 = i * 2;"), "{decompiled}");
+}
+
+#[test]
+fn synchronized_blocks_can_return() {
+    let source = "
+public class Main {
+    static final Object lock = new Object();
+    static int n;
+
+    static int twice(int x) {
+        synchronized (lock) {
+            return x * 2;
+        }
+    }
+
+    static int add(int x) {
+        synchronized (lock) {
+            n += x;
+            return n;
+        }
+    }
+
+    static void skip(int x) {
+        synchronized (lock) {
+            if (x > 0) {
+                return;
+            }
+            n++;
+        }
+    }
+
+    public static void main(String[] args) {
+        System.out.println(twice(3) + \" \" + add(4));
+        skip(1);
+        skip(-1);
+        System.out.println(n);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("synchronized_returns", source, &[]) else {
+        return;
+    };
+    assert_eq!(
+        decompiled.matches("synchronized (Main.lock)").count(),
+        3,
+        "{decompiled}"
+    );
+    assert!(!decompiled.contains("monitor"), "{decompiled}");
+}
+
+#[test]
+fn records_patterns_and_switch_expressions() {
+    let source = "
+public class Main {
+    record Point(int x, int y) {
+        Point {
+            if (x < 0) {
+                throw new IllegalArgumentException();
+            }
+        }
+
+        int sum() {
+            return x + y;
+        }
+    }
+
+    record Named(String name, double weight) {}
+
+    record Empty() {}
+
+    static String describe(Object o) {
+        if (o instanceof Point p && p.x() > 1) {
+            return \"point \" + p.sum();
+        }
+        if (o instanceof String s && !s.isEmpty()) {
+            return \"string \" + s.length();
+        }
+        int n = o instanceof Integer i ? i : -1;
+        return switch (n) {
+            case 1, 2 -> \"small\";
+            case 5 -> {
+                String t = \"five\";
+                yield t + n;
+            }
+            default -> \"other\";
+        };
+    }
+
+    public static void main(String[] args) {
+        Point p = new Point(1, 2);
+        System.out.println(p + \" \" + p.equals(new Point(1, 2)) + \" \" + p.hashCode());
+        System.out.println(new Named(\"a\", 1.5) + \" \" + new Empty() + new Named(\"b\", 2).name());
+        System.out.println(describe(new Point(2, 3)) + describe(\"xy\") + describe(5) + describe(1));
+        System.out.println(describe(1.0) + describe(new Point(0, 1)));
+        try {
+            new Point(-1, 0);
+        } catch (IllegalArgumentException e) {
+            System.out.println(\"negative\");
+        }
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("records", source, &[]) else {
+        return;
+    };
+    assert!(
+        decompiled.contains("record Point(int x, int y) {"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("record Named(String name, double weight) {"),
+        "{decompiled}"
+    );
+    assert!(!decompiled.contains("invokedynamic"), "{decompiled}");
+    assert!(!decompiled.contains("extends Record"), "{decompiled}");
+    assert!(
+        decompiled.contains("instanceof Main.Point point && point.x() > 1"),
+        "{decompiled}"
+    );
+    assert!(decompiled.contains("return switch (i) {"), "{decompiled}");
+    assert!(decompiled.contains("yield str2 + i;"), "{decompiled}");
+}
+
+#[test]
+fn switches_on_strings_and_enums_use_their_names() {
+    let source = "
+public class Main {
+    enum Color { RED, GREEN, BLUE }
+
+    static int score(String s) {
+        switch (s) {
+            case \"one\":
+                return 1;
+            case \"two\":
+            case \"three\":
+                return 23;
+            default:
+                return -1;
+        }
+    }
+
+    static String paint(Color c) {
+        switch (c) {
+            case RED:
+                return \"r\";
+            case GREEN:
+                return \"g\";
+            default:
+                return \"?\";
+        }
+    }
+
+    static int fall(int n) {
+        int r = 0;
+        switch (n) {
+            case 1:
+                r += 1;
+            case 2:
+                r += 2;
+                break;
+            case 10:
+                r = 10;
+                break;
+            default:
+                r = -1;
+        }
+        return r;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(score(\"one\") + score(\"two\") + score(\"three\") + score(\"x\"));
+        for (Color c : Color.values()) {
+            System.out.println(paint(c));
+        }
+        System.out.println(fall(1) + \" \" + fall(2) + \" \" + fall(10) + \" \" + fall(3));
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("named_switches", source, &[]) else {
+        return;
+    };
+    assert!(
+        decompiled.contains("switch (str) {\n            case \"one\":\n                return 1;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("case \"two\":\n            case \"three\":"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("switch (color) {\n            case RED:"),
+        "{decompiled}"
+    );
+    assert!(decompiled.contains("case GREEN:"), "{decompiled}");
+    assert!(
+        decompiled.contains("case 1:\n                i2++;\n            case 2:"),
+        "{decompiled}"
+    );
+    for synthetic in ["hashCode", "equals", "$SwitchMap$", "ordinal"] {
+        assert!(!decompiled.contains(synthetic), "{decompiled}");
+    }
+}
+
+#[test]
+fn lambdas_and_method_references_are_inlined() {
+    let source = "
+import java.io.Serializable;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Function;
+import java.util.function.IntUnaryOperator;
+import java.util.function.Supplier;
+
+public class Main {
+    int base = 2;
+
+    static int twice(IntUnaryOperator f, int x) {
+        return f.applyAsInt(f.applyAsInt(x));
+    }
+
+    int scaled(List<Integer> xs) {
+        return xs.stream().mapToInt(x -> x * base).sum();
+    }
+
+    public static void main(String[] args) {
+        int k = args.length + 3;
+        IntUnaryOperator add = x -> x + k;
+        System.out.println(twice(add, 1));
+        Function<String, Integer> length = String::length;
+        System.out.println(length.apply(\"abcd\"));
+        Supplier<List<String>> make = ArrayList::new;
+        List<String> list = make.get();
+        list.add(\"a\");
+        list.forEach(System.out::println);
+        System.out.println(new Main().scaled(List.of(1, 2, 3)));
+        Runnable run = (Runnable & Serializable) () -> System.out.println(\"run \" + k);
+        run.run();
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("lambdas", source, &[]) else {
+        return;
+    };
+    assert!(
+        decompiled.contains("IntUnaryOperator intUnaryOperator = i2 -> i2 + i;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("return integer * this.base;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("Supplier supplier = ArrayList::new;"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("list.forEach(System.out::println);"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("(Runnable & Serializable) () -> System.out.println(\"run \" + i);"),
+        "{decompiled}"
+    );
+    for synthetic in ["lambda$", "$deserializeLambda$", "LambdaMetafactory"] {
+        assert!(!decompiled.contains(synthetic), "{decompiled}");
+    }
+}
+
+#[test]
+fn accessors_and_bridges_are_hidden() {
+    let source = "
+public class Main implements Comparable<Main> {
+    private int secret = 4;
+
+    private static String hidden() {
+        return \"h\";
+    }
+
+    class In {
+        int peek() {
+            return secret + hidden().length();
+        }
+
+        void poke() {
+            secret++;
+        }
+    }
+
+    public int compareTo(Main other) {
+        return secret - other.secret;
+    }
+
+    static class Base {
+        Object get() {
+            return \"base\";
+        }
+    }
+
+    static class Derived extends Base {
+        String get() {
+            return \"derived\";
+        }
+    }
+
+    public static void main(String[] args) {
+        Main m = new Main();
+        In in = m.new In();
+        in.poke();
+        System.out.println(in.peek() + \" \" + m.compareTo(new Main()) + new Derived().get());
+        Base base = new Derived();
+        System.out.println(base.get());
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("accessors", source, &["--release", "8"])
+    else {
+        return;
+    };
+    assert!(
+        decompiled.contains("return Main.this.secret + Main.hidden().length();"),
+        "{decompiled}"
+    );
+    assert!(decompiled.contains("Main.this.secret++;"), "{decompiled}");
+    assert!(!decompiled.contains("access$"), "{decompiled}");
+    // a bridge is only written where the raw Comparable needs it
+    assert!(
+        decompiled.contains("public int compareTo(Main main) {"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains(
+            "public int compareTo(Object obj) {\n        return this.compareTo((Main) obj);"
+        ),
+        "{decompiled}"
+    );
+    assert_eq!(decompiled.matches(" get() {").count(), 2, "{decompiled}");
+    assert!(decompiled.contains("String get() {"), "{decompiled}");
+}
+
+#[test]
+fn enum_constants_keep_their_bodies() {
+    let source = "
+public class Main {
+    enum Color { RED, GREEN }
+
+    enum Op {
+        PLUS(\"+\") {
+            int apply(int a, int b) {
+                return a + b;
+            }
+        },
+        TIMES(\"*\") {
+            int apply(int a, int b) {
+                return a * b;
+            }
+        };
+
+        final String symbol;
+
+        Op(String symbol) {
+            this.symbol = symbol;
+        }
+
+        abstract int apply(int a, int b);
+    }
+
+    public static void main(String[] args) {
+        for (Op op : Op.values()) {
+            System.out.println(op + op.symbol + op.apply(3, 4) + op.ordinal());
+        }
+        System.out.println(Color.valueOf(\"GREEN\").ordinal() + \" \" + Color.values().length);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("enum_bodies", source, &[]) else {
+        return;
+    };
+    assert!(
+        decompiled.contains(
+            "PLUS(\"+\") {\n            int apply(int i, int i2) {\n                return i + i2;"
+        ),
+        "{decompiled}"
+    );
+    assert!(decompiled.contains("TIMES(\"*\") {"), "{decompiled}");
+    assert!(
+        decompiled.contains("abstract int apply(int arg0, int arg1);"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("enum Color {\n        RED,\n        GREEN;\n    }"),
+        "{decompiled}"
+    );
+    // the constructor only declares the parameter the source gave it
+    let constructor = decompiled
+        .lines()
+        .find(|line| line.contains("Op(String"))
+        .unwrap_or_default();
+    assert!(constructor.ends_with("Op(String str) {"), "{decompiled}");
+    for synthetic in [
+        "$VALUES",
+        "$values",
+        "values() {",
+        "valueOf(String",
+        "super(",
+    ] {
+        assert!(!decompiled.contains(synthetic), "{decompiled}");
+    }
+}
+
+#[test]
+fn initializers_move_out_of_constructors_calling_this() {
+    let source = "
+import java.util.ArrayList;
+import java.util.List;
+
+public class Main {
+    static int counter = 5;
+    static final int LIMIT = 7;
+    int a = 1;
+    String s = \"x\";
+    final List<String> list = new ArrayList<>();
+    int b;
+
+    Main() {
+        this(3);
+    }
+
+    Main(int b) {
+        this.b = b;
+    }
+
+    Main(String s) {
+        this.s = s;
+    }
+
+    public static void main(String[] args) {
+        Main m = new Main();
+        Main n = new Main(\"y\");
+        m.list.add(\"z\");
+        System.out.println(m.a + m.s + m.b + m.list + n.s + n.b + counter + LIMIT);
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("this_initializers", source, &[]) else {
+        return;
+    };
+    for field in [
+        "static int counter = 5;",
+        "static final int LIMIT = 7;",
+        "int a = 1;",
+        "String s = \"x\";",
+        "final List list = new ArrayList();",
+        "int b;",
+    ] {
+        assert!(decompiled.contains(field), "{decompiled}");
+    }
+    assert!(
+        decompiled.contains("Main() {\n        this(3);\n    }"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("Main(int i) {\n        this.b = i;\n    }"),
+        "{decompiled}"
+    );
+    assert!(
+        decompiled.contains("Main(String str) {\n        this.s = str;\n    }"),
+        "{decompiled}"
+    );
+    for leftover in ["this.a", "this.list", "super()", "static {"] {
+        assert!(!decompiled.contains(leftover), "{decompiled}");
+    }
+}
+
+#[test]
+fn asserts_array_initializers_and_compound_assignments_are_sugared() {
+    let source = "
+public class Main {
+    static int check(int x) {
+        assert x > 0 : \"positive\";
+        int[] a = {1, 2, x};
+        int s = 0;
+        for (int i = 0; i < a.length; i++) {
+            s += a[i];
+        }
+        s *= 2;
+        s++;
+        a[0] += 5;
+        Integer boxed = s;
+        return boxed + a[0];
+    }
+
+    public static void main(String[] args) {
+        System.out.println(check(3));
+    }
+}
+";
+    let Some(decompiled) = common::check_decompiled("sugar", source, &[]) else {
+        return;
+    };
+    for line in [
+        "assert i > 0 : \"positive\";",
+        "int[] ints = new int[]{1, 2, i};",
+        "i2 += ints[i3];",
+        "i2 *= 2;",
+        "i2++;",
+        "ints[0] += 5;",
+        "Integer integer = i2;",
+        "return integer + ints[0];",
+    ] {
+        assert!(decompiled.contains(line), "{decompiled}");
+    }
+    for desugared in [
+        "$assertionsDisabled",
+        "AssertionError",
+        "valueOf",
+        "intValue",
+    ] {
+        assert!(!decompiled.contains(desugared), "{decompiled}");
+    }
+}
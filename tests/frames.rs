@@ -0,0 +1,116 @@
+mod common;
+
+use decompile_rs::decompile::read_class_file;
+use decompile_rs::frames::{recompute_frames, SuperclassMap};
+use decompile_rs::writer::to_bytes;
+use std::fs;
+use std::io::Cursor;
+
+const SOURCE: &str = "
+import java.util.ArrayList;
+import java.util.List;
+
+public class Main {
+    static abstract class Shape {
+        abstract double area();
+    }
+
+    static class Square extends Shape {
+        final double side;
+
+        Square(double side) {
+            this.side = side;
+        }
+
+        double area() {
+            return side * side;
+        }
+    }
+
+    static class Circle extends Shape {
+        final double radius;
+
+        Circle(double radius) {
+            this.radius = radius;
+        }
+
+        double area() {
+            return 3 * radius * radius;
+        }
+    }
+
+    static class Named extends Square {
+        final String name;
+
+        Named(String name, long size) {
+            super(size > 10 ? 10 : size);
+            this.name = name == null ? \"none\" : name;
+        }
+    }
+
+    static double total(int n) {
+        double total = 0;
+        for (int i = 0; i < n; i++) {
+            Shape shape = i % 2 == 0 ? new Square(i) : new Circle(i);
+            total += shape.area();
+        }
+        return total;
+    }
+
+    static String parse(String s) {
+        long value = 0;
+        try {
+            value = Long.parseLong(s);
+        } catch (NumberFormatException e) {
+            return \"bad \" + s;
+        } finally {
+            value++;
+        }
+        switch ((int) value % 3) {
+            case 0:
+                return \"zero\";
+            case 1:
+                return \"one \" + value;
+            default:
+                return \"many\";
+        }
+    }
+
+    public static void main(String[] args) {
+        List<Object> out = new ArrayList<>();
+        out.add(total(5));
+        out.add(parse(\"41\") + parse(\"x\") + parse(\"3\"));
+        Named named = new Named(null, 12);
+        out.add(named.name + named.area() + new Named(\"n\", 3).area());
+        int[][] grid = new int[3][4];
+        for (int[] row : grid) {
+            row[1] = row.length;
+        }
+        out.add(grid[2][1]);
+        System.out.println(out);
+    }
+}
+";
+
+// javac's classes still verify with every frame recomputed.
+#[test]
+fn recomputed_frames_verify() {
+    let Some(directory) = common::javac("frames", &[("Main.java", SOURCE)], &[]) else {
+        return;
+    };
+    let expected = common::java(&directory, "Main", &[]);
+    let files = common::class_files(&directory);
+    let mut classes = Vec::new();
+    let mut hierarchy = SuperclassMap::new();
+    for file in &files {
+        let class_file = read_class_file(&mut Cursor::new(fs::read(file).unwrap())).unwrap();
+        hierarchy.add_class_file(&class_file);
+        classes.push(class_file);
+    }
+    for (file, mut class_file) in files.iter().zip(classes) {
+        recompute_frames(&mut class_file, &hierarchy).unwrap();
+        fs::write(file, to_bytes(&class_file).unwrap()).unwrap();
+    }
+    let output = common::java(&directory, "Main", &["-Xverify:all"]);
+    assert_eq!(output, expected);
+}
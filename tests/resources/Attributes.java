@@ -0,0 +1,118 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Function;
+
+// Uses as many kinds of attribute and constant as javac writes.
+@Attributes.Visible(name = "class", values = {1, 2}, kind = ElementType.TYPE, type = String.class)
+public class Attributes<T extends Comparable<T>> {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Visible {
+        String name() default "none";
+        int[] values() default {};
+        ElementType kind() default ElementType.FIELD;
+        Class<?> type() default Object.class;
+        Invisible nested() default @Invisible;
+    }
+
+    @interface Invisible {
+    }
+
+    @Target(ElementType.TYPE_USE)
+    @interface Use {
+    }
+
+    enum Kind {
+        ONE, TWO
+    }
+
+    record Point(int x, @Use int y) {
+    }
+
+    sealed interface Shape permits Circle, Square {
+    }
+
+    static final class Circle implements Shape {
+    }
+
+    static final class Square implements Shape {
+    }
+
+    static final long LONG = 1L << 40;
+    static final double DOUBLE = -0.0;
+    static final float FLOAT = Float.NaN;
+    static final String STRING = "\0\ud800😀 text";
+
+    @Deprecated
+    @Invisible
+    private List<@Use String> names = new ArrayList<>();
+
+    T value;
+
+    Attributes(@Visible T value, @Invisible int unused) throws Exception {
+        this.value = value;
+    }
+
+    @SafeVarargs
+    final <R> List<R> map(Function<? super T, ? extends R> function, T... values) {
+        List<R> list = new ArrayList<>();
+        for (T value : values) {
+            list.add(function.apply(value));
+        }
+        return list;
+    }
+
+    int switches(String text, Kind kind, Object object) {
+        int result = switch (text) {
+            case "a", "b" -> 1;
+            default -> text.length();
+        };
+        switch (kind) {
+            case ONE:
+                result++;
+                break;
+            case TWO:
+                result--;
+                break;
+        }
+        if (object instanceof Point point && point.x() > 0) {
+            result += point.y();
+        }
+        return result;
+    }
+
+    String lambdas(List<String> list) {
+        Runnable runnable = () -> names.add("run");
+        runnable.run();
+        Function<String, Integer> length = String::length;
+        Object anonymous = new Object() {
+            @Override
+            public String toString() {
+                return "anonymous " + value;
+            }
+        };
+        return "" + length.apply(list.get(0)) + anonymous + LONG + DOUBLE;
+    }
+
+    synchronized int exceptions(int[] array) {
+        try {
+            return array[0] / array[1];
+        } catch (ArithmeticException | ArrayIndexOutOfBoundsException e) {
+            return -1;
+        } finally {
+            names.clear();
+        }
+    }
+
+    int big(int i) {
+        switch (i) {
+            case 1: return 10;
+            case 1000: return 20;
+            case 100000: return 30;
+            default: return 0;
+        }
+    }
+}
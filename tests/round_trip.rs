@@ -0,0 +1,87 @@
+mod common;
+
+use decompile_rs::builder::{ClassBuilder, FieldBuilder};
+use decompile_rs::decompile::read_class_file;
+use decompile_rs::error::DecompileError;
+use decompile_rs::types::{ConstantPoolType, ACC_FINAL, ACC_STATIC};
+use decompile_rs::writer::to_bytes;
+use std::fs;
+use std::io::Cursor;
+
+// Reading a class file and writing it back out gives the same bytes.
+#[test]
+fn round_trips_class_files() {
+    let sources = [
+        ("Sample.java", include_str!("resources/Sample.java")),
+        ("Attributes.java", include_str!("resources/Attributes.java")),
+    ];
+    for (name, options) in [
+        ("round_trip", &[][..]),
+        ("round_trip_debug", &["-g", "-parameters"]),
+    ] {
+        let Some(directory) = common::javac(name, &sources, options) else {
+            return;
+        };
+        let files = common::class_files(&directory);
+        assert!(files.len() > 10);
+        for file in files {
+            let bytes = fs::read(&file).unwrap();
+            let class_file = read_class_file(&mut Cursor::new(&bytes)).unwrap();
+            assert!(
+                to_bytes(&class_file).unwrap() == bytes,
+                "{}",
+                file.display()
+            );
+        }
+    }
+}
+
+// A truncated class file is an error rather than a panic.
+#[test]
+fn truncated_class_files_are_errors() {
+    let sources = [("Attributes.java", include_str!("resources/Attributes.java"))];
+    let Some(directory) = common::javac("truncated", &sources, &["-g"]) else {
+        return;
+    };
+    let bytes = fs::read(directory.join("Attributes.class")).unwrap();
+    for length in 0..bytes.len() {
+        let result = read_class_file(&mut Cursor::new(&bytes[..length]));
+        assert!(result.is_err(), "{length} bytes");
+    }
+}
+
+// A field may have one `ConstantValue`, of an int, float, long, double or
+// string constant.
+#[test]
+fn invalid_constant_values_are_errors() {
+    let mut builder = ClassBuilder::new("Main");
+    let name_idx = builder.pool().utf8("Main");
+    let class_file = builder
+        .field(
+            FieldBuilder::new(ACC_STATIC, "main", "LMain;")
+                .constant_value(ConstantPoolType::ConstantClass { name_idx }),
+        )
+        .build();
+    let bytes = to_bytes(&class_file).unwrap();
+    let err = read_class_file(&mut Cursor::new(&bytes)).unwrap_err();
+    assert!(
+        matches!(err, DecompileError::InvalidConstantValue(..)),
+        "{err}"
+    );
+
+    let mut class_file = ClassBuilder::new("Main")
+        .field(
+            FieldBuilder::new(ACC_STATIC | ACC_FINAL, "count", "I")
+                .constant_value(ConstantPoolType::ConstantInteger { value: 1 }),
+        )
+        .build();
+    let field = &mut class_file.fields[0];
+    field.attributes.push(field.attributes[0].clone());
+    field.attributes_count = 2;
+    let bytes = to_bytes(&class_file).unwrap();
+    let err = read_class_file(&mut Cursor::new(&bytes)).unwrap_err();
+    assert!(
+        matches!(err, DecompileError::DuplicateConstantValue(_)),
+        "{err}"
+    );
+}